@@ -104,7 +104,7 @@ impl ConfigManager {
         bucket: Option<&str>,
         credentials_path: Option<&str>,
     ) -> Result<()> {
-        self.add_source_full(name, source_type_str, uri, project, bucket, None, None, None, credentials_path)
+        self.add_source_full(name, source_type_str, uri, project, bucket, None, None, None, None, None, None, credentials_path)
     }
 
     /// Add a new data source (full version with all options)
@@ -118,6 +118,9 @@ impl ConfigManager {
         region: Option<&str>,
         host: Option<&str>,
         port: Option<u16>,
+        database: Option<&str>,
+        workgroup: Option<&str>,
+        output_location: Option<&str>,
         credentials_path: Option<&str>,
     ) -> Result<()> {
         let source_type = match source_type_str.to_lowercase().as_str() {
@@ -129,6 +132,12 @@ impl ConfigManager {
             "bigquery" => SourceType::BigQuery,
             "snowflake" => SourceType::Snowflake,
             "databricks" => SourceType::Databricks,
+            "mongodb" | "mongo" => SourceType::MongoDB,
+            "redis" => SourceType::Redis,
+            "elasticsearch" | "es" | "opensearch" => SourceType::Elasticsearch,
+            "athena" => SourceType::Athena,
+            "redshift" => SourceType::Redshift,
+            "aresadb" => SourceType::AresaDB,
             "s3" => SourceType::S3,
             "gcs" => SourceType::GCS,
             _ => return Err(anyhow::anyhow!("Unknown source type: {}", source_type_str)),
@@ -139,7 +148,7 @@ impl ConfigManager {
             uri: uri.map(String::from),
             host: host.map(String::from),
             port,
-            database: None,
+            database: database.map(String::from),
             schema: None,
             username: None,
             password: None,
@@ -151,6 +160,8 @@ impl ConfigManager {
             warehouse: None,
             catalog: None,
             token: None,
+            workgroup: workgroup.map(String::from),
+            output_location: output_location.map(String::from),
         };
 
         // Store sensitive URI in keychain if provided
@@ -215,7 +226,13 @@ impl ConfigManager {
                         .map(|p| format!("project: {}", p))
                         .unwrap_or_else(|| "(no project)".to_string())
                 }
-                SourceType::Postgres | SourceType::MySQL => {
+                SourceType::Postgres
+                | SourceType::MySQL
+                | SourceType::MongoDB
+                | SourceType::Redis
+                | SourceType::Elasticsearch
+                | SourceType::Redshift
+                | SourceType::AresaDB => {
                     if source.uri.is_some() || self.credentials.exists(name) {
                         if let Some(uri) = &source.uri {
                             // Parse URI to show host without password
@@ -256,6 +273,14 @@ impl ConfigManager {
                     let host = source.host.as_deref().unwrap_or("(no host)");
                     format!("host: {}", host)
                 }
+                SourceType::Athena => {
+                    let workgroup = source.workgroup.as_deref().unwrap_or("primary");
+                    let region = source.region.as_deref().unwrap_or("us-east-1");
+                    match &source.database {
+                        Some(database) => format!("workgroup: {}, db: {} ({})", workgroup, database, region),
+                        None => format!("workgroup: {} ({})", workgroup, region),
+                    }
+                }
             };
 
             // Build example command
@@ -268,6 +293,12 @@ impl ConfigManager {
                 SourceType::ClickHouse => format!("aresa ch {} \"SELECT ...\"", name),
                 SourceType::Snowflake => format!("aresa query {} \"SELECT ...\"", name),
                 SourceType::Databricks => format!("aresa query {} \"SELECT ...\"", name),
+                SourceType::MongoDB => format!("aresa mongo {} --collections", name),
+                SourceType::Redis => format!("aresa redis {} --scan '*'", name),
+                SourceType::Elasticsearch => format!("aresa es {} --indices", name),
+                SourceType::Athena => format!("aresa athena {} \"SELECT ...\"", name),
+                SourceType::Redshift => format!("aresa redshift {} \"SELECT ...\"", name),
+                SourceType::AresaDB => format!("aresa aresadb {} \"SELECT ...\"", name),
                 SourceType::S3 => format!("aresa s3 {} --list", name),
                 SourceType::GCS => format!("aresa gcs {} --list", name),
             };
@@ -331,6 +362,26 @@ impl ConfigManager {
                     .context("Failed to connect to MySQL")?;
                 sqlx::query("SELECT 1").execute(&pool).await?;
             }
+            SourceType::MongoDB => {
+                let uri = self.get_uri(name)?;
+                crate::connectors::mongodb::MongoConnector::new(&uri, source.database.as_deref()).await?;
+            }
+            SourceType::Redis => {
+                let uri = self.get_uri(name)?;
+                crate::connectors::redis::RedisConnector::new(&uri).await?;
+            }
+            SourceType::Elasticsearch => {
+                let uri = self.get_uri(name)?;
+                crate::connectors::elasticsearch::ElasticsearchConnector::new(&uri).await?;
+            }
+            SourceType::Redshift => {
+                let uri = self.get_uri(name)?;
+                crate::connectors::redshift::RedshiftConnector::new(&uri).await?;
+            }
+            SourceType::AresaDB => {
+                let uri = self.get_uri(name)?;
+                crate::connectors::aresadb::AresaDbConnector::new(&uri).await?;
+            }
             SourceType::SQLite | SourceType::DuckDB => {
                 let uri = self.get_uri(name)?;
                 let pool = sqlx::SqlitePool::connect(&uri).await
@@ -393,6 +444,16 @@ impl ConfigManager {
                 ).await.context("Failed to connect to Databricks")?;
                 connector.test_connection().await?;
             }
+            SourceType::Athena => {
+                let credentials = crate::connectors::athena::AwsCredentials::from_env()?;
+                let connector = crate::connectors::athena::AthenaConnector::new(
+                    credentials,
+                    source.region.as_deref(),
+                    source.workgroup.as_deref(),
+                    source.output_location.as_deref(),
+                );
+                connector.test_connection().await?;
+            }
         }
 
         Ok(())
@@ -14,6 +14,12 @@ pub enum SourceType {
     BigQuery,
     Snowflake,
     Databricks,
+    MongoDB,
+    Redis,
+    Elasticsearch,
+    Athena,
+    Redshift,
+    AresaDB,
     S3,
     GCS,
 }
@@ -68,6 +74,12 @@ pub struct DataSource {
     /// Access token (for Databricks)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>,
+    /// Workgroup (for Athena)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workgroup: Option<String>,
+    /// S3 URI for query results (for Athena)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_location: Option<String>,
 }
 
 impl std::fmt::Display for SourceType {
@@ -81,6 +93,12 @@ impl std::fmt::Display for SourceType {
             SourceType::BigQuery => write!(f, "bigquery"),
             SourceType::Snowflake => write!(f, "snowflake"),
             SourceType::Databricks => write!(f, "databricks"),
+            SourceType::MongoDB => write!(f, "mongodb"),
+            SourceType::Redis => write!(f, "redis"),
+            SourceType::Elasticsearch => write!(f, "elasticsearch"),
+            SourceType::Athena => write!(f, "athena"),
+            SourceType::Redshift => write!(f, "redshift"),
+            SourceType::AresaDB => write!(f, "aresadb"),
             SourceType::S3 => write!(f, "s3"),
             SourceType::GCS => write!(f, "gcs"),
         }
@@ -99,6 +117,12 @@ impl SourceType {
             SourceType::BigQuery => "Google BigQuery",
             SourceType::Snowflake => "Snowflake Data Warehouse",
             SourceType::Databricks => "Databricks SQL Warehouse",
+            SourceType::MongoDB => "MongoDB database",
+            SourceType::Redis => "Redis key-value store",
+            SourceType::Elasticsearch => "Elasticsearch / OpenSearch cluster",
+            SourceType::Athena => "Amazon Athena",
+            SourceType::Redshift => "Amazon Redshift data warehouse",
+            SourceType::AresaDB => "AresaDB database",
             SourceType::S3 => "AWS S3 bucket",
             SourceType::GCS => "Google Cloud Storage bucket",
         }
@@ -116,6 +140,9 @@ impl SourceType {
                 | SourceType::BigQuery
                 | SourceType::Snowflake
                 | SourceType::Databricks
+                | SourceType::Athena
+                | SourceType::Redshift
+                | SourceType::AresaDB
         )
     }
 }
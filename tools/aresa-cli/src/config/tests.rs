@@ -14,6 +14,12 @@ mod tests {
         assert_eq!(SourceType::BigQuery.description(), "Google BigQuery");
         assert_eq!(SourceType::Snowflake.description(), "Snowflake Data Warehouse");
         assert_eq!(SourceType::Databricks.description(), "Databricks SQL Warehouse");
+        assert_eq!(SourceType::MongoDB.description(), "MongoDB database");
+        assert_eq!(SourceType::Redis.description(), "Redis key-value store");
+        assert_eq!(SourceType::Elasticsearch.description(), "Elasticsearch / OpenSearch cluster");
+        assert_eq!(SourceType::Athena.description(), "Amazon Athena");
+        assert_eq!(SourceType::Redshift.description(), "Amazon Redshift data warehouse");
+        assert_eq!(SourceType::AresaDB.description(), "AresaDB database");
         assert_eq!(SourceType::S3.description(), "AWS S3 bucket");
         assert_eq!(SourceType::GCS.description(), "Google Cloud Storage bucket");
     }
@@ -28,6 +34,12 @@ mod tests {
         assert!(SourceType::BigQuery.supports_sql());
         assert!(SourceType::Snowflake.supports_sql());
         assert!(SourceType::Databricks.supports_sql());
+        assert!(SourceType::Athena.supports_sql());
+        assert!(SourceType::Redshift.supports_sql());
+        assert!(SourceType::AresaDB.supports_sql());
+        assert!(!SourceType::MongoDB.supports_sql());
+        assert!(!SourceType::Redis.supports_sql());
+        assert!(!SourceType::Elasticsearch.supports_sql());
         assert!(!SourceType::S3.supports_sql());
         assert!(!SourceType::GCS.supports_sql());
     }
@@ -51,6 +63,8 @@ mod tests {
             warehouse: None,
             catalog: None,
             token: None,
+            workgroup: None,
+            output_location: None,
         };
 
         let serialized = toml::to_string(&source).unwrap();
@@ -96,6 +110,8 @@ mod tests {
             warehouse: Some("COMPUTE_WH".to_string()),
             catalog: None,
             token: None,
+            workgroup: None,
+            output_location: None,
         };
 
         let serialized = toml::to_string(&source).unwrap();
@@ -123,6 +139,8 @@ mod tests {
             warehouse: Some("abc123def456".to_string()),
             catalog: Some("main".to_string()),
             token: None,
+            workgroup: None,
+            output_location: None,
         };
 
         let serialized = toml::to_string(&source).unwrap();
@@ -131,6 +149,38 @@ mod tests {
         assert!(serialized.contains("catalog = \"main\""));
     }
 
+    #[test]
+    fn test_athena_source_serialization() {
+        let source = DataSource {
+            source_type: SourceType::Athena,
+            uri: None,
+            host: None,
+            port: None,
+            database: Some("weblogs".to_string()),
+            schema: None,
+            username: None,
+            password: None,
+            project: None,
+            bucket: None,
+            region: Some("eu-west-1".to_string()),
+            credentials_path: None,
+            account: None,
+            warehouse: None,
+            catalog: None,
+            token: None,
+            workgroup: Some("analysts".to_string()),
+            output_location: Some("s3://query-results/athena/".to_string()),
+        };
+
+        let serialized = toml::to_string(&source).unwrap();
+        assert!(serialized.contains("source_type = \"athena\""));
+        assert!(serialized.contains("workgroup = \"analysts\""));
+        assert!(serialized.contains("output_location = \"s3://query-results/athena/\""));
+
+        let deserialized: DataSource = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.database.as_deref(), Some("weblogs"));
+    }
+
     #[test]
     fn test_all_source_types_serializable() {
         let types = vec![
@@ -142,6 +192,12 @@ mod tests {
             SourceType::BigQuery,
             SourceType::Snowflake,
             SourceType::Databricks,
+            SourceType::MongoDB,
+            SourceType::Redis,
+            SourceType::Elasticsearch,
+            SourceType::Athena,
+            SourceType::Redshift,
+            SourceType::AresaDB,
             SourceType::S3,
             SourceType::GCS,
         ];
@@ -164,6 +220,8 @@ mod tests {
                 warehouse: None,
                 catalog: None,
                 token: None,
+                workgroup: None,
+                output_location: None,
             };
 
             let serialized = toml::to_string(&source).unwrap();
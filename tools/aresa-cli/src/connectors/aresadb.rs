@@ -0,0 +1,237 @@
+//! AresaDB connector
+//!
+//! Opens a local aresadb directory (or an `s3://` / `gs://` bucket, read-only)
+//! in-process, or talks to a running `aresadb-server` through the client SDK
+//! when given an `aresadb://[user:pass@]host[:port][/database]` URI. Tables
+//! are aresadb schemas; SQL runs on aresadb's own query engine either way.
+
+use anyhow::{bail, Context, Result};
+use aresadb::{Client, ClientBuilder, Database, QueryEngine, QueryResult, Schema, SchemaManager, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Port `aresadb-server` listens on by default
+pub const DEFAULT_PORT: u16 = 7432;
+
+/// Default connection timeout in seconds
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+type Rows = (Vec<String>, Vec<HashMap<String, String>>);
+
+enum Backend {
+    Local {
+        db: Arc<Database>,
+        engine: QueryEngine,
+        schemas: SchemaManager,
+    },
+    Remote(Client),
+}
+
+/// AresaDB connector for local databases and remote servers
+pub struct AresaDbConnector {
+    backend: Backend,
+}
+
+impl AresaDbConnector {
+    /// Connect to an `aresadb://` server URI, or open a local database
+    /// directory or bucket URL
+    pub async fn new(uri: &str) -> Result<Self> {
+        let backend = if uri.starts_with("aresadb://") {
+            Backend::Remote(connect(uri).await?)
+        } else {
+            let db = Arc::new(
+                Database::open(uri)
+                    .await
+                    .with_context(|| format!("Failed to open aresadb database at {}", uri))?,
+            );
+            Backend::Local {
+                engine: QueryEngine::from_shared(db.clone()),
+                schemas: SchemaManager::from_shared(db.clone()),
+                db,
+            }
+        };
+
+        let connector = Self { backend };
+        connector.test_connection().await?;
+
+        Ok(connector)
+    }
+
+    /// Whether queries go to a remote server
+    pub fn is_remote(&self) -> bool {
+        matches!(self.backend, Backend::Remote(_))
+    }
+
+    /// Test connection
+    pub async fn test_connection(&self) -> Result<()> {
+        match &self.backend {
+            Backend::Local { db, .. } => {
+                db.status().await.context("Failed to read aresadb database")?;
+            }
+            Backend::Remote(client) => {
+                client.ping().await.context("Failed to connect to aresadb server")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute a SQL query
+    pub async fn execute_sql(&self, query: &str, limit: Option<usize>) -> Result<Rows> {
+        let result = match &self.backend {
+            Backend::Local { engine, .. } => engine.execute_sql(query, limit).await?,
+            Backend::Remote(client) => client.query(query, limit).await?,
+        };
+        Ok(result_to_rows(&result))
+    }
+
+    /// Schemas (tables) with their field counts
+    pub async fn list_tables(&self) -> Result<Rows> {
+        let mut schemas = self.schemas().await?;
+        schemas.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let rows = schemas
+            .iter()
+            .map(|schema| {
+                let mut row = HashMap::new();
+                row.insert("table".to_string(), schema.name.clone());
+                row.insert("fields".to_string(), schema.fields.len().to_string());
+                row
+            })
+            .collect();
+        Ok((vec!["table".to_string(), "fields".to_string()], rows))
+    }
+
+    /// Fields of a schema with their types and constraints
+    pub async fn describe_table(&self, table: &str) -> Result<Rows> {
+        let schemas = self.schemas().await?;
+        let Some(schema) = schemas.iter().find(|schema| schema.name == table) else {
+            bail!("Table '{}' not found", table);
+        };
+        Ok(schema_to_rows(schema))
+    }
+
+    async fn schemas(&self) -> Result<Vec<Schema>> {
+        match &self.backend {
+            Backend::Local { schemas, .. } => schemas.list_schemas().await,
+            Backend::Remote(client) => client.list_schemas().await,
+        }
+    }
+}
+
+/// Where an `aresadb://` URI points and how to log in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerUri {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub database: Option<String>,
+}
+
+impl ServerUri {
+    /// Parse `aresadb://[user:pass@]host[:port][/database]`
+    pub fn parse(uri: &str) -> Result<Self> {
+        let url = url::Url::parse(uri).context("Invalid aresadb URI")?;
+        if url.scheme() != "aresadb" {
+            bail!("aresadb server URIs start with aresadb://, got {}://", url.scheme());
+        }
+        let host = url.host_str().context("aresadb URI needs a host")?;
+        let decode = |s: &str| urlencoding::decode(s).map(|s| s.into_owned());
+
+        Ok(Self {
+            host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port: url.port().unwrap_or(DEFAULT_PORT),
+            username: Some(url.username()).filter(|u| !u.is_empty()).map(decode).transpose()?,
+            password: url.password().map(decode).transpose()?,
+            database: Some(url.path().trim_matches('/'))
+                .filter(|d| !d.is_empty())
+                .map(decode)
+                .transpose()?,
+        })
+    }
+}
+
+async fn connect(uri: &str) -> Result<Client> {
+    let server = ServerUri::parse(uri)?;
+
+    // The client wants an address, so resolve host names here
+    let addr = tokio::net::lookup_host((server.host.as_str(), server.port))
+        .await
+        .with_context(|| format!("Failed to resolve {}", server.host))?
+        .next()
+        .with_context(|| format!("No address found for {}", server.host))?;
+
+    let mut builder = ClientBuilder::new()
+        .address(&addr.to_string())
+        .timeout(DEFAULT_CONNECT_TIMEOUT);
+    if let Some(username) = server.username {
+        builder = builder.password(username, server.password.unwrap_or_default());
+    }
+    if let Some(database) = server.database {
+        builder = builder.database(database);
+    }
+
+    builder.build().await.context("Failed to connect to aresadb server")
+}
+
+/// Turn a query result into rows; statements that return no columns
+/// report how many rows they affected
+pub fn result_to_rows(result: &QueryResult) -> Rows {
+    if result.columns.is_empty() {
+        let mut row = HashMap::new();
+        row.insert("rows_affected".to_string(), result.rows_affected.to_string());
+        return (vec!["rows_affected".to_string()], vec![row]);
+    }
+
+    let rows = result
+        .rows
+        .iter()
+        .map(|values| {
+            result
+                .columns
+                .iter()
+                .zip(values)
+                .map(|(column, value)| (column.clone(), value_to_string(value)))
+                .collect()
+        })
+        .collect();
+    (result.columns.clone(), rows)
+}
+
+/// A schema's fields, one row each
+pub fn schema_to_rows(schema: &Schema) -> Rows {
+    let flag = |set: bool| if set { "YES" } else { "NO" }.to_string();
+    let rows = schema
+        .fields
+        .iter()
+        .map(|field| {
+            let mut row = HashMap::new();
+            row.insert("field".to_string(), field.name.clone());
+            row.insert("type".to_string(), field.field_type.to_string());
+            row.insert("nullable".to_string(), flag(field.nullable));
+            row.insert("primary_key".to_string(), flag(field.primary_key));
+            row.insert("unique".to_string(), flag(field.unique));
+            row.insert("indexed".to_string(), flag(field.indexed));
+            row
+        })
+        .collect();
+    (
+        ["field", "type", "nullable", "primary_key", "unique", "indexed"]
+            .iter()
+            .map(|column| column.to_string())
+            .collect(),
+        rows,
+    )
+}
+
+/// Render a value for a table cell; arrays, objects and vectors as JSON
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::Float(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_json().to_string(),
+    }
+}
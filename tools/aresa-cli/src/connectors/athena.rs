@@ -0,0 +1,543 @@
+//! Amazon Athena connector
+//!
+//! Runs queries through the Athena API: StartQueryExecution, then
+//! GetQueryExecution until the query finishes, then GetQueryResults page by
+//! page. Each run reports the bytes scanned and what they cost.
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Athena's list price per TB scanned, in USD
+pub const PRICE_PER_TB_USD: f64 = 5.0;
+
+/// Queries are billed for at least this many bytes
+const MIN_BILLED_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rows GetQueryResults returns per page at most
+const PAGE_SIZE: usize = 1000;
+
+/// How long to wait for a query before stopping it, in seconds
+const DEFAULT_QUERY_TIMEOUT: u64 = 600;
+
+/// Catalog queried when the source doesn't name one
+const DEFAULT_CATALOG: &str = "AwsDataCatalog";
+
+type Rows = (Vec<String>, Vec<HashMap<String, String>>);
+
+/// AWS access keys, with a session token for temporary credentials
+#[derive(Debug, Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Read `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, if set,
+    /// `AWS_SESSION_TOKEN`
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            access_key_id: std::env::var("AWS_ACCESS_KEY_ID").context("AWS_ACCESS_KEY_ID not set")?,
+            secret_access_key: std::env::var("AWS_SECRET_ACCESS_KEY").context("AWS_SECRET_ACCESS_KEY not set")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// What a finished query scanned and how long it took
+#[derive(Debug, Clone, Default)]
+pub struct QueryStats {
+    pub query_execution_id: String,
+    pub data_scanned_bytes: u64,
+    pub engine_time_ms: u64,
+    pub total_time_ms: u64,
+}
+
+impl QueryStats {
+    /// Estimated cost of the query at list price
+    pub fn estimated_cost_usd(&self) -> f64 {
+        estimate_cost_usd(self.data_scanned_bytes)
+    }
+}
+
+/// Estimated cost of scanning `bytes`: billed per MB, rounded up, with a
+/// 10 MB minimum per query. Workgroups on provisioned capacity are billed
+/// by the hour instead.
+pub fn estimate_cost_usd(bytes: u64) -> f64 {
+    const MB: u64 = 1024 * 1024;
+    let billed = bytes.div_ceil(MB).saturating_mul(MB).max(MIN_BILLED_BYTES);
+    billed as f64 / (1u64 << 40) as f64 * PRICE_PER_TB_USD
+}
+
+/// Amazon Athena connector
+pub struct AthenaConnector {
+    endpoint: String,
+    region: String,
+    credentials: AwsCredentials,
+    workgroup: String,
+    output_location: Option<String>,
+    catalog: String,
+    database: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct QueryExecution {
+    status: QueryStatus,
+    statistics: Option<Statistics>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct QueryStatus {
+    state: String,
+    state_change_reason: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+struct Statistics {
+    #[serde(default)]
+    data_scanned_in_bytes: u64,
+    #[serde(default)]
+    engine_execution_time_in_millis: u64,
+    #[serde(default)]
+    total_execution_time_in_millis: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResultsPage {
+    result_set: ResultSet,
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResultSet {
+    result_set_metadata: ResultSetMetadata,
+    #[serde(default)]
+    rows: Vec<ResultRow>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResultSetMetadata {
+    column_info: Vec<ColumnInfo>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ColumnInfo {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResultRow {
+    data: Vec<Datum>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Datum {
+    var_char_value: Option<String>,
+}
+
+impl AthenaConnector {
+    /// Create a new Athena connector
+    ///
+    /// # Arguments
+    /// * `credentials` - AWS access keys
+    /// * `region` - AWS region (default: us-east-1)
+    /// * `workgroup` - Workgroup to run queries in (default: primary)
+    /// * `output_location` - S3 URI for query results; may be omitted when
+    ///   the workgroup sets one
+    pub fn new(
+        credentials: AwsCredentials,
+        region: Option<&str>,
+        workgroup: Option<&str>,
+        output_location: Option<&str>,
+    ) -> Self {
+        let region = region.unwrap_or("us-east-1").to_string();
+        Self {
+            endpoint: format!("https://athena.{}.amazonaws.com", region),
+            region,
+            credentials,
+            workgroup: workgroup.unwrap_or("primary").to_string(),
+            output_location: output_location.map(String::from),
+            catalog: DEFAULT_CATALOG.to_string(),
+            database: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Run queries against `database` in `catalog` (default: AwsDataCatalog)
+    pub fn with_database(mut self, catalog: Option<&str>, database: Option<&str>) -> Self {
+        if let Some(catalog) = catalog {
+            self.catalog = catalog.to_string();
+        }
+        self.database = database.map(String::from);
+        self
+    }
+
+    /// Send requests to another endpoint, such as a VPC endpoint
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Test connection by looking up the workgroup
+    pub async fn test_connection(&self) -> Result<()> {
+        self.call("GetWorkGroup", json!({ "WorkGroup": self.workgroup }))
+            .await
+            .context("Failed to connect to Athena")?;
+        Ok(())
+    }
+
+    /// Execute a SQL query, returning its rows and what it scanned
+    pub async fn execute_sql(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<(Vec<String>, Vec<HashMap<String, String>>, QueryStats)> {
+        let query = with_limit(query, limit);
+        let stats = self.run(&query).await?;
+        let (columns, rows) = self.results(&stats.query_execution_id, limit).await?;
+        Ok((columns, rows, stats))
+    }
+
+    /// Start a query and wait for it to succeed; a query still running
+    /// after the timeout is stopped
+    pub async fn run(&self, query: &str) -> Result<QueryStats> {
+        let mut request = json!({
+            "QueryString": query,
+            "WorkGroup": self.workgroup,
+            "ClientRequestToken": uuid::Uuid::new_v4().to_string(),
+            "QueryExecutionContext": { "Catalog": self.catalog },
+        });
+        if let Some(database) = &self.database {
+            request["QueryExecutionContext"]["Database"] = json!(database);
+        }
+        if let Some(output_location) = &self.output_location {
+            request["ResultConfiguration"] = json!({ "OutputLocation": output_location });
+        }
+
+        let started = self.call("StartQueryExecution", request).await?;
+        let id = started["QueryExecutionId"]
+            .as_str()
+            .context("Athena did not return a query execution ID")?
+            .to_string();
+
+        let deadline = Instant::now() + Duration::from_secs(DEFAULT_QUERY_TIMEOUT);
+        let mut delay = Duration::from_millis(200);
+        loop {
+            let response = self
+                .call("GetQueryExecution", json!({ "QueryExecutionId": id }))
+                .await?;
+            let execution: QueryExecution = serde_json::from_value(response["QueryExecution"].clone())
+                .context("Failed to parse Athena query status")?;
+
+            match execution.status.state.as_str() {
+                "SUCCEEDED" => {
+                    let statistics = execution.statistics.unwrap_or_default();
+                    return Ok(QueryStats {
+                        query_execution_id: id,
+                        data_scanned_bytes: statistics.data_scanned_in_bytes,
+                        engine_time_ms: statistics.engine_execution_time_in_millis,
+                        total_time_ms: statistics.total_execution_time_in_millis,
+                    });
+                }
+                "FAILED" | "CANCELLED" => bail!(
+                    "Athena query {}: {}",
+                    execution.status.state.to_lowercase(),
+                    execution.status.state_change_reason.unwrap_or_default()
+                ),
+                _ => {}
+            }
+
+            if Instant::now() >= deadline {
+                let _ = self.call("StopQueryExecution", json!({ "QueryExecutionId": id })).await;
+                bail!("Athena query {} timed out after {}s and was stopped", id, DEFAULT_QUERY_TIMEOUT);
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(Duration::from_secs(2));
+        }
+    }
+
+    /// Fetch the rows of a finished query, up to `limit`
+    pub async fn results(&self, query_execution_id: &str, limit: Option<usize>) -> Result<Rows> {
+        let mut columns: Vec<String> = Vec::new();
+        let mut rows: Vec<HashMap<String, String>> = Vec::new();
+        let mut next_token: Option<String> = None;
+        let mut first_page = true;
+
+        loop {
+            let wanted = limit.map(|l| l.saturating_sub(rows.len())).unwrap_or(PAGE_SIZE);
+            // SELECT results start with a header row, so ask for one more
+            let mut request = json!({
+                "QueryExecutionId": query_execution_id,
+                "MaxResults": (wanted + usize::from(first_page)).min(PAGE_SIZE),
+            });
+            if let Some(token) = &next_token {
+                request["NextToken"] = json!(token);
+            }
+
+            let page: ResultsPage = serde_json::from_value(self.call("GetQueryResults", request).await?)
+                .context("Failed to parse Athena results")?;
+            if first_page {
+                columns = page
+                    .result_set
+                    .result_set_metadata
+                    .column_info
+                    .iter()
+                    .map(|c| c.name.clone())
+                    .collect();
+            }
+
+            let mut page_rows = page.result_set.rows.into_iter().peekable();
+            if first_page {
+                if let Some(header) = page_rows.peek() {
+                    if is_header(header, &columns) {
+                        page_rows.next();
+                    }
+                }
+                first_page = false;
+            }
+
+            for row in page_rows {
+                rows.push(
+                    columns
+                        .iter()
+                        .zip(row.data)
+                        .map(|(column, datum)| {
+                            (column.clone(), datum.var_char_value.unwrap_or_else(|| "NULL".to_string()))
+                        })
+                        .collect(),
+                );
+            }
+
+            next_token = page.next_token;
+            if next_token.is_none() || limit.is_some_and(|l| rows.len() >= l) {
+                break;
+            }
+        }
+
+        if let Some(limit) = limit {
+            rows.truncate(limit);
+        }
+        Ok((columns, rows))
+    }
+
+    /// Databases in the catalog
+    pub async fn list_databases(&self) -> Result<Rows> {
+        let mut rows = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let mut request = json!({ "CatalogName": self.catalog });
+            if let Some(token) = &next_token {
+                request["NextToken"] = json!(token);
+            }
+            let response = self.call("ListDatabases", request).await?;
+            for database in response["DatabaseList"].as_array().map(Vec::as_slice).unwrap_or_default() {
+                let mut row = HashMap::new();
+                row.insert("database".to_string(), text(&database["Name"]));
+                row.insert("description".to_string(), text(&database["Description"]));
+                rows.push(row);
+            }
+            next_token = response["NextToken"].as_str().map(String::from);
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok((vec!["database".to_string(), "description".to_string()], rows))
+    }
+
+    /// Tables in the configured database, without running a query
+    pub async fn list_tables(&self) -> Result<Rows> {
+        let database = self.database.as_ref().context("No Athena database configured")?;
+
+        let mut rows = Vec::new();
+        let mut next_token: Option<String> = None;
+        loop {
+            let mut request = json!({ "CatalogName": self.catalog, "DatabaseName": database });
+            if let Some(token) = &next_token {
+                request["NextToken"] = json!(token);
+            }
+            let response = self.call("ListTableMetadata", request).await?;
+            for table in response["TableMetadataList"].as_array().map(Vec::as_slice).unwrap_or_default() {
+                let mut row = HashMap::new();
+                row.insert("table".to_string(), text(&table["Name"]));
+                row.insert("type".to_string(), text(&table["TableType"]));
+                rows.push(row);
+            }
+            next_token = response["NextToken"].as_str().map(String::from);
+            if next_token.is_none() {
+                break;
+            }
+        }
+        Ok((vec!["table".to_string(), "type".to_string()], rows))
+    }
+
+    /// Columns and partition keys of a table, without running a query
+    pub async fn describe_table(&self, table: &str) -> Result<Rows> {
+        let database = self.database.as_ref().context("No Athena database configured")?;
+        let response = self
+            .call(
+                "GetTableMetadata",
+                json!({ "CatalogName": self.catalog, "DatabaseName": database, "TableName": table }),
+            )
+            .await?;
+        let metadata = &response["TableMetadata"];
+
+        let mut rows = Vec::new();
+        for (key, partition) in [("Columns", false), ("PartitionKeys", true)] {
+            for column in metadata[key].as_array().map(Vec::as_slice).unwrap_or_default() {
+                let mut row = HashMap::new();
+                row.insert("column".to_string(), text(&column["Name"]));
+                row.insert("type".to_string(), text(&column["Type"]));
+                row.insert("partition".to_string(), if partition { "yes" } else { "" }.to_string());
+                rows.push(row);
+            }
+        }
+        Ok((vec!["column".to_string(), "type".to_string(), "partition".to_string()], rows))
+    }
+
+    /// Call an Athena API action with a JSON request
+    async fn call(&self, action: &str, body: Value) -> Result<Value> {
+        let url = url::Url::parse(&self.endpoint).context("Invalid Athena endpoint")?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let payload = serde_json::to_vec(&body)?;
+        let target = format!("AmazonAthena.{}", action);
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        headers.push(("x-amz-target", target));
+        let authorization = sign_v4(&self.credentials, &self.region, "athena", &headers, &payload, &amz_date)?;
+
+        let mut request = self.client.post(format!("{}/", self.endpoint));
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        let response = request
+            .header("authorization", authorization)
+            .body(payload)
+            .send()
+            .await
+            .context("Failed to send request to Athena")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error: Value = response.json().await.unwrap_or_default();
+            let kind = error["__type"].as_str().unwrap_or_default();
+            let kind = kind.rsplit('#').next().unwrap_or(kind);
+            let message = error["Message"].as_str().or_else(|| error["message"].as_str()).unwrap_or_default();
+            bail!("Athena API error ({}): {} {}", status, kind, message);
+        }
+
+        response.json().await.context("Failed to parse Athena response")
+    }
+}
+
+/// Add LIMIT to a query that returns rows and doesn't limit them itself
+pub(crate) fn with_limit(query: &str, limit: Option<usize>) -> String {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let lower = query.to_lowercase();
+    match limit {
+        Some(limit) if (lower.starts_with("select") || lower.starts_with("with")) && !has_limit(&lower) => {
+            format!("{} LIMIT {}", query, limit)
+        }
+        _ => query.to_string(),
+    }
+}
+
+/// Whether the outermost query ends in its own LIMIT or FETCH clause
+///
+/// Only a trailing clause counts: a LIMIT in a subquery doesn't bound the
+/// rows returned, and a column such as `credit_limit` isn't a clause.
+fn has_limit(query: &str) -> bool {
+    Regex::new(r"\b(limit\s+(\d+|all)|fetch\s+(first|next)\s+(\d+\s+)?rows?\s+only)$")
+        .expect("LIMIT pattern is valid")
+        .is_match(query)
+}
+
+/// AWS Signature V4 for a POST to `/` over `headers`, which must be
+/// lowercase and sorted
+pub(crate) fn sign_v4(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    headers: &[(&str, String)],
+    payload: &[u8],
+    amz_date: &str,
+) -> Result<String> {
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+
+    type HmacSha256 = Hmac<Sha256>;
+
+    let hmac = |key: &[u8], data: &[u8]| -> Result<Vec<u8>> {
+        let mut mac = HmacSha256::new_from_slice(key)?;
+        mac.update(data);
+        Ok(mac.finalize().into_bytes().to_vec())
+    };
+
+    let date_stamp = &amz_date[..8];
+    let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+    let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(payload))
+    );
+
+    let credential_scope = format!("{}/{}/{}/aws4_request", date_stamp, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        credential_scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let k_date = hmac(format!("AWS4{}", credentials.secret_access_key).as_bytes(), date_stamp.as_bytes())?;
+    let k_region = hmac(&k_date, region.as_bytes())?;
+    let k_service = hmac(&k_region, service.as_bytes())?;
+    let k_signing = hmac(&k_service, b"aws4_request")?;
+    let signature = hex::encode(hmac(&k_signing, string_to_sign.as_bytes())?);
+
+    Ok(format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, credential_scope, signed_headers, signature
+    ))
+}
+
+/// SELECT results repeat the column names as their first row
+fn is_header(row: &ResultRow, columns: &[String]) -> bool {
+    row.data.len() == columns.len()
+        && row
+            .data
+            .iter()
+            .zip(columns)
+            .all(|(datum, column)| datum.var_char_value.as_deref() == Some(column.as_str()))
+}
+
+fn text(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
@@ -0,0 +1,318 @@
+//! Elasticsearch / OpenSearch connector
+//!
+//! Query-DSL bodies (`{"query": {...}}`) run against an index through
+//! `_search` and return one row per hit. Anything else is a text query:
+//! ES|QL on Elasticsearch (`FROM logs | WHERE status >= 500`) or SQL on
+//! OpenSearch's SQL plugin, both returning columns and values as-is.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default request timeout in seconds
+const DEFAULT_TIMEOUT: u64 = 30;
+
+type Rows = (Vec<String>, Vec<HashMap<String, String>>);
+
+/// Which engine is behind the URI, from the root endpoint's version info
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    Elasticsearch,
+    OpenSearch,
+}
+
+/// Elasticsearch / OpenSearch connector using the REST API
+pub struct ElasticsearchConnector {
+    base_url: String,
+    client: reqwest::Client,
+    username: Option<String>,
+    password: Option<String>,
+    distribution: Distribution,
+    version: String,
+}
+
+impl ElasticsearchConnector {
+    /// Connect to an `http(s)://[user:pass@]host:port` URI
+    pub async fn new(uri: &str) -> Result<Self> {
+        let mut url = url::Url::parse(uri).context("Invalid Elasticsearch URI")?;
+        let username = Some(url.username())
+            .filter(|u| !u.is_empty())
+            .map(|u| urlencoding::decode(u).map(|u| u.into_owned()))
+            .transpose()?;
+        let password = url
+            .password()
+            .map(|p| urlencoding::decode(p).map(|p| p.into_owned()))
+            .transpose()?;
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(DEFAULT_TIMEOUT))
+            .build()?;
+
+        let mut connector = Self {
+            base_url: url.as_str().trim_end_matches('/').to_string(),
+            client,
+            username,
+            password,
+            distribution: Distribution::Elasticsearch,
+            version: String::new(),
+        };
+
+        // Test connection, learning which engine this is on the way
+        let root = connector.get("/").await.context("Failed to connect to Elasticsearch")?;
+        let version = &root["version"];
+        connector.version = version["number"].as_str().unwrap_or_default().to_string();
+        if version["distribution"].as_str() == Some("opensearch") {
+            connector.distribution = Distribution::OpenSearch;
+        }
+
+        Ok(connector)
+    }
+
+    /// The engine behind the URI
+    pub fn distribution(&self) -> Distribution {
+        self.distribution
+    }
+
+    /// The server version, e.g. "8.13.0"
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Test connection
+    pub async fn test_connection(&self) -> Result<()> {
+        self.get("/").await.context("Failed to connect to Elasticsearch")?;
+        Ok(())
+    }
+
+    /// Run a query-DSL body against `index`, or an ES|QL / OpenSearch SQL
+    /// query; a body is anything starting with `{`
+    pub async fn execute(&self, query: &str, index: Option<&str>, limit: Option<usize>) -> Result<Rows> {
+        if query.trim_start().starts_with('{') {
+            let index = index.context("Query-DSL bodies need an index; pass --index <name>")?;
+            self.search(index, query, limit).await
+        } else {
+            self.execute_text(query, limit).await
+        }
+    }
+
+    /// Run a query-DSL body through `_search`; hits become rows
+    pub async fn search(&self, index: &str, body: &str, limit: Option<usize>) -> Result<Rows> {
+        let mut body: Value = serde_json::from_str(body).context("Query-DSL body is not valid JSON")?;
+        let Some(fields) = body.as_object_mut() else {
+            bail!("Query-DSL body must be a JSON object");
+        };
+        if let Some(limit) = limit {
+            fields.entry("size").or_insert(limit.into());
+        }
+
+        let response = self
+            .post(&format!("/{}/_search", urlencoding::encode(index)), &body)
+            .await?;
+        Ok(hits_to_rows(&response))
+    }
+
+    /// Run ES|QL on Elasticsearch or SQL on OpenSearch
+    pub async fn execute_text(&self, query: &str, limit: Option<usize>) -> Result<Rows> {
+        let query = query.trim().trim_end_matches(';');
+        let query = match limit {
+            Some(limit) if !query.to_lowercase().contains("limit") => match self.distribution {
+                Distribution::Elasticsearch => format!("{} | LIMIT {}", query, limit),
+                Distribution::OpenSearch => format!("{} LIMIT {}", query, limit),
+            },
+            _ => query.to_string(),
+        };
+        let body = serde_json::json!({ "query": query });
+
+        match self.distribution {
+            Distribution::Elasticsearch => {
+                let response = self.post("/_query?format=json", &body).await?;
+                Ok(tabular_to_rows(&response["columns"], &response["values"]))
+            }
+            Distribution::OpenSearch => {
+                let response = self.post("/_plugins/_sql?format=jdbc", &body).await?;
+                Ok(tabular_to_rows(&response["schema"], &response["datarows"]))
+            }
+        }
+    }
+
+    /// Indices with their health, document count and size; hidden and
+    /// system indices (starting with `.`) are left out
+    pub async fn list_indices(&self) -> Result<Rows> {
+        let response = self
+            .get("/_cat/indices?format=json&h=index,health,docs.count,store.size&s=index")
+            .await?;
+
+        let rows = response
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|index| !index["index"].as_str().unwrap_or_default().starts_with('.'))
+            .map(|index| {
+                let mut row = HashMap::new();
+                row.insert("index".to_string(), value_to_string(&index["index"]));
+                row.insert("health".to_string(), value_to_string(&index["health"]));
+                row.insert("documents".to_string(), value_to_string(&index["docs.count"]));
+                row.insert("size".to_string(), value_to_string(&index["store.size"]));
+                row
+            })
+            .collect();
+
+        Ok((
+            vec!["index".to_string(), "health".to_string(), "documents".to_string(), "size".to_string()],
+            rows,
+        ))
+    }
+
+    /// The mapped fields of an index (or index pattern) and their types
+    pub async fn mapping(&self, index: &str) -> Result<Rows> {
+        let response = self
+            .get(&format!("/{}/_mapping", urlencoding::encode(index)))
+            .await?;
+        Ok(mapping_to_rows(&response))
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        self.send(self.client.get(format!("{}{}", self.base_url, path))).await
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<Value> {
+        self.send(self.client.post(format!("{}{}", self.base_url, path)).json(body)).await
+    }
+
+    async fn send(&self, mut request: reqwest::RequestBuilder) -> Result<Value> {
+        if let Some(user) = &self.username {
+            request = request.basic_auth(user, self.password.as_ref());
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send request to Elasticsearch")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body: Value = response.json().await.unwrap_or_default();
+            let reason = body["error"]["reason"]
+                .as_str()
+                .or_else(|| body["error"].as_str())
+                .map(String::from)
+                .unwrap_or_else(|| body.to_string());
+            bail!("Elasticsearch error ({}): {}", status, reason);
+        }
+
+        response.json().await.context("Failed to parse Elasticsearch response")
+    }
+}
+
+/// Turn a `_search` response into rows: `_id`, then the top-level fields
+/// of `_source` in the order they first appear
+pub fn hits_to_rows(response: &Value) -> Rows {
+    let mut columns = vec!["_id".to_string()];
+    let rows = response["hits"]["hits"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|hit| {
+            let mut row = HashMap::new();
+            row.insert("_id".to_string(), value_to_string(&hit["_id"]));
+            if let Some(source) = hit["_source"].as_object() {
+                for (key, value) in source {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                    row.insert(key.clone(), value_to_string(value));
+                }
+            }
+            row
+        })
+        .collect();
+    (columns, rows)
+}
+
+/// Turn an ES|QL or OpenSearch SQL response, a list of `{"name": ...}`
+/// columns and rows of values, into rows
+pub fn tabular_to_rows(columns: &Value, values: &Value) -> Rows {
+    let columns: Vec<String> = columns
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|column| value_to_string(&column["name"]))
+        .collect();
+
+    let rows = values
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .map(|values| {
+            columns
+                .iter()
+                .zip(values.as_array().map(Vec::as_slice).unwrap_or_default())
+                .map(|(column, value)| (column.clone(), value_to_string(value)))
+                .collect()
+        })
+        .collect();
+    (columns, rows)
+}
+
+/// Flatten a `_mapping` response into field paths and types; multi-fields
+/// such as `name.keyword` are listed under their parent, and fields
+/// mapped differently across matching indices show every type
+pub fn mapping_to_rows(response: &Value) -> Rows {
+    let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+    if let Some(indices) = response.as_object() {
+        let mut names: Vec<&String> = indices.keys().collect();
+        names.sort();
+        for name in names {
+            record_properties(&indices[name]["mappings"]["properties"], "", &mut fields);
+        }
+    }
+
+    let rows = fields
+        .into_iter()
+        .map(|(field, types)| {
+            let mut row = HashMap::new();
+            row.insert("field".to_string(), field);
+            row.insert("type".to_string(), types.join(", "));
+            row
+        })
+        .collect();
+    (vec!["field".to_string(), "type".to_string()], rows)
+}
+
+fn record_properties(properties: &Value, prefix: &str, fields: &mut Vec<(String, Vec<String>)>) {
+    let Some(properties) = properties.as_object() else {
+        return;
+    };
+    for (name, property) in properties {
+        let path = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+        // Objects without an explicit type are plain "object" fields
+        let field_type = property["type"].as_str().unwrap_or("object").to_string();
+
+        match fields.iter_mut().find(|(field, _)| *field == path) {
+            Some((_, types)) if !types.contains(&field_type) => types.push(field_type),
+            Some(_) => {}
+            None => fields.push((path.clone(), vec![field_type])),
+        }
+
+        record_properties(&property["properties"], &path, fields);
+        record_properties(&property["fields"], &path, fields);
+    }
+}
+
+/// Render a JSON value for a table cell; objects and arrays as JSON
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "NULL".to_string(),
+        _ => value.to_string(),
+    }
+}
@@ -10,6 +10,12 @@
 //! - **BigQuery**: Google BigQuery via REST API
 //! - **Snowflake**: Snowflake data warehouse via REST API
 //! - **Databricks**: Databricks SQL Warehouse via REST API
+//! - **Athena**: Amazon Athena via its query API, with scan cost reporting
+//! - **Redshift**: Amazon Redshift over the PostgreSQL protocol
+//! - **MongoDB**: find/aggregate commands written as JSON
+//! - **Redis**: key scans and type-aware reads
+//! - **Elasticsearch**: ES|QL, OpenSearch SQL or query-DSL searches
+//! - **AresaDB**: local aresadb directories or remote servers via the client SDK
 //! - **S3**: AWS S3 object storage
 //! - **GCS**: Google Cloud Storage
 
@@ -22,6 +28,12 @@ pub mod clickhouse;
 pub mod bigquery;
 pub mod snowflake;
 pub mod databricks;
+pub mod athena;
+pub mod redshift;
+pub mod mongodb;
+pub mod redis;
+pub mod elasticsearch;
+pub mod aresadb;
 pub mod s3;
 pub mod gcs;
 
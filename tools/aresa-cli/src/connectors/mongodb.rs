@@ -0,0 +1,324 @@
+//! MongoDB connector
+//!
+//! Queries are the `find` and `aggregate` database commands written as
+//! JSON, e.g. `{"find": "users", "filter": {"age": {"$gt": 30}}}` or
+//! `{"aggregate": "orders", "pipeline": [{"$group": {...}}]}`. Documents
+//! become rows keyed by their top-level fields.
+
+use anyhow::{bail, Context, Result};
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::{ClientOptions, FindOptions};
+use mongodb::{Client, Cursor, Database};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default connection timeout in seconds
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+/// Documents sampled to infer a collection's fields
+pub const DEFAULT_SAMPLE_SIZE: usize = 100;
+
+/// A find or aggregate command parsed from JSON
+#[derive(Debug, Clone, PartialEq)]
+pub enum MongoQuery {
+    Find {
+        collection: String,
+        filter: Document,
+        projection: Option<Document>,
+        sort: Option<Document>,
+        limit: Option<i64>,
+    },
+    Aggregate {
+        collection: String,
+        pipeline: Vec<Document>,
+    },
+}
+
+impl MongoQuery {
+    /// Parse a `{"find": ...}` or `{"aggregate": ...}` command; values may
+    /// use extended JSON such as `{"$oid": "..."}` or `{"$date": "..."}`
+    pub fn parse(query: &str) -> Result<Self> {
+        let json: serde_json::Value = serde_json::from_str(query)
+            .context("MongoDB queries are JSON, e.g. {\"find\": \"users\", \"filter\": {}}")?;
+        let command = match Bson::try_from(json).context("Invalid extended JSON")? {
+            Bson::Document(command) => command,
+            _ => bail!("MongoDB query must be a JSON object"),
+        };
+
+        if let Ok(collection) = command.get_str("find") {
+            Ok(MongoQuery::Find {
+                collection: collection.to_string(),
+                filter: optional_document(&command, "filter")?.unwrap_or_default(),
+                projection: optional_document(&command, "projection")?,
+                sort: optional_document(&command, "sort")?,
+                limit: match command.get("limit") {
+                    Some(Bson::Int32(n)) => Some(*n as i64),
+                    Some(Bson::Int64(n)) => Some(*n),
+                    Some(_) => bail!("\"limit\" must be an integer"),
+                    None => None,
+                },
+            })
+        } else if let Ok(collection) = command.get_str("aggregate") {
+            let pipeline = command.get_array("pipeline").context("\"aggregate\" needs a \"pipeline\" array")?;
+            Ok(MongoQuery::Aggregate {
+                collection: collection.to_string(),
+                pipeline: pipeline
+                    .iter()
+                    .map(|stage| match stage {
+                        Bson::Document(stage) => Ok(stage.clone()),
+                        _ => bail!("Pipeline stages must be objects"),
+                    })
+                    .collect::<Result<_>>()?,
+            })
+        } else {
+            bail!("MongoDB query must have a \"find\" or \"aggregate\" collection name")
+        }
+    }
+
+    /// Cap the documents returned, unless the query sets its own limit
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        let Some(limit) = limit else {
+            return self;
+        };
+        match &mut self {
+            MongoQuery::Find { limit: own, .. } => {
+                own.get_or_insert(limit as i64);
+            }
+            MongoQuery::Aggregate { pipeline, .. } => {
+                if !pipeline.iter().any(|stage| stage.contains_key("$limit")) {
+                    pipeline.push(doc! { "$limit": limit as i64 });
+                }
+            }
+        }
+        self
+    }
+}
+
+fn optional_document(command: &Document, key: &str) -> Result<Option<Document>> {
+    match command.get(key) {
+        Some(Bson::Document(document)) => Ok(Some(document.clone())),
+        Some(Bson::Null) | None => Ok(None),
+        Some(_) => bail!("\"{}\" must be an object", key),
+    }
+}
+
+/// MongoDB connector
+pub struct MongoConnector {
+    database: Database,
+}
+
+impl MongoConnector {
+    /// Connect to the database named in the URI, or `database` if given
+    pub async fn new(uri: &str, database: Option<&str>) -> Result<Self> {
+        let mut options = ClientOptions::parse(uri)
+            .await
+            .context("Invalid MongoDB connection URI")?;
+        options.connect_timeout = Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT));
+        options.server_selection_timeout = Some(Duration::from_secs(DEFAULT_CONNECT_TIMEOUT));
+
+        let name = database
+            .map(String::from)
+            .or_else(|| options.default_database.clone())
+            .context("No MongoDB database given; add it to the URI, e.g. mongodb://host:27017/mydb")?;
+        let client = Client::with_options(options).context("Failed to create MongoDB client")?;
+
+        let connector = Self {
+            database: client.database(&name),
+        };
+        connector.test_connection().await?;
+
+        Ok(connector)
+    }
+
+    /// Test connection
+    pub async fn test_connection(&self) -> Result<()> {
+        self.database
+            .run_command(doc! { "ping": 1 }, None)
+            .await
+            .context("Failed to connect to MongoDB")?;
+        Ok(())
+    }
+
+    /// Run a find or aggregate command written as JSON
+    pub async fn execute_json(
+        &self,
+        query: &str,
+        limit: Option<usize>,
+    ) -> Result<(Vec<String>, Vec<HashMap<String, String>>)> {
+        let documents = self.execute(MongoQuery::parse(query)?.with_limit(limit)).await?;
+        Ok(documents_to_rows(&documents))
+    }
+
+    /// Run a parsed query and collect its documents
+    pub async fn execute(&self, query: MongoQuery) -> Result<Vec<Document>> {
+        let cursor = match query {
+            MongoQuery::Find { collection, filter, projection, sort, limit } => {
+                let options = FindOptions::builder()
+                    .projection(projection)
+                    .sort(sort)
+                    .limit(limit)
+                    .build();
+                self.database
+                    .collection::<Document>(&collection)
+                    .find(filter, options)
+                    .await
+                    .context("Failed to execute find")?
+            }
+            MongoQuery::Aggregate { collection, pipeline } => self
+                .database
+                .collection::<Document>(&collection)
+                .aggregate(pipeline, None)
+                .await
+                .context("Failed to execute aggregate")?,
+        };
+        collect(cursor).await
+    }
+
+    /// Collections with their estimated document counts
+    pub async fn list_collections(&self) -> Result<(Vec<String>, Vec<HashMap<String, String>>)> {
+        let mut names = self.database.list_collection_names(None).await?;
+        names.sort();
+
+        let mut rows = Vec::new();
+        for name in names {
+            let count = self
+                .database
+                .collection::<Document>(&name)
+                .estimated_document_count(None)
+                .await?;
+            let mut row = HashMap::new();
+            row.insert("collection".to_string(), name);
+            row.insert("documents".to_string(), count.to_string());
+            rows.push(row);
+        }
+        Ok((vec!["collection".to_string(), "documents".to_string()], rows))
+    }
+
+    /// Infer a collection's fields from a random sample of its documents
+    pub async fn sample_schema(
+        &self,
+        collection: &str,
+        sample_size: usize,
+    ) -> Result<(Vec<String>, Vec<HashMap<String, String>>)> {
+        let pipeline = vec![doc! { "$sample": { "size": sample_size as i64 } }];
+        let cursor = self
+            .database
+            .collection::<Document>(collection)
+            .aggregate(pipeline, None)
+            .await
+            .context("Failed to sample collection")?;
+        let documents = collect(cursor).await?;
+        Ok(infer_schema(&documents))
+    }
+}
+
+async fn collect(mut cursor: Cursor<Document>) -> Result<Vec<Document>> {
+    let mut documents = Vec::new();
+    while cursor.advance().await? {
+        documents.push(cursor.deserialize_current()?);
+    }
+    Ok(documents)
+}
+
+/// Turn documents into rows; columns are the top-level fields in the
+/// order they first appear
+pub fn documents_to_rows(documents: &[Document]) -> (Vec<String>, Vec<HashMap<String, String>>) {
+    let mut columns: Vec<String> = Vec::new();
+    let rows = documents
+        .iter()
+        .map(|document| {
+            document
+                .iter()
+                .map(|(key, value)| {
+                    if !columns.contains(key) {
+                        columns.push(key.clone());
+                    }
+                    (key.clone(), bson_to_string(value))
+                })
+                .collect()
+        })
+        .collect();
+    (columns, rows)
+}
+
+/// Field paths, the types seen at each and how often each is present
+pub fn infer_schema(documents: &[Document]) -> (Vec<String>, Vec<HashMap<String, String>>) {
+    let mut fields: Vec<(String, Vec<&'static str>, usize)> = Vec::new();
+    for document in documents {
+        record_fields(document, "", &mut fields);
+    }
+
+    let rows = fields
+        .into_iter()
+        .map(|(field, types, seen)| {
+            let mut row = HashMap::new();
+            row.insert("field".to_string(), field);
+            row.insert("types".to_string(), types.join(", "));
+            row.insert(
+                "present".to_string(),
+                format!("{:.0}%", seen as f64 * 100.0 / documents.len().max(1) as f64),
+            );
+            row
+        })
+        .collect();
+    (vec!["field".to_string(), "types".to_string(), "present".to_string()], rows)
+}
+
+fn record_fields(document: &Document, prefix: &str, fields: &mut Vec<(String, Vec<&'static str>, usize)>) {
+    for (key, value) in document {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        let index = match fields.iter().position(|(field, _, _)| *field == path) {
+            Some(index) => index,
+            None => {
+                fields.push((path.clone(), Vec::new(), 0));
+                fields.len() - 1
+            }
+        };
+        let (_, types, seen) = &mut fields[index];
+        *seen += 1;
+        let name = type_name(value);
+        if !types.contains(&name) {
+            types.push(name);
+        }
+        if let Bson::Document(nested) = value {
+            record_fields(nested, &path, fields);
+        }
+    }
+}
+
+/// MongoDB's alias for a value's type, as used by `$type`
+fn type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Document(_) => "object",
+        Bson::Array(_) => "array",
+        Bson::Binary(_) => "binData",
+        Bson::ObjectId(_) => "objectId",
+        Bson::Boolean(_) => "bool",
+        Bson::DateTime(_) => "date",
+        Bson::Null | Bson::Undefined => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::Int32(_) => "int",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Int64(_) => "long",
+        Bson::Decimal128(_) => "decimal",
+        _ => "other",
+    }
+}
+
+/// Render a value for a table cell; documents and arrays as relaxed
+/// extended JSON
+pub fn bson_to_string(value: &Bson) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        Bson::Int32(n) => n.to_string(),
+        Bson::Int64(n) => n.to_string(),
+        Bson::Double(n) => n.to_string(),
+        Bson::Boolean(b) => b.to_string(),
+        Bson::Null | Bson::Undefined => "NULL".to_string(),
+        Bson::ObjectId(oid) => oid.to_hex(),
+        Bson::DateTime(dt) => dt.try_to_rfc3339_string().unwrap_or_else(|_| dt.to_string()),
+        other => other.clone().into_relaxed_extjson().to_string(),
+    }
+}
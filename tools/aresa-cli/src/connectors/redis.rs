@@ -0,0 +1,215 @@
+//! Redis connector
+//!
+//! Browse keys with `SCAN` and read a key the way its type calls for:
+//! strings with `GET`, hashes with `HGETALL`, lists, sets, sorted sets
+//! (with scores) and streams as one row per element.
+
+use anyhow::{bail, Context, Result};
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Default connection timeout in seconds
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+/// Keys `SCAN` is asked to examine per call
+const SCAN_COUNT: usize = 500;
+
+/// Rows read from a key or a scan when no limit is given
+pub const DEFAULT_LIMIT: usize = 1000;
+
+/// Redis connector
+pub struct RedisConnector {
+    connection: MultiplexedConnection,
+}
+
+type Rows = (Vec<String>, Vec<HashMap<String, String>>);
+
+impl RedisConnector {
+    /// Connect to a `redis://` or `rediss://` URI
+    pub async fn new(uri: &str) -> Result<Self> {
+        let client = redis::Client::open(uri).context("Invalid Redis connection URI")?;
+        let connection = tokio::time::timeout(
+            Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            client.get_multiplexed_async_connection(),
+        )
+        .await
+        .context("Timed out connecting to Redis")?
+        .context("Failed to connect to Redis")?;
+
+        let connector = Self { connection };
+        connector.test_connection().await?;
+
+        Ok(connector)
+    }
+
+    /// Test connection
+    pub async fn test_connection(&self) -> Result<()> {
+        let mut connection = self.connection.clone();
+        let _: String = redis::cmd("PING")
+            .query_async(&mut connection)
+            .await
+            .context("Redis ping failed")?;
+        Ok(())
+    }
+
+    /// Keys matching a glob pattern, with their types and TTLs
+    ///
+    /// Uses `SCAN`, so it doesn't block the server the way `KEYS` would.
+    pub async fn scan(&self, pattern: &str, limit: Option<usize>) -> Result<Rows> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        let mut connection = self.connection.clone();
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(&mut connection)
+                .await
+                .context("SCAN failed")?;
+            keys.extend(batch);
+            cursor = next;
+            if cursor == 0 || keys.len() >= limit {
+                break;
+            }
+        }
+        keys.truncate(limit);
+        keys.sort();
+
+        let mut rows = Vec::new();
+        for key in keys {
+            let key_type = self.key_type(&key).await?;
+            let ttl: i64 = redis::cmd("TTL").arg(&key).query_async(&mut connection).await?;
+            rows.push(row(&[
+                ("key", key),
+                ("type", key_type),
+                ("ttl", if ttl < 0 { "none".to_string() } else { format!("{}s", ttl) }),
+            ]));
+        }
+        Ok((columns(&["key", "type", "ttl"]), rows))
+    }
+
+    /// The key's type as `TYPE` reports it; `none` if it doesn't exist
+    pub async fn key_type(&self, key: &str) -> Result<String> {
+        let mut connection = self.connection.clone();
+        Ok(redis::cmd("TYPE").arg(key).query_async(&mut connection).await?)
+    }
+
+    /// Read a key as rows, in the shape its type calls for
+    pub async fn read_key(&self, key: &str, limit: Option<usize>) -> Result<Rows> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT);
+        let last = limit as isize - 1;
+        let mut connection = self.connection.clone();
+
+        match self.key_type(key).await?.as_str() {
+            "string" => {
+                let value: Vec<u8> = redis::cmd("GET").arg(key).query_async(&mut connection).await?;
+                Ok((columns(&["key", "value"]), vec![row(&[("key", key.to_string()), ("value", text(&value))])]))
+            }
+            "hash" => {
+                let fields: Vec<(Vec<u8>, Vec<u8>)> = redis::cmd("HGETALL").arg(key).query_async(&mut connection).await?;
+                let rows = fields
+                    .iter()
+                    .take(limit)
+                    .map(|(field, value)| row(&[("field", text(field)), ("value", text(value))]))
+                    .collect();
+                Ok((columns(&["field", "value"]), rows))
+            }
+            "list" => {
+                let items: Vec<Vec<u8>> = redis::cmd("LRANGE").arg(key).arg(0).arg(last).query_async(&mut connection).await?;
+                let rows = items
+                    .iter()
+                    .enumerate()
+                    .map(|(index, value)| row(&[("index", index.to_string()), ("value", text(value))]))
+                    .collect();
+                Ok((columns(&["index", "value"]), rows))
+            }
+            "set" => {
+                let mut members: Vec<String> = redis::cmd("SMEMBERS")
+                    .arg(key)
+                    .query_async::<_, Vec<Vec<u8>>>(&mut connection)
+                    .await?
+                    .iter()
+                    .map(|member| text(member))
+                    .collect();
+                members.sort();
+                members.truncate(limit);
+                let rows = members.into_iter().map(|member| row(&[("member", member)])).collect();
+                Ok((columns(&["member"]), rows))
+            }
+            "zset" => {
+                let members: Vec<(Vec<u8>, f64)> = redis::cmd("ZRANGE")
+                    .arg(key)
+                    .arg(0)
+                    .arg(last)
+                    .arg("WITHSCORES")
+                    .query_async(&mut connection)
+                    .await?;
+                let rows = members
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, (member, score))| {
+                        row(&[("rank", rank.to_string()), ("member", text(member)), ("score", score.to_string())])
+                    })
+                    .collect();
+                Ok((columns(&["rank", "member", "score"]), rows))
+            }
+            "stream" => {
+                let entries: Vec<(String, Vec<(Vec<u8>, Vec<u8>)>)> = redis::cmd("XRANGE")
+                    .arg(key)
+                    .arg("-")
+                    .arg("+")
+                    .arg("COUNT")
+                    .arg(limit)
+                    .query_async(&mut connection)
+                    .await?;
+                let rows = entries
+                    .into_iter()
+                    .map(|(id, fields)| {
+                        let fields: Vec<String> = fields
+                            .iter()
+                            .map(|(field, value)| format!("{}={}", text(field), text(value)))
+                            .collect();
+                        row(&[("id", id), ("fields", fields.join(" "))])
+                    })
+                    .collect();
+                Ok((columns(&["id", "fields"]), rows))
+            }
+            "none" => bail!("Key '{}' not found", key),
+            other => bail!("Reading keys of type '{}' is not supported", other),
+        }
+    }
+
+    /// Server version, memory use and key count from `INFO`
+    pub async fn server_info(&self) -> Result<HashMap<String, String>> {
+        let mut connection = self.connection.clone();
+        let info: String = redis::cmd("INFO").query_async(&mut connection).await?;
+        let keys: u64 = redis::cmd("DBSIZE").query_async(&mut connection).await?;
+
+        let mut result: HashMap<String, String> = info
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(name, _)| matches!(*name, "redis_version" | "used_memory_human" | "connected_clients" | "uptime_in_seconds"))
+            .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+            .collect();
+        result.insert("keys".to_string(), keys.to_string());
+        Ok(result)
+    }
+}
+
+/// Values are usually text; anything else is shown lossily
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn columns(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn row(cells: &[(&str, String)]) -> HashMap<String, String> {
+    cells.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+}
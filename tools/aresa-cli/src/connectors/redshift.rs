@@ -0,0 +1,103 @@
+//! Amazon Redshift connector
+//!
+//! Redshift speaks the PostgreSQL wire protocol, so queries run through the
+//! PostgreSQL connector. Discovery reads Redshift's own system views
+//! (`SVV_TABLES`, `SVV_COLUMNS`), which cover every schema including
+//! external Spectrum and datashare schemas, unlike `pg_table_def`.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use super::postgres::PostgresConnector;
+
+/// Port Redshift clusters and workgroups listen on by default
+pub const DEFAULT_PORT: u16 = 5439;
+
+/// Schema a table name without one is looked up in
+const DEFAULT_SCHEMA: &str = "public";
+
+type Rows = (Vec<String>, Vec<HashMap<String, String>>);
+
+/// Amazon Redshift connector
+pub struct RedshiftConnector {
+    postgres: PostgresConnector,
+}
+
+impl RedshiftConnector {
+    /// Connect to a `redshift://` or `postgresql://` URI
+    pub async fn new(uri: &str) -> Result<Self> {
+        let postgres = PostgresConnector::new(&connection_uri(uri)?)
+            .await
+            .context("Failed to connect to Redshift")?;
+        Ok(Self { postgres })
+    }
+
+    /// Test connection
+    pub async fn test_connection(&self) -> Result<()> {
+        self.postgres.execute_sql("SELECT 1", None).await?;
+        Ok(())
+    }
+
+    /// Execute a SQL query
+    pub async fn execute_sql(&self, query: &str, limit: Option<usize>) -> Result<Rows> {
+        self.postgres.execute_sql(query, limit).await
+    }
+
+    /// Tables and views in user schemas, or in `schema` only
+    pub async fn list_tables(&self, schema: Option<&str>) -> Result<Rows> {
+        let filter = match schema {
+            Some(schema) => format!("table_schema = {}", quote_literal(schema)),
+            None => "table_schema NOT IN ('pg_catalog', 'information_schema', 'pg_internal', 'pg_automv') \
+                     AND table_schema NOT LIKE 'pg_temp%'"
+                .to_string(),
+        };
+        let sql = format!(
+            "SELECT table_schema, table_name, table_type
+             FROM svv_tables
+             WHERE {}
+             ORDER BY table_schema, table_name",
+            filter
+        );
+        self.postgres.execute_sql(&sql, None).await
+    }
+
+    /// Columns of a table, given as `table` (in `public`) or `schema.table`
+    pub async fn describe_table(&self, table: &str) -> Result<Rows> {
+        let (schema, table) = split_table_name(table);
+        let sql = format!(
+            "SELECT column_name, data_type, is_nullable, column_default
+             FROM svv_columns
+             WHERE table_schema = {} AND table_name = {}
+             ORDER BY ordinal_position",
+            quote_literal(schema),
+            quote_literal(table)
+        );
+        self.postgres.execute_sql(&sql, None).await
+    }
+}
+
+/// Turn a `redshift://` URI into the `postgresql://` one the driver
+/// expects, filling in Redshift's default port
+pub fn connection_uri(uri: &str) -> Result<String> {
+    let mut url = url::Url::parse(uri).context("Invalid Redshift connection URI")?;
+    if !matches!(url.scheme(), "redshift" | "postgres" | "postgresql") {
+        anyhow::bail!("Redshift URIs start with redshift:// or postgresql://, got {}://", url.scheme());
+    }
+
+    let port = url.port().unwrap_or(DEFAULT_PORT);
+    url.set_scheme("postgresql")
+        .map_err(|_| anyhow::anyhow!("Invalid Redshift connection URI"))?;
+    url.set_port(Some(port))
+        .map_err(|_| anyhow::anyhow!("Redshift URI needs a host"))?;
+    Ok(url.to_string())
+}
+
+/// Split `schema.table` into its parts; a bare table is in `public`
+pub fn split_table_name(name: &str) -> (&str, &str) {
+    name.split_once('.').unwrap_or((DEFAULT_SCHEMA, name))
+}
+
+/// Quote a string as a SQL literal
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -161,6 +161,130 @@ enum Commands {
         schema: Option<String>,
     },
 
+    /// Query MongoDB with find or aggregate commands written as JSON
+    #[command(visible_alias = "mongo")]
+    Mongodb {
+        /// Connection name from config
+        source: String,
+
+        /// Query as JSON, e.g. '{"find": "users", "filter": {"age": {"$gt": 30}}}'
+        /// or '{"aggregate": "orders", "pipeline": [...]}' (omit for discovery commands)
+        query: Option<String>,
+
+        /// List all collections
+        #[arg(long)]
+        collections: bool,
+
+        /// Infer the fields of a collection from a sample of its documents
+        #[arg(long)]
+        schema: Option<String>,
+
+        /// Documents to sample for --schema
+        #[arg(long, default_value_t = connectors::mongodb::DEFAULT_SAMPLE_SIZE)]
+        sample: usize,
+    },
+
+    /// Browse Redis keys and read them by type
+    Redis {
+        /// Connection name from config
+        source: String,
+
+        /// Key to read: strings with GET, hashes with HGETALL, lists, sets,
+        /// sorted sets and streams one row per element (omit for discovery commands)
+        key: Option<String>,
+
+        /// List keys matching a glob pattern, with types and TTLs
+        #[arg(long)]
+        scan: Option<String>,
+
+        /// Show the type of a key
+        #[arg(long = "type", value_name = "KEY")]
+        key_type: Option<String>,
+
+        /// Show server version, memory use and key count
+        #[arg(long)]
+        info: bool,
+    },
+
+    /// Query Elasticsearch or OpenSearch
+    #[command(visible_alias = "es", visible_alias = "opensearch")]
+    Elasticsearch {
+        /// Connection name from config
+        source: String,
+
+        /// ES|QL (OpenSearch: SQL) query, or a query-DSL body such as
+        /// '{"query": {"match": {"status": "error"}}}' run against --index
+        /// (omit for discovery commands)
+        query: Option<String>,
+
+        /// Index (or pattern) to run a query-DSL body against
+        #[arg(long, short)]
+        index: Option<String>,
+
+        /// List all indices
+        #[arg(long)]
+        indices: bool,
+
+        /// Show the mapped fields of an index
+        #[arg(long)]
+        mapping: Option<String>,
+    },
+
+    /// Query Amazon Athena
+    Athena {
+        /// Connection name from config
+        source: String,
+
+        /// SQL query to execute (omit for discovery commands)
+        query: Option<String>,
+
+        /// List databases in the catalog
+        #[arg(long)]
+        databases: bool,
+
+        /// List all tables
+        #[arg(long)]
+        tables: bool,
+
+        /// Show schema of a table
+        #[arg(long)]
+        schema: Option<String>,
+    },
+
+    /// Query Amazon Redshift
+    Redshift {
+        /// Connection name from config
+        source: String,
+
+        /// SQL query to execute (omit for discovery commands)
+        query: Option<String>,
+
+        /// List tables and views in all user schemas
+        #[arg(long)]
+        tables: bool,
+
+        /// Show schema of a table (`table` in public, or `schema.table`)
+        #[arg(long)]
+        schema: Option<String>,
+    },
+
+    /// Query an AresaDB database, local or on a server
+    Aresadb {
+        /// Connection name from config, a database directory, or an aresadb:// server URI
+        source: String,
+
+        /// SQL query to execute (omit for discovery commands)
+        query: Option<String>,
+
+        /// List all tables
+        #[arg(long)]
+        tables: bool,
+
+        /// Show schema of a table
+        #[arg(long)]
+        schema: Option<String>,
+    },
+
     /// Browse and search AWS S3 buckets
     S3 {
         /// Connection name from config
@@ -267,12 +391,12 @@ enum Commands {
 enum ConfigAction {
     /// Add a new data source
     Add {
-        /// Type: bigquery, postgres, mysql, sqlite, duckdb, clickhouse, s3, gcs
+        /// Type: bigquery, postgres, mysql, sqlite, duckdb, clickhouse, mongodb, redis, elasticsearch, athena, redshift, aresadb, s3, gcs
         #[arg(value_enum)]
         source_type: SourceType,
         /// Name for this connection
         name: String,
-        /// Connection URI (for postgres/mysql/sqlite)
+        /// Connection URI (for postgres/mysql/sqlite/mongodb/redis/elasticsearch/redshift/aresadb)
         #[arg(long)]
         uri: Option<String>,
         /// Project ID (for BigQuery/GCS)
@@ -281,7 +405,7 @@ enum ConfigAction {
         /// Bucket name (for S3/GCS)
         #[arg(long)]
         bucket: Option<String>,
-        /// AWS region (for S3)
+        /// AWS region (for S3/Athena)
         #[arg(long)]
         region: Option<String>,
         /// Host (for ClickHouse)
@@ -290,6 +414,15 @@ enum ConfigAction {
         /// Port (for ClickHouse)
         #[arg(long)]
         port: Option<u16>,
+        /// Database (for Athena)
+        #[arg(long)]
+        database: Option<String>,
+        /// Workgroup (for Athena, default: primary)
+        #[arg(long)]
+        workgroup: Option<String>,
+        /// S3 URI for query results (for Athena, unless the workgroup sets one)
+        #[arg(long)]
+        output_location: Option<String>,
         /// Path to credentials file
         #[arg(long)]
         credentials: Option<String>,
@@ -311,6 +444,12 @@ enum SourceType {
     Sqlite,
     Duckdb,
     Clickhouse,
+    Mongodb,
+    Redis,
+    Elasticsearch,
+    Athena,
+    Redshift,
+    Aresadb,
     S3,
     Gcs,
 }
@@ -360,6 +499,24 @@ async fn main() -> Result<()> {
         Commands::Clickhouse { source, query, tables, schema } => {
             handle_clickhouse(&source, query, tables, schema, &config, &renderer, limit).await?
         }
+        Commands::Mongodb { source, query, collections, schema, sample } => {
+            handle_mongodb(&source, query, collections, schema, sample, &config, &renderer, limit).await?
+        }
+        Commands::Redis { source, key, scan, key_type, info } => {
+            handle_redis(&source, key, scan, key_type, info, &config, &renderer, limit).await?
+        }
+        Commands::Elasticsearch { source, query, index, indices, mapping } => {
+            handle_elasticsearch(&source, query, index, indices, mapping, &config, &renderer, limit).await?
+        }
+        Commands::Athena { source, query, databases, tables, schema } => {
+            handle_athena(&source, query, databases, tables, schema, &config, &renderer, limit).await?
+        }
+        Commands::Redshift { source, query, tables, schema } => {
+            handle_redshift(&source, query, tables, schema, &config, &renderer, limit).await?
+        }
+        Commands::Aresadb { source, query, tables, schema } => {
+            handle_aresadb(&source, query, tables, schema, &config, &renderer, limit).await?
+        }
         Commands::S3 { source, list, search, prefix } => {
             handle_s3(&source, list, search, prefix, &config, &renderer, limit).await?
         }
@@ -686,6 +843,276 @@ async fn handle_clickhouse(
     Ok(())
 }
 
+async fn handle_mongodb(
+    source: &str,
+    query: Option<String>,
+    collections: bool,
+    schema: Option<String>,
+    sample: usize,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    use connectors::mongodb::MongoConnector;
+
+    let uri = config.get_uri(source)?;
+    let database = config.get_source(source).and_then(|s| s.database.as_deref());
+    let connector = MongoConnector::new(&uri, database).await?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows) = if collections {
+        connector.list_collections().await?
+    } else if let Some(collection) = schema {
+        connector.sample_schema(&collection, sample).await?
+    } else if let Some(q) = query {
+        connector.execute_json(&q, limit).await?
+    } else {
+        anyhow::bail!("Provide a JSON query or use --collections or --schema <collection>")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+async fn handle_redis(
+    source: &str,
+    key: Option<String>,
+    scan: Option<String>,
+    key_type: Option<String>,
+    info: bool,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    use connectors::redis::RedisConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = RedisConnector::new(&uri).await?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows) = if info {
+        let info = connector.server_info().await?;
+        let mut columns: Vec<String> = info.keys().cloned().collect();
+        columns.sort();
+        (columns, vec![info])
+    } else if let Some(key) = key_type {
+        let key_type = connector.key_type(&key).await?;
+        let mut row = std::collections::HashMap::new();
+        row.insert("key".to_string(), key);
+        row.insert("type".to_string(), key_type);
+        (vec!["key".to_string(), "type".to_string()], vec![row])
+    } else if let Some(pattern) = scan {
+        connector.scan(&pattern, limit).await?
+    } else if let Some(key) = key {
+        connector.read_key(&key, limit).await?
+    } else {
+        anyhow::bail!("Provide a key or use --scan <pattern>, --type <key> or --info")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+async fn handle_elasticsearch(
+    source: &str,
+    query: Option<String>,
+    index: Option<String>,
+    indices: bool,
+    mapping: Option<String>,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    use connectors::elasticsearch::ElasticsearchConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = ElasticsearchConnector::new(&uri).await?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows) = if indices {
+        connector.list_indices().await?
+    } else if let Some(index) = mapping {
+        connector.mapping(&index).await?
+    } else if let Some(q) = query {
+        connector.execute(&q, index.as_deref(), limit).await?
+    } else {
+        anyhow::bail!("Provide a query or use --indices or --mapping <index>")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+async fn handle_redshift(
+    source: &str,
+    query: Option<String>,
+    tables: bool,
+    schema: Option<String>,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    use connectors::redshift::RedshiftConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = RedshiftConnector::new(&uri).await?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows) = if tables {
+        connector.list_tables(None).await?
+    } else if let Some(table) = schema {
+        connector.describe_table(&table).await?
+    } else if let Some(q) = query {
+        connector.execute_sql(&q, limit).await?
+    } else {
+        anyhow::bail!("Provide a query or use --tables or --schema <table>")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+async fn handle_aresadb(
+    source: &str,
+    query: Option<String>,
+    tables: bool,
+    schema: Option<String>,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    use connectors::aresadb::AresaDbConnector;
+
+    // Not a configured source: take it as a path or server URI
+    let uri = match config.get_source(source) {
+        Some(_) => config.get_uri(source)?,
+        None => source.to_string(),
+    };
+    let connector = AresaDbConnector::new(&uri).await?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows) = if tables {
+        connector.list_tables().await?
+    } else if let Some(table) = schema {
+        connector.describe_table(&table).await?
+    } else if let Some(q) = query {
+        connector.execute_sql(&q, limit).await?
+    } else {
+        anyhow::bail!("Provide a query or use --tables or --schema <table>")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
+
+/// Connect to an Athena source with credentials from the environment
+fn athena_connector(source: &config::DataSource) -> Result<connectors::athena::AthenaConnector> {
+    use connectors::athena::{AthenaConnector, AwsCredentials};
+
+    let credentials = AwsCredentials::from_env()?;
+    Ok(AthenaConnector::new(
+        credentials,
+        source.region.as_deref(),
+        source.workgroup.as_deref(),
+        source.output_location.as_deref(),
+    )
+    .with_database(source.catalog.as_deref(), source.database.as_deref()))
+}
+
+async fn handle_athena(
+    source: &str,
+    query: Option<String>,
+    databases: bool,
+    tables: bool,
+    schema: Option<String>,
+    config: &ConfigManager,
+    renderer: &OutputRenderer,
+    limit: Option<usize>,
+) -> Result<()> {
+    let source_config = config.get_source(source)
+        .context(format!("Athena source '{}' not found", source))?;
+
+    // Athena auth via environment variables (AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_SESSION_TOKEN)
+    let connector = athena_connector(source_config)?;
+
+    let start = std::time::Instant::now();
+    let (columns, rows, stats) = if databases {
+        let (columns, rows) = connector.list_databases().await?;
+        (columns, rows, None)
+    } else if tables {
+        let (columns, rows) = connector.list_tables().await?;
+        (columns, rows, None)
+    } else if let Some(table) = schema {
+        let (columns, rows) = connector.describe_table(&table).await?;
+        (columns, rows, None)
+    } else if let Some(q) = query {
+        let (columns, rows, stats) = connector.execute_sql(&q, limit).await?;
+        (columns, rows, Some(stats))
+    } else {
+        anyhow::bail!("Provide a query or use --databases, --tables or --schema <table>")
+    };
+    let elapsed = start.elapsed();
+
+    renderer.render_query_results_simple(&columns, &rows)?;
+    println!(
+        "\n{} {} rows in {:.2}s",
+        "→".bright_blue(),
+        rows.len(),
+        elapsed.as_secs_f64()
+    );
+    if let Some(stats) = stats {
+        println!(
+            "{} Scanned {} (~${:.4}), query {}",
+            "→".bright_blue(),
+            humansize::format_size(stats.data_scanned_bytes, humansize::BINARY),
+            stats.estimated_cost_usd(),
+            stats.query_execution_id.dimmed()
+        );
+    }
+
+    Ok(())
+}
+
 async fn handle_s3(
     source: &str,
     list: bool,
@@ -834,7 +1261,20 @@ async fn handle_files(
 
 async fn handle_config(action: ConfigAction, config: &ConfigManager) -> Result<()> {
     match action {
-        ConfigAction::Add { source_type, name, uri, project, bucket, region, host, port, credentials } => {
+        ConfigAction::Add {
+            source_type,
+            name,
+            uri,
+            project,
+            bucket,
+            region,
+            host,
+            port,
+            database,
+            workgroup,
+            output_location,
+            credentials,
+        } => {
             let st = match source_type {
                 SourceType::Bigquery => "bigquery",
                 SourceType::Postgres => "postgres",
@@ -842,6 +1282,12 @@ async fn handle_config(action: ConfigAction, config: &ConfigManager) -> Result<(
                 SourceType::Sqlite => "sqlite",
                 SourceType::Duckdb => "duckdb",
                 SourceType::Clickhouse => "clickhouse",
+                SourceType::Mongodb => "mongodb",
+                SourceType::Redis => "redis",
+                SourceType::Elasticsearch => "elasticsearch",
+                SourceType::Athena => "athena",
+                SourceType::Redshift => "redshift",
+                SourceType::Aresadb => "aresadb",
                 SourceType::S3 => "s3",
                 SourceType::Gcs => "gcs",
             };
@@ -854,6 +1300,9 @@ async fn handle_config(action: ConfigAction, config: &ConfigManager) -> Result<(
                 region.as_deref(),
                 host.as_deref(),
                 port,
+                database.as_deref(),
+                workgroup.as_deref(),
+                output_location.as_deref(),
                 credentials.as_deref(),
             )?;
             println!("{} Added {} connection '{}'", "✓".bright_green(), st.bright_cyan(), name.bright_yellow());
@@ -983,6 +1432,24 @@ async fn handle_schema(
         config::SourceType::Databricks => {
             handle_schema_databricks(source_config, table, detailed, renderer).await?;
         }
+        config::SourceType::MongoDB => {
+            handle_schema_mongodb(source, config, table, renderer).await?;
+        }
+        config::SourceType::Elasticsearch => {
+            handle_schema_elasticsearch(source, config, table, renderer).await?;
+        }
+        config::SourceType::Athena => {
+            handle_schema_athena(source_config, table, renderer).await?;
+        }
+        config::SourceType::Redshift => {
+            handle_schema_redshift(source, config, table, renderer).await?;
+        }
+        config::SourceType::AresaDB => {
+            handle_schema_aresadb(source, config, table, renderer).await?;
+        }
+        config::SourceType::Redis => {
+            anyhow::bail!("Redis has no schema. Use 'aresa redis {} --scan <pattern>' to browse keys.", source);
+        }
         config::SourceType::S3 | config::SourceType::GCS => {
             anyhow::bail!("Schema exploration not available for cloud storage. Use --list to browse objects.");
         }
@@ -1360,3 +1827,141 @@ async fn handle_schema_databricks(
 
     Ok(())
 }
+
+async fn handle_schema_mongodb(
+    source: &str,
+    config: &ConfigManager,
+    collection: Option<String>,
+    renderer: &OutputRenderer,
+) -> Result<()> {
+    use connectors::mongodb::{MongoConnector, DEFAULT_SAMPLE_SIZE};
+
+    let uri = config.get_uri(source)?;
+    let database = config.get_source(source).and_then(|s| s.database.as_deref());
+    let connector = MongoConnector::new(&uri, database).await?;
+
+    if let Some(collection) = collection {
+        let (columns, rows) = connector.sample_schema(&collection, DEFAULT_SAMPLE_SIZE).await?;
+        println!("  {} Collection: {} (sampled)\n", "→".dimmed(), collection.bright_white());
+        renderer.render_query_results_simple(&columns, &rows)?;
+    } else {
+        let (_, collections) = connector.list_collections().await?;
+        for c in &collections {
+            if let Some(name) = c.get("collection") {
+                println!("  📋 {}", name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_schema_elasticsearch(
+    source: &str,
+    config: &ConfigManager,
+    index: Option<String>,
+    renderer: &OutputRenderer,
+) -> Result<()> {
+    use connectors::elasticsearch::ElasticsearchConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = ElasticsearchConnector::new(&uri).await?;
+
+    if let Some(index) = index {
+        let (columns, rows) = connector.mapping(&index).await?;
+        println!("  {} Index: {}\n", "→".dimmed(), index.bright_white());
+        renderer.render_query_results_simple(&columns, &rows)?;
+    } else {
+        let (_, indices) = connector.list_indices().await?;
+        for i in &indices {
+            if let Some(name) = i.get("index") {
+                println!("  📋 {}", name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_schema_athena(
+    source: &config::DataSource,
+    table: Option<String>,
+    renderer: &OutputRenderer,
+) -> Result<()> {
+    let connector = athena_connector(source)?;
+
+    if let Some(table) = table {
+        let (columns, rows) = connector.describe_table(&table).await?;
+        println!("  {} Table: {}\n", "→".dimmed(), table.bright_white());
+        renderer.render_query_results_simple(&columns, &rows)?;
+    } else {
+        let (_, tables) = connector.list_tables().await?;
+        for t in &tables {
+            if let Some(name) = t.get("table") {
+                println!("  📋 {}", name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_schema_redshift(
+    source: &str,
+    config: &ConfigManager,
+    table: Option<String>,
+    renderer: &OutputRenderer,
+) -> Result<()> {
+    use connectors::redshift::RedshiftConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = RedshiftConnector::new(&uri).await?;
+
+    if let Some(table_name) = table {
+        let (columns, rows) = connector.describe_table(&table_name).await?;
+        println!("  {} Table: {}\n", "→".dimmed(), table_name.bright_white());
+        renderer.render_query_results_simple(&columns, &rows)?;
+    } else {
+        let (_, tables) = connector.list_tables(None).await?;
+        for t in &tables {
+            let schema = t.get("table_schema").map(|s| s.as_str()).unwrap_or("?");
+            let name = t.get("table_name").map(|s| s.as_str()).unwrap_or("?");
+            let ttype = t.get("table_type").map(|s| s.as_str()).unwrap_or("");
+            let icon = if ttype == "VIEW" { "👁" } else { "📋" };
+            println!("  {} {}.{}", icon, schema, name);
+        }
+
+        println!("\n  {} Use 'aresa schema {} <schema.table>' to see columns", "tip:".dimmed(), source);
+    }
+
+    Ok(())
+}
+
+async fn handle_schema_aresadb(
+    source: &str,
+    config: &ConfigManager,
+    table: Option<String>,
+    renderer: &OutputRenderer,
+) -> Result<()> {
+    use connectors::aresadb::AresaDbConnector;
+
+    let uri = config.get_uri(source)?;
+    let connector = AresaDbConnector::new(&uri).await?;
+
+    if let Some(table_name) = table {
+        let (columns, rows) = connector.describe_table(&table_name).await?;
+        println!("  {} Table: {}\n", "→".dimmed(), table_name.bright_white());
+        renderer.render_query_results_simple(&columns, &rows)?;
+    } else {
+        let (_, tables) = connector.list_tables().await?;
+        for t in &tables {
+            let name = t.get("table").map(|s| s.as_str()).unwrap_or("?");
+            let fields = t.get("fields").map(|s| s.as_str()).unwrap_or("0");
+            println!("  📋 {} {}", name, format!("({} fields)", fields).dimmed());
+        }
+
+        println!("\n  {} Use 'aresa schema {} <table>' to see fields", "tip:".dimmed(), source);
+    }
+
+    Ok(())
+}
@@ -827,6 +827,9 @@ pub mod ui_server {
                     Some(host),
                     req.port,
                     None,
+                    None,
+                    None,
+                    None,
                 )
             }
             _ => {
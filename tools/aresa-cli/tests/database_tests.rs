@@ -187,6 +187,90 @@ async fn test_clickhouse_connection() {
     assert!(result.is_ok() || result.is_err()); // Either outcome is fine for this test
 }
 
+// =============================================================================
+// MongoDB Tests
+// =============================================================================
+
+#[tokio::test]
+#[ignore]
+async fn test_mongodb_find_and_aggregate() {
+    let docker = Cli::default();
+    let mongo = docker.run(Mongo::default());
+
+    let port = mongo.get_host_port_ipv4(27017);
+    let uri = format!("mongodb://localhost:{}/shop", port);
+    let client = ::mongodb::Client::with_uri_str(&uri).await.unwrap();
+    client
+        .database("shop")
+        .collection("products")
+        .insert_many(
+            vec![
+                ::mongodb::bson::doc! { "name": "Laptop", "price": 999 },
+                ::mongodb::bson::doc! { "name": "Mouse", "price": 25 },
+            ],
+            None,
+        )
+        .await
+        .unwrap();
+
+    let connector = mongodb::MongoConnector::new(&uri, None).await.unwrap();
+    let (columns, rows) = connector
+        .execute_json(r#"{"find": "products", "filter": {"price": {"$gt": 100}}, "projection": {"_id": 0}}"#, None)
+        .await
+        .unwrap();
+    assert_eq!(columns, vec!["name", "price"]);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get("name"), Some(&"Laptop".to_string()));
+
+    let (_, rows) = connector
+        .execute_json(r#"{"aggregate": "products", "pipeline": [{"$group": {"_id": null, "total": {"$sum": "$price"}}}]}"#, None)
+        .await
+        .unwrap();
+    assert_eq!(rows[0].get("total"), Some(&"1024".to_string()));
+
+    let (_, collections) = connector.list_collections().await.unwrap();
+    assert!(collections.iter().any(|c| c.get("collection") == Some(&"products".to_string())));
+}
+
+// =============================================================================
+// Redis Tests
+// =============================================================================
+
+#[tokio::test]
+#[ignore]
+async fn test_redis_scan_and_read() {
+    let docker = Cli::default();
+    let container = docker.run(Redis);
+
+    let port = container.get_host_port_ipv4(6379);
+    let uri = format!("redis://localhost:{}", port);
+    let client = ::redis::Client::open(uri.as_str()).unwrap();
+    let mut seed = client.get_multiplexed_async_connection().await.unwrap();
+    ::redis::pipe()
+        .cmd("SET").arg("greeting").arg("hello")
+        .cmd("HSET").arg("user:1").arg("name").arg("Alice").arg("role").arg("admin")
+        .cmd("ZADD").arg("scores").arg(10).arg("bob").arg(30).arg("alice")
+        .query_async::<_, ()>(&mut seed)
+        .await
+        .unwrap();
+
+    let connector = redis::RedisConnector::new(&uri).await.unwrap();
+
+    let (_, keys) = connector.scan("user:*", None).await.unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].get("type"), Some(&"hash".to_string()));
+
+    let (columns, rows) = connector.read_key("user:1", None).await.unwrap();
+    assert_eq!(columns, vec!["field", "value"]);
+    assert!(rows.iter().any(|r| r.get("value") == Some(&"Alice".to_string())));
+
+    let (_, rows) = connector.read_key("scores", None).await.unwrap();
+    assert_eq!(rows[0].get("member"), Some(&"bob".to_string()));
+    assert_eq!(rows[1].get("score"), Some(&"30".to_string()));
+
+    assert!(connector.read_key("missing", None).await.is_err());
+}
+
 // =============================================================================
 // Cross-Database Copy Test
 // =============================================================================
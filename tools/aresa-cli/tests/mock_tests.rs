@@ -87,6 +87,347 @@ mod clickhouse {
     }
 }
 
+// =============================================================================
+// Elasticsearch Mock Tests
+// =============================================================================
+
+mod elasticsearch {
+    use super::*;
+    use aresa_cli::connectors::elasticsearch::{Distribution, ElasticsearchConnector};
+    use wiremock::matchers::{body_json, header};
+
+    async fn cluster(distribution: Option<&str>) -> MockServer {
+        let mock_server = MockServer::start().await;
+        let mut version = serde_json::json!({"number": "8.13.0"});
+        if let Some(distribution) = distribution {
+            version["distribution"] = distribution.into();
+        }
+
+        Mock::given(method("GET"))
+            .and(path("/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"version": version})))
+            .mount(&mock_server)
+            .await;
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn test_elasticsearch_search_hits() {
+        let mock_server = cluster(None).await;
+
+        Mock::given(method("POST"))
+            .and(path("/logs/_search"))
+            .and(body_json(serde_json::json!({"query": {"match_all": {}}, "size": 10})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "hits": {"hits": [{"_id": "a1", "_source": {"status": 500}}]}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let connector = ElasticsearchConnector::new(&mock_server.uri()).await.unwrap();
+        assert_eq!(connector.distribution(), Distribution::Elasticsearch);
+
+        let (columns, rows) = connector
+            .execute(r#"{"query": {"match_all": {}}}"#, Some("logs"), Some(10))
+            .await
+            .unwrap();
+        assert_eq!(columns, vec!["_id", "status"]);
+        assert_eq!(rows[0].get("status"), Some(&"500".to_string()));
+
+        // Bodies need an index to search
+        assert!(connector.execute("{}", None, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_elasticsearch_esql() {
+        let mock_server = cluster(None).await;
+
+        Mock::given(method("POST"))
+            .and(path("/_query"))
+            .and(body_json(serde_json::json!({"query": "FROM logs | STATS c = COUNT(*) | LIMIT 5"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "columns": [{"name": "c", "type": "long"}],
+                "values": [[42]]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let connector = ElasticsearchConnector::new(&mock_server.uri()).await.unwrap();
+        let (columns, rows) = connector
+            .execute("FROM logs | STATS c = COUNT(*);", None, Some(5))
+            .await
+            .unwrap();
+        assert_eq!(columns, vec!["c"]);
+        assert_eq!(rows[0].get("c"), Some(&"42".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_opensearch_sql_with_basic_auth() {
+        let mock_server = cluster(Some("opensearch")).await;
+
+        Mock::given(method("POST"))
+            .and(path("/_plugins/_sql"))
+            .and(header("authorization", "Basic YWRtaW46c2VjcmV0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "schema": [{"name": "status", "type": "integer"}],
+                "datarows": [[200], [404]]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let uri = mock_server.uri().replace("http://", "http://admin:secret@");
+        let connector = ElasticsearchConnector::new(&uri).await.unwrap();
+        assert_eq!(connector.distribution(), Distribution::OpenSearch);
+
+        let (_, rows) = connector.execute("SELECT status FROM logs", None, None).await.unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_elasticsearch_indices_and_errors() {
+        let mock_server = cluster(None).await;
+
+        Mock::given(method("GET"))
+            .and(path("/_cat/indices"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"index": ".security", "health": "green", "docs.count": "3", "store.size": "10kb"},
+                {"index": "logs", "health": "yellow", "docs.count": "1200", "store.size": "2mb"}
+            ])))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/missing/_mapping"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "error": {"type": "index_not_found_exception", "reason": "no such index [missing]"}
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let connector = ElasticsearchConnector::new(&mock_server.uri()).await.unwrap();
+
+        let (_, rows) = connector.list_indices().await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get("documents"), Some(&"1200".to_string()));
+
+        let error = connector.mapping("missing").await.unwrap_err();
+        assert!(error.to_string().contains("no such index [missing]"));
+    }
+}
+
+// =============================================================================
+// Athena Mock Tests
+// =============================================================================
+
+mod athena {
+    use super::*;
+    use aresa_cli::connectors::athena::{AthenaConnector, AwsCredentials};
+    use wiremock::matchers::{body_partial_json, header, header_exists};
+
+    fn connector(mock_server: &MockServer) -> AthenaConnector {
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: None,
+        };
+        AthenaConnector::new(credentials, Some("eu-west-1"), Some("analysts"), Some("s3://results/"))
+            .with_database(None, Some("weblogs"))
+            .with_endpoint(&mock_server.uri())
+    }
+
+    async fn mock_action(mock_server: &MockServer, action: &str, response: serde_json::Value) {
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header("x-amz-target", format!("AmazonAthena.{}", action).as_str()))
+            .and(header_exists("authorization"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_athena_query_with_stats() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(header("x-amz-target", "AmazonAthena.StartQueryExecution"))
+            .and(body_partial_json(serde_json::json!({
+                "QueryString": "SELECT status, hits FROM requests LIMIT 10",
+                "WorkGroup": "analysts",
+                "QueryExecutionContext": {"Catalog": "AwsDataCatalog", "Database": "weblogs"},
+                "ResultConfiguration": {"OutputLocation": "s3://results/"}
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"QueryExecutionId": "q-1"})))
+            .mount(&mock_server)
+            .await;
+
+        mock_action(&mock_server, "GetQueryExecution", serde_json::json!({
+            "QueryExecution": {
+                "Status": {"State": "SUCCEEDED"},
+                "Statistics": {"DataScannedInBytes": 52428800, "EngineExecutionTimeInMillis": 900, "TotalExecutionTimeInMillis": 1100}
+            }
+        })).await;
+
+        mock_action(&mock_server, "GetQueryResults", serde_json::json!({
+            "ResultSet": {
+                "ResultSetMetadata": {"ColumnInfo": [{"Name": "status", "Type": "integer"}, {"Name": "hits", "Type": "bigint"}]},
+                "Rows": [
+                    {"Data": [{"VarCharValue": "status"}, {"VarCharValue": "hits"}]},
+                    {"Data": [{"VarCharValue": "200"}, {"VarCharValue": "1500"}]},
+                    {"Data": [{"VarCharValue": "500"}, {}]}
+                ]
+            }
+        })).await;
+
+        let (columns, rows, stats) = connector(&mock_server)
+            .execute_sql("SELECT status, hits FROM requests;", Some(10))
+            .await
+            .unwrap();
+
+        // The header row is dropped and missing values are NULL
+        assert_eq!(columns, vec!["status", "hits"]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get("hits"), Some(&"NULL".to_string()));
+
+        assert_eq!(stats.query_execution_id, "q-1");
+        assert_eq!(stats.data_scanned_bytes, 50 * 1024 * 1024);
+        assert!(stats.estimated_cost_usd() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_athena_results_paging() {
+        let mock_server = MockServer::start().await;
+
+        mock_action(&mock_server, "StartQueryExecution", serde_json::json!({"QueryExecutionId": "q-3"})).await;
+        mock_action(&mock_server, "GetQueryExecution", serde_json::json!({
+            "QueryExecution": {"Status": {"State": "SUCCEEDED"}}
+        })).await;
+
+        // Only the first page starts with the header row; a later row that
+        // happens to match the column names is data
+        let columns = serde_json::json!({"ColumnInfo": [{"Name": "name", "Type": "varchar"}]});
+        Mock::given(method("POST"))
+            .and(header("x-amz-target", "AmazonAthena.GetQueryResults"))
+            .and(body_partial_json(serde_json::json!({"NextToken": "page-2"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ResultSet": {
+                    "ResultSetMetadata": columns.clone(),
+                    "Rows": [{"Data": [{"VarCharValue": "name"}]}, {"Data": [{"VarCharValue": "carol"}]}]
+                }
+            })))
+            .with_priority(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        mock_action(&mock_server, "GetQueryResults", serde_json::json!({
+            "ResultSet": {
+                "ResultSetMetadata": columns,
+                "Rows": [
+                    {"Data": [{"VarCharValue": "name"}]},
+                    {"Data": [{"VarCharValue": "alice"}]},
+                    {"Data": [{"VarCharValue": "bob"}]}
+                ]
+            },
+            "NextToken": "page-2"
+        })).await;
+
+        let (columns, rows, _) = connector(&mock_server)
+            .execute_sql("SELECT name FROM users", None)
+            .await
+            .unwrap();
+
+        assert_eq!(columns, vec!["name"]);
+        let names: Vec<&str> = rows.iter().map(|row| row["name"].as_str()).collect();
+        assert_eq!(names, vec!["alice", "bob", "name", "carol"]);
+    }
+
+    #[tokio::test]
+    async fn test_athena_results_stop_at_limit() {
+        let mock_server = MockServer::start().await;
+
+        mock_action(&mock_server, "StartQueryExecution", serde_json::json!({"QueryExecutionId": "q-4"})).await;
+        mock_action(&mock_server, "GetQueryExecution", serde_json::json!({
+            "QueryExecution": {"Status": {"State": "SUCCEEDED"}}
+        })).await;
+
+        // The first page asks for one extra row to cover the header, and a
+        // full page means the next one is never fetched
+        Mock::given(method("POST"))
+            .and(header("x-amz-target", "AmazonAthena.GetQueryResults"))
+            .and(body_partial_json(serde_json::json!({"MaxResults": 3})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ResultSet": {
+                    "ResultSetMetadata": {"ColumnInfo": [{"Name": "n", "Type": "integer"}]},
+                    "Rows": [
+                        {"Data": [{"VarCharValue": "n"}]},
+                        {"Data": [{"VarCharValue": "1"}]},
+                        {"Data": [{"VarCharValue": "2"}]}
+                    ]
+                },
+                "NextToken": "page-2"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let (_, rows, _) = connector(&mock_server)
+            .execute_sql("SELECT n FROM numbers", Some(2))
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_athena_query_failure() {
+        let mock_server = MockServer::start().await;
+
+        mock_action(&mock_server, "StartQueryExecution", serde_json::json!({"QueryExecutionId": "q-2"})).await;
+        mock_action(&mock_server, "GetQueryExecution", serde_json::json!({
+            "QueryExecution": {
+                "Status": {"State": "FAILED", "StateChangeReason": "TABLE_NOT_FOUND: line 1:15: Table 'missing' does not exist"}
+            }
+        })).await;
+
+        let error = connector(&mock_server).execute_sql("SELECT * FROM missing", None).await.unwrap_err();
+        assert!(error.to_string().contains("TABLE_NOT_FOUND"));
+    }
+
+    #[tokio::test]
+    async fn test_athena_table_metadata() {
+        let mock_server = MockServer::start().await;
+
+        mock_action(&mock_server, "GetTableMetadata", serde_json::json!({
+            "TableMetadata": {
+                "Name": "requests",
+                "Columns": [{"Name": "status", "Type": "int"}],
+                "PartitionKeys": [{"Name": "dt", "Type": "string"}]
+            }
+        })).await;
+
+        let (_, rows) = connector(&mock_server).describe_table("requests").await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].get("column"), Some(&"dt".to_string()));
+        assert_eq!(rows[1].get("partition"), Some(&"yes".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_athena_api_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "__type": "com.amazonaws.athena#InvalidRequestException",
+                "Message": "WorkGroup analysts is not found."
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let error = connector(&mock_server).test_connection().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("InvalidRequestException WorkGroup analysts is not found."));
+    }
+}
+
 // =============================================================================
 // BigQuery Mock Tests
 // =============================================================================
@@ -0,0 +1,25 @@
+//! Build script
+//!
+//! Generates the gRPC service code from `proto/aresadb.proto` when the
+//! `grpc` feature is enabled. Protos are compiled with protox, so no
+//! system `protoc` is required.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=proto/aresadb.proto");
+
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    let descriptors = protox::compile(["aresadb.proto"], ["proto"])
+        .expect("failed to parse proto/aresadb.proto");
+
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(true)
+        .compile_fds(descriptors)
+        .expect("failed to generate gRPC code");
+}
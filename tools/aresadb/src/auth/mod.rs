@@ -0,0 +1,615 @@
+//! Authentication
+//!
+//! User store for server access control. Users authenticate with a password
+//! (stored as an Argon2 hash) or with an API key. API keys have the form
+//! `aresa_<key id>_<secret>`; only a SHA-256 digest of the secret is kept.
+//!
+//! The store lives in `.aresadb/users.toml` next to the database and is
+//! managed with `aresadb user ...` and `aresadb role ...`.
+
+mod rbac;
+
+pub use rbac::{Grant, Permission, Principal, Role, ADMIN_ROLE, READER_ROLE};
+
+use anyhow::{Result, Context, bail};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::error::CodedError;
+use crate::storage::Timestamp;
+
+/// Prefix of every API key token
+const API_KEY_PREFIX: &str = "aresa";
+
+/// A user account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    /// Unique user name
+    pub name: String,
+    /// Argon2 PHC string; `None` for key-only accounts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password_hash: Option<String>,
+    /// API keys issued to this user
+    #[serde(default)]
+    pub api_keys: Vec<ApiKey>,
+    /// Assigned role names
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// When the user was created
+    pub created_at: Timestamp,
+}
+
+impl User {
+    /// Whether the user can log in with a password
+    pub fn has_password(&self) -> bool {
+        self.password_hash.is_some()
+    }
+}
+
+/// An API key issued to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Public key identifier (embedded in the token)
+    pub id: String,
+    /// Hex SHA-256 digest of the secret part of the token
+    secret_hash: String,
+    /// Optional human-readable description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// When the key was created
+    pub created_at: Timestamp,
+}
+
+/// Credentials presented by a client
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Credentials {
+    /// User name and password
+    Password {
+        /// User name
+        username: String,
+        /// Plain-text password
+        password: String,
+    },
+    /// API key token
+    ApiKey {
+        /// Full `aresa_<id>_<secret>` token
+        key: String,
+    },
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print secrets
+        match self {
+            Credentials::Password { username, .. } => f
+                .debug_struct("Password")
+                .field("username", username)
+                .field("password", &"<redacted>")
+                .finish(),
+            Credentials::ApiKey { .. } => f
+                .debug_struct("ApiKey")
+                .field("key", &"<redacted>")
+                .finish(),
+        }
+    }
+}
+
+/// On-disk layout of the user store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UserFile {
+    #[serde(default)]
+    users: Vec<User>,
+    #[serde(default)]
+    roles: Vec<Role>,
+}
+
+/// Persistent user store
+pub struct UserStore {
+    /// Path to `users.toml`
+    path: PathBuf,
+    /// Loaded users
+    data: RwLock<UserFile>,
+}
+
+impl UserStore {
+    /// Open the user store for a database directory
+    ///
+    /// A missing file is treated as an empty store.
+    pub fn open(db_path: impl AsRef<Path>) -> Result<Self> {
+        let path = db_path.as_ref().join(".aresadb/users.toml");
+
+        let data = if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .context("Failed to read user store")?;
+            toml::from_str(&content).context("Failed to parse user store")?
+        } else {
+            UserFile::default()
+        };
+
+        Ok(Self {
+            path,
+            data: RwLock::new(data),
+        })
+    }
+
+    /// Whether no users are configured
+    pub fn is_empty(&self) -> bool {
+        self.data.read().users.is_empty()
+    }
+
+    /// List all users
+    pub fn list_users(&self) -> Vec<User> {
+        self.data.read().users.clone()
+    }
+
+    /// Get a user by name
+    pub fn get_user(&self, name: &str) -> Option<User> {
+        self.data.read().users.iter().find(|u| u.name == name).cloned()
+    }
+
+    /// Add a new user with an optional password and initial roles
+    pub fn add_user(&self, name: &str, password: Option<&str>, roles: &[String]) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!("Invalid user name: '{}'", name);
+        }
+
+        let password_hash = password.map(hash_password).transpose()?;
+
+        {
+            let mut data = self.data.write();
+            if data.users.iter().any(|u| u.name == name) {
+                return Err(CodedError::conflict(format!("User already exists: {}", name)).into());
+            }
+            for role in roles {
+                if !role_exists(&data, role) {
+                    bail!("Role not found: {}", role);
+                }
+            }
+            data.users.push(User {
+                name: name.to_string(),
+                password_hash,
+                api_keys: Vec::new(),
+                roles: roles.to_vec(),
+                created_at: Timestamp::now(),
+            });
+        }
+
+        self.save()
+    }
+
+    /// Remove a user and all of their API keys
+    pub fn remove_user(&self, name: &str) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            let before = data.users.len();
+            data.users.retain(|u| u.name != name);
+            if data.users.len() == before {
+                bail!("User not found: {}", name);
+            }
+        }
+
+        self.save()
+    }
+
+    /// Set or replace a user's password
+    pub fn set_password(&self, name: &str, password: &str) -> Result<()> {
+        let hash = hash_password(password)?;
+
+        {
+            let mut data = self.data.write();
+            let user = data.users.iter_mut()
+                .find(|u| u.name == name)
+                .with_context(|| format!("User not found: {}", name))?;
+            user.password_hash = Some(hash);
+        }
+
+        self.save()
+    }
+
+    /// Assign a role to a user
+    pub fn assign_role(&self, name: &str, role: &str) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            if !role_exists(&data, role) {
+                bail!("Role not found: {}", role);
+            }
+            let user = data.users.iter_mut()
+                .find(|u| u.name == name)
+                .with_context(|| format!("User not found: {}", name))?;
+            if !user.roles.iter().any(|r| r == role) {
+                user.roles.push(role.to_string());
+            }
+        }
+
+        self.save()
+    }
+
+    /// Remove a role from a user
+    pub fn unassign_role(&self, name: &str, role: &str) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            let user = data.users.iter_mut()
+                .find(|u| u.name == name)
+                .with_context(|| format!("User not found: {}", name))?;
+            let before = user.roles.len();
+            user.roles.retain(|r| r != role);
+            if user.roles.len() == before {
+                bail!("User {} does not have role {}", name, role);
+            }
+        }
+
+        self.save()
+    }
+
+    /// List custom roles followed by the built-in ones
+    pub fn list_roles(&self) -> Vec<Role> {
+        let mut roles = self.data.read().roles.clone();
+        roles.extend([ADMIN_ROLE, READER_ROLE].into_iter().filter_map(Role::builtin));
+        roles
+    }
+
+    /// Create a custom role
+    pub fn create_role(&self, name: &str, grants: Vec<Grant>) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!("Invalid role name: '{}'", name);
+        }
+
+        {
+            let mut data = self.data.write();
+            if role_exists(&data, name) {
+                return Err(CodedError::conflict(format!("Role already exists: {}", name)).into());
+            }
+            data.roles.push(Role { name: name.to_string(), grants });
+        }
+
+        self.save()
+    }
+
+    /// Drop a custom role and remove it from every user
+    pub fn drop_role(&self, name: &str) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            let before = data.roles.len();
+            data.roles.retain(|r| r.name != name);
+            if data.roles.len() == before {
+                if Role::builtin(name).is_some() {
+                    bail!("Cannot drop built-in role: {}", name);
+                }
+                bail!("Role not found: {}", name);
+            }
+            for user in data.users.iter_mut() {
+                user.roles.retain(|r| r != name);
+            }
+        }
+
+        self.save()
+    }
+
+    /// Add or remove a grant on a custom role
+    pub fn update_grant(&self, role: &str, grant: Grant, granted: bool) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            let Some(entry) = data.roles.iter_mut().find(|r| r.name == role) else {
+                if Role::builtin(role).is_some() {
+                    bail!("Cannot modify built-in role: {}", role);
+                }
+                bail!("Role not found: {}", role);
+            };
+
+            if granted {
+                if !entry.grants.contains(&grant) {
+                    entry.grants.push(grant);
+                }
+            } else {
+                let before = entry.grants.len();
+                entry.grants.retain(|g| *g != grant);
+                if entry.grants.len() == before {
+                    bail!("Role {} does not have grant '{}'", role, grant);
+                }
+            }
+        }
+
+        self.save()
+    }
+
+    /// Issue a new API key; the returned token is shown only once
+    pub fn create_api_key(&self, name: &str, description: Option<&str>) -> Result<String> {
+        let id = random_hex(4);
+        let secret = random_hex(32);
+
+        {
+            let mut data = self.data.write();
+            let user = data.users.iter_mut()
+                .find(|u| u.name == name)
+                .with_context(|| format!("User not found: {}", name))?;
+            user.api_keys.push(ApiKey {
+                id: id.clone(),
+                secret_hash: sha256_hex(&secret),
+                description: description.map(String::from),
+                created_at: Timestamp::now(),
+            });
+        }
+
+        self.save()?;
+        Ok(format!("{}_{}_{}", API_KEY_PREFIX, id, secret))
+    }
+
+    /// Revoke an API key by its ID
+    pub fn revoke_api_key(&self, key_id: &str) -> Result<()> {
+        {
+            let mut data = self.data.write();
+            let mut found = false;
+            for user in data.users.iter_mut() {
+                let before = user.api_keys.len();
+                user.api_keys.retain(|k| k.id != key_id);
+                found |= user.api_keys.len() != before;
+            }
+            if !found {
+                bail!("API key not found: {}", key_id);
+            }
+        }
+
+        self.save()
+    }
+
+    /// Verify credentials, returning the user with their resolved grants
+    pub fn authenticate(&self, credentials: &Credentials) -> Option<Principal> {
+        match credentials {
+            Credentials::Password { username, password } => {
+                if !self.verify_password(username, password) {
+                    return None;
+                }
+                Some(Principal::user(username.clone(), self.grants_for(username)))
+            }
+            Credentials::ApiKey { key } => {
+                let username = self.verify_api_key(key)?;
+                let principal = Principal::user(username.clone(), self.grants_for(&username));
+                Some(match parse_api_key(key) {
+                    Some((id, _)) => principal.with_api_key(id),
+                    None => principal,
+                })
+            }
+        }
+    }
+
+    /// All grants a user holds through their roles
+    pub fn grants_for(&self, name: &str) -> Vec<Grant> {
+        let data = self.data.read();
+        let Some(user) = data.users.iter().find(|u| u.name == name) else {
+            return Vec::new();
+        };
+
+        user.roles.iter()
+            .filter_map(|role| {
+                data.roles.iter()
+                    .find(|r| &r.name == role)
+                    .cloned()
+                    .or_else(|| Role::builtin(role))
+            })
+            .flat_map(|role| role.grants)
+            .collect()
+    }
+
+    /// Check a user's password
+    ///
+    /// Users that don't exist or have no password are checked against a
+    /// dummy hash, so the time taken doesn't reveal which names exist.
+    pub fn verify_password(&self, name: &str, password: &str) -> bool {
+        let hash = self.get_user(name).and_then(|u| u.password_hash);
+        let known = hash.is_some();
+        let hash = hash.unwrap_or_else(|| dummy_hash().to_string());
+
+        let verified = match PasswordHash::new(&hash) {
+            Ok(parsed) => Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok(),
+            Err(_) => false,
+        };
+        known && verified
+    }
+
+    /// Check an API key token, returning the owning user name
+    pub fn verify_api_key(&self, token: &str) -> Option<String> {
+        let (id, secret) = parse_api_key(token)?;
+        let digest = sha256_hex(secret);
+
+        let data = self.data.read();
+        data.users.iter().find_map(|user| {
+            user.api_keys.iter()
+                .find(|k| k.id == id && constant_time_eq(k.secret_hash.as_bytes(), digest.as_bytes()))
+                .map(|_| user.name.clone())
+        })
+    }
+
+    /// Write the store back to disk (owner-only permissions on Unix)
+    ///
+    /// The new contents go to a temporary file created owner-only, which
+    /// then replaces the store, so the hashes are never readable by others
+    /// and readers never see a half-written file.
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let content = toml::to_string_pretty(&*self.data.read())?;
+        let temp_path = self.path.with_extension("toml.tmp");
+        // A leftover temporary file may have other permissions
+        match std::fs::remove_file(&temp_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context("Failed to write user store"),
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        {
+            let mut file = options.open(&temp_path).context("Failed to write user store")?;
+            file.write_all(content.as_bytes())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&temp_path, &self.path).context("Failed to write user store")?;
+
+        Ok(())
+    }
+}
+
+/// Split an `aresa_<id>_<secret>` token into its ID and secret
+fn parse_api_key(token: &str) -> Option<(&str, &str)> {
+    token.strip_prefix(API_KEY_PREFIX)?.strip_prefix('_')?.split_once('_')
+}
+
+fn role_exists(data: &UserFile, name: &str) -> bool {
+    Role::builtin(name).is_some() || data.roles.iter().any(|r| r.name == name)
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    if password.is_empty() {
+        bail!("Password must not be empty");
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+/// Hash checked in place of a missing user's, taking as long as a real one
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("aresadb-dummy-password").expect("a fixed password hashes"))
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    to_hex(&bytes)
+}
+
+fn sha256_hex(input: &str) -> String {
+    to_hex(&Sha256::digest(input.as_bytes()))
+}
+
+/// Lowercase hex encoding of `bytes`
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_password_auth() {
+        let temp = TempDir::new().unwrap();
+        let store = UserStore::open(temp.path()).unwrap();
+        assert!(store.is_empty());
+
+        store.add_user("alice", Some("s3cret"), &[]).unwrap();
+        assert!(store.add_user("alice", None, &[]).is_err());
+
+        assert!(store.verify_password("alice", "s3cret"));
+        assert!(!store.verify_password("alice", "wrong"));
+        assert!(!store.verify_password("bob", "s3cret"));
+
+        store.set_password("alice", "changed").unwrap();
+        assert!(!store.verify_password("alice", "s3cret"));
+        assert!(store.verify_password("alice", "changed"));
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let temp = TempDir::new().unwrap();
+        let store = UserStore::open(temp.path()).unwrap();
+        store.add_user("svc", None, &[]).unwrap();
+
+        let token = store.create_api_key("svc", Some("ingest job")).unwrap();
+        assert!(token.starts_with("aresa_"));
+        assert_eq!(store.verify_api_key(&token), Some("svc".to_string()));
+        assert_eq!(store.verify_api_key("aresa_nope_nope"), None);
+
+        let key_id = store.get_user("svc").unwrap().api_keys[0].id.clone();
+        store.revoke_api_key(&key_id).unwrap();
+        assert_eq!(store.verify_api_key(&token), None);
+    }
+
+    #[test]
+    fn test_store_persistence() {
+        let temp = TempDir::new().unwrap();
+        let token = {
+            let store = UserStore::open(temp.path()).unwrap();
+            store.add_user("alice", Some("pw"), &[ADMIN_ROLE.to_string()]).unwrap();
+            store.create_api_key("alice", None).unwrap()
+        };
+
+        let store = UserStore::open(temp.path()).unwrap();
+        let creds = Credentials::ApiKey { key: token };
+        let principal = store.authenticate(&creds).unwrap();
+        assert_eq!(principal.username(), Some("alice"));
+        assert!(principal.credential().unwrap().starts_with("key:"));
+        assert!(principal.can(Permission::Write, "users"));
+        assert!(!format!("{:?}", creds).contains("aresa_"));
+
+        store.remove_user("alice").unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_store_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new().unwrap();
+        let store = UserStore::open(temp.path()).unwrap();
+        store.add_user("alice", Some("pw"), &[]).unwrap();
+        store.add_user("bob", Some("pw"), &[]).unwrap();
+
+        let mode = std::fs::metadata(&store.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!store.path.with_extension("toml.tmp").exists());
+        assert_eq!(UserStore::open(temp.path()).unwrap().list_users().len(), 2);
+    }
+
+    #[test]
+    fn test_roles() {
+        let temp = TempDir::new().unwrap();
+        let store = UserStore::open(temp.path()).unwrap();
+
+        store.create_role("analyst", vec!["read users".parse().unwrap()]).unwrap();
+        assert!(store.create_role("analyst", Vec::new()).is_err());
+        assert!(store.add_user("ana", Some("pw"), &["missing".to_string()]).is_err());
+        store.add_user("ana", Some("pw"), &["analyst".to_string()]).unwrap();
+
+        let creds = Credentials::Password { username: "ana".to_string(), password: "pw".to_string() };
+        let principal = store.authenticate(&creds).unwrap();
+        assert!(principal.can(Permission::Read, "users"));
+        assert!(!principal.can(Permission::Write, "users"));
+
+        store.update_grant("analyst", "write chunks".parse().unwrap(), true).unwrap();
+        assert!(store.update_grant(ADMIN_ROLE, "read x".parse().unwrap(), true).is_err());
+        assert!(store.authenticate(&creds).unwrap().can(Permission::Write, "chunks"));
+
+        store.drop_role("analyst").unwrap();
+        assert!(store.get_user("ana").unwrap().roles.is_empty());
+        assert!(!store.authenticate(&creds).unwrap().can(Permission::Read, "users"));
+        assert!(store.drop_role(READER_ROLE).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Role-Based Access Control
+//!
+//! Roles are named sets of grants such as `read users` or `write chunks`.
+//! A grant applies to a node or edge type, or to every type with `*`.
+//! `write` implies `read` on the same type.
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Built-in role with full access
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Built-in role with read access to every type
+pub const READER_ROLE: &str = "reader";
+
+/// Operation class a grant allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Read nodes, edges, and query results
+    Read,
+    /// Insert, update, and delete (implies read)
+    Write,
+}
+
+impl Permission {
+    /// Whether holding `self` allows `requested`
+    pub fn allows(self, requested: Permission) -> bool {
+        self == Permission::Write || self == requested
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::Read => write!(f, "read"),
+            Permission::Write => write!(f, "write"),
+        }
+    }
+}
+
+/// A single permission on a type (`*` for all types)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Grant {
+    /// Allowed operation class
+    pub permission: Permission,
+    /// Node or edge type, or `*`
+    pub target: String,
+}
+
+impl Grant {
+    /// Create a grant
+    pub fn new(permission: Permission, target: impl Into<String>) -> Self {
+        Self { permission, target: target.into() }
+    }
+
+    /// Whether this grant covers an operation on a type
+    ///
+    /// Only a wildcard grant covers the wildcard target.
+    pub fn covers(&self, permission: Permission, type_name: &str) -> bool {
+        self.permission.allows(permission) && (self.target == "*" || self.target == type_name)
+    }
+}
+
+impl FromStr for Grant {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let (Some(permission), Some(target), None) = (parts.next(), parts.next(), parts.next()) else {
+            bail!("Invalid grant '{}': expected '<read|write> <type|*>'", s);
+        };
+
+        let permission = match permission.to_lowercase().as_str() {
+            "read" => Permission::Read,
+            "write" => Permission::Write,
+            other => bail!("Unknown permission '{}': expected read or write", other),
+        };
+
+        Ok(Self::new(permission, target))
+    }
+}
+
+impl TryFrom<String> for Grant {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Grant> for String {
+    fn from(grant: Grant) -> String {
+        grant.to_string()
+    }
+}
+
+impl fmt::Display for Grant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.permission, self.target)
+    }
+}
+
+/// A named set of grants
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Role {
+    /// Role name
+    pub name: String,
+    /// Grants held by the role
+    #[serde(default)]
+    pub grants: Vec<Grant>,
+}
+
+impl Role {
+    /// Grants of a built-in role, if `name` is one
+    pub fn builtin(name: &str) -> Option<Role> {
+        let grants = match name {
+            ADMIN_ROLE => vec![Grant::new(Permission::Write, "*")],
+            READER_ROLE => vec![Grant::new(Permission::Read, "*")],
+            _ => return None,
+        };
+        Some(Role { name: name.to_string(), grants })
+    }
+}
+
+/// Resolved identity and permissions of a connected client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    username: Option<String>,
+    /// `None` means unrestricted (authentication disabled)
+    grants: Option<Vec<Grant>>,
+    /// Identifies the credential used (`key:<id>` or `user:<name>`)
+    credential: Option<String>,
+}
+
+impl Principal {
+    /// Principal used when authentication is disabled
+    pub fn unrestricted() -> Self {
+        Self { username: None, grants: None, credential: None }
+    }
+
+    /// Principal with no permissions at all
+    pub fn denied() -> Self {
+        Self { username: None, grants: Some(Vec::new()), credential: None }
+    }
+
+    /// Authenticated user with resolved grants
+    pub fn user(username: impl Into<String>, grants: Vec<Grant>) -> Self {
+        let username = username.into();
+        Self {
+            credential: Some(format!("user:{}", username)),
+            username: Some(username),
+            grants: Some(grants),
+        }
+    }
+
+    /// Record that the principal authenticated with a specific API key
+    pub fn with_api_key(mut self, key_id: &str) -> Self {
+        self.credential = Some(format!("key:{}", key_id));
+        self
+    }
+
+    /// Authenticated user name, if any
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Credential identifier, used to key per-credential limits
+    pub fn credential(&self) -> Option<&str> {
+        self.credential.as_deref()
+    }
+
+    /// Whether access checks can be skipped
+    pub fn is_unrestricted(&self) -> bool {
+        self.grants.is_none()
+    }
+
+    /// Whether the principal may administer the server (write on every type)
+    pub fn is_admin(&self) -> bool {
+        self.can(Permission::Write, "*")
+    }
+
+    /// Check a permission on a type
+    pub fn can(&self, permission: Permission, type_name: &str) -> bool {
+        match self.grants {
+            None => true,
+            Some(ref grants) => grants.iter().any(|g| g.covers(permission, type_name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_parsing() {
+        let grant: Grant = "read users".parse().unwrap();
+        assert_eq!(grant, Grant::new(Permission::Read, "users"));
+        assert_eq!(grant.to_string(), "read users");
+
+        assert!("delete users".parse::<Grant>().is_err());
+        assert!("read".parse::<Grant>().is_err());
+        assert!("read users now".parse::<Grant>().is_err());
+    }
+
+    #[test]
+    fn test_principal_checks() {
+        let analyst = Principal::user("ana", vec![
+            "read users".parse().unwrap(),
+            "write chunks".parse().unwrap(),
+        ]);
+
+        assert!(analyst.can(Permission::Read, "users"));
+        assert!(!analyst.can(Permission::Write, "users"));
+        assert!(analyst.can(Permission::Read, "chunks"));
+        assert!(analyst.can(Permission::Write, "chunks"));
+        assert!(!analyst.can(Permission::Read, "orders"));
+        assert!(!analyst.can(Permission::Read, "*"));
+
+        let admin = Principal::user("root", Role::builtin(ADMIN_ROLE).unwrap().grants);
+        assert!(admin.can(Permission::Write, "anything"));
+        assert!(admin.can(Permission::Read, "*"));
+        assert!(admin.is_admin());
+        assert!(!analyst.is_admin());
+
+        assert!(Principal::unrestricted().can(Permission::Write, "users"));
+        assert!(!Principal::denied().can(Permission::Read, "users"));
+    }
+}
@@ -4,91 +4,154 @@
 
 use anyhow::Result;
 use clap::Parser;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[derive(Parser)]
 #[command(name = "aresadb-server")]
 #[command(about = "AresaDB server for remote connections")]
 struct Args {
+    /// Server config file (TOML); flags override its values
+    #[arg(long)]
+    config: Option<String>,
+
     /// Database path
-    #[arg(short, long, default_value = ".")]
-    database: String,
+    #[arg(short, long)]
+    database: Option<String>,
 
     /// Bind address
-    #[arg(short, long, default_value = "127.0.0.1:7432")]
-    bind: String,
+    #[arg(short, long)]
+    bind: Option<String>,
 
     /// Maximum connections
-    #[arg(short, long, default_value = "1000")]
-    max_connections: usize,
+    #[arg(short, long)]
+    max_connections: Option<usize>,
 
     /// Enable compression
-    #[arg(short, long, default_value = "true")]
-    compression: bool,
+    #[arg(short, long)]
+    compression: Option<bool>,
 
     /// Number of shards (0 for single-node mode)
-    #[arg(short, long, default_value = "0")]
-    shards: usize,
-}
+    #[arg(short, long)]
+    shards: Option<usize>,
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "aresadb=info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    /// Address for the HTTP REST API (e.g. 127.0.0.1:8080)
+    #[arg(long)]
+    http: Option<String>,
 
-    let args = Args::parse();
+    /// Address for the gRPC API (e.g. 127.0.0.1:50051)
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc: Option<String>,
 
-    tracing::info!("Starting AresaDB server...");
-    tracing::info!("Database path: {}", args.database);
-    tracing::info!("Bind address: {}", args.bind);
+    /// Address for the Prometheus metrics endpoint (e.g. 127.0.0.1:9464)
+    #[arg(long)]
+    metrics: Option<String>,
 
-    let config = aresadb::server::ServerConfig {
-        bind_addr: args.bind.parse()?,
-        max_connections: args.max_connections,
-        compression: args.compression,
-        ..Default::default()
-    };
+    /// Log every query to .aresadb/logs/query.log
+    #[arg(long)]
+    query_log: bool,
 
-    let server = if args.shards > 0 {
-        tracing::info!("Sharded mode with {} shards", args.shards);
+    /// Log queries at least this slow (ms) to .aresadb/logs/slow.log
+    #[arg(long)]
+    slow_query_ms: Option<u64>,
 
-        let shard_config = aresadb::distributed::ShardConfig {
-            num_shards: args.shards,
-            base_path: std::path::PathBuf::from(&args.database),
-            ..Default::default()
-        };
+    /// Write-ahead log file whose size the metrics endpoint reports
+    #[arg(long)]
+    wal: Option<String>,
 
-        let shards = aresadb::distributed::ShardManager::new(shard_config).await?;
-        aresadb::server::Server::with_shards(shards, config)
-    } else {
-        tracing::info!("Single-node mode");
-
-        // Try to open existing database, or create new one
-        let db = match aresadb::storage::Database::open(&args.database).await {
-            Ok(db) => db,
-            Err(_) => {
-                tracing::info!("Creating new database");
-                aresadb::storage::Database::create(&args.database, "aresadb").await?
-            }
-        };
+    /// Host an additional database as NAME=PATH (repeatable)
+    #[arg(long = "tenant", value_name = "NAME=PATH")]
+    tenants: Vec<String>,
+
+    /// Maximum concurrent connections per additional database
+    #[arg(long)]
+    tenant_max_connections: Option<usize>,
+
+    /// Maximum rows returned per query on additional databases
+    #[arg(long)]
+    tenant_max_rows: Option<usize>,
+
+    /// Maximum requests per second on each connection
+    #[arg(long)]
+    conn_max_qps: Option<f64>,
 
-        aresadb::server::Server::new(db, config)
-    };
+    /// Maximum bytes per second (requests plus responses) on each connection
+    #[arg(long)]
+    conn_max_bytes: Option<u64>,
 
-    // Handle shutdown signal
-    let shutdown = server.shutdown.clone();
-    tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.ok();
-        tracing::info!("Shutdown signal received");
-        *shutdown.write() = true;
-    });
+    /// Maximum requests per second for each API key or user
+    #[arg(long)]
+    key_max_qps: Option<f64>,
 
-    server.run().await?;
+    /// Maximum bytes per second for each API key or user
+    #[arg(long)]
+    key_max_bytes: Option<u64>,
+
+    /// Seconds to wait for open connections to close on shutdown
+    #[arg(long)]
+    drain_timeout: Option<u64>,
+}
+
+impl Args {
+    /// Options from the config file (if any) with flags applied on top
+    fn options(self) -> Result<aresadb::server::ServeOptions> {
+        let mut options = match &self.config {
+            Some(path) => aresadb::server::ServeOptions::load(path)?,
+            None => Default::default(),
+        };
+
+        if let Some(database) = self.database {
+            options.database = database;
+        }
+        if let Some(bind) = self.bind {
+            options.bind = bind;
+        }
+        if let Some(max_connections) = self.max_connections {
+            options.max_connections = max_connections;
+        }
+        if let Some(compression) = self.compression {
+            options.compression = compression;
+        }
+        if let Some(shards) = self.shards {
+            options.shards = shards;
+        }
+        options.http = self.http.or(options.http);
+        #[cfg(feature = "grpc")]
+        {
+            options.grpc = self.grpc.or(options.grpc);
+        }
+        options.metrics = self.metrics.or(options.metrics);
+        options.query_log |= self.query_log;
+        options.slow_query_ms = self.slow_query_ms.or(options.slow_query_ms);
+        options.wal = self.wal.or(options.wal);
+        for spec in &self.tenants {
+            options.add_tenant(spec)?;
+        }
+        options.tenant_max_connections = self.tenant_max_connections.or(options.tenant_max_connections);
+        options.tenant_max_rows = self.tenant_max_rows.or(options.tenant_max_rows);
+        options.conn_max_qps = self.conn_max_qps.or(options.conn_max_qps);
+        options.conn_max_bytes = self.conn_max_bytes.or(options.conn_max_bytes);
+        options.key_max_qps = self.key_max_qps.or(options.key_max_qps);
+        options.key_max_bytes = self.key_max_bytes.or(options.key_max_bytes);
+        if let Some(drain_timeout) = self.drain_timeout {
+            options.drain_timeout_secs = drain_timeout;
+        }
+
+        Ok(options)
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Initialize tracing
+    let (otlp, _telemetry) = aresadb::telemetry::otlp_layer("aresadb-server");
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "aresadb=info".into()),
+        )))
+        .with(otlp)
+        .init();
 
-    Ok(())
+    let options = Args::parse().options()?;
+    aresadb::server::serve(options).await
 }
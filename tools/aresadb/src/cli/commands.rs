@@ -10,8 +10,14 @@ use crate::query::QueryResult;
 pub enum OutputFormat {
     #[default]
     Table,
+    #[value(alias = "jsonl")]
     Json,
     Csv,
+    /// GitHub-flavored Markdown tables
+    #[value(alias = "md")]
+    Markdown,
+    /// One JSON object per row, written as rows are read
+    Ndjson,
 }
 
 /// Handle a CLI command
@@ -30,6 +36,8 @@ pub fn format_result(result: &QueryResult, format: OutputFormat) -> String {
         OutputFormat::Table => format_as_table(result),
         OutputFormat::Json => format_as_json(result),
         OutputFormat::Csv => format_as_csv(result),
+        OutputFormat::Markdown => crate::output::MarkdownRenderer::new().format(result),
+        OutputFormat::Ndjson => format_as_ndjson(result),
     }
 }
 
@@ -94,28 +102,16 @@ fn format_as_json(result: &QueryResult) -> String {
     serde_json::to_string_pretty(&result.to_json()).unwrap_or_else(|_| "{}".to_string())
 }
 
-fn format_as_csv(result: &QueryResult) -> String {
-    let mut lines = Vec::new();
-
-    // Header
-    lines.push(result.columns.join(","));
-
-    // Rows
-    for row in &result.rows {
-        let row_str: Vec<String> = row.iter()
-            .map(|v| {
-                let s = format!("{}", v);
-                if s.contains(',') || s.contains('"') || s.contains('\n') {
-                    format!("\"{}\"", s.replace('"', "\"\""))
-                } else {
-                    s
-                }
-            })
-            .collect();
-        lines.push(row_str.join(","));
-    }
+fn format_as_ndjson(result: &QueryResult) -> String {
+    let mut renderer = crate::output::NdjsonRenderer::to_writer(Vec::new());
+    let _ = renderer.render(result);
+    String::from_utf8_lossy(&renderer.into_inner()).trim_end().to_string()
+}
 
-    lines.join("\n")
+fn format_as_csv(result: &QueryResult) -> String {
+    let mut renderer = crate::output::CsvRenderer::to_writer(Vec::new(), crate::output::CsvOptions::defaults());
+    let _ = renderer.render(result);
+    String::from_utf8_lossy(&renderer.into_inner()).trim_end().to_string()
 }
 
 
@@ -2,12 +2,15 @@
 //!
 //! Handles global and database-specific configuration.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::output::{DisplayOptions, Overflow};
+
 /// Configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -19,6 +22,22 @@ pub struct Config {
     #[serde(default)]
     pub default_limit: Option<usize>,
 
+    /// Widest a table column gets
+    #[serde(default)]
+    pub max_width: Option<usize>,
+
+    /// What happens to wider cells (truncate, wrap)
+    #[serde(default)]
+    pub overflow: Option<String>,
+
+    /// Text shown for NULL
+    #[serde(default)]
+    pub null: Option<String>,
+
+    /// Whether to color output
+    #[serde(default)]
+    pub color: Option<bool>,
+
     /// Additional key-value settings
     #[serde(default)]
     pub settings: BTreeMap<String, String>,
@@ -87,6 +106,13 @@ impl Config {
         match key {
             "default_format" => config.default_format = value.to_string(),
             "default_limit" => config.default_limit = value.parse().ok(),
+            "max_width" => config.max_width = Some(value.parse().context("max_width must be a number")?),
+            "overflow" => {
+                Overflow::from_str(value, true).map_err(|e| anyhow::anyhow!(e))?;
+                config.overflow = Some(value.to_string());
+            }
+            "null" => config.null = Some(value.to_string()),
+            "color" => config.color = Some(value.parse().context("color must be true or false")?),
             _ => {
                 config.settings.insert(key.to_string(), value.to_string());
             }
@@ -100,10 +126,25 @@ impl Config {
         match key {
             "default_format" => Some(self.default_format.clone()),
             "default_limit" => self.default_limit.map(|l| l.to_string()),
+            "max_width" => self.max_width.map(|w| w.to_string()),
+            "overflow" => self.overflow.clone(),
+            "null" => self.null.clone(),
+            "color" => self.color.map(|c| c.to_string()),
             _ => self.settings.get(key).cloned(),
         }
     }
 
+    /// Table display options from this config, falling back to `base`
+    pub fn display_options(&self, base: &DisplayOptions) -> DisplayOptions {
+        DisplayOptions {
+            max_width: self.max_width.unwrap_or(base.max_width),
+            overflow: self.overflow.as_deref()
+                .and_then(|o| Overflow::from_str(o, true).ok())
+                .unwrap_or(base.overflow),
+            null: self.null.clone().unwrap_or_else(|| base.null.clone()),
+        }
+    }
+
     /// Print all configuration
     pub fn print_all(&self) -> Result<()> {
         println!("{}", "Configuration:".bright_yellow().bold());
@@ -113,6 +154,14 @@ impl Config {
         println!("  default_format: {}", if self.default_format.is_empty() { "table" } else { &self.default_format });
         println!("  default_limit: {}", self.default_limit.map(|l| l.to_string()).unwrap_or_else(|| "1000".to_string()));
 
+        let display = self.display_options(&DisplayOptions::default());
+        println!();
+        println!("{}", "Display:".bright_cyan());
+        println!("  max_width: {}", display.max_width);
+        println!("  overflow: {}", self.overflow.as_deref().unwrap_or("truncate"));
+        println!("  null: {}", display.null);
+        println!("  color: {}", self.color.unwrap_or(true));
+
         if !self.settings.is_empty() {
             println!();
             println!("{}", "Custom:".bright_cyan());
@@ -1,9 +1,11 @@
 //! Interactive REPL
 //!
-//! Read-Eval-Print Loop with syntax highlighting for SQL queries.
+//! Read-Eval-Print Loop with syntax highlighting and completion for SQL
+//! queries. Table and column names are completed from the schema registry.
 
 use anyhow::Result;
 use colored::Colorize;
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
 use rustyline::hint::{Hint, Hinter};
@@ -11,108 +13,142 @@ use rustyline::history::DefaultHistory;
 use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{Editor, Helper as RustylineHelper};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use crate::storage::Database;
-use crate::query::QueryEngine;
+use crate::query::{PlanCache, QueryEngine};
 use crate::output::Renderer;
 use super::commands::OutputFormat;
 
+/// SQL keywords, completed and highlighted
+const SQL_KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE",
+    "SET", "DELETE", "ORDER", "BY", "LIMIT", "OFFSET", "AND", "OR",
+    "NOT", "NULL", "TRUE", "FALSE", "AS", "JOIN", "ON", "LEFT", "RIGHT",
+    "INNER", "OUTER", "GROUP", "HAVING", "DISTINCT", "COUNT", "SUM",
+    "AVG", "MIN", "MAX", "LIKE", "IN", "BETWEEN", "IS", "CREATE",
+    "DROP", "ALTER", "TABLE", "INDEX", "ASC", "DESC",
+];
+
+/// REPL commands
+const COMMANDS: &[&str] = &[
+    ".help", ".exit", ".quit", ".schema", ".tables", ".status", ".format", ".clear", ".timing", ".plans",
+];
+
+/// Keywords after which a table name comes
+const TABLE_CONTEXT: &[&str] = &["FROM", "INTO", "UPDATE", "JOIN", "TABLE", ".SCHEMA"];
+
 /// REPL helper for syntax highlighting and completion
+#[derive(Default)]
 struct ReplHelper {
-    keywords: Vec<String>,
+    /// Table names and their columns, from the schema registry
+    tables: BTreeMap<String, Vec<String>>,
 }
 
-impl Default for ReplHelper {
-    fn default() -> Self {
-        Self {
-            keywords: vec![
-                // SQL keywords
-                "SELECT".to_string(),
-                "FROM".to_string(),
-                "WHERE".to_string(),
-                "INSERT".to_string(),
-                "INTO".to_string(),
-                "VALUES".to_string(),
-                "UPDATE".to_string(),
-                "SET".to_string(),
-                "DELETE".to_string(),
-                "ORDER".to_string(),
-                "BY".to_string(),
-                "LIMIT".to_string(),
-                "OFFSET".to_string(),
-                "AND".to_string(),
-                "OR".to_string(),
-                "NOT".to_string(),
-                "NULL".to_string(),
-                "TRUE".to_string(),
-                "FALSE".to_string(),
-                // AresaDB specific
-                ".help".to_string(),
-                ".exit".to_string(),
-                ".quit".to_string(),
-                ".schema".to_string(),
-                ".tables".to_string(),
-                ".status".to_string(),
-                ".format".to_string(),
-                ".clear".to_string(),
-            ],
+impl ReplHelper {
+    /// Completions for the word ending the input, and where it starts
+    fn candidates(&self, input: &str) -> (usize, Vec<String>) {
+        let start = input
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map_or(0, |i| i + 1);
+        let word = &input[start..];
+        let matches = |candidate: &str| {
+            candidate.len() > word.len() && candidate.to_lowercase().starts_with(&word.to_lowercase())
+        };
+
+        if start == 0 && word.starts_with('.') {
+            return (start, COMMANDS.iter().filter(|c| matches(c)).map(|c| c.to_string()).collect());
         }
-    }
-}
-
-impl Highlighter for ReplHelper {
-    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
-        let mut result = line.to_string();
-
-        // Highlight SQL keywords
-        let sql_keywords = [
-            "SELECT", "FROM", "WHERE", "INSERT", "INTO", "VALUES", "UPDATE",
-            "SET", "DELETE", "ORDER", "BY", "LIMIT", "OFFSET", "AND", "OR",
-            "NOT", "NULL", "TRUE", "FALSE", "AS", "JOIN", "ON", "LEFT", "RIGHT",
-            "INNER", "OUTER", "GROUP", "HAVING", "DISTINCT", "COUNT", "SUM",
-            "AVG", "MIN", "MAX", "LIKE", "IN", "BETWEEN", "IS", "CREATE",
-            "DROP", "ALTER", "TABLE", "INDEX",
-        ];
-
-        for keyword in &sql_keywords {
-            let pattern = format!(r"\b{}\b", keyword);
-            if let Ok(re) = regex::Regex::new(&pattern) {
-                result = re.replace_all(&result, |caps: &regex::Captures| {
-                    format!("\x1b[34;1m{}\x1b[0m", &caps[0])
-                }).to_string();
-            }
 
-            // Also match lowercase
-            let pattern_lower = format!(r"\b{}\b", keyword.to_lowercase());
-            if let Ok(re) = regex::Regex::new(&pattern_lower) {
-                result = re.replace_all(&result, |caps: &regex::Captures| {
-                    format!("\x1b[34;1m{}\x1b[0m", &caps[0])
-                }).to_string();
-            }
+        // table.column
+        if let Some(dot) = word.rfind('.') {
+            let columns = self.tables.get(&word[..dot]).cloned().unwrap_or_default();
+            let prefix = word[dot + 1..].to_lowercase();
+            let found = columns.into_iter().filter(|c| c.to_lowercase().starts_with(&prefix)).collect();
+            return (start + dot + 1, found);
         }
 
-        // Highlight strings
-        if let Ok(re) = regex::Regex::new(r#"'[^']*'|"[^"]*""#) {
-            result = re.replace_all(&result, |caps: &regex::Captures| {
-                format!("\x1b[32m{}\x1b[0m", &caps[0])
-            }).to_string();
+        let previous = input[..start].split_whitespace().last().unwrap_or("").to_uppercase();
+        if TABLE_CONTEXT.contains(&previous.as_str()) {
+            return (start, self.tables.keys().filter(|t| matches(t)).cloned().collect());
         }
 
-        // Highlight numbers
-        if let Ok(re) = regex::Regex::new(r"\b\d+(\.\d+)?\b") {
-            result = re.replace_all(&result, |caps: &regex::Captures| {
-                format!("\x1b[33m{}\x1b[0m", &caps[0])
-            }).to_string();
+        // Columns of the tables named so far, or of every table
+        let named: Vec<&Vec<String>> = input.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter_map(|w| self.tables.get(w))
+            .collect();
+        let columns: Vec<&Vec<String>> = if named.is_empty() { self.tables.values().collect() } else { named };
+        let mut found: Vec<String> = Vec::new();
+        for column in columns.into_iter().flatten() {
+            if (word.is_empty() || matches(column)) && !found.contains(column) {
+                found.push(column.clone());
+            }
+        }
+        if !word.is_empty() {
+            // Keywords in the case being typed
+            let lower = word.chars().any(|c| c.is_lowercase());
+            found.extend(SQL_KEYWORDS.iter()
+                .filter(|k| matches(k))
+                .map(|k| if lower { k.to_lowercase() } else { k.to_string() }));
         }
+        (start, found)
+    }
 
-        // Highlight commands (starting with .)
-        if let Ok(re) = regex::Regex::new(r"^\.[a-z]+") {
-            result = re.replace_all(&result, |caps: &regex::Captures| {
-                format!("\x1b[35;1m{}\x1b[0m", &caps[0])
-            }).to_string();
+    /// Color strings, numbers, keywords, table names and commands
+    fn highlight_line(&self, line: &str) -> String {
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let end_of = |i: usize| chars.get(i).map_or(line.len(), |(at, _)| *at);
+        let mut result = String::with_capacity(line.len() * 2);
+        let mut i = 0;
+
+        while i < chars.len() {
+            let (at, c) = chars[i];
+            let mut j = i + 1;
+            let color = if c == '\'' || c == '"' {
+                while j < chars.len() && chars[j].1 != c {
+                    j += 1;
+                }
+                j = (j + 1).min(chars.len());
+                Some("32")
+            } else if c.is_ascii_digit() {
+                while j < chars.len() && (chars[j].1.is_ascii_digit() || chars[j].1 == '.') {
+                    j += 1;
+                }
+                Some("33")
+            } else if c.is_alphanumeric() || c == '_' || (i == 0 && c == '.') {
+                while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                    j += 1;
+                }
+                let word = &line[at..end_of(j)];
+                if c == '.' {
+                    Some("35;1")
+                } else if SQL_KEYWORDS.contains(&word.to_uppercase().as_str()) {
+                    Some("34;1")
+                } else if self.tables.contains_key(word) {
+                    Some("36")
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            let token = &line[at..end_of(j)];
+            match color {
+                Some(color) => result.push_str(&format!("\x1b[{}m{}\x1b[0m", color, token)),
+                None => result.push_str(token),
+            }
+            i = j;
         }
 
-        Cow::Owned(result)
+        result
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(self.highlight_line(line))
     }
 
     fn highlight_prompt<'b, 's: 'b, 'p: 'b>(&'s self, prompt: &'p str, _default: bool) -> Cow<'b, str> {
@@ -159,7 +195,7 @@ impl Hinter for ReplHelper {
         let line_lower = line.to_lowercase();
 
         // Find matching keywords
-        for keyword in &self.keywords {
+        for keyword in SQL_KEYWORDS.iter().chain(COMMANDS) {
             let keyword_lower = keyword.to_lowercase();
             if keyword_lower.starts_with(&line_lower) && keyword_lower != line_lower {
                 let hint = &keyword[line.len()..];
@@ -198,9 +234,12 @@ impl Validator for ReplHelper {
     }
 }
 
-// Implement the required traits for RustylineHelper
-impl rustyline::completion::Completer for ReplHelper {
+impl Completer for ReplHelper {
     type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
 }
 
 impl RustylineHelper for ReplHelper {}
@@ -210,6 +249,10 @@ pub struct Repl {
     editor: Editor<ReplHelper, DefaultHistory>,
     db: Database,
     format: OutputFormat,
+    /// Print how each query was executed after its results
+    timing: bool,
+    /// Plans kept across queries, so repeated statements skip parsing
+    plans: Arc<PlanCache>,
     history_path: Option<std::path::PathBuf>,
 }
 
@@ -232,12 +275,33 @@ impl Repl {
             let _ = editor.load_history(path);
         }
 
-        Ok(Self {
+        let mut repl = Self {
             editor,
             db,
             format: OutputFormat::Table,
+            timing: false,
+            plans: Arc::new(PlanCache::default()),
             history_path,
-        })
+        };
+        repl.refresh_completions().await;
+        Ok(repl)
+    }
+
+    /// Reload table and column names for completion
+    async fn refresh_completions(&mut self) {
+        use crate::schema::SchemaManager;
+
+        let Ok(db) = Database::open(self.db.path()).await else {
+            return;
+        };
+        let Ok(schemas) = SchemaManager::new(db).list_schemas().await else {
+            return;
+        };
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.tables = schemas.into_iter()
+                .map(|schema| (schema.name, schema.fields.into_iter().map(|f| f.name).collect()))
+                .collect();
+        }
     }
 
     /// Run the REPL
@@ -258,8 +322,8 @@ impl Repl {
                     // Add to history
                     let _ = self.editor.add_history_entry(line);
 
-                    // Handle commands
-                    if line.starts_with('.') {
+                    // Handle commands; psql-style \timing works too
+                    if line.starts_with('.') || line.starts_with('\\') {
                         if self.handle_command(line).await? {
                             break;
                         }
@@ -268,6 +332,7 @@ impl Repl {
 
                     // Execute SQL query
                     self.execute_sql(line).await;
+                    self.refresh_completions().await;
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("Use .exit or .quit to exit");
@@ -325,6 +390,7 @@ impl Repl {
                 self.show_status().await?;
             }
             ".tables" | ".schemas" => {
+                self.refresh_completions().await;
                 self.show_tables().await?;
             }
             ".schema" => {
@@ -334,18 +400,45 @@ impl Repl {
                     println!("Usage: .schema <table_name>");
                 }
             }
+            ".timing" | "\\timing" => {
+                match parts.get(1).map(|v| v.to_lowercase()) {
+                    None => self.timing = !self.timing,
+                    Some(v) if v == "on" => self.timing = true,
+                    Some(v) if v == "off" => self.timing = false,
+                    Some(_) => println!("Usage: .timing [on|off]"),
+                }
+                println!("Timing is {}", if self.timing { "on" } else { "off" });
+            }
+            ".plans" => {
+                match parts.get(1).map(|v| v.to_lowercase()) {
+                    None => {
+                        let stats = self.plans.stats();
+                        println!(
+                            "{} plans cached, {} hits, {} misses",
+                            stats.entries, stats.hits, stats.misses
+                        );
+                    }
+                    Some(v) if v == "flush" => {
+                        self.plans.flush();
+                        println!("Plan cache flushed");
+                    }
+                    Some(_) => println!("Usage: .plans [flush]"),
+                }
+            }
             ".format" => {
                 if let Some(fmt) = parts.get(1) {
                     match fmt.to_lowercase().as_str() {
                         "table" => self.format = OutputFormat::Table,
                         "json" => self.format = OutputFormat::Json,
                         "csv" => self.format = OutputFormat::Csv,
-                        _ => println!("Unknown format. Use: table, json, csv"),
+                        "markdown" | "md" => self.format = OutputFormat::Markdown,
+                        "ndjson" => self.format = OutputFormat::Ndjson,
+                        _ => println!("Unknown format. Use: table, json, csv, markdown, ndjson"),
                     }
                     println!("Output format: {:?}", self.format);
                 } else {
                     println!("Current format: {:?}", self.format);
-                    println!("Usage: .format <table|json|csv>");
+                    println!("Usage: .format <table|json|csv|markdown|ndjson>");
                 }
             }
             _ => {
@@ -367,6 +460,8 @@ impl Repl {
         println!("  {} List all tables/schemas", ".tables".bright_green());
         println!("  {} Show schema for a table", ".schema <name>".bright_green());
         println!("  {} Set output format", ".format <fmt>".bright_green());
+        println!("  {} Show rows scanned, index and stage timings", ".timing [on|off]".bright_green());
+        println!("  {} Show plan cache usage, or drop cached plans", ".plans [flush]".bright_green());
         println!();
         println!("{}", "SQL Examples:".bright_yellow().bold());
         println!();
@@ -393,6 +488,8 @@ impl Repl {
             "Size:".bright_cyan(),
             humansize::format_size(status.size_bytes, humansize::BINARY)
         );
+        println!("  {} {}", "Cache:".bright_cyan(), status.cache.summary());
+        println!("  {} {}", "Memory:".bright_cyan(), status.memory.summary());
         println!();
 
         Ok(())
@@ -451,7 +548,7 @@ impl Repl {
                     };
 
                     println!(
-                        "  {} {:?}{}",
+                        "  {} {}{}",
                         field.name.bright_green(),
                         field.field_type,
                         attrs_str.bright_black()
@@ -474,7 +571,7 @@ impl Repl {
 
         let engine = QueryEngine::new(
             Database::open(self.db.path()).await.unwrap()
-        );
+        ).with_plan_cache(Arc::clone(&self.plans));
         let result = engine.execute_sql(sql, None).await;
 
         let elapsed = start.elapsed();
@@ -494,6 +591,9 @@ impl Repl {
                     query_result.row_count(),
                     elapsed.as_secs_f64() * 1000.0
                 );
+                if self.timing {
+                    println!("{} {}", "→".bright_black(), query_result.stats.summary().bright_black());
+                }
             }
             Err(e) => {
                 eprintln!("{} {}", "Error:".bright_red().bold(), e);
@@ -541,3 +641,40 @@ mod dirs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper() -> ReplHelper {
+        let mut helper = ReplHelper::default();
+        helper.tables.insert("users".to_string(), vec!["name".to_string(), "age".to_string()]);
+        helper.tables.insert("posts".to_string(), vec!["title".to_string(), "author".to_string()]);
+        helper
+    }
+
+    #[test]
+    fn test_completion() {
+        let helper = helper();
+        assert_eq!(helper.candidates("SELECT * FROM u"), (14, vec!["users".to_string()]));
+        assert_eq!(helper.candidates("sel"), (0, vec!["select".to_string()]));
+        assert_eq!(helper.candidates(".sch"), (0, vec![".schema".to_string()]));
+        assert_eq!(helper.candidates(".schema p"), (8, vec!["posts".to_string()]));
+        // Columns of the named table come before keywords
+        let found = helper.candidates("SELECT * FROM users WHERE A").1;
+        assert_eq!(found, ["age", "AND", "AS", "AVG", "ALTER", "ASC"]);
+        assert_eq!(helper.candidates("SELECT posts.t"), (13, vec!["title".to_string()]));
+    }
+
+    #[test]
+    fn test_highlighting() {
+        let helper = helper();
+        let line = helper.highlight_line("select name from users where age > 30 and name = 'select 1'");
+        assert!(line.starts_with("\x1b[34;1mselect\x1b[0m name "));
+        assert!(line.contains("\x1b[36musers\x1b[0m"));
+        assert!(line.contains("\x1b[33m30\x1b[0m"));
+        // Keywords and numbers inside strings stay part of the string
+        assert!(line.ends_with("\x1b[32m'select 1'\x1b[0m"));
+        assert_eq!(helper.highlight_line(".tables"), "\x1b[35;1m.tables\x1b[0m");
+    }
+}
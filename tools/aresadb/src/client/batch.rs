@@ -0,0 +1,115 @@
+//! Request Batches
+//!
+//! Collects requests and sends them in a single frame, so bulk work pays
+//! one network round trip instead of one per request.
+
+use anyhow::{Result, bail};
+
+use super::Client;
+use crate::server::{Request, Response};
+use crate::storage::Value;
+
+/// Builder for a batch of requests, created with `Client::batch`
+pub struct Batch {
+    client: Client,
+    requests: Vec<Request>,
+    /// First error hit while building, reported by `send`
+    error: Option<anyhow::Error>,
+}
+
+impl Batch {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            requests: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Add a raw request
+    pub fn request(mut self, request: Request) -> Self {
+        self.requests.push(request);
+        self
+    }
+
+    /// Add a node insert
+    pub fn insert_node(self, node_type: &str, properties: serde_json::Value) -> Self {
+        let node_type = node_type.to_string();
+        self.with_properties(properties, |properties| Request::InsertNode { node_type, properties })
+    }
+
+    /// Add a node update
+    pub fn update_node(self, id: &str, properties: serde_json::Value) -> Self {
+        let id = id.to_string();
+        self.with_properties(properties, |properties| Request::UpdateNode { id, properties })
+    }
+
+    /// Add a node delete
+    pub fn delete_node(self, id: &str) -> Self {
+        self.request(Request::DeleteNode { id: id.to_string() })
+    }
+
+    /// Add an edge creation
+    pub fn create_edge(self, from_id: &str, to_id: &str, edge_type: &str, properties: Option<serde_json::Value>) -> Self {
+        let from_id = from_id.to_string();
+        let to_id = to_id.to_string();
+        let edge_type = edge_type.to_string();
+        match properties {
+            Some(properties) => self.with_properties(properties, |properties| Request::CreateEdge {
+                from_id,
+                to_id,
+                edge_type,
+                properties: Some(properties),
+            }),
+            None => self.request(Request::CreateEdge { from_id, to_id, edge_type, properties: None }),
+        }
+    }
+
+    /// Add a SQL query
+    pub fn query(self, sql: &str, limit: Option<usize>) -> Self {
+        self.request(Request::Query { sql: sql.to_string(), limit })
+    }
+
+    /// Number of requests in the batch
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether the batch has no requests
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Send the batch, returning one response per request in order
+    ///
+    /// Individual requests may fail with a `Response::Error` without
+    /// failing the batch.
+    pub async fn send(self) -> Result<Vec<Response>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.requests.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let expected = self.requests.len();
+        match self.client.send_request(Request::Batch(self.requests)).await? {
+            Response::Batch(responses) if responses.len() == expected => Ok(responses),
+            Response::Batch(responses) => {
+                bail!("Batch returned {} responses for {} requests", responses.len(), expected)
+            }
+            Response::Error { message, .. } => bail!("Batch failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    fn with_properties(mut self, properties: serde_json::Value, request: impl FnOnce(Value) -> Request) -> Self {
+        match Value::from_json(properties) {
+            Ok(properties) => self.requests.push(request(properties)),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+        self
+    }
+}
@@ -4,8 +4,11 @@
 
 use anyhow::{Result, Context};
 use std::net::SocketAddr;
+use std::time::Duration;
 
-use super::Client;
+use super::{Client, RetryPolicy};
+use super::pool::{Handshake, PoolConfig};
+use crate::auth::Credentials;
 
 /// Builder for creating AresaDB clients
 #[derive(Debug, Clone)]
@@ -14,6 +17,13 @@ pub struct ClientBuilder {
     port: u16,
     pub(crate) compression: bool,
     timeout_secs: u64,
+    max_connections: usize,
+    retry: RetryPolicy,
+    request_timeout: Option<Duration>,
+    credentials: Option<Credentials>,
+    database: Option<String>,
+    peers: Vec<String>,
+    settings: Vec<(String, String)>,
 }
 
 impl Default for ClientBuilder {
@@ -30,6 +40,13 @@ impl ClientBuilder {
             port: 7432,
             compression: true,
             timeout_secs: 10,
+            max_connections: 8,
+            retry: RetryPolicy::default(),
+            request_timeout: None,
+            credentials: None,
+            database: None,
+            peers: Vec::new(),
+            settings: Vec::new(),
         }
     }
 
@@ -70,19 +87,92 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the maximum number of pooled connections shared by clones of the client
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Give every request a deadline
+    ///
+    /// The server abandons requests that run past it and the client stops
+    /// waiting, failing the request with a timeout error.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how broken connections and transient server errors are retried
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Apply a session setting on every connection (see `Client::set`)
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.settings.push((name.into(), value.into()));
+        self
+    }
+
+    /// Add another replica (as `host:port`) to ask for the leader when the
+    /// server is unreachable or no longer leads
+    pub fn peer(mut self, addr: impl Into<String>) -> Self {
+        self.peers.push(addr.into());
+        self
+    }
+
+    /// Authenticate with a user name and password after connecting
+    pub fn password(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some(Credentials::Password {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Authenticate with an API key after connecting
+    pub fn api_key(mut self, key: impl Into<String>) -> Self {
+        self.credentials = Some(Credentials::ApiKey { key: key.into() });
+        self
+    }
+
+    /// Select a database hosted by the server (defaults to the server's default)
+    pub fn database(mut self, name: impl Into<String>) -> Self {
+        self.database = Some(name.into());
+        self
+    }
+
     /// Build and connect the client
     pub async fn build(self) -> Result<Client> {
         let addr: SocketAddr = format!("{}:{}", self.host, self.port)
             .parse()
             .context("Invalid server address")?;
 
-        let mut client = Client::connect(addr).await?;
+        self.connect(addr).await
+    }
 
-        if !self.compression {
-            client.compressor = None;
+    /// Connect to an already resolved address
+    pub(crate) async fn connect(self, addr: SocketAddr) -> Result<Client> {
+        let peers = self.peers
+            .iter()
+            .map(|peer| peer.parse().with_context(|| format!("Invalid peer address: {}", peer)))
+            .collect::<Result<Vec<SocketAddr>>>()?;
+
+        let config = PoolConfig {
+            addr,
+            peers,
+            compression: self.compression,
+            connect_timeout: Duration::from_secs(self.timeout_secs),
+            max_connections: self.max_connections,
+            retry: self.retry,
+            request_timeout: self.request_timeout,
+        };
+        let mut handshake = Handshake::new(self.database, self.credentials);
+        for (name, value) in &self.settings {
+            handshake.set(name, value);
         }
 
-        Ok(client)
+        Client::with_pool(config, handshake).await
     }
 
     /// Get the configured address
@@ -110,12 +200,25 @@ mod tests {
             .host("example.com")
             .port(8080)
             .compression(false)
-            .timeout(30);
+            .timeout(30)
+            .max_connections(4)
+            .retry(RetryPolicy::none())
+            .request_timeout(Duration::from_secs(5));
 
         assert_eq!(builder.host, "example.com");
         assert_eq!(builder.port, 8080);
         assert!(!builder.compression);
         assert_eq!(builder.timeout_secs, 30);
+        assert_eq!(builder.max_connections, 4);
+        assert_eq!(builder.retry, RetryPolicy::none());
+        assert_eq!(builder.request_timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_builder_credentials() {
+        let builder = ClientBuilder::new().api_key("aresa_id_secret").database("analytics");
+        assert_eq!(builder.credentials, Some(Credentials::ApiKey { key: "aresa_id_secret".to_string() }));
+        assert_eq!(builder.database.as_deref(), Some("analytics"));
     }
 
     #[test]
@@ -1,45 +1,50 @@
 //! AresaDB Client SDK
 //!
-//! Client library for connecting to AresaDB servers.
+//! Client library for connecting to AresaDB servers. A `Client` is cheap to
+//! clone; clones share a pool of connections, so concurrent requests from
+//! different tasks each get their own connection.
 
 mod connection;
 mod builder;
+mod batch;
+mod pool;
+mod retry;
+mod statement;
 
 pub use connection::Connection;
 pub use builder::ClientBuilder;
+pub use batch::Batch;
+pub use retry::{FailoverError, RetryPolicy};
+pub use statement::Statement;
 
-use anyhow::{Result, Context, bail};
+use anyhow::{Result, bail};
 use std::net::SocketAddr;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpStream;
+use std::sync::Arc;
 
-use crate::storage::{Node, Edge, Value};
-use crate::server::protocol::{Request, Response, ErrorCode};
-use crate::distributed::Compressor;
+use crate::auth::Credentials;
+use crate::rag::{ContextOptions, RetrievedContext};
+use crate::query::QueryStats;
+use crate::schema::{Migration, Schema};
+use crate::storage::{Node, Edge, Value, ChangeEvent, DistanceMetric, SimilarityResult};
+use crate::server::{Request, Response, SessionInfo};
+use pool::{Handshake, PoolConfig, SessionPool};
 
 /// AresaDB client for remote connections
+#[derive(Clone)]
 pub struct Client {
-    /// Server address
-    addr: SocketAddr,
-    /// Active TCP stream
-    stream: TcpStream,
-    /// Compressor for data transfer
-    compressor: Option<Compressor>,
+    /// Connections shared by every clone
+    pool: Arc<SessionPool>,
 }
 
 impl Client {
     /// Create a new client connected to the server
     pub async fn connect(addr: impl Into<SocketAddr>) -> Result<Self> {
-        let addr = addr.into();
-        let stream = TcpStream::connect(&addr)
-            .await
-            .context("Failed to connect to server")?;
+        ClientBuilder::new().connect(addr.into()).await
+    }
 
-        Ok(Self {
-            addr,
-            stream,
-            compressor: Some(Compressor::new()),
-        })
+    async fn with_pool(config: PoolConfig, handshake: Handshake) -> Result<Self> {
+        let pool = SessionPool::connect(config, handshake).await?;
+        Ok(Self { pool: Arc::new(pool) })
     }
 
     /// Create a client builder for configuration
@@ -47,13 +52,26 @@ impl Client {
         ClientBuilder::new()
     }
 
-    /// Get the server address
+    /// Get the address of the server requests currently go to
+    ///
+    /// This changes when the client fails over to another replica.
     pub fn addr(&self) -> SocketAddr {
-        self.addr
+        self.pool.addr()
+    }
+
+    /// Versions negotiated with the server (`None` if the server predates
+    /// version negotiation)
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.pool.server_info()
+    }
+
+    /// Pooled connections currently idle
+    pub fn idle_connections(&self) -> usize {
+        self.pool.idle_count()
     }
 
     /// Ping the server
-    pub async fn ping(&mut self) -> Result<()> {
+    pub async fn ping(&self) -> Result<()> {
         let response = self.send_request(Request::Ping).await?;
         match response {
             Response::Pong => Ok(()),
@@ -62,14 +80,114 @@ impl Client {
         }
     }
 
-    /// Disconnect from the server
-    pub async fn disconnect(mut self) -> Result<()> {
-        let _ = self.send_request(Request::Disconnect).await;
+    /// Authenticate the connection with a user name and password
+    pub async fn authenticate_password(&self, username: &str, password: &str) -> Result<String> {
+        self.authenticate(Credentials::Password {
+            username: username.to_string(),
+            password: password.to_string(),
+        }).await
+    }
+
+    /// Authenticate the connection with an API key
+    pub async fn authenticate_api_key(&self, key: &str) -> Result<String> {
+        self.authenticate(Credentials::ApiKey { key: key.to_string() }).await
+    }
+
+    /// Authenticate the client, returning the user name
+    ///
+    /// Connections opened later authenticate with the same credentials.
+    pub async fn authenticate(&self, credentials: Credentials) -> Result<String> {
+        let request = Request::Authenticate { credentials: credentials.clone() };
+        let response = self.pool
+            .send_and_update(&request, |handshake| handshake.credentials = Some(credentials))
+            .await?;
+        match response {
+            Response::Authenticated { username } => Ok(username),
+            Response::Error { message, .. } => bail!("Authentication failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Switch the client to another database hosted by the server
+    ///
+    /// Existing subscriptions stay on the database they were created on.
+    pub async fn use_database(&self, name: &str) -> Result<()> {
+        let request = Request::UseDatabase { name: name.to_string() };
+        let response = self.pool
+            .send_and_update(&request, |handshake| handshake.database = Some(name.to_string()))
+            .await?;
+        match response {
+            Response::DatabaseSelected { .. } => Ok(()),
+            Response::Error { message, .. } => bail!("Database selection failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Change a session setting (`default_limit`, `statement_timeout`,
+    /// `consistency` or `timezone`); `DEFAULT` restores the default
+    ///
+    /// Connections opened later apply the same settings.
+    pub async fn set(&self, name: &str, value: &str) -> Result<()> {
+        let request = Request::Set { name: name.to_string(), value: value.to_string() };
+        let response = self.pool
+            .send_and_update(&request, |handshake| handshake.set(name, value))
+            .await?;
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => bail!("Set failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Read one session setting, or all of them, as name and value pairs
+    pub async fn show(&self, name: Option<&str>) -> Result<Vec<(String, String)>> {
+        let response = self.send_request(Request::Show { name: name.map(str::to_string) }).await?;
+
+        match response {
+            Response::Settings(settings) => Ok(settings),
+            Response::Error { message, .. } => bail!("Show failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// List clients connected to the server over TCP (admin only)
+    pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>> {
+        let response = self.send_request(Request::ListSessions).await?;
+
+        match response {
+            Response::Sessions(sessions) => Ok(sessions),
+            Response::Error { message, .. } => bail!("List sessions failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Cancel the request a session is running (admin only)
+    pub async fn kill_query(&self, session_id: u64) -> Result<()> {
+        let response = self.send_request(Request::KillQuery { session_id }).await?;
+
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => bail!("Kill failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Start a batch of requests sent in a single round trip
+    pub fn batch(&self) -> Batch {
+        Batch::new(self.clone())
+    }
+
+    /// Disconnect idle connections from the server
+    ///
+    /// Connections in use by clones close when those requests finish and
+    /// the last clone is dropped.
+    pub async fn disconnect(self) -> Result<()> {
+        self.pool.close().await;
         Ok(())
     }
 
     /// Insert a new node
-    pub async fn insert_node(&mut self, node_type: &str, properties: serde_json::Value) -> Result<Node> {
+    pub async fn insert_node(&self, node_type: &str, properties: serde_json::Value) -> Result<Node> {
         let props = Value::from_json(properties)?;
         let response = self.send_request(Request::InsertNode {
             node_type: node_type.to_string(),
@@ -84,7 +202,7 @@ impl Client {
     }
 
     /// Get a node by ID
-    pub async fn get_node(&mut self, id: &str) -> Result<Option<Node>> {
+    pub async fn get_node(&self, id: &str) -> Result<Option<Node>> {
         let response = self.send_request(Request::GetNode {
             id: id.to_string(),
         }).await?;
@@ -97,7 +215,7 @@ impl Client {
     }
 
     /// Update a node
-    pub async fn update_node(&mut self, id: &str, properties: serde_json::Value) -> Result<Node> {
+    pub async fn update_node(&self, id: &str, properties: serde_json::Value) -> Result<Node> {
         let props = Value::from_json(properties)?;
         let response = self.send_request(Request::UpdateNode {
             id: id.to_string(),
@@ -112,7 +230,7 @@ impl Client {
     }
 
     /// Delete a node
-    pub async fn delete_node(&mut self, id: &str) -> Result<()> {
+    pub async fn delete_node(&self, id: &str) -> Result<()> {
         let response = self.send_request(Request::DeleteNode {
             id: id.to_string(),
         }).await?;
@@ -125,7 +243,7 @@ impl Client {
     }
 
     /// Get nodes by type
-    pub async fn get_nodes_by_type(&mut self, node_type: &str, limit: Option<usize>) -> Result<Vec<Node>> {
+    pub async fn get_nodes_by_type(&self, node_type: &str, limit: Option<usize>) -> Result<Vec<Node>> {
         let response = self.send_request(Request::GetNodesByType {
             node_type: node_type.to_string(),
             limit,
@@ -140,7 +258,7 @@ impl Client {
 
     /// Create an edge
     pub async fn create_edge(
-        &mut self,
+        &self,
         from_id: &str,
         to_id: &str,
         edge_type: &str,
@@ -162,7 +280,7 @@ impl Client {
     }
 
     /// Get edges from a node
-    pub async fn get_edges_from(&mut self, node_id: &str, edge_type: Option<&str>) -> Result<Vec<Edge>> {
+    pub async fn get_edges_from(&self, node_id: &str, edge_type: Option<&str>) -> Result<Vec<Edge>> {
         let response = self.send_request(Request::GetEdgesFrom {
             node_id: node_id.to_string(),
             edge_type: edge_type.map(String::from),
@@ -175,24 +293,172 @@ impl Client {
         }
     }
 
+    /// Insert a node with a vector embedding stored in `field`
+    pub async fn insert_with_embedding(
+        &self,
+        node_type: &str,
+        properties: serde_json::Value,
+        field: &str,
+        embedding: Vec<f32>,
+    ) -> Result<Node> {
+        let response = self.send_request(Request::InsertWithEmbedding {
+            node_type: node_type.to_string(),
+            properties: Value::from_json(properties)?,
+            field: field.to_string(),
+            embedding,
+        }).await?;
+
+        match response {
+            Response::Node(node) => Ok(node),
+            Response::Error { message, .. } => bail!("Insert failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Find the `k` nodes of a type whose embedding in `field` is closest
+    /// to `vector`, best match first
+    pub async fn similarity_search(
+        &self,
+        node_type: &str,
+        vector: &[f32],
+        field: &str,
+        k: usize,
+        metric: DistanceMetric,
+    ) -> Result<Vec<SimilarityResult>> {
+        let response = self.send_request(Request::SimilaritySearch {
+            node_type: node_type.to_string(),
+            vector: vector.to_vec(),
+            field: field.to_string(),
+            k,
+            metric,
+        }).await?;
+
+        match response {
+            Response::SimilarityResults(results) => Ok(results),
+            Response::Error { message, .. } => bail!("Similarity search failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Walk outgoing edges from a node up to `depth` hops, optionally
+    /// following only some edge types
+    pub async fn traverse(&self, start_id: &str, depth: u32, edge_types: Option<&[&str]>) -> Result<Traversal> {
+        let response = self.send_request(Request::Traverse {
+            start_id: start_id.to_string(),
+            depth,
+            edge_types: edge_types.map(|types| types.iter().map(|t| t.to_string()).collect()),
+        }).await?;
+
+        match response {
+            Response::TraversalResult { nodes, edges, depth } => Ok(Traversal { nodes, edges, depth }),
+            Response::Error { message, .. } => bail!("Traversal failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Retrieve RAG context for a query embedded as `vector`
+    pub async fn retrieve_context(
+        &self,
+        vector: &[f32],
+        query: &str,
+        options: ContextOptions,
+    ) -> Result<RetrievedContext> {
+        let response = self.send_request(Request::RetrieveContext {
+            vector: vector.to_vec(),
+            query: query.to_string(),
+            options,
+        }).await?;
+
+        match response {
+            Response::Context(context) => Ok(context),
+            Response::Error { message, .. } => bail!("Context retrieval failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
     /// Execute a SQL query
-    pub async fn query(&mut self, sql: &str, limit: Option<usize>) -> Result<QueryResult> {
+    pub async fn query(&self, sql: &str, limit: Option<usize>) -> Result<QueryResult> {
         let response = self.send_request(Request::Query {
             sql: sql.to_string(),
             limit,
         }).await?;
 
-        match response {
-            Response::QueryResult { columns, rows, rows_affected, execution_time_ms } => {
-                Ok(QueryResult { columns, rows, rows_affected, execution_time_ms })
+        match response.split_stats() {
+            (Response::QueryResult { columns, rows, rows_affected, execution_time_ms }, stats) => {
+                Ok(QueryResult { columns, rows, rows_affected, execution_time_ms, stats })
             }
-            Response::Error { message, .. } => bail!("Query failed: {}", message),
+            (Response::Error { message, .. }, _) => bail!("Query failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Prepare a SQL statement with `$1`-style or `?` placeholders for
+    /// repeated execution
+    pub async fn prepare(&self, sql: &str) -> Result<Statement> {
+        Statement::prepare(self.clone(), sql).await
+    }
+
+    /// Create or replace a schema; `fields` uses the CLI's syntax, e.g.
+    /// `name:string:required,age:int`
+    pub async fn create_schema(&self, name: &str, fields: &str) -> Result<Schema> {
+        let response = self.send_request(Request::CreateSchema {
+            name: name.to_string(),
+            fields: fields.to_string(),
+        }).await?;
+
+        match response {
+            Response::Schema(schema) => Ok(schema),
+            Response::Error { message, .. } => bail!("Create schema failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// List the schemas the client may read
+    pub async fn list_schemas(&self) -> Result<Vec<Schema>> {
+        let response = self.send_request(Request::ListSchemas).await?;
+
+        match response {
+            Response::Schemas(schemas) => Ok(schemas),
+            Response::Error { message, .. } => bail!("List schemas failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Drop a schema; without `force`, fails if nodes of the type exist
+    pub async fn drop_schema(&self, name: &str, force: bool) -> Result<()> {
+        let response = self.send_request(Request::DropSchema { name: name.to_string(), force }).await?;
+
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => bail!("Drop schema failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Run pending schema migrations (admin only), returning those applied
+    pub async fn migrate(&self) -> Result<Vec<Migration>> {
+        let response = self.send_request(Request::Migrate).await?;
+
+        match response {
+            Response::Migrations(migrations) => Ok(migrations),
+            Response::Error { message, .. } => bail!("Migrate failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Drop every plan the server has cached (admin only)
+    pub async fn flush_plan_cache(&self) -> Result<()> {
+        let response = self.send_request(Request::FlushPlanCache).await?;
+
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => bail!("Flush plan cache failed: {}", message),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Get database status
-    pub async fn status(&mut self) -> Result<DatabaseStatus> {
+    pub async fn status(&self) -> Result<DatabaseStatus> {
         let response = self.send_request(Request::Status).await?;
 
         match response {
@@ -205,7 +471,7 @@ impl Client {
     }
 
     /// Begin a transaction
-    pub async fn begin_transaction(&mut self) -> Result<u64> {
+    pub async fn begin_transaction(&self) -> Result<u64> {
         let response = self.send_request(Request::BeginTransaction).await?;
 
         match response {
@@ -216,7 +482,7 @@ impl Client {
     }
 
     /// Commit a transaction
-    pub async fn commit_transaction(&mut self, tx_id: u64) -> Result<()> {
+    pub async fn commit_transaction(&self, tx_id: u64) -> Result<()> {
         let response = self.send_request(Request::CommitTransaction { tx_id }).await?;
 
         match response {
@@ -227,7 +493,7 @@ impl Client {
     }
 
     /// Rollback a transaction
-    pub async fn rollback_transaction(&mut self, tx_id: u64) -> Result<()> {
+    pub async fn rollback_transaction(&self, tx_id: u64) -> Result<()> {
         let response = self.send_request(Request::RollbackTransaction { tx_id }).await?;
 
         match response {
@@ -237,44 +503,45 @@ impl Client {
         }
     }
 
+    /// Subscribe to changes for a node type and/or a `WHERE`-style predicate
+    ///
+    /// Returns the subscription ID; use `next_change` to receive events.
+    pub async fn subscribe(&self, node_type: Option<&str>, predicate: Option<&str>) -> Result<u64> {
+        let response = self.pool.send_subscriber(&Request::Subscribe {
+            node_type: node_type.map(String::from),
+            predicate: predicate.map(String::from),
+        }).await?;
+
+        match response {
+            Response::Subscribed { subscription_id } => Ok(subscription_id),
+            Response::Error { message, .. } => bail!("Subscribe failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Cancel a subscription
+    pub async fn unsubscribe(&self, subscription_id: u64) -> Result<()> {
+        let response = self.pool.send_subscriber(&Request::Unsubscribe { subscription_id }).await?;
+
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, .. } => bail!("Unsubscribe failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Wait for the next change notification
+    ///
+    /// Changes arrive on the connection that created the subscriptions,
+    /// which is shared by every clone.
+    pub async fn next_change(&self) -> Result<Change> {
+        self.pool.next_change().await
+    }
+
     // === Private methods ===
 
-    async fn send_request(&mut self, request: Request) -> Result<Response> {
-        // Serialize request
-        let body = bincode::serialize(&request)?;
-
-        // Compress if enabled
-        let body = if let Some(ref comp) = self.compressor {
-            comp.compress(&body)?
-        } else {
-            body
-        };
-
-        // Send length + body
-        let len = body.len() as u32;
-        self.stream.write_all(&len.to_le_bytes()).await?;
-        self.stream.write_all(&body).await?;
-        self.stream.flush().await?;
-
-        // Read response length
-        let mut len_buf = [0u8; 4];
-        self.stream.read_exact(&mut len_buf).await?;
-        let msg_len = u32::from_le_bytes(len_buf) as usize;
-
-        // Read response body
-        let mut body = vec![0u8; msg_len];
-        self.stream.read_exact(&mut body).await?;
-
-        // Decompress if enabled
-        let body = if let Some(ref comp) = self.compressor {
-            comp.decompress(&body)?
-        } else {
-            body
-        };
-
-        // Deserialize response
-        let response: Response = bincode::deserialize(&body)?;
-        Ok(response)
+    async fn send_request(&self, request: Request) -> Result<Response> {
+        self.pool.send(&request).await
     }
 }
 
@@ -285,6 +552,16 @@ pub struct QueryResult {
     pub rows: Vec<Vec<Value>>,
     pub rows_affected: u64,
     pub execution_time_ms: u64,
+    /// How the query was executed, once the session has `timing` on
+    pub stats: Option<QueryStats>,
+}
+
+/// Nodes and edges reached by a traversal
+#[derive(Debug, Clone)]
+pub struct Traversal {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    pub depth: u32,
 }
 
 /// Database status
@@ -296,6 +573,26 @@ pub struct DatabaseStatus {
     pub size_bytes: u64,
 }
 
+/// Server versions reported during the connection handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Protocol version both sides agreed to speak
+    pub protocol_version: u32,
+    /// On-disk format version of the server's databases
+    pub format_version: u32,
+    /// Server release
+    pub server_version: String,
+}
+
+/// Change notification for a subscription
+#[derive(Debug, Clone)]
+pub struct Change {
+    /// Subscription the change matched
+    pub subscription_id: u64,
+    /// The change itself
+    pub event: ChangeEvent,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,603 @@
+//! Client Connection Pool
+//!
+//! The wire protocol answers requests in order on each connection, so a
+//! shared client keeps a pool of connections and gives each in-flight
+//! request one to itself. Connection state (selected database,
+//! credentials and session settings) is replayed on every new connection;
+//! changing it bumps a generation so idle connections set up the old way
+//! are discarded.
+//! Subscriptions live on a dedicated connection that is never pooled or
+//! retried, since reconnecting would silently drop them. A reader task owns
+//! its read half, queueing change notifications on a channel and handing
+//! replies back to the request waiting for them.
+//!
+//! Pooled requests are retried according to the client's `RetryPolicy`.
+//! When the server becomes unreachable or refuses a write because it no
+//! longer leads, the client asks the configured replicas which one leads
+//! and moves there.
+
+use anyhow::{Result, Context, anyhow, bail};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::{Change, FailoverError, RetryPolicy, ServerInfo};
+use crate::auth::Credentials;
+use crate::distributed::Compressor;
+use crate::server::{ErrorCode, Request, Response, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+/// Connection settings shared by every pooled connection
+#[derive(Debug, Clone)]
+pub(crate) struct PoolConfig {
+    pub addr: SocketAddr,
+    /// Other replicas to ask for the leader
+    pub peers: Vec<SocketAddr>,
+    pub compression: bool,
+    pub connect_timeout: Duration,
+    pub max_connections: usize,
+    pub retry: RetryPolicy,
+    pub request_timeout: Option<Duration>,
+}
+
+/// Extra time the client waits past a request's deadline for the server's
+/// timeout response to arrive
+const DEADLINE_GRACE: Duration = Duration::from_millis(250);
+
+/// State replayed when a connection is opened
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Handshake {
+    pub database: Option<String>,
+    pub credentials: Option<Credentials>,
+    /// Session settings, in the order they were set
+    pub settings: Vec<(String, String)>,
+    generation: u64,
+}
+
+impl Handshake {
+    pub fn new(database: Option<String>, credentials: Option<Credentials>) -> Self {
+        Self { database, credentials, settings: Vec::new(), generation: 0 }
+    }
+
+    /// Record a setting, replacing an earlier value for the same name
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.settings.retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.settings.push((name.to_string(), value.to_string()));
+    }
+}
+
+/// A single framed connection to the server
+pub(crate) struct Session {
+    /// Server the connection goes to
+    addr: SocketAddr,
+    stream: TcpStream,
+    compressor: Option<Compressor>,
+    /// Change notifications received while waiting for a response
+    pending_changes: VecDeque<Change>,
+    /// Handshake generation this connection was set up with
+    generation: u64,
+    /// Negotiated version details; `None` for servers predating negotiation
+    server: Option<ServerInfo>,
+}
+
+impl Session {
+    /// Connect and replay the handshake
+    async fn open(config: &PoolConfig, addr: SocketAddr, handshake: &Handshake) -> Result<Self> {
+        let stream = tokio::time::timeout(config.connect_timeout, TcpStream::connect(addr))
+            .await
+            .context("Connection timeout")?
+            .context("Failed to connect to server")?;
+
+        let mut session = Self {
+            addr,
+            stream,
+            compressor: config.compression.then(Compressor::new),
+            pending_changes: VecDeque::new(),
+            generation: handshake.generation,
+            server: None,
+        };
+
+        let hello = Request::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            format_version: crate::FORMAT_VERSION,
+            client_version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        match session.send_request(&hello).await? {
+            Response::Welcome { protocol_version, .. } if protocol_version < MIN_PROTOCOL_VERSION => {
+                bail!(
+                    "Protocol version mismatch: server speaks version {}, client needs {} to {}",
+                    protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+                );
+            }
+            Response::Welcome { protocol_version, format_version, server_version } => {
+                session.server = Some(ServerInfo { protocol_version, format_version, server_version });
+            }
+            Response::Error { code: ErrorCode::VersionMismatch, message } => bail!("{}", message),
+            // Servers predating negotiation can't parse Hello; talk to them unversioned
+            Response::Error { .. } => {
+                warn!("Server at {} doesn't negotiate protocol versions; continuing unversioned", addr);
+            }
+            _ => bail!("Unexpected response"),
+        }
+
+        // Databases may only be selected once authenticated
+        if let Some(ref credentials) = handshake.credentials {
+            let request = Request::Authenticate { credentials: credentials.clone() };
+            if let Response::Error { message, .. } = session.send_request(&request).await? {
+                bail!("Authentication failed: {}", message);
+            }
+        }
+
+        if let Some(ref database) = handshake.database {
+            let request = Request::UseDatabase { name: database.clone() };
+            if let Response::Error { message, .. } = session.send_request(&request).await? {
+                bail!("Database selection failed: {}", message);
+            }
+        }
+
+        for (name, value) in &handshake.settings {
+            let request = Request::Set { name: name.clone(), value: value.clone() };
+            if let Response::Error { message, .. } = session.send_request(&request).await? {
+                bail!("Setting {} failed: {}", name, message);
+            }
+        }
+
+        Ok(session)
+    }
+
+    /// Send a request and wait for its response
+    pub async fn send_request(&mut self, request: &Request) -> Result<Response> {
+        write_frame(&mut self.stream, self.compressor.as_ref(), request).await?;
+
+        // Change notifications may arrive ahead of the reply
+        let pending = &mut self.pending_changes;
+        read_reply(&mut self.stream, self.compressor.as_ref(), self.server.as_ref(), |change| {
+            pending.push_back(change);
+        }).await
+    }
+
+    /// Hand the connection to a reader task for carrying subscriptions
+    fn into_subscriber(self) -> Subscriber {
+        let (mut reader, writer) = self.stream.into_split();
+        let (change_tx, change_rx) = mpsc::unbounded_channel();
+        let (reply_tx, replies) = mpsc::unbounded_channel();
+        for change in self.pending_changes {
+            let _ = change_tx.send(change);
+        }
+
+        let compressor = self.compressor.clone();
+        let server = self.server;
+        let task = tokio::spawn(async move {
+            loop {
+                let reply = read_reply(&mut reader, compressor.as_ref(), server.as_ref(), |change| {
+                    let _ = change_tx.send(change);
+                }).await;
+                let failed = reply.is_err();
+                if reply_tx.send(reply).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Subscriber {
+            writer,
+            compressor: self.compressor,
+            replies,
+            changes: Arc::new(tokio::sync::Mutex::new(change_rx)),
+            task,
+        }
+    }
+}
+
+/// The subscription connection
+struct Subscriber {
+    writer: OwnedWriteHalf,
+    compressor: Option<Compressor>,
+    /// Replies to requests, in order, from the reader task
+    replies: mpsc::UnboundedReceiver<Result<Response>>,
+    /// Change notifications from the reader task; closed when the
+    /// connection fails
+    changes: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<Change>>>,
+    task: JoinHandle<()>,
+}
+
+impl Subscriber {
+    /// Send a request and wait for its reply
+    async fn send_request(&mut self, request: &Request) -> Result<Response> {
+        write_frame(&mut self.writer, self.compressor.as_ref(), request).await?;
+        match self.replies.recv().await {
+            Some(reply) => reply,
+            None => bail!("Subscription connection closed"),
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Write one length-prefixed request frame
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    compressor: Option<&Compressor>,
+    request: &Request,
+) -> Result<()> {
+    // Serialize request
+    let body = bincode::serialize(request)?;
+
+    // Compress if enabled
+    let body = if let Some(comp) = compressor {
+        comp.compress(&body)?
+    } else {
+        body
+    };
+
+    // Send length + body
+    let len = body.len() as u32;
+    writer.write_all(&len.to_le_bytes()).await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read the reply to a request, passing change notifications that arrive
+/// ahead of it to `on_change` and collecting the frames of a chunked reply
+async fn read_reply<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    compressor: Option<&Compressor>,
+    server: Option<&ServerInfo>,
+    mut on_change: impl FnMut(Change),
+) -> Result<Response> {
+    let response = loop {
+        match read_frame(reader, compressor, server).await? {
+            Response::Change { subscription_id, event } => on_change(Change { subscription_id, event: *event }),
+            response => break response,
+        }
+    };
+
+    let Response::Chunk { response, mut more } = response else {
+        return Ok(response);
+    };
+
+    let mut whole = *response;
+    while more {
+        match read_frame(reader, compressor, server).await? {
+            Response::Chunk { response, more: next } => {
+                if !whole.extend_from_chunk(*response) {
+                    bail!("Response chunk doesn't match the response it continues");
+                }
+                more = next;
+            }
+            _ => bail!("Expected a response chunk"),
+        }
+    }
+    Ok(whole)
+}
+
+/// Read one length-prefixed response frame
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    compressor: Option<&Compressor>,
+    server: Option<&ServerInfo>,
+) -> Result<Response> {
+    // Read response length
+    let mut len_buf = [0u8; 4];
+    reader.read_exact(&mut len_buf).await?;
+    let msg_len = u32::from_le_bytes(len_buf) as usize;
+
+    // Read response body
+    let mut body = vec![0u8; msg_len];
+    reader.read_exact(&mut body).await?;
+
+    // Decompress if enabled
+    let body = if let Some(comp) = compressor {
+        comp.decompress(&body)?
+    } else {
+        body
+    };
+
+    // Deserialize response
+    let response: Response = bincode::deserialize(&body).with_context(|| match server {
+        Some(server) => format!(
+            "Failed to decode response from server version {} (protocol {})",
+            server.server_version, server.protocol_version,
+        ),
+        None => "Failed to decode response (server may speak an incompatible protocol version)".to_string(),
+    })?;
+    Ok(response)
+}
+
+/// Whether an error came from the connection rather than the request
+fn is_connection_error(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<std::io::Error>() || cause.is::<tokio::time::error::Elapsed>()
+    })
+}
+
+/// Ask each candidate in turn which replica leads, returning the first answer
+async fn find_leader(config: &PoolConfig, candidates: impl IntoIterator<Item = SocketAddr>) -> Option<SocketAddr> {
+    for candidate in candidates {
+        let response = match Session::open(config, candidate, &Handshake::default()).await {
+            Ok(mut session) => session.send_request(&Request::WhoIsLeader).await,
+            Err(e) => Err(e),
+        };
+
+        match response {
+            Ok(Response::Leader { is_leader: true, .. }) => return Some(candidate),
+            Ok(Response::Leader { leader: Some(leader), .. }) => match leader.parse() {
+                Ok(leader) => return Some(leader),
+                Err(_) => warn!("Replica at {} reported an invalid leader address: {}", candidate, leader),
+            },
+            Ok(_) => debug!("Replica at {} doesn't know the leader", candidate),
+            Err(e) => debug!("Replica at {} didn't answer: {}", candidate, e),
+        }
+    }
+    None
+}
+
+/// Pool of connections to the leading server
+pub(crate) struct SessionPool {
+    config: PoolConfig,
+    /// Server requests currently go to
+    leader: Mutex<SocketAddr>,
+    handshake: Mutex<Handshake>,
+    /// Idle connections, most recently used last
+    idle: Mutex<Vec<Session>>,
+    /// Bounds the number of open pooled connections
+    slots: Semaphore,
+    /// Connection carrying subscriptions; held while a request on it
+    /// waits for its reply, never while waiting for changes
+    subscriber: tokio::sync::Mutex<Option<Subscriber>>,
+    /// Version details from the first connection
+    server: Option<ServerInfo>,
+}
+
+impl SessionPool {
+    /// Create a pool and open its first connection, so connection and
+    /// handshake errors surface immediately
+    pub async fn connect(config: PoolConfig, handshake: Handshake) -> Result<Self> {
+        let first = match Session::open(&config, config.addr, &handshake).await {
+            Ok(first) => first,
+            // The server may be down for good with another replica leading
+            Err(e) if is_connection_error(&e) && !config.peers.is_empty() => {
+                let Some(leader) = find_leader(&config, config.peers.iter().copied()).await else {
+                    return Err(e);
+                };
+                Session::open(&config, leader, &handshake).await?
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(Self {
+            leader: Mutex::new(first.addr),
+            server: first.server.clone(),
+            slots: Semaphore::new(config.max_connections.max(1)),
+            config,
+            handshake: Mutex::new(handshake),
+            idle: Mutex::new(vec![first]),
+            subscriber: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// Address of the server requests currently go to
+    pub fn addr(&self) -> SocketAddr {
+        *self.leader.lock()
+    }
+
+    /// Version details negotiated with the server
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server.as_ref()
+    }
+
+    /// Connections currently idle in the pool
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().len()
+    }
+
+    /// Send a request on a pooled connection
+    pub async fn send(&self, request: &Request) -> Result<Response> {
+        self.send_then(request, |_| None).await
+    }
+
+    /// Send a request that changes connection state, replaying the change
+    /// on connections opened later
+    ///
+    /// `update` runs only if the server accepted the request.
+    pub async fn send_and_update(&self, request: &Request, update: impl FnOnce(&mut Handshake)) -> Result<Response> {
+        self.send_then(request, |handshake| {
+            update(handshake);
+            handshake.generation += 1;
+            Some(handshake.generation)
+        }).await
+    }
+
+    async fn send_then(
+        &self,
+        request: &Request,
+        update: impl FnOnce(&mut Handshake) -> Option<u64>,
+    ) -> Result<Response> {
+        let _slot = self.slots.acquire().await.context("Client pool closed")?;
+        let retry = &self.config.retry;
+        let mut attempt = 0;
+
+        loop {
+            let error = match self.checkout().await {
+                Ok(mut session) => match self.exchange(&mut session, request).await {
+                    Ok(response) => {
+                        let not_leader = matches!(response, Response::Error { code: ErrorCode::NotLeader, .. });
+                        let transient = matches!(response, Response::Error { code, .. } if code.is_transient());
+                        if not_leader && attempt < retry.max_retries && self.failover(true).await {
+                            // The follower refused the write without running it
+                            None
+                        } else if !transient || attempt >= retry.max_retries {
+                            if !response.is_error() {
+                                if let Some(generation) = update(&mut self.handshake.lock()) {
+                                    session.generation = generation;
+                                }
+                            }
+                            self.checkin(session);
+                            return Ok(response);
+                        } else {
+                            self.checkin(session);
+                            None
+                        }
+                    }
+                    // A failed send drops the connection rather than returning
+                    // it, since a response may still be in flight. Idle
+                    // connections likely broke the same way, so drop them too.
+                    Err(e) => {
+                        self.idle.lock().clear();
+                        Some((e, request.is_idempotent()))
+                    }
+                },
+                // Nothing was sent, so any request may be retried
+                Err(e) => Some((e, true)),
+            };
+
+            if let Some((e, safe)) = error {
+                if !is_connection_error(&e) || attempt >= retry.max_retries {
+                    return Err(e);
+                }
+
+                // The server may be gone for good; see if another replica leads now
+                let failed_over = self.failover(false).await;
+                if !safe {
+                    return Err(if failed_over {
+                        FailoverError { operation: request.operation(), leader: self.addr() }.into()
+                    } else {
+                        e
+                    });
+                }
+                debug!("Retrying {} after error: {}", request.operation(), e);
+            }
+
+            tokio::time::sleep(retry.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send a request on a connection, applying the request timeout
+    ///
+    /// The server is told the deadline so it can stop working on the
+    /// request; the client stops waiting shortly after it.
+    async fn exchange(&self, session: &mut Session, request: &Request) -> Result<Response> {
+        let limit = match self.config.request_timeout {
+            Some(limit) if !request.is_connection_state() => limit,
+            _ => return session.send_request(request).await,
+        };
+
+        let request = Request::WithDeadline {
+            timeout_ms: limit.as_millis() as u64,
+            request: Box::new(request.clone()),
+        };
+        match tokio::time::timeout(limit + DEADLINE_GRACE, session.send_request(&request)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("Request timed out after {} ms", limit.as_millis())),
+        }
+    }
+
+    /// Ask the replicas which one leads and move there
+    ///
+    /// Returns `true` if the client switched servers. The current server is
+    /// asked first when it is still reachable.
+    async fn failover(&self, current_reachable: bool) -> bool {
+        if self.config.peers.is_empty() {
+            return false;
+        }
+
+        let current = self.addr();
+        let others = std::iter::once(self.config.addr)
+            .chain(self.config.peers.iter().copied())
+            .filter(|&addr| addr != current);
+        let candidates = current_reachable.then_some(current).into_iter().chain(others);
+
+        match find_leader(&self.config, candidates).await {
+            Some(leader) if leader != current => {
+                warn!("Failing over from {} to the leader at {}", current, leader);
+                *self.leader.lock() = leader;
+                self.idle.lock().clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take an idle connection set up with the current handshake, or open one
+    async fn checkout(&self) -> Result<Session> {
+        let handshake = self.handshake.lock().clone();
+        let leader = self.addr();
+
+        loop {
+            let Some(session) = self.idle.lock().pop() else { break };
+            if session.generation == handshake.generation && session.addr == leader {
+                return Ok(session);
+            }
+        }
+
+        Session::open(&self.config, leader, &handshake).await
+    }
+
+    fn checkin(&self, session: Session) {
+        if session.generation == self.handshake.lock().generation && session.addr == self.addr() {
+            self.idle.lock().push(session);
+        }
+    }
+
+    /// Send a request on the subscription connection, opening it if needed
+    pub async fn send_subscriber(&self, request: &Request) -> Result<Response> {
+        let mut subscriber = self.subscriber.lock().await;
+        if subscriber.is_none() {
+            let handshake = self.handshake.lock().clone();
+            let session = Session::open(&self.config, self.addr(), &handshake).await?;
+            *subscriber = Some(session.into_subscriber());
+        }
+
+        let connection = subscriber.as_mut().expect("subscriber connection is open");
+        let result = connection.send_request(request).await;
+        if result.is_err() {
+            *subscriber = None;
+        }
+        result
+    }
+
+    /// Wait for the next change on the subscription connection
+    pub async fn next_change(&self) -> Result<Change> {
+        let changes = match *self.subscriber.lock().await {
+            Some(ref subscriber) => Arc::clone(&subscriber.changes),
+            None => bail!("No active subscriptions"),
+        };
+
+        let change = changes.lock().await.recv().await;
+        match change {
+            Some(change) => Ok(change),
+            None => {
+                // Drop the failed connection unless it was already replaced
+                let mut subscriber = self.subscriber.lock().await;
+                if subscriber.as_ref().is_some_and(|s| Arc::ptr_eq(&s.changes, &changes)) {
+                    *subscriber = None;
+                }
+                bail!("Subscription connection closed")
+            }
+        }
+    }
+
+    /// Say goodbye on and close every idle connection
+    pub async fn close(&self) {
+        let sessions: Vec<_> = self.idle.lock().drain(..).collect();
+        for mut session in sessions {
+            let _ = session.send_request(&Request::Disconnect).await;
+        }
+        if let Some(mut session) = self.subscriber.lock().await.take() {
+            let _ = session.send_request(&Request::Disconnect).await;
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Retry Policy
+//!
+//! How the client recovers from broken connections and transient server
+//! errors. Requests the server rejected without running them (overload and
+//! rate limits) and requests that never reached a connection are always
+//! safe to retry; requests lost on a broken connection are retried only if
+//! they are idempotent.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Retry settings with exponential backoff
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries
+    pub max_backoff: Duration,
+    /// Factor the delay grows by after each retry
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Default::default() }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.max(1.0).powi(attempt.min(i32::MAX as u32) as i32);
+        self.initial_backoff.mul_f64(factor).min(self.max_backoff)
+    }
+}
+
+/// Error for a request lost when the server became unreachable and the
+/// client moved to a new leader
+///
+/// The old server may or may not have applied the request, and it isn't
+/// idempotent, so the client doesn't resend it. Sending it again goes to
+/// the new leader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverError {
+    /// Operation that was interrupted
+    pub operation: &'static str,
+    /// Server the client moved to
+    pub leader: SocketAddr,
+}
+
+impl std::fmt::Display for FailoverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} was interrupted by a failover to {} and may not have been applied; retry it",
+            self.operation, self.leader,
+        )
+    }
+}
+
+impl std::error::Error for FailoverError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(50));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(20), Duration::from_secs(2));
+        assert_eq!(RetryPolicy::none().max_retries, 0);
+    }
+}
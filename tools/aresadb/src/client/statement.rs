@@ -0,0 +1,95 @@
+//! Prepared Statements
+//!
+//! A statement is parsed and planned once on the server and then executed
+//! with new parameters each time. The server may evict statements or the
+//! client may fail over to a replica that never saw it; either way the
+//! statement is prepared again transparently.
+
+use anyhow::{Result, bail};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{Client, QueryResult};
+use crate::server::{ErrorCode, Request, Response};
+use crate::storage::Value;
+
+/// Statement prepared on the server, created with `Client::prepare`
+pub struct Statement {
+    client: Client,
+    sql: String,
+    id: AtomicU64,
+    param_count: usize,
+}
+
+impl Statement {
+    pub(crate) async fn prepare(client: Client, sql: &str) -> Result<Self> {
+        let (id, param_count) = prepare(&client, sql).await?;
+        Ok(Self {
+            client,
+            sql: sql.to_string(),
+            id: AtomicU64::new(id),
+            param_count,
+        })
+    }
+
+    /// SQL text of the statement
+    pub fn sql(&self) -> &str {
+        &self.sql
+    }
+
+    /// Number of parameters `execute` expects
+    pub fn param_count(&self) -> usize {
+        self.param_count
+    }
+
+    /// Run the statement with one value per placeholder, in order
+    pub async fn execute(&self, params: &[serde_json::Value], limit: Option<usize>) -> Result<QueryResult> {
+        if params.len() != self.param_count {
+            bail!("Statement takes {} parameters, got {}", self.param_count, params.len());
+        }
+        let params = params.iter().cloned().map(Value::from_json).collect::<Result<Vec<_>>>()?;
+
+        let mut response = self.send_execute(&params, limit).await?;
+        if matches!(response, Response::Error { code: ErrorCode::StatementNotFound, .. }) {
+            let (id, _) = prepare(&self.client, &self.sql).await?;
+            self.id.store(id, Ordering::Relaxed);
+            response = self.send_execute(&params, limit).await?;
+        }
+
+        match response.split_stats() {
+            (Response::QueryResult { columns, rows, rows_affected, execution_time_ms }, stats) => {
+                Ok(QueryResult { columns, rows, rows_affected, execution_time_ms, stats })
+            }
+            (Response::Error { message, .. }, _) => bail!("Execute failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Drop the statement on the server
+    ///
+    /// Statements are shared, so this also drops it for other clients
+    /// that prepared the same SQL; they prepare it again on next use.
+    pub async fn close(self) -> Result<()> {
+        let statement_id = self.id.load(Ordering::Relaxed);
+        match self.client.send_request(Request::Deallocate { statement_id }).await? {
+            Response::Ok | Response::Error { code: ErrorCode::StatementNotFound, .. } => Ok(()),
+            Response::Error { message, .. } => bail!("Deallocate failed: {}", message),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    async fn send_execute(&self, params: &[Value], limit: Option<usize>) -> Result<Response> {
+        self.client.send_request(Request::Execute {
+            statement_id: self.id.load(Ordering::Relaxed),
+            params: params.to_vec(),
+            limit,
+        }).await
+    }
+}
+
+async fn prepare(client: &Client, sql: &str) -> Result<(u64, usize)> {
+    match client.send_request(Request::Prepare { sql: sql.to_string() }).await? {
+        Response::Prepared { statement_id, param_count } => Ok((statement_id, param_count)),
+        Response::Error { message, .. } => bail!("Prepare failed: {}", message),
+        _ => bail!("Unexpected response"),
+    }
+}
@@ -18,7 +18,7 @@ mod streaming;
 pub use bloom::{BloomFilter, CountingBloomFilter};
 pub use compression::{Compressor, CompressionStats};
 pub use shard::{ShardManager, ShardConfig, Shard};
-pub use wal::{WriteAheadLog, WalEntry, WalEntryType};
+pub use wal::{WriteAheadLog, WalEntry, WalEntryType, WalScan};
 pub use replication::{ReplicaSet, ReplicaConfig, ReplicaState};
 pub use streaming::{ResultStream, StreamSender, Cursor};
 
@@ -0,0 +1,181 @@
+//! Error codes
+//!
+//! Stable codes for failures, so scripts can branch on what went wrong
+//! rather than on message text. Code that knows why it failed returns a
+//! [`CodedError`]; anything else is classified from the error chain by
+//! [`classify`]. With `--format json` the CLI prints errors as
+//! [`ErrorReport`] objects, and the exit code follows the error code.
+
+use serde::Serialize;
+use std::io;
+
+/// What kind of failure an error is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Any other failure
+    Error,
+    /// A query or input file could not be parsed
+    ParseError,
+    /// A node, edge, schema, file or other object does not exist
+    NotFound,
+    /// The object already exists, or changed underneath the operation
+    Conflict,
+    /// The operation ran out of time
+    Timeout,
+    /// A write broke a schema constraint
+    ConstraintViolation,
+}
+
+impl ErrorCode {
+    /// Process exit code; 2 is left to command-line usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Error => 1,
+            Self::ParseError => 3,
+            Self::NotFound => 4,
+            Self::Conflict => 5,
+            Self::Timeout => 6,
+            Self::ConstraintViolation => 7,
+        }
+    }
+}
+
+/// An error whose code is known where it is raised
+#[derive(Debug)]
+pub struct CodedError {
+    /// What kind of failure this is
+    pub code: ErrorCode,
+    message: String,
+}
+
+impl CodedError {
+    /// Create an error with a code
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    /// Shorthand for a `not_found` error
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    /// Shorthand for a `conflict` error
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+}
+
+impl std::fmt::Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// The code for an error, from the first cause in its chain that has one
+pub fn classify(error: &anyhow::Error) -> ErrorCode {
+    for cause in error.chain() {
+        if let Some(coded) = cause.downcast_ref::<CodedError>() {
+            return coded.code;
+        }
+        if cause.is::<sqlparser::parser::ParserError>() || cause.is::<toml::de::Error>() {
+            return ErrorCode::ParseError;
+        }
+        if let Some(e) = cause.downcast_ref::<serde_json::Error>() {
+            if e.is_syntax() || e.is_eof() {
+                return ErrorCode::ParseError;
+            }
+        }
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return ErrorCode::Timeout;
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::NotFound => return ErrorCode::NotFound,
+                io::ErrorKind::AlreadyExists => return ErrorCode::Conflict,
+                io::ErrorKind::TimedOut => return ErrorCode::Timeout,
+                _ => {}
+            }
+        }
+    }
+
+    // Most errors are ad hoc messages; these phrasings are used consistently
+    let message = format!("{:#}", error).to_lowercase();
+    if message.contains("not found") || message.contains("does not exist") || message.contains("no such file") {
+        ErrorCode::NotFound
+    } else if message.contains("already exists") {
+        ErrorCode::Conflict
+    } else if message.contains("timed out") || message.contains("deadline") {
+        ErrorCode::Timeout
+    } else {
+        ErrorCode::Error
+    }
+}
+
+/// An error as printed with `--format json`
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorReport {
+    /// Stable error code
+    pub code: ErrorCode,
+    /// Top-level message
+    pub message: String,
+    /// Messages of the underlying causes, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub causes: Vec<String>,
+    /// Exit code of the process
+    pub exit_code: i32,
+}
+
+impl ErrorReport {
+    /// Report an error
+    pub fn new(error: &anyhow::Error) -> Self {
+        let code = classify(error);
+        Self {
+            code,
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|c| c.to_string()).collect(),
+            exit_code: code.exit_code(),
+        }
+    }
+
+    /// The report as `{"error": {...}}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({ "error": self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_classify() {
+        let parse = crate::query::QueryParser::new().parse("SELEC * FROM users").unwrap_err();
+        assert_eq!(classify(&parse), ErrorCode::ParseError);
+
+        let missing = anyhow::Error::from(CodedError::not_found("Node not found: n1")).context("traverse");
+        assert_eq!(classify(&missing), ErrorCode::NotFound);
+
+        let io = std::fs::read("/nonexistent/aresadb").context("reading script");
+        assert_eq!(classify(&io.unwrap_err()), ErrorCode::NotFound);
+
+        assert_eq!(classify(&anyhow::anyhow!("User already exists: ada")), ErrorCode::Conflict);
+        assert_eq!(classify(&anyhow::anyhow!("disk full")), ErrorCode::Error);
+
+        let report = ErrorReport::new(&missing);
+        assert_eq!(
+            report.to_json(),
+            serde_json::json!({
+                "error": {
+                    "code": "not_found",
+                    "message": "traverse",
+                    "causes": ["Node not found: n1"],
+                    "exit_code": 4,
+                }
+            })
+        );
+    }
+}
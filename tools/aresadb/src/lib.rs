@@ -92,12 +92,24 @@ pub mod schema;
 pub mod output;
 pub mod cli;
 
+// Server authentication (user store is managed by the CLI)
+pub mod auth;
+
 // V2: Distributed modules
 pub mod distributed;
 
 // RAG (Retrieval-Augmented Generation) utilities
 pub mod rag;
 
+// Progress reporting for long-running operations
+pub mod progress;
+
+// Stable error codes for scripts
+pub mod error;
+
+// Trace export to OpenTelemetry collectors
+pub mod telemetry;
+
 // V2: Server/Client modules (behind feature flags)
 #[cfg(feature = "server")]
 pub mod server;
@@ -108,16 +120,19 @@ pub mod client;
 // Re-exports for convenience
 pub use storage::{
     Database, DatabaseConfig, DatabaseStatus,
-    Node, Edge, NodeId, EdgeId, Value, Timestamp,
-    LocalStorage, BucketStorage, CacheLayer,
-    GraphView, KvView, SyncStats,
+    Node, Edge, NodeId, EdgeId, Value, Timestamp, ArchivedNode, ArchivedValue,
+    LocalStorage, BucketStorage, CacheLayer, CacheConfig, CachePolicy, CacheStats,
+    GraphView, KvView, SyncStats, SyncPreview, PendingFile,
     ParallelExecutor, ParallelTraversalResult, SnapshotReader,
-    VectorIndex, IndexStats,
+    VectorIndex, VectorIndexSpec, VectorDimension, IndexStats,
+    FieldIndex, FieldIndexKind, FieldIndexSpec, FieldIndexStats,
 };
 
+pub use progress::{Progress, ProgressUnit};
+
 pub use query::{
-    QueryParser, QueryEngine, QueryResult, TraversalResult,
-    ParsedQuery, QueryOperation, Condition, Operator, OrderBy,
+    QueryParser, QueryEngine, QueryResult, QueryStats, TraversalResult,
+    ParsedQuery, QueryOperation, Condition, Operator, OrderBy, OnConflict,
 };
 
 pub use schema::{
@@ -129,16 +144,17 @@ pub use distributed::{
     BloomFilter, CountingBloomFilter,
     Compressor, CompressionStats,
     ShardManager, ShardConfig,
-    WriteAheadLog, WalEntry, WalEntryType,
+    WriteAheadLog, WalEntry, WalEntryType, WalScan,
     ReplicaSet, ReplicaConfig, ReplicaState,
     ResultStream, StreamSender, Cursor,
 };
 
 pub use rag::{
     Chunker, ChunkStrategy, DocumentChunk,
-    ContextRetriever, RetrievedContext, ContextChunk,
+    ContextOptions, ContextRetriever, RetrievedContext, ContextChunk, DocumentStore,
     EmbeddingManager, EmbeddingProvider, OpenAIModel,
-    HybridSearch, HybridSearchConfig, HybridSearchResult,
+    HybridSearch, HybridSearchConfig, HybridSearchResult, FusionStrategy, TextAnalyzer,
+    MetadataFilter, Reranker, TokenCounter, ConversationMemory,
 };
 
 #[cfg(feature = "server")]
@@ -148,7 +164,10 @@ pub use server::{Server, ServerConfig};
 pub use client::{Client, ClientBuilder};
 
 /// Database format version for compatibility checking
-pub const FORMAT_VERSION: u32 = 1;
+///
+/// Version 2 stores nodes as rkyv archives; version 1 JSON nodes are still
+/// read and are rewritten as archives when next saved.
+pub const FORMAT_VERSION: u32 = 2;
 
 /// Maximum number of nodes to return in a single query by default
 pub const DEFAULT_QUERY_LIMIT: usize = 1000;
@@ -183,7 +202,7 @@ mod tests {
 
     #[test]
     fn test_version() {
-        assert_eq!(FORMAT_VERSION, 1);
+        assert_eq!(FORMAT_VERSION, 2);
     }
 
     #[test]
@@ -0,0 +1,201 @@
+//! CSV Renderer
+//!
+//! RFC 4180 CSV: fields holding the delimiter, quotes or line breaks are
+//! quoted, quotes inside them are doubled. The delimiter and header row are
+//! configurable, and `--delimiter`/`--no-header` set the defaults for a run.
+
+use anyhow::Result;
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use crate::query::QueryResult;
+use crate::storage::Value;
+
+static DEFAULTS: OnceLock<CsvOptions> = OnceLock::new();
+
+/// How CSV is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// Field separator
+    pub delimiter: char,
+    /// Whether to write a header row
+    pub header: bool,
+}
+
+impl CsvOptions {
+    /// Options for this run, as set by [`set_defaults`]
+    pub fn defaults() -> Self {
+        DEFAULTS.get().copied().unwrap_or_default()
+    }
+
+    /// Use a different field separator
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write or leave out the header row
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self { delimiter: ',', header: true }
+    }
+}
+
+/// Set the options used by every CSV renderer; only the first call counts
+pub fn set_defaults(options: CsvOptions) {
+    let _ = DEFAULTS.set(options);
+}
+
+/// CSV renderer writing to stdout or any other writer
+pub struct CsvRenderer<W: Write = io::Stdout> {
+    out: W,
+    options: CsvOptions,
+}
+
+impl CsvRenderer {
+    /// Create a renderer writing to stdout with the run's options
+    pub fn new() -> Self {
+        Self::to_writer(io::stdout(), CsvOptions::defaults())
+    }
+}
+
+impl Default for CsvRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> CsvRenderer<W> {
+    /// Create a renderer writing to `out`
+    pub fn to_writer(out: W, options: CsvOptions) -> Self {
+        Self { out, options }
+    }
+
+    /// Write the header row, unless headers are turned off
+    pub fn write_header<S: AsRef<str>>(&mut self, columns: &[S]) -> Result<()> {
+        if self.options.header {
+            self.write_record(columns)?;
+        }
+        Ok(())
+    }
+
+    /// Write one row of cells
+    pub fn write_record<I, S>(&mut self, cells: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.write_record_quoting(cells.into_iter().map(|cell| (cell, false)))
+    }
+
+    /// Write one row of cells, quoting those paired with `true` even when
+    /// they need no quotes, so readers can tell them from numbers
+    pub fn write_record_quoting<I, S>(&mut self, cells: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (S, bool)>,
+        S: AsRef<str>,
+    {
+        let mut separator = [0; 4];
+        let separator = self.options.delimiter.encode_utf8(&mut separator).as_bytes();
+        for (i, (cell, always)) in cells.into_iter().enumerate() {
+            if i > 0 {
+                self.out.write_all(separator)?;
+            }
+            let cell = cell.as_ref();
+            if always {
+                write!(self.out, "\"{}\"", cell.replace('"', "\"\""))?;
+            } else {
+                self.out.write_all(quote(cell, self.options.delimiter).as_bytes())?;
+            }
+        }
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Write a header row and every row of a result
+    pub fn render(&mut self, results: &QueryResult) -> Result<()> {
+        self.write_header(&results.columns)?;
+        for row in &results.rows {
+            self.write_record(row.iter().map(format_value))?;
+        }
+        self.flush()
+    }
+
+    /// Flush buffered rows
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// The underlying writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Quote a field if it holds the delimiter, a quote or a line break
+pub fn quote(cell: &str, delimiter: char) -> Cow<'_, str> {
+    if cell.contains([delimiter, '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", cell.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+/// Format a value as a field; NULL is empty, strings are unquoted, nested values are JSON
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Bytes(_) | Value::Vector(_) | Value::Array(_) | Value::Object(_) => value.to_json().to_string(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(quote("plain", ','), "plain");
+        assert_eq!(quote("a,b", ','), "\"a,b\"");
+        assert_eq!(quote("a,b", ';'), "a,b");
+        assert_eq!(quote("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote("two\nlines", ','), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_render() {
+        let result = QueryResult {
+            columns: vec!["name".to_string(), "tags".to_string(), "age".to_string()],
+            rows: vec![
+                vec![Value::String("Lovelace, Ada".to_string()), Value::Array(vec![Value::Int(1)]), Value::Int(36)],
+                vec![Value::String("Bob".to_string()), Value::Null, Value::Float(1.5)],
+            ],
+            ..QueryResult::empty()
+        };
+
+        let mut renderer = CsvRenderer::to_writer(Vec::new(), CsvOptions::default());
+        renderer.render(&result).unwrap();
+        assert_eq!(
+            String::from_utf8(renderer.into_inner()).unwrap(),
+            "name,tags,age\n\"Lovelace, Ada\",[1],36\nBob,,1.5\n"
+        );
+
+        let options = CsvOptions::default().delimiter(';').header(false);
+        let mut renderer = CsvRenderer::to_writer(Vec::new(), options);
+        renderer.render(&result).unwrap();
+        assert_eq!(
+            String::from_utf8(renderer.into_inner()).unwrap(),
+            "Lovelace, Ada;[1];36\nBob;;1.5\n"
+        );
+    }
+}
@@ -1,17 +1,100 @@
 //! Graph Visualization
 //!
-//! ASCII and other graph visualization formats.
+//! ASCII and other graph visualization formats. The ASCII views walk the
+//! graph from its roots: a tree indented by depth, or one box per node with
+//! its outgoing edges. Nodes reached again are shown as a reference instead
+//! of being expanded twice, and past the node cap the rest is summarized.
 
 use anyhow::Result;
 use colored::Colorize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use unicode_width::UnicodeWidthStr;
+
+use super::pager;
+use super::table::{pad, truncate};
+use crate::query::TraversalResult;
+use crate::storage::{GraphView, Node, Edge, NodeId, Value};
+
+/// Widest a node label or property line gets
+const MAX_LABEL_WIDTH: usize = 60;
+
+/// How the ASCII views lay out a graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphLayout {
+    /// Indent each node under the node it was reached from
+    #[default]
+    Tree,
+    /// Draw each node in a box, followed by its outgoing edges
+    Boxes,
+}
 
-use crate::storage::{GraphView, Node, Edge, NodeId};
+/// File formats a graph can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphExport {
+    /// Graphviz DOT
+    Dot,
+    /// D3-style JSON with `nodes` and `links`
+    Json,
+}
+
+impl GraphExport {
+    /// Format for a file, by its extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("dot") | Some("gv") => Ok(Self::Dot),
+            Some("json") => Ok(Self::Json),
+            _ => anyhow::bail!("Cannot tell the export format of {}; use .dot, .gv or .json", path.display()),
+        }
+    }
+}
 
 /// Graph renderer
+#[derive(Debug, Clone)]
 pub struct GraphRenderer {
     max_depth: u32,
     max_nodes: usize,
+    layout: GraphLayout,
+}
+
+/// Nodes and deduplicated outgoing edges, by node ID
+struct Adjacency<'a> {
+    nodes: HashMap<NodeId, &'a Node>,
+    out: HashMap<NodeId, Vec<&'a Edge>>,
+    edges: usize,
+}
+
+impl<'a> Adjacency<'a> {
+    fn new(nodes: &'a [Node], edges: &'a [Edge]) -> Self {
+        let mut lookup = HashMap::new();
+        for node in nodes {
+            lookup.entry(node.id.clone()).or_insert(node);
+        }
+        let mut seen = HashSet::new();
+        let mut out: HashMap<NodeId, Vec<&Edge>> = HashMap::new();
+        for edge in edges {
+            if seen.insert((&edge.from, &edge.to, &edge.edge_type)) {
+                out.entry(edge.from.clone()).or_default().push(edge);
+            }
+        }
+        Self { nodes: lookup, out, edges: seen.len() }
+    }
+
+    fn children(&self, id: &NodeId) -> &[&'a Edge] {
+        self.out.get(id).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Walk state shared by both layouts
+struct Walk {
+    lines: Vec<String>,
+    shown: HashSet<NodeId>,
+}
+
+impl Walk {
+    fn new() -> Self {
+        Self { lines: Vec::new(), shown: HashSet::new() }
+    }
 }
 
 impl GraphRenderer {
@@ -20,147 +103,197 @@ impl GraphRenderer {
         Self {
             max_depth: 5,
             max_nodes: 100,
+            layout: GraphLayout::Tree,
         }
     }
 
-    /// Render graph as ASCII art
-    pub fn render_ascii(&self, graph: &GraphView) -> Result<()> {
-        if graph.nodes.is_empty() {
-            println!("{}", "(empty graph)".bright_black());
-            return Ok(());
-        }
+    /// Set how deep the tree layout goes below each root
+    pub fn max_depth(mut self, depth: u32) -> Self {
+        self.max_depth = depth;
+        self
+    }
 
-        println!();
-        println!("{}", "Graph View".bright_yellow().bold());
-        println!("{}", "═".repeat(60));
+    /// Set how many nodes are drawn before the rest is summarized
+    pub fn max_nodes(mut self, nodes: usize) -> Self {
+        self.max_nodes = nodes.max(1);
+        self
+    }
 
-        // Build adjacency list
-        let mut adj: HashMap<String, Vec<(String, String)>> = HashMap::new();
-        for edge in &graph.edges {
-            let from = edge.from.to_string();
-            let to = edge.to.to_string();
-            adj.entry(from).or_default().push((to, edge.edge_type.clone()));
-        }
+    /// Set the layout
+    pub fn layout(mut self, layout: GraphLayout) -> Self {
+        self.layout = layout;
+        self
+    }
 
-        // Build node lookup
-        let node_lookup: HashMap<String, &Node> = graph.nodes
-            .iter()
-            .map(|n| (n.id.to_string(), n))
-            .collect();
+    /// Render graph as ASCII art
+    pub fn render_ascii(&self, graph: &GraphView) -> Result<()> {
+        pager::page(&self.format_ascii(graph))
+    }
 
-        // Find root nodes (nodes with no incoming edges)
-        let has_incoming: HashSet<String> = graph.edges
-            .iter()
-            .map(|e| e.to.to_string())
-            .collect();
+    /// Render a traversal as ASCII art, starting from its root
+    pub fn render_traversal(&self, traversal: &TraversalResult) -> Result<()> {
+        pager::page(&self.format_traversal(traversal))
+    }
 
-        let roots: Vec<&Node> = graph.nodes
+    /// Format graph as ASCII art, starting from nodes with only outgoing edges
+    pub fn format_ascii(&self, graph: &GraphView) -> String {
+        if graph.nodes.is_empty() {
+            return "(empty graph)".bright_black().to_string();
+        }
+        let incoming: HashSet<&NodeId> = graph.edges.iter().map(|e| &e.to).collect();
+        let outgoing: HashSet<&NodeId> = graph.edges.iter().map(|e| &e.from).collect();
+        let roots: Vec<&NodeId> = graph.nodes
             .iter()
-            .filter(|n| !has_incoming.contains(&n.id.to_string()))
-            .take(10)
+            .filter(|n| outgoing.contains(&n.id) && !incoming.contains(&n.id))
+            .map(|n| &n.id)
             .collect();
+        self.format("Graph View", &graph.nodes, &graph.edges, &roots)
+    }
 
-        // Render each tree from root
-        let mut visited: HashSet<String> = HashSet::new();
+    /// Format a traversal as ASCII art, starting from its root
+    pub fn format_traversal(&self, traversal: &TraversalResult) -> String {
+        let graph = traversal.to_graph_view();
+        let title = format!("Traversal from {} (depth {})", label(&traversal.root), traversal.depth);
+        self.format(&title, &graph.nodes, &graph.edges, &[&traversal.root.id])
+    }
 
-        for root in roots {
-            self.render_subtree(root, &adj, &node_lookup, &mut visited, 0);
+    fn format(&self, title: &str, nodes: &[Node], edges: &[Edge], roots: &[&NodeId]) -> String {
+        let graph = Adjacency::new(nodes, edges);
+        let mut walk = Walk::new();
+        walk.lines.push(String::new());
+        walk.lines.push(title.bright_yellow().bold().to_string());
+        walk.lines.push("═".repeat(title.width().clamp(20, 60)));
+
+        // Roots first, then whatever they do not reach; a graph that is all
+        // cycles has no roots, so its first node stands in
+        let mut starts: Vec<&NodeId> = roots.to_vec();
+        starts.extend(nodes.iter().filter(|n| !graph.children(&n.id).is_empty()).map(|n| &n.id));
+
+        for id in starts {
+            if walk.shown.contains(id) || !graph.nodes.contains_key(id) {
+                continue;
+            }
+            if walk.shown.len() >= self.max_nodes {
+                break;
+            }
+            match self.layout {
+                GraphLayout::Tree => self.tree(&graph, id, &mut walk),
+                GraphLayout::Boxes => self.boxes(&graph, id, &mut walk),
+            }
         }
 
-        // Render any unvisited nodes
-        let unvisited: Vec<&Node> = graph.nodes
-            .iter()
-            .filter(|n| !visited.contains(&n.id.to_string()))
-            .take(20)
-            .collect();
-
-        if !unvisited.is_empty() {
-            println!();
-            println!("{}", "Other nodes:".bright_black());
-            for node in unvisited {
-                self.render_node(node, 1);
+        // Nodes no walk reached, one line each
+        let room = self.max_nodes.saturating_sub(walk.shown.len());
+        let mut listed: Vec<&Node> = Vec::new();
+        for node in nodes {
+            if listed.len() < room && !walk.shown.contains(&node.id) && !listed.iter().any(|n| n.id == node.id) {
+                listed.push(node);
+            }
+        }
+        if !listed.is_empty() {
+            walk.lines.push(String::new());
+            walk.lines.push("Other nodes:".bright_black().to_string());
+            for node in listed {
+                walk.shown.insert(node.id.clone());
+                walk.lines.push(format!("  ● {}", node_line(node)));
             }
         }
 
         // Summary
-        println!();
-        println!(
+        walk.lines.push(String::new());
+        walk.lines.push(format!(
             "{} {} nodes, {} edges",
             "Summary:".bright_black(),
             graph.nodes.len(),
-            graph.edges.len()
-        );
-        println!();
-
-        Ok(())
-    }
-
-    /// Render a subtree recursively
-    fn render_subtree(
-        &self,
-        node: &Node,
-        adj: &HashMap<String, Vec<(String, String)>>,
-        lookup: &HashMap<String, &Node>,
-        visited: &mut HashSet<String>,
-        depth: u32,
-    ) {
-        let id = node.id.to_string();
-
-        if visited.contains(&id) || depth > self.max_depth || visited.len() >= self.max_nodes {
-            return;
+            graph.edges
+        ));
+        let hidden = graph.nodes.len().saturating_sub(walk.shown.len());
+        if hidden > 0 {
+            walk.lines.push(
+                format!("... {} more nodes not shown (raise --max-nodes to see them)", hidden)
+                    .bright_black()
+                    .to_string()
+            );
         }
-        visited.insert(id.clone());
-
-        // Render this node
-        self.render_node(node, depth);
-
-        // Render children
-        if let Some(children) = adj.get(&id) {
-            for (i, (child_id, edge_type)) in children.iter().enumerate() {
-                let is_last = i == children.len() - 1;
-                let prefix = "  ".repeat(depth as usize);
-                let branch = if is_last { "└──" } else { "├──" };
+        walk.lines.push(String::new());
+        walk.lines.join("\n")
+    }
 
-                println!(
-                    "{}{}[{}]→",
-                    prefix,
-                    branch.bright_black(),
-                    edge_type.bright_magenta()
-                );
+    /// Tree layout: each node indented under the edge that reached it
+    fn tree(&self, graph: &Adjacency, root: &NodeId, walk: &mut Walk) {
+        let node = graph.nodes[root];
+        walk.shown.insert(root.clone());
+        walk.lines.push(format!("● {}", node_line(node)));
+        self.subtree(graph, root, "", 1, walk);
+    }
 
-                if let Some(child_node) = lookup.get(child_id) {
-                    self.render_subtree(child_node, adj, lookup, visited, depth + 1);
+    fn subtree(&self, graph: &Adjacency, id: &NodeId, prefix: &str, depth: u32, walk: &mut Walk) {
+        let children = graph.children(id);
+        for (i, edge) in children.iter().enumerate() {
+            let is_last = i == children.len() - 1;
+            let branch = if is_last { "└─" } else { "├─" };
+            let edge_label = format!("{} {} ─▶", branch, edge.edge_type.bright_magenta());
+            let line = format!("{}{}", prefix, edge_label.bright_black());
+
+            if walk.shown.len() >= self.max_nodes && !walk.shown.contains(&edge.to) {
+                let rest = children.len() - i;
+                walk.lines.push(format!("{}{}", prefix, format!("└─ ... {} more", rest).bright_black()));
+                return;
+            }
+            match graph.nodes.get(&edge.to) {
+                None => walk.lines.push(format!("{} {}", line, short_id(&edge.to).bright_black())),
+                Some(child) if walk.shown.contains(&child.id) => {
+                    walk.lines.push(format!("{} ↺ {}", line, format!("{} (shown above)", label(child)).bright_black()));
+                }
+                Some(child) => {
+                    walk.shown.insert(child.id.clone());
+                    walk.lines.push(format!("{} ● {}", line, node_line(child)));
+                    let below = format!("{}{}", prefix, if is_last { "   " } else { "│  " });
+                    if depth < self.max_depth {
+                        self.subtree(graph, &child.id, &below, depth + 1, walk);
+                    } else if !graph.children(&child.id).is_empty() {
+                        walk.lines.push(format!("{}{}", below, "└─ ... (max depth)".bright_black()));
+                    }
                 }
             }
         }
     }
 
-    /// Render a single node
-    fn render_node(&self, node: &Node, depth: u32) {
-        let indent = "  ".repeat(depth as usize);
-
-        // Get display name
-        let name = node.properties.get("name")
-            .map(|v| format!("{}", v))
-            .unwrap_or_else(|| node.id.to_string()[..8].to_string());
-
-        println!(
-            "{}● {} {}",
-            indent,
-            format!("[{}]", node.node_type).bright_cyan(),
-            name.bright_white()
-        );
-
-        // Show a few properties
-        for (key, value) in node.properties.iter().take(3) {
-            if key != "name" {
-                println!(
-                    "{}  {}: {}",
-                    indent,
-                    key.bright_black(),
-                    format!("{}", value).bright_black()
-                );
+    /// Box layout: breadth-first, one box per node with its outgoing edges
+    fn boxes(&self, graph: &Adjacency, root: &NodeId, walk: &mut Walk) {
+        let mut queue = VecDeque::from([root.clone()]);
+        walk.shown.insert(root.clone());
+
+        while let Some(id) = queue.pop_front() {
+            let node = graph.nodes[&id];
+            let mut body = vec![label(node)];
+            body.extend(properties(node).into_iter().map(|(k, v)| format!("{}: {}", k, v)));
+            let body: Vec<String> = body.iter().map(|l| truncate(l, MAX_LABEL_WIDTH)).collect();
+            let width = body.iter().map(|l| l.width()).max().unwrap_or(0);
+
+            walk.lines.push(format!("┌─{}─┐", "─".repeat(width)).bright_black().to_string());
+            for (i, line) in body.iter().enumerate() {
+                let text = if i == 0 { pad(line, width).bright_white().to_string() } else { pad(line, width).bright_black().to_string() };
+                walk.lines.push(format!("{} {} {}", "│".bright_black(), text, "│".bright_black()));
             }
+            walk.lines.push(format!("└─{}─┘", "─".repeat(width)).bright_black().to_string());
+
+            let children = graph.children(&id);
+            for (i, edge) in children.iter().enumerate() {
+                let branch = if i == children.len() - 1 { "└─" } else { "├─" };
+                let target = graph.nodes.get(&edge.to).map_or_else(|| short_id(&edge.to), |n| label(n));
+                walk.lines.push(format!(
+                    "  {} {} {} {}",
+                    branch.bright_black(),
+                    edge.edge_type.bright_magenta(),
+                    "─▶".bright_black(),
+                    target
+                ));
+                if graph.nodes.contains_key(&edge.to) && walk.shown.len() < self.max_nodes && walk.shown.insert(edge.to.clone()) {
+                    queue.push_back(edge.to.clone());
+                }
+            }
+            walk.lines.push(String::new());
         }
     }
 
@@ -172,16 +305,11 @@ impl GraphRenderer {
         lines.push("  node [shape=box, style=rounded];".to_string());
         lines.push("".to_string());
 
-        // Define nodes
+        // Define nodes; UUIDs are only valid DOT IDs when quoted
         for node in &graph.nodes {
-            let id = node.id.to_string().replace('-', "_");
-            let label = node.properties.get("name")
-                .map(|v| format!("{}", v))
-                .unwrap_or_else(|| node.node_type.clone());
-
             lines.push(format!(
-                "  {} [label=\"{}\\n({})\"];",
-                id, label, node.node_type
+                "  \"{}\" [label=\"{}\\n({})\"];",
+                node.id, dot_escape(&display_name(node)), dot_escape(&node.node_type)
             ));
         }
 
@@ -189,12 +317,9 @@ impl GraphRenderer {
 
         // Define edges
         for edge in &graph.edges {
-            let from = edge.from.to_string().replace('-', "_");
-            let to = edge.to.to_string().replace('-', "_");
-
             lines.push(format!(
-                "  {} -> {} [label=\"{}\"];",
-                from, to, edge.edge_type
+                "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                edge.from, edge.to, dot_escape(&edge.edge_type)
             ));
         }
 
@@ -202,6 +327,42 @@ impl GraphRenderer {
         lines.join("\n")
     }
 
+    /// Render graph as D3-style JSON: `nodes` with `id`, `links` with `source` and `target`
+    pub fn render_d3_json(&self, graph: &GraphView) -> serde_json::Value {
+        let nodes: Vec<_> = graph.nodes
+            .iter()
+            .map(|node| {
+                serde_json::json!({
+                    "id": node.id.to_string(),
+                    "label": display_name(node),
+                    "group": node.node_type,
+                    "properties": Value::Object(node.properties.clone()).to_json(),
+                })
+            })
+            .collect();
+        let links: Vec<_> = graph.edges
+            .iter()
+            .map(|edge| {
+                serde_json::json!({
+                    "id": edge.id.to_string(),
+                    "source": edge.from.to_string(),
+                    "target": edge.to.to_string(),
+                    "type": edge.edge_type,
+                    "properties": Value::Object(edge.properties.clone()).to_json(),
+                })
+            })
+            .collect();
+        serde_json::json!({ "nodes": nodes, "links": links })
+    }
+
+    /// Graph in an export format
+    pub fn export(&self, graph: &GraphView, format: GraphExport) -> Result<String> {
+        Ok(match format {
+            GraphExport::Dot => self.render_dot(graph) + "\n",
+            GraphExport::Json => serde_json::to_string_pretty(&self.render_d3_json(graph))? + "\n",
+        })
+    }
+
     /// Render graph as Mermaid format
     pub fn render_mermaid(&self, graph: &GraphView) -> String {
         let mut lines = Vec::new();
@@ -241,4 +402,118 @@ impl Default for GraphRenderer {
     }
 }
 
+/// The first of a node's name, title or label, or a short ID
+fn display_name(node: &Node) -> String {
+    ["name", "title", "label"]
+        .iter()
+        .find_map(|key| match node.properties.get(*key) {
+            Some(Value::String(s)) => Some(s.clone()),
+            _ => None,
+        })
+        .unwrap_or_else(|| short_id(&node.id))
+}
+
+/// `[type] name`
+fn label(node: &Node) -> String {
+    truncate(&format!("[{}] {}", node.node_type, display_name(node)), MAX_LABEL_WIDTH)
+}
 
+/// Escape text for a quoted DOT string
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Label plus a few properties, colored for a tree line
+fn node_line(node: &Node) -> String {
+    let properties: Vec<String> = properties(node).into_iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+    if properties.is_empty() {
+        label(node).bright_white().to_string()
+    } else {
+        let details = truncate(&properties.join(", "), MAX_LABEL_WIDTH);
+        format!("{} {}", label(node).bright_white(), format!("{{{}}}", details).bright_black())
+    }
+}
+
+/// Up to three properties other than the one used as the label
+fn properties(node: &Node) -> Vec<(&str, String)> {
+    node.properties
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "name" | "title" | "label"))
+        .take(3)
+        .map(|(key, value)| (key.as_str(), super::csv::format_value(value).replace('\n', " ")))
+        .collect()
+}
+
+fn short_id(id: &NodeId) -> String {
+    id.to_string()[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn user(name: &str) -> Node {
+        let properties = BTreeMap::from([("name".to_string(), Value::String(name.to_string()))]);
+        Node::with_id(NodeId::new(), "user", properties)
+    }
+
+    fn follows(from: &Node, to: &Node) -> Edge {
+        Edge::new(from.id.clone(), to.id.clone(), "follows", Value::Object(BTreeMap::new()))
+    }
+
+    fn graph() -> GraphView {
+        let (ada, bob, cy, dee) = (user("Ada"), user("Bob"), user("Cy"), user("Dee"));
+        let edges = vec![
+            follows(&ada, &bob),
+            follows(&ada, &bob),
+            follows(&ada, &cy),
+            follows(&cy, &bob),
+        ];
+        GraphView { nodes: vec![ada, bob, cy, dee], edges }
+    }
+
+    #[test]
+    fn test_tree_layout() {
+        colored::control::set_override(false);
+        let text = GraphRenderer::new().format_ascii(&graph());
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"● [user] Ada"));
+        assert!(lines.contains(&"├─ follows ─▶ ● [user] Bob"));
+        assert!(lines.contains(&"└─ follows ─▶ ● [user] Cy"));
+        assert!(lines.contains(&"   └─ follows ─▶ ↺ [user] Bob (shown above)"));
+        assert!(lines.contains(&"  ● [user] Dee"));
+        assert!(lines.contains(&"Summary: 4 nodes, 3 edges"));
+
+        let capped = GraphRenderer::new().max_nodes(2).format_ascii(&graph());
+        assert!(capped.contains("└─ ... 1 more"));
+        assert!(capped.contains("... 2 more nodes not shown"));
+    }
+
+    #[test]
+    fn test_export() {
+        let mut graph = graph();
+        graph.nodes[0].properties.insert("name".to_string(), Value::String("Ada \"The Countess\"".to_string()));
+        let renderer = GraphRenderer::new();
+
+        let dot = renderer.export(&graph, GraphExport::from_path(Path::new("out.DOT")).unwrap()).unwrap();
+        assert!(dot.contains(&format!("  \"{}\" [label=\"Ada \\\"The Countess\\\"\\n(user)\"];", graph.nodes[0].id)));
+        assert!(dot.contains(&format!("  \"{}\" -> \"{}\" [label=\"follows\"];", graph.nodes[0].id, graph.nodes[1].id)));
+
+        let json: serde_json::Value = serde_json::from_str(&renderer.export(&graph, GraphExport::Json).unwrap()).unwrap();
+        assert_eq!(json["nodes"][1]["label"], "Bob");
+        assert_eq!(json["links"][0]["source"], graph.nodes[0].id.to_string());
+        assert_eq!(json["links"].as_array().unwrap().len(), 4);
+
+        assert!(GraphExport::from_path(Path::new("out.png")).is_err());
+    }
+
+    #[test]
+    fn test_box_layout() {
+        colored::control::set_override(false);
+        let text = GraphRenderer::new().layout(GraphLayout::Boxes).format_ascii(&graph());
+        assert!(text.contains("┌────────────┐\n│ [user] Ada │\n└────────────┘\n  ├─ follows ─▶ [user] Bob\n  └─ follows ─▶ [user] Cy"));
+        assert_eq!(text.matches("│ [user] Bob").count(), 1);
+    }
+}
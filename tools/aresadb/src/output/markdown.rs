@@ -0,0 +1,123 @@
+//! Markdown Renderer
+//!
+//! GitHub-flavored Markdown tables, for pasting results into issues and docs.
+
+use anyhow::Result;
+
+use crate::query::QueryResult;
+use crate::storage::Value;
+
+/// Markdown table renderer
+pub struct MarkdownRenderer {
+    max_rows: usize,
+}
+
+impl MarkdownRenderer {
+    /// Create a new Markdown renderer
+    pub fn new() -> Self {
+        Self { max_rows: usize::MAX }
+    }
+
+    /// Set maximum rows to display
+    pub fn max_rows(mut self, rows: usize) -> Self {
+        self.max_rows = rows;
+        self
+    }
+
+    /// Render query results as a Markdown table
+    pub fn render(&self, results: &QueryResult) -> Result<()> {
+        println!("{}", self.format(results));
+        Ok(())
+    }
+
+    /// Format query results as a Markdown table
+    pub fn format(&self, results: &QueryResult) -> String {
+        let rows: Vec<Vec<String>> = results
+            .rows
+            .iter()
+            .take(self.max_rows)
+            .map(|row| row.iter().map(format_value).collect())
+            .collect();
+        let mut out = table(&results.columns, &rows);
+        if results.rows.len() > rows.len() {
+            out.push_str(&format!("\n_Showing {} of {} rows_", rows.len(), results.rows.len()));
+        }
+        out
+    }
+}
+
+impl Default for MarkdownRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Markdown table with a header row
+pub fn table<H: AsRef<str>>(headers: &[H], rows: &[Vec<String>]) -> String {
+    let mut lines = Vec::with_capacity(rows.len() + 2);
+    lines.push(line(headers.iter().map(|h| escape(h.as_ref()))));
+    lines.push(line(headers.iter().map(|_| "---".to_string())));
+    for row in rows {
+        lines.push(line(row.iter().map(|cell| escape(cell))));
+    }
+    lines.join("\n")
+}
+
+fn line(cells: impl Iterator<Item = String>) -> String {
+    format!("| {} |", cells.collect::<Vec<_>>().join(" | "))
+}
+
+/// Make text safe inside a table cell
+pub fn escape(text: &str) -> String {
+    text.trim()
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Format a value as cell text; strings are unquoted, nested values are JSON
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => super::DisplayOptions::defaults().null,
+        Value::String(s) => s.clone(),
+        Value::Bytes(b) => format!("({} bytes)", b.len()),
+        Value::Vector(v) => format!("(vector dim={})", v.len()),
+        Value::Array(_) | Value::Object(_) => format!("`{}`", value.to_json()),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let rows = vec![
+            vec!["1".to_string(), "a | b".to_string()],
+            vec!["2".to_string(), "line one\nline two".to_string()],
+        ];
+        assert_eq!(
+            table(&["id", "note"], &rows),
+            "| id | note |\n| --- | --- |\n| 1 | a \\| b |\n| 2 | line one<br>line two |"
+        );
+    }
+
+    #[test]
+    fn test_format_results() {
+        let result = QueryResult {
+            columns: vec!["name".to_string(), "tags".to_string()],
+            rows: vec![
+                vec![Value::String("Ada".to_string()), Value::Array(vec![Value::Int(1)])],
+                vec![Value::Null, Value::Vector(vec![0.5; 3])],
+            ],
+            ..QueryResult::empty()
+        };
+        assert_eq!(
+            MarkdownRenderer::new().format(&result),
+            "| name | tags |\n| --- | --- |\n| Ada | `[1]` |\n| NULL | (vector dim=3) |"
+        );
+        assert!(MarkdownRenderer::new().max_rows(1).format(&result).ends_with("_Showing 1 of 2 rows_"));
+    }
+}
@@ -5,10 +5,18 @@
 mod table;
 mod graph_viz;
 mod json;
+mod markdown;
+mod ndjson;
+mod csv;
+mod pager;
 
-pub use table::TableRenderer;
-pub use graph_viz::GraphRenderer;
+pub use table::{TableRenderer, DisplayOptions, Overflow, set_defaults as set_display_defaults};
+pub use graph_viz::{GraphRenderer, GraphLayout, GraphExport};
 pub use json::JsonRenderer;
+pub use markdown::{MarkdownRenderer, table as markdown_table};
+pub use ndjson::{NdjsonRenderer, is_broken_pipe};
+pub use csv::{CsvRenderer, CsvOptions, set_defaults as set_csv_defaults};
+pub use pager::{page, disable as disable_pager};
 
 use anyhow::Result;
 use colored::Colorize;
@@ -16,22 +24,29 @@ use colored::Colorize;
 use crate::cli::commands::OutputFormat;
 use crate::query::{QueryResult, TraversalResult};
 use crate::schema::Schema;
-use crate::storage::{Node, GraphView, KvView, SimilarityResult, Database};
+use crate::storage::{Node, Edge, GraphView, KvView, SimilarityResult, Database, Value};
 
 /// Main renderer that dispatches to appropriate sub-renderers
 pub struct Renderer {
     format: OutputFormat,
+    graph: GraphRenderer,
 }
 
 impl Renderer {
     /// Create a new renderer
     pub fn new(format: OutputFormat) -> Self {
-        Self { format }
+        Self { format, graph: GraphRenderer::new() }
+    }
+
+    /// Use these settings for ASCII graph views
+    pub fn graph(mut self, graph: GraphRenderer) -> Self {
+        self.graph = graph;
+        self
     }
 
     /// Render query results
     pub fn render_results(&self, results: &QueryResult) -> Result<()> {
-        if results.is_empty() {
+        if results.is_empty() && self.format != OutputFormat::Ndjson {
             println!("{}", "(no results)".bright_black());
             return Ok(());
         }
@@ -48,6 +63,13 @@ impl Renderer {
             OutputFormat::Csv => {
                 self.render_csv(results)
             }
+            OutputFormat::Markdown => {
+                let renderer = MarkdownRenderer::new();
+                renderer.render(results)
+            }
+            OutputFormat::Ndjson => {
+                NdjsonRenderer::new().render(results)
+            }
         }
     }
 
@@ -55,8 +77,7 @@ impl Renderer {
     pub fn render_traversal(&self, results: &TraversalResult) -> Result<()> {
         match self.format {
             OutputFormat::Table => {
-                let renderer = TableRenderer::new();
-                renderer.render(&results.to_query_result())
+                self.graph.render_traversal(results)
             }
             OutputFormat::Json => {
                 let json = serde_json::to_string_pretty(results)?;
@@ -66,6 +87,13 @@ impl Renderer {
             OutputFormat::Csv => {
                 self.render_csv(&results.to_query_result())
             }
+            OutputFormat::Markdown => {
+                let renderer = MarkdownRenderer::new();
+                renderer.render(&results.to_query_result())
+            }
+            OutputFormat::Ndjson => {
+                print_lines(results.nodes.iter().map(|n| n.to_json()))
+            }
         }
     }
 
@@ -95,6 +123,87 @@ impl Renderer {
                 let result = QueryResult::from_nodes(vec![node.clone()]);
                 self.render_csv(&result)
             }
+            OutputFormat::Markdown => {
+                let mut rows = vec![
+                    vec!["id".to_string(), node.id.to_string()],
+                    vec!["type".to_string(), node.node_type.clone()],
+                ];
+                for (key, value) in &node.properties {
+                    rows.push(vec![key.clone(), markdown::format_value(value)]);
+                }
+                rows.push(vec!["created_at".to_string(), node.created_at.to_string()]);
+                rows.push(vec!["updated_at".to_string(), node.updated_at.to_string()]);
+                println!("{}", markdown::table(&["Field", "Value"], &rows));
+                Ok(())
+            }
+            OutputFormat::Ndjson => {
+                print_lines([node.to_json()])
+            }
+        }
+    }
+
+    /// Render a list of edges
+    pub fn render_edges(&self, edges: &[Edge]) -> Result<()> {
+        match self.format {
+            OutputFormat::Table => {
+                if edges.is_empty() {
+                    println!("{}", "(no edges)".bright_black());
+                    return Ok(());
+                }
+                println!();
+                for edge in edges {
+                    println!(
+                        "{}  {} -[{}]-> {}",
+                        edge.id.to_string().bright_yellow(),
+                        edge.from,
+                        edge.edge_type.bright_cyan(),
+                        edge.to
+                    );
+                    for (key, value) in &edge.properties {
+                        println!("    {}: {}", key.bright_green(), value);
+                    }
+                }
+                println!();
+                Ok(())
+            }
+            OutputFormat::Json => {
+                let json: Vec<_> = edges.iter().map(|e| e.to_json()).collect();
+                println!("{}", serde_json::to_string_pretty(&json)?);
+                Ok(())
+            }
+            OutputFormat::Csv => {
+                let rows = edges.iter().map(|edge| {
+                    vec![
+                        edge.id.to_string(),
+                        edge.from.to_string(),
+                        edge.to.to_string(),
+                        edge.edge_type.clone(),
+                        edge.created_at.to_string(),
+                        csv::format_value(&Value::Object(edge.properties.clone())),
+                    ]
+                });
+                print_csv(&["id", "from", "to", "type", "created_at", "properties"], rows)
+            }
+            OutputFormat::Markdown => {
+                let rows: Vec<Vec<String>> = edges
+                    .iter()
+                    .map(|edge| {
+                        let properties = Value::Object(edge.properties.clone());
+                        vec![
+                            edge.id.to_string(),
+                            edge.from.to_string(),
+                            edge.edge_type.clone(),
+                            edge.to.to_string(),
+                            markdown::format_value(&properties),
+                        ]
+                    })
+                    .collect();
+                println!("{}", markdown::table(&["ID", "From", "Type", "To", "Properties"], &rows));
+                Ok(())
+            }
+            OutputFormat::Ndjson => {
+                print_lines(edges.iter().map(|e| e.to_json()))
+            }
         }
     }
 
@@ -108,8 +217,20 @@ impl Renderer {
     pub fn render_as_graph(&self, graph: &GraphView) -> Result<()> {
         match self.format {
             OutputFormat::Table | OutputFormat::Csv => {
-                let renderer = GraphRenderer::new();
-                renderer.render_ascii(graph)
+                self.graph.render_ascii(graph)
+            }
+            OutputFormat::Markdown => {
+                let rows: Vec<Vec<String>> = graph
+                    .edges
+                    .iter()
+                    .map(|edge| vec![edge.from.to_string(), edge.edge_type.clone(), edge.to.to_string()])
+                    .collect();
+                println!("{}", markdown::table(&["From", "Type", "To"], &rows));
+                Ok(())
+            }
+            OutputFormat::Ndjson => {
+                // Nodes first, then edges; edges are told apart by `from` and `to`
+                print_lines(graph.nodes.iter().map(|n| n.to_json()).chain(graph.edges.iter().map(|e| e.to_json())))
             }
             OutputFormat::Json => {
                 let json = serde_json::json!({
@@ -142,12 +263,21 @@ impl Renderer {
                 Ok(())
             }
             OutputFormat::Csv => {
-                println!("key,value");
-                for (key, value) in &kv.entries {
-                    println!("{},{}", key, value);
-                }
+                let rows = kv.entries.iter().map(|(key, value)| vec![key.clone(), csv::format_value(value)]);
+                print_csv(&["key", "value"], rows)
+            }
+            OutputFormat::Markdown => {
+                let rows: Vec<Vec<String>> = kv
+                    .entries
+                    .iter()
+                    .map(|(key, value)| vec![key.clone(), markdown::format_value(value)])
+                    .collect();
+                println!("{}", markdown::table(&["Key", "Value"], &rows));
                 Ok(())
             }
+            OutputFormat::Ndjson => {
+                print_lines(kv.entries.iter().map(|(key, value)| serde_json::json!({"key": key, "value": value.to_json()})))
+            }
         }
     }
 
@@ -177,12 +307,22 @@ impl Renderer {
                 Ok(())
             }
             OutputFormat::Csv => {
-                println!("name,field_count,version");
-                for schema in schemas {
-                    println!("{},{},{}", schema.name, schema.fields.len(), schema.version);
-                }
+                let rows = schemas
+                    .iter()
+                    .map(|schema| vec![schema.name.clone(), schema.fields.len().to_string(), schema.version.to_string()]);
+                print_csv(&["name", "field_count", "version"], rows)
+            }
+            OutputFormat::Markdown => {
+                let rows: Vec<Vec<String>> = schemas
+                    .iter()
+                    .map(|schema| vec![schema.name.clone(), schema.fields.len().to_string(), schema.version.to_string()])
+                    .collect();
+                println!("{}", markdown::table(&["Schema", "Fields", "Version"], &rows));
                 Ok(())
             }
+            OutputFormat::Ndjson => {
+                print_lines(schemas)
+            }
         }
     }
 
@@ -199,11 +339,15 @@ impl Renderer {
 
                 for field in &schema.fields {
                     let mut attrs = Vec::new();
-                    if !field.nullable {
-                        attrs.push("NOT NULL".to_string());
-                    }
-                    if field.unique {
-                        attrs.push("UNIQUE".to_string());
+                    if field.primary_key {
+                        attrs.push("PRIMARY KEY".to_string());
+                    } else {
+                        if !field.nullable {
+                            attrs.push("NOT NULL".to_string());
+                        }
+                        if field.unique {
+                            attrs.push("UNIQUE".to_string());
+                        }
                     }
                     if field.indexed {
                         attrs.push("INDEXED".to_string());
@@ -211,6 +355,15 @@ impl Renderer {
                     if let Some(ref default) = field.default {
                         attrs.push(format!("DEFAULT {}", default));
                     }
+                    if let Some(min) = field.min {
+                        attrs.push(format!("MIN {}", min));
+                    }
+                    if let Some(max) = field.max {
+                        attrs.push(format!("MAX {}", max));
+                    }
+                    if let Some(ref pattern) = field.pattern {
+                        attrs.push(format!("PATTERN {}", pattern));
+                    }
 
                     let attrs_str = if attrs.is_empty() {
                         String::new()
@@ -219,13 +372,35 @@ impl Renderer {
                     };
 
                     println!(
-                        "  {} {:?}{}",
+                        "  {} {}{}",
                         field.name.bright_green(),
                         field.field_type,
                         attrs_str.bright_black()
                     );
                 }
 
+                if !schema.checks.is_empty() {
+                    println!();
+                    println!("{}", "Checks:".bright_yellow());
+                    for check in &schema.checks {
+                        println!("  {}", check);
+                    }
+                }
+
+                let examples = schema.vector_search_examples();
+                if !examples.is_empty() {
+                    println!();
+                    println!("{}", "Vector search:".bright_yellow());
+                    for example in examples {
+                        println!("  {}", example);
+                    }
+                    println!(
+                        "  {}",
+                        "Insert vectors as {\"$vector\": [...]}; metrics are cosine, euclidean, dot and manhattan"
+                            .bright_black()
+                    );
+                }
+
                 println!();
                 println!("{}", "SQL:".bright_yellow());
                 println!("{}", schema.to_sql().bright_black());
@@ -238,44 +413,57 @@ impl Renderer {
                 Ok(())
             }
             OutputFormat::Csv => {
-                println!("field_name,field_type,nullable,unique,indexed,default");
-                for field in &schema.fields {
-                    println!(
-                        "{},{:?},{},{},{},{}",
-                        field.name,
-                        field.field_type,
-                        field.nullable,
-                        field.unique,
-                        field.indexed,
-                        field.default.as_deref().unwrap_or("")
-                    );
+                let rows = schema.fields.iter().map(|field| {
+                    vec![
+                        field.name.clone(),
+                        field.field_type.to_string(),
+                        field.nullable.to_string(),
+                        field.unique.to_string(),
+                        field.indexed.to_string(),
+                        field.default.clone().unwrap_or_default(),
+                    ]
+                });
+                print_csv(&["field_name", "field_type", "nullable", "unique", "indexed", "default"], rows)
+            }
+            OutputFormat::Markdown => {
+                let flag = |set: bool| if set { "yes" } else { "" }.to_string();
+                let rows: Vec<Vec<String>> = schema
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        vec![
+                            field.name.clone(),
+                            field.field_type.to_string(),
+                            flag(field.nullable),
+                            flag(field.unique),
+                            flag(field.indexed),
+                            field.default.clone().unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                println!("### {} (v{})", schema.name, schema.version);
+                println!();
+                println!("{}", markdown::table(&["Field", "Type", "Nullable", "Unique", "Indexed", "Default"], &rows));
+                println!();
+                println!("```sql\n{}\n```", schema.to_sql());
+                let examples = schema.vector_search_examples();
+                if !examples.is_empty() {
+                    println!();
+                    println!("Vector search (insert vectors as `{{\"$vector\": [...]}}`):");
+                    println!();
+                    println!("```sql\n{}\n```", examples.join("\n"));
                 }
                 Ok(())
             }
+            OutputFormat::Ndjson => {
+                print_lines([schema])
+            }
         }
     }
 
     /// Render as CSV
     fn render_csv(&self, results: &QueryResult) -> Result<()> {
-        // Header
-        println!("{}", results.columns.join(","));
-
-        // Rows
-        for row in &results.rows {
-            let row_str: Vec<String> = row.iter()
-                .map(|v| {
-                    let s = format!("{}", v);
-                    if s.contains(',') || s.contains('"') || s.contains('\n') {
-                        format!("\"{}\"", s.replace('"', "\"\""))
-                    } else {
-                        s
-                    }
-                })
-                .collect();
-            println!("{}", row_str.join(","));
-        }
-
-        Ok(())
+        CsvRenderer::new().render(results)
     }
 
     /// Render similarity search results
@@ -329,20 +517,54 @@ impl Renderer {
                 Ok(())
             }
             OutputFormat::Csv => {
-                println!("rank,node_id,score,distance");
-                for (i, result) in results.iter().enumerate() {
-                    println!(
-                        "{},{},{:.6},{:.6}",
-                        i + 1,
-                        result.node_id,
-                        result.score,
-                        result.distance
-                    );
-                }
+                let rows = results.iter().enumerate().map(|(i, result)| {
+                    vec![
+                        (i + 1).to_string(),
+                        result.node_id.to_string(),
+                        format!("{:.6}", result.score),
+                        format!("{:.6}", result.distance),
+                    ]
+                });
+                print_csv(&["rank", "node_id", "score", "distance"], rows)
+            }
+            OutputFormat::Markdown => {
+                let rows: Vec<Vec<String>> = results
+                    .iter()
+                    .enumerate()
+                    .map(|(i, result)| {
+                        vec![
+                            (i + 1).to_string(),
+                            result.node_id.to_string(),
+                            format!("{:.4}", result.score),
+                            format!("{:.4}", result.distance),
+                        ]
+                    })
+                    .collect();
+                println!("{}", markdown::table(&["#", "Node ID", "Score", "Distance"], &rows));
                 Ok(())
             }
+            OutputFormat::Ndjson => {
+                print_lines(results)
+            }
         }
     }
 }
 
+/// Print a header and rows as CSV
+fn print_csv(headers: &[&str], rows: impl IntoIterator<Item = Vec<String>>) -> Result<()> {
+    let mut renderer = CsvRenderer::new();
+    renderer.write_header(headers)?;
+    for row in rows {
+        renderer.write_record(row)?;
+    }
+    renderer.flush()
+}
 
+/// Print each item as one line of compact JSON
+fn print_lines<T: serde::Serialize>(items: impl IntoIterator<Item = T>) -> Result<()> {
+    let mut renderer = NdjsonRenderer::new();
+    for item in items {
+        renderer.write_json(&serde_json::to_value(item)?)?;
+    }
+    renderer.flush()
+}
@@ -0,0 +1,120 @@
+//! NDJSON Renderer
+//!
+//! Newline-delimited JSON: one compact object per row, written as rows
+//! arrive, for piping into `jq` and other line-oriented tools.
+
+use anyhow::Result;
+use std::io::{self, Write};
+
+use crate::query::QueryResult;
+use crate::storage::Value;
+
+/// NDJSON renderer writing to stdout or any other writer
+pub struct NdjsonRenderer<W: Write = io::Stdout> {
+    out: W,
+}
+
+impl NdjsonRenderer {
+    /// Create a renderer writing to stdout
+    pub fn new() -> Self {
+        Self { out: io::stdout() }
+    }
+}
+
+impl Default for NdjsonRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> NdjsonRenderer<W> {
+    /// Create a renderer writing to `out`
+    pub fn to_writer(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Write one row as an object, keeping the column order
+    pub fn write_row(&mut self, row: Vec<(String, Value)>) -> Result<()> {
+        self.out.write_all(b"{")?;
+        for (i, (column, value)) in row.into_iter().enumerate() {
+            if i > 0 {
+                self.out.write_all(b",")?;
+            }
+            serde_json::to_writer(&mut self.out, &column)?;
+            self.out.write_all(b":")?;
+            serde_json::to_writer(&mut self.out, &value.to_json())?;
+        }
+        self.out.write_all(b"}\n")?;
+        Ok(())
+    }
+
+    /// Write any JSON value as one line
+    pub fn write_json(&mut self, json: &serde_json::Value) -> Result<()> {
+        serde_json::to_writer(&mut self.out, json)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Write every row of a result, leaving out NULL columns
+    pub fn render(&mut self, results: &QueryResult) -> Result<()> {
+        for row in &results.rows {
+            let row = results
+                .columns
+                .iter()
+                .cloned()
+                .zip(row.iter().cloned())
+                .filter(|(_, v)| !v.is_null())
+                .collect();
+            self.write_row(row)?;
+        }
+        self.flush()
+    }
+
+    /// Flush buffered rows
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// The underlying writer
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Whether an error is the reader going away, as when piping into `head`
+pub fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|e| e.downcast_ref::<io::Error>())
+        .any(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        let mut renderer = NdjsonRenderer::to_writer(Vec::new());
+        renderer
+            .write_row(vec![
+                ("id".to_string(), Value::String("n1".to_string())),
+                ("age".to_string(), Value::Int(36)),
+            ])
+            .unwrap();
+        let result = QueryResult {
+            columns: vec!["name".to_string(), "tags".to_string()],
+            rows: vec![vec![Value::String("Ada\n".to_string()), Value::Null]],
+            ..QueryResult::empty()
+        };
+        renderer.render(&result).unwrap();
+        assert_eq!(
+            String::from_utf8(renderer.into_inner()).unwrap(),
+            "{\"id\":\"n1\",\"age\":36}\n{\"name\":\"Ada\\n\"}\n"
+        );
+
+        let error = anyhow::Error::from(io::Error::from(io::ErrorKind::BrokenPipe)).context("writing");
+        assert!(is_broken_pipe(&error));
+    }
+}
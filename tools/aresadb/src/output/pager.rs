@@ -0,0 +1,72 @@
+//! Pager
+//!
+//! Output taller than the terminal goes through `$PAGER` (`less -R` when
+//! unset), like psql. Nothing is paged when stdout is not a terminal, when
+//! `$PAGER` is empty, or after `--no-pager`.
+
+use anyhow::Result;
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn paging off for the rest of the run
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Print text, through the pager if it does not fit on the screen
+pub fn page(text: &str) -> Result<()> {
+    match pager_command(text) {
+        Some(command) if spawn(&command, text).is_ok() => Ok(()),
+        _ => {
+            println!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// The pager to use for `text`, if any
+fn pager_command(text: &str) -> Option<Vec<String>> {
+    if DISABLED.load(Ordering::Relaxed) || !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let (rows, _) = console::Term::stdout().size_checked()?;
+    if !needs_paging(text, rows as usize) {
+        return None;
+    }
+    let command = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    let command: Vec<String> = command.split_whitespace().map(String::from).collect();
+    (!command.is_empty()).then_some(command)
+}
+
+/// Whether text is taller than the terminal, leaving a line for the prompt
+fn needs_paging(text: &str, rows: usize) -> bool {
+    text.lines().count() >= rows
+}
+
+fn spawn(command: &[String], text: &str) -> Result<()> {
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The reader quitting early closes the pipe; that is not an error
+        let _ = writeln!(stdin, "{}", text);
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_paging() {
+        assert!(!needs_paging("a\nb\nc", 24));
+        assert!(needs_paging(&"row\n".repeat(30), 24));
+        assert!(needs_paging("a\nb\nc", 3));
+    }
+}
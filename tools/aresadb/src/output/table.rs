@@ -1,25 +1,79 @@
 //! Table Renderer
 //!
-//! Beautiful table output for query results.
+//! Beautiful table output for query results. Cells wider than a column are
+//! truncated or wrapped, and line breaks inside values stay inside their cell.
 
 use anyhow::Result;
 use colored::Colorize;
+use std::sync::OnceLock;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
+use super::pager;
 use crate::query::QueryResult;
 use crate::storage::Value;
 
+static DEFAULTS: OnceLock<DisplayOptions> = OnceLock::new();
+
+/// What happens to cells wider than their column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Overflow {
+    /// Cut the cell short and end it with `...`
+    #[default]
+    Truncate,
+    /// Continue the cell on the following lines
+    Wrap,
+}
+
+/// How table cells are laid out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayOptions {
+    /// Widest a column gets, in terminal cells
+    pub max_width: usize,
+    /// Truncate or wrap wider cells
+    pub overflow: Overflow,
+    /// Text shown for NULL
+    pub null: String,
+}
+
+impl DisplayOptions {
+    /// Options for this run, as set by [`set_defaults`]
+    pub fn defaults() -> Self {
+        DEFAULTS.get().cloned().unwrap_or_default()
+    }
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            max_width: 50,
+            overflow: Overflow::Truncate,
+            null: "NULL".to_string(),
+        }
+    }
+}
+
+/// Set the options used by every table renderer; only the first call counts
+pub fn set_defaults(options: DisplayOptions) {
+    let _ = DEFAULTS.set(options);
+}
+
 /// Table renderer
 pub struct TableRenderer {
     max_width: usize,
     max_rows: usize,
+    overflow: Overflow,
+    null: String,
 }
 
 impl TableRenderer {
-    /// Create a new table renderer
+    /// Create a new table renderer with the run's display options
     pub fn new() -> Self {
+        let options = DisplayOptions::defaults();
         Self {
-            max_width: 50,
+            max_width: options.max_width,
             max_rows: 1000,
+            overflow: options.overflow,
+            null: options.null,
         }
     }
 
@@ -29,17 +83,33 @@ impl TableRenderer {
         self
     }
 
+    /// Truncate or wrap cells wider than a column
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set the text shown for NULL
+    pub fn null(mut self, text: &str) -> Self {
+        self.null = text.to_string();
+        self
+    }
+
     /// Set maximum rows to display
     pub fn max_rows(mut self, rows: usize) -> Self {
         self.max_rows = rows;
         self
     }
 
-    /// Render query results as a table
+    /// Render query results as a table, through the pager when it is long
     pub fn render(&self, results: &QueryResult) -> Result<()> {
+        pager::page(&self.format(results))
+    }
+
+    /// Format query results as a table
+    pub fn format(&self, results: &QueryResult) -> String {
         if results.is_empty() {
-            println!("{}", "(no results)".bright_black());
-            return Ok(());
+            return "(no results)".bright_black().to_string();
         }
 
         // Convert to tabled-compatible format
@@ -57,44 +127,37 @@ impl TableRenderer {
         }
 
         // Create table
-        println!();
+        let mut lines = vec![String::new()];
 
         if table_data.is_empty() {
-            println!("{}", "(no results)".bright_black());
-            return Ok(());
+            lines.push("(no results)".bright_black().to_string());
+            return lines.join("\n");
         }
 
-        // Print using manual formatting for more control
-        self.print_manual_table(&results.columns, &table_data)?;
+        // Lay out manually for more control
+        self.format_manual_table(&results.columns, &table_data, &mut lines);
 
         // Show truncation notice
         if truncated {
-            println!(
-                "{}",
+            lines.push(
                 format!("... showing {} of {} rows", display_rows, results.rows.len())
                     .bright_black()
+                    .to_string()
             );
         }
 
-        println!();
-
-        Ok(())
+        lines.push(String::new());
+        lines.join("\n")
     }
 
     /// Format a value for display
     fn format_value(&self, value: &Value) -> String {
         match value {
-            Value::Null => "NULL".to_string(),
+            Value::Null => self.null.clone(),
             Value::Bool(b) => if *b { "true" } else { "false" }.to_string(),
             Value::Int(i) => i.to_string(),
             Value::Float(f) => format!("{:.4}", f),
-            Value::String(s) => {
-                if s.len() > self.max_width {
-                    format!("{}...", &s[..self.max_width - 3])
-                } else {
-                    s.clone()
-                }
-            }
+            Value::String(s) => s.clone(),
             Value::Bytes(b) => format!("<{} bytes>", b.len()),
             Value::Array(arr) => {
                 let preview: Vec<String> = arr.iter()
@@ -118,22 +181,23 @@ impl TableRenderer {
         }
     }
 
-    /// Print table manually for better control
-    fn print_manual_table(&self, columns: &[String], data: &[Vec<String>]) -> Result<()> {
+    /// Lay out the table manually for better control
+    fn format_manual_table(&self, columns: &[String], data: &[Vec<String>], lines: &mut Vec<String>) {
         // Calculate column widths
-        let mut widths: Vec<usize> = columns.iter().map(|c| c.len()).collect();
+        let mut widths: Vec<usize> = columns.iter().map(|c| c.width()).collect();
 
         for row in data {
             for (i, cell) in row.iter().enumerate() {
                 if i < widths.len() {
-                    widths[i] = widths[i].max(cell.len());
+                    widths[i] = widths[i].max(cell.lines().map(|l| l.width()).max().unwrap_or(0));
                 }
             }
         }
 
-        // Cap widths
+        // Cap widths, leaving room for "..."
+        let max_width = self.max_width.max(4);
         for w in &mut widths {
-            *w = (*w).min(self.max_width);
+            *w = (*w).min(max_width);
         }
 
         // Print header
@@ -141,35 +205,110 @@ impl TableRenderer {
             .enumerate()
             .map(|(i, c)| {
                 let width = widths.get(i).copied().unwrap_or(10);
-                format!("{:width$}", c, width = width)
+                pad(&truncate(c, width), width)
             })
             .collect();
 
-        println!("{}", header.join(" │ ").bright_cyan().bold());
+        lines.push(header.join(" │ ").bright_cyan().bold().to_string());
 
         // Print separator
         let sep: Vec<String> = widths.iter().map(|w| "─".repeat(*w)).collect();
-        println!("{}", sep.join("─┼─").bright_black());
+        lines.push(sep.join("─┼─").bright_black().to_string());
 
-        // Print rows
+        // Print rows, one line per line of the tallest cell
         for row in data {
-            let row_str: Vec<String> = row.iter()
+            let cells: Vec<Vec<String>> = row.iter()
                 .enumerate()
-                .map(|(i, cell)| {
-                    let width = widths.get(i).copied().unwrap_or(10);
-                    let truncated = if cell.len() > width {
-                        format!("{}...", &cell[..width - 3])
-                    } else {
-                        cell.clone()
-                    };
-                    format!("{:width$}", truncated, width = width)
-                })
+                .map(|(i, cell)| self.fit(cell, widths.get(i).copied().unwrap_or(10)))
                 .collect();
-            println!("{}", row_str.join(" │ "));
+            let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+
+            for line in 0..height {
+                let row_str: Vec<String> = cells.iter()
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        let width = widths.get(i).copied().unwrap_or(10);
+                        pad(cell.get(line).map_or("", String::as_str), width)
+                    })
+                    .collect();
+                lines.push(row_str.join(" │ "));
+            }
+        }
+    }
+
+    /// Lines of a cell that fit in `width`
+    fn fit(&self, cell: &str, width: usize) -> Vec<String> {
+        match self.overflow {
+            Overflow::Truncate => {
+                let flat = cell.replace("\r\n", " ").replace(['\n', '\r', '\t'], " ");
+                vec![truncate(&flat, width)]
+            }
+            Overflow::Wrap => cell
+                .lines()
+                .flat_map(|line| wrap(&line.replace('\t', " "), width))
+                .collect(),
         }
+    }
+}
 
-        Ok(())
+/// Cut text to `width` terminal cells, ending it with "..." when cut
+pub(super) fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width.saturating_sub(3) {
+            break;
+        }
+        out.push(c);
+        used += w;
+    }
+    out.push_str("...");
+    out
+}
+
+/// Break a line into lines of at most `width` cells, at spaces where possible
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut used = 0;
+
+    for word in line.split(' ') {
+        let word_width = word.width();
+        let gap = usize::from(!current.is_empty());
+        if used + gap + word_width <= width {
+            if gap == 1 {
+                current.push(' ');
+            }
+            current.push_str(word);
+            used += gap + word_width;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+            used = 0;
+        }
+        // Break words longer than a whole line
+        for c in word.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+                used = 0;
+            }
+            current.push(c);
+            used += w;
+        }
     }
+    lines.push(current);
+    lines
+}
+
+/// Pad text with spaces to `width` terminal cells
+pub(super) fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
 }
 
 impl Default for TableRenderer {
@@ -178,3 +317,35 @@ impl Default for TableRenderer {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_and_wrap() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a much longer cell", 10), "a much ...");
+        assert_eq!(truncate("日本語のテキスト", 8), "日本...");
+
+        assert_eq!(wrap("the quick brown fox", 10), vec!["the quick", "brown fox"]);
+        assert_eq!(wrap("abcdefghij klm", 4), vec!["abcd", "efgh", "ij", "klm"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn test_multiline_cells() {
+        let result = QueryResult {
+            columns: vec!["id".to_string(), "note".to_string()],
+            rows: vec![vec![Value::Int(1), Value::String("first line\nsecond".to_string())]],
+            ..QueryResult::empty()
+        };
+        colored::control::set_override(false);
+
+        let truncated = TableRenderer::new().max_width(8).format(&result);
+        assert!(truncated.contains("1  │ first..."));
+
+        let wrapped = TableRenderer::new().max_width(8).overflow(Overflow::Wrap).format(&result);
+        assert!(wrapped.contains("1  │ first   \n   │ line    \n   │ second  "));
+    }
+}
@@ -0,0 +1,198 @@
+//! Progress of long-running operations
+//!
+//! Ingest, sync and bulk operations report [`Progress`] events to a
+//! callback as they work, so the CLI and UIs can show how far along an
+//! operation is. [`channel`] turns the callback into a stream for
+//! consumers on another task, such as a server relaying events to a
+//! browser, and [`TerminalBar`] draws them as a progress bar.
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// What a progress count measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressUnit {
+    /// Document chunks embedded
+    Chunks,
+    /// Bytes transferred
+    Bytes,
+    /// Nodes written
+    Nodes,
+    /// Nodes and edges copied
+    Records,
+}
+
+impl ProgressUnit {
+    /// Get the name of this unit
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Chunks => "chunks",
+            Self::Bytes => "bytes",
+            Self::Nodes => "nodes",
+            Self::Records => "records",
+        }
+    }
+}
+
+/// A progress update
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    /// The operation reporting, such as `ingest` or `sync`
+    pub operation: String,
+    /// What `done` and `total` count
+    pub unit: ProgressUnit,
+    /// Work finished so far
+    pub done: u64,
+    /// Work in total, when known up front
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    /// Create an update
+    pub fn new(operation: &str, unit: ProgressUnit, done: u64, total: Option<u64>) -> Self {
+        Self { operation: operation.to_string(), unit, done, total }
+    }
+
+    /// Finished fraction from 0 to 1, when the total is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.done as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+
+    /// Whether the operation has finished its known work
+    pub fn is_complete(&self) -> bool {
+        self.total.is_some_and(|total| self.done >= total)
+    }
+}
+
+/// A callback that ignores progress
+pub fn ignore(_: Progress) {}
+
+/// A progress callback paired with a stream of the events it receives
+///
+/// Events sent after the receiver is dropped are discarded.
+pub fn channel() -> (impl FnMut(Progress) + Clone + Send + Sync + 'static, mpsc::UnboundedReceiver<Progress>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let send = move |progress: Progress| {
+        let _ = tx.send(progress);
+    };
+    (send, rx)
+}
+
+/// A progress bar on stderr, with rate and ETA, fed by progress events
+///
+/// Nothing is drawn when stderr is not a terminal. Clones draw the same bar.
+#[derive(Clone)]
+pub struct TerminalBar {
+    bar: ProgressBar,
+    /// Unit and whether the total is known, as the current style shows them
+    shape: Arc<Mutex<Option<(ProgressUnit, bool)>>>,
+}
+
+impl TerminalBar {
+    /// Create a bar, hidden unless stderr is a terminal
+    pub fn new() -> Self {
+        let target = if std::io::stderr().is_terminal() {
+            ProgressDrawTarget::stderr()
+        } else {
+            ProgressDrawTarget::hidden()
+        };
+        let bar = ProgressBar::with_draw_target(None, target);
+        bar.enable_steady_tick(Duration::from_millis(120));
+        Self { bar, shape: Arc::new(Mutex::new(None)) }
+    }
+
+    /// Show an update
+    pub fn update(&self, progress: &Progress) {
+        let shape = (progress.unit, progress.total.is_some());
+        let mut current = self.shape.lock();
+        if *current != Some(shape) {
+            self.bar.set_style(style(shape.0, shape.1));
+            self.bar.set_message(progress.operation.clone());
+            *current = Some(shape);
+        }
+        match progress.total {
+            Some(total) => self.bar.set_length(total),
+            None => self.bar.unset_length(),
+        }
+        self.bar.set_position(progress.done);
+    }
+
+    /// A callback that shows each update on this bar
+    pub fn callback(&self) -> impl FnMut(Progress) + Clone + Send + Sync + 'static {
+        let bar = self.clone();
+        move |progress: Progress| bar.update(&progress)
+    }
+
+    /// Remove the bar, leaving the line for a summary
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Default for TerminalBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bar style for a unit, with a bar and ETA only when the total is known
+fn style(unit: ProgressUnit, known_total: bool) -> ProgressStyle {
+    let template = match (unit, known_total) {
+        (ProgressUnit::Bytes, true) => {
+            "  {spinner:.blue} {msg} [{bar:30.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta})".to_string()
+        }
+        (ProgressUnit::Bytes, false) => "  {spinner:.blue} {msg} {bytes} ({bytes_per_sec})".to_string(),
+        (unit, true) => format!(
+            "  {{spinner:.blue}} {{msg}} [{{bar:30.cyan/blue}}] {{human_pos}}/{{human_len}} {} ({{per_sec}}, ETA {{eta}})",
+            unit.name()
+        ),
+        (unit, false) => format!("  {{spinner:.blue}} {{msg}} {{human_pos}} {} ({{per_sec}})", unit.name()),
+    };
+    ProgressStyle::with_template(&template)
+        .expect("progress template is valid")
+        .progress_chars("=> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_channel() {
+        let (mut report, mut events) = channel();
+        report(Progress::new("sync", ProgressUnit::Bytes, 50, Some(200)));
+        report(Progress::new("sync", ProgressUnit::Bytes, 200, Some(200)));
+
+        let first = events.try_recv().unwrap();
+        assert_eq!(first.fraction(), Some(0.25));
+        assert!(!first.is_complete());
+        assert!(events.try_recv().unwrap().is_complete());
+        assert!(events.try_recv().is_err());
+
+        assert_eq!(Progress::new("ingest", ProgressUnit::Chunks, 3, None).fraction(), None);
+        assert_eq!(Progress::new("ingest", ProgressUnit::Chunks, 0, Some(0)).fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn test_terminal_bar() {
+        let bar = TerminalBar::new();
+        let mut report = bar.callback();
+        report(Progress::new("migrate", ProgressUnit::Records, 10, None));
+        assert_eq!(bar.bar.length(), None);
+        report(Progress::new("sync", ProgressUnit::Bytes, 512, Some(2048)));
+        assert_eq!((bar.bar.position(), bar.bar.length()), (512, Some(2048)));
+        assert_eq!(bar.bar.message(), "sync");
+        bar.finish();
+        assert!(bar.bar.is_finished());
+    }
+}
@@ -4,64 +4,261 @@
 
 use anyhow::{Result, bail};
 use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::time::Instant;
+use tracing::field::Empty;
 
 use super::{
-    QueryParser, QueryPlanner, QueryPlan, ParsedQuery, QueryResult,
-    TraversalResult, Condition, QueryOperation,
+    QueryParser, QueryPlanner, QueryPlan, ParsedQuery, PlanCache, PreparedStatement, QueryResult,
+    QueryStats, TraversalResult, Condition, Operator, QueryOperation,
 };
 use super::planner::PlanStep;
-use crate::storage::{Database, Node, Edge, NodeId, Value, SimilarityResult};
+use super::spill::ExternalSort;
+use crate::error::CodedError;
+use crate::storage::{Database, FieldIndexKind, MemoryReservation, Node, Edge, NodeId, Value, SimilarityResult};
+
+/// Error returned when a query runs past its deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Query deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Fail once the deadline (if any) has passed
+fn check_deadline(deadline: Option<Instant>) -> Result<()> {
+    match deadline {
+        Some(deadline) if Instant::now() >= deadline => Err(DeadlineExceeded.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Record a query's row count, or its error, on the current span
+fn record_outcome(result: &Result<QueryResult>) {
+    let span = tracing::Span::current();
+    match result {
+        Ok(result) => span.record("rows", result.rows.len().max(result.rows_affected as usize)),
+        Err(e) => span.record("error", e.to_string()),
+    };
+}
+
+/// Fail if the query has placeholders, which only prepared statements bind
+fn check_bound(query: &ParsedQuery) -> Result<()> {
+    if !query.params.is_empty() {
+        bail!(
+            "Query has {} unbound parameters; prepare it and execute it with parameters",
+            query.param_count()
+        );
+    }
+    Ok(())
+}
+
+/// Fewest frontier nodes read per batch during a traversal
+const PREFETCH_MIN_BATCH: usize = 16;
+/// Most frontier nodes read per batch during a traversal
+const PREFETCH_MAX_BATCH: usize = 512;
+/// Edges a traversal aims to read per batch
+const PREFETCH_EDGES_PER_BATCH: f64 = 1024.0;
+
+/// Batch size for reading a frontier's node records, spreading them over
+/// the worker threads
+fn node_batch_size(frontier: usize) -> usize {
+    frontier
+        .div_ceil(rayon::current_num_threads())
+        .clamp(PREFETCH_MIN_BATCH, PREFETCH_MAX_BATCH)
+}
+
+/// Batch size for reading a frontier's edges, given the average number of
+/// edges per node seen at the previous depth
+///
+/// Densely connected frontiers get smaller batches so that each batch
+/// does a similar amount of work.
+fn edge_batch_size(frontier: usize, out_degree: f64) -> usize {
+    let by_edges = (PREFETCH_EDGES_PER_BATCH / out_degree.max(1.0)).max(1.0) as usize;
+    node_batch_size(frontier).min(by_edges)
+}
+
+/// A SELECT answered by scanning one type
+struct StreamedScan<'a> {
+    node_type: &'a str,
+    conditions: Vec<Condition>,
+    offset: usize,
+    count: usize,
+}
 
 /// Query executor
 pub struct QueryEngine {
-    db: Database,
+    db: Arc<Database>,
     parser: QueryParser,
     planner: QueryPlanner,
+    plans: Arc<PlanCache>,
 }
 
 impl QueryEngine {
     /// Create a new query engine
     pub fn new(db: Database) -> Self {
+        Self::from_shared(Arc::new(db))
+    }
+
+    /// Create a query engine over a database shared with other components
+    pub fn from_shared(db: Arc<Database>) -> Self {
         Self {
             db,
             parser: QueryParser::new(),
             planner: QueryPlanner::new(),
+            plans: Arc::new(PlanCache::default()),
         }
     }
 
+    /// Share a plan cache with other engines, e.g. ones created per query
+    pub fn with_plan_cache(mut self, plans: Arc<PlanCache>) -> Self {
+        self.plans = plans;
+        self
+    }
+
+    /// Parsed and planned SQL kept across `execute_sql` calls
+    pub fn plan_cache(&self) -> &PlanCache {
+        &self.plans
+    }
+
     /// Execute a SQL query
     pub async fn execute_sql(&self, sql: &str, limit: Option<usize>) -> Result<QueryResult> {
-        let start = Instant::now();
+        self.execute_sql_until(sql, limit, None).await
+    }
 
-        // Parse SQL
-        let mut query = self.parser.parse(sql)?;
+    /// Execute a SQL query, giving up with `DeadlineExceeded` once the
+    /// deadline passes
+    ///
+    /// The deadline is checked between plan steps. The rows an UPDATE or
+    /// DELETE matches are written in one transaction, so a write that
+    /// times out is either fully applied or not at all.
+    #[tracing::instrument(name = "query", skip_all, fields(sql = %sql, rows = Empty, error = Empty))]
+    pub async fn execute_sql_until(
+        &self,
+        sql: &str,
+        limit: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<QueryResult> {
+        let result = self.run_sql(sql, limit, deadline).await;
+        record_outcome(&result);
+        result
+    }
+
+    async fn run_sql(&self, sql: &str, limit: Option<usize>, deadline: Option<Instant>) -> Result<QueryResult> {
+        let start = Instant::now();
+        let mut stats = QueryStats::default();
+        let cache_hits = self.db.cache_stats().hits;
+
+        // Parse and plan SQL, unless an identical statement already was
+        let statement = match self.plans.get(sql) {
+            Some(statement) => {
+                stats.plan_cached = true;
+                statement
+            }
+            None => {
+                let stage = Instant::now();
+                let query = tracing::debug_span!("query.parse").in_scope(|| self.parser.parse(sql))?;
+                check_bound(&query)?;
+                stats.record_stage("parse", stage.elapsed());
+
+                let stage = Instant::now();
+                let plan = tracing::debug_span!("query.plan").in_scope(|| self.planner.plan(&query))?;
+                stats.record_stage("plan", stage.elapsed());
+
+                let statement = Arc::new(PreparedStatement { sql: sql.to_string(), query, plan });
+                self.plans.insert(Arc::clone(&statement));
+                statement
+            }
+        };
 
         // Apply external limit if provided
+        let mut query = statement.query.clone();
         if let Some(l) = limit {
             query.limit = Some(query.limit.map(|ql| ql.min(l)).unwrap_or(l));
         }
 
         // Handle vector search separately
         if query.operation == QueryOperation::VectorSearch {
-            let results = self.execute_vector_search(&query).await?;
-            let mut result = self.vector_results_to_query_result(results).await?;
-            result.execution_time_ms = start.elapsed().as_millis() as u64;
-            return Ok(result);
+            let result = self.run_vector_search(&query, &mut stats).await?;
+            return Ok(self.finish(result, stats, start, cache_hits));
         }
 
-        // Plan and execute
+        let plan = statement.plan.bind(&query);
+        let result = self.execute_plan(&plan, &query, deadline, &mut stats).await?;
+
+        Ok(self.finish(result, stats, start, cache_hits))
+    }
+
+    /// Parse and plan a SQL statement for repeated execution
+    ///
+    /// The statement may contain `$1`-style or `?` placeholders, bound by
+    /// `execute_prepared`.
+    pub fn prepare(&self, sql: &str) -> Result<PreparedStatement> {
+        let query = self.parser.parse(sql)?;
         let plan = self.planner.plan(&query)?;
-        let mut result = self.execute_plan(&plan, &query).await?;
+        Ok(PreparedStatement {
+            sql: sql.to_string(),
+            query,
+            plan,
+        })
+    }
 
-        result.execution_time_ms = start.elapsed().as_millis() as u64;
-        Ok(result)
+    /// Execute a prepared statement with its parameters
+    ///
+    /// Deadlines behave as in `execute_sql_until`.
+    #[tracing::instrument(name = "query", skip_all, fields(sql = %statement.sql, rows = Empty, error = Empty))]
+    pub async fn execute_prepared(
+        &self,
+        statement: &PreparedStatement,
+        params: &[Value],
+        limit: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<QueryResult> {
+        let result = self.run_prepared(statement, params, limit, deadline).await;
+        record_outcome(&result);
+        result
+    }
+
+    async fn run_prepared(
+        &self,
+        statement: &PreparedStatement,
+        params: &[Value],
+        limit: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<QueryResult> {
+        let start = Instant::now();
+        let mut stats = QueryStats::default();
+        let cache_hits = self.db.cache_stats().hits;
+
+        let stage = Instant::now();
+        let mut query = statement.query.bind(params)?;
+        if let Some(l) = limit {
+            query.limit = Some(query.limit.map(|ql| ql.min(l)).unwrap_or(l));
+        }
+        stats.record_stage("bind", stage.elapsed());
+
+        if query.operation == QueryOperation::VectorSearch {
+            let result = self.run_vector_search(&query, &mut stats).await?;
+            return Ok(self.finish(result, stats, start, cache_hits));
+        }
+
+        let plan = statement.plan.bind(&query);
+        let result = self.execute_plan(&plan, &query, deadline, &mut stats).await?;
+
+        Ok(self.finish(result, stats, start, cache_hits))
     }
 
     /// Execute a parsed query
     pub async fn execute_parsed(&self, query: &ParsedQuery, limit: Option<usize>) -> Result<QueryResult> {
         let start = Instant::now();
+        let mut stats = QueryStats::default();
+        let cache_hits = self.db.cache_stats().hits;
 
+        check_bound(query)?;
         let mut query = query.clone();
         if let Some(l) = limit {
             query.limit = Some(query.limit.map(|ql| ql.min(l)).unwrap_or(l));
@@ -69,24 +266,51 @@ impl QueryEngine {
 
         // Handle vector search separately
         if query.operation == QueryOperation::VectorSearch {
-            let results = self.execute_vector_search(&query).await?;
-            let mut result = self.vector_results_to_query_result(results).await?;
-            result.execution_time_ms = start.elapsed().as_millis() as u64;
-            return Ok(result);
+            let result = self.run_vector_search(&query, &mut stats).await?;
+            return Ok(self.finish(result, stats, start, cache_hits));
         }
 
+        let stage = Instant::now();
         let plan = self.planner.plan(&query)?;
-        let mut result = self.execute_plan(&plan, &query).await?;
+        stats.record_stage("plan", stage.elapsed());
+        let result = self.execute_plan(&plan, &query, None, &mut stats).await?;
 
+        Ok(self.finish(result, stats, start, cache_hits))
+    }
+
+    /// Run a vector search, timing it as the `search` stage
+    async fn run_vector_search(&self, query: &ParsedQuery, stats: &mut QueryStats) -> Result<QueryResult> {
+        let stage = Instant::now();
+        let results = self.execute_vector_search(query).await?;
+        stats.rows_scanned = results.len() as u64;
+        stats.record_stage("search", stage.elapsed());
+        self.vector_results_to_query_result(results).await
+    }
+
+    /// Attach the statistics and total time to a result
+    fn finish(&self, mut result: QueryResult, mut stats: QueryStats, start: Instant, cache_hits: u64) -> QueryResult {
+        stats.cache_hits = self.db.cache_stats().hits.saturating_sub(cache_hits);
+        result.stats = stats;
         result.execution_time_ms = start.elapsed().as_millis() as u64;
-        Ok(result)
+        result
     }
 
     /// Execute a vector search query
+    #[tracing::instrument(name = "query.vector_search", level = "debug", skip_all)]
     pub async fn execute_vector_search(&self, query: &ParsedQuery) -> Result<Vec<SimilarityResult>> {
         let params = query.vector_search.as_ref()
             .ok_or_else(|| anyhow::anyhow!("Missing vector search parameters"))?;
 
+        if !params.fields.is_empty() {
+            return self.db.similarity_search_weighted(
+                &params.query_vector,
+                &query.target,
+                &params.fields,
+                params.k,
+                params.metric,
+            ).await;
+        }
+
         self.db.similarity_search(
             &params.query_vector,
             &query.target,
@@ -125,25 +349,56 @@ impl QueryEngine {
             rows,
             rows_affected: 0,
             execution_time_ms: 0,
+            stats: QueryStats::default(),
         })
     }
 
     /// Execute a query plan
-    async fn execute_plan(&self, plan: &QueryPlan, query: &ParsedQuery) -> Result<QueryResult> {
+    #[tracing::instrument(name = "query.execute", level = "debug", skip_all, fields(steps = plan.steps.len()))]
+    async fn execute_plan(
+        &self,
+        plan: &QueryPlan,
+        query: &ParsedQuery,
+        deadline: Option<Instant>,
+        stats: &mut QueryStats,
+    ) -> Result<QueryResult> {
         let mut nodes: Option<Vec<Node>> = None;
         let mut insert_result: Option<Node> = None;
         let mut rows_affected: u64 = 0;
+        // Memory held by the scanned nodes under a memory limit
+        let mut _memory: Option<MemoryReservation> = None;
 
         for step in &plan.steps {
+            check_deadline(deadline)?;
+            let stage = Instant::now();
             match step {
                 PlanStep::FullScan { node_type } => {
-                    nodes = Some(self.db.get_all_by_type(node_type, None).await?);
+                    // An equality on a field with a property index narrows
+                    // the scan; the filter step still checks every condition
+                    let scanned = match self.index_lookup(node_type, query) {
+                        Some(condition) => {
+                            stats.index_used = Some(format!("{}.{}", node_type, condition.column));
+                            self.db.find_by_property(node_type, &condition.column, &condition.value).await?
+                        }
+                        None if self.db.memory().limit().is_some() => {
+                            let (scanned, reserved) = self.scan_within_budget(plan, node_type, stats)?;
+                            _memory = Some(reserved);
+                            nodes = Some(scanned);
+                            stats.record_stage(step.stage(), stage.elapsed());
+                            continue;
+                        }
+                        None => self.db.get_all_by_type(node_type, None).await?,
+                    };
+                    stats.rows_scanned += scanned.len() as u64;
+                    nodes = Some(scanned);
                 }
 
                 PlanStep::IndexLookup { node_type, field: _, value: _ } => {
                     // For now, fall back to full scan + filter
                     // TODO: Implement actual index lookup
-                    nodes = Some(self.db.get_all_by_type(node_type, None).await?);
+                    let scanned = self.db.get_all_by_type(node_type, None).await?;
+                    stats.rows_scanned += scanned.len() as u64;
+                    nodes = Some(scanned);
                 }
 
                 PlanStep::Filter { conditions } => {
@@ -180,29 +435,43 @@ impl QueryEngine {
                     let _ = columns;
                 }
 
-                PlanStep::InsertNode { node_type, data } => {
-                    let props = Value::Object(data.clone());
-                    let node = self.db.insert_node(node_type, props.to_json()).await?;
-                    insert_result = Some(node);
-                    rows_affected = 1;
+                PlanStep::InsertNode { node_type, data, on_conflict } => {
+                    let existing = match on_conflict {
+                        Some(conflict) => self.find_conflict(node_type, data, &conflict.columns).await?,
+                        None => None,
+                    };
+                    match (existing, on_conflict.as_ref().and_then(|c| c.update.as_ref())) {
+                        (Some(existing), Some(update)) => {
+                            let props = Value::Object(update.clone());
+                            let node = self.db.update_node(&existing.id.to_string(), props.to_json()).await?;
+                            insert_result = Some(node);
+                            rows_affected = 1;
+                        }
+                        // ON CONFLICT DO NOTHING
+                        (Some(_), None) => {}
+                        (None, _) => {
+                            let props = Value::Object(data.clone());
+                            let node = self.db.insert_node(node_type, props.to_json()).await?;
+                            insert_result = Some(node);
+                            rows_affected = 1;
+                        }
+                    }
                 }
 
+                // Writes commit in one transaction, so a request cancelled
+                // at its deadline leaves either every row or none changed
                 PlanStep::UpdateNodes { data } => {
                     if let Some(ref n) = nodes {
-                        for node in n {
-                            let props = Value::Object(data.clone());
-                            self.db.update_node(&node.id.to_string(), props.to_json()).await?;
-                            rows_affected += 1;
-                        }
+                        let props = Value::Object(data.clone());
+                        self.db.update_nodes(n, props.to_json()).await?;
+                        rows_affected = n.len() as u64;
                     }
                 }
 
                 PlanStep::DeleteNodes => {
                     if let Some(ref n) = nodes {
-                        for node in n {
-                            self.db.delete_node(&node.id.to_string()).await?;
-                            rows_affected += 1;
-                        }
+                        self.db.delete_nodes(n).await?;
+                        rows_affected = n.len() as u64;
                     }
                 }
 
@@ -210,9 +479,11 @@ impl QueryEngine {
                     // Handled separately by traverse method
                 }
             }
+            stats.record_stage(step.stage(), stage.elapsed());
         }
 
         // Build result
+        let stage = Instant::now();
         let mut result = if let Some(node) = insert_result {
             QueryResult::from_nodes(vec![node])
         } else if let Some(n) = nodes {
@@ -243,25 +514,207 @@ impl QueryEngine {
         };
 
         result.rows_affected = rows_affected;
+        stats.record_stage("project", stage.elapsed());
         Ok(result)
     }
 
-    /// Check if a node matches all conditions
-    fn matches_conditions(&self, node: &Node, conditions: &[Condition]) -> bool {
-        for condition in conditions {
-            let value = if condition.column == "id" {
-                Value::String(node.id.to_string())
-            } else if condition.column == "type" {
-                Value::String(node.node_type.clone())
-            } else {
-                node.get(&condition.column).cloned().unwrap_or(Value::Null)
-            };
-
-            if !condition.operator.matches(&value, &condition.value) {
-                return false;
+    /// Scan a type without going past the database's memory limit
+    ///
+    /// Filter conditions are applied as nodes are read, so only matching
+    /// nodes are held and reserved. Under an ORDER BY, they are sorted as
+    /// they are read, spilling sorted runs to disk when the buffer outgrows
+    /// the limit, and only the rows up to the LIMIT are kept; without one,
+    /// the scan stops once the LIMIT is reached. The later filter, sort and
+    /// limit steps then see just those. A scan whose matches alone are too
+    /// large for the limit fails.
+    fn scan_within_budget(
+        &self,
+        plan: &QueryPlan,
+        node_type: &str,
+        stats: &mut QueryStats,
+    ) -> Result<(Vec<Node>, MemoryReservation)> {
+        let memory = self.db.memory();
+        let conditions = plan.steps.iter().find_map(|step| match step {
+            PlanStep::Filter { conditions } => Some(conditions.as_slice()),
+            _ => None,
+        }).unwrap_or_default();
+        let keep = plan.steps.iter().find_map(|step| match step {
+            PlanStep::Limit { count, offset } => Some(count.saturating_add(*offset)),
+            _ => None,
+        });
+        let sort = plan.steps.iter().find_map(|step| match step {
+            PlanStep::Sort { field, descending } => Some((field, *descending)),
+            _ => None,
+        });
+        let Some((field, descending)) = sort else {
+            let mut reserved = memory.reservation(format!("scanning {}", node_type));
+            let mut nodes = Vec::new();
+            self.db.scan_by_type(node_type, |node| {
+                stats.rows_scanned += 1;
+                if self.matches_conditions(&node, conditions) {
+                    reserved.grow(node.memory_size() as u64)?;
+                    nodes.push(node);
+                }
+                Ok(!keep.is_some_and(|keep| nodes.len() >= keep))
+            })?;
+            return Ok((nodes, reserved));
+        };
+
+        let compare = |a: &Node, b: &Node| {
+            let cmp = self.compare_values(a.get(field).unwrap_or(&Value::Null), b.get(field).unwrap_or(&Value::Null));
+            if descending { cmp.reverse() } else { cmp }
+        };
+        let mut sorter = ExternalSort::new(
+            compare,
+            memory.reservation(format!("sorting {} by {}", node_type, field)),
+            &self.db.path().join(".aresadb/spill"),
+        );
+        self.db.scan_by_type(node_type, |node| {
+            stats.rows_scanned += 1;
+            if self.matches_conditions(&node, conditions) {
+                sorter.push(node)?;
             }
+            Ok(true)
+        })?;
+        stats.spilled_runs += sorter.spilled_runs() as u64;
+        sorter.finish(0, keep.unwrap_or(usize::MAX))
+    }
+
+    /// An equality condition that a property index can answer
+    fn index_lookup<'a>(&self, node_type: &str, query: &'a ParsedQuery) -> Option<&'a Condition> {
+        let indexed = self.db.field_indexes();
+        query.conditions.iter().find(|c| {
+            c.operator == Operator::Eq
+                && !c.value.is_null()
+                && indexed.iter().any(|spec| {
+                    spec.node_type == node_type
+                        && spec.field == c.column
+                        && spec.kind == FieldIndexKind::Property
+                })
+        })
+    }
+
+    /// Execute a SQL query, handing each row to `on_row` as it is read
+    ///
+    /// A SELECT without ORDER BY that no property index can answer scans
+    /// its type without collecting the nodes, so memory stays flat however
+    /// many rows match; such rows hold only the properties a node has.
+    /// The scan filters archived nodes in place and deserializes only the
+    /// compared properties and the columns of rows it sends.
+    /// Other statements run as `execute_sql` does and their rows are
+    /// handed over afterwards, leaving out NULL columns. Returns the
+    /// number of rows affected by a write.
+    pub async fn stream_sql<F>(&self, sql: &str, limit: Option<usize>, mut on_row: F) -> Result<u64>
+    where
+        F: FnMut(Vec<(String, Value)>) -> Result<()>,
+    {
+        let mut query = self.parser.parse(sql)?;
+        check_bound(&query)?;
+        if let Some(l) = limit {
+            query.limit = Some(query.limit.map(|ql| ql.min(l)).unwrap_or(l));
         }
-        true
+
+        let plan = match query.operation {
+            QueryOperation::Select => Some(self.planner.plan(&query)?),
+            _ => None,
+        };
+        let Some(scan) = plan.as_ref().and_then(|plan| self.streamable_scan(plan, &query)) else {
+            let result = self.execute_parsed(&query, None).await?;
+            for row in result.rows {
+                on_row(result.columns.iter().cloned().zip(row).filter(|(_, v)| !v.is_null()).collect())?;
+            }
+            return Ok(result.rows_affected);
+        };
+
+        let (mut skipped, mut sent) = (0, 0);
+        self.db.scan_archived_by_type(scan.node_type, |node| {
+            if !scan.conditions.iter().all(|condition| condition.matches_archived(node)) {
+                return Ok(true);
+            }
+            if skipped < scan.offset {
+                skipped += 1;
+                return Ok(true);
+            }
+            if sent >= scan.count {
+                return Ok(false);
+            }
+            let mut row = vec![
+                ("id".to_string(), Value::String(node.node_id().to_string())),
+                ("type".to_string(), Value::String(node.node_type.to_string())),
+            ];
+            row.extend(
+                node.properties
+                    .iter()
+                    .filter(|(key, _)| query.columns.is_empty() || query.columns.iter().any(|c| c == key.as_str()))
+                    .map(|(key, value)| (key.to_string(), value.to_value())),
+            );
+            on_row(row)?;
+            sent += 1;
+            Ok(sent < scan.count)
+        })?;
+        Ok(0)
+    }
+
+    /// The scan a plan reduces to, when its rows can be produced one at a time
+    fn streamable_scan<'a>(&self, plan: &'a QueryPlan, query: &ParsedQuery) -> Option<StreamedScan<'a>> {
+        let mut scan = StreamedScan { node_type: "", conditions: Vec::new(), offset: 0, count: usize::MAX };
+        for step in &plan.steps {
+            match step {
+                PlanStep::FullScan { node_type } if self.index_lookup(node_type, query).is_none() => {
+                    scan.node_type = node_type;
+                }
+                PlanStep::Filter { conditions } => scan.conditions.extend(conditions.iter().cloned()),
+                PlanStep::Limit { count, offset } => {
+                    scan.count = *count;
+                    scan.offset = *offset;
+                }
+                PlanStep::Project { .. } => {}
+                _ => return None,
+            }
+        }
+        (!scan.node_type.is_empty()).then_some(scan)
+    }
+
+    /// The stored node an upsert collides with
+    ///
+    /// With explicit conflict columns, a node matching all of them; without,
+    /// a node sharing any unique field of the type's schema.
+    async fn find_conflict(
+        &self,
+        node_type: &str,
+        data: &BTreeMap<String, Value>,
+        columns: &[String],
+    ) -> Result<Option<Node>> {
+        let value = |column: &str| data.get(column).filter(|v| !v.is_null());
+
+        if columns.is_empty() {
+            let unique = self.db.unique_fields(node_type).await?;
+            if unique.is_empty() {
+                bail!("ON CONFLICT without columns needs unique fields, and '{}' has none", node_type);
+            }
+            for field in &unique {
+                if let Some(v) = value(field) {
+                    if let Some(node) = self.db.find_by_property(node_type, field, v).await?.into_iter().next() {
+                        return Ok(Some(node));
+                    }
+                }
+            }
+            return Ok(None);
+        }
+
+        let Some(key) = columns.iter().map(|c| value(c)).collect::<Option<Vec<_>>>() else {
+            // A NULL key never conflicts
+            return Ok(None);
+        };
+        let candidates = self.db.find_by_property(node_type, &columns[0], key[0]).await?;
+        Ok(candidates.into_iter().find(|node| {
+            columns.iter().zip(&key).all(|(column, v)| node.properties.get(column) == Some(*v))
+        }))
+    }
+
+    /// Check if a node matches all conditions
+    fn matches_conditions(&self, node: &Node, conditions: &[Condition]) -> bool {
+        conditions.iter().all(|condition| condition.matches_node(node))
     }
 
     /// Compare two values for sorting
@@ -281,6 +734,7 @@ impl QueryEngine {
     }
 
     /// Perform graph traversal from a starting node
+    #[tracing::instrument(name = "traverse", skip(self, edge_types), fields(nodes = Empty))]
     pub async fn traverse(
         &self,
         start_node_id: &str,
@@ -289,63 +743,65 @@ impl QueryEngine {
     ) -> Result<TraversalResult> {
         let start_id = NodeId::parse(start_node_id)?;
         let root = self.db.get_node(start_node_id).await?
-            .ok_or_else(|| anyhow::anyhow!("Start node not found: {}", start_node_id))?;
+            .ok_or_else(|| CodedError::not_found(format!("Start node not found: {}", start_node_id)))?;
 
         let mut visited_nodes: BTreeMap<String, Node> = BTreeMap::new();
         let mut all_edges: Vec<Edge> = Vec::new();
         let mut adjacency: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
-        // BFS traversal
-        let mut queue: VecDeque<(NodeId, u32)> = VecDeque::new();
+        // Breadth-first, a depth at a time: each frontier's node records and
+        // edges are read in parallel batches rather than one lookup at a time
         let mut visited_ids: HashSet<String> = HashSet::new();
-
-        queue.push_back((start_id, 0));
-
-        while let Some((current_id, depth)) = queue.pop_front() {
-            let id_str = current_id.to_string();
-
-            if visited_ids.contains(&id_str) {
-                continue;
-            }
-            visited_ids.insert(id_str.clone());
-
-            // Get the node
-            if let Some(node) = self.db.get_node(&id_str).await? {
-                visited_nodes.insert(id_str.clone(), node);
+        visited_ids.insert(start_id.to_string());
+        let mut frontier = vec![start_id];
+        let mut depth = 0;
+        // Edges per node at the previous depth, sizing the next edge batches
+        let mut out_degree = 1.0;
+
+        while !frontier.is_empty() {
+            for node in self.db.get_nodes(&frontier, node_batch_size(frontier.len())).await? {
+                visited_nodes.insert(node.id.to_string(), node);
             }
 
             // Stop if max depth reached
             if depth >= max_depth {
-                continue;
+                break;
             }
 
-            // Get outgoing edges
-            let edges = self.db.get_edges_from(&id_str, None).await?;
+            let batch_size = edge_batch_size(frontier.len(), out_degree);
+            let edge_lists = self.db.get_edges_from_many(&frontier, None, batch_size).await?;
+            let edge_count: usize = edge_lists.iter().map(Vec::len).sum();
+            out_degree = edge_count as f64 / frontier.len() as f64;
 
-            let mut neighbors = Vec::new();
+            let mut next = Vec::new();
+            for (id, edges) in frontier.iter().zip(edge_lists) {
+                let mut neighbors = Vec::new();
 
-            for edge in edges {
-                // Filter by edge type if specified
-                if let Some(ref types) = edge_types {
-                    if !types.contains(&edge.edge_type.as_str()) {
-                        continue;
+                for edge in edges {
+                    // Filter by edge type if specified
+                    if let Some(ref types) = edge_types {
+                        if !types.contains(&edge.edge_type.as_str()) {
+                            continue;
+                        }
                     }
-                }
 
-                let to_str = edge.to.to_string();
-                neighbors.push(to_str.clone());
-
-                if !visited_ids.contains(&to_str) {
-                    queue.push_back((edge.to.clone(), depth + 1));
+                    let to_str = edge.to.to_string();
+                    if visited_ids.insert(to_str.clone()) {
+                        next.push(edge.to.clone());
+                    }
+                    neighbors.push(to_str);
+                    all_edges.push(edge);
                 }
 
-                all_edges.push(edge);
+                adjacency.insert(id.to_string(), neighbors);
             }
 
-            adjacency.insert(id_str, neighbors);
+            frontier = next;
+            depth += 1;
         }
 
         let nodes: Vec<Node> = visited_nodes.into_values().collect();
+        tracing::Span::current().record("nodes", nodes.len());
 
         Ok(TraversalResult {
             root,
@@ -357,6 +813,7 @@ impl QueryEngine {
     }
 
     /// Find shortest path between two nodes
+    #[tracing::instrument(name = "shortest_path", skip(self))]
     pub async fn shortest_path(
         &self,
         from_id: &str,
@@ -407,6 +864,7 @@ impl QueryEngine {
     }
 
     /// Get connected components
+    #[tracing::instrument(name = "connected_components", skip(self))]
     pub async fn connected_components(&self, node_type: &str) -> Result<Vec<Vec<Node>>> {
         let all_nodes = self.db.get_all_by_type(node_type, None).await?;
         let mut visited: HashSet<String> = HashSet::new();
@@ -467,6 +925,7 @@ impl QueryEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::query::PlanCacheStats;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -492,6 +951,170 @@ mod tests {
         assert_eq!(result.row_count(), 1);
     }
 
+    #[tokio::test]
+    async fn test_query_stats() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 30)] {
+            db.insert_node("user", serde_json::json!({"name": name, "age": age})).await.unwrap();
+        }
+        db.create_field_index("user", "name", FieldIndexKind::Property, |_| {}).await.unwrap();
+        let engine = QueryEngine::new(db);
+
+        let result = engine.execute_sql("SELECT * FROM user WHERE age = 30 ORDER BY name", None).await.unwrap();
+        assert_eq!(result.stats.rows_scanned, 3);
+        assert_eq!(result.stats.index_used, None);
+        let stages: Vec<&str> = result.stats.stages.iter().map(|s| s.stage.as_str()).collect();
+        assert_eq!(stages[..5], ["parse", "plan", "scan", "filter", "sort"]);
+
+        let result = engine.execute_sql("SELECT * FROM user WHERE name = 'Bob'", None).await.unwrap();
+        assert_eq!(result.stats.rows_scanned, 1);
+        assert_eq!(result.stats.index_used.as_deref(), Some("user.name"));
+        assert!(result.stats.summary().starts_with("scanned 1 rows via user.name, 0 cache hits; parse "));
+    }
+
+    #[tokio::test]
+    async fn test_plan_cache() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 30)] {
+            db.insert_node("user", serde_json::json!({"name": name, "age": age})).await.unwrap();
+        }
+        let engine = QueryEngine::new(db);
+
+        let result = engine.execute_sql("SELECT * FROM user WHERE age = 30", None).await.unwrap();
+        assert!(!result.stats.plan_cached);
+        assert_eq!(result.row_count(), 2);
+
+        let result = engine.execute_sql("SELECT *  FROM user\n WHERE age = 30;", Some(1)).await.unwrap();
+        assert!(result.stats.plan_cached);
+        assert_eq!(result.row_count(), 1);
+        assert!(!result.stats.stages.iter().any(|s| s.stage == "parse"));
+        assert_eq!(engine.plan_cache().stats(), PlanCacheStats { entries: 1, hits: 1, misses: 1 });
+
+        engine.plan_cache().flush();
+        let result = engine.execute_sql("SELECT * FROM user WHERE age = 30", None).await.unwrap();
+        assert!(!result.stats.plan_cached);
+        assert_eq!(result.row_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for n in 0..200 {
+            db.insert_node("item", serde_json::json!({"n": n, "pad": "x".repeat(100)})).await.unwrap();
+        }
+        drop(db);
+        let config_path = temp.path().join(".aresadb/config.toml");
+        let mut config: toml::Table = toml::from_str(&std::fs::read_to_string(&config_path).unwrap()).unwrap();
+        config.insert("memory_limit_bytes".to_string(), toml::Value::Integer(32 * 1024));
+        std::fs::write(&config_path, toml::to_string(&config).unwrap()).unwrap();
+        let engine = QueryEngine::new(Database::open(temp.path()).await.unwrap());
+
+        let result = engine.execute_sql("SELECT * FROM item ORDER BY n DESC LIMIT 3", None).await.unwrap();
+        assert!(result.stats.spilled_runs > 0);
+        assert_eq!(result.stats.rows_scanned, 200);
+        let n = result.columns.iter().position(|c| c == "n").unwrap();
+        let ns: Vec<_> = result.rows.iter().map(|row| row[n].clone()).collect();
+        assert_eq!(ns, vec![Value::Int(199), Value::Int(198), Value::Int(197)]);
+        assert!(!temp.path().join(".aresadb/spill").read_dir().unwrap().any(|_| true));
+
+        let err = engine.execute_sql("SELECT * FROM item", None).await.unwrap_err();
+        assert!(err.is::<crate::storage::MemoryLimitExceeded>());
+        assert!(err.to_string().contains("scanning item"));
+
+        // Only matching nodes are held, so selective statements fit
+        let result = engine.execute_sql("SELECT * FROM item WHERE n >= 198", None).await.unwrap();
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.stats.rows_scanned, 200);
+        let result = engine.execute_sql("UPDATE item SET pad = 'y' WHERE n = 5", None).await.unwrap();
+        assert_eq!(result.rows_affected, 1);
+        let result = engine.execute_sql("DELETE FROM item WHERE n < 3", None).await.unwrap();
+        assert_eq!(result.rows_affected, 3);
+    }
+
+    #[tokio::test]
+    async fn test_upsert() {
+        let temp = TempDir::new().unwrap();
+        let db = Arc::new(Database::create(temp.path(), "test").await.unwrap());
+        crate::schema::SchemaManager::from_shared(Arc::clone(&db))
+            .create_schema("users", "email:string unique, name:string")
+            .await
+            .unwrap();
+        let engine = QueryEngine::from_shared(Arc::clone(&db));
+
+        engine.execute_sql("INSERT INTO users (email, name) VALUES ('a@example.com', 'Ada')", None).await.unwrap();
+        let err = engine
+            .execute_sql("INSERT INTO users (email, name) VALUES ('a@example.com', 'Bob')", None)
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::classify(&err), crate::error::ErrorCode::Conflict);
+
+        let upsert = "INSERT INTO users (email, name) VALUES ('a@example.com', 'Ada L') ON CONFLICT (email) DO UPDATE SET name = EXCLUDED.name";
+        assert_eq!(engine.execute_sql(upsert, None).await.unwrap().rows_affected, 1);
+        let nothing = "INSERT INTO users (email, name) VALUES ('a@example.com', 'Eve') ON CONFLICT DO NOTHING";
+        assert_eq!(engine.execute_sql(nothing, None).await.unwrap().rows_affected, 0);
+
+        let users = db.get_all_by_type("users", None).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].get("name"), Some(&Value::String("Ada L".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_stream_sql() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for age in 20..30 {
+            db.insert_node("user", serde_json::json!({"name": format!("u{}", age), "age": age})).await.unwrap();
+        }
+        let engine = QueryEngine::new(db);
+
+        let mut rows = Vec::new();
+        let affected = engine
+            .stream_sql("SELECT name FROM user WHERE age >= 25 LIMIT 3", None, |row| {
+                rows.push(row);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(affected, 0);
+        assert_eq!(rows.len(), 3);
+        let columns: Vec<_> = rows[0].iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(columns, ["id", "type", "name"]);
+
+        // Sorted queries are collected first and then handed over
+        let mut ages = Vec::new();
+        engine
+            .stream_sql("SELECT * FROM user ORDER BY age DESC", Some(2), |row| {
+                ages.extend(row.into_iter().filter(|(c, _)| c == "age").map(|(_, v)| v));
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(ages, [Value::Int(29), Value::Int(28)]);
+
+        let affected = engine.stream_sql("DELETE FROM user WHERE age < 22", None, |_| Ok(())).await.unwrap();
+        assert_eq!(affected, 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_deadline() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        db.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        let engine = QueryEngine::new(db);
+
+        let expired = Some(Instant::now());
+        let err = engine.execute_sql_until("DELETE FROM user", None, expired).await.unwrap_err();
+        assert!(err.is::<DeadlineExceeded>());
+        assert_eq!(engine.execute_sql("SELECT * FROM user", None).await.unwrap().row_count(), 1);
+
+        let later = Some(Instant::now() + std::time::Duration::from_secs(60));
+        let result = engine.execute_sql_until("SELECT * FROM user", None, later).await.unwrap();
+        assert_eq!(result.row_count(), 1);
+    }
+
     #[tokio::test]
     async fn test_execute_insert() {
         let temp = TempDir::new().unwrap();
@@ -506,6 +1129,29 @@ mod tests {
         assert_eq!(result.rows_affected, 1);
     }
 
+    #[tokio::test]
+    async fn test_execute_prepared() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let engine = QueryEngine::new(db);
+
+        let insert = engine.prepare("INSERT INTO user (name, age) VALUES ($1, $2)").unwrap();
+        for (name, age) in [("Alice", 30), ("Bob", 25), ("Carol", 41)] {
+            let params = [Value::String(name.to_string()), Value::Int(age)];
+            engine.execute_prepared(&insert, &params, None, None).await.unwrap();
+        }
+
+        let select = engine.prepare("SELECT * FROM user WHERE age > $1").unwrap();
+        assert_eq!(select.param_count(), 1);
+        let result = engine.execute_prepared(&select, &[Value::Int(28)], None, None).await.unwrap();
+        assert_eq!(result.row_count(), 2);
+        let result = engine.execute_prepared(&select, &[Value::Int(0)], Some(1), None).await.unwrap();
+        assert_eq!(result.row_count(), 1);
+
+        assert!(engine.execute_prepared(&select, &[], None, None).await.is_err());
+        assert!(engine.execute_sql("SELECT * FROM user WHERE age > $1", None).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_traverse() {
         let temp = TempDir::new().unwrap();
@@ -528,6 +1174,39 @@ mod tests {
         assert_eq!(result.nodes.len(), 3); // Alice, Bob, Charlie
         assert_eq!(result.edges.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_traverse_wide_frontier() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+
+        // A root with 100 children, each with two grandchildren
+        let root = db.insert_node("item", serde_json::json!({"level": 0})).await.unwrap();
+        let root_id = root.id.to_string();
+        for _ in 0..100 {
+            let child = db.insert_node("item", serde_json::json!({"level": 1})).await.unwrap();
+            db.create_edge(&root_id, &child.id.to_string(), "has", None).await.unwrap();
+            for _ in 0..2 {
+                let grandchild = db.insert_node("item", serde_json::json!({"level": 2})).await.unwrap();
+                db.create_edge(&child.id.to_string(), &grandchild.id.to_string(), "has", None).await.unwrap();
+            }
+        }
+        // A cycle back to the root is followed but doesn't revisit it
+        let first = db.get_edges_from(&root_id, None).await.unwrap()[0].to.to_string();
+        db.create_edge(&first, &root_id, "back", None).await.unwrap();
+
+        let engine = QueryEngine::new(db);
+        let result = engine.traverse(&root_id, 2, None).await.unwrap();
+        assert_eq!(result.nodes.len(), 301);
+        assert_eq!(result.edges.len(), 301);
+        assert_eq!(result.adjacency[&root_id].len(), 100);
+        assert_eq!(result.adjacency[&first].len(), 3);
+
+        let result = engine.traverse(&root_id, 1, Some(vec!["has"])).await.unwrap();
+        assert_eq!(result.nodes.len(), 101);
+
+        assert!(edge_batch_size(10_000, 1000.0) < node_batch_size(10_000));
+    }
 }
 
 
@@ -0,0 +1,306 @@
+//! Query Log
+//!
+//! Append-only JSON-lines log of executed queries. With full logging on,
+//! every query goes to `query.log`; queries slower than the configured
+//! threshold also go to `slow.log`. Both files live in `.aresadb/logs/`
+//! of the database and are read back by `aresadb log tail`. The files are
+//! written on a thread of their own, so recording a query never blocks the
+//! async runtime on disk I/O. The most recent slow queries are also kept in
+//! memory for the server's `/slow-queries` endpoint.
+
+use anyhow::{anyhow, Result, Context};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::storage::Timestamp;
+
+/// File receiving every logged query
+pub const QUERY_LOG_FILE: &str = "query.log";
+
+/// File receiving queries over the slow threshold
+pub const SLOW_LOG_FILE: &str = "slow.log";
+
+/// Slow queries kept in memory for `QueryLog::recent_slow`
+const RECENT_SLOW_CAPACITY: usize = 100;
+
+/// A single logged query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryLogEntry {
+    /// When the query finished
+    pub timestamp: Timestamp,
+    /// Database the query ran against
+    pub database: String,
+    /// Client address, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Authenticated user, when authentication is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// SQL text
+    pub query: String,
+    /// Wall-clock duration in milliseconds
+    pub duration_ms: f64,
+    /// Rows returned, or rows affected for writes
+    pub rows: u64,
+    /// Error message if the query failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What gets logged
+#[derive(Debug, Clone)]
+pub struct QueryLogConfig {
+    /// Write every query to `query.log`
+    pub log_all: bool,
+    /// Write queries at least this slow to `slow.log` (disabled when `None`)
+    pub slow_threshold: Option<Duration>,
+}
+
+impl Default for QueryLogConfig {
+    fn default() -> Self {
+        Self {
+            log_all: false,
+            slow_threshold: Some(Duration::from_millis(100)),
+        }
+    }
+}
+
+/// A line on its way to the log files
+enum LogWrite {
+    Line { line: String, slow: bool },
+    /// Answered once every earlier line is written
+    Flush(mpsc::Sender<()>),
+}
+
+/// Query log writer
+pub struct QueryLog {
+    dir: PathBuf,
+    config: QueryLogConfig,
+    recent_slow: Mutex<VecDeque<QueryLogEntry>>,
+    /// Lines for the writer thread; taken on drop to stop it
+    writes: Option<mpsc::Sender<LogWrite>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl QueryLog {
+    /// Log directory of a database
+    pub fn dir_for(db_path: impl AsRef<Path>) -> PathBuf {
+        db_path.as_ref().join(".aresadb/logs")
+    }
+
+    /// Open (creating if needed) the log files in a directory
+    pub fn open(dir: impl AsRef<Path>, config: QueryLogConfig) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).context("Failed to create log directory")?;
+
+        let all = config.log_all
+            .then(|| open_append(&dir.join(QUERY_LOG_FILE)))
+            .transpose()?;
+        let slow = config.slow_threshold
+            .map(|_| open_append(&dir.join(SLOW_LOG_FILE)))
+            .transpose()?;
+
+        let (writes, received) = mpsc::channel();
+        let writer = std::thread::Builder::new()
+            .name("aresadb-query-log".to_string())
+            .spawn(move || write_lines(received, all, slow))
+            .context("Failed to start query log writer")?;
+
+        Ok(Self {
+            dir,
+            config,
+            recent_slow: Mutex::new(VecDeque::new()),
+            writes: Some(writes),
+            writer: Some(writer),
+        })
+    }
+
+    /// Directory holding the log files
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether a query of this duration belongs in the slow log
+    pub fn is_slow(&self, duration: Duration) -> bool {
+        self.config.slow_threshold.is_some_and(|threshold| duration >= threshold)
+    }
+
+    /// Queue an entry for the relevant log files
+    ///
+    /// The entry is written in the background; `flush` waits for it.
+    pub fn record(&self, entry: &QueryLogEntry) -> Result<()> {
+        let duration = Duration::from_secs_f64(entry.duration_ms / 1000.0);
+        let slow = self.is_slow(duration);
+        if slow {
+            let mut recent = self.recent_slow.lock();
+            if recent.len() == RECENT_SLOW_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(entry.clone());
+        }
+
+        if self.config.log_all || slow {
+            let mut line = serde_json::to_string(entry)?;
+            line.push('\n');
+            self.send(LogWrite::Line { line, slow })?;
+        }
+        Ok(())
+    }
+
+    /// Wait until every entry recorded so far is written
+    pub fn flush(&self) -> Result<()> {
+        let (done, written) = mpsc::channel();
+        self.send(LogWrite::Flush(done))?;
+        written.recv().map_err(|_| anyhow!("Query log writer stopped"))
+    }
+
+    fn send(&self, write: LogWrite) -> Result<()> {
+        self.writes
+            .as_ref()
+            .and_then(|writes| writes.send(write).ok())
+            .ok_or_else(|| anyhow!("Query log writer stopped"))
+    }
+
+    /// The last `n` slow queries recorded since the log was opened, oldest
+    /// first
+    pub fn recent_slow(&self, n: usize) -> Vec<QueryLogEntry> {
+        let recent = self.recent_slow.lock();
+        recent.iter().skip(recent.len().saturating_sub(n)).cloned().collect()
+    }
+}
+
+impl Drop for QueryLog {
+    fn drop(&mut self) {
+        // Closing the channel stops the writer once the queued lines are out
+        self.writes.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Write queued lines until the log is dropped
+fn write_lines(received: mpsc::Receiver<LogWrite>, mut all: Option<File>, mut slow: Option<File>) {
+    for write in received {
+        match write {
+            LogWrite::Line { line, slow: is_slow } => {
+                let files = all.iter_mut().chain(slow.iter_mut().filter(|_| is_slow));
+                for file in files {
+                    if let Err(e) = file.write_all(line.as_bytes()) {
+                        tracing::warn!("Failed to write query log: {}", e);
+                    }
+                }
+            }
+            LogWrite::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Read the last `n` entries of a log file
+///
+/// Lines that fail to parse (e.g. a partially written last line) are skipped.
+pub fn tail(path: impl AsRef<Path>, n: usize) -> Result<Vec<QueryLogEntry>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut entries: Vec<QueryLogEntry> = BufReader::new(file)
+        .lines()
+        .map_while(std::result::Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+
+    let skip = entries.len().saturating_sub(n);
+    entries.drain(..skip);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(query: &str, duration_ms: f64) -> QueryLogEntry {
+        QueryLogEntry {
+            timestamp: Timestamp::now(),
+            database: "default".to_string(),
+            client: Some("127.0.0.1:5000".to_string()),
+            user: None,
+            query: query.to_string(),
+            duration_ms,
+            rows: 3,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_query_and_slow_log() {
+        let temp = TempDir::new().unwrap();
+        let config = QueryLogConfig {
+            log_all: true,
+            slow_threshold: Some(Duration::from_millis(50)),
+        };
+        let log = QueryLog::open(temp.path(), config).unwrap();
+
+        log.record(&entry("SELECT * FROM users", 1.5)).unwrap();
+        log.record(&entry("SELECT * FROM orders", 120.0)).unwrap();
+        log.record(&entry("SELECT * FROM items", 2.0)).unwrap();
+        log.flush().unwrap();
+
+        let all = tail(temp.path().join(QUERY_LOG_FILE), 10).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(tail(temp.path().join(QUERY_LOG_FILE), 2).unwrap()[0].query, "SELECT * FROM orders");
+
+        let slow = tail(temp.path().join(SLOW_LOG_FILE), 10).unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].query, "SELECT * FROM orders");
+        assert_eq!(log.recent_slow(10), slow);
+    }
+
+    #[test]
+    fn test_recent_slow_is_bounded() {
+        let temp = TempDir::new().unwrap();
+        let log = QueryLog::open(temp.path(), QueryLogConfig::default()).unwrap();
+
+        for i in 0..RECENT_SLOW_CAPACITY + 5 {
+            log.record(&entry(&format!("SELECT {}", i), 500.0)).unwrap();
+        }
+        let recent = log.recent_slow(usize::MAX);
+        assert_eq!(recent.len(), RECENT_SLOW_CAPACITY);
+        assert_eq!(recent[0].query, "SELECT 5");
+        assert_eq!(log.recent_slow(2)[1].query, format!("SELECT {}", RECENT_SLOW_CAPACITY + 4));
+    }
+
+    #[test]
+    fn test_slow_only() {
+        let temp = TempDir::new().unwrap();
+        let log = QueryLog::open(temp.path(), QueryLogConfig::default()).unwrap();
+
+        log.record(&entry("SELECT 1", 1.0)).unwrap();
+        log.record(&entry("SELECT 2", 200.0)).unwrap();
+        drop(log);
+
+        // Dropping the log writes what was still queued
+        assert!(!temp.path().join(QUERY_LOG_FILE).exists());
+        assert_eq!(tail(temp.path().join(SLOW_LOG_FILE), 10).unwrap().len(), 1);
+    }
+}
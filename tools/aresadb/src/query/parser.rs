@@ -4,16 +4,119 @@
 
 use anyhow::{Result, bail};
 use sqlparser::ast::{
-    BinaryOperator, Expr, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
-    Value as SqlValue, OrderByExpr,
+    Assignment, BinaryOperator, ConflictTarget, Expr, FunctionArg, FunctionArgExpr, OnConflictAction,
+    OnInsert, Query, Select, SelectItem, SetExpr, Statement, TableFactor, Value as SqlValue, OrderByExpr,
+    visit_relations,
 };
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::Token;
 use std::collections::BTreeMap;
+use std::ops::ControlFlow;
 
-use super::{ParsedQuery, QueryOperation, Condition, Operator, OrderBy, VectorSearchParams};
+use super::{ParsedQuery, QueryOperation, Condition, Operator, OnConflict, OrderBy, ParamSlot, VectorSearchParams};
 use crate::storage::{Value, DistanceMetric};
 
+/// Placeholders found while converting a statement
+///
+/// `$1`-style placeholders are numbered explicitly; each `?` takes the next
+/// number. The two styles can't be mixed.
+#[derive(Default)]
+struct Placeholders {
+    slots: Vec<(usize, ParamSlot)>,
+    positional: usize,
+    numbered: bool,
+}
+
+impl Placeholders {
+    fn add(&mut self, name: &str, slot: ParamSlot) -> Result<()> {
+        let index = if name == "?" {
+            self.positional += 1;
+            self.positional - 1
+        } else {
+            self.numbered = true;
+            match name.strip_prefix('$').and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => n - 1,
+                _ => bail!("Invalid parameter placeholder: {}", name),
+            }
+        };
+
+        if self.numbered && self.positional > 0 {
+            bail!("Cannot mix ? and $n parameter placeholders");
+        }
+        self.slots.push((index, slot));
+        Ok(())
+    }
+}
+
+/// Split a script into statements at semicolons
+///
+/// Semicolons inside quotes or comments don't end a statement. Comments
+/// are kept with the statement that follows them; statements holding only
+/// comments and whitespace are dropped.
+pub fn split_statements(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut has_code = false;
+    let mut chars = script.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                has_code = true;
+                current.push(c);
+                while let Some(next) = chars.next() {
+                    current.push(next);
+                    if next == c {
+                        // A doubled quote is an escaped quote
+                        if chars.peek() == Some(&c) {
+                            current.push(chars.next().unwrap_or(c));
+                            continue;
+                        }
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                current.push(c);
+                for next in chars.by_ref() {
+                    current.push(next);
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                current.push(c);
+                current.push(chars.next().unwrap_or('*'));
+                let mut last = '\0';
+                for next in chars.by_ref() {
+                    current.push(next);
+                    if last == '*' && next == '/' {
+                        break;
+                    }
+                    last = next;
+                }
+            }
+            ';' => {
+                if has_code {
+                    statements.push(current.trim().to_string());
+                }
+                current.clear();
+                has_code = false;
+            }
+            c => {
+                has_code |= !c.is_whitespace();
+                current.push(c);
+            }
+        }
+    }
+    if has_code {
+        statements.push(current.trim().to_string());
+    }
+    statements
+}
+
 /// SQL query parser
 pub struct QueryParser {
     dialect: GenericDialect,
@@ -45,23 +148,45 @@ impl QueryParser {
             bail!("Multiple statements not supported");
         }
 
-        self.convert_statement(&statements[0])
+        let mut placeholders = Placeholders::default();
+        let mut query = self.convert_statement(&statements[0], &mut placeholders)?;
+        query.params = placeholders.slots;
+        query.reads = Self::referenced_tables(&statements[0], &query.target);
+        Ok(query)
+    }
+
+    /// Every table a statement names other than `target`, wherever it
+    /// appears: joins, subqueries, CTEs and `INSERT ... SELECT` sources
+    fn referenced_tables(stmt: &Statement, target: &str) -> Vec<String> {
+        let mut tables: Vec<String> = Vec::new();
+        let _ = visit_relations(stmt, |name| {
+            let name = name.to_string();
+            if name != target && !tables.contains(&name) {
+                tables.push(name);
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        tables
     }
 
     /// Convert a SQL AST statement to ParsedQuery
-    fn convert_statement(&self, stmt: &Statement) -> Result<ParsedQuery> {
+    fn convert_statement(&self, stmt: &Statement, placeholders: &mut Placeholders) -> Result<ParsedQuery> {
         match stmt {
-            Statement::Query(query) => self.convert_query(query),
-            Statement::Insert { table_name, columns, source, .. } => {
+            Statement::Query(query) => self.convert_query(query, placeholders),
+            Statement::Insert { table_name, columns, source, on, .. } => {
                 let target = table_name.to_string();
                 let column_names: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
 
                 // Extract values from source
                 let data = if let Some(source) = source {
-                    self.extract_insert_values(&column_names, source)?
+                    self.extract_insert_values(&column_names, source, placeholders)?
                 } else {
                     None
                 };
+                let on_conflict = on
+                    .as_ref()
+                    .map(|on| self.convert_on_insert(on, data.as_ref(), placeholders))
+                    .transpose()?;
 
                 Ok(ParsedQuery {
                     operation: QueryOperation::Insert,
@@ -73,6 +198,9 @@ impl QueryParser {
                     offset: None,
                     data,
                     vector_search: None,
+                    params: Vec::new(),
+                    on_conflict,
+                    reads: Vec::new(),
                 })
             }
             Statement::Update { table, assignments, selection, .. } => {
@@ -84,13 +212,14 @@ impl QueryParser {
                 let mut data = BTreeMap::new();
                 for assignment in assignments {
                     let column = assignment.id.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".");
-                    let value = self.convert_expr(&assignment.value)?;
+                    let slot = ParamSlot::Data(column.clone());
+                    let value = self.convert_operand(&assignment.value, slot, placeholders)?;
                     data.insert(column, value);
                 }
 
                 let conditions = selection
                     .as_ref()
-                    .map(|expr| self.extract_conditions(expr))
+                    .map(|expr| self.extract_conditions(expr, placeholders))
                     .transpose()?
                     .unwrap_or_default();
 
@@ -104,6 +233,9 @@ impl QueryParser {
                     offset: None,
                     data: Some(data),
                     vector_search: None,
+                    params: Vec::new(),
+                    on_conflict: None,
+                    reads: Vec::new(),
                 })
             }
             Statement::Delete { from, selection, .. } => {
@@ -116,7 +248,7 @@ impl QueryParser {
 
                 let conditions = selection
                     .as_ref()
-                    .map(|expr| self.extract_conditions(expr))
+                    .map(|expr| self.extract_conditions(expr, placeholders))
                     .transpose()?
                     .unwrap_or_default();
 
@@ -130,6 +262,9 @@ impl QueryParser {
                     offset: None,
                     data: None,
                     vector_search: None,
+                    params: Vec::new(),
+                    on_conflict: None,
+                    reads: Vec::new(),
                 })
             }
             _ => bail!("Unsupported SQL statement type"),
@@ -137,17 +272,17 @@ impl QueryParser {
     }
 
     /// Convert a SELECT query
-    fn convert_query(&self, query: &Query) -> Result<ParsedQuery> {
+    fn convert_query(&self, query: &Query, placeholders: &mut Placeholders) -> Result<ParsedQuery> {
         let select = match &*query.body {
             SetExpr::Select(select) => select,
             _ => bail!("Only SELECT queries are supported"),
         };
 
-        self.convert_select(select, query)
+        self.convert_select(select, query, placeholders)
     }
 
     /// Convert a SELECT statement
-    fn convert_select(&self, select: &Select, query: &Query) -> Result<ParsedQuery> {
+    fn convert_select(&self, select: &Select, query: &Query, placeholders: &mut Placeholders) -> Result<ParsedQuery> {
         // Extract table name
         let target = select
             .from
@@ -176,7 +311,7 @@ impl QueryParser {
         let conditions = select
             .selection
             .as_ref()
-            .map(|expr| self.extract_conditions(expr))
+            .map(|expr| self.extract_conditions(expr, placeholders))
             .transpose()?
             .unwrap_or_default();
 
@@ -224,23 +359,82 @@ impl QueryParser {
             offset,
             data: None,
             vector_search: None,
+            params: Vec::new(),
+            on_conflict: None,
+            reads: Vec::new(),
         })
     }
 
+    /// Parse a boolean expression, such as a schema check, into conditions
+    /// that must all hold
+    ///
+    /// Unlike WHERE clauses, which skip what they cannot filter on, anything
+    /// the conditions cannot express exactly is an error.
+    pub fn parse_conditions(&self, expression: &str) -> Result<Vec<Condition>> {
+        let mut parser = Parser::new(&self.dialect).try_with_sql(expression)?;
+        let expr = parser.parse_expr()?;
+        if parser.peek_token().token != Token::EOF {
+            bail!("Unexpected input after expression: {}", expression);
+        }
+
+        let mut placeholders = Placeholders::default();
+        let conditions = self.extract_conditions(&expr, &mut placeholders)?;
+        if !placeholders.slots.is_empty() {
+            bail!("Parameters are not allowed here: {}", expression);
+        }
+        if Some(conditions.len()) != Self::count_predicates(&expr) {
+            bail!(
+                "Unsupported expression: {} (use comparisons, LIKE, IN and IS [NOT] NULL on columns, joined by AND)",
+                expression
+            );
+        }
+        Ok(conditions)
+    }
+
+    /// Number of conditions `extract_conditions` turns an expression into,
+    /// or `None` if it would skip part of it
+    fn count_predicates(expr: &Expr) -> Option<usize> {
+        let is_column = |e: &Expr| matches!(e, Expr::Identifier(_));
+        match expr {
+            Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+                Some(Self::count_predicates(left)? + Self::count_predicates(right)?)
+            }
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::Eq
+                | BinaryOperator::NotEq
+                | BinaryOperator::Lt
+                | BinaryOperator::LtEq
+                | BinaryOperator::Gt
+                | BinaryOperator::GtEq,
+                right,
+            } if is_column(left) && !is_column(right) => Some(1),
+            Expr::Like { negated: false, expr, .. } if is_column(expr) => Some(1),
+            Expr::InList { negated: false, expr, .. } if is_column(expr) => Some(1),
+            Expr::IsNull(expr) | Expr::IsNotNull(expr) if is_column(expr) => Some(1),
+            _ => None,
+        }
+    }
+
     /// Extract conditions from a WHERE expression
-    fn extract_conditions(&self, expr: &Expr) -> Result<Vec<Condition>> {
+    fn extract_conditions(&self, expr: &Expr, placeholders: &mut Placeholders) -> Result<Vec<Condition>> {
         let mut conditions = Vec::new();
-        self.extract_conditions_recursive(expr, &mut conditions)?;
+        self.extract_conditions_recursive(expr, &mut conditions, placeholders)?;
         Ok(conditions)
     }
 
-    fn extract_conditions_recursive(&self, expr: &Expr, conditions: &mut Vec<Condition>) -> Result<()> {
+    fn extract_conditions_recursive(
+        &self,
+        expr: &Expr,
+        conditions: &mut Vec<Condition>,
+        placeholders: &mut Placeholders,
+    ) -> Result<()> {
         match expr {
             Expr::BinaryOp { left, op, right } => {
                 match op {
                     BinaryOperator::And => {
-                        self.extract_conditions_recursive(left, conditions)?;
-                        self.extract_conditions_recursive(right, conditions)?;
+                        self.extract_conditions_recursive(left, conditions, placeholders)?;
+                        self.extract_conditions_recursive(right, conditions, placeholders)?;
                     }
                     BinaryOperator::Eq
                     | BinaryOperator::NotEq
@@ -259,7 +453,8 @@ impl QueryParser {
                                 BinaryOperator::GtEq => Operator::Ge,
                                 _ => unreachable!(),
                             };
-                            let value = self.convert_expr(right)?;
+                            let slot = ParamSlot::Condition(conditions.len());
+                            let value = self.convert_operand(right, slot, placeholders)?;
                             conditions.push(Condition { column, operator, value });
                         }
                     }
@@ -269,7 +464,8 @@ impl QueryParser {
             Expr::Like { expr, pattern, .. } => {
                 if let Expr::Identifier(ident) = &**expr {
                     let column = ident.to_string();
-                    let value = self.convert_expr(pattern)?;
+                    let slot = ParamSlot::Condition(conditions.len());
+                    let value = self.convert_operand(pattern, slot, placeholders)?;
                     conditions.push(Condition {
                         column,
                         operator: Operator::Like,
@@ -297,7 +493,12 @@ impl QueryParser {
             }
             Expr::InList { expr, list, .. } => {
                 if let Expr::Identifier(ident) = &**expr {
-                    let values: Result<Vec<Value>> = list.iter().map(|e| self.convert_expr(e)).collect();
+                    let index = conditions.len();
+                    let values: Result<Vec<Value>> = list
+                        .iter()
+                        .enumerate()
+                        .map(|(i, e)| self.convert_operand(e, ParamSlot::ConditionItem(index, i), placeholders))
+                        .collect();
                     conditions.push(Condition {
                         column: ident.to_string(),
                         operator: Operator::In,
@@ -310,6 +511,18 @@ impl QueryParser {
         Ok(())
     }
 
+    /// Convert a value expression, recording a placeholder as a parameter
+    /// bound at `slot` (it reads as null until bound)
+    fn convert_operand(&self, expr: &Expr, slot: ParamSlot, placeholders: &mut Placeholders) -> Result<Value> {
+        match expr {
+            Expr::Value(SqlValue::Placeholder(name)) => {
+                placeholders.add(name, slot)?;
+                Ok(Value::Null)
+            }
+            expr => self.convert_expr(expr),
+        }
+    }
+
     /// Convert a SQL expression to a Value
     fn convert_expr(&self, expr: &Expr) -> Result<Value> {
         match expr {
@@ -346,14 +559,74 @@ impl QueryParser {
         }
     }
 
+    /// Convert the `ON CONFLICT` / `ON DUPLICATE KEY UPDATE` clause of an INSERT
+    ///
+    /// `EXCLUDED.col` and MySQL's `VALUES(col)` stand for the inserted value
+    /// of `col`, including when that value is a parameter.
+    fn convert_on_insert(
+        &self,
+        on: &OnInsert,
+        data: Option<&BTreeMap<String, Value>>,
+        placeholders: &mut Placeholders,
+    ) -> Result<OnConflict> {
+        let (columns, assignments): (Vec<String>, Option<&Vec<Assignment>>) = match on {
+            OnInsert::DuplicateKeyUpdate(assignments) => (Vec::new(), Some(assignments)),
+            OnInsert::OnConflict(conflict) => {
+                let columns = match &conflict.conflict_target {
+                    Some(ConflictTarget::Columns(columns)) => columns.iter().map(|c| c.to_string()).collect(),
+                    Some(ConflictTarget::OnConstraint(name)) => bail!("ON CONFLICT ON CONSTRAINT is not supported: {}", name),
+                    None => Vec::new(),
+                };
+                match &conflict.action {
+                    OnConflictAction::DoNothing => (columns, None),
+                    OnConflictAction::DoUpdate(update) if update.selection.is_some() => {
+                        bail!("ON CONFLICT DO UPDATE ... WHERE is not supported")
+                    }
+                    OnConflictAction::DoUpdate(update) => (columns, Some(&update.assignments)),
+                }
+            }
+            _ => bail!("Unsupported INSERT conflict clause"),
+        };
+
+        let Some(assignments) = assignments else {
+            return Ok(OnConflict { columns, update: None });
+        };
+        let mut update = BTreeMap::new();
+        for assignment in assignments {
+            let column = assignment.id.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(".");
+            let value = match inserted_column(&assignment.value) {
+                Some(source) => {
+                    let bound: Vec<usize> = placeholders
+                        .slots
+                        .iter()
+                        .filter(|(_, slot)| *slot == ParamSlot::Data(source.clone()))
+                        .map(|(index, _)| *index)
+                        .collect();
+                    for index in bound {
+                        placeholders.slots.push((index, ParamSlot::ConflictData(column.clone())));
+                    }
+                    data.and_then(|d| d.get(&source)).cloned().unwrap_or(Value::Null)
+                }
+                None => self.convert_operand(&assignment.value, ParamSlot::ConflictData(column.clone()), placeholders)?,
+            };
+            update.insert(column, value);
+        }
+        Ok(OnConflict { columns, update: Some(update) })
+    }
+
     /// Extract values from INSERT statement
-    fn extract_insert_values(&self, columns: &[String], source: &Query) -> Result<Option<BTreeMap<String, Value>>> {
+    fn extract_insert_values(
+        &self,
+        columns: &[String],
+        source: &Query,
+        placeholders: &mut Placeholders,
+    ) -> Result<Option<BTreeMap<String, Value>>> {
         if let SetExpr::Values(values) = &*source.body {
             if let Some(row) = values.rows.first() {
                 let mut data = BTreeMap::new();
                 for (i, expr) in row.iter().enumerate() {
                     let column = columns.get(i).cloned().unwrap_or_else(|| format!("col{}", i));
-                    let value = self.convert_expr(expr)?;
+                    let value = self.convert_operand(expr, ParamSlot::Data(column.clone()), placeholders)?;
                     data.insert(column, value);
                 }
                 return Ok(Some(data));
@@ -363,6 +636,22 @@ impl QueryParser {
     }
 }
 
+/// The column named by `EXCLUDED.col` or `VALUES(col)`
+fn inserted_column(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::CompoundIdentifier(parts) if parts.len() == 2 && parts[0].value.eq_ignore_ascii_case("excluded") => {
+            Some(parts[1].to_string())
+        }
+        Expr::Function(function) if function.name.to_string().eq_ignore_ascii_case("values") => {
+            match &function.args[..] {
+                [FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Identifier(ident)))] => Some(ident.to_string()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
 impl Default for QueryParser {
     fn default() -> Self {
         Self::new()
@@ -378,6 +667,7 @@ impl QueryParser {
     /// Try to parse a vector search query
     /// Supports syntax: VECTOR SEARCH <table> FIELD <field> FOR <vector> [METRIC <metric>] [LIMIT <n>]
     /// Example: VECTOR SEARCH documents FIELD embedding FOR [0.1, 0.2, 0.3] METRIC cosine LIMIT 10
+    /// Several fields are combined with weights: FIELD title_embedding WEIGHT 0.3, body_embedding WEIGHT 0.7
     pub fn parse_vector_search(&self, sql: &str) -> Option<ParsedQuery> {
         let sql_upper = sql.to_uppercase();
 
@@ -397,11 +687,26 @@ impl QueryParser {
 
         // Find FIELD keyword
         let field_idx = parts.iter().position(|&p| p.eq_ignore_ascii_case("FIELD"))?;
-        let embedding_field = parts.get(field_idx + 1)?.to_string();
+        let embedding_field = parts.get(field_idx + 1)?.trim_end_matches(',').to_string();
 
         // Find FOR keyword and extract vector
         let for_idx = parts.iter().position(|&p| p.eq_ignore_ascii_case("FOR"))?;
 
+        // Fields between FIELD and FOR: "<field> [WEIGHT <w>]", comma separated
+        let mut fields = Vec::new();
+        for field in parts.get(field_idx + 1..for_idx)?.join(" ").split(',') {
+            match field.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [name] => fields.push((name.to_string(), 1.0)),
+                [name, weight_kw, weight] if weight_kw.eq_ignore_ascii_case("WEIGHT") => {
+                    fields.push((name.to_string(), weight.parse().ok()?));
+                }
+                _ => return None,
+            }
+        }
+        if fields.len() == 1 {
+            fields.clear();
+        }
+
         // Find the vector - it starts with [ and ends with ]
         let vector_start = sql.find('[')? + 1;
         let vector_end = sql.find(']')?;
@@ -447,9 +752,13 @@ impl QueryParser {
             vector_search: Some(VectorSearchParams {
                 query_vector,
                 embedding_field,
+                fields,
                 k,
                 metric,
             }),
+            params: Vec::new(),
+            on_conflict: None,
+            reads: Vec::new(),
         })
     }
 
@@ -498,6 +807,22 @@ impl QueryParser {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_split_statements() {
+        let script = "-- setup; not a statement\n\
+            INSERT INTO users (name) VALUES ('O''Brien; Jr');\n\
+            /* count; them */ SELECT COUNT(*) FROM users;;\n\
+            SELECT * FROM users WHERE name = \"a;b\"\n\
+            -- trailing comment;";
+        let statements = split_statements(script);
+        assert_eq!(statements.len(), 3);
+        assert!(statements[0].starts_with("-- setup; not a statement\n"));
+        assert!(statements[0].ends_with("VALUES ('O''Brien; Jr')"));
+        assert_eq!(statements[1], "/* count; them */ SELECT COUNT(*) FROM users");
+        assert!(statements[2].starts_with("SELECT * FROM users WHERE name = \"a;b\""));
+        assert!(split_statements("  ; -- nothing\n").is_empty());
+    }
+
     #[test]
     fn test_parse_select() {
         let parser = QueryParser::new();
@@ -509,6 +834,41 @@ mod tests {
         assert_eq!(query.conditions[0].column, "age");
     }
 
+    #[test]
+    fn test_parse_reads() {
+        let parser = QueryParser::new();
+
+        let query = parser.parse("SELECT * FROM users WHERE age > 25").unwrap();
+        assert!(query.reads.is_empty());
+
+        let query = parser
+            .parse("SELECT * FROM users JOIN orders ON users.id = orders.user_id \
+                    WHERE users.id IN (SELECT user_id FROM refunds)")
+            .unwrap();
+        assert_eq!(query.target, "users");
+        assert_eq!(query.reads, vec!["orders", "refunds"]);
+
+        let query = parser.parse("INSERT INTO archive SELECT * FROM users").unwrap();
+        assert_eq!(query.target, "archive");
+        assert_eq!(query.reads, vec!["users"]);
+    }
+
+    #[test]
+    fn test_parse_conditions() {
+        let parser = QueryParser::new();
+
+        let conditions = parser.parse_conditions("age >= 0 AND age < 150 AND status IN ('a', 'b')").unwrap();
+        assert_eq!(conditions.len(), 3);
+        assert_eq!(conditions[1].operator, Operator::Lt);
+        assert_eq!(conditions[1].value, Value::Int(150));
+
+        assert!(parser.parse_conditions("age >= 0 OR age < 150").is_err());
+        assert!(parser.parse_conditions("age NOT IN (1, 2)").is_err());
+        assert!(parser.parse_conditions("age > min_age").is_err());
+        assert!(parser.parse_conditions("age > ?").is_err());
+        assert!(parser.parse_conditions("age > 1 age").is_err());
+    }
+
     #[test]
     fn test_parse_select_with_limit() {
         let parser = QueryParser::new();
@@ -518,6 +878,24 @@ mod tests {
         assert_eq!(query.limit, Some(10));
     }
 
+    #[test]
+    fn test_parse_upsert() {
+        let parser = QueryParser::new();
+        let query = parser
+            .parse("INSERT INTO users (email, name) VALUES ($1, $2) ON CONFLICT (email) DO UPDATE SET name = EXCLUDED.name, visits = 1")
+            .unwrap();
+        let conflict = query.on_conflict.as_ref().unwrap();
+        assert_eq!(conflict.columns, vec!["email"]);
+        assert_eq!(conflict.update.as_ref().unwrap().get("visits"), Some(&Value::Int(1)));
+
+        let bound = query.bind(&[Value::String("a@example.com".into()), Value::String("Ada".into())]).unwrap();
+        let update = bound.on_conflict.unwrap().update.unwrap();
+        assert_eq!(update.get("name"), Some(&Value::String("Ada".into())));
+
+        let query = parser.parse("INSERT INTO users (email) VALUES ('a@example.com') ON CONFLICT DO NOTHING").unwrap();
+        assert_eq!(query.on_conflict, Some(OnConflict { columns: Vec::new(), update: None }));
+    }
+
     #[test]
     fn test_parse_insert() {
         let parser = QueryParser::new();
@@ -549,6 +927,27 @@ mod tests {
         assert_eq!(query.conditions.len(), 1);
     }
 
+    #[test]
+    fn test_parse_params() {
+        let parser = QueryParser::new();
+
+        let query = parser.parse("SELECT * FROM users WHERE name = $2 AND age IN ($1, 40)").unwrap();
+        assert_eq!(query.param_count(), 2);
+        assert_eq!(query.conditions[0].value, Value::Null);
+
+        let bound = query.bind(&[Value::Int(30), Value::String("John".to_string())]).unwrap();
+        assert!(bound.params.is_empty());
+        assert_eq!(bound.conditions[0].value.as_str(), Some("John"));
+        assert_eq!(bound.conditions[1].value, Value::Array(vec![Value::Int(30), Value::Int(40)]));
+        assert!(query.bind(&[Value::Int(30)]).is_err());
+
+        let query = parser.parse("INSERT INTO users (name, age) VALUES (?, ?)").unwrap();
+        let bound = query.bind(&[Value::String("Ann".to_string()), Value::Int(7)]).unwrap();
+        assert_eq!(bound.data.unwrap().get("age").unwrap().as_int(), Some(7));
+
+        assert!(parser.parse("SELECT * FROM users WHERE a = ? AND b = $1").is_err());
+    }
+
     #[test]
     fn test_parse_vector_search() {
         let parser = QueryParser::new();
@@ -577,6 +976,24 @@ mod tests {
         let params = query.vector_search.unwrap();
         assert_eq!(params.metric, crate::storage::DistanceMetric::Euclidean);
         assert_eq!(params.k, 5);
+        assert!(params.fields.is_empty());
+    }
+
+    #[test]
+    fn test_parse_vector_search_weighted_fields() {
+        let parser = QueryParser::new();
+
+        let query = parser.parse(
+            "VECTOR SEARCH docs FIELD title_embedding WEIGHT 0.3, body_embedding WEIGHT 0.7 FOR [1.0, 2.0] LIMIT 5"
+        ).unwrap();
+
+        let params = query.vector_search.unwrap();
+        assert_eq!(params.embedding_field, "title_embedding");
+        assert_eq!(params.fields, vec![("title_embedding".to_string(), 0.3), ("body_embedding".to_string(), 0.7)]);
+
+        let query = parser.parse("VECTOR SEARCH docs FIELD a, b FOR [1.0] LIMIT 5").unwrap();
+        assert_eq!(query.vector_search.unwrap().fields, vec![("a".to_string(), 1.0), ("b".to_string(), 1.0)]);
+        assert!(parser.parse("VECTOR SEARCH docs FIELD a WEIGHT x, b FOR [1.0] LIMIT 5").is_err());
     }
 }
 
@@ -5,7 +5,7 @@
 use anyhow::Result;
 use std::collections::HashSet;
 
-use super::{ParsedQuery, QueryOperation, Condition};
+use super::{ParsedQuery, QueryOperation, Condition, OnConflict};
 use crate::schema::Schema;
 
 /// A query execution plan
@@ -19,6 +19,65 @@ pub struct QueryPlan {
     pub uses_index: bool,
 }
 
+impl QueryPlan {
+    /// Copy of the plan carrying the values of `query`
+    ///
+    /// A plan's shape depends on which columns a query touches, not on the
+    /// values it compares them with, so a prepared statement is planned
+    /// once and rebound to each execution's parameters and limit.
+    pub fn bind(&self, query: &ParsedQuery) -> QueryPlan {
+        let mut plan = self.clone();
+        for step in &mut plan.steps {
+            match step {
+                PlanStep::IndexLookup { field, value, .. } => {
+                    if let Some(condition) = query.conditions.iter().find(|c| c.column == *field) {
+                        *value = condition.value.clone();
+                    }
+                }
+                PlanStep::Filter { conditions } => {
+                    let columns: HashSet<&String> = conditions.iter().map(|c| &c.column).collect();
+                    *conditions = query.conditions
+                        .iter()
+                        .filter(|c| columns.contains(&c.column))
+                        .cloned()
+                        .collect();
+                }
+                PlanStep::InsertNode { data, on_conflict, .. } => {
+                    if let Some(bound) = &query.data {
+                        *data = bound.clone();
+                    }
+                    on_conflict.clone_from(&query.on_conflict);
+                }
+                PlanStep::UpdateNodes { data } => {
+                    if let Some(bound) = &query.data {
+                        *data = bound.clone();
+                    }
+                }
+                PlanStep::Limit { count, .. } => {
+                    if let Some(limit) = query.limit {
+                        *count = limit;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // A limit passed at execution time may apply to a statement without one
+        let has_limit = plan.steps.iter().any(|s| matches!(s, PlanStep::Limit { .. }));
+        if let (Some(limit), false, QueryOperation::Select) = (query.limit, has_limit, &query.operation) {
+            let position = plan.steps
+                .iter()
+                .position(|s| matches!(s, PlanStep::Project { .. }))
+                .unwrap_or(plan.steps.len());
+            plan.steps.insert(position, PlanStep::Limit {
+                count: limit,
+                offset: query.offset.unwrap_or(0),
+            });
+        }
+        plan
+    }
+}
+
 /// A single step in the query plan
 #[derive(Debug, Clone)]
 pub enum PlanStep {
@@ -56,10 +115,11 @@ pub enum PlanStep {
         depth: u32,
         edge_types: Option<Vec<String>>,
     },
-    /// Insert a node
+    /// Insert a node, or upsert one when `on_conflict` is set
     InsertNode {
         node_type: String,
         data: std::collections::BTreeMap<String, crate::storage::Value>,
+        on_conflict: Option<OnConflict>,
     },
     /// Update nodes
     UpdateNodes {
@@ -69,6 +129,23 @@ pub enum PlanStep {
     DeleteNodes,
 }
 
+impl PlanStep {
+    /// Name of the stage this step is timed under in `QueryStats`
+    pub fn stage(&self) -> &'static str {
+        match self {
+            PlanStep::FullScan { .. } | PlanStep::IndexLookup { .. } => "scan",
+            PlanStep::Filter { .. } => "filter",
+            PlanStep::Sort { .. } => "sort",
+            PlanStep::Limit { .. } => "limit",
+            PlanStep::Project { .. } => "project",
+            PlanStep::Traverse { .. } => "traverse",
+            PlanStep::InsertNode { .. } => "insert",
+            PlanStep::UpdateNodes { .. } => "update",
+            PlanStep::DeleteNodes => "delete",
+        }
+    }
+}
+
 /// Query planner
 pub struct QueryPlanner {
     /// Available schemas for optimization hints
@@ -172,6 +249,7 @@ impl QueryPlanner {
                     steps.push(PlanStep::InsertNode {
                         node_type: query.target.clone(),
                         data: data.clone(),
+                        on_conflict: query.on_conflict.clone(),
                     });
                     estimated_cost = 1.0; // Insert is constant time
                 }
@@ -314,9 +392,18 @@ impl QueryPlanner {
                         .unwrap_or_else(|| "all".to_string());
                     format!("  {}. Traverse from '{}' depth {} edges [{}]", i + 1, start_node, depth, edges)
                 }
-                PlanStep::InsertNode { node_type, .. } => {
+                PlanStep::InsertNode { node_type, on_conflict: None, .. } => {
                     format!("  {}. Insert into '{}'", i + 1, node_type)
                 }
+                PlanStep::InsertNode { node_type, on_conflict: Some(conflict), .. } => {
+                    let key = if conflict.columns.is_empty() {
+                        "unique fields".to_string()
+                    } else {
+                        conflict.columns.join(", ")
+                    };
+                    let action = if conflict.update.is_some() { "update" } else { "do nothing" };
+                    format!("  {}. Upsert into '{}' on ({}): {}", i + 1, node_type, key, action)
+                }
                 PlanStep::UpdateNodes { .. } => {
                     format!("  {}. Update nodes", i + 1)
                 }
@@ -361,6 +448,9 @@ mod tests {
             offset: None,
             data: None,
             vector_search: None,
+            params: Vec::new(),
+            on_conflict: None,
+            reads: Vec::new(),
         };
 
         let plan = planner.plan(&query).unwrap();
@@ -387,11 +477,40 @@ mod tests {
             offset: None,
             data: None,
             vector_search: None,
+            params: Vec::new(),
+            on_conflict: None,
+            reads: Vec::new(),
         };
 
         let plan = planner.plan(&query).unwrap();
         assert!(plan.uses_index);
     }
+
+    #[test]
+    fn test_plan_bind() {
+        let mut planner = QueryPlanner::new();
+        planner.indexed_fields.insert(("users".to_string(), "email".to_string()));
+
+        let query = crate::query::QueryParser::new()
+            .parse("SELECT name FROM users WHERE email = $1 AND age > $2")
+            .unwrap();
+        let plan = planner.plan(&query).unwrap();
+
+        let mut bound = query.bind(&[Value::String("a@example.com".to_string()), Value::Int(30)]).unwrap();
+        bound.limit = Some(5);
+        let plan = plan.bind(&bound);
+
+        assert!(matches!(
+            &plan.steps[0],
+            PlanStep::IndexLookup { value: Value::String(email), .. } if email == "a@example.com"
+        ));
+        assert!(matches!(
+            &plan.steps[1],
+            PlanStep::Filter { conditions } if conditions.len() == 1 && conditions[0].value == Value::Int(30)
+        ));
+        assert!(matches!(plan.steps[2], PlanStep::Limit { count: 5, offset: 0 }));
+        assert!(matches!(plan.steps[3], PlanStep::Project { .. }));
+    }
 }
 
 
@@ -0,0 +1,263 @@
+//! Prepared Statements
+//!
+//! A prepared statement is SQL parsed and planned once, then executed many
+//! times with different parameters. Statements are cached by ID, and
+//! preparing SQL that is already cached hands back the cached statement,
+//! so clients running the same hot query share one parse and plan.
+//!
+//! Plain SQL gets the same treatment implicitly: the plan cache keeps the
+//! parse and plan of statements run without placeholders, keyed by their
+//! text with whitespace normalized.
+
+use moka::sync::Cache;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::{ParsedQuery, QueryPlan};
+
+/// Statements kept by default before the least recently used are evicted
+pub const DEFAULT_STATEMENT_CAPACITY: u64 = 1024;
+
+/// Plans kept by default before the least recently used are evicted
+pub const DEFAULT_PLAN_CAPACITY: u64 = 512;
+
+/// Parsed and planned SQL awaiting parameters
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    /// SQL text as prepared
+    pub sql: String,
+    /// Parsed query, with placeholders where parameters go
+    pub query: ParsedQuery,
+    /// Plan for the query, rebound on every execution
+    pub plan: QueryPlan,
+}
+
+impl PreparedStatement {
+    /// Number of parameters an execution must supply
+    pub fn param_count(&self) -> usize {
+        self.query.param_count()
+    }
+}
+
+/// Bounded cache of prepared statements
+///
+/// An evicted statement answers as unknown; clients prepare it again.
+pub struct StatementCache {
+    by_id: Cache<u64, Arc<PreparedStatement>>,
+    by_sql: Cache<String, u64>,
+    next_id: AtomicU64,
+}
+
+impl StatementCache {
+    /// Create a cache holding up to `capacity` statements
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            by_id: Cache::new(capacity),
+            by_sql: Cache::new(capacity),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// ID of a cached statement for this SQL, if any
+    pub fn find(&self, sql: &str) -> Option<(u64, Arc<PreparedStatement>)> {
+        let id = self.by_sql.get(sql)?;
+        self.by_id.get(&id).map(|statement| (id, statement))
+    }
+
+    /// Cache a statement, returning its ID
+    pub fn insert(&self, statement: PreparedStatement) -> (u64, Arc<PreparedStatement>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let statement = Arc::new(statement);
+        self.by_sql.insert(statement.sql.clone(), id);
+        self.by_id.insert(id, Arc::clone(&statement));
+        (id, statement)
+    }
+
+    /// Look up a statement by ID
+    pub fn get(&self, id: u64) -> Option<Arc<PreparedStatement>> {
+        self.by_id.get(&id)
+    }
+
+    /// Drop a statement; `false` if it wasn't cached
+    pub fn remove(&self, id: u64) -> bool {
+        let Some(statement) = self.by_id.remove(&id) else {
+            return false;
+        };
+        if self.by_sql.get(&statement.sql) == Some(id) {
+            self.by_sql.invalidate(&statement.sql);
+        }
+        true
+    }
+}
+
+impl Default for StatementCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATEMENT_CAPACITY)
+    }
+}
+
+/// Bounded cache of parsed and planned SQL, keyed by normalized text
+///
+/// Plans depend only on the statement, not on stored data or indexes
+/// (index lookups are chosen at execution), so entries never go stale.
+pub struct PlanCache {
+    plans: Cache<String, Arc<PreparedStatement>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Plan cache usage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlanCacheStats {
+    /// Plans cached
+    pub entries: u64,
+    /// Statements that skipped parsing and planning
+    pub hits: u64,
+    /// Statements parsed and planned
+    pub misses: u64,
+}
+
+impl PlanCache {
+    /// Create a cache holding up to `capacity` plans
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            plans: Cache::new(capacity),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached plan for this SQL, if any
+    pub fn get(&self, sql: &str) -> Option<Arc<PreparedStatement>> {
+        let plan = self.plans.get(&normalize_sql(sql));
+        let counter = if plan.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        plan
+    }
+
+    /// Cache the plan for a statement
+    pub fn insert(&self, statement: Arc<PreparedStatement>) {
+        self.plans.insert(normalize_sql(&statement.sql), statement);
+    }
+
+    /// Drop every cached plan
+    pub fn flush(&self) {
+        self.plans.invalidate_all();
+    }
+
+    /// Entries, hits and misses so far
+    pub fn stats(&self) -> PlanCacheStats {
+        self.plans.run_pending_tasks();
+        PlanCacheStats {
+            entries: self.plans.entry_count(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for PlanCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_PLAN_CAPACITY)
+    }
+}
+
+/// SQL with whitespace outside quotes collapsed, line comments dropped and
+/// trailing semicolons dropped, so reformatted copies of a statement share
+/// a plan
+///
+/// A line comment runs to its newline, so dropping it can't join the next
+/// line onto it; block comments are kept as written.
+pub fn normalize_sql(sql: &str) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut normalized = String::with_capacity(sql.len());
+    let mut pending_space = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            pending_space = true;
+            i += 1;
+            continue;
+        }
+        if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            pending_space = true;
+            continue;
+        }
+
+        if pending_space && !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        pending_space = false;
+        let end = match c {
+            '\'' | '"' => chars[i + 1..].iter().position(|&q| q == c).map_or(chars.len(), |n| i + n + 2),
+            '/' if next == Some('*') => block_comment_end(&chars, i),
+            _ => i + 1,
+        };
+        normalized.extend(&chars[i..end]);
+        i = end;
+    }
+
+    let kept = normalized.trim_end_matches(|c: char| c == ';' || c.is_whitespace()).len();
+    normalized.truncate(kept);
+    normalized
+}
+
+/// Index just past a block comment starting at `start`; comments nest
+fn block_comment_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < chars.len() {
+        match (chars[i], chars[i + 1]) {
+            ('/', '*') => {
+                depth += 1;
+                i += 2;
+            }
+            ('*', '/') => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(
+            normalize_sql("  SELECT *\n  FROM users\tWHERE name = 'a  b' ;  "),
+            "SELECT * FROM users WHERE name = 'a  b'"
+        );
+        assert_eq!(normalize_sql("SELECT 1"), normalize_sql("SELECT   1;"));
+        assert_ne!(normalize_sql("SELECT 'x y'"), normalize_sql("SELECT 'x  y'"));
+    }
+
+    #[test]
+    fn test_normalize_sql_comments() {
+        // The newline ending a comment keeps the next line out of it
+        let filtered = normalize_sql("SELECT * FROM t -- c\nWHERE x = 1");
+        let commented = normalize_sql("SELECT * FROM t -- c WHERE x = 1");
+        assert_ne!(filtered, commented);
+        assert_eq!(filtered, "SELECT * FROM t WHERE x = 1");
+        assert_eq!(commented, "SELECT * FROM t");
+        assert_eq!(normalize_sql("SELECT 1; -- done"), "SELECT 1");
+
+        // Comment markers in strings are text, and block comments are kept
+        assert_eq!(normalize_sql("SELECT '--x'  -- y"), "SELECT '--x'");
+        assert_eq!(normalize_sql("SELECT /* a  -- b */ 1"), "SELECT /* a  -- b */ 1");
+        assert_eq!(normalize_sql("SELECT /* /* */ ' */  1"), "SELECT /* /* */ ' */ 1");
+    }
+}
@@ -0,0 +1,293 @@
+//! Sorting past the memory limit
+//!
+//! An ORDER BY buffers matching nodes under a memory reservation. When
+//! the reservation can't grow, the buffer is sorted and written to disk
+//! as a run, and the runs are merged once the scan ends. Only the rows
+//! the query returns are held in memory at the end. A sort with more runs
+//! than it may open at once first merges them in groups into longer runs.
+//!
+//! Runs are written with bincode, whose tagged encoding of `Value` reads
+//! back exactly what was written; untagged JSON would turn an array of
+//! small ints into bytes or a vector.
+
+use anyhow::{Context, Result};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::storage::{MemoryReservation, Node};
+
+/// Most runs merged at once, keeping well clear of open file limits
+const MERGE_FAN_IN: usize = 64;
+
+/// Sorts nodes with a bounded buffer, spilling sorted runs to disk
+pub struct ExternalSort<F> {
+    compare: F,
+    buffer: Vec<Node>,
+    memory: MemoryReservation,
+    /// Directory the runs are written to, created with the first run
+    dir: PathBuf,
+    /// Run files and the number of nodes in each
+    runs: Vec<(PathBuf, usize)>,
+    /// Number for the next run file's name
+    next_run: usize,
+}
+
+impl<F> ExternalSort<F>
+where
+    F: Fn(&Node, &Node) -> Ordering,
+{
+    /// Create a sort whose runs go in a new directory under `spill_root`
+    pub fn new(compare: F, memory: MemoryReservation, spill_root: &Path) -> Self {
+        Self {
+            compare,
+            buffer: Vec::new(),
+            memory,
+            dir: spill_root.join(uuid::Uuid::new_v4().to_string()),
+            runs: Vec::new(),
+            next_run: 0,
+        }
+    }
+
+    /// Runs written to disk so far
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Add a node, spilling the buffer first if the node doesn't fit
+    pub fn push(&mut self, node: Node) -> Result<()> {
+        let size = node.memory_size() as u64;
+        if self.memory.grow(size).is_err() {
+            self.spill()?;
+            // A node bigger than the whole budget can't be sorted at all
+            self.memory.grow(size)?;
+        }
+        self.buffer.push(node);
+        Ok(())
+    }
+
+    /// Sort the buffer and write it out as a run
+    fn spill(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let (path, mut writer) = self.create_run()?;
+        self.buffer.sort_by(&self.compare);
+        let len = self.buffer.len();
+        for node in self.buffer.drain(..) {
+            bincode::serialize_into(&mut writer, &node)?;
+        }
+        writer.flush()?;
+        self.runs.push((path, len));
+        self.memory.clear();
+        Ok(())
+    }
+
+    /// Create the next run file, and the spill directory with the first
+    fn create_run(&mut self) -> Result<(PathBuf, BufWriter<File>)> {
+        std::fs::create_dir_all(&self.dir).context("Failed to create sort spill directory")?;
+        let path = self.dir.join(format!("run-{}.bin", self.next_run));
+        self.next_run += 1;
+        let writer = BufWriter::new(File::create(&path).context("Failed to create sort run")?);
+        Ok((path, writer))
+    }
+
+    /// Merge runs a group at a time until few enough remain to open at once
+    fn reduce_runs(&mut self) -> Result<()> {
+        while self.runs.len() > MERGE_FAN_IN {
+            let group: Vec<(PathBuf, usize)> = self.runs.drain(..MERGE_FAN_IN).collect();
+            let mut inputs = open_runs(&group)?;
+            let (path, mut writer) = self.create_run()?;
+            let mut len = 0;
+            while let Some(i) = smallest(&inputs, &self.compare) {
+                let node = inputs[i].head.take().expect("run has a head");
+                inputs[i].advance()?;
+                bincode::serialize_into(&mut writer, &node)?;
+                len += 1;
+            }
+            writer.flush()?;
+            drop(inputs);
+            for (merged, _) in &group {
+                let _ = std::fs::remove_file(merged);
+            }
+            self.runs.push((path, len));
+        }
+        Ok(())
+    }
+
+    /// The sorted nodes after skipping `offset`, at most `count` of them
+    ///
+    /// The returned nodes stay reserved against the memory limit until
+    /// the sort is dropped.
+    pub fn finish(mut self, offset: usize, count: usize) -> Result<(Vec<Node>, MemoryReservation)> {
+        if self.runs.is_empty() {
+            self.buffer.sort_by(&self.compare);
+            let nodes: Vec<Node> = std::mem::take(&mut self.buffer).into_iter().skip(offset).take(count).collect();
+            let output = self.memory_for_output();
+            return Ok((nodes, std::mem::replace(&mut self.memory, output)));
+        }
+
+        self.spill()?;
+        self.reduce_runs()?;
+        let mut runs = open_runs(&self.runs)?;
+
+        let mut output = self.memory_for_output();
+        let mut nodes = Vec::new();
+        let mut skipped = 0;
+        while nodes.len() < count {
+            let Some(i) = smallest(&runs, &self.compare) else {
+                break;
+            };
+            let node = runs[i].head.take().expect("run has a head");
+            runs[i].advance()?;
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            output.grow(node.memory_size() as u64)?;
+            nodes.push(node);
+        }
+        Ok((nodes, output))
+    }
+
+    fn memory_for_output(&self) -> MemoryReservation {
+        self.memory.sibling("sorted query results")
+    }
+}
+
+impl<F> Drop for ExternalSort<F> {
+    fn drop(&mut self) {
+        // Also removes a run whose write failed before it was recorded
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Open runs for merging, each with its first node read
+fn open_runs(runs: &[(PathBuf, usize)]) -> Result<Vec<Run>> {
+    let mut opened = Vec::with_capacity(runs.len());
+    for (path, len) in runs {
+        let mut run = Run { reader: BufReader::new(File::open(path)?), remaining: *len, head: None };
+        run.advance()?;
+        opened.push(run);
+    }
+    Ok(opened)
+}
+
+/// Index of the run whose next node sorts first, if any has one left
+fn smallest<F>(runs: &[Run], compare: &F) -> Option<usize>
+where
+    F: Fn(&Node, &Node) -> Ordering,
+{
+    runs.iter()
+        .enumerate()
+        .filter_map(|(i, run)| run.head.as_ref().map(|node| (i, node)))
+        .min_by(|(_, a), (_, b)| compare(a, b))
+        .map(|(i, _)| i)
+}
+
+/// A sorted run being merged, with its next node read ahead
+struct Run {
+    reader: BufReader<File>,
+    /// Nodes not yet read
+    remaining: usize,
+    head: Option<Node>,
+}
+
+impl Run {
+    fn advance(&mut self) -> Result<()> {
+        self.head = if self.remaining > 0 {
+            self.remaining -= 1;
+            Some(bincode::deserialize_from(&mut self.reader).context("Failed to read sort run")?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{MemoryBudget, Value};
+    use std::sync::Arc;
+
+    fn node(n: i64) -> Node {
+        Node::new("item", Value::Object([("n".to_string(), Value::Int(n))].into_iter().collect()))
+    }
+
+    fn by_n(a: &Node, b: &Node) -> Ordering {
+        let n = |node: &Node| match node.get("n") {
+            Some(Value::Int(n)) => *n,
+            _ => 0,
+        };
+        n(a).cmp(&n(b))
+    }
+
+    #[test]
+    fn test_external_sort_spills() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let size = node(0).memory_size() as u64;
+        let budget = Arc::new(MemoryBudget::new(Some(size * 10)));
+
+        let mut sort = ExternalSort::new(by_n, budget.reservation("sort"), temp.path());
+        for n in (0..50).rev() {
+            sort.push(node(n)).unwrap();
+        }
+        assert!(sort.spilled_runs() >= 4);
+
+        let (nodes, _memory) = sort.finish(5, 3).unwrap();
+        let values: Vec<_> = nodes.iter().map(|node| node.get("n").cloned()).collect();
+        assert_eq!(values, vec![Some(Value::Int(5)), Some(Value::Int(6)), Some(Value::Int(7))]);
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+
+        // Returning more rows than the limit holds is an error, not a spill
+        let mut sort = ExternalSort::new(by_n, budget.reservation("sort"), temp.path());
+        for n in 0..50 {
+            sort.push(node(n)).unwrap();
+        }
+        assert!(sort.finish(0, 50).is_err());
+    }
+
+    #[test]
+    fn test_many_runs_merge_in_passes() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let budget = Arc::new(MemoryBudget::new(Some(node(0).memory_size() as u64)));
+
+        // One node per run, far more runs than are opened at once
+        let total = MERGE_FAN_IN as i64 * 3;
+        let mut sort = ExternalSort::new(by_n, budget.reservation("sort"), temp.path());
+        for n in (0..total).rev() {
+            sort.push(node(n)).unwrap();
+        }
+        assert!(sort.spilled_runs() > MERGE_FAN_IN * 2);
+
+        let (nodes, _memory) = sort.finish(100, 1).unwrap();
+        assert_eq!(nodes[0].get("n"), Some(&Value::Int(100)));
+        assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_spilled_values_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let item = |n: i64| {
+            let properties = [
+                ("n".to_string(), Value::Int(n)),
+                ("small".to_string(), Value::Array(vec![Value::Int(1), Value::Int(2)])),
+                ("floats".to_string(), Value::Array(vec![Value::Float(0.5)])),
+            ];
+            Node::new("item", Value::Object(properties.into_iter().collect()))
+        };
+        let budget = Arc::new(MemoryBudget::new(Some(item(0).memory_size() as u64 * 2)));
+
+        let mut sort = ExternalSort::new(by_n, budget.reservation("sort"), temp.path());
+        for n in (0..6).rev() {
+            sort.push(item(n)).unwrap();
+        }
+        assert!(sort.spilled_runs() > 0);
+
+        let (nodes, _memory) = sort.finish(0, 1).unwrap();
+        assert_eq!(nodes[0].get("small"), Some(&Value::Array(vec![Value::Int(1), Value::Int(2)])));
+        assert_eq!(nodes[0].get("floats"), Some(&Value::Array(vec![Value::Float(0.5)])));
+    }
+}
@@ -0,0 +1,311 @@
+//! Text analysis for keyword search
+//!
+//! Keyword search compares terms, not raw text. An analyzer turns text into
+//! terms: it splits it into tokens, lowercases them, drops stop words,
+//! reduces words to a stem so "networks" finds "network", and optionally
+//! joins neighbouring words into n-grams so phrases match as a unit. Query
+//! and documents must be analyzed the same way.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How text is split into tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tokenizer {
+    /// Runs of letters and digits; punctuation separates words
+    #[default]
+    Words,
+    /// Whitespace-separated pieces, punctuation included
+    Whitespace,
+}
+
+/// Language of the text, selecting stop words and stemming rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    /// English
+    #[default]
+    English,
+    /// German
+    German,
+    /// French
+    French,
+    /// Spanish
+    Spanish,
+    /// Any language: no stop words and no stemming
+    Neutral,
+}
+
+impl Language {
+    /// Parse a language name or ISO 639-1 code
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "english" | "en" => Ok(Self::English),
+            "german" | "de" => Ok(Self::German),
+            "french" | "fr" => Ok(Self::French),
+            "spanish" | "es" => Ok(Self::Spanish),
+            "neutral" | "none" => Ok(Self::Neutral),
+            _ => anyhow::bail!("Unknown language: {}. Use english, german, french, spanish or neutral", name),
+        }
+    }
+
+    /// Get the name of this language
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::English => "english",
+            Self::German => "german",
+            Self::French => "french",
+            Self::Spanish => "spanish",
+            Self::Neutral => "neutral",
+        }
+    }
+
+    /// Common words that carry little meaning for search
+    pub fn stop_words(&self) -> &'static [&'static str] {
+        match self {
+            Self::English => &[
+                "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have",
+                "how", "i", "if", "in", "into", "is", "it", "its", "of", "on", "or", "our", "so",
+                "such", "than", "that", "the", "their", "then", "there", "these", "they", "this",
+                "to", "was", "we", "were", "what", "when", "where", "which", "who", "will", "with",
+                "you", "your",
+            ],
+            Self::German => &[
+                "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das",
+                "dass", "dem", "den", "der", "des", "die", "ein", "eine", "einem", "einen", "einer",
+                "es", "für", "hat", "ich", "im", "in", "ist", "mit", "nach", "nicht", "noch", "oder",
+                "sich", "sie", "sind", "so", "und", "von", "war", "wie", "wir", "zu", "zum", "zur",
+            ],
+            Self::French => &[
+                "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "est",
+                "et", "il", "ils", "je", "la", "le", "les", "leur", "lui", "mais", "me", "même",
+                "mes", "ne", "nous", "on", "ou", "par", "pas", "pour", "qu", "que", "qui", "sa",
+                "se", "ses", "son", "sur", "ta", "te", "un", "une", "vous",
+            ],
+            Self::Spanish => &[
+                "a", "al", "como", "con", "de", "del", "el", "en", "es", "esta", "este", "la", "las",
+                "le", "lo", "los", "más", "mi", "no", "o", "para", "pero", "por", "que", "se", "sin",
+                "su", "sus", "un", "una", "uno", "y", "ya",
+            ],
+            Self::Neutral => &[],
+        }
+    }
+
+    /// Reduce a lowercase word to its stem
+    ///
+    /// These are light stemmers: they strip plural and the most common
+    /// inflectional suffixes, keeping at least three characters, rather
+    /// than implementing full Snowball rules.
+    pub fn stem(&self, word: &str) -> String {
+        match self {
+            Self::English => stem_english(word),
+            Self::German => {
+                let word: String = word.chars().map(|c| match c {
+                    'ä' => 'a',
+                    'ö' => 'o',
+                    'ü' => 'u',
+                    'ß' => 's',
+                    c => c,
+                }).collect();
+                strip_suffix(&word, &["heiten", "keiten", "ungen", "heit", "keit", "ung", "ern", "em", "en", "er", "es", "e", "s", "n"])
+            }
+            Self::French => strip_suffix(word, &[
+                "issements", "issement", "ations", "ation", "ements", "ement", "euses", "euse",
+                "ités", "ité", "es", "s", "e", "x",
+            ]),
+            Self::Spanish => strip_suffix(word, &[
+                "amientos", "imientos", "amiento", "imiento", "aciones", "ación", "acion", "mente",
+                "idades", "idad", "ces", "es", "os", "as", "s", "o", "a",
+            ]),
+            Self::Neutral => word.to_string(),
+        }
+    }
+}
+
+/// Minimum characters a stem keeps
+const MIN_STEM: usize = 3;
+
+/// Remove the first of the suffixes that leaves a long enough stem
+fn strip_suffix(word: &str, suffixes: &[&str]) -> String {
+    for suffix in suffixes {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            if stem.chars().count() >= MIN_STEM {
+                return stem.to_string();
+            }
+        }
+    }
+    word.to_string()
+}
+
+/// English plurals, then -ing, -ed and -ly
+fn stem_english(word: &str) -> String {
+    let has_vowel = |s: &str| s.chars().any(|c| "aeiouy".contains(c));
+    let long_enough = |s: &str| s.chars().count() >= MIN_STEM && has_vowel(s);
+
+    let mut word = word.to_string();
+    if let Some(stem) = word.strip_suffix("ies").filter(|s| long_enough(s)) {
+        word = format!("{}y", stem);
+    } else if let Some(stem) = word.strip_suffix("sses") {
+        word = format!("{}ss", stem);
+    } else if ["ches", "shes", "xes", "zes"].iter().any(|s| word.ends_with(s)) && word.len() > 4 {
+        word.truncate(word.len() - 2);
+    } else if word.ends_with('s') && !["ss", "us", "is"].iter().any(|s| word.ends_with(s)) && word.len() > MIN_STEM {
+        word.pop();
+    }
+
+    for suffix in ["ingly", "edly", "ing", "ed", "ly"] {
+        if let Some(stem) = word.strip_suffix(suffix).filter(|s| long_enough(s)) {
+            let mut stem = stem.to_string();
+            // running -> run, stopped -> stop
+            let bytes = stem.as_bytes();
+            if suffix != "ly" && bytes.len() >= 2 && bytes[bytes.len() - 1] == bytes[bytes.len() - 2]
+                && !b"lsz".contains(&bytes[bytes.len() - 1])
+                && !b"aeiou".contains(&bytes[bytes.len() - 1])
+            {
+                stem.pop();
+            }
+            return stem;
+        }
+    }
+    word
+}
+
+/// Turns text into search terms
+#[derive(Debug, Clone)]
+pub struct TextAnalyzer {
+    tokenizer: Tokenizer,
+    language: Language,
+    lowercase: bool,
+    stop_words: HashSet<String>,
+    stemming: bool,
+    ngrams: (usize, usize),
+}
+
+impl Default for TextAnalyzer {
+    fn default() -> Self {
+        Self::new(Language::default())
+    }
+}
+
+impl TextAnalyzer {
+    /// Create an analyzer with a language's stop words and stemming
+    pub fn new(language: Language) -> Self {
+        Self {
+            tokenizer: Tokenizer::default(),
+            language,
+            lowercase: true,
+            stop_words: language.stop_words().iter().map(|w| w.to_string()).collect(),
+            stemming: language != Language::Neutral,
+            ngrams: (1, 1),
+        }
+    }
+
+    /// Set how text is split into tokens
+    pub fn tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    /// Set whether tokens are lowercased
+    pub fn lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Replace the stop words; pass an empty list to keep every word
+    pub fn stop_words<I, S>(mut self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.stop_words = words.into_iter().map(|w| w.as_ref().to_lowercase()).collect();
+        self
+    }
+
+    /// Set whether words are reduced to their stem
+    pub fn stemming(mut self, stemming: bool) -> Self {
+        self.stemming = stemming;
+        self
+    }
+
+    /// Emit runs of `min` to `max` consecutive words as terms, joined by a
+    /// space; `(1, 2)` adds word pairs to the single words
+    pub fn ngrams(mut self, min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        self.ngrams = (min, max.max(min));
+        self
+    }
+
+    /// Get the language
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Terms of a text, in order
+    pub fn analyze(&self, text: &str) -> Vec<String> {
+        let tokens: Vec<&str> = match self.tokenizer {
+            Tokenizer::Words => text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).collect(),
+            Tokenizer::Whitespace => text.split_whitespace().collect(),
+        };
+
+        let words: Vec<String> = tokens
+            .into_iter()
+            .map(|token| if self.lowercase { token.to_lowercase() } else { token.to_string() })
+            .filter(|word| !self.stop_words.contains(&word.to_lowercase()))
+            .map(|word| if self.stemming { self.language.stem(&word) } else { word })
+            .collect();
+
+        let (min, max) = self.ngrams;
+        if (min, max) == (1, 1) {
+            return words;
+        }
+        let mut terms = Vec::new();
+        for start in 0..words.len() {
+            for n in min..=max.min(words.len() - start) {
+                terms.push(words[start..start + n].join(" "));
+            }
+        }
+        terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english_analysis() {
+        let analyzer = TextAnalyzer::default();
+        assert_eq!(
+            analyzer.analyze("The networks were running; studies learned quickly!"),
+            ["network", "run", "study", "learn", "quick"]
+        );
+        assert_eq!(analyzer.analyze("classes boxes bus analysis"), ["class", "box", "bus", "analysis"]);
+
+        let raw = TextAnalyzer::new(Language::Neutral).tokenizer(Tokenizer::Whitespace);
+        assert_eq!(raw.analyze("The Networks, running"), ["the", "networks,", "running"]);
+
+        let custom = TextAnalyzer::default().stop_words(["networks"]).stemming(false).lowercase(false);
+        assert_eq!(custom.analyze("The Networks run"), ["The", "run"]);
+    }
+
+    #[test]
+    fn test_ngrams_and_languages() {
+        let analyzer = TextAnalyzer::default().ngrams(1, 2);
+        assert_eq!(
+            analyzer.analyze("machine learning models"),
+            ["machine", "machine learn", "learn", "learn model", "model"]
+        );
+        assert_eq!(TextAnalyzer::default().ngrams(2, 2).analyze("neural network"), ["neural network"]);
+
+        let german = TextAnalyzer::new(Language::parse("de").unwrap());
+        assert_eq!(german.analyze("Die Häuser und die Zeitungen"), ["haus", "zeit"]);
+        let french = TextAnalyzer::new(Language::French);
+        assert_eq!(french.analyze("les réseaux neuronaux"), ["réseau", "neuronau"]);
+        let spanish = TextAnalyzer::new(Language::Spanish);
+        assert_eq!(spanish.analyze("las redes neuronales"), ["red", "neuronal"]);
+        assert!(Language::parse("klingon").is_err());
+    }
+}
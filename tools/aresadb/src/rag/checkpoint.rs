@@ -0,0 +1,138 @@
+//! Resumable embedding
+//!
+//! A large ingest spends most of its time waiting on the embedding
+//! provider, and writes each document only once all its chunks are
+//! embedded. The checkpoint appends every embedding to a file as it
+//! arrives, keyed by content hash, so an ingest that dies halfway picks up
+//! where it stopped instead of paying for the same embeddings again.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    provider: String,
+    hash: String,
+    embedding: Vec<f32>,
+}
+
+/// Embeddings made so far by an interrupted ingest
+pub struct EmbeddingCheckpoint {
+    path: PathBuf,
+    provider: String,
+    embeddings: HashMap<String, Vec<f32>>,
+    file: File,
+}
+
+impl EmbeddingCheckpoint {
+    /// Open or create a checkpoint file
+    ///
+    /// Only embeddings made by `provider` are reused, so switching models
+    /// between runs doesn't mix vectors. A line cut short by a crash is
+    /// ignored.
+    pub fn open(path: impl AsRef<Path>, provider: &str) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut embeddings = HashMap::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)
+                .with_context(|| format!("Failed to read checkpoint {}", path.display()))?);
+            for line in reader.lines() {
+                if let Ok(entry) = serde_json::from_str::<Entry>(&line?) {
+                    if entry.provider == provider {
+                        embeddings.insert(entry.hash, entry.embedding);
+                    }
+                }
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open checkpoint {}", path.display()))?;
+        // Start on a fresh line after a partial one
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                writeln!(file)?;
+            }
+        }
+        Ok(Self { path, provider: provider.to_string(), embeddings, file })
+    }
+
+    /// Number of embeddings available to reuse
+    pub fn len(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    /// Whether there is nothing to reuse
+    pub fn is_empty(&self) -> bool {
+        self.embeddings.is_empty()
+    }
+
+    /// The stored embedding of a chunk, by content hash
+    pub fn get(&self, hash: &str) -> Option<&Vec<f32>> {
+        self.embeddings.get(hash)
+    }
+
+    /// Save an embedding
+    pub fn record(&mut self, hash: &str, embedding: &[f32]) -> Result<()> {
+        let entry = Entry {
+            provider: self.provider.clone(),
+            hash: hash.to_string(),
+            embedding: embedding.to_vec(),
+        };
+        writeln!(self.file, "{}", serde_json::to_string(&entry)?)?;
+        self.file.flush()?;
+        self.embeddings.insert(entry.hash, entry.embedding);
+        Ok(())
+    }
+
+    /// Delete the checkpoint once the ingest has finished
+    pub fn finish(self) -> Result<()> {
+        drop(self.file);
+        std::fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove checkpoint {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_resume() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("ingest.checkpoint");
+
+        let mut checkpoint = EmbeddingCheckpoint::open(&path, "local").unwrap();
+        assert!(checkpoint.is_empty());
+        checkpoint.record("aaa", &[1.0, 0.0]).unwrap();
+        checkpoint.record("bbb", &[0.0, 1.0]).unwrap();
+        drop(checkpoint);
+
+        // A crash mid-write leaves a partial last line
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"provider\":\"local\",\"hash\":\"ccc\",\"embe").unwrap();
+        drop(file);
+
+        let mut checkpoint = EmbeddingCheckpoint::open(&path, "local").unwrap();
+        assert_eq!(checkpoint.len(), 2);
+        checkpoint.record("ddd", &[0.5, 0.5]).unwrap();
+        drop(checkpoint);
+        let checkpoint = EmbeddingCheckpoint::open(&path, "local").unwrap();
+        assert_eq!(checkpoint.len(), 3);
+        assert_eq!(checkpoint.get("bbb"), Some(&vec![0.0, 1.0]));
+        assert!(EmbeddingCheckpoint::open(&path, "openai").unwrap().is_empty());
+
+        checkpoint.finish().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -5,6 +5,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::code::{self, CodeLanguage, Line};
+use super::tokenizer::TokenCounter;
+
 /// A chunk of a document
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentChunk {
@@ -34,7 +37,8 @@ pub enum ChunkStrategy {
         chunk_size: usize,
         overlap: usize,
     },
-    /// Split by sentences, grouping up to max_tokens
+    /// Split by sentences, grouping up to max_tokens as counted by the
+    /// chunker's token counter
     Sentence {
         max_tokens: usize,
     },
@@ -46,6 +50,30 @@ pub enum ChunkStrategy {
     Semantic {
         max_size: usize,
     },
+    /// Split Markdown on headings and between blocks, never inside a code
+    /// fence; each chunk's metadata carries its heading breadcrumb
+    Markdown {
+        /// Largest chunk in bytes; code fences may exceed it
+        max_size: usize,
+    },
+    /// Split recursively on paragraphs, then lines, sentences, words and
+    /// characters, using the coarsest boundary that yields pieces of at
+    /// most chunk_size characters
+    Recursive {
+        /// Largest chunk in characters
+        chunk_size: usize,
+        /// Characters repeated from the end of the previous chunk, made of
+        /// whole pieces at the level the chunks were split on
+        overlap: usize,
+    },
+    /// Split source code between top-level items (functions, classes,
+    /// impl blocks), and between their members when an item is too large
+    Code {
+        /// Language of the source
+        language: CodeLanguage,
+        /// Largest chunk in bytes
+        max_size: usize,
+    },
 }
 
 impl Default for ChunkStrategy {
@@ -60,12 +88,20 @@ impl Default for ChunkStrategy {
 /// Document chunker
 pub struct Chunker {
     strategy: ChunkStrategy,
+    tokens: TokenCounter,
 }
 
 impl Chunker {
-    /// Create a new chunker with the given strategy
+    /// Create a new chunker with the given strategy, counting tokens with
+    /// the tokenizer configured by `ARESADB_TOKENIZER`
     pub fn new(strategy: ChunkStrategy) -> Self {
-        Self { strategy }
+        Self { strategy, tokens: TokenCounter::from_env() }
+    }
+
+    /// Count tokens with a specific counter
+    pub fn with_token_counter(mut self, tokens: TokenCounter) -> Self {
+        self.tokens = tokens;
+        self
     }
 
     /// Create a chunker with default settings (512 chars, 50 overlap)
@@ -98,6 +134,15 @@ impl Chunker {
             ChunkStrategy::Semantic { max_size } => {
                 self.chunk_semantic(document_id, content, max_size)
             }
+            ChunkStrategy::Markdown { max_size } => {
+                self.chunk_markdown(document_id, content, max_size)
+            }
+            ChunkStrategy::Recursive { chunk_size, overlap } => {
+                self.chunk_recursive(document_id, content, chunk_size, overlap)
+            }
+            ChunkStrategy::Code { language, max_size } => {
+                self.chunk_code(document_id, content, language, max_size)
+            }
         }
     }
 
@@ -168,17 +213,14 @@ impl Chunker {
         let sentences = self.split_sentences(content);
         let mut chunks = Vec::new();
         let mut current_chunk = String::new();
+        let mut current_tokens = 0;
         let mut current_start = 0;
         let mut chunk_index = 0;
 
         for sentence in sentences {
-            let sentence_len = sentence.chars().count();
-            let current_len = current_chunk.chars().count();
-
-            // Rough token estimate: chars / 4
-            let estimated_tokens = (current_len + sentence_len) / 4;
+            let sentence_tokens = self.tokens.count(sentence);
 
-            if estimated_tokens > max_tokens && !current_chunk.is_empty() {
+            if current_tokens + sentence_tokens > max_tokens && !current_chunk.is_empty() {
                 // Save current chunk and start new one
                 let end_offset = current_start + current_chunk.len();
                 chunks.push(DocumentChunk {
@@ -194,11 +236,13 @@ impl Chunker {
                 chunk_index += 1;
                 current_start = end_offset;
                 current_chunk = sentence.to_string();
+                current_tokens = sentence_tokens;
             } else {
                 if !current_chunk.is_empty() {
                     current_chunk.push(' ');
                 }
                 current_chunk.push_str(sentence);
+                current_tokens += sentence_tokens;
             }
         }
 
@@ -370,6 +414,170 @@ impl Chunker {
         chunks
     }
 
+    /// Markdown chunking
+    ///
+    /// Sections start at ATX headings. Within a section, blocks (paragraphs,
+    /// lists, code fences) are packed into chunks of up to `max_size`
+    /// bytes. A code fence larger than that becomes a chunk of its own
+    /// rather than being split; other oversized blocks are split on lines.
+    fn chunk_markdown(
+        &self,
+        document_id: &str,
+        content: &str,
+        max_size: usize,
+    ) -> Vec<DocumentChunk> {
+        let max_size = max_size.max(1);
+        let mut chunks = Vec::new();
+
+        for section in markdown_sections(content) {
+            let metadata = (!section.headings.is_empty()).then(|| serde_json::json!({
+                "headings": section.headings,
+                "breadcrumb": section.headings.join(" > "),
+            }));
+            let mut push = |start: usize, end: usize| {
+                let text = content[start..end].trim();
+                if !text.is_empty() {
+                    chunks.push(DocumentChunk {
+                        id: String::new(),
+                        document_id: document_id.to_string(),
+                        content: text.to_string(),
+                        chunk_index: 0,
+                        total_chunks: 0,
+                        start_offset: start,
+                        end_offset: end,
+                        metadata: metadata.clone(),
+                    });
+                }
+            };
+
+            let mut current: Option<(usize, usize)> = None;
+            for block in section.blocks {
+                if let Some((start, end)) = current {
+                    if block.end - start <= max_size {
+                        current = Some((start, block.end));
+                        continue;
+                    }
+                    push(start, end);
+                    current = None;
+                }
+
+                if block.end - block.start <= max_size || block.code {
+                    current = Some((block.start, block.end));
+                } else {
+                    for (start, end) in split_lines(content, block.start, block.end, max_size) {
+                        push(start, end);
+                    }
+                }
+            }
+            if let Some((start, end)) = current {
+                push(start, end);
+            }
+        }
+
+        let total = chunks.len();
+        for (i, chunk) in chunks.iter_mut().enumerate() {
+            chunk.id = format!("{}_{}", document_id, i);
+            chunk.chunk_index = i;
+            chunk.total_chunks = total;
+        }
+
+        chunks
+    }
+
+    /// Recursive chunking
+    ///
+    /// The text is split on the coarsest separator first and the pieces are
+    /// packed into chunks; only a piece larger than a chunk is split again
+    /// on the next separator. Overlap is taken from whole trailing pieces
+    /// of the previous chunk, so it never starts mid-sentence when the
+    /// chunks were split on sentences, or mid-word when split on words.
+    fn chunk_recursive(
+        &self,
+        document_id: &str,
+        content: &str,
+        chunk_size: usize,
+        overlap: usize,
+    ) -> Vec<DocumentChunk> {
+        let mut ranges = Vec::new();
+        split_recursive(content, 0, content.len(), 0, chunk_size.max(1), overlap, &mut ranges);
+
+        let mut chunks = Vec::new();
+        for (start, end) in ranges {
+            let text = content[start..end].trim();
+            if text.is_empty() {
+                continue;
+            }
+            chunks.push(DocumentChunk {
+                id: format!("{}_{}", document_id, chunks.len()),
+                document_id: document_id.to_string(),
+                content: text.to_string(),
+                chunk_index: chunks.len(),
+                total_chunks: 0,
+                start_offset: start,
+                end_offset: end,
+                metadata: None,
+            });
+        }
+
+        let total = chunks.len();
+        for chunk in &mut chunks {
+            chunk.total_chunks = total;
+        }
+
+        chunks
+    }
+
+    /// Source code chunking
+    ///
+    /// Items small enough are packed together up to `max_size` bytes; an
+    /// item too large is split between its members (methods of a class or
+    /// impl block), and failing that on lines. Each chunk's metadata lists
+    /// the language, the signatures of the items it holds and, for
+    /// members, the enclosing item.
+    fn chunk_code(
+        &self,
+        document_id: &str,
+        content: &str,
+        language: CodeLanguage,
+        max_size: usize,
+    ) -> Vec<DocumentChunk> {
+        let lines = code::scan_lines(content, language);
+        let mut pieces = Vec::new();
+        collect_code(content, &lines, 0, language, max_size.max(1), None, &mut pieces);
+
+        let mut chunks = Vec::new();
+        for piece in pieces {
+            let text = content[piece.start..piece.end].trim();
+            if text.is_empty() {
+                continue;
+            }
+            let mut metadata = serde_json::json!({
+                "language": language.name(),
+                "symbols": piece.symbols,
+            });
+            if let Some(parent) = piece.parent {
+                metadata["parent"] = serde_json::json!(parent);
+            }
+            chunks.push(DocumentChunk {
+                id: format!("{}_{}", document_id, chunks.len()),
+                document_id: document_id.to_string(),
+                content: text.to_string(),
+                chunk_index: chunks.len(),
+                total_chunks: 0,
+                start_offset: piece.start,
+                end_offset: piece.end,
+                metadata: Some(metadata),
+            });
+        }
+
+        let total = chunks.len();
+        for chunk in &mut chunks {
+            chunk.total_chunks = total;
+        }
+
+        chunks
+    }
+
     /// Split text into sentences (simple implementation)
     fn split_sentences<'a>(&self, text: &'a str) -> Vec<&'a str> {
         let mut sentences = Vec::new();
@@ -424,6 +632,303 @@ impl Chunker {
     }
 }
 
+/// Separator levels of the recursive splitter: paragraphs, lines,
+/// sentences, words and characters
+const RECURSIVE_LEVELS: usize = 5;
+
+/// Pack the pieces of a byte range split at `level` into chunks of at most
+/// `chunk_size` characters, recursing into pieces that are too large
+fn split_recursive(
+    content: &str,
+    start: usize,
+    end: usize,
+    level: usize,
+    chunk_size: usize,
+    overlap: usize,
+    ranges: &mut Vec<(usize, usize)>,
+) {
+    // Pieces in the chunk being built, with their lengths in characters
+    let mut window: std::collections::VecDeque<(usize, usize, usize)> = Default::default();
+    let mut window_len = 0;
+
+    for (piece_start, piece_end) in separator_pieces(content, start, end, level) {
+        let len = content[piece_start..piece_end].chars().count();
+
+        if len > chunk_size && level + 1 < RECURSIVE_LEVELS {
+            if let (Some(first), Some(last)) = (window.front(), window.back()) {
+                ranges.push((first.0, last.1));
+            }
+            window.clear();
+            window_len = 0;
+            split_recursive(content, piece_start, piece_end, level + 1, chunk_size, overlap, ranges);
+            continue;
+        }
+
+        if window_len + len > chunk_size && !window.is_empty() {
+            if let (Some(first), Some(last)) = (window.front(), window.back()) {
+                ranges.push((first.0, last.1));
+            }
+            // Keep whole trailing pieces as overlap, leaving room for this one
+            while window_len > overlap || (window_len + len > chunk_size && !window.is_empty()) {
+                let (_, _, dropped) = window.pop_front().expect("window is not empty");
+                window_len -= dropped;
+            }
+        }
+        window.push_back((piece_start, piece_end, len));
+        window_len += len;
+    }
+
+    if let (Some(first), Some(last)) = (window.front(), window.back()) {
+        ranges.push((first.0, last.1));
+    }
+}
+
+/// Split a byte range on the separator of a level, keeping each separator
+/// at the end of the piece before it
+fn separator_pieces(content: &str, start: usize, end: usize, level: usize) -> Vec<(usize, usize)> {
+    let text = &content[start..end];
+    let mut pieces = Vec::new();
+    let mut piece_start = 0;
+    let mut push = |piece_end: usize| {
+        if piece_end > piece_start {
+            pieces.push((start + piece_start, start + piece_end));
+            piece_start = piece_end;
+        }
+    };
+
+    match level {
+        0 | 1 | 3 => {
+            let mut offset = 0;
+            let parts: Box<dyn Iterator<Item = &str>> = match level {
+                0 => Box::new(text.split_inclusive("\n\n")),
+                1 => Box::new(text.split_inclusive('\n')),
+                _ => Box::new(text.split_inclusive(char::is_whitespace)),
+            };
+            for part in parts {
+                offset += part.len();
+                push(offset);
+            }
+        }
+        2 => {
+            let mut ended = false;
+            for (i, c) in text.char_indices() {
+                if ended && !c.is_whitespace() {
+                    push(i);
+                }
+                ended = matches!(c, '.' | '!' | '?') || (ended && c.is_whitespace());
+            }
+        }
+        _ => {
+            for (i, c) in text.char_indices() {
+                push(i + c.len_utf8());
+            }
+        }
+    }
+    push(text.len());
+    pieces
+}
+
+/// Byte range of a code chunk with the items it holds
+struct CodePiece {
+    start: usize,
+    end: usize,
+    symbols: Vec<String>,
+    parent: Option<String>,
+}
+
+/// Split the items at `level` into pieces of at most `max_size` bytes
+fn collect_code(
+    content: &str,
+    lines: &[Line],
+    level: usize,
+    language: CodeLanguage,
+    max_size: usize,
+    parent: Option<&str>,
+    pieces: &mut Vec<CodePiece>,
+) {
+    let mut current: Option<CodePiece> = None;
+
+    for (a, b) in code::items(content, lines, level, language) {
+        let item = &lines[a..b];
+        let (start, end) = (item[0].start, item[item.len() - 1].end);
+        let symbol = code::signature(content, item, language);
+
+        if let Some(piece) = current.as_mut() {
+            if end - piece.start <= max_size {
+                piece.end = end;
+                piece.symbols.extend(symbol);
+                continue;
+            }
+        }
+        pieces.extend(current.take());
+
+        if end - start <= max_size {
+            current = Some(CodePiece {
+                start,
+                end,
+                symbols: symbol.into_iter().collect(),
+                parent: parent.map(str::to_string),
+            });
+            continue;
+        }
+
+        let members = code::inner_level(item, level, language)
+            .filter(|inner| code::items(content, item, *inner, language).len() > 1);
+        match members {
+            Some(inner) => {
+                let outer = symbol.as_deref().or(parent);
+                collect_code(content, item, inner, language, max_size, outer, pieces);
+            }
+            None => {
+                for (start, end) in split_lines(content, start, end, max_size) {
+                    pieces.push(CodePiece {
+                        start,
+                        end,
+                        symbols: symbol.iter().cloned().collect(),
+                        parent: parent.map(str::to_string),
+                    });
+                }
+            }
+        }
+    }
+    pieces.extend(current);
+}
+
+/// A heading-delimited part of a Markdown document
+struct MarkdownSection {
+    /// Titles of the enclosing headings, outermost first
+    headings: Vec<String>,
+    blocks: Vec<MarkdownBlock>,
+}
+
+/// Byte range of a paragraph, heading or code fence
+struct MarkdownBlock {
+    start: usize,
+    end: usize,
+    code: bool,
+}
+
+/// Split Markdown into sections of blocks
+fn markdown_sections(content: &str) -> Vec<MarkdownSection> {
+    let mut sections = vec![MarkdownSection { headings: Vec::new(), blocks: Vec::new() }];
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    // Open code fence: marker character, marker length and block start
+    let mut fence: Option<(char, usize, usize)> = None;
+    // Start of the paragraph being read
+    let mut paragraph: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let section = sections.last_mut().expect("there is always a section");
+
+        if let Some((marker, len, block_start)) = fence {
+            if fence_marker(line).is_some_and(|(m, l, rest)| m == marker && l >= len && rest.trim().is_empty()) {
+                section.blocks.push(MarkdownBlock { start: block_start, end: offset, code: true });
+                fence = None;
+            }
+            continue;
+        }
+
+        let opens_fence = fence_marker(line).filter(|(m, _, rest)| *m == '~' || !rest.contains('`'));
+        let heading = heading(line);
+        if line.trim().is_empty() || opens_fence.is_some() || heading.is_some() {
+            if let Some(block_start) = paragraph.take() {
+                section.blocks.push(MarkdownBlock { start: block_start, end: start, code: false });
+            }
+        }
+
+        if let Some((marker, len, _)) = opens_fence {
+            fence = Some((marker, len, start));
+        } else if let Some((level, title)) = heading {
+            while stack.last().is_some_and(|(l, _)| *l >= level) {
+                stack.pop();
+            }
+            stack.push((level, title));
+            sections.push(MarkdownSection {
+                headings: stack.iter().map(|(_, t)| t.clone()).collect(),
+                blocks: vec![MarkdownBlock { start, end: offset, code: false }],
+            });
+        } else if !line.trim().is_empty() && paragraph.is_none() {
+            paragraph = Some(start);
+        }
+    }
+
+    let section = sections.last_mut().expect("there is always a section");
+    if let Some((_, _, block_start)) = fence {
+        // Unclosed fence runs to the end of the document
+        section.blocks.push(MarkdownBlock { start: block_start, end: offset, code: true });
+    } else if let Some(block_start) = paragraph {
+        section.blocks.push(MarkdownBlock { start: block_start, end: offset, code: false });
+    }
+    sections
+}
+
+/// Code fence marker of a line: its character, length and the rest
+fn fence_marker(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == marker).count();
+    (len >= 3).then(|| (marker, len, &trimmed[len..]))
+}
+
+/// Level and title of an ATX heading line
+fn heading(line: &str) -> Option<(usize, String)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|c| *c == '#').count();
+    let rest = &trimmed[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t', '\n', '\r'])) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end().to_string()))
+}
+
+/// Split a byte range into pieces of at most `max_size` bytes, at line
+/// ends where possible and at character boundaries otherwise
+fn split_lines(content: &str, start: usize, end: usize, max_size: usize) -> Vec<(usize, usize)> {
+    let mut pieces = Vec::new();
+    let mut piece_start = start;
+    let mut piece_end = start;
+    let mut offset = start;
+
+    for line in content[start..end].split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        if offset - piece_start <= max_size {
+            piece_end = offset;
+            continue;
+        }
+        if piece_end > piece_start {
+            pieces.push((piece_start, piece_end));
+        }
+        piece_start = line_start;
+        // A single line longer than a piece
+        while offset - piece_start > max_size {
+            let mut cut = piece_start + max_size;
+            while !content.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            if cut == piece_start {
+                cut = piece_start + content[piece_start..].chars().next().map_or(1, char::len_utf8);
+            }
+            pieces.push((piece_start, cut));
+            piece_start = cut;
+        }
+        piece_end = offset;
+    }
+    if piece_end > piece_start {
+        pieces.push((piece_start, piece_end));
+    }
+    pieces
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -476,6 +981,104 @@ mod tests {
         assert!(chunks.len() >= 2);
     }
 
+    #[test]
+    fn test_markdown_chunking() {
+        let chunker = Chunker::new(ChunkStrategy::Markdown { max_size: 80 });
+        let content = "Preamble text.\n\n\
+# Guide\n\nIntro to the guide.\n\n\
+## Install\n\nRun the installer.\n\n\
+```sh\n# not a heading\ncargo install aresadb --locked --features full\n\ncargo test\n```\n\n\
+### Linux ###\n\nUse the package.\n\n\
+# FAQ\n\nNone yet.\n";
+        let chunks = chunker.chunk("doc", content);
+        let breadcrumb = |c: &DocumentChunk| {
+            c.metadata.as_ref().map(|m| m["breadcrumb"].as_str().unwrap().to_string())
+        };
+
+        assert_eq!(chunks[0].content, "Preamble text.");
+        assert_eq!(breadcrumb(&chunks[0]), None);
+        assert_eq!(breadcrumb(&chunks[1]).as_deref(), Some("Guide"));
+
+        // The code fence is longer than a chunk but stays whole
+        let code = chunks.iter().find(|c| c.content.contains("cargo install")).unwrap();
+        assert!(code.content.starts_with("```sh") && code.content.ends_with("```"));
+        assert_eq!(breadcrumb(code).as_deref(), Some("Guide > Install"));
+        assert!(chunks.iter().all(|c| breadcrumb(c).as_deref() != Some("Guide > not a heading")));
+
+        let linux = chunks.iter().find(|c| c.content.contains("Use the package")).unwrap();
+        assert_eq!(breadcrumb(linux).as_deref(), Some("Guide > Install > Linux"));
+        let faq = chunks.last().unwrap();
+        assert_eq!(breadcrumb(faq).as_deref(), Some("FAQ"));
+        assert_eq!(faq.chunk_index + 1, faq.total_chunks);
+        assert_eq!(&content[faq.start_offset..faq.end_offset].trim(), &faq.content);
+
+        // Oversized prose is split on lines
+        let prose = "word ".repeat(20) + "\n" + &"more ".repeat(20);
+        let chunks = Chunker::new(ChunkStrategy::Markdown { max_size: 110 }).chunk("p", &prose);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|c| c.content.len() <= 110));
+    }
+
+    #[test]
+    fn test_recursive_chunking() {
+        let content = "First paragraph is short.\n\n\
+Second paragraph runs long. It has three sentences. Each is short enough.\n\n\
+Third.";
+        let chunker = Chunker::new(ChunkStrategy::Recursive { chunk_size: 40, overlap: 0 });
+        let chunks = chunker.chunk("doc", content);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(texts, [
+            "First paragraph is short.",
+            "Second paragraph runs long.",
+            "It has three sentences.",
+            "Each is short enough.",
+            "Third.",
+        ]);
+        assert_eq!(&content[chunks[1].start_offset..chunks[1].start_offset + 6], "Second");
+
+        // Overlap repeats whole sentences, never part of one
+        let chunker = Chunker::new(ChunkStrategy::Recursive { chunk_size: 60, overlap: 30 });
+        let chunks = chunker.chunk("doc", content);
+        assert!(chunks.iter().any(|c| c.content == "Second paragraph runs long. It has three sentences."));
+        assert!(chunks.iter().any(|c| c.content == "It has three sentences. Each is short enough."));
+
+        // Words longer than a chunk fall back to characters
+        let chunks = Chunker::new(ChunkStrategy::Recursive { chunk_size: 4, overlap: 0 })
+            .chunk("doc", "abcdefghij kl");
+        let texts: Vec<&str> = chunks.iter().map(|c| c.content.as_str()).collect();
+        assert_eq!(texts, ["abcd", "efgh", "ij", "kl"]);
+    }
+
+    #[test]
+    fn test_code_chunking() {
+        let method = |name: &str| format!(
+            "    /// Does {name}\n    fn {name}(&self) -> usize {{\n        let x = {{ 1 }};\n        x + 1\n    }}\n\n"
+        );
+        let content = format!(
+            "use std::fmt;\n\nconst LIMIT: usize = 3;\n\nimpl Engine {{\n{}{}{}}}\n\nfn main() {{}}\n",
+            method("start"), method("stop"), method("restart"),
+        );
+        let chunker = Chunker::new(ChunkStrategy::Code { language: CodeLanguage::Rust, max_size: 150 });
+        let chunks = chunker.chunk("engine.rs", &content);
+        let meta = |c: &DocumentChunk| c.metadata.clone().unwrap();
+
+        // Small top-level items are packed together
+        assert!(chunks[0].content.starts_with("use std::fmt;") && chunks[0].content.contains("LIMIT"));
+        assert_eq!(meta(&chunks[0])["language"], "rust");
+
+        // The impl is too large, so it is split between methods
+        let stop = chunks.iter().find(|c| c.content.contains("fn stop")).unwrap();
+        assert!(stop.content.starts_with("/// Does stop") || stop.content.contains("\n    /// Does stop"));
+        assert!(!stop.content.contains("fn start") || !stop.content.contains("fn restart"));
+        assert_eq!(meta(stop)["parent"], "impl Engine");
+        for chunk in &chunks {
+            // No method is cut in half
+            assert_eq!(chunk.content.matches("fn ").count(), chunk.content.matches("x + 1").count()
+                + usize::from(chunk.content.contains("fn main")));
+        }
+        assert!(chunks.last().unwrap().content.ends_with("fn main() {}"));
+    }
+
     #[test]
     fn test_token_estimation() {
         let text = "This is a test sentence with some words.";
@@ -0,0 +1,332 @@
+//! Source code structure for chunking
+//!
+//! Finds the top-level items of a source file (functions, classes, impl
+//! blocks) with lightweight per-language heuristics: brace depth for
+//! C-family languages and indentation for Python. Comments, attributes
+//! and decorators stay with the item they precede. This is not a parser;
+//! it only needs to find boundaries good enough to keep items whole.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Language of a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CodeLanguage {
+    /// Rust
+    Rust,
+    /// Python
+    Python,
+    /// JavaScript and TypeScript
+    JavaScript,
+    /// Go
+    Go,
+    /// Java, Kotlin and Scala
+    Java,
+    /// C, C++ and C#
+    C,
+}
+
+impl CodeLanguage {
+    /// Language for a file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Some(Self::JavaScript),
+            "go" => Some(Self::Go),
+            "java" | "kt" | "kts" | "scala" => Some(Self::Java),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "cs" => Some(Self::C),
+            _ => None,
+        }
+    }
+
+    /// Language of a file, by its extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Self::from_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    /// Language by name, such as `rust` or `typescript`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "rust" => Some(Self::Rust),
+            "python" => Some(Self::Python),
+            "javascript" | "typescript" => Some(Self::JavaScript),
+            "go" | "golang" => Some(Self::Go),
+            "java" | "kotlin" | "scala" => Some(Self::Java),
+            "c" | "cpp" | "c++" | "csharp" | "c#" => Some(Self::C),
+            other => Self::from_extension(other),
+        }
+    }
+
+    /// Lowercase name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::Go => "go",
+            Self::Java => "java",
+            Self::C => "c",
+        }
+    }
+
+    /// Whether a line only annotates the item after it
+    fn is_prefix(&self, line: &str) -> bool {
+        let line = line.trim();
+        match self {
+            Self::Python => line.starts_with('#') || line.starts_with('@'),
+            Self::Rust => line.starts_with("//") || line.starts_with("#[") || line.starts_with("/*")
+                || line.starts_with('*'),
+            Self::Java | Self::JavaScript => line.starts_with("//") || line.starts_with("/*")
+                || line.starts_with('*') || line.starts_with('@'),
+            Self::Go | Self::C => line.starts_with("//") || line.starts_with("/*") || line.starts_with('*')
+                || (matches!(self, Self::C) && line.starts_with('[')),
+        }
+    }
+
+    /// Whether a line continues the statement before it at the same level
+    fn is_continuation(&self, line: &str) -> bool {
+        let line = line.trim_start();
+        match self {
+            Self::Python => ["else", "elif", "except", "finally", ")", "]", "}"]
+                .iter()
+                .any(|p| line.starts_with(p)),
+            _ => ["}", ")", "]", ".", "else", "catch", "finally", "where", "->", "&&", "||", "?", ":"]
+                .iter()
+                .any(|p| line.starts_with(p)),
+        }
+    }
+}
+
+/// Byte range of a line and its nesting level; `None` inside multi-line
+/// strings, comments or bracketed continuations, where no item can start
+pub(super) struct Line {
+    pub start: usize,
+    pub end: usize,
+    pub level: Option<usize>,
+}
+
+/// Lines of a source file with their nesting levels
+pub(super) fn scan_lines(content: &str, language: CodeLanguage) -> Vec<Line> {
+    match language {
+        CodeLanguage::Python => scan_indented(content),
+        _ => scan_braced(content, language),
+    }
+}
+
+/// Levels by brace depth
+fn scan_braced(content: &str, language: CodeLanguage) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut depth = 0usize;
+    let mut in_block_comment = false;
+    // Template literals and raw strings span lines
+    let mut in_multiline_string = false;
+    let mut offset = 0;
+
+    for text in content.split_inclusive('\n') {
+        let start = offset;
+        offset += text.len();
+        let level = (!in_block_comment && !in_multiline_string).then_some(depth);
+
+        let chars: Vec<char> = text.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            if in_block_comment {
+                if c == '*' && next == Some('/') {
+                    in_block_comment = false;
+                    i += 1;
+                }
+            } else if in_multiline_string {
+                if c == '\\' {
+                    i += 1;
+                } else if c == '`' {
+                    in_multiline_string = false;
+                }
+            } else if c == '/' && next == Some('/') {
+                break;
+            } else if c == '/' && next == Some('*') {
+                in_block_comment = true;
+                i += 1;
+            } else if c == '"' {
+                i = skip_string(&chars, i, '"');
+            } else if c == '\'' {
+                // Character literal; Rust lifetimes ('a) have no closing quote
+                let closes = next == Some('\\') || chars.get(i + 2) == Some(&'\'');
+                if closes || language != CodeLanguage::Rust {
+                    i = skip_string(&chars, i, '\'');
+                }
+            } else if c == '`' && matches!(language, CodeLanguage::JavaScript | CodeLanguage::Go) {
+                in_multiline_string = true;
+            } else if c == '{' {
+                depth += 1;
+            } else if c == '}' {
+                depth = depth.saturating_sub(1);
+            }
+            i += 1;
+        }
+
+        lines.push(Line { start, end: offset, level });
+    }
+    lines
+}
+
+/// Index of the quote closing a string opened at `open`, or the last
+/// character of the line if it doesn't close
+fn skip_string(chars: &[char], open: usize, quote: char) -> usize {
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            c if c == quote => return i,
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Levels by indentation
+fn scan_indented(content: &str) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut brackets = 0usize;
+    let mut triple_quote: Option<&str> = None;
+    let mut offset = 0;
+
+    for text in content.split_inclusive('\n') {
+        let start = offset;
+        offset += text.len();
+        let indent = text.len() - text.trim_start().len();
+        let level = (brackets == 0 && triple_quote.is_none()).then_some(indent);
+
+        let mut rest = text;
+        while !rest.is_empty() {
+            if let Some(quote) = triple_quote {
+                match rest.find(quote) {
+                    Some(i) => {
+                        rest = &rest[i + 3..];
+                        triple_quote = None;
+                    }
+                    None => break,
+                }
+                continue;
+            }
+            let c = rest.chars().next().expect("rest is not empty");
+            if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+                triple_quote = Some(&rest[..3]);
+                rest = &rest[3..];
+                continue;
+            }
+            match c {
+                '#' => break,
+                '"' | '\'' => {
+                    let chars: Vec<char> = rest.chars().collect();
+                    let end = skip_string(&chars, 0, c);
+                    let bytes: usize = chars.iter().take(end + 1).map(|c| c.len_utf8()).sum();
+                    rest = &rest[bytes.min(rest.len())..];
+                    continue;
+                }
+                '(' | '[' | '{' => brackets += 1,
+                ')' | ']' | '}' => brackets = brackets.saturating_sub(1),
+                _ => {}
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+
+        lines.push(Line { start, end: offset, level });
+    }
+    lines
+}
+
+/// Split lines into items at a nesting level
+///
+/// Returns line index ranges. Lines before the first item at the level,
+/// such as the header of a class whose methods are being split, form an
+/// item of their own.
+pub(super) fn items(content: &str, lines: &[Line], level: usize, language: CodeLanguage) -> Vec<(usize, usize)> {
+    let mut items = Vec::new();
+    let mut item_start = 0;
+    let mut after_prefix = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let text = &content[line.start..line.end];
+        if text.trim().is_empty() || line.level != Some(level) {
+            continue;
+        }
+        let starts_item = !after_prefix && !language.is_continuation(text);
+        if starts_item && i > item_start {
+            // Blank lines before the item belong to the previous one
+            items.push((item_start, i));
+            item_start = i;
+        }
+        after_prefix = language.is_prefix(text);
+    }
+    if item_start < lines.len() {
+        items.push((item_start, lines.len()));
+    }
+    items
+}
+
+/// Level of the items nested directly inside a line range
+pub(super) fn inner_level(lines: &[Line], level: usize, language: CodeLanguage) -> Option<usize> {
+    match language {
+        CodeLanguage::Python => lines.iter().filter_map(|l| l.level).filter(|l| *l > level).min(),
+        _ => lines.iter().any(|l| l.level == Some(level + 1)).then_some(level + 1),
+    }
+}
+
+/// First line of an item that isn't a comment or attribute, as a label
+pub(super) fn signature(content: &str, lines: &[Line], language: CodeLanguage) -> Option<String> {
+    let line = lines.iter()
+        .map(|l| content[l.start..l.end].trim())
+        .find(|text| !text.is_empty() && !language.is_prefix(text))?;
+    let line = line.trim_end_matches(['{', ':', ' ']);
+    Some(match line.char_indices().nth(120) {
+        Some((cut, _)) => format!("{}...", &line[..cut]),
+        None => line.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item_texts(content: &str, language: CodeLanguage) -> Vec<String> {
+        let lines = scan_lines(content, language);
+        items(content, &lines, 0, language)
+            .into_iter()
+            .map(|(a, b)| content[lines[a].start..lines[b - 1].end].trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_items() {
+        let content = "use std::fmt;\n\n/// Says hi\n#[inline]\nfn hi<'a>(s: &'a str) -> &'a str {\n    let c = '{';\n    \"}\"\n}\n\nimpl Foo {\n    fn a() {}\n}\n";
+        let items = item_texts(content, CodeLanguage::Rust);
+        assert_eq!(items.len(), 3);
+        assert!(items[1].starts_with("/// Says hi") && items[1].ends_with('}'));
+        assert!(items[2].starts_with("impl Foo"));
+    }
+
+    #[test]
+    fn test_python_items() {
+        let content = "import os\n\n@cached\ndef f(a,\n      b):\n    \"\"\"Doc\n\ndef not_an_item():\n\"\"\"\n    return a\n\nclass C:\n    def m(self):\n        pass\n";
+        let items = item_texts(content, CodeLanguage::Python);
+        assert_eq!(items.len(), 3);
+        assert!(items[1].starts_with("@cached") && items[1].ends_with("return a"));
+
+        let lines = scan_lines(content, CodeLanguage::Python);
+        let class = &lines[11..];
+        assert_eq!(inner_level(class, 0, CodeLanguage::Python), Some(4));
+        assert_eq!(signature(content, class, CodeLanguage::Python).as_deref(), Some("class C"));
+    }
+
+    #[test]
+    fn test_languages() {
+        assert_eq!(CodeLanguage::from_path("src/main.rs"), Some(CodeLanguage::Rust));
+        assert_eq!(CodeLanguage::from_path("app.tsx"), Some(CodeLanguage::JavaScript));
+        assert_eq!(CodeLanguage::from_name("TypeScript"), Some(CodeLanguage::JavaScript));
+        assert_eq!(CodeLanguage::from_path("README.md"), None);
+    }
+}
@@ -5,16 +5,22 @@
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::storage::{Database, Node, DistanceMetric, SimilarityResult};
+use crate::storage::{Database, Node, DistanceMetric, SimilarityResult, Value};
 use super::chunker::DocumentChunk;
+use super::filter::MetadataFilter;
+use super::hybrid::FusionStrategy;
+use super::rerank::{DEFAULT_RERANK_CANDIDATES, Reranker};
+use super::tokenizer::TokenCounter;
 
 /// Retrieved context with relevance scores
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RetrievedContext {
     /// The chunks of context retrieved
     pub chunks: Vec<ContextChunk>,
-    /// Total tokens in the context (estimated)
+    /// Total tokens in the context, as counted by the retriever's token
+    /// counter (estimated unless a tokenizer is configured)
     pub estimated_tokens: usize,
     /// Query that was used
     pub query: String,
@@ -35,16 +41,26 @@ pub struct ContextChunk {
     pub document_id: Option<String>,
     /// Chunk index in document (if available)
     pub chunk_index: Option<usize>,
-    /// Additional metadata
-    pub metadata: Option<serde_json::Value>,
+    /// Character offset in the original document (if available)
+    #[serde(default)]
+    pub start_offset: Option<usize>,
+    /// End character offset in the original document (if available)
+    #[serde(default)]
+    pub end_offset: Option<usize>,
+    /// Additional metadata (a storage value, so contexts can be sent over
+    /// the binary client protocol)
+    pub metadata: Option<Value>,
 }
 
 impl RetrievedContext {
     /// Format context as a single string for LLM consumption
+    ///
+    /// Chunks are stitched first, so contiguous chunks of a document form
+    /// one source and overlapping text appears once.
     pub fn format_for_llm(&self) -> String {
         let mut output = String::new();
 
-        for (i, chunk) in self.chunks.iter().enumerate() {
+        for (i, chunk) in self.stitched().chunks.iter().enumerate() {
             if i > 0 {
                 output.push_str("\n\n---\n\n");
             }
@@ -76,6 +92,65 @@ impl RetrievedContext {
         })
     }
 
+    /// Merge chunks that are contiguous in their document
+    ///
+    /// Chunks of the same document are contiguous when their chunk indices
+    /// follow each other or their offsets meet or overlap. Each run becomes
+    /// one block, in the position of its best-ranked chunk, with the text
+    /// the chunks share (chunker overlap) kept once. A block takes the id,
+    /// score and distance of its best chunk and the start of its first.
+    /// `estimated_tokens` is kept as is, an upper bound for the merged text.
+    pub fn stitched(&self) -> Self {
+        // Rank of each chunk, and chunks grouped by document in source order
+        let mut by_document: Vec<(&str, Vec<(usize, &ContextChunk)>)> = Vec::new();
+        let mut blocks: Vec<(usize, ContextChunk)> = Vec::new();
+        for (rank, chunk) in self.chunks.iter().enumerate() {
+            match (chunk.document_id.as_deref(), chunk.chunk_index.or(chunk.start_offset)) {
+                (Some(doc), Some(_)) => match by_document.iter_mut().find(|(d, _)| *d == doc) {
+                    Some((_, chunks)) => chunks.push((rank, chunk)),
+                    None => by_document.push((doc, vec![(rank, chunk)])),
+                },
+                _ => blocks.push((rank, chunk.clone())),
+            }
+        }
+
+        for (_, mut chunks) in by_document {
+            chunks.sort_by_key(|(_, c)| (c.chunk_index, c.start_offset));
+            let mut run: Option<(usize, ContextChunk)> = None;
+            for (rank, chunk) in chunks {
+                run = Some(match run {
+                    Some((best, mut block)) if contiguous(&block, chunk) => {
+                        let overlap = overlap_len(&block, chunk);
+                        if overlap == 0 {
+                            block.content.push('\n');
+                        }
+                        block.content.push_str(&chunk.content[overlap..]);
+                        block.chunk_index = chunk.chunk_index;
+                        block.end_offset = block.end_offset.max(chunk.end_offset);
+                        if rank < best {
+                            block.node_id = chunk.node_id.clone();
+                            block.score = chunk.score;
+                            block.distance = chunk.distance;
+                        }
+                        (best.min(rank), block)
+                    }
+                    previous => {
+                        blocks.extend(previous);
+                        (rank, chunk.clone())
+                    }
+                });
+            }
+            blocks.extend(run);
+        }
+
+        blocks.sort_by_key(|(rank, _)| *rank);
+        Self {
+            chunks: blocks.into_iter().map(|(_, block)| block).collect(),
+            estimated_tokens: self.estimated_tokens,
+            query: self.query.clone(),
+        }
+    }
+
     /// Get only the text content
     pub fn text_only(&self) -> String {
         self.chunks
@@ -86,6 +161,44 @@ impl RetrievedContext {
     }
 }
 
+/// What a context retrieval searches and how much it returns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextOptions {
+    /// Node type holding the chunks
+    pub node_type: String,
+    /// Field containing embeddings
+    pub embedding_field: String,
+    /// Field containing text content
+    pub content_field: String,
+    /// Maximum tokens to retrieve
+    pub max_tokens: usize,
+    /// Minimum similarity score
+    pub min_score: f64,
+    /// Distance metric
+    pub metric: DistanceMetric,
+    /// Only chunks matching this filter are considered
+    #[serde(default)]
+    pub filter: Option<MetadataFilter>,
+    /// Diversify chunks by maximal marginal relevance with this lambda
+    #[serde(default)]
+    pub mmr_lambda: Option<f64>,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            node_type: "chunk".to_string(),
+            embedding_field: "embedding".to_string(),
+            content_field: "content".to_string(),
+            max_tokens: 4096,
+            min_score: 0.0,
+            metric: DistanceMetric::Cosine,
+            filter: None,
+            mmr_lambda: None,
+        }
+    }
+}
+
 /// Context retriever for RAG applications
 pub struct ContextRetriever<'a> {
     db: &'a Database,
@@ -95,19 +208,36 @@ pub struct ContextRetriever<'a> {
     max_tokens: usize,
     min_score: f64,
     metric: DistanceMetric,
+    filter: Option<MetadataFilter>,
+    reranker: Option<Box<dyn Reranker>>,
+    rerank_candidates: usize,
+    mmr_lambda: Option<f64>,
+    fusion: FusionStrategy,
+    tokens: TokenCounter,
 }
 
 impl<'a> ContextRetriever<'a> {
     /// Create a new context retriever
     pub fn new(db: &'a Database) -> Self {
+        Self::with_options(db, ContextOptions::default())
+    }
+
+    /// Create a context retriever from options
+    pub fn with_options(db: &'a Database, options: ContextOptions) -> Self {
         Self {
             db,
-            node_type: "chunk".to_string(),
-            embedding_field: "embedding".to_string(),
-            content_field: "content".to_string(),
-            max_tokens: 4096,
-            min_score: 0.0,
-            metric: DistanceMetric::Cosine,
+            node_type: options.node_type,
+            embedding_field: options.embedding_field,
+            content_field: options.content_field,
+            max_tokens: options.max_tokens,
+            min_score: options.min_score,
+            metric: options.metric,
+            filter: options.filter,
+            reranker: None,
+            rerank_candidates: DEFAULT_RERANK_CANDIDATES,
+            mmr_lambda: options.mmr_lambda.map(|lambda| lambda.clamp(0.0, 1.0)),
+            fusion: FusionStrategy::default(),
+            tokens: TokenCounter::from_env(),
         }
     }
 
@@ -147,40 +277,128 @@ impl<'a> ContextRetriever<'a> {
         self
     }
 
+    /// Only consider chunks matching a metadata filter
+    pub fn filter(mut self, filter: MetadataFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Rerank the top candidates before assembling context
+    ///
+    /// Chunks then come in reranked order and their `score` is the
+    /// reranker's; `distance` stays the vector distance.
+    pub fn reranker(mut self, reranker: impl Reranker + 'static) -> Self {
+        self.reranker = Some(Box::new(reranker));
+        self
+    }
+
+    /// Set how many top candidates the reranker and MMR see
+    pub fn rerank_candidates(mut self, n: usize) -> Self {
+        self.rerank_candidates = n.max(1);
+        self
+    }
+
+    /// Diversify chunks by maximal marginal relevance
+    ///
+    /// Each next chunk is the candidate maximizing `lambda * relevance -
+    /// (1 - lambda) * similarity`, where similarity is the highest cosine
+    /// similarity of its embedding to a chunk already picked. A lambda of
+    /// 1.0 ranks by relevance alone; lower values skip near-duplicates of
+    /// chunks already returned. 0.5 to 0.7 is typical.
+    pub fn mmr(mut self, lambda: f64) -> Self {
+        self.mmr_lambda = Some(lambda.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Set how `retrieve_multi` combines the results of its queries
+    pub fn fusion(mut self, fusion: FusionStrategy) -> Self {
+        self.fusion = fusion;
+        self
+    }
+
+    /// Count tokens against `max_tokens` with a specific counter; by
+    /// default, the tokenizer configured by `ARESADB_TOKENIZER`
+    pub fn token_counter(mut self, tokens: TokenCounter) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// Top `k` chunks by similarity, among those the filter accepts
+    async fn search(&self, query_vector: &[f32], k: usize) -> Result<Vec<SimilarityResult>> {
+        match &self.filter {
+            Some(filter) if !filter.is_empty() => self.db.similarity_search_where(
+                query_vector,
+                &self.node_type,
+                &self.embedding_field,
+                k,
+                self.metric,
+                |node| filter.matches(node),
+            ).await,
+            _ => self.db.similarity_search(
+                query_vector,
+                &self.node_type,
+                &self.embedding_field,
+                k,
+                self.metric,
+            ).await,
+        }
+    }
+
+    /// Number of search results to consider per query
+    fn candidate_count(&self) -> usize {
+        // Get more results than needed, then filter by token limit
+        if self.reranker.is_some() || self.mmr_lambda.is_some() {
+            self.rerank_candidates
+        } else {
+            (self.max_tokens / 100).clamp(10, 100) // Rough estimate: 100 chars per result
+        }
+    }
+
     /// Retrieve context for a query vector
     pub async fn retrieve(&self, query_vector: &[f32], query_text: &str) -> Result<RetrievedContext> {
-        // Get more results than needed, then filter by token limit
-        let k = self.max_tokens / 100; // Rough estimate: 100 chars per result
-        let k = k.max(10).min(100);
+        let mut results = self.search(query_vector, self.candidate_count()).await?;
+        // Skip low-score results
+        results.retain(|result| result.score >= self.min_score);
+        self.assemble(results, query_text).await
+    }
 
-        let results = self.db.similarity_search(
-            query_vector,
-            &self.node_type,
-            &self.embedding_field,
-            k,
-            self.metric.clone(),
-        ).await?;
+    /// Retrieve context for several query vectors at once
+    ///
+    /// Each vector (a paraphrase of the question, or one of its
+    /// sub-questions) is searched on its own, results below `min_score`
+    /// dropped, and the union of the results ranked by the configured
+    /// fusion. Chunk scores are then fused scores: with reciprocal rank
+    /// fusion, the mean of `1 / (60 + rank)` over the queries; with
+    /// weighted scores, the mean of each query's min-max normalized
+    /// similarity. A single vector is the same as `retrieve`.
+    pub async fn retrieve_multi(&self, query_vectors: &[Vec<f32>], query_text: &str) -> Result<RetrievedContext> {
+        match query_vectors {
+            [] => anyhow::bail!("No query vectors"),
+            [query_vector] => return self.retrieve(query_vector, query_text).await,
+            _ => {}
+        }
 
-        let mut chunks = Vec::new();
-        let mut total_tokens = 0;
+        let mut lists = Vec::with_capacity(query_vectors.len());
+        for query_vector in query_vectors {
+            let mut results = self.search(query_vector, self.candidate_count()).await?;
+            results.retain(|result| result.score >= self.min_score);
+            lists.push(results);
+        }
+        self.assemble(fuse_results(&lists, self.fusion), query_text).await
+    }
 
+    /// Turn ranked search results into context: rerank, diversify, then
+    /// take chunks up to the token budget
+    async fn assemble(&self, results: Vec<SimilarityResult>, query_text: &str) -> Result<RetrievedContext> {
+        let mut candidates = Vec::new();
         for result in results {
-            // Skip low-score results
-            if result.score < self.min_score {
-                continue;
-            }
-
             // Get the node to extract content
             if let Some(node) = self.db.get_node(&result.node_id.to_string()).await? {
                 let content = self.extract_content(&node);
-                let tokens = estimate_tokens(&content);
-
-                // Check token limit
-                if total_tokens + tokens > self.max_tokens {
-                    break;
-                }
-
-                let chunk = ContextChunk {
+                let embedding = node.properties.get(&self.embedding_field)
+                    .and_then(|v| v.as_vector())
+                    .map(|v| v.to_vec());
+                candidates.push((ContextChunk {
                     node_id: result.node_id.to_string(),
                     content,
                     score: result.score,
@@ -189,12 +407,43 @@ impl<'a> ContextRetriever<'a> {
                         .and_then(|v| v.as_str().map(|s| s.to_string())),
                     chunk_index: node.properties.get("chunk_index")
                         .and_then(|v| v.as_int().map(|i| i as usize)),
-                    metadata: node.properties.get("metadata").map(|v| v.to_json()),
-                };
+                    start_offset: node.properties.get("start_offset")
+                        .and_then(|v| v.as_int().map(|i| i as usize)),
+                    end_offset: node.properties.get("end_offset")
+                        .and_then(|v| v.as_int().map(|i| i as usize)),
+                    metadata: node.properties.get("metadata").cloned(),
+                }, embedding));
+            }
+        }
+
+        if let Some(reranker) = &self.reranker {
+            let documents: Vec<&str> = candidates.iter().map(|(c, _)| c.content.as_str()).collect();
+            let scores = reranker.rerank(query_text, &documents).await?;
+            if scores.len() != candidates.len() {
+                anyhow::bail!("Reranker {} returned {} scores for {} chunks",
+                    reranker.name(), scores.len(), candidates.len());
+            }
+            for ((chunk, _), score) in candidates.iter_mut().zip(scores) {
+                chunk.score = score;
+            }
+            candidates.sort_by(|(a, _), (b, _)| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        }
 
-                total_tokens += tokens;
-                chunks.push(chunk);
+        let candidates = match self.mmr_lambda {
+            Some(lambda) => mmr_order(candidates, lambda),
+            None => candidates.into_iter().map(|(chunk, _)| chunk).collect(),
+        };
+
+        let mut chunks = Vec::new();
+        let mut total_tokens = 0;
+        for chunk in candidates {
+            // Check token limit
+            let tokens = self.tokens.count(&chunk.content);
+            if total_tokens + tokens > self.max_tokens {
+                break;
             }
+            total_tokens += tokens;
+            chunks.push(chunk);
         }
 
         Ok(RetrievedContext {
@@ -222,13 +471,7 @@ impl<'a> ContextRetriever<'a> {
         // Get more results initially
         let initial_k = (self.max_tokens / 50).max(20).min(200);
 
-        let results = self.db.similarity_search(
-            query_vector,
-            &self.node_type,
-            &self.embedding_field,
-            initial_k,
-            self.metric.clone(),
-        ).await?;
+        let results = self.search(query_vector, initial_k).await?;
 
         // Fetch content and compute rerank scores
         let mut scored_chunks: Vec<(f64, ContextChunk)> = Vec::new();
@@ -256,7 +499,11 @@ impl<'a> ContextRetriever<'a> {
                         .and_then(|v| v.as_str().map(|s| s.to_string())),
                     chunk_index: node.properties.get("chunk_index")
                         .and_then(|v| v.as_int().map(|i| i as usize)),
-                    metadata: node.properties.get("metadata").map(|v| v.to_json()),
+                    start_offset: node.properties.get("start_offset")
+                        .and_then(|v| v.as_int().map(|i| i as usize)),
+                    end_offset: node.properties.get("end_offset")
+                        .and_then(|v| v.as_int().map(|i| i as usize)),
+                    metadata: node.properties.get("metadata").cloned(),
                 };
 
                 scored_chunks.push((combined_score, chunk));
@@ -271,7 +518,7 @@ impl<'a> ContextRetriever<'a> {
         let mut total_tokens = 0;
 
         for (_, chunk) in scored_chunks {
-            let tokens = estimate_tokens(&chunk.content);
+            let tokens = self.tokens.count(&chunk.content);
             if total_tokens + tokens > self.max_tokens {
                 break;
             }
@@ -287,10 +534,111 @@ impl<'a> ContextRetriever<'a> {
     }
 }
 
-/// Estimate token count from text
-fn estimate_tokens(text: &str) -> usize {
-    // Rough approximation: ~4 characters per token for English
-    text.chars().count() / 4
+/// Shortest overlap recognized between chunks whose offsets are unknown
+const MIN_TEXT_OVERLAP: usize = 16;
+
+/// Whether a chunk directly follows a block of the same document
+///
+/// `block` carries the chunk index and end offset of its last chunk.
+fn contiguous(block: &ContextChunk, next: &ContextChunk) -> bool {
+    match (block.end_offset, next.start_offset) {
+        (Some(end), Some(start)) => start <= end,
+        _ => matches!((block.chunk_index, next.chunk_index), (Some(last), Some(index)) if index == last + 1),
+    }
+}
+
+/// Bytes at the start of `next` that repeat the end of `block`
+///
+/// Offsets give the overlap exactly when the text agrees with them;
+/// otherwise the longest suffix of the block that starts `next` is taken,
+/// if it is long enough not to be a coincidence.
+fn overlap_len(block: &ContextChunk, next: &ContextChunk) -> usize {
+    let repeats = |len: usize| next.content.is_char_boundary(len) && block.content.ends_with(&next.content[..len]);
+    if let (Some(end), Some(start)) = (block.end_offset, next.start_offset) {
+        let chars = end.saturating_sub(start);
+        let len = next.content.char_indices().nth(chars).map_or(next.content.len(), |(i, _)| i);
+        if chars > 0 && repeats(len) {
+            return len;
+        }
+    }
+    let longest = block.content.len().min(next.content.len());
+    (MIN_TEXT_OVERLAP..=longest).rev().find(|len| repeats(*len)).unwrap_or(0)
+}
+
+/// RRF k parameter for fusing multi-query results
+const MULTI_QUERY_RRF_K: f64 = 60.0;
+
+/// Union several ranked result lists, best fused score first
+///
+/// Each result keeps its smallest distance. Ties keep first-seen order.
+fn fuse_results(lists: &[Vec<SimilarityResult>], fusion: FusionStrategy) -> Vec<SimilarityResult> {
+    let mut fused: Vec<SimilarityResult> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for list in lists {
+        let (min, max) = list.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), r| {
+            (min.min(r.score), max.max(r.score))
+        });
+        for (rank, result) in list.iter().enumerate() {
+            let contribution = match fusion {
+                FusionStrategy::ReciprocalRank => 1.0 / (MULTI_QUERY_RRF_K + rank as f64 + 1.0),
+                FusionStrategy::WeightedScore if max > min => (result.score - min) / (max - min),
+                FusionStrategy::WeightedScore => 1.0,
+            } / lists.len() as f64;
+
+            let key = result.node_id.to_string();
+            match positions.get(&key) {
+                Some(&i) => {
+                    fused[i].score += contribution;
+                    fused[i].distance = fused[i].distance.min(result.distance);
+                }
+                None => {
+                    positions.insert(key, fused.len());
+                    fused.push(SimilarityResult { score: contribution, ..result.clone() });
+                }
+            }
+        }
+    }
+    fused.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    fused
+}
+
+/// Order candidates by maximal marginal relevance
+///
+/// Relevance is the score relative to the best candidate's, so reranker
+/// scores of any range weigh evenly against similarity.
+fn mmr_order(candidates: Vec<(ContextChunk, Option<Vec<f32>>)>, lambda: f64) -> Vec<ContextChunk> {
+    let max = candidates.iter().map(|(c, _)| c.score).fold(f64::NEG_INFINITY, f64::max);
+    let relevance = |score: f64| if max > 0.0 { score / max } else { score };
+
+    let mut remaining: Vec<(ContextChunk, Option<Vec<f32>>, f64)> = candidates
+        .into_iter()
+        .map(|(chunk, embedding)| (chunk, embedding, f64::NEG_INFINITY))
+        .collect();
+    let mut ordered = Vec::with_capacity(remaining.len());
+
+    while !remaining.is_empty() {
+        let mmr = |(chunk, _, redundancy): &(ContextChunk, Option<Vec<f32>>, f64)| {
+            let redundancy = if redundancy.is_finite() { *redundancy } else { 0.0 };
+            lambda * relevance(chunk.score) - (1.0 - lambda) * redundancy
+        };
+        let best = (0..remaining.len())
+            .max_by(|a, b| mmr(&remaining[*a]).partial_cmp(&mmr(&remaining[*b])).unwrap_or(std::cmp::Ordering::Equal))
+            .expect("remaining is not empty");
+        // Ties keep the earlier, higher-scored candidate
+        let best = (0..=best).find(|i| mmr(&remaining[*i]) == mmr(&remaining[best])).unwrap_or(best);
+        let (chunk, embedding, _) = remaining.remove(best);
+
+        // Track each candidate's highest similarity to the chunks picked
+        if let Some(picked) = &embedding {
+            for (_, other, redundancy) in &mut remaining {
+                if let Some(similarity) = other.as_deref().and_then(|o| Value::cosine_similarity(picked, o)) {
+                    *redundancy = redundancy.max(similarity);
+                }
+            }
+        }
+        ordered.push(chunk);
+    }
+    ordered
 }
 
 /// Simple keyword-based reranker
@@ -370,6 +718,8 @@ mod tests {
                     distance: 0.05,
                     document_id: Some("doc1".to_string()),
                     chunk_index: Some(0),
+                    start_offset: None,
+                    end_offset: None,
                     metadata: None,
                 },
                 ContextChunk {
@@ -379,6 +729,8 @@ mod tests {
                     distance: 0.15,
                     document_id: Some("doc2".to_string()),
                     chunk_index: Some(1),
+                    start_offset: None,
+                    end_offset: None,
                     metadata: None,
                 },
             ],
@@ -392,10 +744,171 @@ mod tests {
         assert!(formatted.contains("First chunk content"));
     }
 
+    #[test]
+    fn test_stitch_adjacent_chunks() {
+        let chunk = |id: &str, doc: &str, index: usize, offsets: Option<(usize, usize)>, content: &str, score: f64| ContextChunk {
+            node_id: id.to_string(),
+            content: content.to_string(),
+            score,
+            distance: 1.0 - score,
+            document_id: Some(doc.to_string()),
+            chunk_index: Some(index),
+            start_offset: offsets.map(|(start, _)| start),
+            end_offset: offsets.map(|(_, end)| end),
+            metadata: None,
+        };
+        let context = RetrievedContext {
+            chunks: vec![
+                chunk("b", "guide", 1, Some((12, 32)), "Over here. Then more", 0.9),
+                chunk("x", "faq", 4, None, "Unrelated answer", 0.8),
+                chunk("a", "guide", 0, Some((0, 22)), "Start here. Over here.", 0.7),
+                chunk("y", "faq", 5, None, "Next answer", 0.6),
+                chunk("z", "faq", 7, None, "Far answer", 0.5),
+            ],
+            estimated_tokens: 20,
+            query: "q".to_string(),
+        };
+
+        let stitched = context.stitched();
+        let blocks: Vec<(&str, &str)> = stitched.chunks.iter()
+            .map(|c| (c.node_id.as_str(), c.content.as_str()))
+            .collect();
+        assert_eq!(blocks, [
+            ("b", "Start here. Over here. Then more"),
+            ("x", "Unrelated answer\nNext answer"),
+            ("z", "Far answer"),
+        ]);
+        assert_eq!(stitched.chunks[0].score, 0.9);
+        assert_eq!((stitched.chunks[0].start_offset, stitched.chunks[0].end_offset), (Some(0), Some(32)));
+
+        let formatted = context.format_for_llm();
+        assert!(formatted.contains("[Source 3]") && !formatted.contains("[Source 4]"));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_filter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (document_id, tags, embedding) in [
+            ("guide", vec!["rust"], vec![1.0, 0.0]),
+            ("faq", vec!["rust", "ops"], vec![0.9, 0.1]),
+            ("faq", vec!["ops"], vec![0.0, 1.0]),
+        ] {
+            let properties = serde_json::json!({
+                "content": format!("{} chunk", document_id),
+                "document_id": document_id,
+                "tags": tags,
+            });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let faq = ContextRetriever::new(&db)
+            .filter(MetadataFilter::new().document("faq"))
+            .retrieve(&[1.0, 0.0], "q")
+            .await
+            .unwrap();
+        assert_eq!(faq.chunks.len(), 2);
+        assert!(faq.chunks.iter().all(|c| c.document_id.as_deref() == Some("faq")));
+
+        let options = ContextOptions {
+            filter: Some(MetadataFilter::new().contains("tags", "rust")),
+            ..Default::default()
+        };
+        let rust = ContextRetriever::with_options(&db, options)
+            .retrieve_with_reranking(&[0.0, 1.0], "q", keyword_reranker)
+            .await
+            .unwrap();
+        assert_eq!(rust.chunks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_reranker() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (content, embedding) in [
+            ("closest vector, off topic", vec![1.0, 0.0]),
+            ("mentions the raft consensus protocol", vec![0.5, 0.5]),
+        ] {
+            let properties = serde_json::json!({ "content": content });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let plain = ContextRetriever::new(&db).retrieve(&[1.0, 0.0], "raft consensus").await.unwrap();
+        assert_eq!(plain.chunks[0].content, "closest vector, off topic");
+
+        let reranked = ContextRetriever::new(&db)
+            .reranker(crate::rag::KeywordReranker)
+            .retrieve(&[1.0, 0.0], "raft consensus")
+            .await
+            .unwrap();
+        assert_eq!(reranked.chunks[0].content, "mentions the raft consensus protocol");
+        assert_eq!(reranked.chunks[0].score, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_mmr() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (content, embedding) in [
+            ("best match", vec![1.0, 0.12]),
+            ("near duplicate", vec![1.0, 0.1]),
+            ("different angle", vec![0.6, 0.8]),
+        ] {
+            let properties = serde_json::json!({ "content": content });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let order = |context: RetrievedContext| -> Vec<String> {
+            context.chunks.into_iter().map(|c| c.content).collect()
+        };
+        let plain = ContextRetriever::new(&db).retrieve(&[1.0, 0.2], "q").await.unwrap();
+        assert_eq!(order(plain), ["best match", "near duplicate", "different angle"]);
+
+        let diverse = ContextRetriever::new(&db).mmr(0.5).retrieve(&[1.0, 0.2], "q").await.unwrap();
+        assert_eq!(order(diverse), ["best match", "different angle", "near duplicate"]);
+
+        let relevance_only = ContextRetriever::new(&db).mmr(1.0).retrieve(&[1.0, 0.2], "q").await.unwrap();
+        assert_eq!(order(relevance_only), ["best match", "near duplicate", "different angle"]);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_multi_query() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (content, embedding) in [
+            ("first topic", vec![1.0, 0.0]),
+            ("second topic", vec![0.0, 1.0]),
+            ("both topics", vec![0.7, 0.7]),
+        ] {
+            let properties = serde_json::json!({ "content": content });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let order = |context: RetrievedContext| -> Vec<String> {
+            context.chunks.into_iter().map(|c| c.content).collect()
+        };
+        let retriever = ContextRetriever::new(&db).min_score(0.5);
+        let single = retriever.retrieve_multi(&[vec![1.0, 0.0]], "q").await.unwrap();
+        assert_eq!(order(single), ["first topic", "both topics"]);
+
+        // Found by both queries, so ranked first
+        let queries = [vec![1.0, 0.0], vec![0.0, 1.0]];
+        let fused = retriever.retrieve_multi(&queries, "q").await.unwrap();
+        assert!((fused.chunks[0].score - 1.0 / 62.0).abs() < 1e-9);
+        assert_eq!(order(fused), ["both topics", "first topic", "second topic"]);
+
+        let weighted = ContextRetriever::new(&db).min_score(0.5)
+            .fusion(FusionStrategy::WeightedScore)
+            .retrieve_multi(&queries, "q").await.unwrap();
+        assert_eq!(order(weighted), ["first topic", "second topic", "both topics"]);
+
+        assert!(retriever.retrieve_multi(&[], "q").await.is_err());
+    }
+
     #[test]
     fn test_estimate_tokens() {
         let text = "This is a test with about forty characters.";
-        let tokens = estimate_tokens(text);
+        let tokens = TokenCounter::Estimate.count(text);
         assert!(tokens > 5 && tokens < 20);
     }
 }
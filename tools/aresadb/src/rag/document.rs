@@ -0,0 +1,433 @@
+//! Document lifecycle
+//!
+//! Chunk nodes record the `document_id` they were cut from. A document is
+//! deleted by removing every chunk node that names it, and re-ingested by
+//! swapping its old chunks for new ones in a single transaction, so an
+//! updated document never leaves stale chunks behind and retrieval never
+//! sees a half-replaced one.
+//!
+//! Each chunk also records a `content_hash`. Re-ingesting compares hashes
+//! with the stored chunks and embeds only new or changed text, so
+//! re-ingesting a mostly unchanged tree costs little more than reading it.
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::progress::{Progress, ProgressUnit};
+use crate::storage::{Database, Node, Value};
+use super::checkpoint::EmbeddingCheckpoint;
+use super::chunker::DocumentChunk;
+use super::embeddings::EmbeddingManager;
+use super::filter::MetadataFilter;
+
+/// Hex SHA-256 of a chunk's text
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// What re-ingesting a document changes, from [`DocumentStore::plan`]
+#[derive(Debug, Clone)]
+pub struct IngestPlan {
+    /// Stored chunks identical to new ones, left as they are
+    pub unchanged: usize,
+    /// Stored chunks whose text is unchanged but whose position or
+    /// properties moved, with rebuilt nodes reusing their embeddings
+    pub moved: Vec<(Node, Node)>,
+    /// New or changed chunks, which need embedding
+    pub pending: Vec<DocumentChunk>,
+    /// Stored chunks no longer in the document
+    pub stale: Vec<Node>,
+}
+
+/// Chunk counts of an applied [`IngestPlan`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestStats {
+    /// Chunks kept without re-embedding
+    pub skipped: usize,
+    /// Chunks embedded and written
+    pub updated: usize,
+    /// Stale chunks deleted
+    pub removed: usize,
+    /// Chunks whose embeddings came from a checkpoint
+    pub resumed: usize,
+}
+
+/// How [`DocumentStore::ingest`] embeds and writes a document
+#[derive(Debug, Clone)]
+pub struct IngestOptions {
+    /// Properties every chunk node gets
+    pub base: serde_json::Value,
+    /// Chunks embedded per provider request
+    pub batch_size: usize,
+    /// Embedding requests in flight at once
+    pub concurrency: usize,
+    /// Replace the document's stored chunks rather than adding to them
+    pub replace: bool,
+}
+
+impl Default for IngestOptions {
+    fn default() -> Self {
+        Self {
+            base: serde_json::json!({}),
+            batch_size: 32,
+            concurrency: 4,
+            replace: false,
+        }
+    }
+}
+
+/// Chunk nodes of ingested documents, grouped by `document_id`
+pub struct DocumentStore<'a> {
+    db: &'a Database,
+    node_type: String,
+    embedding_field: String,
+}
+
+impl<'a> DocumentStore<'a> {
+    /// Create a store over `chunk` nodes with embeddings in `embedding`
+    pub fn new(db: &'a Database) -> Self {
+        Self {
+            db,
+            node_type: "chunk".to_string(),
+            embedding_field: "embedding".to_string(),
+        }
+    }
+
+    /// Set the node type holding the chunks
+    pub fn node_type(mut self, node_type: &str) -> Self {
+        self.node_type = node_type.to_string();
+        self
+    }
+
+    /// Set the field chunk embeddings are stored in
+    pub fn embedding_field(mut self, field: &str) -> Self {
+        self.embedding_field = field.to_string();
+        self
+    }
+
+    /// Build the node for a chunk
+    ///
+    /// Properties are `base` plus the chunk's content, `content_hash`, `document_id`,
+    /// `chunk_index`, `total_chunks`, offsets, `metadata` if any, and the
+    /// embedding.
+    pub fn chunk_node(
+        &self,
+        chunk: &DocumentChunk,
+        base: &serde_json::Value,
+        embedding: Vec<f32>,
+    ) -> Result<Node> {
+        let mut props = base.clone();
+        if let Some(obj) = props.as_object_mut() {
+            obj.insert("content".to_string(), serde_json::json!(chunk.content));
+            obj.insert("content_hash".to_string(), serde_json::json!(content_hash(&chunk.content)));
+            obj.insert("document_id".to_string(), serde_json::json!(chunk.document_id));
+            obj.insert("chunk_index".to_string(), serde_json::json!(chunk.chunk_index));
+            obj.insert("total_chunks".to_string(), serde_json::json!(chunk.total_chunks));
+            obj.insert("start_offset".to_string(), serde_json::json!(chunk.start_offset));
+            obj.insert("end_offset".to_string(), serde_json::json!(chunk.end_offset));
+            if let Some(metadata) = &chunk.metadata {
+                obj.insert("metadata".to_string(), metadata.clone());
+            }
+        }
+
+        let mut props = Value::from_json(props)?;
+        if let Value::Object(ref mut map) = props {
+            map.insert(self.embedding_field.clone(), Value::Vector(embedding));
+        }
+        Ok(Node::new(&self.node_type, props))
+    }
+
+    /// Chunk nodes of a document, in chunk order
+    pub async fn chunks(&self, document_id: &str) -> Result<Vec<Node>> {
+        let filter = MetadataFilter::new().document(document_id);
+        let mut nodes = self.db.get_all_by_type(&self.node_type, None).await?;
+        nodes.retain(|node| filter.matches(node));
+        nodes.sort_by_key(|node| node.properties.get("chunk_index").and_then(|v| v.as_int()));
+        Ok(nodes)
+    }
+
+    /// Documents and how many chunks each has
+    pub async fn documents(&self) -> Result<BTreeMap<String, usize>> {
+        let mut documents = BTreeMap::new();
+        for node in self.db.get_all_by_type(&self.node_type, None).await? {
+            if let Some(id) = node.properties.get("document_id").and_then(|v| v.as_str()) {
+                *documents.entry(id.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok(documents)
+    }
+
+    /// Add chunk nodes, all or none
+    pub async fn insert(&self, chunks: Vec<Node>) -> Result<()> {
+        self.db.replace_nodes(Vec::new(), chunks).await
+    }
+
+    /// Delete every chunk of a document, returning how many there were
+    pub async fn delete(&self, document_id: &str) -> Result<usize> {
+        let old = self.chunks(document_id).await?;
+        let removed = old.len();
+        self.db.replace_nodes(old, Vec::new()).await?;
+        Ok(removed)
+    }
+
+    /// Replace a document's chunks atomically, returning how many were
+    /// removed
+    pub async fn replace(&self, document_id: &str, chunks: Vec<Node>) -> Result<usize> {
+        if let Some(node) = chunks.iter().find(|node| {
+            node.properties.get("document_id").and_then(|v| v.as_str()) != Some(document_id)
+        }) {
+            anyhow::bail!("Chunk {} does not belong to document {}", node.id, document_id);
+        }
+
+        let old = self.chunks(document_id).await?;
+        let removed = old.len();
+        self.db.replace_nodes(old, chunks).await?;
+        Ok(removed)
+    }
+
+    /// Match a document's new chunks against its stored ones by content hash
+    ///
+    /// Each stored chunk can stand in for one new chunk with the same text.
+    /// Chunks stored before hashes were recorded are hashed from their
+    /// content.
+    pub async fn plan(
+        &self,
+        document_id: &str,
+        chunks: Vec<DocumentChunk>,
+        base: &serde_json::Value,
+    ) -> Result<IngestPlan> {
+        let mut stored = self.chunks(document_id).await?;
+        let mut plan = IngestPlan { unchanged: 0, moved: Vec::new(), pending: Vec::new(), stale: Vec::new() };
+
+        for chunk in chunks {
+            let hash = content_hash(&chunk.content);
+            let found = stored.iter().position(|node| {
+                match node.properties.get("content_hash").and_then(|v| v.as_str()) {
+                    Some(stored_hash) => stored_hash == hash,
+                    None => node.properties.get("content").and_then(|v| v.as_str()).map(content_hash) == Some(hash.clone()),
+                }
+            });
+            let embedding = found.and_then(|i| {
+                stored[i].properties.get(&self.embedding_field).and_then(|v| v.as_vector()).map(|v| v.to_vec())
+            });
+            match (found, embedding) {
+                (Some(i), Some(embedding)) => {
+                    let old = stored.remove(i);
+                    let rebuilt = self.chunk_node(&chunk, base, embedding)?;
+                    if rebuilt.properties == old.properties {
+                        plan.unchanged += 1;
+                    } else {
+                        plan.moved.push((old, rebuilt));
+                    }
+                }
+                _ => plan.pending.push(chunk),
+            }
+        }
+        plan.stale = stored;
+        Ok(plan)
+    }
+
+    /// Write a plan with the nodes embedded from its pending chunks, all or
+    /// none
+    ///
+    /// With `replace`, moved chunks are rewritten and stale ones deleted, so
+    /// the document ends up exactly as re-chunked. Without it, stored chunks
+    /// are left alone and only the new ones are added.
+    pub async fn apply(&self, plan: IngestPlan, embedded: Vec<Node>, replace: bool) -> Result<IngestStats> {
+        let stats = IngestStats {
+            skipped: plan.unchanged + plan.moved.len(),
+            updated: embedded.len(),
+            removed: if replace { plan.stale.len() } else { 0 },
+            resumed: 0,
+        };
+
+        let (mut old, mut new) = (Vec::new(), embedded);
+        if replace {
+            for (stored, rebuilt) in plan.moved {
+                old.push(stored);
+                new.push(rebuilt);
+            }
+            old.extend(plan.stale);
+        }
+        if !old.is_empty() || !new.is_empty() {
+            self.db.replace_nodes(old, new).await?;
+        }
+        Ok(stats)
+    }
+
+    /// Plan, embed and apply a document's chunks
+    ///
+    /// Only new and changed chunks are embedded. With a checkpoint,
+    /// embeddings are saved as they arrive and reused on a rerun.
+    /// `on_progress` sees the chunks embedded so far; nothing is written
+    /// until every chunk is embedded.
+    pub async fn ingest<F>(
+        &self,
+        document_id: &str,
+        chunks: Vec<DocumentChunk>,
+        embedder: &EmbeddingManager,
+        options: &IngestOptions,
+        mut checkpoint: Option<&mut EmbeddingCheckpoint>,
+        mut on_progress: F,
+    ) -> Result<IngestStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let plan = self.plan(document_id, chunks, &options.base).await?;
+        let hashes: Vec<String> = plan.pending.iter().map(|chunk| content_hash(&chunk.content)).collect();
+        // Embeddings a previous run saved, before this run adds its own
+        let mut saved: Vec<Option<Vec<f32>>> = hashes.iter()
+            .map(|hash| checkpoint.as_ref().and_then(|c| c.get(hash)).cloned())
+            .collect();
+        let missing: Vec<&str> = plan.pending.iter()
+            .zip(&saved)
+            .filter(|(_, saved)| saved.is_none())
+            .map(|(chunk, _)| chunk.content.as_str())
+            .collect();
+
+        let total = missing.len() as u64;
+        let mut done = 0;
+        on_progress(Progress::new("ingest", ProgressUnit::Chunks, done, Some(total)));
+        let embeddings = embedder.embed_documents_batched(&missing, options.batch_size, options.concurrency, |batch, embeddings| {
+            if let Some(checkpoint) = checkpoint.as_deref_mut() {
+                for (text, embedding) in batch.iter().zip(embeddings) {
+                    checkpoint.record(&content_hash(text), embedding)?;
+                }
+            }
+            done += batch.len() as u64;
+            on_progress(Progress::new("ingest", ProgressUnit::Chunks, done, Some(total)));
+            Ok(())
+        }).await?;
+
+        let mut embeddings = embeddings.into_iter();
+        let resumed = saved.iter().filter(|e| e.is_some()).count();
+        let mut nodes = Vec::with_capacity(plan.pending.len());
+        for (chunk, saved) in plan.pending.iter().zip(saved.iter_mut()) {
+            let embedding = match saved.take() {
+                Some(embedding) => embedding,
+                None => embeddings.next().ok_or_else(|| anyhow::anyhow!("No embedding returned"))?,
+            };
+            nodes.push(self.chunk_node(chunk, &options.base, embedding)?);
+        }
+
+        let stats = self.apply(plan, nodes, options.replace).await?;
+        Ok(IngestStats { resumed, ..stats })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::{ChunkStrategy, Chunker};
+
+    #[tokio::test]
+    async fn test_document_lifecycle() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let store = DocumentStore::new(&db);
+        let chunker = Chunker::new(ChunkStrategy::Paragraph { max_size: 10 });
+        let nodes = |doc: &str, text: &str| {
+            chunker.chunk(doc, text).iter()
+                .map(|chunk| store.chunk_node(chunk, &serde_json::json!({"tag": "x"}), vec![1.0, 0.0]))
+                .collect::<Result<Vec<_>>>()
+                .unwrap()
+        };
+
+        store.insert(nodes("guide", "first part\n\nsecond part\n\nthird part")).await.unwrap();
+        store.insert(nodes("faq", "question")).await.unwrap();
+        let documents = store.documents().await.unwrap();
+        assert_eq!(documents.get("guide"), Some(&3));
+        assert_eq!(documents.get("faq"), Some(&1));
+
+        let chunks = store.chunks("guide").await.unwrap();
+        assert_eq!(chunks[0].properties.get("content").and_then(|v| v.as_str()), Some("first part"));
+        assert_eq!(chunks[0].properties.get("tag").and_then(|v| v.as_str()), Some("x"));
+        assert!(chunks[0].properties.get("embedding").and_then(|v| v.as_vector()).is_some());
+
+        // Re-ingesting swaps the old chunks for the new ones
+        assert_eq!(store.replace("guide", nodes("guide", "updated")).await.unwrap(), 3);
+        let chunks = store.chunks("guide").await.unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].properties.get("content").and_then(|v| v.as_str()), Some("updated"));
+        assert!(store.replace("guide", nodes("faq", "wrong")).await.is_err());
+
+        assert_eq!(store.delete("guide").await.unwrap(), 1);
+        assert!(store.chunks("guide").await.unwrap().is_empty());
+        assert_eq!(db.get_all_by_type("chunk", None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_incremental_ingest() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let store = DocumentStore::new(&db);
+        let chunker = Chunker::new(ChunkStrategy::Paragraph { max_size: 5 });
+        let base = serde_json::json!({});
+        let ingest = |text: &'static str, replace: bool| {
+            let store = &store;
+            let chunker = &chunker;
+            let base = &base;
+            async move {
+                let plan = store.plan("guide", chunker.chunk("guide", text), base).await.unwrap();
+                let embedded = plan.pending.iter()
+                    .map(|chunk| store.chunk_node(chunk, base, vec![1.0, 0.0]).unwrap())
+                    .collect();
+                store.apply(plan, embedded, replace).await.unwrap()
+            }
+        };
+
+        let stats = ingest("alpha\n\nbravo\n\ngamma", true).await;
+        assert_eq!(stats, IngestStats { skipped: 0, updated: 3, removed: 0, resumed: 0 });
+        let ids: Vec<_> = store.chunks("guide").await.unwrap().into_iter().map(|n| n.id).collect();
+
+        // Nothing changed: nothing is embedded or written
+        let stats = ingest("alpha\n\nbravo\n\ngamma", true).await;
+        assert_eq!(stats, IngestStats { skipped: 3, updated: 0, removed: 0, resumed: 0 });
+        let chunks = store.chunks("guide").await.unwrap();
+        assert_eq!(chunks.iter().map(|n| n.id.clone()).collect::<Vec<_>>(), ids);
+        assert_eq!(
+            chunks[0].properties.get("content_hash").and_then(|v| v.as_str()),
+            Some(content_hash("alpha").as_str())
+        );
+
+        // A new first paragraph is embedded; the rest keep their embeddings
+        // but move down one place, and "gamma" is gone
+        let stats = ingest("intro\n\nalpha\n\nbravo", true).await;
+        assert_eq!(stats, IngestStats { skipped: 2, updated: 1, removed: 1, resumed: 0 });
+        let chunks = store.chunks("guide").await.unwrap();
+        let contents: Vec<_> = chunks.iter().filter_map(|n| n.properties.get("content").and_then(|v| v.as_str())).collect();
+        assert_eq!(contents, ["intro", "alpha", "bravo"]);
+
+        // Without replace, stored chunks stay and duplicates are not added
+        let stats = ingest("bravo\n\ndelta", false).await;
+        assert_eq!(stats, IngestStats { skipped: 1, updated: 1, removed: 0, resumed: 0 });
+        assert_eq!(store.chunks("guide").await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_progress_and_checkpoint() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let store = DocumentStore::new(&db);
+        let embedder = EmbeddingManager::local(8);
+        let chunker = Chunker::new(ChunkStrategy::Paragraph { max_size: 5 });
+        let options = IngestOptions { batch_size: 2, replace: true, ..IngestOptions::default() };
+
+        // An earlier run saved one embedding before dying
+        let mut checkpoint = EmbeddingCheckpoint::open(temp.path().join("ingest.checkpoint"), embedder.name()).unwrap();
+        let saved = embedder.embed_documents(&["alpha"]).await.unwrap().remove(0);
+        checkpoint.record(&content_hash("alpha"), &saved).unwrap();
+
+        let mut events = Vec::new();
+        let chunks = chunker.chunk("guide", "alpha\n\nbravo\n\ngamma");
+        let stats = store.ingest("guide", chunks, &embedder, &options, Some(&mut checkpoint), |p| events.push(p)).await.unwrap();
+        assert_eq!(stats, IngestStats { skipped: 0, updated: 3, removed: 0, resumed: 1 });
+        assert_eq!(events.iter().map(|p| p.done).collect::<Vec<_>>(), [0, 2]);
+        assert!(events.iter().all(|p| p.total == Some(2) && p.unit == ProgressUnit::Chunks));
+        assert_eq!(checkpoint.len(), 3);
+
+        let stored = store.chunks("guide").await.unwrap();
+        assert_eq!(stored[0].properties.get("embedding").and_then(|v| v.as_vector()), Some(saved.as_slice()));
+    }
+}
@@ -2,14 +2,28 @@
 //!
 //! Supports multiple embedding providers:
 //! - OpenAI (text-embedding-3-small, text-embedding-ada-002)
+//! - Ollama (any embedding model served by a local Ollama server)
+//! - Vertex AI (Google's text-embedding and Gemini embedding models)
+//! - Cohere (embed-v3) and Voyage, which embed queries and documents
+//!   differently
+//! - Local sentence-transformer models (with the `local-embeddings` feature)
 //! - Local hash-based embeddings (for testing/offline use)
 //! - Custom providers via trait implementation
+//!
+//! HTTP providers retry rate limits (429), server errors (5xx) and
+//! connection failures with exponential backoff; see [`RetryPolicy`].
 
 use anyhow::{Result, Context};
 use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::google_auth::GoogleCredentials;
 
 /// Embedding provider trait
 #[async_trait]
@@ -26,6 +40,19 @@ pub trait EmbeddingProvider: Send + Sync {
         Ok(results)
     }
 
+    /// Generate an embedding for a search query
+    ///
+    /// Models that embed queries and documents differently override this
+    /// and `embed_documents`; for the rest both are plain `embed`.
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(text).await
+    }
+
+    /// Generate embeddings for documents being stored for retrieval
+    async fn embed_documents(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_batch(texts).await
+    }
+
     /// Get the dimension of embeddings produced by this provider
     fn dimension(&self) -> usize;
 
@@ -33,6 +60,79 @@ pub trait EmbeddingProvider: Send + Sync {
     fn name(&self) -> &str;
 }
 
+/// How HTTP providers retry failed requests
+///
+/// Rate limits, server errors and connection failures are retried after
+/// `base_delay`, doubling each time up to `max_delay`, with jitter so
+/// parallel requests don't retry in lockstep. A `Retry-After` header from
+/// the provider takes precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Longest delay between attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 6,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Fail on the first error
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Self::default() }
+    }
+
+    /// Delay before retry number `attempt` (from 0)
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(self.max_delay);
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// Whether a response status is worth retrying
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Send a request built by `build`, retrying under `policy`
+///
+/// Returns the last response once it succeeds, fails for good, or retries
+/// run out, leaving the caller to report unsuccessful statuses.
+async fn send_with_retry<F>(policy: &RetryPolicy, build: F) -> reqwest::Result<reqwest::Response>
+where
+    F: Fn() -> reqwest::RequestBuilder,
+{
+    let mut attempt = 0;
+    loop {
+        let retry_after = match build().send().await {
+            Ok(response) if attempt < policy.max_retries && is_retryable(response.status()) => {
+                response.headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs)
+            }
+            Err(e) if attempt < policy.max_retries && (e.is_connect() || e.is_timeout()) => None,
+            result => return result,
+        };
+        tokio::time::sleep(policy.delay(attempt, retry_after)).await;
+        attempt += 1;
+    }
+}
+
 /// OpenAI embedding models
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpenAIModel {
@@ -80,6 +180,7 @@ pub struct OpenAIEmbeddings {
     api_key: String,
     model: OpenAIModel,
     client: reqwest::Client,
+    retry: RetryPolicy,
 }
 
 impl OpenAIEmbeddings {
@@ -89,6 +190,7 @@ impl OpenAIEmbeddings {
             api_key,
             model,
             client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
         }
     }
 
@@ -103,6 +205,12 @@ impl OpenAIEmbeddings {
     pub fn default_from_env() -> Result<Self> {
         Self::from_env(OpenAIModel::TextEmbedding3Small)
     }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
 }
 
 #[derive(Serialize)]
@@ -135,12 +243,11 @@ impl EmbeddingProvider for OpenAIEmbeddings {
             input: texts.to_vec(),
         };
 
-        let response = self.client
+        let response = send_with_retry(&self.retry, || self.client
             .post("https://api.openai.com/v1/embeddings")
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
+            .json(&request))
             .await
             .context("Failed to send request to OpenAI")?;
 
@@ -164,6 +271,517 @@ impl EmbeddingProvider for OpenAIEmbeddings {
     }
 }
 
+/// What a text is embedded for, for models that distinguish the two
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    /// A search query
+    Query,
+    /// A document stored for retrieval
+    Document,
+}
+
+/// Cohere embed-v3 provider
+///
+/// `embed` and `embed_batch` embed documents; use `embed_query` for
+/// search queries.
+pub struct CohereEmbeddings {
+    api_key: String,
+    model: String,
+    base_url: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl CohereEmbeddings {
+    /// English model
+    pub const ENGLISH: &'static str = "embed-english-v3.0";
+    /// Multilingual model
+    pub const MULTILINGUAL: &'static str = "embed-multilingual-v3.0";
+
+    /// Create a provider for a Cohere model
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            api_key,
+            model: model.to_string(),
+            base_url: "https://api.cohere.com".to_string(),
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Create from environment variable COHERE_API_KEY
+    pub fn from_env(model: &str) -> Result<Self> {
+        let api_key = std::env::var("COHERE_API_KEY")
+            .context("COHERE_API_KEY environment variable not set")?;
+        Ok(Self::new(api_key, model))
+    }
+
+    /// Send requests to another endpoint, such as a proxy
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Embed texts for a purpose
+    pub async fn embed_as(&self, texts: &[&str], input_type: InputType) -> Result<Vec<Vec<f32>>> {
+        let request = CohereRequest {
+            model: &self.model,
+            texts: texts.to_vec(),
+            input_type: match input_type {
+                InputType::Query => "search_query",
+                InputType::Document => "search_document",
+            },
+        };
+
+        let response = send_with_retry(&self.retry, || self.client
+            .post(format!("{}/v1/embed", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request))
+            .await
+            .context("Failed to send request to Cohere")?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Cohere API error: {}", error_text);
+        }
+
+        let result: CohereResponse = response.json().await
+            .context("Failed to parse Cohere response")?;
+        Ok(result.embeddings)
+    }
+}
+
+#[derive(Serialize)]
+struct CohereRequest<'a> {
+    model: &'a str,
+    texts: Vec<&'a str>,
+    input_type: &'a str,
+}
+
+#[derive(Deserialize)]
+struct CohereResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for CohereEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let results = self.embed_batch(&[text]).await?;
+        results.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_as(texts, InputType::Document).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_as(&[text], InputType::Query).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    fn dimension(&self) -> usize {
+        if self.model.contains("light") { 384 } else { 1024 }
+    }
+
+    fn name(&self) -> &str {
+        "cohere"
+    }
+}
+
+/// Voyage AI embedding provider
+///
+/// `embed` and `embed_batch` embed documents; use `embed_query` for
+/// search queries.
+pub struct VoyageEmbeddings {
+    api_key: String,
+    model: String,
+    base_url: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl VoyageEmbeddings {
+    /// General-purpose model
+    pub const VOYAGE_3: &'static str = "voyage-3";
+    /// Smaller, cheaper model
+    pub const VOYAGE_3_LITE: &'static str = "voyage-3-lite";
+
+    /// Create a provider for a Voyage model
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            api_key,
+            model: model.to_string(),
+            base_url: "https://api.voyageai.com".to_string(),
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Create from environment variable VOYAGE_API_KEY
+    pub fn from_env(model: &str) -> Result<Self> {
+        let api_key = std::env::var("VOYAGE_API_KEY")
+            .context("VOYAGE_API_KEY environment variable not set")?;
+        Ok(Self::new(api_key, model))
+    }
+
+    /// Send requests to another endpoint, such as a proxy
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Embed texts for a purpose
+    pub async fn embed_as(&self, texts: &[&str], input_type: InputType) -> Result<Vec<Vec<f32>>> {
+        let request = VoyageRequest {
+            model: &self.model,
+            input: texts.to_vec(),
+            input_type: match input_type {
+                InputType::Query => "query",
+                InputType::Document => "document",
+            },
+        };
+
+        let response = send_with_retry(&self.retry, || self.client
+            .post(format!("{}/v1/embeddings", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request))
+            .await
+            .context("Failed to send request to Voyage")?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Voyage API error: {}", error_text);
+        }
+
+        // Same shape as OpenAI's response
+        let result: OpenAIResponse = response.json().await
+            .context("Failed to parse Voyage response")?;
+        Ok(result.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct VoyageRequest<'a> {
+    model: &'a str,
+    input: Vec<&'a str>,
+    input_type: &'a str,
+}
+
+#[async_trait]
+impl EmbeddingProvider for VoyageEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let results = self.embed_batch(&[text]).await?;
+        results.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_as(texts, InputType::Document).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_as(&[text], InputType::Query).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    fn dimension(&self) -> usize {
+        if self.model.contains("lite") { 512 } else { 1024 }
+    }
+
+    fn name(&self) -> &str {
+        "voyage"
+    }
+}
+
+/// Vertex AI embedding provider
+///
+/// Authenticates with Google Cloud credentials found the same way as for
+/// GCS buckets. `embed` and `embed_batch` embed documents; use
+/// `embed_query` for search queries.
+pub struct VertexEmbeddings {
+    credentials: GoogleCredentials,
+    project: Option<String>,
+    location: String,
+    model: String,
+    base_url: Option<String>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl VertexEmbeddings {
+    /// Text embedding model
+    pub const TEXT_EMBEDDING: &'static str = "text-embedding-005";
+    /// Gemini embedding model
+    pub const GEMINI_EMBEDDING: &'static str = "gemini-embedding-001";
+    /// Region used unless `GOOGLE_CLOUD_LOCATION` names another
+    pub const DEFAULT_LOCATION: &'static str = "us-central1";
+
+    /// Create a provider for a model
+    ///
+    /// Without a project, the one the credentials belong to is used.
+    pub fn new(credentials: GoogleCredentials, project: Option<String>, location: &str, model: &str) -> Self {
+        Self {
+            credentials,
+            project,
+            location: location.to_string(),
+            model: model.to_string(),
+            base_url: None,
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Create from the environment: Google credentials, plus the optional
+    /// `GOOGLE_CLOUD_PROJECT` and `GOOGLE_CLOUD_LOCATION`
+    pub fn from_env(model: &str) -> Result<Self> {
+        let credentials = GoogleCredentials::from_env()?;
+        let project = std::env::var("GOOGLE_CLOUD_PROJECT").ok();
+        let location = std::env::var("GOOGLE_CLOUD_LOCATION")
+            .unwrap_or_else(|_| Self::DEFAULT_LOCATION.to_string());
+        Ok(Self::new(credentials, project, &location, model))
+    }
+
+    /// Send requests to another endpoint, such as a proxy
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Embed texts for a purpose
+    pub async fn embed_as(&self, texts: &[&str], input_type: InputType) -> Result<Vec<Vec<f32>>> {
+        let project = match &self.project {
+            Some(project) => project.clone(),
+            None => self.credentials.resolve_project_id().await?,
+        };
+        let base_url = self.base_url.clone()
+            .unwrap_or_else(|| format!("https://{}-aiplatform.googleapis.com", self.location));
+        let url = format!(
+            "{}/v1/projects/{}/locations/{}/publishers/google/models/{}:predict",
+            base_url, project, self.location, self.model,
+        );
+        let task_type = match input_type {
+            InputType::Query => "RETRIEVAL_QUERY",
+            InputType::Document => "RETRIEVAL_DOCUMENT",
+        };
+
+        // Gemini models take one text per request
+        let per_request = if self.model.starts_with("gemini") { 1 } else { 250 };
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(per_request) {
+            let request = VertexRequest {
+                instances: batch.iter().map(|content| VertexInstance { content, task_type }).collect(),
+            };
+
+            let token = self.credentials.token().await?;
+            let response = send_with_retry(&self.retry, || self.client
+                .post(&url)
+                .bearer_auth(&token)
+                .json(&request))
+                .await
+                .context("Failed to send request to Vertex AI")?;
+
+            if !response.status().is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Vertex AI error: {}", error_text);
+            }
+
+            let result: VertexResponse = response.json().await
+                .context("Failed to parse Vertex AI response")?;
+            embeddings.extend(result.predictions.into_iter().map(|p| p.embeddings.values));
+        }
+        Ok(embeddings)
+    }
+}
+
+#[derive(Serialize)]
+struct VertexRequest<'a> {
+    instances: Vec<VertexInstance<'a>>,
+}
+
+#[derive(Serialize)]
+struct VertexInstance<'a> {
+    content: &'a str,
+    task_type: &'a str,
+}
+
+#[derive(Deserialize)]
+struct VertexResponse {
+    predictions: Vec<VertexPrediction>,
+}
+
+#[derive(Deserialize)]
+struct VertexPrediction {
+    embeddings: VertexValues,
+}
+
+#[derive(Deserialize)]
+struct VertexValues {
+    values: Vec<f32>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for VertexEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let results = self.embed_batch(&[text]).await?;
+        results.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embed_as(texts, InputType::Document).await
+    }
+
+    async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_as(&[text], InputType::Query).await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    fn dimension(&self) -> usize {
+        if self.model.starts_with("gemini") { 3072 } else { 768 }
+    }
+
+    fn name(&self) -> &str {
+        "vertex"
+    }
+}
+
+/// Default Ollama server address
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
+/// Default Ollama embedding model
+pub const DEFAULT_OLLAMA_MODEL: &str = "nomic-embed-text";
+
+/// Ollama embedding provider, for fully local pipelines
+pub struct OllamaEmbeddings {
+    base_url: String,
+    model: String,
+    /// Known for common models, otherwise learned from the first response
+    dimension: AtomicUsize,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl OllamaEmbeddings {
+    /// Create a provider for a model served at `base_url`
+    pub fn new(base_url: &str, model: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            dimension: AtomicUsize::new(Self::known_dimension(model).unwrap_or(0)),
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Create from `OLLAMA_HOST` and `OLLAMA_EMBED_MODEL`, falling back to
+    /// the local default server and `nomic-embed-text`
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("OLLAMA_HOST").unwrap_or_else(|_| DEFAULT_OLLAMA_URL.to_string());
+        let model = std::env::var("OLLAMA_EMBED_MODEL").unwrap_or_else(|_| DEFAULT_OLLAMA_MODEL.to_string());
+        Self::new(&base_url, &model)
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Model name
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn known_dimension(model: &str) -> Option<usize> {
+        match model.split(':').next().unwrap_or(model) {
+            "nomic-embed-text" => Some(768),
+            "mxbai-embed-large" => Some(1024),
+            "all-minilm" => Some(384),
+            "snowflake-arctic-embed" => Some(1024),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OllamaRequest<'a> {
+    model: &'a str,
+    input: Vec<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+#[async_trait]
+impl EmbeddingProvider for OllamaEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let results = self.embed_batch(&[text]).await?;
+        results.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let request = OllamaRequest {
+            model: &self.model,
+            input: texts.to_vec(),
+        };
+
+        let response = send_with_retry(&self.retry, || self.client
+            .post(format!("{}/api/embed", self.base_url))
+            .json(&request))
+            .await
+            .with_context(|| format!("Failed to reach Ollama at {}", self.base_url))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error: {}", error_text);
+        }
+
+        let result: OllamaResponse = response.json().await
+            .context("Failed to parse Ollama response")?;
+
+        if let Some(first) = result.embeddings.first() {
+            self.dimension.store(first.len(), Ordering::Relaxed);
+        }
+        Ok(result.embeddings)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension.load(Ordering::Relaxed)
+    }
+
+    fn name(&self) -> &str {
+        "ollama"
+    }
+}
+
 /// Local hash-based embedding provider (for testing/offline use)
 ///
 /// This creates deterministic embeddings based on text hashing.
@@ -345,6 +963,27 @@ impl EmbeddingManager {
         })
     }
 
+    /// Create with a Cohere provider
+    pub fn cohere(api_key: String, model: &str) -> Self {
+        Self {
+            provider: Box::new(CohereEmbeddings::new(api_key, model)),
+        }
+    }
+
+    /// Create with a Voyage provider
+    pub fn voyage(api_key: String, model: &str) -> Self {
+        Self {
+            provider: Box::new(VoyageEmbeddings::new(api_key, model)),
+        }
+    }
+
+    /// Create with an Ollama provider
+    pub fn ollama(base_url: &str, model: &str) -> Self {
+        Self {
+            provider: Box::new(OllamaEmbeddings::new(base_url, model)),
+        }
+    }
+
     /// Create with local hash embeddings (for testing)
     pub fn local(dimension: usize) -> Self {
         Self {
@@ -357,8 +996,32 @@ impl EmbeddingManager {
         Self::local(384)
     }
 
+    /// Create with a sentence-transformer model loaded from a directory
+    #[cfg(feature = "local-embeddings")]
+    pub fn local_model(dir: impl AsRef<std::path::Path>) -> Result<Self> {
+        Ok(Self {
+            provider: Box::new(super::transformer::LocalModelEmbeddings::load(dir)?),
+        })
+    }
+
     /// Create from provider name string
+    ///
+    /// `ollama:<model>` picks an Ollama model; plain `ollama` uses
+    /// `OLLAMA_EMBED_MODEL` or the default. With the `local-embeddings`
+    /// feature, `local-model:<dir>` loads a sentence-transformer; `local`
+    /// is always hash embeddings.
     pub fn from_name(name: &str, api_key: Option<&str>) -> Result<Self> {
+        if let Some(model) = name.strip_prefix("ollama:") {
+            let base_url = std::env::var("OLLAMA_HOST").unwrap_or_else(|_| DEFAULT_OLLAMA_URL.to_string());
+            return Ok(Self::ollama(&base_url, model));
+        }
+        if let Some(dir) = name.strip_prefix("local-model:") {
+            #[cfg(feature = "local-embeddings")]
+            return Self::local_model(dir);
+            #[cfg(not(feature = "local-embeddings"))]
+            anyhow::bail!("Cannot load {}: built without the local-embeddings feature", dir);
+        }
+
         match name.to_lowercase().as_str() {
             "openai" | "openai-small" => {
                 let key = api_key
@@ -374,10 +1037,51 @@ impl EmbeddingManager {
                     .context("OpenAI requires API key")?;
                 Ok(Self::openai(key, OpenAIModel::TextEmbedding3Large))
             }
-            "local" | "hash" | "local-hash" => {
+            "cohere" | "cohere-multilingual" => {
+                let key = api_key
+                    .map(|k| k.to_string())
+                    .or_else(|| std::env::var("COHERE_API_KEY").ok())
+                    .context("Cohere requires API key (--api-key or COHERE_API_KEY env var)")?;
+                let model = if name.ends_with("multilingual") {
+                    CohereEmbeddings::MULTILINGUAL
+                } else {
+                    CohereEmbeddings::ENGLISH
+                };
+                Ok(Self::cohere(key, model))
+            }
+            "voyage" | "voyage-lite" => {
+                let key = api_key
+                    .map(|k| k.to_string())
+                    .or_else(|| std::env::var("VOYAGE_API_KEY").ok())
+                    .context("Voyage requires API key (--api-key or VOYAGE_API_KEY env var)")?;
+                let model = if name.ends_with("lite") {
+                    VoyageEmbeddings::VOYAGE_3_LITE
+                } else {
+                    VoyageEmbeddings::VOYAGE_3
+                };
+                Ok(Self::voyage(key, model))
+            }
+            "vertex" => {
+                Ok(Self { provider: Box::new(VertexEmbeddings::from_env(VertexEmbeddings::TEXT_EMBEDDING)?) })
+            }
+            "gemini" => {
+                Ok(Self { provider: Box::new(VertexEmbeddings::from_env(VertexEmbeddings::GEMINI_EMBEDDING)?) })
+            }
+            "ollama" => {
+                Ok(Self { provider: Box::new(OllamaEmbeddings::from_env()) })
+            }
+            "local-model" => {
+                anyhow::bail!("local-model requires a model directory: local-model:<dir>")
+            }
+            "local" => {
                 Ok(Self::local_default())
             }
-            _ => anyhow::bail!("Unknown embedding provider: {}. Use: openai, openai-large, local", name),
+            "hash" | "local-hash" => {
+                Ok(Self::local_default())
+            }
+            _ => anyhow::bail!("Unknown embedding provider: {}. Use: openai, openai-large, cohere, cohere-multilingual, voyage, voyage-lite, vertex, gemini, ollama, local-model:<dir>, local",
+                name,
+            ),
         }
     }
 
@@ -391,6 +1095,51 @@ impl EmbeddingManager {
         self.provider.embed_batch(texts).await
     }
 
+    /// Embed a search query
+    pub async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        self.provider.embed_query(text).await
+    }
+
+    /// Embed documents being stored for retrieval
+    pub async fn embed_documents(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.provider.embed_documents(texts).await
+    }
+
+    /// Embed documents in batches of `batch_size`, with at most
+    /// `concurrency` requests in flight
+    ///
+    /// `on_batch` sees each batch's texts and embeddings in order as they
+    /// complete, so callers can report progress or checkpoint work that a
+    /// later failure would otherwise lose.
+    pub async fn embed_documents_batched<F>(
+        &self,
+        texts: &[&str],
+        batch_size: usize,
+        concurrency: usize,
+        mut on_batch: F,
+    ) -> Result<Vec<Vec<f32>>>
+    where
+        F: FnMut(&[&str], &[Vec<f32>]) -> Result<()>,
+    {
+        let batches = texts.chunks(batch_size.max(1));
+        let mut results = futures::stream::iter(batches)
+            .map(|batch| async move {
+                let embeddings = self.provider.embed_documents(batch).await?;
+                if embeddings.len() != batch.len() {
+                    anyhow::bail!("Expected {} embeddings, got {}", batch.len(), embeddings.len());
+                }
+                Ok((batch, embeddings))
+            })
+            .buffered(concurrency.max(1));
+
+        let mut all = Vec::with_capacity(texts.len());
+        while let Some((batch, embeddings)) = results.try_next().await? {
+            on_batch(batch, &embeddings)?;
+            all.extend(embeddings);
+        }
+        Ok(all)
+    }
+
     /// Get embedding dimension
     pub fn dimension(&self) -> usize {
         self.provider.dimension()
@@ -457,6 +1206,146 @@ mod tests {
         assert_eq!("ada".parse::<OpenAIModel>().unwrap(), OpenAIModel::Ada002);
     }
 
+    /// Stand-in for an embedding API answering one request with `body`;
+    /// returns its base URL and the request it received
+    async fn serve_once(body: &'static str) -> (String, tokio::sync::oneshot::Receiver<String>) {
+        serve(vec![("200 OK", body)]).await
+    }
+
+    /// Stand-in for an embedding API answering successive requests with
+    /// each status and body in turn; the receiver gets the last request
+    async fn serve(responses: Vec<(&'static str, &'static str)>) -> (String, tokio::sync::oneshot::Receiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                request.clear();
+                let mut buf = [0u8; 4096];
+                // Every request body is a JSON object
+                while !request.ends_with(b"}") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nRetry-After: 0\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body,
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            let _ = tx.send(String::from_utf8_lossy(&request).into_owned());
+        });
+        (format!("http://{}/", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn test_retry_rate_limits() {
+        let (url, request) = serve(vec![
+            ("429 Too Many Requests", r#"{"message":"slow down"}"#),
+            ("503 Service Unavailable", r#"{"message":"busy"}"#),
+            ("200 OK", r#"{"id":"x","embeddings":[[1.0,0.0]]}"#),
+        ]).await;
+        let provider = CohereEmbeddings::new("key".to_string(), CohereEmbeddings::ENGLISH).with_base_url(&url);
+        assert_eq!(provider.embed_query("what?").await.unwrap(), vec![1.0, 0.0]);
+        assert!(request.await.unwrap().starts_with("POST /v1/embed "));
+
+        // Out of retries, the provider's error is reported
+        let (url, _) = serve(vec![("429 Too Many Requests", r#"{"message":"slow down"}"#)]).await;
+        let provider = provider.with_base_url(&url).with_retry(RetryPolicy::none());
+        let err = provider.embed_query("what?").await.unwrap_err();
+        assert!(err.to_string().contains("slow down"));
+
+        let policy = RetryPolicy { max_retries: 3, base_delay: Duration::from_secs(1), max_delay: Duration::from_secs(4) };
+        assert!(policy.delay(1, None) >= Duration::from_secs(1) && policy.delay(1, None) <= Duration::from_secs(2));
+        assert_eq!(policy.delay(5, Some(Duration::from_secs(10))), Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_batched_embedding() {
+        let manager = EmbeddingManager::local(8);
+        let texts = ["a", "b", "c", "d", "e"];
+        let mut seen = Vec::new();
+        let embeddings = manager.embed_documents_batched(&texts, 2, 3, |batch, embeddings| {
+            assert_eq!(batch.len(), embeddings.len());
+            seen.extend(batch.iter().map(|t| t.to_string()));
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(seen, texts);
+        assert_eq!(embeddings, manager.embed_documents(&texts).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ollama_embeddings() {
+        let (url, request) = serve_once(r#"{"model":"tiny","embeddings":[[0.1,0.2,0.3],[0.4,0.5,0.6]]}"#).await;
+
+        let provider = OllamaEmbeddings::new(&url, "tiny");
+        assert_eq!(provider.dimension(), 0);
+        let embeddings = provider.embed_batch(&["a", "b"]).await.unwrap();
+        assert_eq!(embeddings, vec![vec![0.1, 0.2, 0.3], vec![0.4, 0.5, 0.6]]);
+        assert_eq!(provider.dimension(), 3);
+        assert!(request.await.unwrap().starts_with("POST /api/embed "));
+
+        assert_eq!(OllamaEmbeddings::new(DEFAULT_OLLAMA_URL, "nomic-embed-text:latest").dimension(), 768);
+        assert_eq!(EmbeddingManager::from_name("ollama:all-minilm", None).unwrap().dimension(), 384);
+    }
+
+    #[tokio::test]
+    async fn test_cohere_input_types() {
+        let (url, request) = serve_once(r#"{"id":"x","embeddings":[[1.0,0.0]]}"#).await;
+        let provider = CohereEmbeddings::new("key".to_string(), CohereEmbeddings::ENGLISH).with_base_url(&url);
+        assert_eq!(provider.embed_query("what?").await.unwrap(), vec![1.0, 0.0]);
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /v1/embed "));
+        assert!(request.contains(r#""input_type":"search_query""#));
+
+        let (url, request) = serve_once(r#"{"id":"y","embeddings":[[0.0,1.0]]}"#).await;
+        let provider = provider.with_base_url(&url);
+        provider.embed_documents(&["doc"]).await.unwrap();
+        assert!(request.await.unwrap().contains(r#""input_type":"search_document""#));
+    }
+
+    #[tokio::test]
+    async fn test_voyage_input_types() {
+        let (url, request) = serve_once(r#"{"data":[{"embedding":[0.5,0.5],"index":0}]}"#).await;
+        let provider = VoyageEmbeddings::new("key".to_string(), VoyageEmbeddings::VOYAGE_3).with_base_url(&url);
+        assert_eq!(provider.embed_query("what?").await.unwrap(), vec![0.5, 0.5]);
+        let request = request.await.unwrap();
+        assert!(request.starts_with("POST /v1/embeddings "));
+        assert!(request.contains(r#""input_type":"query""#));
+
+        let manager = EmbeddingManager::from_name("voyage-lite", Some("key")).unwrap();
+        assert_eq!((manager.name(), manager.dimension()), ("voyage", 512));
+        assert!(EmbeddingManager::from_name("cohere-multilingual", Some("key")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_vertex_embeddings() {
+        let (url, request) = serve_once(r#"{"predictions":[{"embeddings":{"values":[0.6,0.8]}}]}"#).await;
+        let provider = VertexEmbeddings::new(
+            GoogleCredentials::from_token("token"),
+            Some("my-project".to_string()),
+            "europe-west4",
+            VertexEmbeddings::TEXT_EMBEDDING,
+        )
+        .with_base_url(&url);
+        assert_eq!(provider.embed_query("what?").await.unwrap(), vec![0.6, 0.8]);
+
+        let request = request.await.unwrap();
+        assert!(request.starts_with(
+            "POST /v1/projects/my-project/locations/europe-west4/publishers/google/models/text-embedding-005:predict "
+        ));
+        assert!(request.to_lowercase().contains("authorization: bearer token"));
+        assert!(request.contains(r#""task_type":"RETRIEVAL_QUERY""#));
+        assert_eq!(provider.dimension(), 768);
+    }
+
     #[tokio::test]
     async fn test_tfidf_embeddings() {
         let mut provider = TfIdfEmbeddings::new(100);
@@ -0,0 +1,259 @@
+//! Retrieval evaluation
+//!
+//! Measures retrieval against a labeled dataset: queries, each with the
+//! chunks a good retrieval should return. Changes to chunking, embeddings,
+//! filters or fusion can then be compared by recall@k, mean reciprocal rank
+//! and nDCG@k rather than by eye.
+//!
+//! Datasets are JSON Lines, one query per line:
+//!
+//! ```text
+//! {"query": "How are passwords reset?", "relevant": ["guide.md"]}
+//! {"query": "Opening hours", "vector": [0.1, ...], "relevant": [{"document_id": "faq", "contains": "nine"}]}
+//! ```
+//!
+//! A relevant entry is a node ID or document ID, or an object whose given
+//! fields (`document_id`, `chunk_index`, `contains`) a chunk must all
+//! match. Matching by document and text keeps a dataset valid across
+//! re-chunking, when node IDs and chunk indices change.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::context::{ContextChunk, ContextRetriever};
+
+/// A chunk judged relevant to a query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RelevantChunk {
+    /// A node ID, or a document ID matching any of its chunks
+    Id(String),
+    /// Conditions a chunk must all meet
+    Match {
+        /// Document the chunk was cut from
+        #[serde(default)]
+        document_id: Option<String>,
+        /// Position of the chunk in its document
+        #[serde(default)]
+        chunk_index: Option<usize>,
+        /// Text the chunk contains
+        #[serde(default)]
+        contains: Option<String>,
+    },
+}
+
+impl RelevantChunk {
+    /// Whether a retrieved chunk is this one
+    pub fn matches(&self, chunk: &ContextChunk) -> bool {
+        match self {
+            Self::Id(id) => chunk.node_id == *id || chunk.document_id.as_ref() == Some(id),
+            Self::Match { document_id, chunk_index, contains } => {
+                document_id.as_ref().is_none_or(|d| chunk.document_id.as_ref() == Some(d))
+                    && chunk_index.is_none_or(|i| chunk.chunk_index == Some(i))
+                    && contains.as_ref().is_none_or(|text| chunk.content.contains(text.as_str()))
+            }
+        }
+    }
+}
+
+/// A labeled query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalQuery {
+    /// Query text
+    pub query: String,
+    /// Query embedding; embedded from the text when absent
+    #[serde(default)]
+    pub vector: Option<Vec<f32>>,
+    /// Chunks a good retrieval returns
+    pub relevant: Vec<RelevantChunk>,
+}
+
+/// Load a JSON Lines dataset
+pub fn load_eval_dataset(path: impl AsRef<Path>) -> Result<Vec<EvalQuery>> {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read dataset {}", path.display()))?;
+    let mut queries = Vec::new();
+    for (n, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let query: EvalQuery = serde_json::from_str(line)
+            .with_context(|| format!("{} line {}", path.display(), n + 1))?;
+        if query.relevant.is_empty() {
+            anyhow::bail!("{} line {}: no relevant chunks", path.display(), n + 1);
+        }
+        queries.push(query);
+    }
+    anyhow::ensure!(!queries.is_empty(), "Dataset {} has no queries", path.display());
+    Ok(queries)
+}
+
+/// Metrics of one query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMetrics {
+    /// Query text
+    pub query: String,
+    /// Fraction of relevant chunks in the top k
+    pub recall: f64,
+    /// One over the rank of the first relevant chunk, or 0
+    pub reciprocal_rank: f64,
+    /// Discounted gain of the top k relative to a perfect ranking
+    pub ndcg: f64,
+    /// Ranks (from 1) of the relevant chunks found
+    pub hits: Vec<usize>,
+}
+
+/// Metrics over a dataset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    /// Number of chunks considered per query
+    pub k: usize,
+    /// Mean recall@k
+    pub recall: f64,
+    /// Mean reciprocal rank
+    pub mrr: f64,
+    /// Mean nDCG@k
+    pub ndcg: f64,
+    /// Per-query metrics
+    pub queries: Vec<QueryMetrics>,
+}
+
+impl EvalReport {
+    /// Average per-query metrics
+    pub fn new(k: usize, queries: Vec<QueryMetrics>) -> Self {
+        let mean = |metric: fn(&QueryMetrics) -> f64| {
+            if queries.is_empty() {
+                0.0
+            } else {
+                queries.iter().map(metric).sum::<f64>() / queries.len() as f64
+            }
+        };
+        Self {
+            k,
+            recall: mean(|q| q.recall),
+            mrr: mean(|q| q.reciprocal_rank),
+            ndcg: mean(|q| q.ndcg),
+            queries,
+        }
+    }
+}
+
+/// Score a ranking against the relevant chunks of its query
+///
+/// Each relevant entry is credited once, to the first chunk matching it,
+/// so a document-level entry does not count every chunk of the document.
+/// Gains are binary.
+pub fn score_ranking(query: &str, chunks: &[ContextChunk], relevant: &[RelevantChunk], k: usize) -> QueryMetrics {
+    let mut credited = vec![false; relevant.len()];
+    let mut hits = Vec::new();
+    let mut dcg = 0.0;
+    for (i, chunk) in chunks.iter().take(k).enumerate() {
+        if let Some(j) = (0..relevant.len()).find(|&j| !credited[j] && relevant[j].matches(chunk)) {
+            credited[j] = true;
+            hits.push(i + 1);
+            dcg += 1.0 / (i as f64 + 2.0).log2();
+        }
+    }
+
+    let ideal: f64 = (0..relevant.len().min(k)).map(|i| 1.0 / (i as f64 + 2.0).log2()).sum();
+    QueryMetrics {
+        query: query.to_string(),
+        recall: if relevant.is_empty() { 0.0 } else { hits.len() as f64 / relevant.len() as f64 },
+        reciprocal_rank: hits.first().map_or(0.0, |rank| 1.0 / *rank as f64),
+        ndcg: if ideal > 0.0 { dcg / ideal } else { 0.0 },
+        hits,
+    }
+}
+
+/// Run every query through a retriever and score its top `k` chunks
+///
+/// Queries must carry vectors. The retriever's token budget still applies,
+/// so set it high enough to return `k` chunks.
+pub async fn evaluate(retriever: &ContextRetriever<'_>, queries: &[EvalQuery], k: usize) -> Result<EvalReport> {
+    let mut metrics = Vec::with_capacity(queries.len());
+    for query in queries {
+        let vector = query.vector.as_ref()
+            .with_context(|| format!("Query \"{}\" has no vector", query.query))?;
+        let context = retriever.retrieve(vector, &query.query).await?;
+        metrics.push(score_ranking(&query.query, &context.chunks, &query.relevant, k));
+    }
+    Ok(EvalReport::new(k, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+
+    fn chunk(id: &str, document_id: &str, index: usize, content: &str) -> ContextChunk {
+        ContextChunk {
+            node_id: id.to_string(),
+            content: content.to_string(),
+            score: 1.0,
+            distance: 0.0,
+            document_id: Some(document_id.to_string()),
+            chunk_index: Some(index),
+            start_offset: None,
+            end_offset: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_score_ranking() {
+        let chunks = [
+            chunk("n1", "faq", 0, "Opening hours"),
+            chunk("n2", "guide", 0, "Reset your password"),
+            chunk("n3", "guide", 1, "Password rules"),
+            chunk("n4", "faq", 1, "We open at nine"),
+        ];
+        let relevant: Vec<RelevantChunk> = serde_json::from_str(
+            r#"["guide", {"document_id": "faq", "contains": "nine"}]"#
+        ).unwrap();
+
+        // "guide" is credited once, at rank 2; the faq entry at rank 4
+        let metrics = score_ranking("q", &chunks, &relevant, 4);
+        assert_eq!(metrics.hits, vec![2, 4]);
+        assert_eq!(metrics.recall, 1.0);
+        assert_eq!(metrics.reciprocal_rank, 0.5);
+        let ideal = 1.0 + 1.0 / 3f64.log2();
+        assert!((metrics.ndcg - (1.0 / 3f64.log2() + 1.0 / 5f64.log2()) / ideal).abs() < 1e-9);
+
+        let top2 = score_ranking("q", &chunks, &relevant, 2);
+        assert_eq!((top2.recall, top2.hits.len()), (0.5, 1));
+        let miss = score_ranking("q", &chunks, &[RelevantChunk::Id("other".to_string())], 4);
+        assert_eq!((miss.recall, miss.reciprocal_rank, miss.ndcg), (0.0, 0.0, 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_dataset() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (document_id, content, embedding) in [
+            ("guide", "Reset your password", vec![1.0, 0.0]),
+            ("faq", "We open at nine", vec![0.0, 1.0]),
+        ] {
+            let properties = serde_json::json!({ "content": content, "document_id": document_id, "chunk_index": 0 });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let path = temp.path().join("eval.jsonl");
+        std::fs::write(&path, concat!(
+            r#"{"query": "password", "vector": [1.0, 0.1], "relevant": ["guide"]}"#, "\n",
+            "\n",
+            r#"{"query": "hours", "vector": [1.0, 0.2], "relevant": [{"document_id": "faq"}]}"#, "\n",
+        )).unwrap();
+        let queries = load_eval_dataset(&path).unwrap();
+        assert_eq!(queries.len(), 2);
+
+        let report = evaluate(&ContextRetriever::new(&db), &queries, 1).await.unwrap();
+        assert_eq!(report.queries[0].recall, 1.0);
+        assert_eq!(report.queries[1].recall, 0.0);
+        assert_eq!((report.recall, report.mrr), (0.5, 0.5));
+
+        std::fs::write(&path, r#"{"query": "q", "relevant": []}"#).unwrap();
+        assert!(load_eval_dataset(&path).is_err());
+    }
+}
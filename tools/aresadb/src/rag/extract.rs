@@ -0,0 +1,604 @@
+//! Text extraction for ingestion
+//!
+//! Finds the files under a directory or matching a glob and turns each
+//! into plain text by type: text and Markdown as they are, HTML with its
+//! markup removed, PDF from the text operators of its content streams,
+//! and source code as it is, for the code chunker.
+
+use anyhow::{Context, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::chunker::ChunkStrategy;
+use super::code::CodeLanguage;
+
+/// Type of an ingestible file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// Plain text
+    Text,
+    /// Markdown
+    Markdown,
+    /// HTML, reduced to its text
+    Html,
+    /// PDF, reduced to its text
+    Pdf,
+    /// Source code
+    Code(CodeLanguage),
+}
+
+impl FileKind {
+    /// Type of a file by its extension; `None` if it can't be ingested
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let path = path.as_ref();
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "txt" | "text" | "rst" | "csv" | "log" => Some(Self::Text),
+            "md" | "markdown" | "mdx" => Some(Self::Markdown),
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "pdf" => Some(Self::Pdf),
+            other => CodeLanguage::from_extension(other).map(Self::Code),
+        }
+    }
+
+    /// Lowercase name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Markdown => "markdown",
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+            Self::Code(language) => language.name(),
+        }
+    }
+
+    /// Chunking strategy suited to the type, at a chunk size in bytes
+    pub fn chunk_strategy(&self, chunk_size: usize, overlap: usize) -> ChunkStrategy {
+        match self {
+            Self::Markdown => ChunkStrategy::Markdown { max_size: chunk_size },
+            Self::Code(language) => ChunkStrategy::Code { language: *language, max_size: chunk_size },
+            Self::Text | Self::Html | Self::Pdf => ChunkStrategy::FixedSize { chunk_size, overlap },
+        }
+    }
+}
+
+/// Ingestible files for a path: the file itself, every supported file
+/// under a directory (skipping hidden entries), or the files a glob
+/// pattern matches, in path order
+pub fn discover_files(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    let mut files = Vec::new();
+
+    if path.is_dir() {
+        let walker = walkdir::WalkDir::new(path).into_iter().filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+        for entry in walker {
+            let entry = entry?;
+            if entry.file_type().is_file() && FileKind::from_path(entry.path()).is_some() {
+                files.push(entry.into_path());
+            }
+        }
+    } else if path.exists() {
+        files.push(path.to_path_buf());
+    } else if input.contains(['*', '?', '[']) {
+        for entry in glob::glob(input).with_context(|| format!("Invalid pattern: {}", input))? {
+            let file = entry?;
+            if file.is_file() && FileKind::from_path(&file).is_some() {
+                files.push(file);
+            }
+        }
+    } else {
+        anyhow::bail!("No such file or directory: {}", input);
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Document ID of a file found by [`discover_files`]: its path relative
+/// to the directory, or to the directory a glob pattern starts from
+pub fn document_id(input: &str, file: &Path) -> String {
+    let root: PathBuf = if Path::new(input).is_dir() {
+        input.into()
+    } else {
+        Path::new(input)
+            .components()
+            .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect()
+    };
+    let relative = file.strip_prefix(&root).unwrap_or(file);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Text of a file and its type
+pub fn extract_file(path: &Path) -> Result<(String, FileKind)> {
+    let kind = FileKind::from_path(path).unwrap_or(FileKind::Text);
+    let text = match kind {
+        FileKind::Pdf => {
+            let data = std::fs::read(path)
+                .with_context(|| format!("Failed to read file {}", path.display()))?;
+            pdf_to_text(&data).with_context(|| format!("Failed to extract text from {}", path.display()))?
+        }
+        _ => {
+            let data = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read file {}", path.display()))?;
+            match kind {
+                FileKind::Html => html_to_text(&data),
+                _ => data,
+            }
+        }
+    };
+    Ok((text, kind))
+}
+
+/// Elements whose content isn't text
+const HIDDEN_ELEMENTS: &[&str] = &["script", "style", "head", "noscript", "template", "svg"];
+
+/// Elements set apart by a blank line
+const PARAGRAPH_ELEMENTS: &[&str] = &[
+    "p", "ul", "ol", "dl", "table", "blockquote", "pre", "figure", "hr",
+    "h1", "h2", "h3", "h4", "h5", "h6",
+];
+
+/// Elements that start on a line of their own
+const LINE_ELEMENTS: &[&str] = &[
+    "div", "li", "dt", "dd", "tr", "section", "article", "header", "footer", "nav", "aside",
+    "main", "figcaption", "title", "form",
+];
+
+/// Visible text of an HTML document
+///
+/// Drops tags, comments and non-text elements, breaks lines at block
+/// elements and blank lines around paragraphs, headings and lists, marks
+/// list items with `- ` and decodes character references. Whitespace is
+/// collapsed except inside `<pre>`.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    let mut hidden: Option<String> = None;
+    let mut pre = 0usize;
+
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            let tag = &rest[1..end.saturating_sub(1).max(1)];
+            rest = &rest[end..];
+
+            let closing = tag.starts_with('/');
+            let name: String = tag.trim_start_matches('/')
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase();
+
+            if let Some(element) = &hidden {
+                if closing && name == *element {
+                    hidden = None;
+                }
+                continue;
+            }
+            if !closing && !tag.ends_with('/') && HIDDEN_ELEMENTS.contains(&name.as_str()) {
+                hidden = Some(name);
+                continue;
+            }
+            if name == "pre" {
+                pre = if closing { pre.saturating_sub(1) } else { pre + 1 };
+            }
+            if name == "br" {
+                text.push('\n');
+            } else if PARAGRAPH_ELEMENTS.contains(&name.as_str()) {
+                while !text.is_empty() && !text.ends_with("\n\n") {
+                    text.push('\n');
+                }
+            } else if LINE_ELEMENTS.contains(&name.as_str()) {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                if name == "li" && !closing {
+                    text.push_str("- ");
+                }
+            } else if matches!(name.as_str(), "td" | "th") && closing {
+                text.push('\t');
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        if hidden.is_none() {
+            let chunk = decode_entities(&rest[..end]);
+            if pre > 0 {
+                text.push_str(&chunk);
+            } else {
+                for (i, word) in chunk.split_whitespace().enumerate() {
+                    let joins = i == 0 && !chunk.starts_with(char::is_whitespace);
+                    if !joins && !text.is_empty() && !text.ends_with(['\n', ' ', '\t']) {
+                        text.push(' ');
+                    }
+                    text.push_str(word);
+                }
+                if chunk.ends_with(char::is_whitespace) && !text.ends_with(['\n', ' ']) {
+                    text.push(' ');
+                }
+            }
+        }
+        rest = &rest[end..];
+    }
+
+    // Drop trailing whitespace and allow at most one blank line in a row
+    let mut out = String::new();
+    let mut blank = true;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            if !blank {
+                out.push('\n');
+            }
+            blank = true;
+        } else {
+            out.push_str(line);
+            out.push('\n');
+            blank = false;
+        }
+    }
+    out.trim_end().to_string()
+}
+
+/// Decode HTML character references
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..].find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end + 1];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                "copy" => Some('©'),
+                _ => entity.strip_prefix('#').and_then(|n| match n.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => n.parse().ok(),
+                }).and_then(char::from_u32),
+            };
+            c.map(|c| (c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Text of a PDF
+///
+/// Reads the text-showing operators (`Tj`, `TJ`, `'` and `"`) of every
+/// content stream, inflating Flate-compressed streams. Line and text
+/// object breaks become newlines and wide `TJ` gaps become spaces. Fonts
+/// with custom encodings (common for CJK text) aren't mapped, so PDFs
+/// that rely on them come out garbled or empty.
+pub fn pdf_to_text(data: &[u8]) -> Result<String> {
+    anyhow::ensure!(data.starts_with(b"%PDF"), "Not a PDF file");
+
+    let mut text = String::new();
+    let mut offset = 0;
+    while let Some(found) = find(&data[offset..], b"stream") {
+        let keyword = offset + found;
+        offset = keyword + b"stream".len();
+        // Skip "endstream" and require the keyword to end its line
+        if data[..keyword].ends_with(b"end") {
+            continue;
+        }
+        let start = match &data[offset..] {
+            [b'\r', b'\n', ..] => offset + 2,
+            [b'\n', ..] | [b'\r', ..] => offset + 1,
+            _ => continue,
+        };
+
+        let dict = stream_dict(&data[..keyword]);
+        let end = stream_length(dict)
+            .map(|len| start + len)
+            .filter(|end| *end <= data.len() && find(&data[*end..(*end + 20).min(data.len())], b"endstream").is_some())
+            .or_else(|| find(&data[start..], b"endstream").map(|i| start + i))
+            .context("Unterminated stream")?;
+        offset = end;
+
+        if contains(dict, b"/Image") || contains(dict, b"/XRef") || contains(dict, b"/ObjStm")
+            || contains(dict, b"/FontFile") || contains(dict, b"/Length1")
+        {
+            continue;
+        }
+        let raw = &data[start..end];
+        let content = if contains(dict, b"/FlateDecode") {
+            let mut inflated = Vec::new();
+            if flate2::read::ZlibDecoder::new(raw).read_to_end(&mut inflated).is_err() {
+                continue;
+            }
+            inflated
+        } else if contains(dict, b"/Filter") {
+            // Other filters compress images or fonts, not page text
+            continue;
+        } else {
+            raw.to_vec()
+        };
+
+        if find(&content, b"BT").is_some() {
+            content_text(&content, &mut text);
+        }
+    }
+
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    Ok(lines.join("\n"))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    find(haystack, needle).is_some()
+}
+
+/// Dictionary of the stream whose keyword follows `before`
+fn stream_dict(before: &[u8]) -> &[u8] {
+    let from = before.windows(3).rposition(|w| w == b"obj").unwrap_or(0);
+    &before[from..]
+}
+
+/// Direct `/Length` of a stream dictionary
+fn stream_length(dict: &[u8]) -> Option<usize> {
+    let at = find(dict, b"/Length")? + b"/Length".len();
+    let rest = &dict[at..];
+    let rest = &rest[rest.iter().position(|b| !b.is_ascii_whitespace())?..];
+    let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+    let after = rest[digits..].iter().copied().find(|b| !b.is_ascii_whitespace());
+    // "5 0 R" is a reference to the length, not the length
+    if after.is_some_and(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    std::str::from_utf8(&rest[..digits]).ok()?.parse().ok()
+}
+
+/// Append the text shown by a content stream
+fn content_text(content: &[u8], out: &mut String) {
+    // String operands since the last operator
+    let mut operands: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < content.len() {
+        match content[i] {
+            b'(' => {
+                let (s, end) = literal_string(content, i);
+                operands.push(s);
+                i = end;
+            }
+            b'<' if content.get(i + 1) != Some(&b'<') => {
+                let end = content[i..].iter().position(|b| *b == b'>').map_or(content.len(), |p| i + p);
+                operands.push(hex_string(&content[i + 1..end]));
+                i = end + 1;
+            }
+            b'[' => {
+                // TJ array: strings with kerning adjustments between them
+                let mut s = String::new();
+                i += 1;
+                while i < content.len() && content[i] != b']' {
+                    match content[i] {
+                        b'(' => {
+                            let (part, end) = literal_string(content, i);
+                            s.push_str(&part);
+                            i = end;
+                        }
+                        b'<' => {
+                            let end = content[i..].iter().position(|b| *b == b'>').map_or(content.len(), |p| i + p);
+                            s.push_str(&hex_string(&content[i + 1..end]));
+                            i = end + 1;
+                        }
+                        b'-' | b'0'..=b'9' | b'.' => {
+                            let len = content[i..].iter()
+                                .take_while(|b| matches!(b, b'-' | b'0'..=b'9' | b'.'))
+                                .count();
+                            let gap: f64 = std::str::from_utf8(&content[i..i + len])
+                                .ok()
+                                .and_then(|n| n.parse().ok())
+                                .unwrap_or(0.0);
+                            // Offsets are thousandths of an em; a wide one is a space
+                            if gap < -200.0 && !s.ends_with(' ') {
+                                s.push(' ');
+                            }
+                            i += len;
+                        }
+                        _ => i += 1,
+                    }
+                }
+                operands.push(s);
+                i += 1;
+            }
+            b'%' => {
+                while i < content.len() && content[i] != b'\n' && content[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            b if b.is_ascii_alphabetic() || b == b'\'' || b == b'"' || b == b'*' => {
+                let len = content[i..].iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || matches!(b, b'\'' | b'"' | b'*'))
+                    .count();
+                match &content[i..i + len] {
+                    b"Tj" | b"TJ" => {
+                        for s in operands.drain(..) {
+                            out.push_str(&s);
+                        }
+                    }
+                    b"'" | b"\"" => {
+                        out.push('\n');
+                        for s in operands.drain(..) {
+                            out.push_str(&s);
+                        }
+                    }
+                    b"T*" | b"ET" => out.push('\n'),
+                    // Moving the text position starts a new line
+                    b"Td" | b"TD" if !out.ends_with(['\n', ' ']) => out.push('\n'),
+                    _ => {}
+                }
+                operands.clear();
+                i += len;
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/// Decode a literal string starting at `(`; returns it and the index after `)`
+fn literal_string(content: &[u8], open: usize) -> (String, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 0;
+    let mut i = open + 1;
+    while i < content.len() {
+        let b = content[i];
+        match b {
+            b'\\' => {
+                i += 1;
+                let Some(&escaped) = content.get(i) else { break };
+                match escaped {
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'b' => bytes.push(8),
+                    b'f' => bytes.push(12),
+                    b'0'..=b'7' => {
+                        let len = content[i..].iter().take(3).take_while(|b| matches!(b, b'0'..=b'7')).count();
+                        let code = std::str::from_utf8(&content[i..i + len])
+                            .ok()
+                            .and_then(|n| u16::from_str_radix(n, 8).ok())
+                            .unwrap_or(0);
+                        bytes.push(code as u8);
+                        i += len - 1;
+                    }
+                    b'\r' => {
+                        if content.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'\n' => {}
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(b);
+            }
+            b')' if depth == 0 => return (decode_pdf_bytes(&bytes), i + 1),
+            b')' => {
+                depth -= 1;
+                bytes.push(b);
+            }
+            _ => bytes.push(b),
+        }
+        i += 1;
+    }
+    (decode_pdf_bytes(&bytes), content.len())
+}
+
+/// Decode a hex string's digits
+fn hex_string(hex: &[u8]) -> String {
+    let digits: Vec<u8> = hex.iter()
+        .filter_map(|b| (*b as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    let bytes: Vec<u8> = digits.chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect();
+    decode_pdf_bytes(&bytes)
+}
+
+/// Text of string bytes: UTF-16 with a byte order mark, else Latin-1
+fn decode_pdf_bytes(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    bytes.iter()
+        .map(|b| *b as char)
+        .filter(|c| !c.is_control() || c.is_whitespace())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<!DOCTYPE html><html><head><title>T</title><style>p{}</style></head>\
+<body><h1>Guide &amp; FAQ</h1><!-- note --><p>First  <b>bold</b>\nline.</p>\
+<script>var x = '<p>';</script><ul><li>one</li><li>two&#x21;</li></ul>\
+<pre>a\n  b</pre></body></html>";
+        assert_eq!(html_to_text(html), "Guide & FAQ\n\nFirst bold line.\n\n- one\n- two!\n\na\n  b");
+    }
+
+    #[test]
+    fn test_pdf_to_text() {
+        let page = b"BT /F1 12 Tf 72 720 Td (Hello \\(PDF\\)) Tj 0 -14 Td [(Wor) 20 (ld) -500 (again)] TJ ET";
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"BT <FEFF00E9> Tj ET").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut pdf = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n".to_vec();
+        pdf.extend(format!("4 0 obj\n<< /Length {} >>\nstream\n", page.len()).as_bytes());
+        pdf.extend(page);
+        pdf.extend(b"\nendstream\nendobj\n");
+        pdf.extend(b"5 0 obj\n<< /Length 6 0 R /Filter /FlateDecode >>\nstream\r\n");
+        pdf.extend(&compressed);
+        pdf.extend(b"\r\nendstream\nendobj\n%%EOF\n");
+
+        assert_eq!(pdf_to_text(&pdf).unwrap(), "Hello (PDF)\nWorld again\né");
+        assert!(pdf_to_text(b"not a pdf").is_err());
+    }
+
+    #[test]
+    fn test_discover_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        for file in ["a.md", "docs/b.html", "docs/c.rs", "image.png", ".git/config.txt"] {
+            let path = temp.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+        let root = temp.path().to_str().unwrap();
+
+        let names = |files: Vec<PathBuf>| -> Vec<String> {
+            files.iter().map(|f| f.strip_prefix(temp.path()).unwrap().to_string_lossy().into_owned()).collect()
+        };
+        assert_eq!(names(discover_files(root).unwrap()), ["a.md", "docs/b.html", "docs/c.rs"]);
+        assert_eq!(names(discover_files(&format!("{}/docs/*", root)).unwrap()), ["docs/b.html", "docs/c.rs"]);
+        assert!(discover_files(&format!("{}/missing.md", root)).is_err());
+        assert_eq!(document_id(root, &temp.path().join("docs/c.rs")), "docs/c.rs");
+        assert_eq!(document_id(&format!("{}/do*/*.rs", root), &temp.path().join("docs/c.rs")), "docs/c.rs");
+
+        assert_eq!(FileKind::from_path("x.HTM"), Some(FileKind::Html));
+        assert_eq!(FileKind::from_path("x.py"), Some(FileKind::Code(CodeLanguage::Python)));
+    }
+}
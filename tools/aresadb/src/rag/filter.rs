@@ -0,0 +1,178 @@
+//! Metadata filters for retrieval
+//!
+//! Constrain which chunk nodes a retrieval considers by their properties,
+//! such as `document_id`, tags or a date range, so retrieval can be scoped
+//! per user or collection. Filters are applied before similarity scoring.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use crate::storage::{Node, Value};
+
+/// One constraint on a property
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FilterCondition {
+    /// Property equals the value
+    Eq(Value),
+    /// Property equals any of the values
+    In(Vec<Value>),
+    /// Property is an array containing the value
+    Contains(Value),
+    /// Property lies within the bounds, inclusive; numbers compare as
+    /// numbers and strings (such as ISO-8601 dates) lexically
+    Range {
+        /// Lower bound, if any
+        min: Option<Value>,
+        /// Upper bound, if any
+        max: Option<Value>,
+    },
+}
+
+impl FilterCondition {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            FilterCondition::Eq(expected) => compare(value, expected) == Some(Ordering::Equal),
+            FilterCondition::In(options) => {
+                options.iter().any(|expected| compare(value, expected) == Some(Ordering::Equal))
+            }
+            FilterCondition::Contains(expected) => match value {
+                Value::Array(items) => {
+                    items.iter().any(|item| compare(item, expected) == Some(Ordering::Equal))
+                }
+                _ => false,
+            },
+            FilterCondition::Range { min, max } => {
+                let above = min.as_ref().is_none_or(|min| {
+                    matches!(compare(value, min), Some(Ordering::Greater | Ordering::Equal))
+                });
+                let below = max.as_ref().is_none_or(|max| {
+                    matches!(compare(value, max), Some(Ordering::Less | Ordering::Equal))
+                });
+                above && below
+            }
+        }
+    }
+}
+
+/// Conditions on node properties, all of which must hold
+///
+/// Properties are named by key; a dotted path such as `metadata.source`
+/// reaches into object properties. A node lacking a filtered property
+/// doesn't match.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetadataFilter {
+    conditions: Vec<(String, FilterCondition)>,
+}
+
+impl MetadataFilter {
+    /// Create a filter that matches every node
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a condition on a property
+    pub fn condition(mut self, property: &str, condition: FilterCondition) -> Self {
+        self.conditions.push((property.to_string(), condition));
+        self
+    }
+
+    /// Require a property to equal a value
+    pub fn eq(self, property: &str, value: impl Into<Value>) -> Self {
+        self.condition(property, FilterCondition::Eq(value.into()))
+    }
+
+    /// Require a property to equal one of the values
+    pub fn any_of<V: Into<Value>>(self, property: &str, values: impl IntoIterator<Item = V>) -> Self {
+        self.condition(property, FilterCondition::In(values.into_iter().map(Into::into).collect()))
+    }
+
+    /// Require an array property, such as tags, to contain a value
+    pub fn contains(self, property: &str, value: impl Into<Value>) -> Self {
+        self.condition(property, FilterCondition::Contains(value.into()))
+    }
+
+    /// Require a property to lie within bounds, inclusive
+    pub fn range(self, property: &str, min: Option<Value>, max: Option<Value>) -> Self {
+        self.condition(property, FilterCondition::Range { min, max })
+    }
+
+    /// Restrict to one document
+    pub fn document(self, document_id: &str) -> Self {
+        self.eq("document_id", document_id)
+    }
+
+    /// Whether the filter has no conditions
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+
+    /// Whether a node satisfies every condition
+    pub fn matches(&self, node: &Node) -> bool {
+        self.conditions.iter().all(|(property, condition)| {
+            lookup(&node.properties, property).is_some_and(|value| condition.matches(value))
+        })
+    }
+}
+
+/// Property at a dotted path
+fn lookup<'a>(properties: &'a BTreeMap<String, Value>, path: &str) -> Option<&'a Value> {
+    if let Some(value) = properties.get(path) {
+        return Some(value);
+    }
+    let mut keys = path.split('.');
+    let first = properties.get(keys.next()?)?;
+    keys.try_fold(first, |value, key| value.get(key))
+}
+
+/// Order of two comparable values; `None` when their types differ
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(properties: serde_json::Value) -> Node {
+        Node::new("chunk", Value::from_json(properties).unwrap())
+    }
+
+    #[test]
+    fn test_metadata_filter() {
+        let node = chunk(serde_json::json!({
+            "document_id": "guide",
+            "tags": ["rust", "db"],
+            "published": "2024-03-01",
+            "chunk_index": 2,
+            "metadata": { "owner": "alice" },
+        }));
+
+        assert!(MetadataFilter::new().matches(&node));
+        assert!(MetadataFilter::new().document("guide").contains("tags", "rust").matches(&node));
+        assert!(!MetadataFilter::new().document("other").matches(&node));
+        assert!(MetadataFilter::new().any_of("document_id", ["faq", "guide"]).matches(&node));
+        assert!(!MetadataFilter::new().contains("tags", "python").matches(&node));
+        assert!(MetadataFilter::new().eq("metadata.owner", "alice").matches(&node));
+        assert!(!MetadataFilter::new().eq("missing", "x").matches(&node));
+
+        let in_2024 = MetadataFilter::new().range(
+            "published",
+            Some(Value::from("2024-01-01")),
+            Some(Value::from("2024-12-31")),
+        );
+        assert!(in_2024.matches(&node));
+        assert!(MetadataFilter::new().range("chunk_index", Some(Value::Float(1.5)), None).matches(&node));
+        assert!(!MetadataFilter::new().range("chunk_index", None, Some(Value::Int(1))).matches(&node));
+    }
+}
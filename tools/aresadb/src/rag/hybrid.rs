@@ -1,12 +1,15 @@
 //! Hybrid Search - combining keyword and vector search
 //!
-//! Implements Reciprocal Rank Fusion (RRF) to combine results from
-//! keyword-based (BM25-style) and vector-based similarity searches.
+//! Combines results from keyword-based (BM25-style) and vector-based
+//! similarity searches with Reciprocal Rank Fusion (RRF) or a weighted sum
+//! of normalized scores.
 
 use anyhow::Result;
 use std::collections::HashMap;
 
-use crate::storage::{Database, Node, NodeId, DistanceMetric};
+use crate::storage::{Database, Node, NodeId, DistanceMetric, SimilarityResult};
+use super::analyzer::TextAnalyzer;
+use super::filter::MetadataFilter;
 
 /// Result from hybrid search
 #[derive(Debug, Clone)]
@@ -15,7 +18,7 @@ pub struct HybridSearchResult {
     pub node_id: NodeId,
     /// The node
     pub node: Option<Node>,
-    /// Combined RRF score
+    /// Combined score, from the configured fusion strategy
     pub rrf_score: f64,
     /// Keyword search rank (if found)
     pub keyword_rank: Option<usize>,
@@ -25,9 +28,23 @@ pub struct HybridSearchResult {
     pub vector_score: Option<f64>,
 }
 
+/// How keyword and vector results are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FusionStrategy {
+    /// Reciprocal Rank Fusion: weighted `1 / (k + rank)` per result list;
+    /// ignores raw scores, so it is robust when their scales differ
+    #[default]
+    ReciprocalRank,
+    /// Weighted sum of scores, each list min-max normalized to 0-1; keeps
+    /// how much better one result is than the next
+    WeightedScore,
+}
+
 /// Hybrid search configuration
 #[derive(Debug, Clone)]
 pub struct HybridSearchConfig {
+    /// How keyword and vector results are combined
+    pub fusion: FusionStrategy,
     /// Weight for keyword results (0.0 to 1.0)
     pub keyword_weight: f64,
     /// Weight for vector results (0.0 to 1.0)
@@ -38,16 +55,20 @@ pub struct HybridSearchConfig {
     pub min_keyword_matches: usize,
     /// Vector distance metric
     pub metric: DistanceMetric,
+    /// How query and content are turned into keyword terms
+    pub analyzer: TextAnalyzer,
 }
 
 impl Default for HybridSearchConfig {
     fn default() -> Self {
         Self {
+            fusion: FusionStrategy::default(),
             keyword_weight: 0.5,
             vector_weight: 0.5,
             rrf_k: 60.0,
             min_keyword_matches: 1,
             metric: DistanceMetric::Cosine,
+            analyzer: TextAnalyzer::default(),
         }
     }
 }
@@ -70,26 +91,51 @@ impl HybridSearchConfig {
             ..Default::default()
         }
     }
+
+    /// Use weighted-score fusion instead of RRF
+    pub fn weighted(keyword_weight: f64, vector_weight: f64) -> Self {
+        Self {
+            fusion: FusionStrategy::WeightedScore,
+            keyword_weight,
+            vector_weight,
+            ..Default::default()
+        }
+    }
+
+    /// Analyze keyword terms with a specific analyzer
+    pub fn analyzer(mut self, analyzer: TextAnalyzer) -> Self {
+        self.analyzer = analyzer;
+        self
+    }
 }
 
 /// Hybrid search engine
 pub struct HybridSearch<'a> {
     db: &'a Database,
     config: HybridSearchConfig,
+    filter: Option<MetadataFilter>,
 }
 
 impl<'a> HybridSearch<'a> {
     /// Create a new hybrid search engine
     pub fn new(db: &'a Database) -> Self {
-        Self {
-            db,
-            config: HybridSearchConfig::default(),
-        }
+        Self::with_config(db, HybridSearchConfig::default())
     }
 
     /// Create with custom config
     pub fn with_config(db: &'a Database, config: HybridSearchConfig) -> Self {
-        Self { db, config }
+        Self { db, config, filter: None }
+    }
+
+    /// Only consider nodes matching a metadata filter, in both the keyword
+    /// and the vector search
+    pub fn filter(mut self, filter: MetadataFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    fn accepts(&self, node: &Node) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches(node))
     }
 
     /// Perform hybrid search
@@ -101,6 +147,22 @@ impl<'a> HybridSearch<'a> {
         content_field: &str,
         embedding_field: &str,
         k: usize,
+    ) -> Result<Vec<HybridSearchResult>> {
+        self.search_with_config(query_text, query_vector, node_type, content_field, embedding_field, k, &self.config)
+            .await
+    }
+
+    /// Perform hybrid search with a config for this query only
+    #[allow(clippy::too_many_arguments)]
+    pub async fn search_with_config(
+        &self,
+        query_text: &str,
+        query_vector: &[f32],
+        node_type: &str,
+        content_field: &str,
+        embedding_field: &str,
+        k: usize,
+        config: &HybridSearchConfig,
     ) -> Result<Vec<HybridSearchResult>> {
         // Get more candidates than needed for fusion
         let candidate_k = k * 3;
@@ -111,22 +173,24 @@ impl<'a> HybridSearch<'a> {
             node_type,
             content_field,
             candidate_k,
+            config.min_keyword_matches,
+            &config.analyzer,
         ).await?;
 
         // Perform vector search
-        let vector_results = self.db.similarity_search(
+        let vector_results = self.db.similarity_search_where(
             query_vector,
             node_type,
             embedding_field,
             candidate_k,
-            self.config.metric.clone(),
+            config.metric,
+            |node| self.accepts(node),
         ).await?;
 
-        // Fuse results using RRF
-        let fused = self.reciprocal_rank_fusion(
-            &keyword_results,
-            &vector_results,
-        );
+        let fused = match config.fusion {
+            FusionStrategy::ReciprocalRank => reciprocal_rank_fusion(config, &keyword_results, &vector_results),
+            FusionStrategy::WeightedScore => weighted_score_fusion(config, &keyword_results, &vector_results),
+        };
 
         // Take top k and fetch nodes
         let mut results = Vec::with_capacity(k);
@@ -145,20 +209,17 @@ impl<'a> HybridSearch<'a> {
         Ok(results)
     }
 
-    /// Simple keyword search using term matching
+    /// Keyword search over analyzed terms
     async fn keyword_search(
         &self,
         query: &str,
         node_type: &str,
         content_field: &str,
         k: usize,
+        min_matches: usize,
+        analyzer: &TextAnalyzer,
     ) -> Result<Vec<(NodeId, f64)>> {
-        // Tokenize query
-        let query_terms: Vec<String> = query
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+        let query_terms = query_terms(analyzer, query);
 
         if query_terms.is_empty() {
             return Ok(Vec::new());
@@ -170,13 +231,13 @@ impl<'a> HybridSearch<'a> {
         // Score each node using BM25-like scoring
         let mut scored: Vec<(NodeId, f64)> = Vec::new();
 
-        for node in nodes {
+        for node in nodes.into_iter().filter(|node| self.accepts(node)) {
             let content = node.properties
                 .get(content_field)
                 .and_then(|v| v.as_str())
                 .unwrap_or("");
 
-            let score = self.bm25_score(&query_terms, content);
+            let score = bm25_score(&query_terms, &analyzer.analyze(content), min_matches);
 
             if score > 0.0 {
                 scored.push((node.id, score));
@@ -189,93 +250,138 @@ impl<'a> HybridSearch<'a> {
 
         Ok(scored)
     }
+}
 
-    /// BM25-like scoring
-    fn bm25_score(&self, query_terms: &[String], content: &str) -> f64 {
-        let k1 = 1.5;
-        let b = 0.75;
-        let avg_doc_len = 500.0;
-
-        let content_lower = content.to_lowercase();
-        let doc_len = content.chars().count() as f64;
-
-        let mut score = 0.0;
-        let mut matches = 0;
-
-        for term in query_terms {
-            let tf = content_lower.matches(term).count() as f64;
-            if tf > 0.0 {
-                matches += 1;
-                let numerator = tf * (k1 + 1.0);
-                let denominator = tf + k1 * (1.0 - b + b * doc_len / avg_doc_len);
-                score += numerator / denominator;
-            }
-        }
+/// Distinct terms of a query, in order
+fn query_terms(analyzer: &TextAnalyzer, query: &str) -> Vec<String> {
+    let mut terms = analyzer.analyze(query);
+    let mut seen = std::collections::HashSet::new();
+    terms.retain(|term| seen.insert(term.clone()));
+    terms
+}
 
-        // Require minimum matches
-        if matches < self.config.min_keyword_matches {
-            return 0.0;
-        }
+/// BM25-like scoring over analyzed document terms
+fn bm25_score(query_terms: &[String], doc_terms: &[String], min_matches: usize) -> f64 {
+    let k1 = 1.5;
+    let b = 0.75;
+    let avg_doc_len = 100.0;
 
-        score
+    let doc_len = doc_terms.len() as f64;
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for term in doc_terms {
+        *counts.entry(term.as_str()).or_insert(0) += 1;
     }
 
-    /// Reciprocal Rank Fusion
-    fn reciprocal_rank_fusion(
-        &self,
-        keyword_results: &[(NodeId, f64)],
-        vector_results: &[crate::storage::SimilarityResult],
-    ) -> Vec<(NodeId, f64, Option<usize>, Option<usize>, Option<f64>)> {
-        let k = self.config.rrf_k;
-        let kw_weight = self.config.keyword_weight;
-        let vec_weight = self.config.vector_weight;
-
-        // Map node_id -> (combined_score, keyword_rank, vector_rank, vector_score)
-        let mut scores: HashMap<NodeId, (f64, Option<usize>, Option<usize>, Option<f64>)> =
-            HashMap::new();
-
-        // Add keyword results
-        for (rank, (node_id, _score)) in keyword_results.iter().enumerate() {
-            let rrf_score = kw_weight * (1.0 / (k + rank as f64 + 1.0));
-            scores.insert(
-                node_id.clone(),
-                (rrf_score, Some(rank + 1), None, None),
-            );
+    let mut score = 0.0;
+    let mut matches = 0;
+
+    for term in query_terms {
+        let tf = counts.get(term.as_str()).copied().unwrap_or(0) as f64;
+        if tf > 0.0 {
+            matches += 1;
+            let numerator = tf * (k1 + 1.0);
+            let denominator = tf + k1 * (1.0 - b + b * doc_len / avg_doc_len);
+            score += numerator / denominator;
         }
+    }
 
-        // Add vector results
-        for (rank, result) in vector_results.iter().enumerate() {
-            let rrf_score = vec_weight * (1.0 / (k + rank as f64 + 1.0));
+    // Require minimum matches
+    if matches < min_matches {
+        return 0.0;
+    }
 
-            if let Some(entry) = scores.get_mut(&result.node_id) {
-                entry.0 += rrf_score;
-                entry.2 = Some(rank + 1);
-                entry.3 = Some(result.score);
-            } else {
-                scores.insert(
-                    result.node_id.clone(),
-                    (rrf_score, None, Some(rank + 1), Some(result.score)),
-                );
-            }
-        }
+    score
+}
 
-        // Convert to sorted vector
-        let mut results: Vec<_> = scores
-            .into_iter()
-            .map(|(id, (score, kw_rank, vec_rank, vec_score))| {
-                (id, score, kw_rank, vec_rank, vec_score)
-            })
-            .collect();
+/// Fused result: node, combined score, keyword rank, vector rank, vector score
+type Fused = (NodeId, f64, Option<usize>, Option<usize>, Option<f64>);
+
+/// Reciprocal Rank Fusion
+fn reciprocal_rank_fusion(
+    config: &HybridSearchConfig,
+    keyword_results: &[(NodeId, f64)],
+    vector_results: &[SimilarityResult],
+) -> Vec<Fused> {
+    let k = config.rrf_k;
+    fuse(
+        config,
+        keyword_results,
+        vector_results,
+        |rank, _| 1.0 / (k + rank as f64 + 1.0),
+        |rank, _| 1.0 / (k + rank as f64 + 1.0),
+    )
+}
+
+/// Weighted sum of min-max normalized scores
+fn weighted_score_fusion(
+    config: &HybridSearchConfig,
+    keyword_results: &[(NodeId, f64)],
+    vector_results: &[SimilarityResult],
+) -> Vec<Fused> {
+    let keyword = normalizer(keyword_results.iter().map(|(_, score)| *score));
+    let vector = normalizer(vector_results.iter().map(|r| r.score));
+    fuse(config, keyword_results, vector_results, |_, score| keyword(score), |_, score| vector(score))
+}
+
+/// Map scores onto 0-1 by the range of a result list; a list whose scores
+/// are all equal maps to 1
+fn normalizer(scores: impl Iterator<Item = f64>) -> impl Fn(f64) -> f64 {
+    let (min, max) = scores.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), s| (lo.min(s), hi.max(s)));
+    move |score| if max > min { (score - min) / (max - min) } else { 1.0 }
+}
+
+/// Weight each list's per-result contribution and sum them per node
+fn fuse(
+    config: &HybridSearchConfig,
+    keyword_results: &[(NodeId, f64)],
+    vector_results: &[SimilarityResult],
+    keyword_contribution: impl Fn(usize, f64) -> f64,
+    vector_contribution: impl Fn(usize, f64) -> f64,
+) -> Vec<Fused> {
+    // Map node_id -> (combined_score, keyword_rank, vector_rank, vector_score)
+    let mut scores: HashMap<NodeId, (f64, Option<usize>, Option<usize>, Option<f64>)> =
+        HashMap::new();
+
+    // Add keyword results
+    for (rank, (node_id, score)) in keyword_results.iter().enumerate() {
+        let fused = config.keyword_weight * keyword_contribution(rank, *score);
+        scores.insert(node_id.clone(), (fused, Some(rank + 1), None, None));
+    }
 
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        results
+    // Add vector results
+    for (rank, result) in vector_results.iter().enumerate() {
+        let fused = config.vector_weight * vector_contribution(rank, result.score);
+        let entry = scores.entry(result.node_id.clone()).or_insert((0.0, None, None, None));
+        entry.0 += fused;
+        entry.2 = Some(rank + 1);
+        entry.3 = Some(result.score);
     }
+
+    // Convert to sorted vector
+    let mut results: Vec<_> = scores
+        .into_iter()
+        .map(|(id, (score, kw_rank, vec_rank, vec_score))| {
+            (id, score, kw_rank, vec_rank, vec_score)
+        })
+        .collect();
+
+    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    results
 }
 
 /// Perform simple keyword-only search
 pub fn keyword_search_sync(query: &str, documents: &[(String, String)]) -> Vec<(String, f64)> {
-    let query_lower = query.to_lowercase();
-    let query_terms: Vec<&str> = query_lower.split_whitespace().collect();
+    keyword_search_sync_with(&TextAnalyzer::default(), query, documents)
+}
+
+/// Perform simple keyword-only search, scoring documents by the fraction
+/// of query terms they contain after analysis
+pub fn keyword_search_sync_with(
+    analyzer: &TextAnalyzer,
+    query: &str,
+    documents: &[(String, String)],
+) -> Vec<(String, f64)> {
+    let query_terms = query_terms(analyzer, query);
 
     if query_terms.is_empty() {
         return Vec::new();
@@ -284,10 +390,10 @@ pub fn keyword_search_sync(query: &str, documents: &[(String, String)]) -> Vec<(
     let mut results: Vec<(String, f64)> = documents
         .iter()
         .filter_map(|(id, content)| {
-            let content_lower = content.to_lowercase();
+            let content_terms: std::collections::HashSet<String> = analyzer.analyze(content).into_iter().collect();
             let matches: usize = query_terms
                 .iter()
-                .filter(|term| content_lower.contains(*term))
+                .filter(|term| content_terms.contains(*term))
                 .count();
 
             if matches > 0 {
@@ -331,6 +437,80 @@ mod tests {
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_keyword_search_analysis() {
+        let documents = vec![
+            ("doc1".to_string(), "Training deep networks".to_string()),
+            ("doc2".to_string(), "The network is trained".to_string()),
+        ];
+
+        // Stemming matches inflections; stop words do not count
+        let results = keyword_search_sync("the trained network", &documents);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|(_, score)| *score == 1.0));
+
+        // Word pairs reward the phrase
+        let analyzer = TextAnalyzer::default().ngrams(1, 2);
+        let results = keyword_search_sync_with(&analyzer, "deep networks", &documents);
+        assert_eq!(results[0], ("doc1".to_string(), 1.0));
+        assert!(results[1].1 < 1.0);
+
+        let terms = |query: &str| bm25_score(&query_terms(&analyzer, query), &analyzer.analyze("deep networks"), 1);
+        assert!(terms("deep network") > terms("networks"));
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_filter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        for (owner, content, embedding) in [
+            ("alice", "rust storage engines", vec![1.0, 0.0]),
+            ("bob", "rust storage engines", vec![1.0, 0.0]),
+        ] {
+            let properties = serde_json::json!({ "content": content, "owner": owner });
+            db.insert_with_embedding("chunk", properties, "embedding", embedding).await.unwrap();
+        }
+
+        let search = HybridSearch::new(&db).filter(MetadataFilter::new().eq("owner", "bob"));
+        let results = search
+            .search("rust storage", &[1.0, 0.0], "chunk", "content", "embedding", 5)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let node = results[0].node.as_ref().unwrap();
+        assert_eq!(node.properties.get("owner").and_then(|v| v.as_str()), Some("bob"));
+        assert!(results[0].keyword_rank.is_some() && results[0].vector_rank.is_some());
+
+        // Per-query override: single results normalize to 1 per list
+        let config = HybridSearchConfig::weighted(0.25, 0.75);
+        let results = search
+            .search_with_config("rust storage", &[1.0, 0.0], "chunk", "content", "embedding", 5, &config)
+            .await
+            .unwrap();
+        assert_eq!(results[0].rrf_score, 1.0);
+    }
+
+    #[test]
+    fn test_fusion_strategies() {
+        let (a, b, c) = (NodeId::new(), NodeId::new(), NodeId::new());
+        let keyword = vec![(a.clone(), 10.0), (b.clone(), 1.0)];
+        let vector = vec![
+            SimilarityResult { node_id: b.clone(), score: 0.9, distance: 0.1 },
+            SimilarityResult { node_id: c.clone(), score: 0.89, distance: 0.11 },
+        ];
+        let order = |fused: Vec<Fused>| fused.into_iter().map(|f| f.0).collect::<Vec<_>>();
+
+        // RRF rewards B for appearing in both lists
+        let rrf = HybridSearchConfig { keyword_weight: 0.7, vector_weight: 0.3, ..Default::default() };
+        assert_eq!(order(reciprocal_rank_fusion(&rrf, &keyword, &vector)), vec![b.clone(), a.clone(), c.clone()]);
+
+        // Weighted scores see A's keyword score dwarf B's
+        let weighted = HybridSearchConfig::weighted(0.7, 0.3);
+        let fused = weighted_score_fusion(&weighted, &keyword, &vector);
+        assert_eq!(fused[0].1, 0.7);
+        assert_eq!(order(fused), vec![a, b, c]);
+    }
+
     #[test]
     fn test_hybrid_config_default() {
         let config = HybridSearchConfig::default();
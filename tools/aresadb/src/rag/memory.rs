@@ -0,0 +1,332 @@
+//! Conversation memory
+//!
+//! Chat applications send the model the conversation so far along with
+//! retrieved documents. Turns are stored as nodes carrying their
+//! conversation, role, position and (optionally) an embedding. The next
+//! call sees a window of the most recent turns plus earlier turns recalled
+//! by similarity to the new message, so long conversations keep relevant
+//! history without sending all of it.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Database, DistanceMetric, Node};
+use super::context::{ContextRetriever, RetrievedContext};
+use super::filter::MetadataFilter;
+use super::tokenizer::TokenCounter;
+
+/// Who spoke a turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Instructions to the model
+    System,
+    /// The person chatting
+    User,
+    /// The model
+    Assistant,
+}
+
+impl Role {
+    /// Parse a role name
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "system" => Ok(Self::System),
+            "user" => Ok(Self::User),
+            "assistant" => Ok(Self::Assistant),
+            _ => anyhow::bail!("Unknown role: {}. Use system, user or assistant", name),
+        }
+    }
+
+    /// Get the name of this role
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+        }
+    }
+}
+
+/// A stored conversation turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Turn {
+    /// The node ID from the database
+    pub node_id: String,
+    /// Who spoke
+    pub role: Role,
+    /// What was said
+    pub content: String,
+    /// Position in the conversation, from 0
+    pub sequence: usize,
+    /// Similarity to the query, for recalled turns
+    pub score: Option<f64>,
+}
+
+impl Turn {
+    fn from_node(node: &Node) -> Option<Self> {
+        Some(Self {
+            node_id: node.id.to_string(),
+            role: Role::parse(node.properties.get("role")?.as_str()?).ok()?,
+            content: node.properties.get("content")?.as_str()?.to_string(),
+            sequence: node.properties.get("sequence")?.as_int()? as usize,
+            score: None,
+        })
+    }
+}
+
+/// Conversation history and retrieved documents for the next model call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatContext {
+    /// Earlier turns similar to the query, in conversation order
+    pub recalled: Vec<Turn>,
+    /// The most recent turns, in conversation order
+    pub recent: Vec<Turn>,
+    /// Documents retrieved for the query
+    pub documents: RetrievedContext,
+}
+
+impl ChatContext {
+    /// Format history and documents as a single string for LLM consumption
+    pub fn format_for_llm(&self) -> String {
+        let turns = |turns: &[Turn]| {
+            turns.iter()
+                .map(|t| format!("{}: {}", t.role.name(), t.content))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let mut sections = Vec::new();
+        if !self.recalled.is_empty() {
+            sections.push(format!("## Earlier in the conversation\n{}", turns(&self.recalled)));
+        }
+        if !self.recent.is_empty() {
+            sections.push(format!("## Recent conversation\n{}", turns(&self.recent)));
+        }
+        if !self.documents.chunks.is_empty() {
+            sections.push(format!("## Retrieved documents\n{}", self.documents.format_for_llm()));
+        }
+        sections.join("\n\n")
+    }
+}
+
+/// The turns of one conversation
+pub struct ConversationMemory<'a> {
+    db: &'a Database,
+    conversation_id: String,
+    node_type: String,
+    embedding_field: String,
+    window: usize,
+    recall: usize,
+    max_tokens: usize,
+    tokens: TokenCounter,
+}
+
+impl<'a> ConversationMemory<'a> {
+    /// Memory of a conversation, stored as `turn` nodes
+    pub fn new(db: &'a Database, conversation_id: &str) -> Self {
+        Self {
+            db,
+            conversation_id: conversation_id.to_string(),
+            node_type: "turn".to_string(),
+            embedding_field: "embedding".to_string(),
+            window: 10,
+            recall: 3,
+            max_tokens: 2048,
+            tokens: TokenCounter::from_env(),
+        }
+    }
+
+    /// Set the node type holding the turns
+    pub fn node_type(mut self, node_type: &str) -> Self {
+        self.node_type = node_type.to_string();
+        self
+    }
+
+    /// Set the field turn embeddings are stored in
+    pub fn embedding_field(mut self, field: &str) -> Self {
+        self.embedding_field = field.to_string();
+        self
+    }
+
+    /// Set how many recent turns are always included
+    pub fn window(mut self, turns: usize) -> Self {
+        self.window = turns;
+        self
+    }
+
+    /// Set how many earlier turns are recalled by similarity
+    pub fn recall(mut self, turns: usize) -> Self {
+        self.recall = turns;
+        self
+    }
+
+    /// Set the token budget for history; the oldest turns are dropped
+    /// first, recalled turns before recent ones
+    pub fn max_tokens(mut self, tokens: usize) -> Self {
+        self.max_tokens = tokens;
+        self
+    }
+
+    /// Count tokens against `max_tokens` with a specific counter
+    pub fn token_counter(mut self, tokens: TokenCounter) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    fn filter(&self) -> MetadataFilter {
+        MetadataFilter::new().eq("conversation_id", self.conversation_id.as_str())
+    }
+
+    /// Append a turn, with an embedding to make it recallable
+    pub async fn add(&self, role: Role, content: &str, embedding: Option<Vec<f32>>) -> Result<Turn> {
+        let sequence = self.history().await?.last().map_or(0, |t| t.sequence + 1);
+        let properties = serde_json::json!({
+            "conversation_id": self.conversation_id,
+            "role": role.name(),
+            "content": content,
+            "sequence": sequence,
+        });
+        let node = match embedding {
+            Some(embedding) => {
+                self.db.insert_with_embedding(&self.node_type, properties, &self.embedding_field, embedding).await?
+            }
+            None => self.db.insert_node(&self.node_type, properties).await?,
+        };
+        Ok(Turn {
+            node_id: node.id.to_string(),
+            role,
+            content: content.to_string(),
+            sequence,
+            score: None,
+        })
+    }
+
+    /// All turns, in conversation order
+    pub async fn history(&self) -> Result<Vec<Turn>> {
+        let filter = self.filter();
+        let mut turns: Vec<Turn> = self.db.get_all_by_type(&self.node_type, None).await?
+            .iter()
+            .filter(|node| filter.matches(node))
+            .filter_map(Turn::from_node)
+            .collect();
+        turns.sort_by_key(|t| t.sequence);
+        Ok(turns)
+    }
+
+    /// Delete every turn, returning how many there were
+    pub async fn clear(&self) -> Result<usize> {
+        let filter = self.filter();
+        let mut nodes = self.db.get_all_by_type(&self.node_type, None).await?;
+        nodes.retain(|node| filter.matches(node));
+        let removed = nodes.len();
+        self.db.replace_nodes(nodes, Vec::new()).await?;
+        Ok(removed)
+    }
+
+    /// Recent turns plus earlier turns similar to the query, within the
+    /// token budget
+    ///
+    /// Returns `(recalled, recent)`, each in conversation order.
+    pub async fn recall_turns(&self, query_vector: Option<&[f32]>) -> Result<(Vec<Turn>, Vec<Turn>)> {
+        let history = self.history().await?;
+        let split = history.len().saturating_sub(self.window);
+        let recent = history[split..].to_vec();
+
+        let mut recalled = Vec::new();
+        if let (Some(query_vector), true) = (query_vector, self.recall > 0 && split > 0) {
+            let filter = self.filter();
+            let results = self.db.similarity_search_where(
+                query_vector,
+                &self.node_type,
+                &self.embedding_field,
+                self.recall,
+                DistanceMetric::Cosine,
+                |node| filter.matches(node)
+                    && node.properties.get("sequence").and_then(|v| v.as_int()).is_some_and(|s| (s as usize) < split),
+            ).await?;
+            for result in results {
+                if let Some(turn) = history.iter().find(|t| t.node_id == result.node_id.to_string()) {
+                    recalled.push(Turn { score: Some(result.score), ..turn.clone() });
+                }
+            }
+        }
+
+        // Keep the newest recent turns, then the most similar recalled ones
+        let mut budget = self.max_tokens;
+        let mut fit = |turn: &Turn| {
+            let tokens = self.tokens.count(&turn.content);
+            let fits = tokens <= budget;
+            if fits {
+                budget -= tokens;
+            }
+            fits
+        };
+        let mut kept_recent: Vec<Turn> = recent.into_iter().rev().take_while(|t| fit(t)).collect();
+        kept_recent.reverse();
+        let mut kept_recalled: Vec<Turn> = recalled.into_iter().take_while(|t| fit(t)).collect();
+        kept_recalled.sort_by_key(|t| t.sequence);
+        Ok((kept_recalled, kept_recent))
+    }
+
+    /// History and retrieved documents for the next model call
+    pub async fn context(
+        &self,
+        query_vector: &[f32],
+        query_text: &str,
+        retriever: &ContextRetriever<'_>,
+    ) -> Result<ChatContext> {
+        let (recalled, recent) = self.recall_turns(Some(query_vector)).await?;
+        let documents = retriever.retrieve(query_vector, query_text).await?;
+        Ok(ChatContext { recalled, recent, documents })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_conversation_memory() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let memory = ConversationMemory::new(&db, "chat-1").window(2).recall(1);
+        let other = ConversationMemory::new(&db, "chat-2");
+
+        memory.add(Role::User, "How do I reset my password?", Some(vec![1.0, 0.0])).await.unwrap();
+        memory.add(Role::Assistant, "Use the account page.", Some(vec![0.9, 0.1])).await.unwrap();
+        memory.add(Role::User, "What are your opening hours?", Some(vec![0.0, 1.0])).await.unwrap();
+        memory.add(Role::Assistant, "Nine to five.", Some(vec![0.1, 0.9])).await.unwrap();
+        other.add(Role::User, "Password help please", Some(vec![1.0, 0.0])).await.unwrap();
+
+        let history = memory.history().await.unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3].sequence, 3);
+
+        // The window holds the last two turns; the password question is
+        // recalled from earlier, not the other conversation's
+        let (recalled, recent) = memory.recall_turns(Some(&[1.0, 0.0])).await.unwrap();
+        assert_eq!(recent.iter().map(|t| t.content.as_str()).collect::<Vec<_>>(), ["What are your opening hours?", "Nine to five."]);
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].content, "How do I reset my password?");
+
+        let properties = serde_json::json!({"content": "Passwords are reset from the account page"});
+        db.insert_with_embedding("chunk", properties, "embedding", vec![1.0, 0.0]).await.unwrap();
+        let retriever = ContextRetriever::new(&db);
+        let context = memory.context(&[1.0, 0.0], "reset password", &retriever).await.unwrap();
+        let formatted = context.format_for_llm();
+        assert!(formatted.find("Earlier").unwrap() < formatted.find("Recent").unwrap());
+        assert!(formatted.contains("user: How do I reset my password?"));
+        assert!(formatted.contains("Passwords are reset"));
+
+        // A tight budget keeps the newest turn only
+        let tight = ConversationMemory::new(&db, "chat-1").window(2).max_tokens(4).token_counter(TokenCounter::Estimate);
+        let (recalled, recent) = tight.recall_turns(Some(&[1.0, 0.0])).await.unwrap();
+        assert!(recalled.is_empty());
+        assert_eq!(recent.len(), 1);
+
+        assert_eq!(memory.clear().await.unwrap(), 4);
+        assert!(memory.history().await.unwrap().is_empty());
+        assert_eq!(other.history().await.unwrap().len(), 1);
+    }
+}
@@ -3,19 +3,48 @@
 //! Provides document chunking, embedding workflows, and context retrieval
 //! for building RAG applications with AresaDB.
 
+mod analyzer;
+mod checkpoint;
 mod chunker;
+mod code;
 mod context;
+mod document;
 mod embeddings;
+mod eval;
+mod extract;
+mod filter;
+mod google_auth;
 mod hybrid;
+mod memory;
+mod rerank;
+mod tokenizer;
+#[cfg(feature = "local-embeddings")]
+mod transformer;
 
+pub use analyzer::{Language, TextAnalyzer, Tokenizer};
+pub use checkpoint::EmbeddingCheckpoint;
 pub use chunker::{Chunker, ChunkStrategy, DocumentChunk};
-pub use context::{ContextRetriever, RetrievedContext, ContextChunk};
+pub use code::CodeLanguage;
+pub use context::{ContextOptions, ContextRetriever, RetrievedContext, ContextChunk};
+pub use document::{DocumentStore, IngestOptions, IngestPlan, IngestStats, content_hash};
+pub use eval::{EvalQuery, EvalReport, QueryMetrics, RelevantChunk, evaluate, load_eval_dataset};
 pub use embeddings::{
-    EmbeddingProvider, EmbeddingManager,
+    EmbeddingProvider, EmbeddingManager, RetryPolicy,
     OpenAIEmbeddings, OpenAIModel,
+    OllamaEmbeddings, DEFAULT_OLLAMA_MODEL, DEFAULT_OLLAMA_URL,
+    CohereEmbeddings, VoyageEmbeddings, InputType,
+    VertexEmbeddings,
     LocalHashEmbeddings, TfIdfEmbeddings,
 };
-pub use hybrid::{HybridSearch, HybridSearchConfig, HybridSearchResult, keyword_search_sync};
+pub use extract::{FileKind, discover_files, document_id, extract_file, html_to_text, pdf_to_text};
+pub use filter::{FilterCondition, MetadataFilter};
+pub use google_auth::GoogleCredentials;
+#[cfg(feature = "local-embeddings")]
+pub use transformer::LocalModelEmbeddings;
+pub use rerank::{Bm25Reranker, CohereReranker, KeywordReranker, Reranker, DEFAULT_RERANK_CANDIDATES};
+pub use tokenizer::{BpeTokenizer, TokenCounter, TOKENIZER_ENV};
+pub use memory::{ChatContext, ConversationMemory, Role, Turn};
+pub use hybrid::{FusionStrategy, HybridSearch, HybridSearchConfig, HybridSearchResult, keyword_search_sync, keyword_search_sync_with};
 
 /// Default chunk size in characters
 pub const DEFAULT_CHUNK_SIZE: usize = 512;
@@ -0,0 +1,166 @@
+//! Reranking
+//!
+//! A reranker rescores the top candidates of a vector search against the
+//! query text before context is assembled. Cross-encoders such as Cohere
+//! Rerank read query and chunk together, so they rank long documents far
+//! more precisely than embedding similarity alone.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::context::{bm25_reranker, keyword_reranker};
+
+/// Default number of candidates handed to a reranker
+pub const DEFAULT_RERANK_CANDIDATES: usize = 50;
+
+/// Rescores retrieval candidates against the query
+#[async_trait]
+pub trait Reranker: Send + Sync {
+    /// Relevance of each document to the query, in document order;
+    /// higher is more relevant
+    async fn rerank(&self, query: &str, documents: &[&str]) -> Result<Vec<f64>>;
+
+    /// Get the name of this reranker
+    fn name(&self) -> &str;
+}
+
+/// Reranks by the fraction of query words a chunk contains
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeywordReranker;
+
+#[async_trait]
+impl Reranker for KeywordReranker {
+    async fn rerank(&self, query: &str, documents: &[&str]) -> Result<Vec<f64>> {
+        Ok(documents.iter().map(|doc| keyword_reranker(query, doc)).collect())
+    }
+
+    fn name(&self) -> &str {
+        "keyword"
+    }
+}
+
+/// Reranks by BM25-style term frequency
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bm25Reranker;
+
+#[async_trait]
+impl Reranker for Bm25Reranker {
+    async fn rerank(&self, query: &str, documents: &[&str]) -> Result<Vec<f64>> {
+        Ok(documents.iter().map(|doc| bm25_reranker(query, doc)).collect())
+    }
+
+    fn name(&self) -> &str {
+        "bm25"
+    }
+}
+
+/// Cohere Rerank cross-encoder
+pub struct CohereReranker {
+    api_key: String,
+    model: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl CohereReranker {
+    /// English model
+    pub const ENGLISH: &'static str = "rerank-english-v3.0";
+    /// Multilingual model
+    pub const MULTILINGUAL: &'static str = "rerank-multilingual-v3.0";
+
+    /// Create a reranker for a Cohere model
+    pub fn new(api_key: String, model: &str) -> Self {
+        Self {
+            api_key,
+            model: model.to_string(),
+            base_url: "https://api.cohere.com".to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Create from environment variable COHERE_API_KEY
+    pub fn from_env(model: &str) -> Result<Self> {
+        let api_key = std::env::var("COHERE_API_KEY")
+            .context("COHERE_API_KEY environment variable not set")?;
+        Ok(Self::new(api_key, model))
+    }
+
+    /// Send requests to another endpoint, such as a proxy
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct RerankRequest<'a> {
+    model: &'a str,
+    query: &'a str,
+    documents: &'a [&'a str],
+}
+
+#[derive(Deserialize)]
+struct RerankResponse {
+    results: Vec<RerankResult>,
+}
+
+#[derive(Deserialize)]
+struct RerankResult {
+    index: usize,
+    relevance_score: f64,
+}
+
+#[async_trait]
+impl Reranker for CohereReranker {
+    async fn rerank(&self, query: &str, documents: &[&str]) -> Result<Vec<f64>> {
+        if documents.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let request = RerankRequest { model: &self.model, query, documents };
+        let response = self.client
+            .post(format!("{}/v1/rerank", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&request)
+            .send()
+            .await
+            .context("Failed to send request to Cohere")?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Cohere API error: {}", error_text);
+        }
+
+        let result: RerankResponse = response.json().await
+            .context("Failed to parse Cohere response")?;
+
+        // Results come sorted by relevance; put them back in document order
+        let mut scores = vec![0.0; documents.len()];
+        for r in result.results {
+            if let Some(score) = scores.get_mut(r.index) {
+                *score = r.relevance_score;
+            }
+        }
+        Ok(scores)
+    }
+
+    fn name(&self) -> &str {
+        "cohere"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_heuristic_rerankers() {
+        let documents = ["graph databases store edges", "cooking with cast iron"];
+        let scores = KeywordReranker.rerank("graph edges", &documents).await.unwrap();
+        assert_eq!(scores, vec![1.0, 0.0]);
+
+        let scores = Bm25Reranker.rerank("graph", &documents).await.unwrap();
+        assert!(scores[0] > scores[1]);
+    }
+}
@@ -0,0 +1,294 @@
+//! Token counting
+//!
+//! Chunk sizes and context budgets are measured in model tokens. Without a
+//! vocabulary, tokens are estimated at four characters each. With a
+//! tiktoken rank file (such as `cl100k_base.tiktoken`, the vocabulary of
+//! the GPT-4 and text-embedding-3 models), text is encoded with byte-pair
+//! merges exactly as tiktoken does, so counts match the model's limits.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// Environment variable naming a tiktoken rank file to count tokens with
+pub const TOKENIZER_ENV: &str = "ARESADB_TOKENIZER";
+
+/// Counts the tokens of a text
+#[derive(Debug, Clone, Default)]
+pub enum TokenCounter {
+    /// About four characters per token
+    #[default]
+    Estimate,
+    /// Exact counts from a BPE vocabulary
+    Bpe(Arc<BpeTokenizer>),
+}
+
+impl TokenCounter {
+    /// Count tokens with a tiktoken rank file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::Bpe(Arc::new(BpeTokenizer::from_file(path)?)))
+    }
+
+    /// Counter configured by `ARESADB_TOKENIZER`, or the estimate if unset
+    ///
+    /// The vocabulary is loaded once per process. A file that fails to
+    /// load is reported and the estimate used instead.
+    pub fn from_env() -> Self {
+        static COUNTER: OnceLock<TokenCounter> = OnceLock::new();
+        COUNTER
+            .get_or_init(|| match std::env::var(TOKENIZER_ENV) {
+                Ok(path) if !path.is_empty() => Self::load(&path).unwrap_or_else(|e| {
+                    tracing::warn!("Falling back to estimated token counts: {:#}", e);
+                    Self::Estimate
+                }),
+                _ => Self::Estimate,
+            })
+            .clone()
+    }
+
+    /// Number of tokens in a text
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Self::Estimate => text.chars().count() / 4,
+            Self::Bpe(tokenizer) => tokenizer.count(text),
+        }
+    }
+
+    /// Get the name of this counter
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Estimate => "estimate",
+            Self::Bpe(tokenizer) => tokenizer.name(),
+        }
+    }
+}
+
+/// Byte-pair encoder over a tiktoken vocabulary
+///
+/// Text is first split into pieces with the `cl100k_base` pattern
+/// (contractions, words with one leading non-letter, runs of up to three
+/// digits, punctuation and whitespace), then each piece is merged from
+/// single bytes, lowest rank first.
+pub struct BpeTokenizer {
+    name: String,
+    ranks: HashMap<Vec<u8>, u32>,
+}
+
+impl std::fmt::Debug for BpeTokenizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BpeTokenizer")
+            .field("name", &self.name)
+            .field("vocab_size", &self.ranks.len())
+            .finish()
+    }
+}
+
+impl BpeTokenizer {
+    /// Load a `.tiktoken` file, named after its file stem
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tokenizer {}", path.display()))?;
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("bpe");
+        Self::from_tiktoken(name, &data)
+            .with_context(|| format!("Invalid tokenizer {}", path.display()))
+    }
+
+    /// Parse tiktoken ranks: one base64 token and its rank per line
+    pub fn from_tiktoken(name: &str, data: &str) -> Result<Self> {
+        let mut ranks = HashMap::new();
+        for (n, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (token, rank) = line.split_once(' ')
+                .with_context(|| format!("Line {}: expected a token and a rank", n + 1))?;
+            let token = base64::engine::general_purpose::STANDARD.decode(token)
+                .with_context(|| format!("Line {}: invalid base64", n + 1))?;
+            let rank = rank.trim().parse()
+                .with_context(|| format!("Line {}: invalid rank", n + 1))?;
+            ranks.insert(token, rank);
+        }
+        anyhow::ensure!(!ranks.is_empty(), "No tokens");
+        Ok(Self { name: name.to_string(), ranks })
+    }
+
+    /// Get the name of this vocabulary
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of tokens in the vocabulary
+    pub fn vocab_size(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// Token ids of a text
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut tokens = Vec::new();
+        for piece in pretokenize(text) {
+            let bytes = piece.as_bytes();
+            match self.ranks.get(bytes) {
+                Some(rank) => tokens.push(*rank),
+                None => tokens.extend(self.merge(bytes).into_iter().map(|part| {
+                    // Vocabularies hold every single byte; a partial one
+                    // counts an unknown byte as one token
+                    self.ranks.get(part).copied().unwrap_or(u32::MAX)
+                })),
+            }
+        }
+        tokens
+    }
+
+    /// Number of tokens in a text
+    pub fn count(&self, text: &str) -> usize {
+        pretokenize(text)
+            .map(|piece| match self.ranks.contains_key(piece.as_bytes()) {
+                true => 1,
+                false => self.merge(piece.as_bytes()).len(),
+            })
+            .sum()
+    }
+
+    /// Split a piece into tokens by merging the adjacent pair with the
+    /// lowest rank until no pair is in the vocabulary
+    fn merge<'a>(&self, piece: &'a [u8]) -> Vec<&'a [u8]> {
+        // Start offsets of the parts, plus the end
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let best = (0..bounds.len().saturating_sub(2))
+                .filter_map(|i| self.ranks.get(&piece[bounds[i]..bounds[i + 2]]).map(|r| (*r, i)))
+                .min();
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => break,
+            }
+        }
+        bounds.windows(2).map(|w| &piece[w[0]..w[1]]).collect()
+    }
+}
+
+/// Split text the way the `cl100k_base` pattern does:
+///
+/// `'s|'t|'re|'ve|'m|'ll|'d` (any case), `[^\r\n\p{L}\p{N}]?\p{L}+`,
+/// `\p{N}{1,3}`, ` ?[^\s\p{L}\p{N}]+[\r\n]*`, `\s*[\r\n]+`, `\s+(?!\S)`
+/// and `\s+`, tried in that order at each position.
+fn pretokenize(text: &str) -> impl Iterator<Item = &str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        if i >= chars.len() {
+            return None;
+        }
+        let start = i;
+        i = piece_end(&chars, i);
+        let from = chars[start].0;
+        let to = chars.get(i).map_or(text.len(), |(offset, _)| *offset);
+        Some(&text[from..to])
+    })
+}
+
+/// Index of the character after the piece starting at `i`
+fn piece_end(chars: &[(usize, char)], i: usize) -> usize {
+    let at = |j: usize| chars.get(j).map(|(_, c)| *c);
+    let is_letter = |c: Option<char>| c.is_some_and(char::is_alphabetic);
+    let is_number = |c: Option<char>| c.is_some_and(char::is_numeric);
+    let is_space = |c: Option<char>| c.is_some_and(char::is_whitespace);
+    let is_newline = |c: Option<char>| matches!(c, Some('\r' | '\n'));
+    let is_other = |c: Option<char>| c.is_some_and(|c| !c.is_whitespace() && !c.is_alphabetic() && !c.is_numeric());
+    let run = |mut j: usize, pred: &dyn Fn(Option<char>) -> bool| {
+        while pred(at(j)) {
+            j += 1;
+        }
+        j
+    };
+    let c = at(i);
+
+    // Contractions
+    if c == Some('\'') {
+        let lower = |j: usize| at(j).map(|c| c.to_ascii_lowercase());
+        if matches!((lower(i + 1), lower(i + 2)), (Some('r'), Some('e')) | (Some('v'), Some('e')) | (Some('l'), Some('l'))) {
+            return i + 3;
+        }
+        if matches!(lower(i + 1), Some('s' | 't' | 'm' | 'd')) {
+            return i + 2;
+        }
+    }
+
+    // Words, with one leading character that is not a newline, letter or digit
+    if is_letter(c) {
+        return run(i, &is_letter);
+    }
+    if !is_newline(c) && !is_number(c) && is_letter(at(i + 1)) {
+        return run(i + 1, &is_letter);
+    }
+
+    // Up to three digits
+    if is_number(c) {
+        let mut j = i;
+        while j < i + 3 && is_number(at(j)) {
+            j += 1;
+        }
+        return j;
+    }
+
+    // Punctuation, with one leading space and trailing newlines
+    let punct = if c == Some(' ') && is_other(at(i + 1)) { i + 1 } else { i };
+    if is_other(at(punct)) {
+        let j = run(punct, &is_other);
+        return run(j, &is_newline);
+    }
+
+    // Whitespace ending in newlines; else all but the last whitespace
+    // character before a word, which that word takes
+    let end = run(i, &is_space);
+    if let Some(last_newline) = (i..end).rev().find(|j| is_newline(at(*j))) {
+        return last_newline + 1;
+    }
+    if end < chars.len() && end - i > 1 {
+        return end - 1;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vocab(tokens: &[&str]) -> BpeTokenizer {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let mut data = String::new();
+        for byte in 0..=255u8 {
+            data.push_str(&format!("{} {}\n", engine.encode([byte]), byte));
+        }
+        for (i, token) in tokens.iter().enumerate() {
+            data.push_str(&format!("{} {}\n", engine.encode(token), 256 + i));
+        }
+        BpeTokenizer::from_tiktoken("test", &data).unwrap()
+    }
+
+    #[test]
+    fn test_pretokenize() {
+        let pieces: Vec<&str> = pretokenize("Hello world's 12345 !!\n\n  x\tDON'T").collect();
+        assert_eq!(pieces, ["Hello", " world", "'s", " ", "123", "45", " !!\n\n", " ", " x", "\tDON", "'T"]);
+        assert_eq!(pretokenize("a  \n b  ").collect::<Vec<_>>(), ["a", "  \n", " b", "  "]);
+    }
+
+    #[test]
+    fn test_bpe_encoding() {
+        let tokenizer = vocab(&["ab", " ab", "cd", "abc"]);
+        // " abcd": "ab", then " ab" (257) before "cd" (258) and "abc" (259)
+        assert_eq!(tokenizer.encode(" abcd"), vec![257, 258]);
+        assert_eq!(tokenizer.encode("abc"), vec![259]);
+        assert_eq!(tokenizer.count("abc abcd x"), 1 + 2 + 2);
+        assert_eq!(tokenizer.vocab_size(), 260);
+
+        let counter = TokenCounter::Bpe(Arc::new(tokenizer));
+        assert_eq!(counter.count("abc"), 1);
+        assert_eq!(TokenCounter::Estimate.count("abcdefgh"), 2);
+    }
+}
@@ -0,0 +1,414 @@
+//! Local Transformer Embeddings
+//!
+//! Runs a BERT-style sentence-transformer (such as all-MiniLM-L6-v2) on the
+//! CPU with candle, so ingestion gets real semantic vectors without an API
+//! key or a model server. Behind the `local-embeddings` feature.
+//!
+//! A model is a directory as published on the Hugging Face hub:
+//! `config.json`, `vocab.txt` and `model.safetensors` with f32, f16 or bf16
+//! weights. Embeddings are the mean of the last hidden states over the real
+//! tokens, normalized to unit length, as sentence-transformers computes them.
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+use super::embeddings::EmbeddingProvider;
+
+/// Longest input, in tokens, unless the model allows less
+const DEFAULT_MAX_LENGTH: usize = 256;
+
+/// Longest word the tokenizer splits into pieces
+const MAX_WORD_CHARS: usize = 100;
+
+/// BERT's WordPiece tokenizer, read from `vocab.txt`
+struct WordPiece {
+    vocab: HashMap<String, u32>,
+    lowercase: bool,
+    strip_accents: bool,
+}
+
+impl WordPiece {
+    fn load(dir: &Path) -> Result<Self> {
+        let vocab_text = std::fs::read_to_string(dir.join("vocab.txt"))
+            .with_context(|| format!("Failed to read {}", dir.join("vocab.txt").display()))?;
+        let vocab: HashMap<String, u32> = vocab_text
+            .lines()
+            .enumerate()
+            .map(|(i, token)| (token.to_string(), i as u32))
+            .collect();
+        for special in ["[CLS]", "[SEP]", "[UNK]"] {
+            if !vocab.contains_key(special) {
+                bail!("Vocabulary is missing {}", special);
+            }
+        }
+
+        let config = std::fs::read_to_string(dir.join("tokenizer_config.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+            .unwrap_or_default();
+        let lowercase = config.get("do_lower_case").and_then(|b| b.as_bool()).unwrap_or(true);
+        // Unset means "same as lowercasing", as in Hugging Face's BertTokenizer
+        let strip_accents = config.get("strip_accents").and_then(|b| b.as_bool()).unwrap_or(lowercase);
+
+        Ok(Self { vocab, lowercase, strip_accents })
+    }
+
+    /// WordPiece token IDs of a text, framed by `[CLS]` and `[SEP]`
+    fn tokenize(&self, text: &str, max_length: usize) -> Vec<u32> {
+        let unk = self.vocab["[UNK]"];
+        let mut ids = vec![self.vocab["[CLS]"]];
+        let budget = max_length.saturating_sub(1);
+
+        'words: for word in split_words(text, self.lowercase, self.strip_accents) {
+            let chars: Vec<char> = word.chars().collect();
+            if ids.len() >= budget {
+                break;
+            }
+            if chars.len() > MAX_WORD_CHARS {
+                ids.push(unk);
+                continue;
+            }
+
+            // Greedy longest-match-first, as BERT's tokenizer does
+            let mut pieces = Vec::new();
+            let mut start = 0;
+            while start < chars.len() {
+                let mut end = chars.len();
+                let mut found = None;
+                while start < end {
+                    let mut piece: String = chars[start..end].iter().collect();
+                    if start > 0 {
+                        piece.insert_str(0, "##");
+                    }
+                    if let Some(&id) = self.vocab.get(&piece) {
+                        found = Some(id);
+                        break;
+                    }
+                    end -= 1;
+                }
+                match found {
+                    Some(id) => pieces.push(id),
+                    None => {
+                        pieces = vec![unk];
+                        break;
+                    }
+                }
+                start = end;
+            }
+
+            for id in pieces {
+                if ids.len() >= budget {
+                    break 'words;
+                }
+                ids.push(id);
+            }
+        }
+
+        ids.push(self.vocab["[SEP]"]);
+        ids
+    }
+}
+
+/// Split text into words and punctuation, as BERT's basic tokenizer does
+fn split_words(text: &str, lowercase: bool, strip_accents: bool) -> Vec<String> {
+    let mut text = if lowercase { text.to_lowercase() } else { text.to_string() };
+    if strip_accents {
+        // Decompose, then drop the combining marks: "café" becomes "cafe"
+        text = text.nfd().filter(|&c| !unicode_normalization::char::is_combining_mark(c)).collect();
+    }
+    let mut words = Vec::new();
+    let mut current = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || c.is_control() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else if c.is_ascii_punctuation() || is_cjk(c) || (!c.is_alphanumeric() && !c.is_ascii()) {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            words.push(c.to_string());
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0x20000..=0x2A6DF | 0x2A700..=0x2B73F
+        | 0x2B740..=0x2B81F | 0x2B820..=0x2CEAF | 0xF900..=0xFAFF | 0x2F800..=0x2FA1F)
+}
+
+/// A loaded BERT encoder and its tokenizer
+struct Model {
+    bert: BertModel,
+    config: Config,
+    tokenizer: WordPiece,
+}
+
+impl Model {
+    fn load(dir: &Path) -> Result<Self> {
+        let config: Config = serde_json::from_str(
+            &std::fs::read_to_string(dir.join("config.json"))
+                .with_context(|| format!("Failed to read {}", dir.join("config.json").display()))?,
+        )
+        .context("Invalid model config.json")?;
+        let tokenizer = WordPiece::load(dir)?;
+        if tokenizer.vocab.len() > config.vocab_size {
+            bail!("Vocabulary has {} tokens but the model embeds only {}", tokenizer.vocab.len(), config.vocab_size);
+        }
+
+        let weights = std::fs::read(dir.join("model.safetensors"))
+            .with_context(|| format!("Failed to read {}", dir.join("model.safetensors").display()))?;
+        let vb = VarBuilder::from_buffered_safetensors(weights, DTYPE, &Device::Cpu)
+            .context("Invalid model.safetensors")?;
+        // Falls back to `bert.`-prefixed names, as BertForX checkpoints use
+        let bert = BertModel::load(vb, &config)?;
+
+        Ok(Self { bert, config, tokenizer })
+    }
+
+    /// Mean-pooled, unit-length embeddings of a batch of texts
+    fn embed(&self, texts: &[String], max_length: usize) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let tokens: Vec<Vec<u32>> = texts.iter()
+            .map(|text| self.tokenizer.tokenize(text, max_length))
+            .collect();
+        let width = tokens.iter().map(Vec::len).max().unwrap_or(0);
+
+        // Pad to the longest input; the mask keeps padding out of attention and pooling
+        let pad = self.config.pad_token_id as u32;
+        let mut ids = Vec::with_capacity(tokens.len() * width);
+        let mut mask = Vec::with_capacity(tokens.len() * width);
+        for row in &tokens {
+            ids.extend(row.iter().copied().chain(std::iter::repeat_n(pad, width - row.len())));
+            mask.extend((0..width).map(|i| u32::from(i < row.len())));
+        }
+
+        let device = &self.bert.device;
+        let ids = Tensor::from_vec(ids, (tokens.len(), width), device)?;
+        let mask = Tensor::from_vec(mask, (tokens.len(), width), device)?;
+        let hidden = self.bert.forward(&ids, &ids.zeros_like()?, Some(&mask))?;
+
+        let mask = mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let pooled = hidden.broadcast_mul(&mask)?.sum(1)?.broadcast_div(&mask.sum(1)?)?;
+        let norm = pooled.sqr()?.sum_keepdim(1)?.sqrt()?;
+        Ok(pooled.broadcast_div(&norm)?.to_vec2::<f32>()?)
+    }
+}
+
+/// Sentence-transformer embeddings computed on the local CPU
+pub struct LocalModelEmbeddings {
+    model: Arc<Model>,
+    name: String,
+    max_length: usize,
+}
+
+impl LocalModelEmbeddings {
+    /// Load a model directory
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let model = Model::load(dir)
+            .with_context(|| format!("Failed to load embedding model from {}", dir.display()))?;
+        let name = dir.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "local-model".to_string());
+        let provider = Self { model: Arc::new(model), name, max_length: 0 };
+        Ok(provider.with_max_length(DEFAULT_MAX_LENGTH))
+    }
+
+    /// Truncate inputs to this many tokens (at most the model's limit, and
+    /// never fewer than `[CLS]` and `[SEP]`)
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length.min(self.model.config.max_position_embeddings).max(2);
+        self
+    }
+
+    /// Model name, taken from its directory
+    pub fn model(&self) -> &str {
+        &self.name
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for LocalModelEmbeddings {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let results = self.embed_batch(&[text]).await?;
+        results.into_iter().next()
+            .ok_or_else(|| anyhow::anyhow!("No embedding returned"))
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let model = Arc::clone(&self.model);
+        let max_length = self.max_length;
+        let texts: Vec<String> = texts.iter().map(|t| t.to_string()).collect();
+        // Inference is CPU-bound; keep it off the async workers
+        tokio::task::spawn_blocking(move || model.embed(&texts, max_length))
+            .await
+            .context("Embedding task failed")?
+    }
+
+    fn dimension(&self) -> usize {
+        self.model.config.hidden_size
+    }
+
+    fn name(&self) -> &str {
+        "local-model"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Write a tiny randomly initialized BERT model
+    fn write_model(dir: &Path, vocab: &[&str]) {
+        use rand::{Rng, SeedableRng};
+
+        let hidden = 8;
+        let config = serde_json::json!({
+            "vocab_size": vocab.len(),
+            "hidden_size": hidden,
+            "num_hidden_layers": 2,
+            "num_attention_heads": 2,
+            "intermediate_size": 16,
+            "hidden_act": "gelu",
+            "hidden_dropout_prob": 0.1,
+            "max_position_embeddings": 16,
+            "type_vocab_size": 2,
+            "initializer_range": 0.02,
+            "layer_norm_eps": 1e-12,
+            "pad_token_id": 0,
+            "model_type": "bert",
+        });
+        std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+        std::fs::write(dir.join("vocab.txt"), vocab.join("\n")).unwrap();
+
+        let mut shapes: Vec<(String, Vec<usize>)> = vec![
+            ("bert.embeddings.word_embeddings.weight".into(), vec![vocab.len(), hidden]),
+            ("bert.embeddings.position_embeddings.weight".into(), vec![16, hidden]),
+            ("bert.embeddings.token_type_embeddings.weight".into(), vec![2, hidden]),
+            ("bert.embeddings.LayerNorm.weight".into(), vec![hidden]),
+            ("bert.embeddings.LayerNorm.bias".into(), vec![hidden]),
+        ];
+        for i in 0..2 {
+            for (name, shape) in [
+                ("attention.self.query", vec![hidden, hidden]),
+                ("attention.self.key", vec![hidden, hidden]),
+                ("attention.self.value", vec![hidden, hidden]),
+                ("attention.output.dense", vec![hidden, hidden]),
+                ("intermediate.dense", vec![16, hidden]),
+                ("output.dense", vec![hidden, 16]),
+            ] {
+                shapes.push((format!("bert.encoder.layer.{}.{}.bias", i, name), vec![shape[0]]));
+                shapes.push((format!("bert.encoder.layer.{}.{}.weight", i, name), shape));
+            }
+            for name in ["attention.output.LayerNorm", "output.LayerNorm"] {
+                shapes.push((format!("bert.encoder.layer.{}.{}.weight", i, name), vec![hidden]));
+                shapes.push((format!("bert.encoder.layer.{}.{}.bias", i, name), vec![hidden]));
+            }
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut header = serde_json::Map::new();
+        let mut data = Vec::new();
+        for (name, shape) in shapes {
+            let start = data.len();
+            for _ in 0..shape.iter().product::<usize>() {
+                let value = if name.ends_with("LayerNorm.weight") { 1.0f32 } else { rng.gen_range(-0.5..0.5) };
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            header.insert(name, serde_json::json!({
+                "dtype": "F32",
+                "shape": shape,
+                "data_offsets": [start, data.len()],
+            }));
+        }
+        let header = serde_json::Value::Object(header).to_string();
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&data);
+        std::fs::write(dir.join("model.safetensors"), bytes).unwrap();
+    }
+
+    const VOCAB: [&str; 11] = ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "graph", "data", "##base", "!", "the", "rust", "cafe"];
+
+    #[test]
+    fn test_tokenize() {
+        let dir = TempDir::new().unwrap();
+        write_model(dir.path(), &VOCAB);
+        let tokenizer = WordPiece::load(dir.path()).unwrap();
+
+        // [CLS] graph data ##base ! [UNK] [SEP]
+        assert_eq!(tokenizer.tokenize("Graph Database! zebra", 16), vec![2, 4, 5, 6, 7, 1, 3]);
+        // Truncation keeps room for [SEP]
+        assert_eq!(tokenizer.tokenize("the the the the", 4), vec![2, 8, 8, 3]);
+        // Accents are stripped along with case
+        assert_eq!(tokenizer.tokenize("Café CAFÉ", 16), vec![2, 10, 10, 3]);
+
+        std::fs::write(
+            dir.path().join("tokenizer_config.json"),
+            r#"{"do_lower_case": true, "strip_accents": false}"#,
+        ).unwrap();
+        let tokenizer = WordPiece::load(dir.path()).unwrap();
+        assert_eq!(tokenizer.tokenize("café", 16), vec![2, 1, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_local_model_embeddings() {
+        let dir = TempDir::new().unwrap();
+        write_model(dir.path(), &VOCAB);
+        let provider = LocalModelEmbeddings::load(dir.path()).unwrap();
+        assert_eq!(provider.dimension(), 8);
+
+        // Inputs of different lengths are padded within the batch
+        let embeddings = provider.embed_batch(&["the graph database", "rust!", "the graph database"]).await.unwrap();
+        assert_eq!(embeddings.len(), 3);
+        assert_ne!(embeddings[0], embeddings[1]);
+        let alone = provider.embed("rust!").await.unwrap();
+        for (a, b) in embeddings[1].iter().zip(&alone) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        for (a, b) in embeddings[0].iter().zip(&embeddings[2]) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        let norm: f32 = embeddings[1].iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        assert!(provider.embed_batch(&[]).await.unwrap().is_empty());
+
+        // Inputs longer than the model's positions are truncated
+        let long = "rust ".repeat(100);
+        assert_eq!(provider.embed(&long).await.unwrap().len(), 8);
+
+        std::fs::remove_file(dir.path().join("vocab.txt")).unwrap();
+        assert!(LocalModelEmbeddings::load(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_max_length() {
+        let dir = TempDir::new().unwrap();
+        write_model(dir.path(), &VOCAB);
+        let provider = LocalModelEmbeddings::load(dir.path()).unwrap();
+        assert_eq!(provider.max_length, 16);
+
+        let provider = provider.with_max_length(0);
+        assert_eq!(provider.max_length, 2);
+        let provider = provider.with_max_length(1000);
+        assert_eq!(provider.max_length, 16);
+    }
+}
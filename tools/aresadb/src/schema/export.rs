@@ -0,0 +1,189 @@
+//! Schema Export
+//!
+//! Describes schemas, their relationships and vector fields in other schema
+//! languages, compactly enough to paste into an LLM prompt.
+
+use serde_json::{json, Value as JsonValue};
+
+use super::{Schema, SchemaField, SchemaRelation};
+use crate::output::markdown_table;
+
+/// JSON Schema of each schema, with its relationships under
+/// `x-relationships` and checks under `x-checks`; a single schema is exported on its own, several as an
+/// object keyed by name
+pub fn to_json_schema(schemas: &[Schema], relations: &[SchemaRelation]) -> JsonValue {
+    let describe = |schema: &Schema| {
+        let mut json = schema.to_json_schema();
+        let links: Vec<JsonValue> = relations_of(schema, relations)
+            .map(|r| {
+                json!({
+                    "edge_type": r.edge_type,
+                    "relation": r.relation_type.to_string(),
+                    "to": r.to_schema,
+                    "strict": r.strict,
+                })
+            })
+            .collect();
+        if !links.is_empty() {
+            json["x-relationships"] = JsonValue::Array(links);
+        }
+        if !schema.checks.is_empty() {
+            json["x-checks"] = json!(schema.checks);
+        }
+        json
+    };
+
+    match schemas {
+        [schema] => describe(schema),
+        _ => JsonValue::Object(schemas.iter().map(|s| (s.name.clone(), describe(s))).collect()),
+    }
+}
+
+/// SQL `CREATE TABLE` statements, with relationships and vector search
+/// queries as comments
+pub fn to_ddl(schemas: &[Schema], relations: &[SchemaRelation]) -> String {
+    let mut statements = Vec::new();
+    for schema in schemas {
+        let mut text = schema.to_sql();
+        for relation in relations_of(schema, relations) {
+            text.push_str(&format!("\n-- {}", describe_relation(relation)));
+        }
+        for example in schema.vector_search_examples() {
+            text.push_str(&format!("\n-- {}", example));
+        }
+        statements.push(text);
+    }
+    statements.join("\n\n")
+}
+
+/// A section per schema with a field table, checks, relationships and
+/// vector search queries
+pub fn to_markdown(schemas: &[Schema], relations: &[SchemaRelation]) -> String {
+    let mut sections = Vec::new();
+    for schema in schemas {
+        let rows: Vec<Vec<String>> = schema
+            .fields
+            .iter()
+            .map(|field| vec![field.name.clone(), field.field_type.to_string(), constraints(field).join(", ")])
+            .collect();
+        let mut text = format!("## {}\n\n{}", schema.name, markdown_table(&["Field", "Type", "Constraints"], &rows));
+
+        if !schema.checks.is_empty() {
+            let checks: Vec<String> = schema.checks.iter().map(|c| format!("- `{}`", c)).collect();
+            text.push_str(&format!("\n\nChecks:\n\n{}", checks.join("\n")));
+        }
+
+        let links: Vec<String> = relations_of(schema, relations)
+            .map(|r| format!("- {}", describe_relation(r)))
+            .collect();
+        if !links.is_empty() {
+            text.push_str(&format!("\n\nRelationships:\n\n{}", links.join("\n")));
+        }
+
+        let examples = schema.vector_search_examples();
+        if !examples.is_empty() {
+            text.push_str(&format!(
+                "\n\nVector search (insert vectors as `{{\"$vector\": [...]}}`):\n\n```sql\n{}\n```",
+                examples.join("\n")
+            ));
+        }
+        sections.push(text);
+    }
+    sections.join("\n\n")
+}
+
+/// Relationships starting at a schema
+fn relations_of<'a>(schema: &'a Schema, relations: &'a [SchemaRelation]) -> impl Iterator<Item = &'a SchemaRelation> {
+    relations.iter().filter(move |r| r.from_schema == schema.name)
+}
+
+/// One line describing a relationship and the edges behind it
+fn describe_relation(relation: &SchemaRelation) -> String {
+    format!(
+        "{} {} {} via `{}` edges{}",
+        relation.from_schema,
+        relation.relation_type,
+        relation.to_schema,
+        relation.edge_type,
+        if relation.strict { " (enforced)" } else { "" }
+    )
+}
+
+/// Constraints of a field, as written in `schema create`
+fn constraints(field: &SchemaField) -> Vec<String> {
+    let mut parts = Vec::new();
+    if field.primary_key {
+        parts.push("primary key".to_string());
+    } else {
+        if !field.nullable {
+            parts.push("required".to_string());
+        }
+        if field.unique {
+            parts.push("unique".to_string());
+        }
+    }
+    if field.indexed {
+        parts.push("indexed".to_string());
+    }
+    if let Some(ref default) = field.default {
+        parts.push(format!("default {}", default));
+    }
+    if let Some(min) = field.min {
+        parts.push(format!("min={}", min));
+    }
+    if let Some(max) = field.max {
+        parts.push(format!("max={}", max));
+    }
+    if let Some(ref pattern) = field.pattern {
+        parts.push(format!("pattern={}", pattern));
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{FieldType, RelationType};
+
+    fn catalog() -> (Vec<Schema>, Vec<SchemaRelation>) {
+        let schemas = vec![
+            Schema::new(
+                "doc",
+                vec![
+                    SchemaField::new("title", FieldType::String).nullable(false),
+                    SchemaField::new("embedding", FieldType::Vector(3)),
+                ],
+            ),
+            Schema::new("author", vec![SchemaField::new("name", FieldType::String).unique(true)]),
+        ];
+        let relations = vec![SchemaRelation {
+            from_schema: "doc".to_string(),
+            to_schema: "author".to_string(),
+            relation_type: RelationType::BelongsTo,
+            alias: None,
+            edge_type: "doc_author".to_string(),
+            strict: true,
+        }];
+        (schemas, relations)
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let (schemas, relations) = catalog();
+
+        let json = to_json_schema(&schemas, &relations);
+        assert_eq!(json["doc"]["x-relationships"][0]["relation"], "belongs_to");
+        assert_eq!(json["doc"]["properties"]["embedding"]["maxItems"], 3);
+        assert!(json["author"].get("x-relationships").is_none());
+
+        let ddl = to_ddl(&schemas, &relations);
+        assert!(ddl.contains("embedding VECTOR(3)"));
+        assert!(ddl.contains("-- doc belongs_to author via `doc_author` edges (enforced)"));
+        assert!(ddl.contains("-- VECTOR SEARCH doc FIELD embedding FOR [<3 floats>]"));
+
+        let markdown = to_markdown(&schemas, &relations);
+        assert!(markdown.contains("## author"));
+        assert!(markdown.contains("| title | string | required |"));
+        assert!(markdown.contains("| name | string | unique |"));
+    }
+}
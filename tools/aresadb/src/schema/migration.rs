@@ -1,8 +1,32 @@
 //! Schema Migrations
 //!
 //! Handles schema versioning and migrations.
+//!
+//! Versioned migrations live in a directory as JSON files named
+//! `<version>_<description>.json`, holding the `up` actions and optionally
+//! the `down` actions that undo them:
+//!
+//! ```json
+//! {
+//!   "up": [{ "AddField": { "schema": "users", "field": { "name": "age", "field_type": "Int" } } }],
+//!   "down": [{ "RemoveField": { "schema": "users", "field_name": "age" } }]
+//! }
+//! ```
+//!
+//! When `down` is left out it is derived from `up` where every action can
+//! be undone without knowing the previous state.
+//!
+//! Most actions only edit the catalog. `RenameProperty` and
+//! `ConvertProperty` also rewrite every node of the type, in batches:
+//!
+//! ```json
+//! { "up": [{ "ConvertProperty": { "node_type": "users", "field": "age", "to": "Int", "on_error": "Null" } }] }
+//! ```
 
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 use super::{Schema, SchemaField, FieldType};
 
 /// A migration to apply to the database
@@ -22,20 +46,116 @@ pub struct Migration {
     pub applied: bool,
     /// Applied timestamp
     pub applied_at: Option<i64>,
+    /// Actions that undo `actions`, empty if the migration cannot be reverted
+    #[serde(default)]
+    pub down: Vec<MigrationAction>,
+    /// SHA-256 of the up and down actions, to detect edits after applying
+    #[serde(default)]
+    pub checksum: String,
 }
 
 impl Migration {
-    /// Create a new migration
+    /// Create a new migration, deriving the down actions where possible
     pub fn new(description: &str, actions: Vec<MigrationAction>) -> Self {
-        Self {
+        let down = reverse_all(&actions).unwrap_or_default();
+        Self::versioned(1, description, actions, down)
+    }
+
+    /// Create a migration with a version and explicit down actions
+    pub fn versioned(version: u32, description: &str, actions: Vec<MigrationAction>, down: Vec<MigrationAction>) -> Self {
+        let mut migration = Self {
             id: uuid::Uuid::new_v4().to_string(),
-            version: 1,
+            version,
             description: description.to_string(),
             actions,
             created_at: chrono::Utc::now().timestamp_millis(),
             applied: false,
             applied_at: None,
+            down,
+            checksum: String::new(),
+        };
+        migration.checksum = migration.compute_checksum();
+        migration
+    }
+
+    /// Load the migrations in a directory, ordered by version
+    ///
+    /// A missing directory has no migrations.
+    pub fn load_dir(dir: &Path) -> Result<Vec<Migration>> {
+        if !dir.exists() {
+            return Ok(Vec::new());
         }
+
+        let mut migrations: Vec<Migration> = Vec::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let migration = Self::load_file(&path)?;
+            if migrations.iter().any(|m| m.version == migration.version) {
+                anyhow::bail!("Duplicate migration version {} in {}", migration.version, dir.display());
+            }
+            migrations.push(migration);
+        }
+
+        migrations.sort_by_key(|m| m.version);
+        Ok(migrations)
+    }
+
+    /// Load one migration file named `<version>_<description>.json`
+    pub fn load_file(path: &Path) -> Result<Migration> {
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        let (version, name) = stem.split_once('_').unwrap_or((stem, ""));
+        let version: u32 = version.parse().map_err(|_| {
+            anyhow::anyhow!("Migration file {} must be named <version>_<description>.json", path.display())
+        })?;
+
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file: MigrationFile =
+            serde_json::from_str(&text).with_context(|| format!("Invalid migration {}", path.display()))?;
+
+        let down = match file.down {
+            Some(down) => down,
+            None => reverse_all(&file.up).unwrap_or_default(),
+        };
+        let description = file.description.unwrap_or_else(|| name.replace('_', " "));
+        Ok(Self::versioned(version, &description, file.up, down))
+    }
+
+    /// Write the migration to `dir` as `<version>_<description>.json`
+    pub fn save(&self, dir: &Path) -> Result<PathBuf> {
+        let slug: String = self
+            .description
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{:04}_{}.json", self.version, slug.trim_matches('_')));
+
+        let file = serde_json::json!({
+            "description": self.description,
+            "up": self.actions,
+            "down": self.down,
+        });
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(&path, serde_json::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Checksum of the up and down actions
+    pub fn compute_checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&self.actions).unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(&self.down).unwrap_or_default());
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Whether the migration can be reverted
+    pub fn is_reversible(&self) -> bool {
+        self.actions.is_empty() || !self.down.is_empty()
     }
 
     /// Generate SQL for this migration
@@ -48,8 +168,24 @@ impl Migration {
     }
 }
 
+/// A migration file as written by hand
+#[derive(Deserialize)]
+struct MigrationFile {
+    #[serde(default)]
+    description: Option<String>,
+    up: Vec<MigrationAction>,
+    #[serde(default)]
+    down: Option<Vec<MigrationAction>>,
+}
+
+/// Undo every action in reverse order, if all of them can be undone
+fn reverse_all(actions: &[MigrationAction]) -> Option<Vec<MigrationAction>> {
+    actions.iter().rev().map(MigrationAction::reverse).collect()
+}
+
 /// A single migration action
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
 pub enum MigrationAction {
     /// Create a new schema/table
     CreateSchema(Schema),
@@ -102,10 +238,53 @@ pub enum MigrationAction {
         field: String,
     },
 
+    /// Add a check expression; fails if stored nodes break it
+    AddCheck {
+        schema: String,
+        expression: String,
+    },
+
+    /// Remove a check expression
+    RemoveCheck {
+        schema: String,
+        expression: String,
+    },
+
+    /// Rename a property on every node of a type, and the field if the type
+    /// has a schema
+    RenameProperty {
+        node_type: String,
+        old_name: String,
+        new_name: String,
+    },
+
+    /// Convert a property on every node of a type to another type, and the
+    /// field if the type has a schema
+    ConvertProperty {
+        node_type: String,
+        field: String,
+        to: FieldType,
+        #[serde(default)]
+        on_error: ConversionPolicy,
+    },
+
     /// Raw SQL for custom migrations
     RawSql(String),
 }
 
+/// What a type conversion does with values that have no faithful
+/// conversion, such as `"abc"` to an int
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConversionPolicy {
+    /// Fail the migration before any node is changed
+    #[default]
+    Fail,
+    /// Set the value to null
+    Null,
+    /// Leave the value as it is
+    Keep,
+}
+
 impl MigrationAction {
     /// Generate SQL for this action
     pub fn to_sql(&self) -> String {
@@ -171,15 +350,117 @@ impl MigrationAction {
                 format!("DROP INDEX IF EXISTS {}_{}_idx", schema, field)
             }
 
+            MigrationAction::AddCheck { schema, expression } => {
+                format!("ALTER TABLE {} ADD CHECK ({})", schema, expression)
+            }
+
+            MigrationAction::RemoveCheck { schema, expression } => {
+                format!("ALTER TABLE {} DROP CHECK ({})", schema, expression)
+            }
+
+            MigrationAction::RenameProperty { node_type, old_name, new_name } => {
+                format!(
+                    "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                    node_type, old_name, new_name
+                )
+            }
+
+            MigrationAction::ConvertProperty { node_type, field, to, .. } => {
+                format!(
+                    "ALTER TABLE {} ALTER COLUMN {} TYPE {}",
+                    node_type,
+                    field,
+                    to.to_sql()
+                )
+            }
+
             MigrationAction::RawSql(sql) => sql.clone(),
         }
     }
+
+    /// The action that undoes this one, if it can be built from the action
+    /// alone; dropped schemas and fields need an explicit down action
+    pub fn reverse(&self) -> Option<MigrationAction> {
+        let reversed = match self {
+            MigrationAction::CreateSchema(schema) => MigrationAction::DropSchema(schema.name.clone()),
+            MigrationAction::AddField { schema, field } => MigrationAction::RemoveField {
+                schema: schema.clone(),
+                field_name: field.name.clone(),
+            },
+            MigrationAction::ModifyField { schema, old_field, new_field } => MigrationAction::ModifyField {
+                schema: schema.clone(),
+                old_field: new_field.clone(),
+                new_field: old_field.clone(),
+            },
+            MigrationAction::RenameField { schema, old_name, new_name } => MigrationAction::RenameField {
+                schema: schema.clone(),
+                old_name: new_name.clone(),
+                new_name: old_name.clone(),
+            },
+            MigrationAction::RenameSchema { old_name, new_name } => MigrationAction::RenameSchema {
+                old_name: new_name.clone(),
+                new_name: old_name.clone(),
+            },
+            MigrationAction::AddIndex { schema, field, .. } => MigrationAction::RemoveIndex {
+                schema: schema.clone(),
+                field: field.clone(),
+            },
+            MigrationAction::AddCheck { schema, expression } => MigrationAction::RemoveCheck {
+                schema: schema.clone(),
+                expression: expression.clone(),
+            },
+            MigrationAction::RemoveCheck { schema, expression } => MigrationAction::AddCheck {
+                schema: schema.clone(),
+                expression: expression.clone(),
+            },
+            MigrationAction::RenameProperty { node_type, old_name, new_name } => MigrationAction::RenameProperty {
+                node_type: node_type.clone(),
+                old_name: new_name.clone(),
+                new_name: old_name.clone(),
+            },
+            MigrationAction::DropSchema(_)
+            | MigrationAction::RemoveField { .. }
+            | MigrationAction::RemoveIndex { .. }
+            | MigrationAction::ConvertProperty { .. }
+            | MigrationAction::RawSql(_) => return None,
+        };
+        Some(reversed)
+    }
 }
 
 /// Migration generator - compares schemas and generates migrations
 pub struct MigrationGenerator;
 
 impl MigrationGenerator {
+    /// Steps that turn the `current` catalog into the `desired` one
+    ///
+    /// Schemas are matched by name, so a renamed schema shows up as a drop
+    /// and a create. New schemas come first and dropped ones last.
+    pub fn diff(current: &[Schema], desired: &[Schema]) -> Vec<MigrationAction> {
+        let find = |schemas: &[Schema], name: &str| schemas.iter().find(|s| s.name == name).cloned();
+        let mut actions = Vec::new();
+
+        for schema in desired {
+            if find(current, &schema.name).is_none() {
+                actions.push(MigrationAction::CreateSchema(schema.clone()));
+            }
+        }
+
+        for schema in desired {
+            if let Some(old) = find(current, &schema.name) {
+                actions.extend(Self::generate(&old, schema));
+            }
+        }
+
+        for schema in current {
+            if find(desired, &schema.name).is_none() {
+                actions.push(MigrationAction::DropSchema(schema.name.clone()));
+            }
+        }
+
+        actions
+    }
+
     /// Generate migrations to transform old schema into new schema
     pub fn generate(old: &Schema, new: &Schema) -> Vec<MigrationAction> {
         let mut actions = Vec::new();
@@ -199,6 +480,13 @@ impl MigrationGenerator {
                     schema: new.name.clone(),
                     field: new_field.clone(),
                 });
+                if new_field.indexed {
+                    actions.push(MigrationAction::AddIndex {
+                        schema: new.name.clone(),
+                        field: new_field.name.clone(),
+                        unique: new_field.unique,
+                    });
+                }
             }
         }
 
@@ -239,6 +527,20 @@ impl MigrationGenerator {
             }
         }
 
+        // Find changed checks
+        for check in old.checks.iter().filter(|c| !new.checks.contains(c)) {
+            actions.push(MigrationAction::RemoveCheck {
+                schema: new.name.clone(),
+                expression: check.clone(),
+            });
+        }
+        for check in new.checks.iter().filter(|c| !old.checks.contains(c)) {
+            actions.push(MigrationAction::AddCheck {
+                schema: new.name.clone(),
+                expression: check.clone(),
+            });
+        }
+
         actions
     }
 
@@ -247,7 +549,11 @@ impl MigrationGenerator {
         old.field_type != new.field_type
             || old.nullable != new.nullable
             || old.unique != new.unique
+            || old.primary_key != new.primary_key
             || old.default != new.default
+            || old.min != new.min
+            || old.max != new.max
+            || old.pattern != new.pattern
     }
 }
 
@@ -288,6 +594,57 @@ mod tests {
         let sql = action.to_sql();
         assert!(sql.contains("ALTER TABLE users ADD COLUMN age BIGINT NOT NULL"));
     }
+
+    #[test]
+    fn test_diff() {
+        let current = vec![
+            Schema::new("users", vec![SchemaField::new("name", FieldType::String)]),
+            Schema::new("legacy", vec![]),
+        ];
+        let desired = vec![
+            Schema::new("users", vec![
+                SchemaField::new("name", FieldType::String).nullable(false),
+                SchemaField::new("email", FieldType::String).indexed(true),
+            ]),
+            Schema::new("orders", vec![SchemaField::new("total", FieldType::Float)]),
+        ];
+
+        let sql: Vec<String> = MigrationGenerator::diff(&current, &desired).iter().map(|a| a.to_sql()).collect();
+        assert!(sql[0].starts_with("CREATE TABLE orders"));
+        assert_eq!(sql[1], "ALTER TABLE users ADD COLUMN email TEXT");
+        assert_eq!(sql[2], "CREATE INDEX users_email_idx ON users (email)");
+        assert_eq!(sql[3], "ALTER TABLE users ALTER COLUMN name TYPE TEXT");
+        assert_eq!(sql[4], "DROP TABLE IF EXISTS legacy");
+        assert_eq!(sql.len(), 5);
+
+        assert!(MigrationGenerator::diff(&desired, &desired).is_empty());
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0002_add_age.json");
+        std::fs::write(
+            &path,
+            r#"{"up": [{"AddField": {"schema": "users", "field": {"name": "age", "field_type": "Int"}}}]}"#,
+        )
+        .unwrap();
+
+        let migration = Migration::load_file(&path).unwrap();
+        assert_eq!(migration.version, 2);
+        assert_eq!(migration.description, "add age");
+        assert!(migration.is_reversible());
+        assert!(matches!(
+            &migration.down[..],
+            [MigrationAction::RemoveField { field_name, .. }] if field_name == "age"
+        ));
+        assert_eq!(migration.checksum, Migration::load_file(&path).unwrap().checksum);
+
+        std::fs::write(dir.path().join("0003_drop.json"), r#"{"up": [{"DropSchema": "users"}]}"#).unwrap();
+        let migrations = Migration::load_dir(dir.path()).unwrap();
+        assert_eq!(migrations.iter().map(|m| m.version).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(!migrations[1].is_reversible());
+    }
 }
 
 
@@ -4,42 +4,75 @@
 
 mod registry;
 mod migration;
+mod export;
+mod seed;
+mod source;
 
 pub use registry::{Schema, SchemaField, FieldType, SchemaRelation, RelationType};
-pub use migration::{Migration, MigrationAction, MigrationGenerator};
+pub use migration::{ConversionPolicy, Migration, MigrationAction, MigrationGenerator};
+pub use export::{to_ddl, to_json_schema, to_markdown};
+pub use seed::{GraphSpec, SeedOptions, SeedStats, preset_fields, seed};
+pub use source::{ForeignKey, MigrateStats, SourceKind, SourceOptions, SourceSchema, SourceTable, introspect, migrate_from};
 
-use anyhow::Result;
-use crate::storage::Database;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::error::{CodedError, ErrorCode};
+use crate::progress::{self, Progress, ProgressUnit};
+use crate::storage::{Database, FieldIndexKind, Timestamp, Value};
+
+/// Node type recording applied migrations
+const MIGRATIONS_TYPE: &str = "_migrations";
+
+/// Nodes rewritten per transaction by data migrations
+const REWRITE_BATCH: usize = 1000;
 
 /// Schema manager for database
 pub struct SchemaManager {
-    db: Database,
+    db: Arc<Database>,
 }
 
 impl SchemaManager {
     /// Create a new schema manager
     pub fn new(db: Database) -> Self {
+        Self::from_shared(Arc::new(db))
+    }
+
+    /// Create a schema manager over a database shared with other components
+    pub fn from_shared(db: Arc<Database>) -> Self {
         Self { db }
     }
 
     /// Create a new schema
     pub async fn create_schema(&self, name: &str, fields_str: &str) -> Result<Schema> {
+        self.create_schema_with_checks(name, fields_str, &[]).await
+    }
+
+    /// Create a new schema whose nodes must satisfy check expressions
+    pub async fn create_schema_with_checks(&self, name: &str, fields_str: &str, checks: &[String]) -> Result<Schema> {
         let fields = Self::parse_fields(fields_str)?;
-        let schema = Schema::new(name, fields);
+        let mut schema = Schema::new(name, fields);
+        for check in checks {
+            schema.add_check(check)?;
+        }
+        schema.check()?;
 
         // Store schema in database metadata
         self.save_schema(&schema).await?;
+        self.index_fields(&schema, &schema.fields).await?;
 
         Ok(schema)
     }
 
-    /// Create a relationship between schemas
+    /// Create a relationship between schemas; strict relationships reject
+    /// edges beyond their cardinality
     pub async fn create_relationship(
         &self,
         from: &str,
         to: &str,
         relation_type: &str,
         alias: Option<&str>,
+        strict: bool,
     ) -> Result<SchemaRelation> {
         let rel_type = match relation_type.to_lowercase().as_str() {
             "has_one" | "hasone" => RelationType::HasOne,
@@ -55,6 +88,7 @@ impl SchemaManager {
             relation_type: rel_type,
             alias: alias.map(|s| s.to_string()),
             edge_type: format!("{}_{}", from, to),
+            strict,
         };
 
         self.save_relation(&relation).await?;
@@ -62,11 +96,37 @@ impl SchemaManager {
         Ok(relation)
     }
 
+    /// Register a schema built elsewhere, replacing any with the same name
+    pub async fn import_schema(&self, schema: &Schema) -> Result<()> {
+        schema.check()?;
+        self.save_schema(schema).await
+    }
+
+    /// Register a relationship built elsewhere, unless the same edge type
+    /// already links the same schemas
+    pub async fn import_relation(&self, relation: &SchemaRelation) -> Result<()> {
+        let registered = self.list_relations().await?.iter().any(|r| {
+            r.from_schema == relation.from_schema
+                && r.to_schema == relation.to_schema
+                && r.edge_type == relation.edge_type
+        });
+        if !registered {
+            self.save_relation(relation).await?;
+        }
+        Ok(())
+    }
+
     /// List all schemas
     pub async fn list_schemas(&self) -> Result<Vec<Schema>> {
         self.load_schemas().await
     }
 
+    /// List all relationships between schemas
+    pub async fn list_relations(&self) -> Result<Vec<SchemaRelation>> {
+        let nodes = self.db.get_all_by_type(SchemaRelation::NODE_TYPE, None).await?;
+        Ok(nodes.iter().filter_map(SchemaRelation::from_node).collect())
+    }
+
     /// Get a schema by name
     pub async fn get_schema(&self, name: &str) -> Result<Schema> {
         let schemas = self.load_schemas().await?;
@@ -90,14 +150,132 @@ impl SchemaManager {
         Ok(())
     }
 
-    /// Run pending migrations
+    /// Directory holding the database's migration files
+    pub fn migrations_dir(&self) -> PathBuf {
+        self.db.path().join("migrations")
+    }
+
+    /// Run pending migrations from the database's migrations directory
     pub async fn run_migrations(&self) -> Result<Vec<Migration>> {
-        let migrations = self.detect_migrations().await?;
+        let migrations = Migration::load_dir(&self.migrations_dir())?;
+        self.migrate(&migrations).await
+    }
+
+    /// Apply the migrations that have not been applied yet, in version order
+    ///
+    /// Fails before changing anything if an applied migration was edited
+    /// since it ran.
+    pub async fn migrate(&self, migrations: &[Migration]) -> Result<Vec<Migration>> {
+        self.migrate_with_progress(migrations, progress::ignore).await
+    }
+
+    /// Apply pending migrations, reporting nodes rewritten by data
+    /// migrations
+    pub async fn migrate_with_progress<F>(&self, migrations: &[Migration], mut on_progress: F) -> Result<Vec<Migration>>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let applied = self.applied_migrations().await?;
+        for migration in migrations {
+            if let Some(done) = applied.iter().find(|m| m.version == migration.version) {
+                if done.checksum != migration.checksum {
+                    return Err(CodedError::conflict(format!(
+                        "Migration {} ({}) was changed after it was applied: checksum {} does not match {}",
+                        migration.version, migration.description, migration.checksum, done.checksum
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        let mut pending: Vec<&Migration> = migrations
+            .iter()
+            .filter(|m| !applied.iter().any(|a| a.version == m.version))
+            .collect();
+        pending.sort_by_key(|m| m.version);
+
+        let mut ran = Vec::new();
+        for migration in pending {
+            for action in &migration.actions {
+                self.apply_action(action, &mut on_progress)
+                    .await
+                    .with_context(|| format!("Migration {} ({}) failed", migration.version, migration.description))?;
+            }
+            ran.push(self.record_migration(migration).await?);
+        }
+
+        Ok(ran)
+    }
+
+    /// Steps that bring the catalog in line with declared schemas
+    pub async fn diff(&self, desired: &[Schema]) -> Result<Vec<MigrationAction>> {
+        Ok(MigrationGenerator::diff(&self.list_schemas().await?, desired))
+    }
 
-        for migration in &migrations {
-            self.apply_migration(migration).await?;
+    /// A migration from the catalog to declared schemas, numbered after the
+    /// applied migrations and those in `dir`
+    ///
+    /// The down actions are the diff the other way, so dropped schemas and
+    /// fields come back with their full definitions.
+    pub async fn plan_migration(&self, desired: &[Schema], description: &str, dir: &std::path::Path) -> Result<Migration> {
+        let current = self.list_schemas().await?;
+        let up = MigrationGenerator::diff(&current, desired);
+        let down = MigrationGenerator::diff(desired, &current);
+
+        let version = self
+            .applied_migrations()
+            .await?
+            .iter()
+            .chain(Migration::load_dir(dir)?.iter())
+            .map(|m| m.version)
+            .max()
+            .unwrap_or(0)
+            + 1;
+        Ok(Migration::versioned(version, description, up, down))
+    }
+
+    /// Revert the last `steps` applied migrations, newest first
+    pub async fn revert_migrations(&self, steps: usize) -> Result<Vec<Migration>> {
+        let mut applied = self.applied_migrations().await?;
+        applied.reverse();
+        applied.truncate(steps);
+
+        // Check everything up front so a revert does not stop halfway
+        if let Some(stuck) = applied.iter().find(|m| !m.is_reversible()) {
+            anyhow::bail!(
+                "Migration {} ({}) cannot be reverted: it has no down actions",
+                stuck.version,
+                stuck.description
+            );
         }
 
+        for migration in &applied {
+            for action in &migration.down {
+                self.apply_action(action, &mut progress::ignore)
+                    .await
+                    .with_context(|| format!("Reverting migration {} ({}) failed", migration.version, migration.description))?;
+            }
+            self.forget_migration(migration.version).await?;
+        }
+
+        Ok(applied)
+    }
+
+    /// Migrations recorded as applied, oldest version first
+    pub async fn applied_migrations(&self) -> Result<Vec<Migration>> {
+        let nodes = self.db.get_all_by_type(MIGRATIONS_TYPE, None).await?;
+        let mut migrations = Vec::new();
+
+        for node in nodes {
+            if let Some(crate::storage::Value::Object(data)) = node.properties.get("migration_data") {
+                let json = crate::storage::Value::Object(data.clone()).to_json();
+                if let Ok(migration) = serde_json::from_value::<Migration>(json) {
+                    migrations.push(migration);
+                }
+            }
+        }
+
+        migrations.sort_by_key(|m| m.version);
         Ok(migrations)
     }
 
@@ -105,30 +283,42 @@ impl SchemaManager {
     fn parse_fields(fields_str: &str) -> Result<Vec<SchemaField>> {
         let mut fields = Vec::new();
 
-        for field_def in fields_str.split(',') {
+        for field_def in split_top_level(fields_str, |c| c == ',') {
             let field_def = field_def.trim();
             if field_def.is_empty() {
                 continue;
             }
 
-            let parts: Vec<&str> = field_def.split(':').collect();
-            if parts.is_empty() {
-                continue;
-            }
-
-            let name = parts[0].trim().to_string();
-            let field_type = parts.get(1)
-                .map(|t| FieldType::parse(t.trim()))
-                .unwrap_or(FieldType::String);
+            // `name:type:modifier` or `name:type modifier ...`
+            let name_end = field_def.find(|c: char| c == ':' || c.is_whitespace()).unwrap_or(field_def.len());
+            let name = field_def[..name_end].to_string();
+            let mut parts = split_top_level(&field_def[name_end..], |c| c == ':' || c.is_whitespace())
+                .into_iter()
+                .filter(|p| !p.is_empty())
+                .peekable();
+            let field_type = match parts.peek() {
+                Some(_) if field_def[name_end..].starts_with(':') => FieldType::parse(parts.next().unwrap_or_default()),
+                _ => FieldType::String,
+            };
 
             let mut field = SchemaField::new(&name, field_type);
 
             // Check for modifiers
-            for part in parts.iter().skip(2) {
-                match part.trim().to_lowercase().as_str() {
+            for part in parts {
+                if let Some((key, value)) = part.split_once('=') {
+                    match key.trim().to_lowercase().as_str() {
+                        "min" => field.min = Some(Self::parse_bound(&name, value)?),
+                        "max" => field.max = Some(Self::parse_bound(&name, value)?),
+                        "pattern" | "regex" => field = field.pattern(value.trim()),
+                        other => anyhow::bail!("Unknown constraint '{}' on field {}", other, name),
+                    }
+                    continue;
+                }
+                match part.to_lowercase().as_str() {
                     "unique" => field.unique = true,
                     "required" | "notnull" => field.nullable = false,
                     "indexed" | "index" => field.indexed = true,
+                    "primary" | "primary_key" | "pk" => field = field.primary_key(true),
                     _ => {}
                 }
             }
@@ -139,6 +329,13 @@ impl SchemaManager {
         Ok(fields)
     }
 
+    fn parse_bound(field: &str, value: &str) -> Result<f64> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid bound '{}' on field {}: expected a number", value.trim(), field))
+    }
+
     // Internal methods for schema persistence
 
     async fn save_schema(&self, schema: &Schema) -> Result<()> {
@@ -173,51 +370,545 @@ impl SchemaManager {
             "relation_data": relation_json,
         });
 
-        self.db.insert_node("__relation__", props).await?;
+        self.db.insert_node(SchemaRelation::NODE_TYPE, props).await?;
         Ok(())
     }
 
     async fn load_schemas(&self) -> Result<Vec<Schema>> {
+        let nodes = self.db.get_all_by_type(Schema::NODE_TYPE, None).await?;
+        Ok(nodes.iter().filter_map(Schema::from_node).collect())
+    }
+
+    async fn remove_schema(&self, name: &str) -> Result<()> {
         let nodes = self.db.get_all_by_type("__schema__", None).await?;
-        let mut schemas = Vec::new();
 
         for node in nodes {
-            if let Some(crate::storage::Value::Object(data)) = node.properties.get("schema_data") {
-                let json = crate::storage::Value::Object(data.clone()).to_json();
-                if let Ok(schema) = serde_json::from_value::<Schema>(json) {
-                    schemas.push(schema);
+            if let Some(crate::storage::Value::String(n)) = node.properties.get("name") {
+                if n == name {
+                    self.db.delete_node(&node.id.to_string()).await?;
+                    return Ok(());
                 }
             }
         }
 
-        Ok(schemas)
+        Ok(())
     }
 
-    async fn remove_schema(&self, name: &str) -> Result<()> {
-        let nodes = self.db.get_all_by_type("__schema__", None).await?;
+    /// Build the property indexes behind indexed and unique fields, and
+    /// fix the dimensions of vector fields
+    async fn index_fields(&self, schema: &Schema, fields: &[SchemaField]) -> Result<()> {
+        for field in fields.iter().filter(|f| f.indexed || f.unique) {
+            self.db.create_field_index(&schema.name, &field.name, FieldIndexKind::Property, |_| {}).await?;
+        }
+        for field in fields {
+            if let FieldType::Vector(dim) = field.field_type {
+                self.db.set_vector_dimension(&schema.name, &field.name, dim)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Save a changed schema as its next version
+    async fn update_schema(&self, mut schema: Schema) -> Result<()> {
+        schema.version += 1;
+        schema.updated_at = chrono::Utc::now().timestamp_millis();
+        self.save_schema(&schema).await
+    }
+
+    async fn apply_action(&self, action: &MigrationAction, on_progress: &mut (dyn FnMut(Progress) + Send)) -> Result<()> {
+        match action {
+            MigrationAction::CreateSchema(schema) => {
+                if self.get_schema(&schema.name).await.is_ok() {
+                    return Err(CodedError::conflict(format!("Schema already exists: {}", schema.name)).into());
+                }
+                let mut schema = schema.clone();
+                if schema.created_at == 0 {
+                    schema.created_at = chrono::Utc::now().timestamp_millis();
+                    schema.updated_at = schema.created_at;
+                }
+                self.save_schema(&schema).await?;
+                self.index_fields(&schema, &schema.fields).await
+            }
+            MigrationAction::DropSchema(name) => {
+                self.get_schema(name).await?;
+                self.remove_schema(name).await
+            }
+            MigrationAction::AddField { schema, field } => {
+                let mut current = self.get_schema(schema).await?;
+                if current.get_field(&field.name).is_some() {
+                    return Err(CodedError::conflict(format!("Field already exists: {}.{}", schema, field.name)).into());
+                }
+                current.fields.push(field.clone());
+                self.update_schema(current.clone()).await?;
+                self.index_fields(&current, std::slice::from_ref(field)).await
+            }
+            MigrationAction::RemoveField { schema, field_name } => {
+                let mut current = self.get_schema(schema).await?;
+                let before = current.fields.len();
+                current.fields.retain(|f| &f.name != field_name);
+                if current.fields.len() == before {
+                    return Err(CodedError::not_found(format!("Field not found: {}.{}", schema, field_name)).into());
+                }
+                self.update_schema(current).await
+            }
+            MigrationAction::ModifyField { schema, old_field, new_field } => {
+                let mut current = self.get_schema(schema).await?;
+                let field = current
+                    .fields
+                    .iter_mut()
+                    .find(|f| f.name == old_field.name)
+                    .ok_or_else(|| CodedError::not_found(format!("Field not found: {}.{}", schema, old_field.name)))?;
+                *field = new_field.clone();
+                self.update_schema(current).await
+            }
+            MigrationAction::RenameField { schema, old_name, new_name } => {
+                let mut current = self.get_schema(schema).await?;
+                if current.get_field(new_name).is_some() {
+                    return Err(CodedError::conflict(format!("Field already exists: {}.{}", schema, new_name)).into());
+                }
+                let field = current
+                    .fields
+                    .iter_mut()
+                    .find(|f| &f.name == old_name)
+                    .ok_or_else(|| CodedError::not_found(format!("Field not found: {}.{}", schema, old_name)))?;
+                field.name = new_name.clone();
+                self.update_schema(current).await
+            }
+            MigrationAction::RenameSchema { old_name, new_name } => {
+                let mut current = self.get_schema(old_name).await?;
+                if self.get_schema(new_name).await.is_ok() {
+                    return Err(CodedError::conflict(format!("Schema already exists: {}", new_name)).into());
+                }
+                self.remove_schema(old_name).await?;
+                current.name = new_name.clone();
+                self.update_schema(current).await
+            }
+            MigrationAction::AddIndex { schema, field, unique } => {
+                let mut current = self.get_schema(schema).await?;
+                let entry = current
+                    .fields
+                    .iter_mut()
+                    .find(|f| &f.name == field)
+                    .ok_or_else(|| CodedError::not_found(format!("Field not found: {}.{}", schema, field)))?;
+                entry.indexed = true;
+                entry.unique = *unique;
+                self.update_schema(current).await?;
+                self.db.create_field_index(schema, field, FieldIndexKind::Property, |_| {}).await?;
+                Ok(())
+            }
+            MigrationAction::RemoveIndex { schema, field } => {
+                let mut current = self.get_schema(schema).await?;
+                if let Some(entry) = current.fields.iter_mut().find(|f| &f.name == field) {
+                    entry.indexed = false;
+                    entry.unique = false;
+                }
+                self.update_schema(current).await?;
+                self.db.drop_field_index(schema, field)?;
+                Ok(())
+            }
+            MigrationAction::AddCheck { schema, expression } => {
+                let mut current = self.get_schema(schema).await?;
+                if current.checks.iter().any(|c| c == expression.trim()) {
+                    return Err(CodedError::conflict(format!("Check already exists on {}: {}", schema, expression)).into());
+                }
+                current.add_check(expression)?;
+
+                let mut failing = Vec::new();
+                self.db.scan_by_type(schema, |node| {
+                    if !current.failed_checks(&node.properties).is_empty() {
+                        failing.push(node.id.to_string());
+                    }
+                    Ok(true)
+                })?;
+                if !failing.is_empty() {
+                    return Err(CodedError::new(
+                        ErrorCode::ConstraintViolation,
+                        format!(
+                            "{} nodes of {} break the check {} (e.g. {})",
+                            failing.len(),
+                            schema,
+                            expression,
+                            failing.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
+                        ),
+                    )
+                    .into());
+                }
+                self.update_schema(current).await
+            }
+            MigrationAction::RemoveCheck { schema, expression } => {
+                let mut current = self.get_schema(schema).await?;
+                if !current.remove_check(expression) {
+                    return Err(CodedError::not_found(format!("Check not found on {}: {}", schema, expression)).into());
+                }
+                self.update_schema(current).await
+            }
+            MigrationAction::RenameProperty { node_type, old_name, new_name } => {
+                if let Ok(mut current) = self.get_schema(node_type).await {
+                    if current.get_field(new_name).is_some() {
+                        return Err(CodedError::conflict(format!("Field already exists: {}.{}", node_type, new_name)).into());
+                    }
+                    if let Some(entry) = current.fields.iter_mut().find(|f| &f.name == old_name) {
+                        entry.name = new_name.clone();
+                        self.update_schema(current).await?;
+                    }
+                }
+
+                self.rewrite_nodes(node_type, on_progress, |properties| match properties.remove(old_name) {
+                    Some(value) => {
+                        properties.insert(new_name.clone(), value);
+                        true
+                    }
+                    None => false,
+                })
+                .await?;
+
+                // Move any index to the new name, built from the rewritten nodes
+                let index = self.db.field_indexes().into_iter().find(|s| &s.node_type == node_type && &s.field == old_name);
+                if let Some(spec) = index {
+                    self.db.drop_field_index(node_type, old_name)?;
+                    self.db.create_field_index(node_type, new_name, spec.kind, |_| {}).await?;
+                }
+                Ok(())
+            }
+            MigrationAction::ConvertProperty { node_type, field, to, on_error } => {
+                // Find values that do not convert before changing anything
+                if *on_error == ConversionPolicy::Fail {
+                    let mut failures = Vec::new();
+                    self.db.scan_by_type(node_type, |node| {
+                        if let Some(value) = node.properties.get(field) {
+                            if to.convert(value).is_none() {
+                                failures.push(format!("{} has {}", node.id, value.to_json()));
+                            }
+                        }
+                        Ok(true)
+                    })?;
+                    if !failures.is_empty() {
+                        return Err(CodedError::new(
+                            ErrorCode::ConstraintViolation,
+                            format!(
+                                "{} values of {}.{} cannot be converted to {} (e.g. {})",
+                                failures.len(),
+                                node_type,
+                                field,
+                                to,
+                                failures.iter().take(3).cloned().collect::<Vec<_>>().join("; ")
+                            ),
+                        )
+                        .into());
+                    }
+                }
+
+                if let Ok(mut current) = self.get_schema(node_type).await {
+                    if let Some(entry) = current.fields.iter_mut().find(|f| &f.name == field) {
+                        entry.field_type = to.clone();
+                        self.update_schema(current).await?;
+                    }
+                }
+
+                self.rewrite_nodes(node_type, on_progress, |properties| {
+                    let Some(value) = properties.get(field) else { return false };
+                    let converted = match (to.convert(value), on_error) {
+                        (Some(converted), _) => converted,
+                        (None, ConversionPolicy::Null) => Value::Null,
+                        (None, _) => return false,
+                    };
+                    if &converted == value {
+                        return false;
+                    }
+                    properties.insert(field.clone(), converted);
+                    true
+                })
+                .await?;
+                Ok(())
+            }
+            MigrationAction::RawSql(sql) => {
+                let engine = crate::query::QueryEngine::from_shared(self.db.clone());
+                engine.execute_sql(sql, None).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Rewrite the properties of every node of a type, in batches of
+    /// [`REWRITE_BATCH`] nodes written in one transaction each; `rewrite`
+    /// returns whether it changed a node. Returns the nodes changed.
+    ///
+    /// Batches already written stay written if a later one fails.
+    async fn rewrite_nodes<F>(
+        &self,
+        node_type: &str,
+        on_progress: &mut (dyn FnMut(Progress) + Send),
+        mut rewrite: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&mut std::collections::BTreeMap<String, Value>) -> bool,
+    {
+        let nodes = self.db.get_all_by_type(node_type, None).await?;
+        let total = Some(nodes.len() as u64);
+        let mut visited = 0;
+        let mut changed = 0;
+        on_progress(Progress::new("migrate", ProgressUnit::Nodes, 0, total));
+
+        for batch in nodes.chunks(REWRITE_BATCH) {
+            let mut old = Vec::new();
+            let mut new = Vec::new();
+            for node in batch {
+                let mut updated = node.clone();
+                if rewrite(&mut updated.properties) {
+                    updated.updated_at = Timestamp::now();
+                    old.push(node.clone());
+                    new.push(updated);
+                }
+            }
+            changed += new.len();
+            if !new.is_empty() {
+                self.db.replace_nodes(old, new).await?;
+            }
+            visited += batch.len();
+            on_progress(Progress::new("migrate", ProgressUnit::Nodes, visited as u64, total));
+        }
+        Ok(changed)
+    }
+
+    async fn record_migration(&self, migration: &Migration) -> Result<Migration> {
+        let mut applied = migration.clone();
+        applied.applied = true;
+        applied.applied_at = Some(chrono::Utc::now().timestamp_millis());
+
+        let props = serde_json::json!({
+            "version": applied.version,
+            "description": applied.description,
+            "checksum": applied.checksum,
+            "applied_at": applied.applied_at,
+            "migration_data": serde_json::to_value(&applied)?,
+        });
+        self.db.insert_node(MIGRATIONS_TYPE, props).await?;
+        Ok(applied)
+    }
+
+    async fn forget_migration(&self, version: u32) -> Result<()> {
+        let nodes = self.db.get_all_by_type(MIGRATIONS_TYPE, None).await?;
 
         for node in nodes {
-            if let Some(crate::storage::Value::String(n)) = node.properties.get("name") {
-                if n == name {
+            if let Some(crate::storage::Value::Int(v)) = node.properties.get("version") {
+                if *v == version as i64 {
                     self.db.delete_node(&node.id.to_string()).await?;
-                    return Ok(());
                 }
             }
         }
 
         Ok(())
     }
+}
 
-    async fn detect_migrations(&self) -> Result<Vec<Migration>> {
-        // Compare current schemas with stored schemas
-        // For now, return empty - full implementation would diff schemas
-        Ok(Vec::new())
+/// Split on separators outside `enum(...)` and `array<...>`
+fn split_top_level(text: &str, is_separator: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '<' => depth += 1,
+            ')' | '>' => depth = depth.saturating_sub(1),
+            c if depth == 0 && is_separator(c) => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
     }
+    parts.push(&text[start..]);
+    parts
+}
 
-    async fn apply_migration(&self, _migration: &Migration) -> Result<()> {
-        // Apply migration changes
-        // Full implementation would execute migration actions
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fields() {
+        let fields = SchemaManager::parse_fields(
+            "name:string:required, status:enum(pending, active), tags:array<string>, email:string unique, id:int primary key",
+        )
+        .unwrap();
+        assert_eq!(fields.len(), 5);
+        assert!(!fields[0].nullable);
+        assert_eq!(fields[1].field_type, FieldType::Enum(vec!["pending".to_string(), "active".to_string()]));
+        assert_eq!(fields[2].field_type, FieldType::Array(Box::new(FieldType::String)));
+        assert!(fields[3].unique && fields[3].field_type == FieldType::String);
+        assert!(fields[4].primary_key && fields[4].unique && !fields[4].nullable);
+    }
+
+    #[tokio::test]
+    async fn test_strict_relationship_cardinality() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_relationship("users", "profiles", "has_one", None, true).await.unwrap();
+        manager.create_relationship("users", "posts", "has_many", None, false).await.unwrap();
+
+        let node = |t: &'static str| db.insert_node(t, serde_json::json!({}));
+        let (alice, bob) = (node("users").await.unwrap().id.to_string(), node("users").await.unwrap().id.to_string());
+        let (first, second) = (node("profiles").await.unwrap().id.to_string(), node("profiles").await.unwrap().id.to_string());
+
+        db.create_edge(&alice, &first, "users_profiles", None).await.unwrap();
+        let error = db.create_edge(&alice, &second, "users_profiles", None).await.unwrap_err();
+        assert_eq!(crate::error::classify(&error), crate::error::ErrorCode::Conflict);
+        assert!(db.create_edge(&bob, &first, "users_profiles", None).await.is_err());
+        db.create_edge(&bob, &second, "users_profiles", None).await.unwrap();
+
+        // Relationships without --strict are documentation only
+        let post = node("posts").await.unwrap().id.to_string();
+        db.create_edge(&alice, &post, "users_posts", None).await.unwrap();
+        db.create_edge(&bob, &post, "users_posts", None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_strict_relationship_race() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_relationship("users", "profiles", "has_one", None, true).await.unwrap();
+        let alice = db.insert_node("users", serde_json::json!({})).await.unwrap().id.to_string();
+        let mut profiles = Vec::new();
+        for _ in 0..8 {
+            profiles.push(db.insert_node("profiles", serde_json::json!({})).await.unwrap().id.to_string());
+        }
+
+        // A flush window puts the racing writers into the same group commit
+        db.local().set_flush_window(std::time::Duration::from_millis(20));
+        let edges = profiles.into_iter().map(|profile| {
+            let (db, alice) = (Arc::clone(&db), alice.clone());
+            tokio::spawn(async move { db.create_edge(&alice, &profile, "users_profiles", None).await })
+        });
+
+        let results = futures::future::join_all(edges).await;
+        let created = results.into_iter().filter(|r| r.as_ref().unwrap().is_ok()).count();
+        assert_eq!(created, 1);
+        assert_eq!(db.get_edges_from(&alice, Some("users_profiles")).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_data_migrations() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_schema("users", "name:string, age:string").await.unwrap();
+        let ok = db.insert_node("users", serde_json::json!({"name": "Ada", "age": "36"})).await.unwrap();
+        let bad = db.insert_node("users", serde_json::json!({"name": "Bob", "age": "old"})).await.unwrap();
+
+        let rename = MigrationAction::RenameProperty {
+            node_type: "users".to_string(),
+            old_name: "name".to_string(),
+            new_name: "full_name".to_string(),
+        };
+        let convert = |on_error| MigrationAction::ConvertProperty {
+            node_type: "users".to_string(),
+            field: "age".to_string(),
+            to: FieldType::Int,
+            on_error,
+        };
+        let mut events = Vec::new();
+        manager
+            .migrate_with_progress(&[Migration::versioned(1, "rename", vec![rename], Vec::new())], |p| events.push(p))
+            .await
+            .unwrap();
+        assert_eq!(events.last().map(|p| (p.done, p.total)), Some((2, Some(2))));
+        let node = db.get_node(&ok.id.to_string()).await.unwrap().unwrap();
+        assert_eq!(node.properties.get("full_name"), Some(&Value::String("Ada".to_string())));
+        assert!(node.properties.get("name").is_none());
+        assert!(manager.get_schema("users").await.unwrap().get_field("full_name").is_some());
+
+        let error = manager
+            .migrate(&[Migration::versioned(2, "age", vec![convert(ConversionPolicy::Fail)], Vec::new())])
+            .await
+            .unwrap_err();
+        assert_eq!(crate::error::classify(&error), ErrorCode::ConstraintViolation);
+        assert_eq!(manager.get_schema("users").await.unwrap().get_field("age").unwrap().field_type, FieldType::String);
+
+        manager
+            .migrate(&[Migration::versioned(2, "age", vec![convert(ConversionPolicy::Null)], Vec::new())])
+            .await
+            .unwrap();
+        let age = |id: String| {
+            let db = Arc::clone(&db);
+            async move { db.get_node(&id).await.unwrap().unwrap().properties.get("age").cloned() }
+        };
+        assert_eq!(age(ok.id.to_string()).await, Some(Value::Int(36)));
+        assert_eq!(age(bad.id.to_string()).await, Some(Value::Null));
+        assert_eq!(manager.get_schema("users").await.unwrap().get_field("age").unwrap().field_type, FieldType::Int);
+    }
+
+    #[tokio::test]
+    async fn test_add_check_against_stored_nodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_schema("users", "age:int").await.unwrap();
+        let old = db.insert_node("users", serde_json::json!({"age": 200})).await.unwrap();
+
+        let add = Migration::new(
+            "age check",
+            vec![MigrationAction::AddCheck { schema: "users".to_string(), expression: "age < 150".to_string() }],
+        );
+        let error = manager.migrate(std::slice::from_ref(&add)).await.unwrap_err();
+        assert_eq!(crate::error::classify(&error), ErrorCode::ConstraintViolation);
+
+        db.update_node(&old.id.to_string(), serde_json::json!({"age": 20})).await.unwrap();
+        manager.migrate(std::slice::from_ref(&add)).await.unwrap();
+        assert_eq!(manager.get_schema("users").await.unwrap().checks, vec!["age < 150"]);
+        assert!(db.insert_node("users", serde_json::json!({"age": 151})).await.is_err());
+
+        manager.revert_migrations(1).await.unwrap();
+        assert!(manager.get_schema("users").await.unwrap().checks.is_empty());
+        db.insert_node("users", serde_json::json!({"age": 151})).await.unwrap();
+
+        // A check that doesn't parse is refused up front, not failed on every write
+        let bad = Migration::new(
+            "bad check",
+            vec![MigrationAction::AddCheck { schema: "users".to_string(), expression: "age <".to_string() }],
+        );
+        assert!(manager.migrate(std::slice::from_ref(&bad)).await.is_err());
+        assert!(manager.create_schema_with_checks("items", "n:int", &["n >".to_string()]).await.is_err());
+        let mut imported = Schema::new("items", Vec::new());
+        imported.checks.push("n >".to_string());
+        assert!(manager.import_schema(&imported).await.is_err());
+        db.insert_node("users", serde_json::json!({"age": 1})).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_migrate_and_revert() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path(), "test").await.unwrap();
+        let manager = SchemaManager::new(db);
+
+        let create = Migration::versioned(
+            1,
+            "create users",
+            vec![MigrationAction::CreateSchema(Schema::new("users", vec![SchemaField::new("name", FieldType::String)]))],
+            vec![MigrationAction::DropSchema("users".to_string())],
+        );
+        let add = Migration::new(
+            "add age",
+            vec![MigrationAction::AddField { schema: "users".to_string(), field: SchemaField::new("age", FieldType::Int) }],
+        );
+        let add = Migration { version: 2, ..add };
+        let migrations = vec![create, add];
+
+        assert_eq!(manager.migrate(&migrations).await.unwrap().len(), 2);
+        assert!(manager.migrate(&migrations).await.unwrap().is_empty());
+        assert!(manager.get_schema("users").await.unwrap().get_field("age").is_some());
+
+        let reverted = manager.revert_migrations(1).await.unwrap();
+        assert_eq!(reverted[0].version, 2);
+        assert!(manager.get_schema("users").await.unwrap().get_field("age").is_none());
+        assert_eq!(manager.applied_migrations().await.unwrap().len(), 1);
+
+        let mut edited = migrations.clone();
+        edited[0].actions.clear();
+        edited[0].checksum = edited[0].compute_checksum();
+        let error = manager.migrate(&edited).await.unwrap_err();
+        assert_eq!(crate::error::classify(&error), crate::error::ErrorCode::Conflict);
     }
 }
 
@@ -2,8 +2,13 @@
 //!
 //! Defines schema structures and types.
 
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::query::{Condition, Operator, QueryParser};
 
 /// A schema definition (like a table schema)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,14 +18,34 @@ pub struct Schema {
     /// Field definitions
     pub fields: Vec<SchemaField>,
     /// Schema version for migrations
+    #[serde(default = "default_version")]
     pub version: u32,
     /// Created timestamp
+    #[serde(default)]
     pub created_at: i64,
     /// Updated timestamp
+    #[serde(default)]
     pub updated_at: i64,
+    /// Expressions every node must satisfy, such as `age >= 0 AND age < 150`
+    #[serde(default)]
+    pub checks: Vec<String>,
+    /// `checks`, parsed on first use
+    #[serde(skip)]
+    compiled_checks: OnceLock<Vec<Option<Vec<Condition>>>>,
+}
+
+fn default_version() -> u32 {
+    1
+}
+
+fn default_nullable() -> bool {
+    true
 }
 
 impl Schema {
+    /// Node type the catalog stores schemas as
+    pub const NODE_TYPE: &'static str = "__schema__";
+
     /// Create a new schema
     pub fn new(name: &str, fields: Vec<SchemaField>) -> Self {
         let now = chrono::Utc::now().timestamp_millis();
@@ -30,9 +55,131 @@ impl Schema {
             version: 1,
             created_at: now,
             updated_at: now,
+            checks: Vec::new(),
+            compiled_checks: OnceLock::new(),
         }
     }
 
+    /// Add a check expression, refusing one that doesn't parse
+    pub fn add_check(&mut self, expression: &str) -> Result<()> {
+        QueryParser::new()
+            .parse_conditions(expression)
+            .with_context(|| format!("Invalid check for {}", self.name))?;
+        self.checks.push(expression.trim().to_string());
+        self.compiled_checks = OnceLock::new();
+        Ok(())
+    }
+
+    /// Remove a check expression, returning whether it was declared
+    pub fn remove_check(&mut self, expression: &str) -> bool {
+        let before = self.checks.len();
+        self.checks.retain(|c| c != expression.trim());
+        self.compiled_checks = OnceLock::new();
+        self.checks.len() != before
+    }
+
+    /// The check expressions that properties fail
+    ///
+    /// As in SQL, a comparison with a missing or null property does not
+    /// fail; combine with `required` or `IS NOT NULL` to insist on a value.
+    pub fn failed_checks(&self, properties: &BTreeMap<String, crate::storage::Value>) -> Vec<&str> {
+        let compiled = self.compiled_checks.get_or_init(|| {
+            let parser = QueryParser::new();
+            self.checks.iter().map(|c| parser.parse_conditions(c).ok()).collect()
+        });
+
+        self.checks
+            .iter()
+            .zip(compiled)
+            .filter(|(_, conditions)| {
+                let Some(conditions) = conditions else { return true };
+                !conditions.iter().all(|condition| {
+                    let value = properties.get(&condition.column).cloned().unwrap_or(crate::storage::Value::Null);
+                    match condition.operator {
+                        Operator::IsNull | Operator::IsNotNull => condition.operator.matches(&value, &condition.value),
+                        _ => value.is_null() || condition.operator.matches(&value, &condition.value),
+                    }
+                })
+            })
+            .map(|(check, _)| check.as_str())
+            .collect()
+    }
+
+    /// Load declared schemas from a `.toml` or `.json` file
+    ///
+    /// The file holds a `schemas` list, e.g. in TOML:
+    ///
+    /// ```toml
+    /// [[schemas]]
+    /// name = "users"
+    /// fields = [
+    ///     { name = "email", field_type = "String", nullable = false, unique = true },
+    ///     { name = "age", field_type = "Int" },
+    /// ]
+    /// ```
+    pub fn load_file(path: &Path) -> Result<Vec<Schema>> {
+        #[derive(Deserialize)]
+        struct SchemaFile {
+            schemas: Vec<Schema>,
+        }
+
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let file: SchemaFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&text).with_context(|| format!("Invalid schema file {}", path.display()))?,
+            Some("json") => serde_json::from_str(&text).with_context(|| format!("Invalid schema file {}", path.display()))?,
+            _ => anyhow::bail!("Schema file must end in .toml or .json: {}", path.display()),
+        };
+        for schema in &file.schemas {
+            schema.check().with_context(|| format!("Invalid schema file {}", path.display()))?;
+        }
+        Ok(file.schemas)
+    }
+
+    /// The schema stored in a catalog node
+    pub fn from_node(node: &crate::storage::Node) -> Option<Schema> {
+        let Some(crate::storage::Value::Object(data)) = node.properties.get("schema_data") else {
+            return None;
+        };
+        let json = crate::storage::Value::Object(data.clone()).to_json();
+        serde_json::from_value(json).ok()
+    }
+
+    /// The primary key field, if one is declared
+    pub fn primary_key(&self) -> Option<&SchemaField> {
+        self.fields.iter().find(|f| f.primary_key)
+    }
+
+    /// Names of the fields no two nodes may share a value of
+    pub fn unique_fields(&self) -> Vec<&str> {
+        self.fields.iter().filter(|f| f.unique).map(|f| f.name.as_str()).collect()
+    }
+
+    /// Check that the constraints themselves make sense
+    pub fn check(&self) -> Result<()> {
+        if self.fields.iter().filter(|f| f.primary_key).count() > 1 {
+            anyhow::bail!("Schema {} declares more than one primary key", self.name);
+        }
+        for field in &self.fields {
+            if let Some(pattern) = &field.pattern {
+                regex::Regex::new(pattern)
+                    .with_context(|| format!("Invalid pattern for {}.{}", self.name, field.name))?;
+            }
+            if let (Some(min), Some(max)) = (field.min, field.max) {
+                if min > max {
+                    anyhow::bail!("{}.{}: min {} is greater than max {}", self.name, field.name, min, max);
+                }
+            }
+            if field.field_type == FieldType::Vector(0) {
+                anyhow::bail!("{}.{}: a vector needs at least one dimension", self.name, field.name);
+            }
+        }
+        let parser = QueryParser::new();
+        for check in &self.checks {
+            parser.parse_conditions(check).with_context(|| format!("Invalid check for {}", self.name))?;
+        }
+        Ok(())
+    }
+
     /// Get a field by name
     pub fn get_field(&self, name: &str) -> Option<&SchemaField> {
         self.fields.iter().find(|f| f.name == name)
@@ -43,6 +190,17 @@ impl Schema {
         self.fields.iter().filter(|f| f.indexed || f.unique).collect()
     }
 
+    /// Embedding fields with their dimensions
+    pub fn vector_fields(&self) -> Vec<(&SchemaField, usize)> {
+        self.fields
+            .iter()
+            .filter_map(|f| match f.field_type {
+                FieldType::Vector(dim) => Some((f, dim)),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Get all required fields
     pub fn required_fields(&self) -> Vec<&SchemaField> {
         self.fields.iter().filter(|f| !f.nullable).collect()
@@ -54,23 +212,44 @@ impl Schema {
 
         // Check required fields
         for field in &self.fields {
-            if !field.nullable && !properties.contains_key(&field.name) {
+            if !field.nullable && properties.get(&field.name).is_none_or(|v| v.is_null()) {
                 errors.push(format!("Missing required field: {}", field.name));
             }
         }
 
-        // Check field types
+        // Check field types, then constraints
         for (key, value) in properties {
             if let Some(field) = self.get_field(key) {
-                if !field.field_type.matches(value) {
+                if let (FieldType::Enum(values), crate::storage::Value::String(s)) = (&field.field_type, value) {
+                    if !values.contains(s) {
+                        errors.push(format!("Field '{}' must be one of {}; got {:?}", key, values.join(", "), s));
+                    }
+                } else if let (FieldType::Vector(dim), false) = (&field.field_type, field.field_type.matches(value)) {
+                    match value.vector_dimension() {
+                        Some(len) => errors.push(format!(
+                            "Field '{}' must be a vector of {} dimensions, got {}",
+                            key, dim, len
+                        )),
+                        None => errors.push(format!(
+                            "Field '{}' must be a vector of {} dimensions, written as {{\"$vector\": [...]}}",
+                            key, dim
+                        )),
+                    }
+                } else if !field.field_type.matches(value) {
                     errors.push(format!(
                         "Field '{}' type mismatch: expected {:?}, got {:?}",
                         key, field.field_type, value
                     ));
+                } else {
+                    errors.extend(field.check(value));
                 }
             }
         }
 
+        for check in self.failed_checks(properties) {
+            errors.push(format!("Check failed: {}", check));
+        }
+
         if errors.is_empty() {
             Ok(())
         } else {
@@ -78,21 +257,57 @@ impl Schema {
         }
     }
 
+    /// Describe the properties of this schema's nodes as a JSON Schema
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let properties: serde_json::Map<String, serde_json::Value> =
+            self.fields.iter().map(|f| (f.name.clone(), f.to_json_schema())).collect();
+        let required: Vec<&str> = self.required_fields().iter().map(|f| f.name.as_str()).collect();
+
+        serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": self.name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+
+    /// Example VECTOR SEARCH queries, one per vector field, with the query
+    /// vector written as a placeholder for its dimension
+    pub fn vector_search_examples(&self) -> Vec<String> {
+        self.vector_fields()
+            .into_iter()
+            .map(|(field, dim)| {
+                format!(
+                    "VECTOR SEARCH {} FIELD {} FOR [<{} floats>] METRIC cosine LIMIT 10",
+                    self.name, field.name, dim
+                )
+            })
+            .collect()
+    }
+
     /// Convert to SQL CREATE TABLE statement (for reference)
     pub fn to_sql(&self) -> String {
         let mut columns = Vec::new();
 
-        columns.push("id UUID PRIMARY KEY".to_string());
+        if self.primary_key().is_some() {
+            columns.push("id UUID NOT NULL UNIQUE".to_string());
+        } else {
+            columns.push("id UUID PRIMARY KEY".to_string());
+        }
 
         for field in &self.fields {
             let mut col = format!("{} {}", field.name, field.field_type.to_sql());
 
-            if !field.nullable {
-                col.push_str(" NOT NULL");
-            }
-
-            if field.unique {
-                col.push_str(" UNIQUE");
+            if field.primary_key {
+                col.push_str(" PRIMARY KEY");
+            } else {
+                if !field.nullable {
+                    col.push_str(" NOT NULL");
+                }
+                if field.unique {
+                    col.push_str(" UNIQUE");
+                }
             }
 
             if let Some(ref default) = field.default {
@@ -105,6 +320,10 @@ impl Schema {
         columns.push("created_at TIMESTAMP NOT NULL DEFAULT NOW()".to_string());
         columns.push("updated_at TIMESTAMP NOT NULL DEFAULT NOW()".to_string());
 
+        for check in &self.checks {
+            columns.push(format!("CHECK ({})", check));
+        }
+
         format!("CREATE TABLE {} (\n  {}\n);", self.name, columns.join(",\n  "))
     }
 }
@@ -117,15 +336,35 @@ pub struct SchemaField {
     /// Field type
     pub field_type: FieldType,
     /// Whether the field can be null
+    #[serde(default = "default_nullable")]
     pub nullable: bool,
     /// Whether the field has a unique constraint
+    #[serde(default)]
     pub unique: bool,
     /// Whether the field is indexed
+    #[serde(default)]
     pub indexed: bool,
+    /// Whether the field identifies nodes of the schema; implies unique and required
+    #[serde(default)]
+    pub primary_key: bool,
     /// Default value (as JSON string)
+    #[serde(default)]
     pub default: Option<String>,
     /// Field description
+    #[serde(default)]
     pub description: Option<String>,
+    /// Smallest allowed number, or shortest string or array
+    #[serde(default)]
+    pub min: Option<f64>,
+    /// Largest allowed number, or longest string or array
+    #[serde(default)]
+    pub max: Option<f64>,
+    /// Regular expression string values must match
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// `pattern`, compiled on first use
+    #[serde(skip)]
+    compiled: OnceLock<Option<regex::Regex>>,
 }
 
 impl SchemaField {
@@ -137,8 +376,13 @@ impl SchemaField {
             nullable: true,
             unique: false,
             indexed: false,
+            primary_key: false,
             default: None,
             description: None,
+            min: None,
+            max: None,
+            pattern: None,
+            compiled: OnceLock::new(),
         }
     }
 
@@ -160,14 +404,117 @@ impl SchemaField {
         self
     }
 
+    /// Make this the primary key, which is also unique and required
+    pub fn primary_key(mut self, primary_key: bool) -> Self {
+        self.primary_key = primary_key;
+        if primary_key {
+            self.unique = true;
+            self.nullable = false;
+        }
+        self
+    }
+
     /// Set default value
     pub fn default(mut self, default: &str) -> Self {
         self.default = Some(default.to_string());
         self
     }
+
+    /// Set the smallest allowed value or length
+    pub fn min(mut self, min: f64) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    /// Set the largest allowed value or length
+    pub fn max(mut self, max: f64) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Set a regular expression string values must match
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.pattern = Some(pattern.to_string());
+        self.compiled = OnceLock::new();
+        self
+    }
+
+    /// JSON Schema of this field's values
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let mut schema = self.field_type.to_json_schema();
+        let Some(object) = schema.as_object_mut() else { return schema };
+
+        let (min, max) = match self.field_type {
+            FieldType::Int | FieldType::Float => ("minimum", "maximum"),
+            FieldType::Array(_) | FieldType::Vector(_) => ("minItems", "maxItems"),
+            _ => ("minLength", "maxLength"),
+        };
+        if let Some(value) = self.min {
+            object.insert(min.to_string(), serde_json::json!(value));
+        }
+        if let Some(value) = self.max {
+            object.insert(max.to_string(), serde_json::json!(value));
+        }
+        if let Some(pattern) = &self.pattern {
+            object.insert("pattern".to_string(), serde_json::json!(pattern));
+        }
+        if let Some(default) = &self.default {
+            let value = serde_json::from_str(default).unwrap_or_else(|_| serde_json::json!(default));
+            object.insert("default".to_string(), value);
+        }
+        if let Some(description) = &self.description {
+            object.insert("description".to_string(), serde_json::json!(description));
+        }
+
+        // Nullable fields also take null
+        if self.nullable {
+            if let Some(serde_json::Value::String(t)) = object.get("type").cloned() {
+                object.insert("type".to_string(), serde_json::json!([t, "null"]));
+            }
+            if let Some(serde_json::Value::Array(values)) = object.get_mut("enum") {
+                values.push(serde_json::Value::Null);
+            }
+        }
+        schema
+    }
+
+    /// Constraint violations of a value already known to have the right type
+    pub fn check(&self, value: &crate::storage::Value) -> Vec<String> {
+        use crate::storage::Value;
+
+        let mut errors = Vec::new();
+        let (size, unit) = match value {
+            Value::Int(i) => (*i as f64, ""),
+            Value::Float(f) => (*f, ""),
+            Value::String(s) => (s.chars().count() as f64, " characters"),
+            Value::Array(items) => (items.len() as f64, " items"),
+            _ => return errors,
+        };
+        let what = if unit.is_empty() { "" } else { " long" };
+
+        if let Some(min) = self.min.filter(|min| size < *min) {
+            errors.push(format!("Field '{}' must be at least {}{}{}, got {}", self.name, min, unit, what, size));
+        }
+        if let Some(max) = self.max.filter(|max| size > *max) {
+            errors.push(format!("Field '{}' must be at most {}{}{}, got {}", self.name, max, unit, what, size));
+        }
+
+        if let (Some(pattern), Value::String(s)) = (&self.pattern, value) {
+            let regex = self.compiled.get_or_init(|| regex::Regex::new(pattern).ok());
+            match regex {
+                Some(regex) if regex.is_match(s) => {}
+                Some(_) => errors.push(format!("Field '{}' does not match pattern '{}': {:?}", self.name, pattern, s)),
+                None => errors.push(format!("Field '{}' has an invalid pattern '{}'", self.name, pattern)),
+            }
+        }
+
+        errors
+    }
 }
 
 /// Field types
+///
+/// Displayed in the syntax [`FieldType::parse`] reads, e.g. `enum(pending, active)`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FieldType {
     String,
@@ -181,6 +528,7 @@ pub enum FieldType {
     Enum(Vec<String>),
     Array(Box<FieldType>),
     Reference(String), // Reference to another schema
+    Vector(usize),     // Embedding with a fixed dimension
 }
 
 impl FieldType {
@@ -203,6 +551,16 @@ impl FieldType {
             return FieldType::Array(Box::new(FieldType::parse(inner)));
         }
 
+        // Check for vector
+        if let Some(dim) = s_lower
+            .strip_prefix("vector")
+            .and_then(|rest| rest.strip_prefix('(').and_then(|r| r.strip_suffix(')'))
+                .or_else(|| rest.strip_prefix('<').and_then(|r| r.strip_suffix('>'))))
+            .and_then(|dim| dim.trim().parse().ok())
+        {
+            return FieldType::Vector(dim);
+        }
+
         // Check for reference
         if s_lower.starts_with("ref:") || s_lower.starts_with("reference:") {
             let target = s.split(':').nth(1).unwrap_or("unknown");
@@ -223,19 +581,49 @@ impl FieldType {
     }
 
     /// Convert to SQL type
-    pub fn to_sql(&self) -> &'static str {
+    pub fn to_sql(&self) -> String {
         match self {
-            FieldType::String => "TEXT",
-            FieldType::Int => "BIGINT",
-            FieldType::Float => "DOUBLE PRECISION",
-            FieldType::Bool => "BOOLEAN",
-            FieldType::DateTime => "TIMESTAMP",
-            FieldType::Json => "JSONB",
-            FieldType::Bytes => "BYTEA",
-            FieldType::Uuid => "UUID",
-            FieldType::Enum(_) => "TEXT", // Enums stored as text
-            FieldType::Array(_) => "JSONB", // Arrays stored as JSON
-            FieldType::Reference(_) => "UUID", // References are UUIDs
+            FieldType::String => "TEXT".to_string(),
+            FieldType::Int => "BIGINT".to_string(),
+            FieldType::Float => "DOUBLE PRECISION".to_string(),
+            FieldType::Bool => "BOOLEAN".to_string(),
+            FieldType::DateTime => "TIMESTAMP".to_string(),
+            FieldType::Json => "JSONB".to_string(),
+            FieldType::Bytes => "BYTEA".to_string(),
+            FieldType::Uuid => "UUID".to_string(),
+            FieldType::Enum(_) => "TEXT".to_string(), // Enums stored as text
+            FieldType::Array(_) => "JSONB".to_string(), // Arrays stored as JSON
+            FieldType::Reference(_) => "UUID".to_string(), // References are UUIDs
+            FieldType::Vector(dim) => format!("VECTOR({})", dim), // As in pgvector
+        }
+    }
+
+    /// JSON Schema of values of this type
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        match self {
+            FieldType::String => json!({ "type": "string" }),
+            FieldType::Int => json!({ "type": "integer" }),
+            FieldType::Float => json!({ "type": "number" }),
+            FieldType::Bool => json!({ "type": "boolean" }),
+            FieldType::DateTime => json!({ "type": "string", "format": "date-time" }),
+            FieldType::Json => json!({}),
+            FieldType::Bytes => json!({ "type": "string", "contentEncoding": "base64" }),
+            FieldType::Uuid => json!({ "type": "string", "format": "uuid" }),
+            FieldType::Enum(values) => json!({ "type": "string", "enum": values }),
+            FieldType::Array(inner) => json!({ "type": "array", "items": inner.to_json_schema() }),
+            FieldType::Reference(target) => json!({
+                "type": "string",
+                "format": "uuid",
+                "description": format!("ID of a {} node", target),
+            }),
+            FieldType::Vector(dim) => json!({
+                "type": "array",
+                "items": { "type": "number" },
+                "minItems": dim,
+                "maxItems": dim,
+            }),
         }
     }
 
@@ -250,8 +638,9 @@ impl FieldType {
             (FieldType::Float, Value::Float(_)) => true,
             (FieldType::Float, Value::Int(_)) => true, // Int can be used as float
             (FieldType::Bool, Value::Bool(_)) => true,
-            (FieldType::Json, Value::Object(_)) => true,
-            (FieldType::Json, Value::Array(_)) => true,
+            (FieldType::DateTime, Value::String(s)) => is_datetime(s),
+            // JSON columns hold any JSON value, scalars included
+            (FieldType::Json, value) => !matches!(value, Value::Bytes(_) | Value::Vector(_)),
             (FieldType::Bytes, Value::Bytes(_)) => true,
             (FieldType::Uuid, Value::String(s)) => {
                 uuid::Uuid::parse_str(s).is_ok()
@@ -265,9 +654,86 @@ impl FieldType {
             (FieldType::Reference(_), Value::String(s)) => {
                 uuid::Uuid::parse_str(s).is_ok()
             }
+            (FieldType::Vector(dim), Value::Vector(v)) => v.len() == *dim,
             _ => false,
         }
     }
+
+    /// Convert a value to this type, as a type-change migration does;
+    /// `None` if the value has no faithful conversion
+    pub fn convert(&self, value: &crate::storage::Value) -> Option<crate::storage::Value> {
+        use crate::storage::Value;
+
+        if self.matches(value) {
+            return Some(value.clone());
+        }
+
+        let converted = match (self, value) {
+            (FieldType::String, Value::Int(n)) => Value::String(n.to_string()),
+            (FieldType::String, Value::Float(f)) => Value::String(f.to_string()),
+            (FieldType::String, Value::Bool(b)) => Value::String(b.to_string()),
+            (FieldType::Int, Value::String(s)) => Value::Int(s.trim().parse().ok()?),
+            (FieldType::Int, Value::Float(f)) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => Value::Int(*f as i64),
+            (FieldType::Int, Value::Bool(b)) => Value::Int(*b as i64),
+            (FieldType::Float, Value::String(s)) => Value::Float(s.trim().parse().ok()?),
+            (FieldType::Bool, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Value::Bool(true),
+                "false" | "no" | "0" => Value::Bool(false),
+                _ => return None,
+            },
+            (FieldType::Bool, Value::Int(0)) => Value::Bool(false),
+            (FieldType::Bool, Value::Int(1)) => Value::Bool(true),
+            (FieldType::Enum(_), _) => {
+                let text = FieldType::String.convert(value)?;
+                return self.matches(&text).then_some(text);
+            }
+            (FieldType::Array(inner), Value::Array(items)) => {
+                Value::Array(items.iter().map(|item| inner.convert(item)).collect::<Option<_>>()?)
+            }
+            (FieldType::Vector(dim), Value::Array(items)) if items.len() == *dim => Value::Vector(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        Value::Int(n) => Some(*n as f32),
+                        Value::Float(f) => Some(*f as f32),
+                        _ => None,
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            _ => return None,
+        };
+        Some(converted)
+    }
+}
+
+/// Whether a string is a timestamp or date: RFC 3339, or a date with an
+/// optional time and no offset, as SQL databases print them
+fn is_datetime(s: &str) -> bool {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    DateTime::parse_from_rfc3339(s).is_ok()
+        || NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        || NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").is_ok()
+        || NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok()
+}
+
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::String => write!(f, "string"),
+            FieldType::Int => write!(f, "int"),
+            FieldType::Float => write!(f, "float"),
+            FieldType::Bool => write!(f, "bool"),
+            FieldType::DateTime => write!(f, "datetime"),
+            FieldType::Json => write!(f, "json"),
+            FieldType::Bytes => write!(f, "bytes"),
+            FieldType::Uuid => write!(f, "uuid"),
+            FieldType::Enum(values) => write!(f, "enum({})", values.join(", ")),
+            FieldType::Array(inner) => write!(f, "array<{}>", inner),
+            FieldType::Reference(target) => write!(f, "ref:{}", target),
+            FieldType::Vector(dim) => write!(f, "vector({})", dim),
+        }
+    }
 }
 
 /// A relationship between schemas
@@ -283,6 +749,33 @@ pub struct SchemaRelation {
     pub alias: Option<String>,
     /// Edge type name
     pub edge_type: String,
+    /// Reject edges that would break the relation's cardinality
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl SchemaRelation {
+    /// Node type the catalog stores relationships as
+    pub const NODE_TYPE: &'static str = "__relation__";
+
+    /// Read a relationship back from its catalog node
+    pub fn from_node(node: &crate::storage::Node) -> Option<SchemaRelation> {
+        let Some(crate::storage::Value::Object(data)) = node.properties.get("relation_data") else {
+            return None;
+        };
+        let json = crate::storage::Value::Object(data.clone()).to_json();
+        serde_json::from_value(json).ok()
+    }
+
+    /// Whether each source node may have at most one edge of this relation
+    pub fn single_source_edge(&self) -> bool {
+        matches!(self.relation_type, RelationType::HasOne | RelationType::BelongsTo)
+    }
+
+    /// Whether each target node may have at most one edge of this relation
+    pub fn single_target_edge(&self) -> bool {
+        matches!(self.relation_type, RelationType::HasOne | RelationType::HasMany)
+    }
 }
 
 /// Relationship types
@@ -298,6 +791,17 @@ pub enum RelationType {
     ManyToMany,
 }
 
+impl std::fmt::Display for RelationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelationType::HasOne => write!(f, "has_one"),
+            RelationType::HasMany => write!(f, "has_many"),
+            RelationType::BelongsTo => write!(f, "belongs_to"),
+            RelationType::ManyToMany => write!(f, "many_to_many"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,6 +821,24 @@ mod tests {
         assert!(schema.get_field("email").unwrap().unique);
     }
 
+    #[test]
+    fn test_field_type_matches() {
+        use crate::storage::Value;
+
+        let text = |s: &str| Value::String(s.to_string());
+        assert!(FieldType::DateTime.matches(&text("2024-03-01T12:30:00Z")));
+        assert!(FieldType::DateTime.matches(&text("2024-03-01T12:30:00.25+05:30")));
+        assert!(FieldType::DateTime.matches(&text("2024-03-01 12:30:00")));
+        assert!(FieldType::DateTime.matches(&text("2024-03-01")));
+        assert!(!FieldType::DateTime.matches(&text("yesterday")));
+        assert!(!FieldType::DateTime.matches(&Value::Int(1)));
+
+        assert!(FieldType::Json.matches(&text("plain")));
+        assert!(FieldType::Json.matches(&Value::Int(3)));
+        assert!(FieldType::Json.matches(&Value::Array(vec![Value::Bool(true)])));
+        assert!(!FieldType::Json.matches(&Value::Bytes(vec![1])));
+    }
+
     #[test]
     fn test_field_type_parsing() {
         assert_eq!(FieldType::parse("string"), FieldType::String);
@@ -352,6 +874,98 @@ mod tests {
         assert!(schema.validate(&props).is_err());
     }
 
+    #[test]
+    fn test_constraints() {
+        use crate::storage::Value;
+
+        let schema = Schema::new("users", vec![
+            SchemaField::new("name", FieldType::String).nullable(false).min(2.0),
+            SchemaField::new("age", FieldType::Int).min(0.0).max(150.0),
+            SchemaField::new("email", FieldType::String).pattern(r"^[^@]+@[^@]+$"),
+        ]);
+        let props = |pairs: &[(&str, Value)]| pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+
+        let ok = props(&[("name", Value::String("Ada".into())), ("age", Value::Int(36)), ("email", Value::String("ada@example.com".into()))]);
+        assert!(schema.validate(&ok).is_ok());
+
+        let errors = schema
+            .validate(&props(&[("name", Value::String("A".into())), ("age", Value::Int(-1)), ("email", Value::String("ada".into()))]))
+            .unwrap_err();
+        assert_eq!(errors, vec![
+            "Field 'age' must be at least 0, got -1",
+            "Field 'email' does not match pattern '^[^@]+@[^@]+$': \"ada\"",
+            "Field 'name' must be at least 2 characters long, got 1",
+        ]);
+
+        // NULL does not satisfy a required field
+        assert!(schema.validate(&props(&[("name", Value::Null)])).is_err());
+        assert!(Schema::new("bad", vec![SchemaField::new("x", FieldType::String).pattern("(")]).check().is_err());
+    }
+
+    #[test]
+    fn test_enum_fields() {
+        use crate::storage::Value;
+
+        let status = FieldType::parse("enum(pending, active)");
+        assert_eq!(status.to_string(), "enum(pending, active)");
+        assert_eq!(FieldType::parse(&status.to_string()), status);
+
+        let schema = Schema::new("orders", vec![SchemaField::new("status", status).nullable(false)]);
+        let props = |s: &str| [("status".to_string(), Value::String(s.to_string()))].into();
+        assert!(schema.validate(&props("active")).is_ok());
+        assert_eq!(
+            schema.validate(&props("shipped")).unwrap_err(),
+            vec!["Field 'status' must be one of pending, active; got \"shipped\""]
+        );
+
+        let json = schema.to_json_schema();
+        assert_eq!(json["properties"]["status"], serde_json::json!({ "type": "string", "enum": ["pending", "active"] }));
+        assert_eq!(json["required"], serde_json::json!(["status"]));
+    }
+
+    #[test]
+    fn test_vector_fields() {
+        use crate::storage::Value;
+
+        let embedding = FieldType::parse("vector(3)");
+        assert_eq!(embedding, FieldType::Vector(3));
+        assert_eq!(FieldType::parse("vector<3>"), embedding);
+        assert_eq!(FieldType::parse(&embedding.to_string()), embedding);
+        assert_eq!(embedding.to_sql(), "VECTOR(3)");
+
+        let schema = Schema::new("doc", vec![SchemaField::new("embedding", embedding)]);
+        let props = |v: Value| [("embedding".to_string(), v)].into();
+        assert!(schema.validate(&props(Value::Vector(vec![0.0, 0.6, 0.8]))).is_ok());
+        assert_eq!(
+            schema.validate(&props(Value::Vector(vec![1.0]))).unwrap_err(),
+            vec!["Field 'embedding' must be a vector of 3 dimensions, got 1"]
+        );
+        assert!(schema.validate(&props(Value::Array(vec![Value::Float(1.0)]))).is_err());
+        assert_eq!(
+            schema.vector_search_examples(),
+            vec!["VECTOR SEARCH doc FIELD embedding FOR [<3 floats>] METRIC cosine LIMIT 10"]
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        use crate::storage::Value;
+
+        assert_eq!(FieldType::Int.convert(&Value::String(" 42 ".to_string())), Some(Value::Int(42)));
+        assert_eq!(FieldType::Int.convert(&Value::String("abc".to_string())), None);
+        assert_eq!(FieldType::Int.convert(&Value::Float(2.5)), None);
+        assert_eq!(FieldType::String.convert(&Value::Int(7)), Some(Value::String("7".to_string())));
+        assert_eq!(FieldType::Bool.convert(&Value::String("yes".to_string())), Some(Value::Bool(true)));
+        assert_eq!(FieldType::Float.convert(&Value::Int(3)), Some(Value::Int(3)));
+        let status = FieldType::Enum(vec!["1".to_string()]);
+        assert_eq!(status.convert(&Value::Int(1)), Some(Value::String("1".to_string())));
+        assert_eq!(status.convert(&Value::Int(2)), None);
+        assert_eq!(
+            FieldType::Vector(2).convert(&Value::Array(vec![Value::Int(1), Value::Float(0.5)])),
+            Some(Value::Vector(vec![1.0, 0.5]))
+        );
+    }
+
     #[test]
     fn test_to_sql() {
         let fields = vec![
@@ -367,5 +981,3 @@ mod tests {
         assert!(sql.contains("email TEXT UNIQUE"));
     }
 }
-
-
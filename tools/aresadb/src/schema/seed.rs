@@ -0,0 +1,493 @@
+//! Synthetic data
+//!
+//! Fills a node type with fake but plausible records for demos and load
+//! tests. Fields come from the type's schema when one is registered, and
+//! from a preset picked by the type name otherwise; field names such as
+//! `email`, `city` or `price` steer what is generated. Optionally links
+//! the new nodes with edges, preferring a few popular targets the way
+//! follower graphs do.
+
+use anyhow::{bail, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+use super::registry::{FieldType, SchemaField};
+use crate::progress::{Progress, ProgressUnit};
+use crate::storage::{Database, Edge, Node, NodeId, Value};
+
+const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Amara", "Ben", "Carlos", "Chen", "Dana", "Elena", "Fatima", "Grace",
+    "Hiro", "Ivan", "Jamal", "Julia", "Kai", "Lena", "Liam", "Maya", "Noah", "Olga",
+    "Priya", "Quinn", "Rosa", "Sam", "Sofia", "Tariq", "Uma", "Victor", "Wei", "Yara",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Anderson", "Baker", "Chuba", "Diaz", "Evans", "Fischer", "Garcia", "Hughes", "Ito",
+    "Johnson", "Kim", "Lopez", "Martin", "Nakamura", "Okafor", "Patel", "Quinn", "Rossi",
+    "Schmidt", "Tanaka", "Ueda", "Volkov", "Wang", "Xu", "Young", "Zhang",
+];
+
+const CITIES: &[&str] = &[
+    "Amsterdam", "Austin", "Berlin", "Bogota", "Cairo", "Chicago", "Kyiv", "Lagos", "Lisbon",
+    "London", "Melbourne", "Mumbai", "Nairobi", "New York", "Paris", "Seoul", "Singapore",
+    "Sao Paulo", "Tokyo", "Toronto",
+];
+
+const COUNTRIES: &[&str] = &[
+    "Australia", "Brazil", "Canada", "Colombia", "Egypt", "France", "Germany", "India",
+    "Japan", "Kenya", "Netherlands", "Nigeria", "Portugal", "Singapore", "South Korea",
+    "Ukraine", "United Kingdom", "United States",
+];
+
+const DOMAINS: &[&str] = &["example.com", "example.org", "mail.test", "inbox.test"];
+
+const ADJECTIVES: &[&str] = &[
+    "Classic", "Compact", "Deluxe", "Ergonomic", "Handmade", "Lightweight", "Modern",
+    "Portable", "Rustic", "Sleek", "Smart", "Vintage", "Wireless",
+];
+
+const PRODUCTS: &[&str] = &[
+    "Backpack", "Chair", "Desk Lamp", "Headphones", "Kettle", "Keyboard", "Mug", "Notebook",
+    "Speaker", "Sunglasses", "Watch", "Water Bottle",
+];
+
+const CATEGORIES: &[&str] = &["books", "electronics", "garden", "home", "outdoors", "sports", "toys"];
+
+const WORDS: &[&str] = &[
+    "across", "build", "data", "design", "every", "fast", "graph", "happy", "idea", "join",
+    "keep", "learn", "model", "network", "open", "query", "river", "simple", "team", "under",
+    "value", "while", "world", "year",
+];
+
+/// Edges to generate between seeded nodes, parsed from
+/// `EDGE_TYPE[:avg_degree=N]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphSpec {
+    /// Type of the generated edges
+    pub edge_type: String,
+    /// Average outgoing edges per node
+    pub avg_degree: f64,
+}
+
+impl GraphSpec {
+    /// Parse `follows:avg_degree=5` (or `follows:5`; the degree defaults to 5)
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (edge_type, degree) = match spec.split_once(':') {
+            Some((edge_type, degree)) => (edge_type, Some(degree)),
+            None => (spec, None),
+        };
+        if edge_type.is_empty() {
+            bail!("Invalid graph spec '{}': missing edge type", spec);
+        }
+        let avg_degree = match degree {
+            Some(degree) => {
+                let value = degree.strip_prefix("avg_degree=").unwrap_or(degree);
+                value.parse::<f64>()
+                    .ok()
+                    .filter(|d| d.is_finite() && *d >= 0.0)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid average degree in '{}'", spec))?
+            }
+            None => 5.0,
+        };
+        Ok(Self { edge_type: edge_type.to_string(), avg_degree })
+    }
+}
+
+/// What to generate
+#[derive(Debug, Clone)]
+pub struct SeedOptions {
+    /// Type of the generated nodes
+    pub node_type: String,
+    /// Number of nodes
+    pub count: usize,
+    /// Fields of each node; see [`preset_fields`] when no schema exists
+    pub fields: Vec<SchemaField>,
+    /// Add an `embedding` vector of this many dimensions to each node
+    pub vector_dim: Option<usize>,
+    /// Edges linking the generated nodes
+    pub graphs: Vec<GraphSpec>,
+    /// Random seed, for reproducible data
+    pub seed: Option<u64>,
+    /// Records written per transaction
+    pub batch_size: usize,
+}
+
+impl SeedOptions {
+    /// Generate `count` nodes of `node_type` with the type's preset fields
+    pub fn new(node_type: &str, count: usize) -> Self {
+        Self {
+            node_type: node_type.to_string(),
+            count,
+            fields: preset_fields(node_type),
+            vector_dim: None,
+            graphs: Vec::new(),
+            seed: None,
+            batch_size: 1000,
+        }
+    }
+
+    /// Use these fields instead of the preset
+    pub fn with_fields(mut self, fields: Vec<SchemaField>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Add an `embedding` vector to each node
+    pub fn with_vectors(mut self, dimension: usize) -> Self {
+        self.vector_dim = Some(dimension);
+        self
+    }
+
+    /// Link the nodes with edges
+    pub fn with_graph(mut self, graph: GraphSpec) -> Self {
+        self.graphs.push(graph);
+        self
+    }
+
+    /// Use a fixed random seed
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Counts of what [`seed`] wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeedStats {
+    /// Nodes inserted
+    pub nodes: usize,
+    /// Edges inserted
+    pub edges: usize,
+}
+
+/// Fields generated for a type without a schema
+///
+/// People-like names (`users`, `customers`, ...), products and posts get
+/// their own presets; anything else gets a name, a score and a timestamp.
+pub fn preset_fields(node_type: &str) -> Vec<SchemaField> {
+    let name = node_type.to_lowercase();
+    let name = name.trim_end_matches('s');
+    let fields: &[(&str, FieldType)] = match name {
+        "user" | "person" | "people" | "customer" | "member" | "account" | "employee" | "author" => &[
+            ("name", FieldType::String),
+            ("email", FieldType::String),
+            ("age", FieldType::Int),
+            ("city", FieldType::String),
+            ("country", FieldType::String),
+            ("active", FieldType::Bool),
+            ("created_at", FieldType::DateTime),
+        ],
+        "product" | "item" => &[
+            ("name", FieldType::String),
+            ("category", FieldType::String),
+            ("price", FieldType::Float),
+            ("stock", FieldType::Int),
+            ("created_at", FieldType::DateTime),
+        ],
+        "post" | "article" | "document" | "doc" | "comment" | "message" => &[
+            ("title", FieldType::String),
+            ("body", FieldType::String),
+            ("likes", FieldType::Int),
+            ("created_at", FieldType::DateTime),
+        ],
+        _ => &[
+            ("name", FieldType::String),
+            ("score", FieldType::Float),
+            ("created_at", FieldType::DateTime),
+        ],
+    };
+    fields.iter().map(|(name, field_type)| SchemaField::new(name, field_type.clone())).collect()
+}
+
+/// Generates field values
+struct Generator {
+    rng: StdRng,
+    /// IDs of existing nodes by type, for reference fields
+    references: BTreeMap<String, Vec<NodeId>>,
+    /// Generate product names for `name` fields
+    products: bool,
+}
+
+impl Generator {
+    fn pick(&mut self, values: &[&str]) -> String {
+        values.choose(&mut self.rng).copied().unwrap_or_default().to_string()
+    }
+
+    fn words(&mut self, min: usize, max: usize) -> String {
+        let count = self.rng.gen_range(min..=max);
+        (0..count).map(|_| self.pick(WORDS)).collect::<Vec<_>>().join(" ")
+    }
+
+    /// A timestamp within the last two years
+    fn timestamp(&mut self) -> String {
+        let age = chrono::Duration::seconds(self.rng.gen_range(0..2 * 365 * 24 * 3600));
+        (chrono::Utc::now() - age).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    }
+
+    fn vector(&mut self, dimension: usize) -> Vec<f32> {
+        let vector: Vec<f32> = (0..dimension).map(|_| self.rng.gen_range(-1.0..1.0)).collect();
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return vector;
+        }
+        vector.into_iter().map(|x| x / norm).collect()
+    }
+
+    /// A record's properties; `index` keeps unique-looking values such as
+    /// emails distinct
+    fn record(&mut self, fields: &[SchemaField], index: usize) -> BTreeMap<String, Value> {
+        let first = self.pick(FIRST_NAMES);
+        let last = self.pick(LAST_NAMES);
+        fields
+            .iter()
+            .map(|field| {
+                let value = self.value(&field.name.to_lowercase(), &field.field_type, &first, &last, index);
+                (field.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn value(&mut self, name: &str, field_type: &FieldType, first: &str, last: &str, index: usize) -> Value {
+        match field_type {
+            FieldType::String => Value::String(self.text(name, first, last, index)),
+            FieldType::Int => Value::Int(match name {
+                n if n.contains("age") => self.rng.gen_range(18..80),
+                n if n.contains("year") => self.rng.gen_range(1970..2026),
+                n if n.contains("stock") || n.contains("quantity") => self.rng.gen_range(0..500),
+                // Skewed so a few records collect most of the likes or views
+                _ => (self.rng.gen::<f64>().powi(4) * 10_000.0) as i64,
+            }),
+            FieldType::Float => Value::Float(match name {
+                n if n.contains("price") || n.contains("amount") || n.contains("total") => {
+                    (self.rng.gen_range(1.0..500.0) * 100.0_f64).round() / 100.0
+                }
+                n if n.contains("lat") => self.rng.gen_range(-90.0..90.0),
+                n if n.contains("lon") || n.contains("lng") => self.rng.gen_range(-180.0..180.0),
+                _ => (self.rng.gen_range(0.0..100.0) * 100.0_f64).round() / 100.0,
+            }),
+            FieldType::Bool => Value::Bool(self.rng.gen_bool(0.8)),
+            FieldType::DateTime => Value::String(self.timestamp()),
+            FieldType::Uuid => Value::String(uuid::Uuid::new_v4().to_string()),
+            FieldType::Enum(values) => {
+                let values: Vec<&str> = values.iter().map(String::as_str).collect();
+                Value::String(self.pick(&values))
+            }
+            FieldType::Array(inner) => {
+                let len = self.rng.gen_range(1..=3);
+                Value::Array((0..len).map(|_| self.value(name, inner, first, last, index)).collect())
+            }
+            FieldType::Reference(target) => {
+                match self.references.get(target).and_then(|ids| ids.choose(&mut self.rng)) {
+                    Some(id) => Value::String(id.to_string()),
+                    None => Value::Null,
+                }
+            }
+            FieldType::Json => {
+                let mut object = BTreeMap::new();
+                object.insert("tag".to_string(), Value::String(self.pick(WORDS)));
+                object.insert("rank".to_string(), Value::Int(self.rng.gen_range(1..100)));
+                Value::Object(object)
+            }
+            FieldType::Bytes => Value::Bytes((0..16).map(|_| self.rng.gen()).collect()),
+            FieldType::Vector(dim) => Value::Vector(self.vector(*dim)),
+        }
+    }
+
+    fn text(&mut self, name: &str, first: &str, last: &str, index: usize) -> String {
+        match name {
+            n if n.contains("email") => {
+                let domain = self.pick(DOMAINS);
+                format!("{}.{}{}@{}", first.to_lowercase(), last.to_lowercase(), index, domain)
+            }
+            n if n.contains("first") => first.to_string(),
+            n if n.contains("last") || n.contains("surname") => last.to_string(),
+            n if n.contains("user") || n.contains("handle") || n.contains("login") => {
+                format!("{}{}", first.to_lowercase(), index)
+            }
+            n if n.contains("phone") => format!(
+                "+1-555-{:03}-{:04}",
+                self.rng.gen_range(100..1000),
+                self.rng.gen_range(0..10_000)
+            ),
+            n if n.contains("city") => self.pick(CITIES),
+            n if n.contains("country") => self.pick(COUNTRIES),
+            n if n.contains("categor") || n.contains("tag") => self.pick(CATEGORIES),
+            n if n.contains("sku") || n.contains("code") => format!("SKU-{:06}", index),
+            n if n.contains("url") || n.contains("website") => {
+                format!("https://{}/{}", self.pick(DOMAINS), self.pick(WORDS))
+            }
+            n if n.contains("title") || n.contains("subject") => {
+                let mut title = self.words(3, 7);
+                if let Some(c) = title.get_mut(0..1) {
+                    c.make_ascii_uppercase();
+                }
+                title
+            }
+            n if n.contains("body") || n.contains("description") || n.contains("content")
+                || n.contains("text") || n.contains("bio") => format!("{}.", self.words(12, 40)),
+            n if n.contains("name") && self.products => {
+                format!("{} {}", self.pick(ADJECTIVES), self.pick(PRODUCTS))
+            }
+            n if n.contains("name") => format!("{} {}", first, last),
+            _ => self.words(1, 3),
+        }
+    }
+}
+
+/// Insert synthetic nodes (and edges between them)
+pub async fn seed<F>(db: &Database, options: &SeedOptions, mut on_progress: F) -> Result<SeedStats>
+where
+    F: FnMut(Progress),
+{
+    let mut references = BTreeMap::new();
+    for field in &options.fields {
+        if let FieldType::Reference(target) = &field.field_type {
+            let ids = db.get_all_by_type(target, Some(10_000)).await?.into_iter().map(|n| n.id).collect();
+            references.insert(target.clone(), ids);
+        }
+    }
+
+    let mut generator = Generator {
+        rng: match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        },
+        references,
+        products: preset_is_product(&options.node_type),
+    };
+
+    let edge_counts: Vec<usize> = options
+        .graphs
+        .iter()
+        .map(|g| if options.count < 2 { 0 } else { (options.count as f64 * g.avg_degree).round() as usize })
+        .collect();
+    let total = (options.count + edge_counts.iter().sum::<usize>()) as u64;
+    let batch_size = options.batch_size.max(1);
+    let mut stats = SeedStats::default();
+    on_progress(Progress::new("seed", ProgressUnit::Records, 0, Some(total)));
+
+    let mut ids = Vec::with_capacity(options.count);
+    while stats.nodes < options.count {
+        let size = batch_size.min(options.count - stats.nodes);
+        let mut batch = Vec::with_capacity(size);
+        for n in stats.nodes..stats.nodes + size {
+            let mut properties = generator.record(&options.fields, n + 1);
+            if let Some(dimension) = options.vector_dim {
+                properties.insert("embedding".to_string(), Value::Vector(generator.vector(dimension)));
+            }
+            let node = Node::new(&options.node_type, Value::Object(properties));
+            ids.push(node.id.clone());
+            batch.push(node);
+        }
+        db.replace_nodes(Vec::new(), batch).await?;
+        stats.nodes += size;
+        on_progress(Progress::new("seed", ProgressUnit::Records, stats.nodes as u64, Some(total)));
+    }
+
+    for (graph, count) in options.graphs.iter().zip(edge_counts) {
+        let mut written = 0;
+        while written < count {
+            let size = batch_size.min(count - written);
+            let mut batch = Vec::with_capacity(size);
+            for _ in 0..size {
+                let from = generator.rng.gen_range(0..ids.len());
+                // Squaring a uniform draw favours low indexes, giving a few
+                // nodes many incoming edges
+                let mut to = (generator.rng.gen::<f64>().powi(2) * ids.len() as f64) as usize;
+                if to == from {
+                    to = (to + 1) % ids.len();
+                }
+                let properties = BTreeMap::from([("since".to_string(), Value::String(generator.timestamp()))]);
+                batch.push(Edge::new(ids[from].clone(), ids[to].clone(), &graph.edge_type, Value::Object(properties)));
+            }
+            db.insert_edges(batch).await?;
+            written += size;
+            stats.edges += size;
+            on_progress(Progress::new(
+                "seed",
+                ProgressUnit::Records,
+                (stats.nodes + stats.edges) as u64,
+                Some(total),
+            ));
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Whether a type's `name` fields should be product names
+fn preset_is_product(node_type: &str) -> bool {
+    matches!(node_type.to_lowercase().trim_end_matches('s'), "product" | "item")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_graph_spec() {
+        assert_eq!(
+            GraphSpec::parse("follows:avg_degree=2.5").unwrap(),
+            GraphSpec { edge_type: "follows".to_string(), avg_degree: 2.5 }
+        );
+        assert_eq!(GraphSpec::parse("likes:3").unwrap().avg_degree, 3.0);
+        assert_eq!(GraphSpec::parse("knows").unwrap().avg_degree, 5.0);
+        assert!(GraphSpec::parse("follows:avg_degree=x").is_err());
+        assert!(GraphSpec::parse(":5").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seed_nodes_and_edges() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(dir.path(), "test").await.unwrap();
+
+        let options = SeedOptions::new("users", 50)
+            .with_vectors(8)
+            .with_graph(GraphSpec::parse("follows:avg_degree=2").unwrap())
+            .with_seed(7);
+        let mut last = None;
+        let stats = seed(&db, &options, |p| last = Some(p)).await.unwrap();
+        assert_eq!(stats, SeedStats { nodes: 50, edges: 100 });
+        assert!(last.unwrap().is_complete());
+
+        let users = db.get_all_by_type("users", None).await.unwrap();
+        assert_eq!(users.len(), 50);
+        let user = &users[0];
+        for field in ["name", "email", "age", "city", "created_at", "embedding"] {
+            assert!(user.properties.contains_key(field), "missing {}", field);
+        }
+        let Some(Value::String(email)) = user.properties.get("email") else { panic!("email") };
+        assert!(email.contains('@'));
+        let Some(Value::Vector(embedding)) = user.properties.get("embedding") else { panic!("embedding") };
+        assert_eq!(embedding.len(), 8);
+
+        let mut edges = 0;
+        for user in &users {
+            let out = db.get_edges_from(&user.id.to_string(), Some("follows")).await.unwrap();
+            assert!(out.iter().all(|e| e.from != e.to));
+            edges += out.len();
+        }
+        assert_eq!(edges, 100);
+    }
+
+    #[tokio::test]
+    async fn test_seed_into_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = std::sync::Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        crate::schema::SchemaManager::from_shared(db.clone())
+            .create_schema("events", "name:string, created_at:datetime, meta:json")
+            .await
+            .unwrap();
+
+        // Writes are checked against the schema, so timestamps and JSON
+        // values must pass as their types
+        let stats = seed(&db, &SeedOptions::new("events", 20).with_seed(3), |_| {}).await.unwrap();
+        assert_eq!(stats.nodes, 20);
+
+        let events = db.get_all_by_type("events", None).await.unwrap();
+        let Some(Value::String(created_at)) = events[0].properties.get("created_at") else { panic!("created_at") };
+        assert!(chrono::DateTime::parse_from_rfc3339(created_at).is_ok());
+    }
+}
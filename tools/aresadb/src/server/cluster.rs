@@ -0,0 +1,86 @@
+//! Cluster Leadership
+//!
+//! A replicated server answers `Request::WhoIsLeader` so clients can find
+//! the leader after a failover. Replicas know each other by node ID, so the
+//! view also maps node IDs to the addresses clients connect to. Followers
+//! refuse writes with `ErrorCode::NotLeader`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::protocol::{ErrorCode, Response};
+use crate::distributed::ReplicaSet;
+
+/// Leadership as seen by one replica
+pub struct ClusterView {
+    replica: Arc<ReplicaSet>,
+    /// Client address of each replica, by node ID
+    addresses: HashMap<String, String>,
+}
+
+impl ClusterView {
+    /// Create a view of a replica set
+    pub fn new(replica: Arc<ReplicaSet>) -> Self {
+        Self {
+            replica,
+            addresses: HashMap::new(),
+        }
+    }
+
+    /// Record the address clients use to reach a replica
+    pub fn with_address(mut self, node_id: impl Into<String>, addr: impl Into<String>) -> Self {
+        self.addresses.insert(node_id.into(), addr.into());
+        self
+    }
+
+    /// Whether this replica leads
+    pub fn is_leader(&self) -> bool {
+        self.replica.is_leader()
+    }
+
+    /// Client address of the current leader, if known
+    pub fn leader_address(&self) -> Option<String> {
+        let leader = self.replica.leader()?;
+        self.addresses.get(&leader).cloned()
+    }
+
+    /// Answer to `Request::WhoIsLeader`
+    pub fn leader_response(&self) -> Response {
+        Response::Leader {
+            leader: self.leader_address(),
+            is_leader: self.is_leader(),
+        }
+    }
+
+    /// Error for a write this follower can't accept
+    pub fn not_leader(&self) -> Response {
+        let message = match self.leader_address() {
+            Some(leader) => format!("Not the leader; writes go to {}", leader),
+            None => "Not the leader; no leader is currently known".to_string(),
+        };
+        Response::error(ErrorCode::NotLeader, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributed::ReplicaConfig;
+
+    #[test]
+    fn test_cluster_view() {
+        let leader = Arc::new(ReplicaSet::new(ReplicaConfig { node_id: "a".to_string(), ..Default::default() }));
+        let follower = Arc::new(ReplicaSet::new(ReplicaConfig { node_id: "b".to_string(), ..Default::default() }));
+
+        let view = ClusterView::new(Arc::clone(&follower))
+            .with_address("a", "10.0.0.1:7432")
+            .with_address("b", "10.0.0.2:7432");
+        assert!(view.leader_address().is_none());
+        assert!(matches!(view.not_leader(), Response::Error { code: ErrorCode::NotLeader, .. }));
+
+        leader.become_leader();
+        follower.process_message(leader.create_heartbeat());
+        assert!(!view.is_leader());
+        assert_eq!(view.leader_address().as_deref(), Some("10.0.0.1:7432"));
+    }
+}
@@ -0,0 +1,714 @@
+//! gRPC Interface
+//!
+//! tonic service generated from `proto/aresadb.proto`. Like the REST API,
+//! each RPC is translated into a protocol `Request` and dispatched through
+//! the shared `RequestHandler` of the database named by the
+//! `x-aresadb-database` metadata key (the default database without one).
+
+use anyhow::{Result, Context};
+use futures::Stream;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::{Request as GrpcRequest, Response as GrpcResponse, Status};
+use tracing::info;
+
+use super::handler::RequestHandler;
+use super::protocol::{Request, Response, ErrorCode};
+use super::tenant::{TenantPermit, TenantRegistry, DATABASE_HEADER};
+use crate::auth::Principal;
+use crate::storage::{DistanceMetric, Edge, Node, Value};
+
+/// Generated protobuf types and service stubs
+#[allow(missing_docs, clippy::all)]
+pub mod proto {
+    tonic::include_proto!("aresadb.v1");
+}
+
+use proto::aresa_db_server::{AresaDb, AresaDbServer};
+
+/// Default number of rows per `QueryStream` chunk
+const DEFAULT_STREAM_BATCH: usize = 1000;
+
+/// Chunks a `QueryStream` reads ahead of the client
+const STREAM_BUFFER_CHUNKS: usize = 4;
+
+/// gRPC service backed by the hosted databases
+pub struct GrpcService {
+    tenants: Arc<TenantRegistry>,
+}
+
+impl GrpcService {
+    /// Create a service over the hosted databases
+    pub fn new(tenants: Arc<TenantRegistry>) -> Self {
+        Self { tenants }
+    }
+
+    /// Wrap the service for registration with a tonic server
+    ///
+    /// Calls are checked for an `authorization: Bearer <api key>` header
+    /// when authentication is required.
+    pub fn into_server(self) -> InterceptedService<AresaDbServer<Self>, AuthInterceptor> {
+        let interceptor = AuthInterceptor { tenants: Arc::clone(&self.tenants) };
+        AresaDbServer::with_interceptor(self, interceptor)
+    }
+
+    async fn call(
+        &self,
+        (principal, client, permit): &Caller,
+        request: Request,
+    ) -> Result<Response, Status> {
+        let handler = self.handler(permit);
+        match handler.handle_from(principal, *client, request).await {
+            Response::Error { code, message } => Err(to_status(code, message)),
+            response => Ok(response),
+        }
+    }
+
+    /// Handler of the database a call was admitted to
+    fn handler(&self, permit: &Option<Arc<TenantPermit>>) -> Arc<RequestHandler> {
+        match permit {
+            Some(permit) => Arc::clone(permit.tenant().handler()),
+            None => Arc::clone(self.tenants.default_tenant().handler()),
+        }
+    }
+}
+
+/// Interceptor enforcing bearer API key authentication and picking the
+/// database each call goes to, with a connection slot on it held until the
+/// call finishes
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    tenants: Arc<TenantRegistry>,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, request: GrpcRequest<()>) -> Result<GrpcRequest<()>, Status> {
+        let header = request.metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok());
+
+        let database = request.metadata()
+            .get(DATABASE_HEADER)
+            .and_then(|value| value.to_str().ok());
+
+        // Message sizes aren't visible here, so only request rates apply
+        match self.tenants.admit_request(database, header, 0) {
+            Ok((principal, permit)) => {
+                let mut request = request;
+                request.extensions_mut().insert(principal);
+                request.extensions_mut().insert(Arc::new(permit));
+                Ok(request)
+            }
+            Err(Response::Error { code, message }) => Err(to_status(code, message)),
+            Err(_) => Err(unexpected()),
+        }
+    }
+}
+
+/// Bind to an address and serve the gRPC API
+pub async fn serve(addr: SocketAddr, tenants: Arc<TenantRegistry>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context("Failed to bind gRPC listener")?;
+    serve_listener(listener, tenants).await
+}
+
+/// Serve the gRPC API on an already bound listener
+pub async fn serve_listener(listener: TcpListener, tenants: Arc<TenantRegistry>) -> Result<()> {
+    info!("AresaDB gRPC API listening on {}", listener.local_addr()?);
+    tonic::transport::Server::builder()
+        .add_service(GrpcService::new(tenants).into_server())
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+        .context("gRPC server error")
+}
+
+type QueryChunkStream = Pin<Box<dyn Stream<Item = Result<proto::QueryChunk, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl AresaDb for GrpcService {
+    async fn ping(
+        &self,
+        request: GrpcRequest<proto::PingRequest>,
+    ) -> Result<GrpcResponse<proto::PingResponse>, Status> {
+        let caller = caller(&request);
+        match self.call(&caller, Request::Ping).await? {
+            Response::Pong => Ok(GrpcResponse::new(proto::PingResponse {})),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn insert_node(
+        &self,
+        request: GrpcRequest<proto::InsertNodeRequest>,
+    ) -> Result<GrpcResponse<proto::Node>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::InsertNode {
+            node_type: req.node_type,
+            properties: Value::Object(props_from_proto(req.properties)),
+        }).await?;
+
+        match response {
+            Response::Node(node) => Ok(GrpcResponse::new(node_to_proto(&node))),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn get_node(
+        &self,
+        request: GrpcRequest<proto::GetNodeRequest>,
+    ) -> Result<GrpcResponse<proto::GetNodeResponse>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        match self.call(&caller, Request::GetNode { id: req.id }).await? {
+            Response::MaybeNode(node) => Ok(GrpcResponse::new(proto::GetNodeResponse {
+                node: node.as_ref().map(node_to_proto),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn update_node(
+        &self,
+        request: GrpcRequest<proto::UpdateNodeRequest>,
+    ) -> Result<GrpcResponse<proto::Node>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::UpdateNode {
+            id: req.id,
+            properties: Value::Object(props_from_proto(req.properties)),
+        }).await?;
+
+        match response {
+            Response::Node(node) => Ok(GrpcResponse::new(node_to_proto(&node))),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn delete_node(
+        &self,
+        request: GrpcRequest<proto::DeleteNodeRequest>,
+    ) -> Result<GrpcResponse<proto::Empty>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        match self.call(&caller, Request::DeleteNode { id: req.id }).await? {
+            Response::Ok => Ok(GrpcResponse::new(proto::Empty {})),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn get_nodes_by_type(
+        &self,
+        request: GrpcRequest<proto::GetNodesByTypeRequest>,
+    ) -> Result<GrpcResponse<proto::NodeList>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::GetNodesByType {
+            node_type: req.node_type,
+            limit: req.limit.map(|l| l as usize),
+        }).await?;
+
+        match response {
+            Response::Nodes(nodes) => Ok(GrpcResponse::new(proto::NodeList {
+                nodes: nodes.iter().map(node_to_proto).collect(),
+            })),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn create_edge(
+        &self,
+        request: GrpcRequest<proto::CreateEdgeRequest>,
+    ) -> Result<GrpcResponse<proto::Edge>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let properties = if req.properties.is_empty() {
+            None
+        } else {
+            Some(Value::Object(props_from_proto(req.properties)))
+        };
+
+        let response = self.call(&caller, Request::CreateEdge {
+            from_id: req.from_id,
+            to_id: req.to_id,
+            edge_type: req.edge_type,
+            properties,
+        }).await?;
+
+        match response {
+            Response::Edge(edge) => Ok(GrpcResponse::new(edge_to_proto(&edge))),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn get_edges_from(
+        &self,
+        request: GrpcRequest<proto::GetEdgesRequest>,
+    ) -> Result<GrpcResponse<proto::EdgeList>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::GetEdgesFrom {
+            node_id: req.node_id,
+            edge_type: req.edge_type,
+        }).await?;
+
+        match response {
+            Response::Edges(edges) => Ok(GrpcResponse::new(edge_list(&edges))),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn get_edges_to(
+        &self,
+        request: GrpcRequest<proto::GetEdgesRequest>,
+    ) -> Result<GrpcResponse<proto::EdgeList>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::GetEdgesTo {
+            node_id: req.node_id,
+            edge_type: req.edge_type,
+        }).await?;
+
+        match response {
+            Response::Edges(edges) => Ok(GrpcResponse::new(edge_list(&edges))),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn delete_edge(
+        &self,
+        request: GrpcRequest<proto::DeleteEdgeRequest>,
+    ) -> Result<GrpcResponse<proto::Empty>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        match self.call(&caller, Request::DeleteEdge { edge_id: req.edge_id }).await? {
+            Response::Ok => Ok(GrpcResponse::new(proto::Empty {})),
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn query(
+        &self,
+        request: GrpcRequest<proto::QueryRequest>,
+    ) -> Result<GrpcResponse<proto::QueryResponse>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let response = self.call(&caller, Request::Query {
+            sql: req.sql,
+            limit: req.limit.map(|l| l as usize),
+        }).await?;
+
+        match response {
+            Response::QueryResult { columns, rows, rows_affected, execution_time_ms } => {
+                Ok(GrpcResponse::new(proto::QueryResponse {
+                    columns,
+                    rows: rows.iter().map(|r| row_to_proto(r)).collect(),
+                    rows_affected,
+                    execution_time_ms,
+                }))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+
+    type QueryStreamStream = QueryChunkStream;
+
+    async fn query_stream(
+        &self,
+        request: GrpcRequest<proto::QueryRequest>,
+    ) -> Result<GrpcResponse<Self::QueryStreamStream>, Status> {
+        let (principal, client, permit) = caller(&request);
+        let handler = self.handler(&permit);
+        let req = request.into_inner();
+        let batch_size = req.batch_size
+            .map(|b| b.max(1) as usize)
+            .unwrap_or(DEFAULT_STREAM_BATCH);
+        let limit = req.limit.map(|l| l as usize);
+
+        // Scans read storage synchronously, so the query runs on a blocking
+        // thread and waits whenever the client falls behind
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER_CHUNKS);
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let _runtime = runtime.enter();
+            let start = std::time::Instant::now();
+            let mut batch = Vec::with_capacity(batch_size);
+            let streamed = futures::executor::block_on(handler.stream_query(
+                &principal,
+                client,
+                &req.sql,
+                limit,
+                |row| {
+                    batch.push(row);
+                    if batch.len() >= batch_size {
+                        let chunk = query_chunk(std::mem::take(&mut batch), 0, 0);
+                        tx.blocking_send(Ok(chunk)).map_err(|_| anyhow::anyhow!("Query stream closed"))?;
+                    }
+                    Ok(())
+                },
+            ));
+
+            // The last chunk carries the totals, even when it has no rows
+            let last = match streamed {
+                Ok(rows_affected) => Ok(query_chunk(batch, rows_affected, start.elapsed().as_millis() as u64)),
+                Err(Response::Error { code, message }) => Err(to_status(code, message)),
+                Err(_) => Err(unexpected()),
+            };
+            let _ = tx.blocking_send(last);
+            // The database slot is held until the stream is done
+            drop(permit);
+        });
+
+        Ok(GrpcResponse::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn similarity_search(
+        &self,
+        request: GrpcRequest<proto::SimilaritySearchRequest>,
+    ) -> Result<GrpcResponse<proto::SimilaritySearchResponse>, Status> {
+        let caller = caller(&request);
+        let req = request.into_inner();
+        let metric = match proto::DistanceMetric::try_from(req.metric) {
+            Ok(proto::DistanceMetric::Cosine) => DistanceMetric::Cosine,
+            Ok(proto::DistanceMetric::Euclidean) => DistanceMetric::Euclidean,
+            Ok(proto::DistanceMetric::DotProduct) => DistanceMetric::DotProduct,
+            Ok(proto::DistanceMetric::Manhattan) => DistanceMetric::Manhattan,
+            Err(_) => return Err(Status::invalid_argument("Unknown distance metric")),
+        };
+        let field = if req.field.is_empty() { "embedding".to_string() } else { req.field };
+        let k = if req.k == 0 { 10 } else { req.k as usize };
+
+        let response = self.call(&caller, Request::SimilaritySearch {
+            node_type: req.node_type,
+            vector: req.vector,
+            field,
+            k,
+            metric,
+        }).await?;
+
+        match response {
+            Response::SimilarityResults(results) => {
+                Ok(GrpcResponse::new(proto::SimilaritySearchResponse {
+                    results: results.iter().map(|r| proto::SimilarityMatch {
+                        node_id: r.node_id.to_string(),
+                        score: r.score,
+                        distance: r.distance,
+                    }).collect(),
+                }))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+
+    async fn status(
+        &self,
+        request: GrpcRequest<proto::StatusRequest>,
+    ) -> Result<GrpcResponse<proto::StatusResponse>, Status> {
+        let caller = caller(&request);
+        match self.call(&caller, Request::Status).await? {
+            Response::Status { name, node_count, edge_count, size_bytes } => {
+                Ok(GrpcResponse::new(proto::StatusResponse {
+                    name,
+                    node_count,
+                    edge_count,
+                    size_bytes,
+                }))
+            }
+            _ => Err(unexpected()),
+        }
+    }
+}
+
+// ========== Conversions ==========
+
+/// Map protocol error codes onto gRPC status codes
+fn to_status(code: ErrorCode, message: String) -> Status {
+    match code {
+        ErrorCode::InvalidRequest | ErrorCode::QueryParseError => Status::invalid_argument(message),
+        ErrorCode::NodeNotFound
+        | ErrorCode::EdgeNotFound
+        | ErrorCode::DatabaseNotFound
+        | ErrorCode::StatementNotFound => Status::not_found(message),
+        ErrorCode::Unauthenticated => Status::unauthenticated(message),
+        ErrorCode::PermissionDenied => Status::permission_denied(message),
+        ErrorCode::ServerOverloaded | ErrorCode::RateLimited | ErrorCode::MemoryLimitExceeded => {
+            Status::resource_exhausted(message)
+        }
+        ErrorCode::Timeout => Status::deadline_exceeded(message),
+        ErrorCode::NotLeader => Status::unavailable(message),
+        ErrorCode::Cancelled => Status::cancelled(message),
+        ErrorCode::VersionMismatch => Status::failed_precondition(message),
+        ErrorCode::TransactionError => Status::aborted(message),
+        ErrorCode::QueryExecutionError | ErrorCode::Unknown | ErrorCode::InternalError => {
+            Status::internal(message)
+        }
+    }
+}
+
+/// Principal, peer address and database slot of a call
+type Caller = (Principal, Option<SocketAddr>, Option<Arc<TenantPermit>>);
+
+/// Principal and database slot attached by `AuthInterceptor`, and the peer
+/// address; the slot is held for as long as the caller is
+///
+/// Calls that bypassed the interceptor get no permissions and the default
+/// database.
+fn caller<T>(request: &GrpcRequest<T>) -> Caller {
+    let principal = request.extensions()
+        .get::<Principal>()
+        .cloned()
+        .unwrap_or_else(Principal::denied);
+    let permit = request.extensions().get::<Arc<TenantPermit>>().cloned();
+    (principal, request.remote_addr(), permit)
+}
+
+fn unexpected() -> Status {
+    Status::internal("Unexpected response")
+}
+
+/// Convert a storage value into its protobuf representation
+pub fn value_to_proto(value: &Value) -> proto::Value {
+    use proto::value::Kind;
+
+    let kind = match value {
+        Value::Null => Kind::NullValue(true),
+        Value::Bool(b) => Kind::BoolValue(*b),
+        Value::Int(i) => Kind::IntValue(*i),
+        Value::Float(f) => Kind::FloatValue(*f),
+        Value::String(s) => Kind::StringValue(s.clone()),
+        Value::Bytes(b) => Kind::BytesValue(b.clone()),
+        Value::Vector(v) => Kind::VectorValue(proto::Vector { values: v.clone() }),
+        Value::Array(arr) => Kind::ArrayValue(proto::ValueList {
+            values: arr.iter().map(value_to_proto).collect(),
+        }),
+        Value::Object(obj) => Kind::ObjectValue(proto::ValueMap {
+            fields: props_to_proto(obj),
+        }),
+    };
+
+    proto::Value { kind: Some(kind) }
+}
+
+/// Convert a protobuf value back into a storage value
+pub fn value_from_proto(value: proto::Value) -> Value {
+    use proto::value::Kind;
+
+    match value.kind {
+        None | Some(Kind::NullValue(_)) => Value::Null,
+        Some(Kind::BoolValue(b)) => Value::Bool(b),
+        Some(Kind::IntValue(i)) => Value::Int(i),
+        Some(Kind::FloatValue(f)) => Value::Float(f),
+        Some(Kind::StringValue(s)) => Value::String(s),
+        Some(Kind::BytesValue(b)) => Value::Bytes(b),
+        Some(Kind::VectorValue(v)) => Value::Vector(v.values),
+        Some(Kind::ArrayValue(list)) => {
+            Value::Array(list.values.into_iter().map(value_from_proto).collect())
+        }
+        Some(Kind::ObjectValue(map)) => Value::Object(props_from_proto(map.fields)),
+    }
+}
+
+fn props_to_proto(props: &BTreeMap<String, Value>) -> std::collections::HashMap<String, proto::Value> {
+    props.iter().map(|(k, v)| (k.clone(), value_to_proto(v))).collect()
+}
+
+fn props_from_proto(props: std::collections::HashMap<String, proto::Value>) -> BTreeMap<String, Value> {
+    props.into_iter().map(|(k, v)| (k, value_from_proto(v))).collect()
+}
+
+fn row_to_proto(row: &[Value]) -> proto::Row {
+    proto::Row {
+        values: row.iter().map(value_to_proto).collect(),
+    }
+}
+
+/// A `QueryStream` chunk naming the columns its rows fill
+///
+/// Streamed rows leave out NULL properties, so the columns are those any
+/// row of the chunk has, in order of first appearance.
+fn query_chunk(rows: Vec<Vec<(String, Value)>>, rows_affected: u64, execution_time_ms: u64) -> proto::QueryChunk {
+    let mut columns: Vec<String> = Vec::new();
+    for row in &rows {
+        for (column, _) in row {
+            if !columns.contains(column) {
+                columns.push(column.clone());
+            }
+        }
+    }
+
+    let rows = rows.into_iter()
+        .map(|row| {
+            let mut values = vec![Value::Null; columns.len()];
+            for (column, value) in row {
+                if let Some(i) = columns.iter().position(|c| *c == column) {
+                    values[i] = value;
+                }
+            }
+            row_to_proto(&values)
+        })
+        .collect();
+
+    proto::QueryChunk { columns, rows, rows_affected, execution_time_ms }
+}
+
+fn node_to_proto(node: &Node) -> proto::Node {
+    proto::Node {
+        id: node.id.to_string(),
+        node_type: node.node_type.clone(),
+        properties: props_to_proto(&node.properties),
+        created_at: node.created_at.millis,
+        updated_at: node.updated_at.millis,
+    }
+}
+
+fn edge_to_proto(edge: &Edge) -> proto::Edge {
+    proto::Edge {
+        id: edge.id.to_string(),
+        from: edge.from.to_string(),
+        to: edge.to.to_string(),
+        edge_type: edge.edge_type.clone(),
+        properties: props_to_proto(&edge.properties),
+        created_at: edge.created_at.millis,
+    }
+}
+
+fn edge_list(edges: &[Edge]) -> proto::EdgeList {
+    proto::EdgeList {
+        edges: edges.iter().map(edge_to_proto).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::proto::aresa_db_client::AresaDbClient;
+    use crate::storage::Database;
+    use tempfile::TempDir;
+
+    async fn spawn_grpc(temp: &TempDir) -> AresaDbClient<tonic::transport::Channel> {
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(listener, tenants));
+
+        AresaDbClient::connect(format!("http://{}", addr)).await.unwrap()
+    }
+
+    #[test]
+    fn test_value_roundtrip() {
+        let value = Value::from_json(serde_json::json!({
+            "name": "Alice",
+            "age": 30,
+            "tags": ["a", "b"],
+            "embedding": {"$vector": [0.1, 0.2]},
+        })).unwrap();
+
+        assert_eq!(value_from_proto(value_to_proto(&value)), value);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_insert_and_get() {
+        let temp = TempDir::new().unwrap();
+        let mut client = spawn_grpc(&temp).await;
+
+        let mut properties = std::collections::HashMap::new();
+        properties.insert("name".to_string(), value_to_proto(&Value::String("Alice".into())));
+
+        let node = client.insert_node(proto::InsertNodeRequest {
+            node_type: "user".to_string(),
+            properties,
+        }).await.unwrap().into_inner();
+
+        let fetched = client.get_node(proto::GetNodeRequest { id: node.id.clone() })
+            .await
+            .unwrap()
+            .into_inner()
+            .node
+            .unwrap();
+        assert_eq!(fetched.node_type, "user");
+
+        let err = client.query(proto::QueryRequest {
+            sql: "SELEC nonsense".to_string(),
+            limit: None,
+            batch_size: None,
+        }).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_query_stream() {
+        let temp = TempDir::new().unwrap();
+        let mut client = spawn_grpc(&temp).await;
+
+        for i in 0..5 {
+            let mut properties = std::collections::HashMap::new();
+            properties.insert("n".to_string(), value_to_proto(&Value::Int(i)));
+            client.insert_node(proto::InsertNodeRequest {
+                node_type: "item".to_string(),
+                properties,
+            }).await.unwrap();
+        }
+
+        let mut stream = client.query_stream(proto::QueryRequest {
+            sql: "SELECT * FROM item".to_string(),
+            limit: None,
+            batch_size: Some(2),
+        }).await.unwrap().into_inner();
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.message().await.unwrap() {
+            chunks.push(chunk);
+        }
+
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.columns.contains(&"n".to_string())));
+        assert_eq!(chunks.iter().map(|c| c.rows.len()).sum::<usize>(), 5);
+        assert!(chunks.iter().all(|c| c.rows.iter().all(|r| r.values.len() == c.columns.len())));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_selects_database() {
+        let temp = TempDir::new().unwrap();
+        let default = Database::create(temp.path().join("main"), "main").await.unwrap();
+        let analytics = Database::create(temp.path().join("analytics"), "analytics").await.unwrap();
+
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(default)));
+        tenants.add("analytics", RequestHandler::new(analytics), Default::default()).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(listener, tenants));
+        let mut client = AresaDbClient::connect(format!("http://{}", addr)).await.unwrap();
+
+        fn on<T>(database: &str, message: T) -> GrpcRequest<T> {
+            let mut request = GrpcRequest::new(message);
+            request.metadata_mut().insert(DATABASE_HEADER, database.parse().unwrap());
+            request
+        }
+
+        let node = client.insert_node(on("analytics", proto::InsertNodeRequest {
+            node_type: "event".to_string(),
+            properties: Default::default(),
+        })).await.unwrap().into_inner();
+
+        let get = || proto::GetNodeRequest { id: node.id.clone() };
+        let found = client.get_node(on("analytics", get())).await.unwrap().into_inner();
+        assert!(found.node.is_some());
+        let found = client.get_node(get()).await.unwrap().into_inner();
+        assert!(found.node.is_none());
+
+        let err = client.get_node(on("missing", get())).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        assert!(err.message().contains("Database not found"));
+    }
+}
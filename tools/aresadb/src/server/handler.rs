@@ -2,25 +2,61 @@
 //!
 //! Processes incoming requests and interacts with storage.
 
-use anyhow::Result;
 use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 
+use super::cluster::ClusterView;
+use super::metrics::RequestMetrics;
 use super::protocol::{Request, Response, ErrorCode};
-use crate::storage::{Database, Node, Edge, Value};
+use super::rate_limit::KeyedRateLimiter;
+use super::settings::{Consistency, SessionSettings};
+use super::subscription::Subscription;
+use crate::auth::{Credentials, Permission, Principal, UserStore};
+use crate::query::{
+    DeadlineExceeded, ParsedQuery, PlanCacheStats, PreparedStatement, QueryEngine, QueryLog, QueryLogEntry,
+    QueryOperation, QueryParser, QueryResult, StatementCache,
+};
+use crate::rag::{ContextOptions, ContextRetriever};
+use crate::schema::SchemaManager;
+use crate::storage::{
+    Database, Node, NodeId, Edge, EdgeId, Value, DistanceMetric, ChangeEvent, CacheStats, MaintenanceStatus,
+    MemoryLimitExceeded,
+};
 use crate::distributed::ShardManager;
 
 /// Request handler for processing client requests
 pub struct RequestHandler {
     /// Database (single node mode)
-    db: Option<Database>,
+    db: Option<Arc<Database>>,
+    /// Query engine sharing the single-node database
+    engine: Option<QueryEngine>,
     /// Shard manager (distributed mode)
     shards: Option<ShardManager>,
     /// Active transactions
     transactions: RwLock<HashMap<u64, Transaction>>,
     /// Transaction ID counter
     tx_counter: AtomicU64,
+    /// Subscription ID counter
+    subscription_counter: AtomicU64,
+    /// User store; authentication is enforced when it has any users
+    users: RwLock<Option<Arc<UserStore>>>,
+    /// Cap on rows returned by queries and node listings
+    max_result_rows: Option<usize>,
+    /// Request counters and latencies
+    metrics: RequestMetrics,
+    /// Query log and the database name recorded in it
+    query_log: RwLock<Option<(Arc<QueryLog>, String)>>,
+    /// Per-credential rate limits
+    rate_limiter: RwLock<Option<Arc<KeyedRateLimiter>>>,
+    /// Replica leadership; `None` when the server isn't replicated
+    cluster: RwLock<Option<Arc<ClusterView>>>,
+    /// Prepared statements, shared by every connection
+    statements: StatementCache,
 }
 
 struct Transaction {
@@ -32,11 +68,21 @@ struct Transaction {
 impl RequestHandler {
     /// Create handler with a database
     pub fn new(db: Database) -> Self {
+        let db = Arc::new(db);
         Self {
+            engine: Some(QueryEngine::from_shared(Arc::clone(&db))),
             db: Some(db),
             shards: None,
             transactions: RwLock::new(HashMap::new()),
             tx_counter: AtomicU64::new(1),
+            subscription_counter: AtomicU64::new(1),
+            users: RwLock::new(None),
+            max_result_rows: None,
+            metrics: RequestMetrics::new(),
+            query_log: RwLock::new(None),
+            rate_limiter: RwLock::new(None),
+            cluster: RwLock::new(None),
+            statements: StatementCache::default(),
         }
     }
 
@@ -44,18 +90,46 @@ impl RequestHandler {
     pub fn with_shards(shards: ShardManager) -> Self {
         Self {
             db: None,
+            engine: None,
             shards: Some(shards),
             transactions: RwLock::new(HashMap::new()),
             tx_counter: AtomicU64::new(1),
+            subscription_counter: AtomicU64::new(1),
+            users: RwLock::new(None),
+            max_result_rows: None,
+            metrics: RequestMetrics::new(),
+            query_log: RwLock::new(None),
+            rate_limiter: RwLock::new(None),
+            cluster: RwLock::new(None),
+            statements: StatementCache::default(),
         }
     }
 
+    /// Cap the number of rows returned by queries and node listings
+    pub fn with_max_result_rows(mut self, max_rows: Option<usize>) -> Self {
+        self.max_result_rows = max_rows;
+        self
+    }
+
     /// Handle a request
     pub async fn handle(&self, request: Request) -> Response {
+        self.handle_from(&Principal::unrestricted(), None, request).await
+    }
+
+    async fn dispatch(&self, request: Request, deadline: Option<Instant>) -> Response {
         match request {
             Request::Ping => Response::Pong,
             Request::Disconnect => Response::Goodbye,
 
+            Request::Authenticate { credentials } => {
+                match self.authenticate(&credentials).await {
+                    Ok(principal) => Response::Authenticated {
+                        username: principal.username().unwrap_or("anonymous").to_string(),
+                    },
+                    Err(response) => response,
+                }
+            }
+
             Request::InsertNode { node_type, properties } => {
                 self.handle_insert_node(&node_type, properties).await
             }
@@ -93,17 +167,59 @@ impl RequestHandler {
             }
 
             Request::Query { sql, limit } => {
-                self.handle_query(&sql, limit).await
+                self.handle_query(&sql, limit, deadline).await
+            }
+
+            Request::Prepare { sql } => {
+                self.handle_prepare(&sql)
+            }
+
+            Request::Execute { statement_id, params, limit } => {
+                self.handle_execute(statement_id, &params, limit, deadline).await
+            }
+
+            Request::Deallocate { statement_id } => {
+                if self.statements.remove(statement_id) {
+                    Response::Ok
+                } else {
+                    statement_not_found(statement_id)
+                }
+            }
+
+            Request::SimilaritySearch { node_type, vector, field, k, metric } => {
+                self.handle_similarity_search(&node_type, &vector, &field, k, metric).await
             }
 
             Request::Traverse { start_id, depth, edge_types } => {
                 self.handle_traverse(&start_id, depth, edge_types).await
             }
 
+            Request::InsertWithEmbedding { node_type, properties, field, embedding } => {
+                self.handle_insert_with_embedding(&node_type, properties, &field, embedding).await
+            }
+
+            Request::RetrieveContext { vector, query, options } => {
+                self.handle_retrieve_context(&vector, &query, options).await
+            }
+
+            Request::CreateSchema { .. }
+            | Request::ListSchemas
+            | Request::DropSchema { .. }
+            | Request::Migrate => {
+                self.handle_schema(request).await
+            }
+
             Request::Status => {
                 self.handle_status().await
             }
 
+            Request::FlushPlanCache => {
+                if let Some(ref engine) = self.engine {
+                    engine.plan_cache().flush();
+                }
+                Response::Ok
+            }
+
             Request::BeginTransaction => {
                 self.handle_begin_transaction()
             }
@@ -115,9 +231,600 @@ impl RequestHandler {
             Request::RollbackTransaction { tx_id } => {
                 self.handle_rollback_transaction(tx_id)
             }
+
+            // Subscriptions and database selection are connection state;
+            // streaming transports intercept these before they reach the handler
+            Request::Subscribe { .. } | Request::Unsubscribe { .. } => {
+                Response::error(
+                    ErrorCode::InvalidRequest,
+                    "Subscriptions require a streaming connection",
+                )
+            }
+
+            Request::UseDatabase { .. } => {
+                Response::error(
+                    ErrorCode::InvalidRequest,
+                    "Database selection requires a streaming connection",
+                )
+            }
+
+            Request::Hello { protocol_version, .. } => Response::hello(protocol_version),
+
+            Request::WhoIsLeader => match *self.cluster.read() {
+                Some(ref cluster) => cluster.leader_response(),
+                None => Response::Leader { leader: None, is_leader: true },
+            },
+
+            Request::ListSessions | Request::KillQuery { .. } => {
+                Response::error(
+                    ErrorCode::InvalidRequest,
+                    "Session administration requires a streaming connection",
+                )
+            }
+
+            Request::Set { .. } | Request::Show { .. } => {
+                Response::error(
+                    ErrorCode::InvalidRequest,
+                    "Session settings require a streaming connection",
+                )
+            }
+
+            // Batches and deadlines are unpacked by `handle_from`, so these were nested
+            Request::Batch(_) => {
+                Response::error(ErrorCode::InvalidRequest, "Batches cannot be nested")
+            }
+
+            Request::WithDeadline { .. } => {
+                Response::error(ErrorCode::InvalidRequest, "Deadlines cannot be nested")
+            }
+        }
+    }
+
+    /// Handle a request on behalf of an authenticated principal
+    ///
+    /// Every operation, including SQL, is checked against the principal's
+    /// grants before it reaches storage.
+    pub async fn handle_as(&self, principal: &Principal, request: Request) -> Response {
+        self.handle_from(principal, None, request).await
+    }
+
+    /// Handle a request from a known client address
+    ///
+    /// Like `handle_as`, and records the client in the query log.
+    pub async fn handle_from(
+        &self,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+        request: Request,
+    ) -> Response {
+        self.handle_with(principal, client, &SessionSettings::default(), request).await
+    }
+
+    /// Handle a request under a connection's session settings
+    pub async fn handle_with(
+        &self,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+        settings: &SessionSettings,
+        request: Request,
+    ) -> Response {
+        let request = settings.apply(request);
+        let Request::WithDeadline { timeout_ms, request } = request else {
+            return self.handle_batch(principal, client, settings, request, None).await;
+        };
+
+        let operation = request.operation();
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(timeout_ms);
+        let handled = self.handle_batch(principal, client, settings, *request, Some(deadline));
+
+        match tokio::time::timeout_at(deadline.into(), handled).await {
+            Ok(response) => response,
+            Err(_) => {
+                self.metrics.record(operation, start.elapsed(), true);
+                Response::error(
+                    ErrorCode::Timeout,
+                    format!("Request exceeded its {} ms deadline", timeout_ms),
+                )
+            }
+        }
+    }
+
+    async fn handle_batch(
+        &self,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+        settings: &SessionSettings,
+        request: Request,
+        deadline: Option<Instant>,
+    ) -> Response {
+        let Request::Batch(requests) = request else {
+            return self.handle_one(principal, client, settings, request, deadline).await;
+        };
+
+        let start = Instant::now();
+        let mut responses = Vec::with_capacity(requests.len());
+        for request in requests {
+            let response = if request.is_batchable() {
+                self.handle_one(principal, client, settings, request, deadline).await
+            } else {
+                Response::error(
+                    ErrorCode::InvalidRequest,
+                    format!("{} is not allowed in a batch", request.operation()),
+                )
+            };
+            responses.push(response);
+        }
+
+        self.metrics.record("batch", start.elapsed(), false);
+        Response::Batch(responses)
+    }
+
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(operation = request.operation(), otel.kind = "server", error = tracing::field::Empty),
+    )]
+    async fn handle_one(
+        &self,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+        settings: &SessionSettings,
+        request: Request,
+        deadline: Option<Instant>,
+    ) -> Response {
+        let operation = request.operation();
+        let statement = match request {
+            Request::Execute { statement_id, .. } => self.statements.get(statement_id),
+            _ => None,
+        };
+        let sql = match request {
+            _ if self.query_log.read().is_none() => None,
+            Request::Query { ref sql, .. } => Some(sql.clone()),
+            Request::Execute { .. } => statement.as_ref().map(|statement| statement.sql.clone()),
+            _ => None,
+        };
+        let start = Instant::now();
+        let response = match self.admit(principal, settings, &request, statement.as_deref()).await {
+            Err(response) => response,
+            Ok(()) if principal.is_unrestricted() => self.dispatch(request, deadline).await,
+            Ok(()) => match self.dispatch(request, deadline).await {
+                // Edge listings and traversals may span types; hide the
+                // ones the caller can't read
+                Response::Edges(edges) => Response::Edges(
+                    edges.into_iter()
+                        .filter(|e| principal.can(Permission::Read, &e.edge_type))
+                        .collect(),
+                ),
+                Response::Schemas(schemas) => Response::Schemas(
+                    schemas.into_iter()
+                        .filter(|s| principal.can(Permission::Read, &s.name))
+                        .collect(),
+                ),
+                Response::TraversalResult { nodes, edges, depth } => Response::TraversalResult {
+                    nodes: nodes.into_iter()
+                        .filter(|n| principal.can(Permission::Read, &n.node_type))
+                        .collect(),
+                    edges: edges.into_iter()
+                        .filter(|e| principal.can(Permission::Read, &e.edge_type))
+                        .collect(),
+                    depth,
+                },
+                response => response,
+            },
+        };
+
+        let (response, stats) = response.split_stats();
+        let elapsed = start.elapsed();
+        self.metrics.record(operation, elapsed, response.is_error());
+        if let Response::Error { code, message } = &response {
+            tracing::Span::current().record("error", format!("{:?}: {}", code, message));
+        }
+        if let Some(sql) = sql {
+            let (rows, error) = match &response {
+                Response::QueryResult { rows, rows_affected, .. } if rows.is_empty() => (*rows_affected, None),
+                Response::QueryResult { rows, .. } => (rows.len() as u64, None),
+                Response::Error { message, .. } => (0, Some(message.clone())),
+                _ => (0, None),
+            };
+            self.log_query(sql, elapsed, rows, error, principal, client);
+        }
+        match stats {
+            Some(stats) if settings.timing => Response::Stats { response: Box::new(response), stats },
+            _ => response,
+        }
+    }
+
+    /// Run a SQL query, handing each row to `on_row` as it is read instead
+    /// of collecting the rows into a `Response::QueryResult`
+    ///
+    /// Replica leadership, permissions, the row cap, metrics and the query
+    /// log apply as they do to `Request::Query`. Returns the number of rows
+    /// a write affected.
+    pub async fn stream_query<F>(
+        &self,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+        sql: &str,
+        limit: Option<usize>,
+        mut on_row: F,
+    ) -> Result<u64, Response>
+    where
+        F: FnMut(Vec<(String, Value)>) -> anyhow::Result<()>,
+    {
+        let request = Request::Query { sql: sql.to_string(), limit };
+        let start = Instant::now();
+        let mut rows = 0;
+
+        let result = match self.admit(principal, &SessionSettings::default(), &request, None).await {
+            Err(response) => Err(response),
+            Ok(()) => match self.engine {
+                None => Err(Response::error(
+                    ErrorCode::InternalError,
+                    "SQL queries are not supported in sharded mode",
+                )),
+                Some(ref engine) => {
+                    // Reads stop scanning at the cap; a write still touches
+                    // every row it matches and only its returned rows are cut
+                    let max = self.max_result_rows;
+                    let limit = match max {
+                        Some(max) if !request.is_write() => Some(limit.map_or(max, |l| l.min(max))),
+                        _ => limit,
+                    };
+                    let streamed = engine.stream_sql(sql, limit, |row| {
+                        if max.is_some_and(|max| rows >= max as u64) {
+                            return Ok(());
+                        }
+                        rows += 1;
+                        on_row(row)
+                    });
+                    streamed.await.map_err(query_error)
+                }
+            },
+        };
+
+        let elapsed = start.elapsed();
+        self.metrics.record(request.operation(), elapsed, result.is_err());
+        let (logged_rows, error) = match &result {
+            Ok(affected) if rows == 0 => (*affected, None),
+            Ok(_) => (rows, None),
+            Err(Response::Error { message, .. }) => (0, Some(message.clone())),
+            Err(_) => (0, None),
+        };
+        self.log_query(sql.to_string(), elapsed, logged_rows, error, principal, client);
+        result
+    }
+
+    /// Record queries to a log under the given database name
+    pub fn set_query_log(&self, log: Option<Arc<QueryLog>>, database: &str) {
+        *self.query_log.write() = log.map(|log| (log, database.to_string()));
+    }
+
+    fn log_query(
+        &self,
+        sql: String,
+        elapsed: std::time::Duration,
+        rows: u64,
+        error: Option<String>,
+        principal: &Principal,
+        client: Option<SocketAddr>,
+    ) {
+        let Some((ref log, ref database)) = *self.query_log.read() else {
+            return;
+        };
+
+        let entry = QueryLogEntry {
+            timestamp: crate::storage::Timestamp::now(),
+            database: database.clone(),
+            client: client.map(|addr| addr.to_string()),
+            user: principal.username().map(str::to_string),
+            query: sql,
+            duration_ms: elapsed.as_secs_f64() * 1000.0,
+            rows,
+            error,
+        };
+
+        if let Err(e) = log.record(&entry) {
+            tracing::warn!("Failed to write query log: {}", e);
+        }
+    }
+
+    /// Request counters and latencies
+    pub fn metrics(&self) -> &RequestMetrics {
+        &self.metrics
+    }
+
+    /// Transactions begun and not yet committed or rolled back
+    pub fn active_transactions(&self) -> usize {
+        self.transactions.read().len()
+    }
+
+    /// Cache statistics (single node mode only)
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.db.as_ref().map(|db| db.cache_stats())
+    }
+
+    /// Run maintenance on the database in the background (single node mode only)
+    pub fn start_maintenance(&self) -> Option<tokio::task::JoinHandle<()>> {
+        self.db.as_ref().map(|db| db.start_maintenance())
+    }
+
+    /// Maintenance status (single node mode only)
+    pub fn maintenance_status(&self) -> Option<MaintenanceStatus> {
+        self.db.as_ref().map(|db| db.maintenance_status())
+    }
+
+    /// Plan cache statistics (single node mode only)
+    pub fn plan_cache_stats(&self) -> Option<PlanCacheStats> {
+        self.engine.as_ref().map(|engine| engine.plan_cache().stats())
+    }
+
+    /// Node count per shard; a single-node database reports as shard 0
+    pub async fn shard_node_counts(&self) -> Vec<(usize, u64)> {
+        if let Some(ref db) = self.db {
+            match db.status().await {
+                Ok(status) => vec![(0, status.node_count)],
+                Err(_) => Vec::new(),
+            }
+        } else if let Some(ref shards) = self.shards {
+            match shards.stats().await {
+                Ok(stats) => stats.shards.iter().map(|s| (s.id, s.node_count)).collect(),
+                Err(_) => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Refuse a request this replica or the caller may not run
+    ///
+    /// Followers take no writes, and reads only at eventual consistency.
+    /// An unknown statement is refused as such wherever it is sent.
+    async fn admit(
+        &self,
+        principal: &Principal,
+        settings: &SessionSettings,
+        request: &Request,
+        statement: Option<&PreparedStatement>,
+    ) -> Result<(), Response> {
+        let follower = self.cluster.read().clone().filter(|cluster| !cluster.is_leader());
+        let is_write = match (request, statement) {
+            (Request::Execute { .. }, Some(statement)) => is_write_query(&statement.query),
+            (Request::Execute { .. }, None) => false,
+            _ => request.is_write(),
+        };
+        let leader_only = is_write
+            || (settings.consistency == Consistency::Strong && !request.is_public());
+
+        if let Some(cluster) = follower.filter(|_| leader_only) {
+            return Err(cluster.not_leader());
+        }
+        if principal.is_unrestricted() {
+            return Ok(());
+        }
+        self.authorize(principal, request, statement).await
+    }
+
+    /// Check that a principal may perform a request
+    ///
+    /// `statement` is the prepared statement a `Request::Execute` runs.
+    async fn authorize(
+        &self,
+        principal: &Principal,
+        request: &Request,
+        statement: Option<&PreparedStatement>,
+    ) -> Result<(), Response> {
+        let check = |permission: Permission, type_name: &str| {
+            if principal.can(permission, type_name) {
+                Ok(())
+            } else {
+                Err(Response::error(
+                    ErrorCode::PermissionDenied,
+                    format!("Permission denied: {} {}", permission, type_name),
+                ))
+            }
+        };
+        // The target needs the statement's permission; every other type it
+        // touches (joins, subqueries, INSERT ... SELECT) needs read
+        let check_query = |query: &ParsedQuery| {
+            check(query_permission(query), &query.target)?;
+            query.reads.iter().try_for_each(|type_name| check(Permission::Read, type_name))
+        };
+
+        match request {
+            Request::InsertNode { node_type, .. }
+            | Request::InsertWithEmbedding { node_type, .. } => check(Permission::Write, node_type),
+
+            Request::RetrieveContext { options, .. } => check(Permission::Read, &options.node_type),
+
+            Request::CreateSchema { name, .. } | Request::DropSchema { name, .. } => {
+                check(Permission::Write, name)
+            }
+
+            Request::Migrate if !principal.is_admin() => {
+                Err(Response::error(ErrorCode::PermissionDenied, "Migrations require admin"))
+            }
+
+            Request::FlushPlanCache if !principal.is_admin() => {
+                Err(Response::error(ErrorCode::PermissionDenied, "Flushing the plan cache requires admin"))
+            }
+
+
+            Request::GetNodesByType { node_type, .. }
+            | Request::SimilaritySearch { node_type, .. } => check(Permission::Read, node_type),
+
+            Request::GetNode { id } | Request::Traverse { start_id: id, .. } => {
+                match self.lookup_node_type(id).await {
+                    Some(node_type) => check(Permission::Read, &node_type),
+                    None => Ok(()),
+                }
+            }
+
+            Request::UpdateNode { id, .. } | Request::DeleteNode { id } => {
+                match self.lookup_node_type(id).await {
+                    Some(node_type) => check(Permission::Write, &node_type),
+                    None => Ok(()),
+                }
+            }
+
+            Request::CreateEdge { edge_type, .. } => check(Permission::Write, edge_type),
+
+            Request::DeleteEdge { edge_id } => {
+                match self.lookup_edge_type(edge_id).await {
+                    Some(edge_type) => check(Permission::Write, &edge_type),
+                    None => Ok(()),
+                }
+            }
+
+            // SQL that can't be parsed can't be checked, so it is refused
+            // here rather than left to the engine
+            Request::Query { sql, .. } => match QueryParser::new().parse(sql) {
+                Ok(query) => check_query(&query),
+                Err(e) => Err(query_error(e)),
+            },
+
+            // Unknown statements are rejected by dispatch
+            Request::Execute { .. } => match statement {
+                Some(statement) => check_query(&statement.query),
+                None => Ok(()),
+            },
+
+            // Edge listings are filtered after the fact; everything else is
+            // connection-level and open to any authenticated principal
+            _ => Ok(()),
+        }
+    }
+
+    async fn lookup_node_type(&self, id: &str) -> Option<String> {
+        let node = if let Some(ref db) = self.db {
+            db.get_node(id).await.ok()?
+        } else if let Some(ref shards) = self.shards {
+            shards.get_node(&NodeId::parse(id).ok()?).await.ok()?
+        } else {
+            None
+        };
+        node.map(|n| n.node_type)
+    }
+
+    async fn lookup_edge_type(&self, id: &str) -> Option<String> {
+        let db = self.db.as_ref()?;
+        let edge = db.local().get_edge(&EdgeId::parse(id).ok()?).await.ok()??;
+        Some(edge.edge_type)
+    }
+
+    /// Attach a user store, enabling authentication if it has users
+    pub fn set_user_store(&self, users: Option<Arc<UserStore>>) {
+        *self.users.write() = users;
+    }
+
+    /// Whether clients must authenticate before issuing requests
+    pub fn auth_required(&self) -> bool {
+        self.users.read().as_ref().is_some_and(|users| !users.is_empty())
+    }
+
+    /// Verify credentials
+    ///
+    /// Returns the principal to act as; unrestricted when authentication is
+    /// disabled. Argon2 takes tens of milliseconds, so the check runs on
+    /// the blocking pool rather than a runtime worker.
+    pub async fn authenticate(&self, credentials: &Credentials) -> Result<Principal, Response> {
+        if !self.auth_required() {
+            return Ok(Principal::unrestricted());
+        }
+
+        let Some(users) = self.users.read().clone() else {
+            return Err(Response::error(ErrorCode::Unauthenticated, "Invalid credentials"));
+        };
+        let credentials = credentials.clone();
+        tokio::task::spawn_blocking(move || users.authenticate(&credentials))
+            .await
+            .map_err(|e| Response::error(ErrorCode::InternalError, format!("Authentication failed: {}", e)))?
+            .ok_or_else(|| Response::error(ErrorCode::Unauthenticated, "Invalid credentials"))
+    }
+
+    /// Verify an HTTP `Authorization: Bearer <api key>` header value
+    pub fn authenticate_bearer(&self, header: Option<&str>) -> Result<Principal, Response> {
+        if !self.auth_required() {
+            return Ok(Principal::unrestricted());
+        }
+
+        // API keys are checked by SHA-256 digest, cheap enough to run inline
+        match header.and_then(|h| h.strip_prefix("Bearer ")) {
+            Some(key) => self.users.read().clone()
+                .and_then(|users| users.authenticate(&Credentials::ApiKey { key: key.trim().to_string() }))
+                .ok_or_else(|| Response::error(ErrorCode::Unauthenticated, "Invalid credentials")),
+            None => Err(Response::error(ErrorCode::Unauthenticated, "Authentication required")),
         }
     }
 
+    /// Report replica leadership and refuse writes while following
+    pub fn set_cluster(&self, cluster: Option<Arc<ClusterView>>) {
+        *self.cluster.write() = cluster;
+    }
+
+    /// Enforce per-credential rate limits
+    pub fn set_rate_limiter(&self, limiter: Option<Arc<KeyedRateLimiter>>) {
+        *self.rate_limiter.write() = limiter;
+    }
+
+    /// Admit `requests` requests of `bytes` bytes in all against the
+    /// principal's credential; a batch counts each of its parts
+    ///
+    /// Unauthenticated principals have no credential and are only subject
+    /// to per-connection limits.
+    pub fn check_rate_limit(&self, principal: &Principal, requests: u64, bytes: u64) -> Result<(), Response> {
+        let (Some(limiter), Some(credential)) = (self.rate_limiter.read().clone(), principal.credential()) else {
+            return Ok(());
+        };
+
+        if limiter.try_acquire_many(credential, requests, bytes) {
+            Ok(())
+        } else {
+            Err(Response::error(ErrorCode::RateLimited, "Rate limit exceeded for credential"))
+        }
+    }
+
+    /// Charge response bytes to the principal's credential
+    pub fn charge_rate_limit(&self, principal: &Principal, bytes: u64) {
+        if let (Some(limiter), Some(credential)) = (self.rate_limiter.read().as_ref(), principal.credential()) {
+            limiter.charge_bytes(credential, bytes);
+        }
+    }
+
+    /// Attach to the database change feed (single node mode only)
+    pub fn subscribe_changes(&self) -> Option<broadcast::Receiver<ChangeEvent>> {
+        self.db.as_ref().map(|db| db.subscribe_changes())
+    }
+
+    /// Validate a subscription request and allocate its ID
+    ///
+    /// Subscribing to every type requires a wildcard read grant.
+    pub fn create_subscription(
+        &self,
+        principal: &Principal,
+        node_type: Option<String>,
+        predicate: Option<&str>,
+    ) -> Result<Subscription, Response> {
+        let target = node_type.as_deref().unwrap_or("*");
+        if !principal.can(Permission::Read, target) {
+            return Err(Response::error(
+                ErrorCode::PermissionDenied,
+                format!("Permission denied: read {}", target),
+            ));
+        }
+
+        if self.db.is_none() {
+            return Err(Response::error(
+                ErrorCode::InvalidRequest,
+                "Subscriptions are not supported in sharded mode",
+            ));
+        }
+
+        let id = self.subscription_counter.fetch_add(1, Ordering::SeqCst);
+        Subscription::new(id, node_type, predicate)
+            .map_err(|e| Response::error(ErrorCode::QueryParseError, e.to_string()))
+    }
+
     async fn handle_insert_node(&self, node_type: &str, properties: Value) -> Response {
         let props_json = properties.to_json();
 
@@ -193,6 +900,11 @@ impl RequestHandler {
     }
 
     async fn handle_get_nodes_by_type(&self, node_type: &str, limit: Option<usize>) -> Response {
+        let limit = match (limit, self.max_result_rows) {
+            (Some(limit), Some(max)) => Some(limit.min(max)),
+            (limit, max) => limit.or(max),
+        };
+
         let result = if let Some(ref db) = self.db {
             db.get_all_by_type(node_type, limit).await
         } else if let Some(ref shards) = self.shards {
@@ -214,7 +926,7 @@ impl RequestHandler {
         edge_type: &str,
         properties: Option<Value>,
     ) -> Response {
-        let props_json = properties.map(|p| p.to_json());
+        let props_json = properties.as_ref().map(|p| p.to_json());
 
         let result = if let Some(ref db) = self.db {
             db.create_edge(from_id, to_id, edge_type, props_json).await
@@ -270,24 +982,185 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_delete_edge(&self, _edge_id: &str) -> Response {
-        // TODO: Implement edge deletion
-        Response::error(ErrorCode::InternalError, "Not implemented")
+    async fn handle_delete_edge(&self, edge_id: &str) -> Response {
+        let result = if let Some(ref db) = self.db {
+            db.delete_edge(edge_id).await
+        } else {
+            return Response::error(ErrorCode::InternalError, "Sharded mode doesn't support edge deletion");
+        };
+
+        match result {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::error(ErrorCode::EdgeNotFound, e.to_string()),
+        }
+    }
+
+    async fn handle_query(&self, sql: &str, limit: Option<usize>, deadline: Option<Instant>) -> Response {
+        let Some(ref engine) = self.engine else {
+            return Response::error(ErrorCode::InternalError, "SQL queries are not supported in sharded mode");
+        };
+
+        self.query_response(engine.execute_sql_until(sql, limit, deadline).await)
+    }
+
+    fn handle_prepare(&self, sql: &str) -> Response {
+        let Some(ref engine) = self.engine else {
+            return Response::error(ErrorCode::InternalError, "SQL queries are not supported in sharded mode");
+        };
+
+        let (statement_id, statement) = match self.statements.find(sql) {
+            Some(cached) => cached,
+            None => match engine.prepare(sql) {
+                Ok(statement) => self.statements.insert(statement),
+                Err(e) => return query_error(e),
+            },
+        };
+        Response::Prepared {
+            statement_id,
+            param_count: statement.param_count(),
+        }
+    }
+
+    async fn handle_execute(
+        &self,
+        statement_id: u64,
+        params: &[Value],
+        limit: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Response {
+        let Some(ref engine) = self.engine else {
+            return Response::error(ErrorCode::InternalError, "SQL queries are not supported in sharded mode");
+        };
+        let Some(statement) = self.statements.get(statement_id) else {
+            return statement_not_found(statement_id);
+        };
+
+        if params.len() != statement.param_count() {
+            return Response::error(
+                ErrorCode::InvalidRequest,
+                format!("Statement takes {} parameters, got {}", statement.param_count(), params.len()),
+            );
+        }
+        self.query_response(engine.execute_prepared(&statement, params, limit, deadline).await)
     }
 
-    async fn handle_query(&self, _sql: &str, _limit: Option<usize>) -> Response {
-        // TODO: Implement query execution
-        Response::error(ErrorCode::InternalError, "Query execution not implemented")
+    fn query_response(&self, result: anyhow::Result<QueryResult>) -> Response {
+        match result {
+            Ok(mut result) => {
+                // Truncate rather than pass the cap down, which would also
+                // bound how many rows an UPDATE or DELETE touches
+                if let Some(max) = self.max_result_rows {
+                    result.rows.truncate(max);
+                }
+                let response = Response::QueryResult {
+                    columns: result.columns,
+                    rows: result.rows,
+                    rows_affected: result.rows_affected,
+                    execution_time_ms: result.execution_time_ms,
+                };
+                Response::Stats { response: Box::new(response), stats: result.stats }
+            }
+            Err(e) => query_error(e),
+        }
+    }
+
+    async fn handle_similarity_search(
+        &self,
+        node_type: &str,
+        vector: &[f32],
+        field: &str,
+        k: usize,
+        metric: DistanceMetric,
+    ) -> Response {
+        let Some(ref db) = self.db else {
+            return Response::error(ErrorCode::InternalError, "Similarity search is not supported in sharded mode");
+        };
+
+        match db.similarity_search(vector, node_type, field, k, metric).await {
+            Ok(results) => Response::SimilarityResults(results),
+            Err(e) => Response::error(ErrorCode::QueryExecutionError, e.to_string()),
+        }
     }
 
     async fn handle_traverse(
         &self,
-        _start_id: &str,
-        _depth: u32,
-        _edge_types: Option<Vec<String>>,
+        start_id: &str,
+        depth: u32,
+        edge_types: Option<Vec<String>>,
     ) -> Response {
-        // TODO: Implement traversal
-        Response::error(ErrorCode::InternalError, "Traversal not implemented")
+        let (Some(engine), Some(db)) = (&self.engine, &self.db) else {
+            return Response::error(ErrorCode::InternalError, "Traversal is not supported in sharded mode");
+        };
+        if !matches!(db.get_node(start_id).await, Ok(Some(_))) {
+            return Response::error(ErrorCode::NodeNotFound, format!("Node not found: {}", start_id));
+        }
+
+        let edge_types: Option<Vec<&str>> = edge_types.as_ref().map(|types| types.iter().map(String::as_str).collect());
+        match engine.traverse(start_id, depth, edge_types).await {
+            Ok(result) => Response::TraversalResult {
+                nodes: result.nodes,
+                edges: result.edges,
+                depth: result.depth,
+            },
+            Err(e) => Response::error(ErrorCode::QueryExecutionError, e.to_string()),
+        }
+    }
+
+    async fn handle_insert_with_embedding(
+        &self,
+        node_type: &str,
+        properties: Value,
+        field: &str,
+        embedding: Vec<f32>,
+    ) -> Response {
+        let result = if let Some(ref db) = self.db {
+            db.insert_with_embedding(node_type, properties.to_json(), field, embedding).await
+        } else if let Some(ref shards) = self.shards {
+            let mut properties = properties;
+            if let Value::Object(ref mut map) = properties {
+                map.insert(field.to_string(), Value::Vector(embedding));
+            }
+            let node = Node::new(node_type, properties);
+            shards.insert_node(&node).await.map(|_| node)
+        } else {
+            return Response::error(ErrorCode::InternalError, "No storage configured");
+        };
+
+        match result {
+            Ok(node) => Response::Node(node),
+            Err(e) => Response::error(ErrorCode::InternalError, e.to_string()),
+        }
+    }
+
+    async fn handle_retrieve_context(&self, vector: &[f32], query: &str, options: ContextOptions) -> Response {
+        let Some(ref db) = self.db else {
+            return Response::error(ErrorCode::InternalError, "Context retrieval is not supported in sharded mode");
+        };
+
+        match ContextRetriever::with_options(db, options).retrieve(vector, query).await {
+            Ok(context) => Response::Context(context),
+            Err(e) => Response::error(ErrorCode::QueryExecutionError, e.to_string()),
+        }
+    }
+
+    async fn handle_schema(&self, request: Request) -> Response {
+        let Some(ref db) = self.db else {
+            return Response::error(ErrorCode::InternalError, "Schema management is not supported in sharded mode");
+        };
+
+        let manager = SchemaManager::from_shared(Arc::clone(db));
+        let result = match request {
+            Request::CreateSchema { name, fields } => manager.create_schema(&name, &fields).await.map(Response::Schema),
+            Request::ListSchemas => manager.list_schemas().await.map(Response::Schemas),
+            Request::DropSchema { name, force } => manager.drop_schema(&name, force).await.map(|()| Response::Ok),
+            Request::Migrate => manager.run_migrations().await.map(Response::Migrations),
+            _ => return Response::error(ErrorCode::InvalidRequest, "Not a schema request"),
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(e) => Response::error(ErrorCode::QueryExecutionError, e.to_string()),
+        }
     }
 
     async fn handle_status(&self) -> Response {
@@ -341,6 +1214,42 @@ impl RequestHandler {
     }
 }
 
+/// Permission a query needs on its target
+fn query_permission(query: &ParsedQuery) -> Permission {
+    if is_write_query(query) {
+        Permission::Write
+    } else {
+        Permission::Read
+    }
+}
+
+fn is_write_query(query: &ParsedQuery) -> bool {
+    !matches!(
+        query.operation,
+        QueryOperation::Select | QueryOperation::Traverse | QueryOperation::VectorSearch
+    )
+}
+
+fn query_error(e: anyhow::Error) -> Response {
+    let code = if e.downcast_ref::<sqlparser::parser::ParserError>().is_some() {
+        ErrorCode::QueryParseError
+    } else if e.is::<DeadlineExceeded>() {
+        ErrorCode::Timeout
+    } else if e.is::<MemoryLimitExceeded>() {
+        ErrorCode::MemoryLimitExceeded
+    } else {
+        ErrorCode::QueryExecutionError
+    };
+    Response::error(code, e.to_string())
+}
+
+fn statement_not_found(statement_id: u64) -> Response {
+    Response::error(
+        ErrorCode::StatementNotFound,
+        format!("Prepared statement {} not found", statement_id),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -356,6 +1265,48 @@ mod tests {
         assert!(matches!(response, Response::Pong));
     }
 
+    #[tokio::test]
+    async fn test_handler_batch() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let handler = RequestHandler::new(db);
+
+        let response = handler.handle(Request::Batch(vec![
+            Request::InsertNode {
+                node_type: "user".to_string(),
+                properties: Value::from_json(serde_json::json!({"name": "Alice"})).unwrap(),
+            },
+            Request::GetNode { id: "missing".to_string() },
+            Request::UseDatabase { name: "other".to_string() },
+            Request::Batch(vec![Request::Ping]),
+            Request::Ping,
+        ])).await;
+
+        let Response::Batch(responses) = response else {
+            panic!("Expected Batch response");
+        };
+        assert_eq!(responses.len(), 5);
+        assert!(matches!(responses[0], Response::Node(_)));
+        assert!(matches!(responses[2], Response::Error { code: ErrorCode::InvalidRequest, .. }));
+        assert!(matches!(responses[3], Response::Error { code: ErrorCode::InvalidRequest, .. }));
+        assert!(matches!(responses[4], Response::Pong));
+        assert_eq!(handler.metrics().request_count("insert_node"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handler_deadline() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let handler = RequestHandler::new(db);
+
+        let query = || Box::new(Request::Query { sql: "SELECT * FROM user".to_string(), limit: None });
+        let response = handler.handle(Request::WithDeadline { timeout_ms: 0, request: query() }).await;
+        assert!(matches!(response, Response::Error { code: ErrorCode::Timeout, .. }));
+
+        let response = handler.handle(Request::WithDeadline { timeout_ms: 60_000, request: query() }).await;
+        assert!(matches!(response, Response::QueryResult { .. }));
+    }
+
     #[tokio::test]
     async fn test_handler_insert_get_node() {
         let temp = TempDir::new().unwrap();
@@ -400,4 +1351,115 @@ mod tests {
         let response = handler.handle(Request::CommitTransaction { tx_id }).await;
         assert!(matches!(response, Response::TransactionCommitted));
     }
+
+    #[tokio::test]
+    async fn test_handler_read_only_principal() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let handler = RequestHandler::new(db);
+
+        let node_id = match handler.handle(Request::InsertNode {
+            node_type: "users".to_string(),
+            properties: Value::from_json(serde_json::json!({"name": "Alice"})).unwrap(),
+        }).await {
+            Response::Node(node) => node.id.to_string(),
+            other => panic!("Unexpected response: {:?}", other),
+        };
+
+        let analyst = Principal::user("ana", vec!["read users".parse().unwrap()]);
+        let denied = |response: Response| {
+            matches!(response, Response::Error { code: ErrorCode::PermissionDenied, .. })
+        };
+
+        let response = handler.handle_as(&analyst, Request::GetNode { id: node_id.clone() }).await;
+        assert!(matches!(response, Response::MaybeNode(Some(_))));
+        let response = handler.handle_as(&analyst, Request::Query {
+            sql: "SELECT * FROM users".to_string(),
+            limit: None,
+        }).await;
+        assert!(matches!(response, Response::QueryResult { .. }));
+
+        assert!(denied(handler.handle_as(&analyst, Request::DeleteNode { id: node_id.clone() }).await));
+        assert!(denied(handler.handle_as(&analyst, Request::Query {
+            sql: "DELETE FROM users WHERE name = 'Alice'".to_string(),
+            limit: None,
+        }).await));
+        assert!(denied(handler.handle_as(&analyst, Request::Query {
+            sql: "SELECT * FROM orders".to_string(),
+            limit: None,
+        }).await));
+        assert!(denied(handler.handle_as(&analyst, Request::InsertNode {
+            node_type: "users".to_string(),
+            properties: Value::Null,
+        }).await));
+        assert!(handler.create_subscription(&analyst, None, None).is_err());
+        assert!(denied(handler.handle_as(&analyst, Request::CreateSchema {
+            name: "users".to_string(),
+            fields: "name:string".to_string(),
+        }).await));
+        assert!(denied(handler.handle_as(&analyst, Request::Migrate).await));
+
+        let response = handler.handle_as(&analyst, Request::GetNode { id: node_id }).await;
+        assert!(matches!(response, Response::MaybeNode(Some(_))));
+    }
+
+    #[tokio::test]
+    async fn test_handler_query_reads_every_type() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let handler = RequestHandler::new(db);
+
+        let analyst = Principal::user("ana", vec!["read users".parse().unwrap()]);
+        let denied = |response: Response| {
+            matches!(response, Response::Error { code: ErrorCode::PermissionDenied, .. })
+        };
+        let query = |sql: &str| Request::Query { sql: sql.to_string(), limit: None };
+
+        assert!(denied(handler.handle_as(&analyst,
+            query("SELECT * FROM users JOIN orders ON users.id = orders.user_id")).await));
+        assert!(denied(handler.handle_as(&analyst,
+            query("SELECT * FROM users WHERE id IN (SELECT user_id FROM orders)")).await));
+        assert!(denied(handler.handle_as(&analyst,
+            query("SELECT * FROM (SELECT * FROM orders) AS o")).await));
+
+        // Writing users from orders needs write on users and read on orders
+        let writer = Principal::user("wes", vec!["write users".parse().unwrap()]);
+        assert!(denied(handler.handle_as(&writer,
+            query("INSERT INTO users SELECT * FROM orders")).await));
+
+        // Unparseable SQL can't be checked, so it never reaches the engine
+        let response = handler.handle_as(&analyst, query("SELEC * FROM orders")).await;
+        assert!(matches!(response, Response::Error { code: ErrorCode::QueryParseError, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_handler_query_log() {
+        use crate::query::{log, QueryLogConfig};
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let handler = RequestHandler::new(db);
+
+        let config = QueryLogConfig { log_all: true, slow_threshold: None };
+        let query_log = Arc::new(QueryLog::open(temp.path().join("logs"), config).unwrap());
+        handler.set_query_log(Some(Arc::clone(&query_log)), "main");
+
+        let principal = Principal::user("ana", vec!["write *".parse().unwrap()]);
+        let client: SocketAddr = "10.0.0.1:4000".parse().unwrap();
+        handler.handle_from(&principal, Some(client), Request::Query {
+            sql: "INSERT INTO users (name) VALUES ('Alice')".to_string(),
+            limit: None,
+        }).await;
+        handler.handle(Request::Query { sql: "SELEC nonsense".to_string(), limit: None }).await;
+        handler.handle(Request::Status).await;
+        query_log.flush().unwrap();
+
+        let entries = log::tail(temp.path().join("logs").join(log::QUERY_LOG_FILE), 10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].database, "main");
+        assert_eq!(entries[0].user.as_deref(), Some("ana"));
+        assert_eq!(entries[0].client.as_deref(), Some("10.0.0.1:4000"));
+        assert_eq!(entries[0].rows, 1);
+        assert!(entries[1].error.is_some());
+    }
 }
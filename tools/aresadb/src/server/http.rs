@@ -0,0 +1,658 @@
+//! HTTP REST API
+//!
+//! JSON endpoints served alongside the binary TCP protocol. Every route is
+//! translated into a protocol `Request` and dispatched through the same
+//! `RequestHandler`, so both surfaces share one set of semantics. Requests
+//! go to the default database unless an `x-aresadb-database` header names
+//! another hosted one.
+
+use anyhow::{Result, Context};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, Path, Query, Request as HttpRequest, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tracing::info;
+
+use super::handler::RequestHandler;
+use super::settings::SessionSettings;
+use super::protocol::{Request, Response, ErrorCode};
+use super::subscription::{Subscription, SubscriptionSet};
+use super::tenant::{TenantRegistry, DATABASE_HEADER};
+use crate::auth::Principal;
+use crate::storage::{ChangeEvent, ChangeRecord, DistanceMetric, Value};
+
+type ApiResponse = (StatusCode, Json<serde_json::Value>);
+
+/// Body for `POST /query`
+#[derive(Debug, Deserialize)]
+struct QueryBody {
+    sql: String,
+    limit: Option<usize>,
+}
+
+/// Body for `POST /nodes`
+#[derive(Debug, Deserialize)]
+struct InsertNodeBody {
+    node_type: String,
+    #[serde(default)]
+    properties: serde_json::Value,
+}
+
+/// Body for `POST /edges`
+#[derive(Debug, Deserialize)]
+struct CreateEdgeBody {
+    from_id: String,
+    to_id: String,
+    edge_type: String,
+    properties: Option<serde_json::Value>,
+}
+
+/// Body for `POST /search`
+#[derive(Debug, Deserialize)]
+struct SearchBody {
+    node_type: String,
+    vector: Vec<f32>,
+    #[serde(default = "default_field")]
+    field: String,
+    #[serde(default = "default_k")]
+    k: usize,
+    #[serde(default = "default_metric")]
+    metric: DistanceMetric,
+}
+
+/// Query parameters for `GET /subscribe`
+#[derive(Debug, Deserialize)]
+struct SubscribeParams {
+    node_type: Option<String>,
+    predicate: Option<String>,
+}
+
+fn default_field() -> String {
+    "embedding".to_string()
+}
+
+fn default_k() -> usize {
+    10
+}
+
+fn default_metric() -> DistanceMetric {
+    DistanceMetric::Cosine
+}
+
+/// Build the REST router over the hosted databases
+///
+/// When authentication is required, every route except `/health` expects
+/// an `Authorization: Bearer <api key>` header.
+pub fn router(tenants: Arc<TenantRegistry>) -> Router {
+    let protected = Router::new()
+        .route("/status", get(status))
+        .route("/query", post(query))
+        .route("/nodes", post(insert_node))
+        .route("/nodes/:id", get(get_node).delete(delete_node))
+        .route("/edges", post(create_edge))
+        .route("/search", post(search))
+        .route("/subscribe", get(subscribe))
+        .route_layer(middleware::from_fn_with_state(tenants, require_auth));
+
+    Router::new()
+        .route("/health", get(health))
+        .merge(protected)
+}
+
+/// Bind to an address and serve the REST API until the task is dropped
+pub async fn serve(addr: SocketAddr, tenants: Arc<TenantRegistry>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context("Failed to bind HTTP listener")?;
+    serve_listener(listener, tenants).await
+}
+
+/// Serve the REST API on an already bound listener
+pub async fn serve_listener(listener: TcpListener, tenants: Arc<TenantRegistry>) -> Result<()> {
+    info!("AresaDB HTTP API listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(tenants).into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .context("HTTP server error")
+}
+
+/// Authenticate the caller and pick the database the request goes to,
+/// holding a connection slot on it while the request runs
+async fn require_auth(
+    State(tenants): State<Arc<TenantRegistry>>,
+    request: HttpRequest,
+    next: Next,
+) -> axum::response::Response {
+    let header = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+
+    let content_length = request.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+
+    let database = request.headers()
+        .get(DATABASE_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    match tenants.admit_request(database, header, content_length) {
+        Ok((principal, permit)) => {
+            let mut request = request;
+            request.extensions_mut().insert(principal);
+            request.extensions_mut().insert(Arc::clone(permit.tenant().handler()));
+            let response = next.run(request).await;
+            drop(permit);
+            response
+        }
+        Err(response) => to_http(response).into_response(),
+    }
+}
+
+async fn health() -> ApiResponse {
+    (StatusCode::OK, Json(serde_json::json!({ "status": "ok" })))
+}
+
+async fn status(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+) -> ApiResponse {
+    to_http(handler.handle_from(&principal, Some(client), Request::Status).await)
+}
+
+async fn query(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Json(body): Json<QueryBody>,
+) -> ApiResponse {
+    // JSON clients ignore fields they don't know, so results always carry
+    // their statistics
+    let settings = SessionSettings { timing: true, ..SessionSettings::default() };
+    let request = Request::Query { sql: body.sql, limit: body.limit };
+    to_http(handler.handle_with(&principal, Some(client), &settings, request).await)
+}
+
+async fn insert_node(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Json(body): Json<InsertNodeBody>,
+) -> ApiResponse {
+    let properties = match Value::from_json(body.properties) {
+        Ok(props) => props,
+        Err(e) => return error_body(ErrorCode::InvalidRequest, &e.to_string()),
+    };
+
+    let response = handler.handle_from(&principal, Some(client), Request::InsertNode {
+        node_type: body.node_type,
+        properties,
+    }).await;
+
+    match response {
+        Response::Node(node) => (StatusCode::CREATED, Json(node.to_json())),
+        other => to_http(other),
+    }
+}
+
+async fn get_node(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+) -> ApiResponse {
+    to_http(handler.handle_from(&principal, Some(client), Request::GetNode { id }).await)
+}
+
+async fn delete_node(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+) -> ApiResponse {
+    to_http(handler.handle_from(&principal, Some(client), Request::DeleteNode { id }).await)
+}
+
+async fn create_edge(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Json(body): Json<CreateEdgeBody>,
+) -> ApiResponse {
+    let properties = match body.properties.map(Value::from_json).transpose() {
+        Ok(props) => props,
+        Err(e) => return error_body(ErrorCode::InvalidRequest, &e.to_string()),
+    };
+
+    let response = handler.handle_from(&principal, Some(client), Request::CreateEdge {
+        from_id: body.from_id,
+        to_id: body.to_id,
+        edge_type: body.edge_type,
+        properties,
+    }).await;
+
+    match response {
+        Response::Edge(edge) => (StatusCode::CREATED, Json(edge.to_json())),
+        other => to_http(other),
+    }
+}
+
+async fn search(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Json(body): Json<SearchBody>,
+) -> ApiResponse {
+    to_http(handler.handle_from(&principal, Some(client), Request::SimilaritySearch {
+        node_type: body.node_type,
+        vector: body.vector,
+        field: body.field,
+        k: body.k,
+        metric: body.metric,
+    }).await)
+}
+
+/// Upgrade to a WebSocket that streams matching changes as JSON text frames
+async fn subscribe(
+    Extension(handler): Extension<Arc<RequestHandler>>,
+    Extension(principal): Extension<Principal>,
+    Query(params): Query<SubscribeParams>,
+    ws: WebSocketUpgrade,
+) -> axum::response::Response {
+    match handler.create_subscription(&principal, params.node_type, params.predicate.as_deref()) {
+        Ok(subscription) => {
+            ws.on_upgrade(move |socket| stream_changes(socket, handler, subscription))
+        }
+        Err(response) => to_http(response).into_response(),
+    }
+}
+
+async fn stream_changes(mut socket: WebSocket, handler: Arc<RequestHandler>, subscription: Subscription) {
+    let subscription_id = subscription.id();
+    let mut subscriptions = SubscriptionSet::default();
+    subscriptions.add(subscription, || handler.subscribe_changes());
+
+    let hello = serde_json::json!({ "subscription_id": subscription_id });
+    if socket.send(Message::Text(hello.to_string())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            Some((_, event)) = subscriptions.next() => {
+                let body = change_to_json(subscription_id, &event);
+                if socket.send(Message::Text(body.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// JSON form of a change notification
+fn change_to_json(subscription_id: u64, event: &ChangeEvent) -> serde_json::Value {
+    let (entity, data) = match &event.record {
+        ChangeRecord::Node(node) => ("node", node.to_json()),
+        ChangeRecord::Edge(edge) => ("edge", edge.to_json()),
+    };
+
+    serde_json::json!({
+        "subscription_id": subscription_id,
+        "seq": event.seq,
+        "kind": event.kind,
+        "entity": entity,
+        "data": data,
+        "timestamp": event.timestamp,
+    })
+}
+
+/// Convert a protocol response into an HTTP status and JSON body
+fn to_http(response: Response) -> ApiResponse {
+    let body = match response {
+        Response::Pong | Response::Ok | Response::Goodbye => serde_json::json!({ "ok": true }),
+        Response::Authenticated { username } => serde_json::json!({ "username": username }),
+        Response::DatabaseSelected { name } => serde_json::json!({ "database": name }),
+        Response::Node(node) | Response::MaybeNode(Some(node)) => node.to_json(),
+        Response::MaybeNode(None) => {
+            return error_body(ErrorCode::NodeNotFound, "Node not found");
+        }
+        Response::Nodes(nodes) => {
+            serde_json::Value::Array(nodes.iter().map(|n| n.to_json()).collect())
+        }
+        Response::Edge(edge) => edge.to_json(),
+        Response::Edges(edges) => {
+            serde_json::Value::Array(edges.iter().map(|e| e.to_json()).collect())
+        }
+        Response::QueryResult { columns, rows, rows_affected, execution_time_ms } => {
+            serde_json::json!({
+                "columns": columns,
+                "rows": rows.iter().map(|row| {
+                    row.iter().map(|v| v.to_json()).collect::<Vec<_>>()
+                }).collect::<Vec<_>>(),
+                "rows_affected": rows_affected,
+                "execution_time_ms": execution_time_ms,
+            })
+        }
+        Response::SimilarityResults(results) => serde_json::json!(results),
+        Response::TraversalResult { nodes, edges, depth } => {
+            serde_json::json!({
+                "nodes": nodes.iter().map(|n| n.to_json()).collect::<Vec<_>>(),
+                "edges": edges.iter().map(|e| e.to_json()).collect::<Vec<_>>(),
+                "depth": depth,
+            })
+        }
+        Response::Status { name, node_count, edge_count, size_bytes } => {
+            serde_json::json!({
+                "name": name,
+                "node_count": node_count,
+                "edge_count": edge_count,
+                "size_bytes": size_bytes,
+            })
+        }
+        Response::TransactionStarted { tx_id } => serde_json::json!({ "tx_id": tx_id }),
+        Response::TransactionCommitted | Response::TransactionRolledBack => {
+            serde_json::json!({ "ok": true })
+        }
+        Response::Subscribed { subscription_id } => {
+            serde_json::json!({ "subscription_id": subscription_id })
+        }
+        Response::Change { subscription_id, event } => change_to_json(subscription_id, &event),
+        Response::Sessions(sessions) => serde_json::json!(sessions),
+        Response::Welcome { protocol_version, format_version, server_version } => {
+            serde_json::json!({
+                "protocol_version": protocol_version,
+                "format_version": format_version,
+                "server_version": server_version,
+            })
+        }
+        Response::Batch(responses) => {
+            serde_json::Value::Array(responses.into_iter().map(|r| to_http(r).1 .0).collect())
+        }
+        Response::Settings(settings) => {
+            serde_json::Value::Object(
+                settings.into_iter().map(|(name, value)| (name, serde_json::Value::String(value))).collect(),
+            )
+        }
+        Response::Leader { leader, is_leader } => {
+            serde_json::json!({ "leader": leader, "is_leader": is_leader })
+        }
+        Response::Context(context) => context.to_json(),
+        Response::Schema(schema) => serde_json::json!(schema),
+        Response::Schemas(schemas) => serde_json::json!(schemas),
+        Response::Migrations(migrations) => serde_json::json!(migrations),
+        Response::Prepared { statement_id, param_count } => {
+            serde_json::json!({ "statement_id": statement_id, "param_count": param_count })
+        }
+        Response::Chunk { response, .. } => return to_http(*response),
+        Response::Stats { response, stats } => {
+            let (status, Json(mut body)) = to_http(*response);
+            if let Some(body) = body.as_object_mut() {
+                body.insert("stats".to_string(), serde_json::json!(stats));
+            }
+            return (status, Json(body));
+        }
+        Response::Error { code, message } => return error_body(code, &message),
+    };
+
+    (StatusCode::OK, Json(body))
+}
+
+fn error_body(code: ErrorCode, message: &str) -> ApiResponse {
+    let body = serde_json::json!({
+        "error": {
+            "code": code,
+            "message": message,
+        }
+    });
+    (http_status(code), Json(body))
+}
+
+/// Map protocol error codes onto HTTP status codes
+fn http_status(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::InvalidRequest | ErrorCode::QueryParseError | ErrorCode::VersionMismatch => {
+            StatusCode::BAD_REQUEST
+        }
+        ErrorCode::NodeNotFound
+        | ErrorCode::EdgeNotFound
+        | ErrorCode::DatabaseNotFound
+        | ErrorCode::StatementNotFound => StatusCode::NOT_FOUND,
+        ErrorCode::Unauthenticated => StatusCode::UNAUTHORIZED,
+        ErrorCode::PermissionDenied => StatusCode::FORBIDDEN,
+        ErrorCode::ServerOverloaded | ErrorCode::NotLeader => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::TransactionError => StatusCode::CONFLICT,
+        ErrorCode::MemoryLimitExceeded => StatusCode::INSUFFICIENT_STORAGE,
+        ErrorCode::QueryExecutionError
+        | ErrorCode::Unknown
+        | ErrorCode::InternalError
+        | ErrorCode::Cancelled => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+    use tempfile::TempDir;
+
+    async fn spawn_api(temp: &TempDir) -> String {
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_listener(listener, tenants));
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_http_insert_and_query() {
+        let temp = TempDir::new().unwrap();
+        let base = spawn_api(&temp).await;
+        let client = reqwest::Client::new();
+
+        let response = client.post(format!("{}/nodes", base))
+            .json(&serde_json::json!({"node_type": "user", "properties": {"name": "Alice"}}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+        let node: serde_json::Value = response.json().await.unwrap();
+        let id = node["id"].as_str().unwrap().to_string();
+
+        let response = client.get(format!("{}/nodes/{}", base, id)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        let response = client.post(format!("{}/query", base))
+            .json(&serde_json::json!({"sql": "SELECT * FROM user"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let result: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(result["rows"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_errors() {
+        let temp = TempDir::new().unwrap();
+        let base = spawn_api(&temp).await;
+        let client = reqwest::Client::new();
+
+        let response = client.post(format!("{}/query", base))
+            .json(&serde_json::json!({"sql": "SELEC nonsense"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "QueryParseError");
+
+        let missing = crate::storage::NodeId::new();
+        let response = client.get(format!("{}/nodes/{}", base, missing)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_http_search() {
+        let temp = TempDir::new().unwrap();
+        let base = spawn_api(&temp).await;
+        let client = reqwest::Client::new();
+
+        for vector in [[1.0, 0.0], [0.0, 1.0]] {
+            let response = client.post(format!("{}/nodes", base))
+                .json(&serde_json::json!({"node_type": "doc", "properties": {"embedding": {"$vector": vector}}}))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), 201);
+        }
+
+        let response = client.post(format!("{}/search", base))
+            .json(&serde_json::json!({"node_type": "doc", "vector": [1.0, 0.0], "k": 1}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let results: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(results.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_http_auth() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let users = crate::auth::UserStore::open(temp.path()).unwrap();
+        users.add_user("svc", None, &[crate::auth::ADMIN_ROLE.to_string()]).unwrap();
+        let token = users.create_api_key("svc", None).unwrap();
+
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+        tenants.set_user_store(Some(Arc::new(users)));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve_listener(listener, tenants));
+        let client = reqwest::Client::new();
+
+        let response = client.get(format!("{}/health", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        let response = client.get(format!("{}/status", base)).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        let response = client.get(format!("{}/status", base))
+            .bearer_auth("aresa_bad_key")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+
+        let response = client.get(format!("{}/status", base))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+
+        // Database names are only checked once the caller is authenticated
+        let response = client.get(format!("{}/status", base))
+            .header(DATABASE_HEADER, "missing")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_http_selects_database() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path().join("main"), "main").await.unwrap();
+        let analytics = Database::create(temp.path().join("analytics"), "analytics").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+        let limits = crate::server::TenantLimits { max_connections: Some(1), ..Default::default() };
+        tenants.add("analytics", RequestHandler::new(analytics), limits).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve_listener(listener, Arc::clone(&tenants)));
+        let client = reqwest::Client::new();
+
+        let response = client.post(format!("{}/nodes", base))
+            .header(DATABASE_HEADER, "analytics")
+            .json(&serde_json::json!({"node_type": "event", "properties": {"n": 1}}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 201);
+
+        let status = |database: Option<&'static str>| {
+            let mut request = client.get(format!("{}/status", base));
+            if let Some(database) = database {
+                request = request.header(DATABASE_HEADER, database);
+            }
+            request.send()
+        };
+        let body: serde_json::Value = status(Some("analytics")).await.unwrap().json().await.unwrap();
+        assert_eq!((body["name"].as_str(), body["node_count"].as_u64()), (Some("analytics"), Some(1)));
+        let body: serde_json::Value = status(None).await.unwrap().json().await.unwrap();
+        assert_eq!((body["name"].as_str(), body["node_count"].as_u64()), (Some("main"), Some(0)));
+        assert_eq!(status(Some("missing")).await.unwrap().status().as_u16(), 404);
+
+        // Requests take a connection slot like binary protocol clients do
+        let held = tenants.get("analytics").unwrap().admit().unwrap();
+        assert_eq!(status(Some("analytics")).await.unwrap().status().as_u16(), 503);
+        drop(held);
+        assert_eq!(status(Some("analytics")).await.unwrap().status().as_u16(), 200);
+        assert_eq!(tenants.get("analytics").unwrap().connection_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_subscribe() {
+        use futures::StreamExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let temp = TempDir::new().unwrap();
+        let base = spawn_api(&temp).await;
+        let client = reqwest::Client::new();
+
+        let url = format!("{}/subscribe?node_type=user&predicate=age%20%3E%3D%2018", base.replace("http", "ws"));
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+        let next_json = |msg: WsMessage| -> serde_json::Value {
+            serde_json::from_str(msg.to_text().unwrap()).unwrap()
+        };
+
+        let hello = next_json(socket.next().await.unwrap().unwrap());
+        assert!(hello["subscription_id"].is_u64());
+
+        for age in [12, 40] {
+            client.post(format!("{}/nodes", base))
+                .json(&serde_json::json!({"node_type": "user", "properties": {"age": age}}))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let change = next_json(socket.next().await.unwrap().unwrap());
+        assert_eq!(change["kind"], "insert");
+        assert_eq!(change["entity"], "node");
+        assert_eq!(change["data"]["properties"]["age"], 40);
+    }
+}
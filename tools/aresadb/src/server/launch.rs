@@ -0,0 +1,368 @@
+//! Server Launch
+//!
+//! Options for starting a server (from flags or a TOML config file) and
+//! the startup sequence shared by `aresadb serve` and `aresadb-server`.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{RateLimit, Server, ServerConfig, TenantLimits};
+use crate::auth::UserStore;
+use crate::distributed::{ShardConfig, ShardManager, WriteAheadLog};
+use crate::query::{QueryLog, QueryLogConfig};
+use crate::storage::Database;
+
+/// Everything needed to start a server
+///
+/// Deserializes from TOML; missing keys take their defaults:
+///
+/// ```toml
+/// database = "./mydb"
+/// bind = "0.0.0.0:7432"
+/// shards = 4
+/// http = "0.0.0.0:8080"
+///
+/// [tenants]
+/// analytics = "./analytics"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeOptions {
+    /// Database path
+    pub database: String,
+    /// Bind address for the binary protocol
+    pub bind: String,
+    /// Maximum connections
+    pub max_connections: usize,
+    /// Enable compression
+    pub compression: bool,
+    /// Number of shards (0 for single-node mode)
+    pub shards: usize,
+    /// Address for the HTTP REST API
+    pub http: Option<String>,
+    /// Address for the gRPC API
+    pub grpc: Option<String>,
+    /// Address for the Prometheus metrics endpoint
+    pub metrics: Option<String>,
+    /// Log every query to .aresadb/logs/query.log
+    pub query_log: bool,
+    /// Log queries at least this slow (ms) to .aresadb/logs/slow.log
+    pub slow_query_ms: Option<u64>,
+    /// Write-ahead log file whose size the metrics endpoint reports
+    pub wal: Option<String>,
+    /// Additional databases by name and path
+    pub tenants: BTreeMap<String, String>,
+    /// Maximum concurrent connections per additional database
+    pub tenant_max_connections: Option<usize>,
+    /// Maximum rows returned per query on additional databases
+    pub tenant_max_rows: Option<usize>,
+    /// Maximum requests per second on each connection
+    pub conn_max_qps: Option<f64>,
+    /// Maximum bytes per second on each connection
+    pub conn_max_bytes: Option<u64>,
+    /// Maximum requests per second for each API key or user
+    pub key_max_qps: Option<f64>,
+    /// Maximum bytes per second for each API key or user
+    pub key_max_bytes: Option<u64>,
+    /// Seconds to wait for open connections to close on shutdown
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ServeOptions {
+    fn default() -> Self {
+        Self {
+            database: ".".to_string(),
+            bind: "127.0.0.1:7432".to_string(),
+            max_connections: 1000,
+            compression: true,
+            shards: 0,
+            http: None,
+            grpc: None,
+            metrics: None,
+            query_log: false,
+            slow_query_ms: None,
+            wal: None,
+            tenants: BTreeMap::new(),
+            tenant_max_connections: None,
+            tenant_max_rows: None,
+            conn_max_qps: None,
+            conn_max_bytes: None,
+            key_max_qps: None,
+            key_max_bytes: None,
+            drain_timeout_secs: 30,
+        }
+    }
+}
+
+impl ServeOptions {
+    /// Load options from a TOML file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Invalid server config {}", path.display()))
+    }
+
+    /// Add a tenant from a `NAME=PATH` spec
+    pub fn add_tenant(&mut self, spec: &str) -> Result<()> {
+        let Some((name, path)) = spec.split_once('=') else {
+            bail!("Invalid tenant '{}': expected NAME=PATH", spec);
+        };
+        self.tenants.insert(name.to_string(), path.to_string());
+        Ok(())
+    }
+
+    /// Build the server configuration
+    pub fn server_config(&self) -> Result<ServerConfig> {
+        let addr = |value: &Option<String>| -> Result<_> {
+            value
+                .as_deref()
+                .map(|a| a.parse().with_context(|| format!("Invalid address '{}'", a)))
+                .transpose()
+        };
+
+        #[cfg(not(feature = "grpc"))]
+        if self.grpc.is_some() {
+            bail!("gRPC API requires the 'grpc' feature");
+        }
+
+        Ok(ServerConfig {
+            bind_addr: self.bind.parse().with_context(|| format!("Invalid bind address '{}'", self.bind))?,
+            max_connections: self.max_connections,
+            compression: self.compression,
+            http_addr: addr(&self.http)?,
+            #[cfg(feature = "grpc")]
+            grpc_addr: addr(&self.grpc)?,
+            metrics_addr: addr(&self.metrics)?,
+            connection_rate_limit: RateLimit {
+                requests_per_sec: self.conn_max_qps,
+                bytes_per_sec: self.conn_max_bytes,
+            },
+            key_rate_limit: RateLimit {
+                requests_per_sec: self.key_max_qps,
+                bytes_per_sec: self.key_max_bytes,
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Build a server: open the databases, user store, query log and WAL
+    pub async fn build(&self) -> Result<Server> {
+        let config = self.server_config()?;
+
+        if let Some(http_addr) = config.http_addr {
+            info!("HTTP API address: {}", http_addr);
+        }
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = config.grpc_addr {
+            info!("gRPC API address: {}", grpc_addr);
+        }
+
+        if let Some(metrics_addr) = config.metrics_addr {
+            info!("Metrics address: {}", metrics_addr);
+        }
+
+        let server = if self.shards > 0 {
+            info!("Sharded mode with {} shards", self.shards);
+
+            let shard_config = ShardConfig {
+                num_shards: self.shards,
+                base_path: PathBuf::from(&self.database),
+                ..Default::default()
+            };
+
+            Server::with_shards(ShardManager::new(shard_config).await?, config)
+        } else {
+            info!("Single-node mode");
+            Server::new(open_or_create(&self.database, "aresadb").await?, config)
+        };
+
+        let limits = TenantLimits {
+            max_connections: self.tenant_max_connections,
+            max_result_rows: self.tenant_max_rows,
+        };
+        for (name, path) in &self.tenants {
+            let db = open_or_create(path, name).await?;
+            server.add_database(name, db, limits.clone())?;
+            info!("Hosting database '{}' from {}", name, path);
+        }
+
+        // Authentication is enforced once users exist (see `aresadb user add`)
+        let users = UserStore::open(&self.database)?;
+        if users.is_empty() {
+            warn!("No users configured; authentication is disabled");
+        } else {
+            info!("Authentication enabled ({} users)", users.list_users().len());
+        }
+        let mut server = server.with_user_store(users);
+
+        if self.query_log || self.slow_query_ms.is_some() {
+            let log_config = QueryLogConfig {
+                log_all: self.query_log,
+                slow_threshold: self.slow_query_ms.map(Duration::from_millis),
+            };
+            let log_dir = QueryLog::dir_for(&self.database);
+            info!("Query log directory: {}", log_dir.display());
+            server = server.with_query_log(QueryLog::open(log_dir, log_config)?);
+        }
+
+        if let Some(ref path) = self.wal {
+            info!("Write-ahead log: {}", path);
+            server = server.with_wal(Arc::new(WriteAheadLog::open(path)?));
+        }
+
+        Ok(server)
+    }
+}
+
+/// Open a database, creating it if it doesn't exist
+async fn open_or_create(path: &str, name: &str) -> Result<Database> {
+    match Database::open(path).await {
+        Ok(db) => Ok(db),
+        Err(_) => {
+            info!("Creating new database at {}", path);
+            Database::create(path, name).await
+        }
+    }
+}
+
+/// Wait for Ctrl-C, or SIGTERM on Unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                tokio::signal::ctrl_c().await.ok();
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
+
+/// Start a server and run it until Ctrl-C or SIGTERM
+///
+/// On a signal the server stops accepting connections and waits up to
+/// `drain_timeout_secs` for open ones to close.
+pub async fn serve(options: ServeOptions) -> Result<()> {
+    info!("Starting AresaDB server...");
+    info!("Database path: {}", options.database);
+    info!("Bind address: {}", options.bind);
+
+    let server = Arc::new(options.build().await?);
+
+    let signalled = Arc::clone(&server);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received");
+        signalled.shutdown();
+    });
+
+    server.run().await?;
+
+    let open = server.drain(Duration::from_secs(options.drain_timeout_secs)).await;
+    if open > 0 {
+        warn!("Closing {} connections still open after {}s", open, options.drain_timeout_secs);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.toml");
+        std::fs::write(
+            &path,
+            r#"
+database = "./mydb"
+bind = "0.0.0.0:7433"
+shards = 4
+http = "127.0.0.1:8080"
+
+[tenants]
+analytics = "./analytics"
+"#,
+        )
+        .unwrap();
+
+        let mut options = ServeOptions::load(&path).unwrap();
+        assert_eq!(options.database, "./mydb");
+        assert_eq!(options.shards, 4);
+        assert_eq!(options.max_connections, 1000);
+        assert_eq!(options.tenants["analytics"], "./analytics");
+
+        options.add_tenant("logs=./logs").unwrap();
+        assert_eq!(options.tenants.len(), 2);
+        assert!(options.add_tenant("logs").is_err());
+
+        let config = options.server_config().unwrap();
+        assert_eq!(config.bind_addr.port(), 7433);
+        assert_eq!(config.http_addr.unwrap().port(), 8080);
+
+        std::fs::write(&path, "bnid = \"0.0.0.0:7432\"\n").unwrap();
+        assert!(ServeOptions::load(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_accept_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = ServeOptions {
+            database: dir.path().to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        let server = Arc::new(options.build().await.unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let running = Arc::clone(&server);
+        let task = tokio::spawn(async move { running.serve(listener).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        server.shutdown();
+        tokio::time::timeout(Duration::from_secs(5), task).await.unwrap().unwrap().unwrap();
+        assert_eq!(server.drain(Duration::from_millis(100)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_report_wal_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_path = dir.path().join("server.wal");
+        let options = ServeOptions {
+            database: dir.path().to_string_lossy().into_owned(),
+            wal: Some(wal_path.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let server = options.build().await.unwrap();
+
+        let wal = WriteAheadLog::open(&wal_path).unwrap();
+        wal.log_insert_node(&crate::storage::Node::new("user", crate::storage::Value::Null)).unwrap();
+        let size = wal.size_bytes().unwrap();
+        assert!(size > 0);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(crate::server::metrics::serve_listener(listener, server.metrics_source()));
+
+        let samples = crate::server::scrape_metrics(&addr).await.unwrap();
+        let sample = samples.iter().find(|s| s.name == "aresadb_wal_size_bytes").unwrap();
+        assert_eq!(sample.value, size as f64);
+    }
+}
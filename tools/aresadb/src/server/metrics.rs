@@ -0,0 +1,457 @@
+//! Prometheus Metrics
+//!
+//! Request counters and latency histograms are recorded by each tenant's
+//! request handler; storage gauges are sampled when `/metrics` is scraped.
+//! The output uses the Prometheus text exposition format. The same listener
+//! serves `/slow-queries`, the server's most recent slow queries as JSON.
+
+use anyhow::{Result, Context};
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use super::pool::ConnectionPool;
+use super::tenant::TenantRegistry;
+use crate::distributed::WriteAheadLog;
+use crate::query::QueryLogEntry;
+
+/// Upper bounds of the request latency histogram, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0];
+
+/// Slow queries returned by `/slow-queries` when no limit is given
+const DEFAULT_SLOW_QUERY_LIMIT: usize = 10;
+
+/// Counters for one operation
+#[derive(Debug, Clone, Default)]
+struct OperationStats {
+    count: u64,
+    errors: u64,
+    /// Non-cumulative counts per bucket; the extra slot is `+Inf`
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum_secs: f64,
+}
+
+/// Request counters and latencies keyed by operation
+#[derive(Debug, Default)]
+pub struct RequestMetrics {
+    operations: Mutex<BTreeMap<&'static str, OperationStats>>,
+}
+
+impl RequestMetrics {
+    /// Create empty metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a completed request
+    pub fn record(&self, operation: &'static str, elapsed: Duration, is_error: bool) {
+        let secs = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS.iter()
+            .position(|&bound| secs <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        let mut operations = self.operations.lock();
+        let stats = operations.entry(operation).or_default();
+        stats.count += 1;
+        stats.errors += u64::from(is_error);
+        stats.buckets[bucket] += 1;
+        stats.sum_secs += secs;
+    }
+
+    /// Total requests recorded for an operation
+    pub fn request_count(&self, operation: &str) -> u64 {
+        self.operations.lock().get(operation).map_or(0, |s| s.count)
+    }
+
+    fn snapshot(&self) -> Vec<(&'static str, OperationStats)> {
+        self.operations.lock().iter().map(|(op, stats)| (*op, stats.clone())).collect()
+    }
+}
+
+/// Everything a scrape reads from
+#[derive(Clone)]
+pub struct MetricsSource {
+    /// Hosted databases
+    pub tenants: Arc<TenantRegistry>,
+    /// Server-wide connection pool
+    pub pool: Arc<ConnectionPool>,
+    /// Write-ahead log, if the embedder runs one
+    pub wal: Option<Arc<WriteAheadLog>>,
+}
+
+impl MetricsSource {
+    /// Render all metrics in the Prometheus text format
+    pub async fn render(&self) -> String {
+        let mut out = String::new();
+        let tenants: Vec<_> = self.tenants.names()
+            .into_iter()
+            .filter_map(|name| self.tenants.get(&name))
+            .collect();
+
+        header(&mut out, "aresadb_requests_total", "counter", "Requests handled");
+        for tenant in &tenants {
+            for (op, stats) in tenant.handler().metrics().snapshot() {
+                let _ = writeln!(out, "aresadb_requests_total{{database=\"{}\",operation=\"{}\"}} {}", tenant.name(), op, stats.count);
+            }
+        }
+
+        header(&mut out, "aresadb_request_errors_total", "counter", "Requests that returned an error");
+        for tenant in &tenants {
+            for (op, stats) in tenant.handler().metrics().snapshot() {
+                let _ = writeln!(out, "aresadb_request_errors_total{{database=\"{}\",operation=\"{}\"}} {}", tenant.name(), op, stats.errors);
+            }
+        }
+
+        header(&mut out, "aresadb_request_duration_seconds", "histogram", "Request latency");
+        for tenant in &tenants {
+            for (op, stats) in tenant.handler().metrics().snapshot() {
+                let labels = format!("database=\"{}\",operation=\"{}\"", tenant.name(), op);
+                let mut cumulative = 0;
+                for (i, count) in stats.buckets.iter().enumerate() {
+                    cumulative += count;
+                    let le = LATENCY_BUCKETS.get(i).map_or("+Inf".to_string(), |b| b.to_string());
+                    let _ = writeln!(out, "aresadb_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, le, cumulative);
+                }
+                let _ = writeln!(out, "aresadb_request_duration_seconds_sum{{{}}} {}", labels, stats.sum_secs);
+                let _ = writeln!(out, "aresadb_request_duration_seconds_count{{{}}} {}", labels, stats.count);
+            }
+        }
+
+        header(&mut out, "aresadb_active_connections", "gauge", "Open client connections");
+        let _ = writeln!(out, "aresadb_active_connections {}", self.pool.active_count());
+
+        header(&mut out, "aresadb_database_connections", "gauge", "Open connections per database");
+        for tenant in &tenants {
+            let _ = writeln!(out, "aresadb_database_connections{{database=\"{}\"}} {}", tenant.name(), tenant.connection_count());
+        }
+
+        header(&mut out, "aresadb_active_transactions", "gauge", "Open transactions per database");
+        for tenant in &tenants {
+            let _ = writeln!(out, "aresadb_active_transactions{{database=\"{}\"}} {}", tenant.name(), tenant.handler().active_transactions());
+        }
+
+        if let Some(ref wal) = self.wal {
+            match wal.size_bytes() {
+                Ok(size) => {
+                    header(&mut out, "aresadb_wal_size_bytes", "gauge", "Write-ahead log size");
+                    let _ = writeln!(out, "aresadb_wal_size_bytes {}", size);
+                }
+                Err(e) => warn!("Failed to read WAL size: {}", e),
+            }
+        }
+
+        header(&mut out, "aresadb_cache_hit_ratio", "gauge", "Fraction of cache lookups that hit");
+        for tenant in &tenants {
+            if let Some(stats) = tenant.handler().cache_stats() {
+                let _ = writeln!(out, "aresadb_cache_hit_ratio{{database=\"{}\"}} {}", tenant.name(), stats.hit_rate());
+            }
+        }
+
+        header(&mut out, "aresadb_cache_evictions_total", "counter", "Cache entries evicted to stay within the size bound");
+        for tenant in &tenants {
+            if let Some(stats) = tenant.handler().cache_stats() {
+                let _ = writeln!(out, "aresadb_cache_evictions_total{{database=\"{}\"}} {}", tenant.name(), stats.evictions);
+            }
+        }
+
+        header(&mut out, "aresadb_maintenance_runs_total", "counter", "Completed runs of each maintenance task");
+        for tenant in &tenants {
+            if let Some(status) = tenant.handler().maintenance_status() {
+                for task in &status.tasks {
+                    let _ = writeln!(out, "aresadb_maintenance_runs_total{{database=\"{}\",task=\"{}\"}} {}", tenant.name(), task.task.name(), task.runs);
+                }
+            }
+        }
+
+        header(&mut out, "aresadb_maintenance_failed", "gauge", "Whether a maintenance task's last run failed");
+        for tenant in &tenants {
+            if let Some(status) = tenant.handler().maintenance_status() {
+                for task in &status.tasks {
+                    let _ = writeln!(out, "aresadb_maintenance_failed{{database=\"{}\",task=\"{}\"}} {}", tenant.name(), task.task.name(), u8::from(task.last_error.is_some()));
+                }
+            }
+        }
+
+        header(&mut out, "aresadb_maintenance_paused", "gauge", "Whether scheduled maintenance is paused");
+        for tenant in &tenants {
+            if let Some(status) = tenant.handler().maintenance_status() {
+                let _ = writeln!(out, "aresadb_maintenance_paused{{database=\"{}\"}} {}", tenant.name(), u8::from(status.paused));
+            }
+        }
+
+        header(&mut out, "aresadb_plan_cache_hits_total", "counter", "Queries that reused a cached plan");
+        for tenant in &tenants {
+            if let Some(stats) = tenant.handler().plan_cache_stats() {
+                let _ = writeln!(out, "aresadb_plan_cache_hits_total{{database=\"{}\"}} {}", tenant.name(), stats.hits);
+            }
+        }
+
+        header(&mut out, "aresadb_plan_cache_misses_total", "counter", "Queries parsed and planned from scratch");
+        for tenant in &tenants {
+            if let Some(stats) = tenant.handler().plan_cache_stats() {
+                let _ = writeln!(out, "aresadb_plan_cache_misses_total{{database=\"{}\"}} {}", tenant.name(), stats.misses);
+            }
+        }
+
+        header(&mut out, "aresadb_nodes", "gauge", "Stored nodes per shard (shard 0 in single-node mode)");
+        for tenant in &tenants {
+            for (shard, nodes) in tenant.handler().shard_node_counts().await {
+                let _ = writeln!(out, "aresadb_nodes{{database=\"{}\",shard=\"{}\"}} {}", tenant.name(), shard, nodes);
+            }
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// One sample read back from the text format
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    /// Metric name
+    pub name: String,
+    /// Label values by label name
+    pub labels: BTreeMap<String, String>,
+    /// Sample value
+    pub value: f64,
+}
+
+impl MetricSample {
+    /// Value of a label, or "" when absent
+    pub fn label(&self, name: &str) -> &str {
+        self.labels.get(name).map_or("", String::as_str)
+    }
+}
+
+/// Parse the Prometheus text format, skipping comments and malformed lines
+pub fn parse(text: &str) -> Vec<MetricSample> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(parse_line)
+        .collect()
+}
+
+fn parse_line(line: &str) -> Option<MetricSample> {
+    let (series, value) = line.rsplit_once(' ')?;
+    let value = value.parse().ok()?;
+    let Some((name, labels)) = series.split_once('{') else {
+        return Some(MetricSample { name: series.to_string(), labels: BTreeMap::new(), value });
+    };
+
+    let mut parsed = BTreeMap::new();
+    let mut rest = labels.strip_suffix('}')?;
+    while let Some((key, tail)) = rest.split_once("=\"") {
+        let end = tail.find('"')?;
+        parsed.insert(key.trim_start_matches(',').to_string(), tail[..end].to_string());
+        rest = &tail[end + 1..];
+    }
+    Some(MetricSample { name: name.to_string(), labels: parsed, value })
+}
+
+/// URL of a path on the metrics listener
+///
+/// `addr` is a `host:port` or a full URL of the `/metrics` endpoint.
+fn endpoint(addr: &str, path: &str) -> String {
+    match addr.split_once("://") {
+        Some((scheme, rest)) => {
+            let host = rest.split('/').next().unwrap_or(rest);
+            format!("{}://{}{}", scheme, host, path)
+        }
+        None => format!("http://{}{}", addr, path),
+    }
+}
+
+/// Fetch and parse a `/metrics` endpoint
+///
+/// `addr` is a `host:port` or a full URL.
+pub async fn scrape(addr: &str) -> Result<Vec<MetricSample>> {
+    let url = if addr.contains("://") {
+        addr.to_string()
+    } else {
+        endpoint(addr, "/metrics")
+    };
+    let text = reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to scrape {}", url))?
+        .text()
+        .await
+        .context("Failed to read metrics")?;
+    Ok(parse(&text))
+}
+
+/// Fetch the last `limit` slow queries from a metrics listener, oldest first
+///
+/// `addr` is the same `host:port` or `/metrics` URL passed to [`scrape`].
+pub async fn scrape_slow_queries(addr: &str, limit: usize) -> Result<Vec<QueryLogEntry>> {
+    let url = endpoint(addr, &format!("/slow-queries?limit={}", limit));
+    reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .json()
+        .await
+        .context("Failed to read slow queries")
+}
+
+/// Bind to an address and serve `/metrics` and `/slow-queries`
+pub async fn serve(addr: SocketAddr, source: MetricsSource) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context("Failed to bind metrics listener")?;
+    serve_listener(listener, source).await
+}
+
+/// Serve `/metrics` and `/slow-queries` on an already bound listener
+pub async fn serve_listener(listener: TcpListener, source: MetricsSource) -> Result<()> {
+    info!("AresaDB metrics listening on {}", listener.local_addr()?);
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/slow-queries", get(slow_queries))
+        .with_state(source);
+
+    axum::serve(listener, app)
+        .await
+        .context("Metrics server error")
+}
+
+async fn metrics(State(source): State<MetricsSource>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        source.render().await,
+    )
+}
+
+#[derive(Debug, Deserialize)]
+struct SlowQueryParams {
+    limit: Option<usize>,
+}
+
+/// Recent slow queries across all databases; empty without a query log
+async fn slow_queries(
+    State(source): State<MetricsSource>,
+    Query(params): Query<SlowQueryParams>,
+) -> Json<Vec<QueryLogEntry>> {
+    let limit = params.limit.unwrap_or(DEFAULT_SLOW_QUERY_LIMIT);
+    Json(source.tenants.query_log().map_or_else(Vec::new, |log| log.recent_slow(limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{Request, RequestHandler};
+    use crate::storage::Database;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_request_metrics() {
+        let metrics = RequestMetrics::new();
+        metrics.record("query", Duration::from_micros(300), false);
+        metrics.record("query", Duration::from_secs(2), true);
+
+        let snapshot = metrics.snapshot();
+        let (op, stats) = &snapshot[0];
+        assert_eq!(*op, "query");
+        assert_eq!((stats.count, stats.errors), (2, 1));
+        assert_eq!(stats.buckets[0], 1);
+        assert_eq!(stats.buckets[LATENCY_BUCKETS.len()], 1);
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+        let handler = Arc::clone(tenants.default_tenant().handler());
+        handler.handle(Request::Status).await;
+
+        let source = MetricsSource {
+            tenants,
+            pool: Arc::new(ConnectionPool::new(10)),
+            wal: None,
+        };
+        let text = source.render().await;
+
+        assert!(text.contains("aresadb_requests_total{database=\"default\",operation=\"status\"} 1"));
+        assert!(text.contains("le=\"+Inf\"} 1"));
+        assert!(text.contains("aresadb_active_connections 0"));
+        assert!(text.contains("aresadb_nodes{database=\"default\",shard=\"0\"} 0"));
+        assert!(!text.contains("aresadb_wal_size_bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_parse() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+        let handler = Arc::clone(tenants.default_tenant().handler());
+        handler.handle(Request::Status).await;
+        handler.handle(Request::BeginTransaction).await;
+
+        let source = MetricsSource {
+            tenants,
+            pool: Arc::new(ConnectionPool::new(10)),
+            wal: None,
+        };
+        let samples = parse(&source.render().await);
+
+        let requests = samples.iter().find(|s| s.name == "aresadb_requests_total" && s.label("operation") == "status").unwrap();
+        assert_eq!(requests.label("database"), "default");
+        assert_eq!(requests.value, 1.0);
+        let transactions = samples.iter().find(|s| s.name == "aresadb_active_transactions").unwrap();
+        assert_eq!(transactions.value, 1.0);
+        assert!(samples.iter().any(|s| s.name == "aresadb_active_connections" && s.labels.is_empty()));
+        assert!(parse("# HELP x\nbroken\nx{a=\"1\"} nan?").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_slow_queries_endpoint() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let tenants = Arc::new(TenantRegistry::new(RequestHandler::new(db)));
+        let config = crate::query::QueryLogConfig {
+            log_all: false,
+            slow_threshold: Some(Duration::ZERO),
+        };
+        let log = crate::query::QueryLog::open(temp.path().join("logs"), config).unwrap();
+        tenants.set_query_log(Some(Arc::new(log)));
+
+        let handler = Arc::clone(tenants.default_tenant().handler());
+        for sql in ["SELECT * FROM users", "SELECT * FROM orders"] {
+            handler.handle(Request::Query { sql: sql.to_string(), limit: None }).await;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let source = MetricsSource {
+            tenants,
+            pool: Arc::new(ConnectionPool::new(10)),
+            wal: None,
+        };
+        tokio::spawn(serve_listener(listener, source));
+
+        let slow = scrape_slow_queries(&addr, 1).await.unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].query, "SELECT * FROM orders");
+        assert_eq!(slow[0].database, "default");
+
+        let url = format!("http://{}/metrics", addr);
+        assert_eq!(scrape_slow_queries(&url, 10).await.unwrap().len(), 2);
+    }
+}
@@ -1,26 +1,51 @@
 //! AresaDB Server
 //!
 //! TCP server for remote database access with connection pooling
-//! and request handling.
+//! and request handling, plus optional HTTP REST and gRPC APIs.
+//! One server can host several named databases (tenants).
 
 mod protocol;
+mod cluster;
 mod handler;
+mod launch;
+mod metrics;
 mod pool;
+mod rate_limit;
+mod session;
+mod settings;
+mod subscription;
+mod tenant;
+pub mod http;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 
-pub use protocol::{Request, Response, ErrorCode};
+pub use protocol::{Request, Response, ErrorCode, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use protocol::CHUNKED_PROTOCOL_VERSION;
+pub use cluster::ClusterView;
 pub use handler::RequestHandler;
+pub use launch::{serve, ServeOptions};
+pub use metrics::{parse as parse_metrics, scrape as scrape_metrics, scrape_slow_queries, MetricSample, MetricsSource, RequestMetrics};
 pub use pool::ConnectionPool;
+pub use rate_limit::{KeyedRateLimiter, RateLimit, RateLimiter};
+pub use session::{Session, SessionInfo, SessionRegistry};
+pub use settings::{Consistency, SessionSettings, SETTING_NAMES};
+pub use subscription::{Subscription, SubscriptionSet, parse_target};
+pub use tenant::{Tenant, TenantLimits, TenantPermit, TenantRegistry, DATABASE_HEADER, DEFAULT_DATABASE};
 
 use anyhow::{Result, Context};
 use parking_lot::RwLock;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, Notify};
 use tracing::{info, warn, error, debug};
 
+use crate::auth::{Principal, UserStore};
+use crate::query::QueryLog;
 use crate::storage::Database;
-use crate::distributed::ShardManager;
+use crate::distributed::{ShardManager, WriteAheadLog};
 
 /// Server configuration
 #[derive(Debug, Clone)]
@@ -29,12 +54,31 @@ pub struct ServerConfig {
     pub bind_addr: SocketAddr,
     /// Maximum connections
     pub max_connections: usize,
-    /// Read timeout in seconds
+    /// Seconds a client may take to send the rest of a request once it
+    /// has started one (0 disables the timeout; idle connections stay open)
     pub read_timeout_secs: u64,
-    /// Write timeout in seconds
+    /// Seconds a response may take to write before the connection is
+    /// dropped (0 disables the timeout)
     pub write_timeout_secs: u64,
     /// Enable compression
     pub compression: bool,
+    /// Address for the HTTP REST API (disabled when `None`)
+    pub http_addr: Option<SocketAddr>,
+    /// Address for the gRPC API (disabled when `None`)
+    #[cfg(feature = "grpc")]
+    pub grpc_addr: Option<SocketAddr>,
+    /// Address for the Prometheus `/metrics` listener (disabled when `None`)
+    pub metrics_addr: Option<SocketAddr>,
+    /// Rate limit for each TCP connection
+    pub connection_rate_limit: RateLimit,
+    /// Rate limit for each API key or user, across all connections and APIs
+    pub key_rate_limit: RateLimit,
+    /// Reject TCP clients that don't negotiate a protocol version with
+    /// `Request::Hello` (otherwise they are served unversioned)
+    pub require_hello: bool,
+    /// Larger `Nodes` and `QueryResult` responses are streamed to TCP
+    /// clients in chunks of about this many bytes (0 disables chunking)
+    pub chunk_size_bytes: usize,
 }
 
 impl Default for ServerConfig {
@@ -45,6 +89,14 @@ impl Default for ServerConfig {
             read_timeout_secs: 30,
             write_timeout_secs: 30,
             compression: true,
+            http_addr: None,
+            #[cfg(feature = "grpc")]
+            grpc_addr: None,
+            metrics_addr: None,
+            connection_rate_limit: RateLimit::default(),
+            key_rate_limit: RateLimit::default(),
+            require_hello: false,
+            chunk_size_bytes: 4 * 1024 * 1024,
         }
     }
 }
@@ -52,49 +104,152 @@ impl Default for ServerConfig {
 /// AresaDB TCP Server
 pub struct Server {
     config: ServerConfig,
-    handler: Arc<RequestHandler>,
+    tenants: Arc<TenantRegistry>,
     pool: Arc<ConnectionPool>,
+    /// Connected TCP clients
+    sessions: Arc<SessionRegistry>,
+    /// WAL reported by the metrics endpoint
+    wal: Option<Arc<WriteAheadLog>>,
     /// Shutdown flag
     pub shutdown: Arc<RwLock<bool>>,
+    /// Wakes the accept loop when shutdown is requested
+    shutdown_notify: Arc<Notify>,
 }
 
 impl Server {
     /// Create a new server with a database
     pub fn new(db: Database, config: ServerConfig) -> Self {
-        let handler = Arc::new(RequestHandler::new(db));
-        let pool = Arc::new(ConnectionPool::new(config.max_connections));
-
-        Self {
-            config,
-            handler,
-            pool,
-            shutdown: Arc::new(RwLock::new(false)),
-        }
+        Self::from_handler(RequestHandler::new(db), config)
     }
 
     /// Create a new server with a shard manager
     pub fn with_shards(shards: ShardManager, config: ServerConfig) -> Self {
-        let handler = Arc::new(RequestHandler::with_shards(shards));
-        let pool = Arc::new(ConnectionPool::new(config.max_connections));
+        Self::from_handler(RequestHandler::with_shards(shards), config)
+    }
+
+    fn from_handler(handler: RequestHandler, config: ServerConfig) -> Self {
+        let tenants = Arc::new(TenantRegistry::new(handler));
+        if !config.key_rate_limit.is_unlimited() {
+            tenants.set_rate_limiter(Some(Arc::new(KeyedRateLimiter::new(config.key_rate_limit))));
+        }
+        let pool = Arc::new(ConnectionPool::with_rate_limit(
+            config.max_connections,
+            config.connection_rate_limit,
+        ));
 
         Self {
             config,
-            handler,
+            tenants,
             pool,
+            sessions: Arc::new(SessionRegistry::new()),
+            wal: None,
             shutdown: Arc::new(RwLock::new(false)),
+            shutdown_notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Require authentication against a user store
+    ///
+    /// Enforcement only kicks in once the store has at least one user.
+    pub fn with_user_store(self, users: UserStore) -> Self {
+        self.tenants.set_user_store(Some(Arc::new(users)));
+        self
+    }
+
+    /// Record queries from every hosted database to a query log
+    pub fn with_query_log(self, log: QueryLog) -> Self {
+        self.tenants.set_query_log(Some(Arc::new(log)));
+        self
+    }
+
+    /// Join a replica set: report its leader to clients and refuse writes
+    /// while following
+    pub fn with_cluster(self, cluster: ClusterView) -> Self {
+        self.tenants.set_cluster(Some(Arc::new(cluster)));
+        self
+    }
+
+    /// Report a write-ahead log's size on the metrics endpoint
+    pub fn with_wal(mut self, wal: Arc<WriteAheadLog>) -> Self {
+        self.wal = Some(wal);
+        self
+    }
+
+    /// Sources sampled by the metrics endpoint
+    pub fn metrics_source(&self) -> MetricsSource {
+        MetricsSource {
+            tenants: Arc::clone(&self.tenants),
+            pool: Arc::clone(&self.pool),
+            wal: self.wal.clone(),
         }
     }
 
+    /// Host an additional database under a name
+    ///
+    /// Clients select it with `Request::UseDatabase`; HTTP and gRPC
+    /// requests name it in the `x-aresadb-database` header.
+    pub fn add_database(&self, name: &str, db: Database, limits: TenantLimits) -> Result<()> {
+        self.tenants.add(name, RequestHandler::new(db), limits)
+    }
+
+    /// Names of all hosted databases
+    pub fn databases(&self) -> Vec<String> {
+        self.tenants.names()
+    }
+
     /// Start the server
     pub async fn run(&self) -> Result<()> {
         let listener = TcpListener::bind(&self.config.bind_addr)
             .await
             .context("Failed to bind server")?;
 
-        info!("AresaDB server listening on {}", self.config.bind_addr);
+        self.serve(listener).await
+    }
+
+    /// Serve the binary protocol (and any configured APIs) on a bound listener
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        info!("AresaDB server listening on {}", listener.local_addr()?);
+
+        if let Some(http_addr) = self.config.http_addr {
+            let tenants = Arc::clone(&self.tenants);
+            tokio::spawn(async move {
+                if let Err(e) = http::serve(http_addr, tenants).await {
+                    error!("HTTP API error: {}", e);
+                }
+            });
+        }
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = self.config.grpc_addr {
+            let tenants = Arc::clone(&self.tenants);
+            tokio::spawn(async move {
+                if let Err(e) = grpc::serve(grpc_addr, tenants).await {
+                    error!("gRPC API error: {}", e);
+                }
+            });
+        }
+
+        if let Some(metrics_addr) = self.config.metrics_addr {
+            let source = self.metrics_source();
+            tokio::spawn(async move {
+                if let Err(e) = metrics::serve(metrics_addr, source).await {
+                    error!("Metrics endpoint error: {}", e);
+                }
+            });
+        }
+
+        for name in self.tenants.names() {
+            if let Some(tenant) = self.tenants.get(&name) {
+                tenant.handler().start_maintenance();
+            }
+        }
 
         while !*self.shutdown.read() {
-            match listener.accept().await {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = self.shutdown_notify.notified() => break,
+            };
+            match accepted {
                 Ok((stream, addr)) => {
                     debug!("New connection from {}", addr);
 
@@ -104,12 +259,14 @@ impl Server {
                         continue;
                     }
 
-                    let handler = Arc::clone(&self.handler);
+                    let tenants = Arc::clone(&self.tenants);
+                    let sessions = Arc::clone(&self.sessions);
                     let pool = Arc::clone(&self.pool);
-                    let compression = self.config.compression;
+                    let limiter = self.pool.connection_limiter();
+                    let settings = ConnectionSettings::from_config(&self.config);
 
                     tokio::spawn(async move {
-                        if let Err(e) = handle_connection(stream, handler, compression).await {
+                        if let Err(e) = handle_connection(stream, tenants, sessions, limiter, settings).await {
                             warn!("Connection error from {}: {}", addr, e);
                         }
                         pool.release();
@@ -127,84 +284,393 @@ impl Server {
     }
 
     /// Shutdown the server
+    ///
+    /// Stops accepting connections; connections already open keep running
+    /// until they close (see [`Server::drain`]).
     pub fn shutdown(&self) {
         *self.shutdown.write() = true;
+        self.shutdown_notify.notify_one();
+    }
+
+    /// Wait for open connections to close, giving up after `timeout`
+    ///
+    /// Returns the number of connections still open.
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.pool.active_count() > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.pool.active_count()
     }
 
     /// Get current connection count
     pub fn connection_count(&self) -> usize {
         self.pool.active_count()
     }
+
+    /// Connected TCP clients
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.sessions.list()
+    }
+}
+
+/// Per-connection settings taken from the server configuration
+#[derive(Debug, Clone, Copy)]
+struct ConnectionSettings {
+    compression: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    require_hello: bool,
+    chunk_size_bytes: usize,
+}
+
+impl ConnectionSettings {
+    fn from_config(config: &ServerConfig) -> Self {
+        let timeout = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        Self {
+            compression: config.compression,
+            read_timeout: timeout(config.read_timeout_secs),
+            write_timeout: timeout(config.write_timeout_secs),
+            require_hello: config.require_hello,
+            chunk_size_bytes: config.chunk_size_bytes,
+        }
+    }
 }
 
 /// Handle a single client connection
+///
+/// Requests are answered in order. Once the client subscribes, change
+/// notifications are interleaved with responses as `Response::Change`.
+/// The connection holds a slot on its current database until it switches
+/// or disconnects.
 async fn handle_connection(
-    mut stream: TcpStream,
-    handler: Arc<RequestHandler>,
-    compression: bool,
+    stream: TcpStream,
+    tenants: Arc<TenantRegistry>,
+    sessions: Arc<SessionRegistry>,
+    limiter: Option<RateLimiter>,
+    settings: ConnectionSettings,
+) -> Result<()> {
+    let Ok(mut permit) = tenants.default_tenant().admit() else {
+        anyhow::bail!("Connection limit reached for database {}", DEFAULT_DATABASE);
+    };
+
+    let session = sessions.open(stream.peer_addr().ok(), permit.tenant().name());
+    let result = serve_connection(stream, &tenants, &sessions, &session, &mut permit, limiter, settings).await;
+    sessions.close(session.id());
+    result
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    tenants: &TenantRegistry,
+    sessions: &SessionRegistry,
+    session: &Session,
+    permit: &mut TenantPermit,
+    limiter: Option<RateLimiter>,
+    settings: ConnectionSettings,
 ) -> Result<()> {
-    let compressor = if compression {
+    let compressor = if settings.compression {
         Some(crate::distributed::Compressor::new())
     } else {
         None
     };
 
-    loop {
-        // Read message length (4 bytes)
-        let mut len_buf = [0u8; 4];
-        match stream.read_exact(&mut len_buf).await {
-            Ok(_) => {}
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-            Err(e) => return Err(e.into()),
+    let client = stream.peer_addr().ok();
+    let (mut reader, mut writer) = stream.into_split();
+
+    // Frames are read on their own task so a half-read request is never
+    // dropped when a change notification wins the select below
+    let (frame_tx, mut frames) = mpsc::channel::<std::io::Result<Vec<u8>>>(16);
+    let reader_task = tokio::spawn(async move {
+        loop {
+            match read_frame(&mut reader, settings.read_timeout).await {
+                Ok(Some(frame)) => {
+                    if frame_tx.send(Ok(frame)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = frame_tx.send(Err(e)).await;
+                    break;
+                }
+            }
         }
+    });
 
-        let msg_len = u32::from_le_bytes(len_buf) as usize;
+    let mut subscriptions = SubscriptionSet::default();
+    // Set once the connection is authenticated (immediately if auth is off)
+    let denied = Principal::denied();
+    let mut principal = (!permit.tenant().handler().auth_required()).then(Principal::unrestricted);
+    // Protocol version agreed with `Request::Hello`; `None` for unversioned clients
+    let mut negotiated: Option<u32> = None;
+    let mut session_settings = SessionSettings::default();
 
-        // Read message body
-        let mut body = vec![0u8; msg_len];
-        stream.read_exact(&mut body).await?;
+    let result: Result<()> = async {
+        loop {
+            tokio::select! {
+                frame = frames.recv() => {
+                    let Some(body) = frame else { break };
+                    let body = body?;
+                    let frame_len = body.len() as u64;
 
-        // Decompress if needed
-        let body = if let Some(ref comp) = compressor {
-            comp.decompress(&body)?
-        } else {
-            body
-        };
+                    if limiter.as_ref().is_some_and(|l| !l.try_acquire(frame_len)) {
+                        let response = Response::error(ErrorCode::RateLimited, "Rate limit exceeded for connection");
+                        send_response(&mut writer, &response, compressor.as_ref(), settings.write_timeout).await?;
+                        continue;
+                    }
 
-        // Parse request
-        let request: Request = match bincode::deserialize(&body) {
-            Ok(req) => req,
-            Err(e) => {
-                let response = Response::Error {
-                    code: ErrorCode::InvalidRequest,
-                    message: format!("Failed to parse request: {}", e),
-                };
-                send_response(&mut stream, &response, compressor.as_ref()).await?;
-                continue;
-            }
-        };
+                    // Decompress if needed
+                    let body = if let Some(ref comp) = compressor {
+                        comp.decompress(&body)?
+                    } else {
+                        body
+                    };
+
+                    // Parse request. Unversioned clients may not know newer
+                    // error codes, so version problems are reported to them
+                    // as InvalidRequest with an explanatory message.
+                    let request: Request = match bincode::deserialize(&body) {
+                        Ok(req) => req,
+                        Err(e) => {
+                            let message = match negotiated {
+                                Some(_) => format!("Failed to parse request: {}", e),
+                                None => format!(
+                                    "Failed to parse request: {} (possible protocol version mismatch; \
+                                     this server speaks version {})",
+                                    e, PROTOCOL_VERSION,
+                                ),
+                            };
+                            let response = Response::error(ErrorCode::InvalidRequest, message);
+                            send_response(&mut writer, &response, compressor.as_ref(), settings.write_timeout).await?;
+                            continue;
+                        }
+                    };
+
+                    if settings.require_hello && negotiated.is_none() && !matches!(request, Request::Hello { .. }) {
+                        let response = Response::error(
+                            ErrorCode::InvalidRequest,
+                            format!(
+                                "Protocol version mismatch: this server requires clients to negotiate \
+                                 protocol version {}; upgrade the client",
+                                PROTOCOL_VERSION,
+                            ),
+                        );
+                        send_response(&mut writer, &response, compressor.as_ref(), settings.write_timeout).await?;
+                        continue;
+                    }
+
+                    // The frame paid for one request; a batch pays for the rest of its parts
+                    let requests = request.request_count();
+                    if requests > 1 && limiter.as_ref().is_some_and(|l| !l.try_acquire_many(requests - 1, 0)) {
+                        let response = Response::error(ErrorCode::RateLimited, "Rate limit exceeded for connection");
+                        send_response(&mut writer, &response, compressor.as_ref(), settings.write_timeout).await?;
+                        continue;
+                    }
 
-        // Handle request
-        let response = handler.handle(request).await;
+                    // Handle request
+                    let handler = Arc::clone(permit.tenant().handler());
+                    let response = match request {
+                        request if principal.is_none() && !request.is_public() => {
+                            Response::error(ErrorCode::Unauthenticated, "Authentication required")
+                        }
+                        Request::Hello { protocol_version, .. } => {
+                            let response = Response::hello(protocol_version);
+                            if let Response::Welcome { protocol_version, .. } = response {
+                                debug!("Connection negotiated protocol version {}", protocol_version);
+                                negotiated = Some(protocol_version);
+                            }
+                            response
+                        }
+                        Request::Authenticate { credentials } => {
+                            match handler.authenticate(&credentials).await {
+                                Ok(authenticated) => {
+                                    let username = authenticated.username()
+                                        .unwrap_or("anonymous")
+                                        .to_string();
+                                    debug!("Connection authenticated as {}", username);
+                                    session.set_user(authenticated.username());
+                                    principal = Some(authenticated);
+                                    Response::Authenticated { username }
+                                }
+                                Err(response) => response,
+                            }
+                        }
+                        Request::Subscribe { node_type, predicate } => {
+                            let principal = principal.as_ref().unwrap_or(&denied);
+                            match handler.create_subscription(principal, node_type, predicate.as_deref()) {
+                                Ok(subscription) => {
+                                    let subscription_id = subscription.id();
+                                    subscriptions.add(subscription, || handler.subscribe_changes());
+                                    Response::Subscribed { subscription_id }
+                                }
+                                Err(response) => response,
+                            }
+                        }
+                        Request::UseDatabase { name } => {
+                            match tenants.get(&name) {
+                                None => Response::error(
+                                    ErrorCode::DatabaseNotFound,
+                                    format!("Database not found: {}", name),
+                                ),
+                                Some(next) if Arc::ptr_eq(&next, permit.tenant()) => {
+                                    Response::DatabaseSelected { name }
+                                }
+                                // Connections let in without credentials must
+                                // authenticate for a database that wants them
+                                Some(next) if next.handler().auth_required()
+                                    && principal.as_ref().is_some_and(Principal::is_unrestricted) => {
+                                    Response::error(ErrorCode::Unauthenticated, "Authentication required")
+                                }
+                                Some(next) => match next.admit() {
+                                    Ok(next) => {
+                                        // Subscriptions follow the old database's change feed
+                                        subscriptions = SubscriptionSet::default();
+                                        *permit = next;
+                                        session.set_database(&name);
+                                        debug!("Connection switched to database {}", name);
+                                        Response::DatabaseSelected { name }
+                                    }
+                                    Err(response) => response,
+                                },
+                            }
+                        }
+                        Request::Unsubscribe { subscription_id } => {
+                            if subscriptions.remove(subscription_id) {
+                                Response::Ok
+                            } else {
+                                Response::error(ErrorCode::InvalidRequest, "Subscription not found")
+                            }
+                        }
+                        Request::Set { name, value } => match session_settings.set(&name, &value) {
+                            Ok(()) => Response::Ok,
+                            Err(e) => Response::error(ErrorCode::InvalidRequest, e.to_string()),
+                        },
+                        Request::Show { name: None } => Response::Settings(session_settings.show()),
+                        Request::Show { name: Some(name) } => match session_settings.get(&name) {
+                            Ok(value) => Response::Settings(vec![(name, value)]),
+                            Err(e) => Response::error(ErrorCode::InvalidRequest, e.to_string()),
+                        },
+                        Request::ListSessions | Request::KillQuery { .. }
+                            if !principal.as_ref().is_some_and(Principal::is_admin) =>
+                        {
+                            Response::error(ErrorCode::PermissionDenied, "Session administration requires admin")
+                        }
+                        Request::ListSessions => Response::Sessions(sessions.list()),
+                        Request::KillQuery { session_id } => match sessions.get(session_id) {
+                            None => Response::error(
+                                ErrorCode::InvalidRequest,
+                                format!("Session not found: {}", session_id),
+                            ),
+                            Some(target) if target.cancel() => Response::Ok,
+                            Some(_) => Response::error(
+                                ErrorCode::InvalidRequest,
+                                format!("Session {} has no running request", session_id),
+                            ),
+                        },
+                        request => {
+                            let principal = principal.as_ref().unwrap_or(&denied);
+                            match handler.check_rate_limit(principal, requests, frame_len) {
+                                Ok(()) => {
+                                    let cancel = session.begin(&request);
+                                    let handled = handler.handle_with(principal, client, &session_settings, request);
+                                    let response = tokio::select! {
+                                        response = handled => response,
+                                        _ = cancel.notified() => Response::error(
+                                            ErrorCode::Cancelled,
+                                            "Request cancelled by an administrator",
+                                        ),
+                                    };
+                                    session.finish();
+                                    response
+                                }
+                                Err(response) => response,
+                            }
+                        }
+                    };
 
-        // Send response
-        send_response(&mut stream, &response, compressor.as_ref()).await?;
+                    // Send response, charging its size to the bandwidth limits.
+                    // Clients that understand chunks get large results in
+                    // parts rather than as one huge frame.
+                    let goodbye = matches!(response, Response::Goodbye);
+                    let frames = match negotiated {
+                        Some(version) if version >= CHUNKED_PROTOCOL_VERSION => {
+                            response.into_chunks(settings.chunk_size_bytes)
+                        }
+                        _ => vec![response],
+                    };
+                    let mut sent = 0;
+                    for frame in &frames {
+                        sent += send_response(&mut writer, frame, compressor.as_ref(), settings.write_timeout).await?;
+                    }
+                    if let Some(ref limiter) = limiter {
+                        limiter.charge_bytes(sent);
+                    }
+                    if let Some(ref principal) = principal {
+                        handler.charge_rate_limit(principal, sent);
+                    }
 
-        // Check for disconnect request
-        if matches!(response, Response::Goodbye) {
-            break;
+                    // Check for disconnect request
+                    if goodbye {
+                        break;
+                    }
+                }
+                Some((ids, event)) = subscriptions.next() => {
+                    for subscription_id in ids {
+                        let response = Response::Change { subscription_id, event: Box::new(event.clone()) };
+                        send_response(&mut writer, &response, compressor.as_ref(), settings.write_timeout).await?;
+                    }
+                }
+            }
         }
+        Ok(())
+    }.await;
+
+    reader_task.abort();
+    result
+}
+
+/// Read one length-prefixed frame; `None` on a clean disconnect
+///
+/// The timeout starts once the length prefix arrives, so idle connections
+/// are left alone but a client can't stall halfway through a request.
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    body_timeout: Option<Duration>,
+) -> std::io::Result<Option<Vec<u8>>> {
+    // Read message length (4 bytes)
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
     }
 
-    Ok(())
+    let msg_len = u32::from_le_bytes(len_buf) as usize;
+
+    // Read message body
+    let mut body = vec![0u8; msg_len];
+    let read = reader.read_exact(&mut body);
+    match body_timeout {
+        Some(limit) => {
+            tokio::time::timeout(limit, read).await.map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::TimedOut, "Timed out reading request")
+            })??;
+        }
+        None => {
+            read.await?;
+        }
+    }
+    Ok(Some(body))
 }
 
-/// Send a response to the client
-async fn send_response(
-    stream: &mut TcpStream,
+/// Send a response to the client, returning the bytes written
+async fn send_response<W: AsyncWrite + Unpin>(
+    stream: &mut W,
     response: &Response,
     compressor: Option<&crate::distributed::Compressor>,
-) -> Result<()> {
+    write_timeout: Option<Duration>,
+) -> Result<u64> {
     let body = bincode::serialize(response)?;
 
     let body = if let Some(comp) = compressor {
@@ -214,11 +680,17 @@ async fn send_response(
     };
 
     let len = body.len() as u32;
-    stream.write_all(&len.to_le_bytes()).await?;
-    stream.write_all(&body).await?;
-    stream.flush().await?;
+    let write = async {
+        stream.write_all(&len.to_le_bytes()).await?;
+        stream.write_all(&body).await?;
+        stream.flush().await
+    };
+    match write_timeout {
+        Some(limit) => tokio::time::timeout(limit, write).await.context("Timed out writing response")??,
+        None => write.await?,
+    }
 
-    Ok(())
+    Ok(4 + body.len() as u64)
 }
 
 #[cfg(test)]
@@ -231,6 +703,7 @@ mod tests {
         let config = ServerConfig::default();
         assert_eq!(config.max_connections, 1000);
         assert!(config.compression);
+        assert!(config.http_addr.is_none());
     }
 
     #[tokio::test]
@@ -243,4 +716,659 @@ mod tests {
 
         assert_eq!(server.connection_count(), 0);
     }
+
+    #[tokio::test]
+    async fn test_subscribe_over_tcp() {
+        use crate::client::Client;
+        use crate::storage::{ChangeKind, ChangeRecord};
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let subscriber = Client::connect(addr).await.unwrap();
+        let subscription_id = subscriber.subscribe(Some("user"), Some("active = true")).await.unwrap();
+
+        let writer = Client::connect(addr).await.unwrap();
+        writer.insert_node("user", serde_json::json!({"active": false})).await.unwrap();
+        let node = writer.insert_node("user", serde_json::json!({"active": true})).await.unwrap();
+        writer.insert_node("post", serde_json::json!({"active": true})).await.unwrap();
+
+        let change = subscriber.next_change().await.unwrap();
+        assert_eq!(change.subscription_id, subscription_id);
+        assert_eq!(change.event.kind, ChangeKind::Insert);
+        match change.event.record {
+            ChangeRecord::Node(changed) => assert_eq!(changed.id, node.id),
+            ChangeRecord::Edge(_) => panic!("Expected node change"),
+        }
+
+        // Requests still work on a subscribed connection
+        subscriber.ping().await.unwrap();
+        subscriber.unsubscribe(subscription_id).await.unwrap();
+        assert!(subscriber.unsubscribe(subscription_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_while_waiting_for_changes() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let subscriber = Client::connect(addr).await.unwrap();
+        let users = subscriber.subscribe(Some("user"), None).await.unwrap();
+        let waiting = subscriber.clone();
+        let next = tokio::spawn(async move { waiting.next_change().await });
+        tokio::task::yield_now().await;
+
+        // A pending next_change doesn't hold up requests on the subscription connection
+        let limit = std::time::Duration::from_secs(5);
+        let posts = tokio::time::timeout(limit, subscriber.subscribe(Some("post"), None)).await.unwrap().unwrap();
+        tokio::time::timeout(limit, subscriber.unsubscribe(posts)).await.unwrap().unwrap();
+
+        let writer = Client::connect(addr).await.unwrap();
+        writer.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        let change = tokio::time::timeout(limit, next).await.unwrap().unwrap().unwrap();
+        assert_eq!(change.subscription_id, users);
+    }
+
+    #[tokio::test]
+    async fn test_authentication_required() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let users = UserStore::open(temp.path()).unwrap();
+        users.add_user("alice", Some("s3cret"), &[crate::auth::ADMIN_ROLE.to_string()]).unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()).with_user_store(users));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        client.ping().await.unwrap();
+        assert!(client.status().await.is_err());
+        assert!(client.use_database(DEFAULT_DATABASE).await.is_err());
+        assert!(client.authenticate_password("alice", "wrong").await.is_err());
+        assert!(client.status().await.is_err());
+
+        let username = client.authenticate_password("alice", "s3cret").await.unwrap();
+        assert_eq!(username, "alice");
+        client.status().await.unwrap();
+        client.use_database(DEFAULT_DATABASE).await.unwrap();
+
+        // The builder authenticates before selecting its database
+        let client = Client::builder()
+            .address(&addr.to_string())
+            .password("alice", "s3cret")
+            .database(DEFAULT_DATABASE)
+            .build()
+            .await
+            .unwrap();
+        client.status().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_multi_tenant() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path().join("main"), "main").await.unwrap();
+        let analytics = Database::create(temp.path().join("analytics"), "analytics").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+        let limits = TenantLimits { max_connections: Some(1), max_result_rows: Some(1) };
+        server.add_database("analytics", analytics, limits).unwrap();
+        assert_eq!(server.databases(), vec!["analytics".to_string(), DEFAULT_DATABASE.to_string()]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::builder().address(&addr.to_string()).database("analytics").build().await.unwrap();
+        client.insert_node("event", serde_json::json!({"n": 1})).await.unwrap();
+        client.insert_node("event", serde_json::json!({"n": 2})).await.unwrap();
+        assert_eq!(client.get_nodes_by_type("event", None).await.unwrap().len(), 1);
+
+        // The tenant only allows one connection
+        let other = Client::connect(addr).await.unwrap();
+        assert!(other.use_database("analytics").await.is_err());
+        assert!(other.use_database("missing").await.is_err());
+        assert!(other.get_nodes_by_type("event", None).await.unwrap().is_empty());
+
+        client.use_database(DEFAULT_DATABASE).await.unwrap();
+        other.use_database("analytics").await.unwrap();
+        assert_eq!(other.get_nodes_by_type("event", None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_rate_limit() {
+        use crate::client::{Client, RetryPolicy};
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let config = ServerConfig {
+            connection_rate_limit: RateLimit { requests_per_sec: Some(3.0), bytes_per_sec: None },
+            ..Default::default()
+        };
+        let server = Arc::new(Server::new(db, config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::builder()
+            .address(&addr.to_string())
+            .retry(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        // The handshake's Hello counts against the budget too
+        client.ping().await.unwrap();
+        client.ping().await.unwrap();
+        let err = client.ping().await.unwrap_err();
+        assert!(err.to_string().contains("Rate limit"));
+
+        // Other connections have their own budget
+        Client::connect(addr).await.unwrap().ping().await.unwrap();
+
+        // With retries the client backs off until the budget refills
+        let client = Client::connect(addr).await.unwrap();
+        for _ in 0..4 {
+            client.ping().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_rate_limit() {
+        use crate::client::{Client, RetryPolicy};
+
+        async fn serve(config: ServerConfig, users: Option<UserStore>, temp: &TempDir) -> SocketAddr {
+            let db = Database::create(temp.path(), "test").await.unwrap();
+            let server = Server::new(db, config);
+            let server = Arc::new(match users {
+                Some(users) => server.with_user_store(users),
+                None => server,
+            });
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { server.serve(listener).await });
+            addr
+        }
+        let pings = |client: &Client| client.batch().request(Request::Ping).request(Request::Ping);
+
+        // Each part of a batch counts against the connection's limit; Hello
+        // and the first batch leave one of four
+        let temp = TempDir::new().unwrap();
+        let config = ServerConfig {
+            connection_rate_limit: RateLimit { requests_per_sec: Some(4.0), bytes_per_sec: None },
+            ..Default::default()
+        };
+        let addr = serve(config, None, &temp).await;
+        let client = Client::builder().address(&addr.to_string()).retry(RetryPolicy::none()).build().await.unwrap();
+        assert_eq!(pings(&client).send().await.unwrap().len(), 2);
+        let err = pings(&client).send().await.unwrap_err();
+        assert!(err.to_string().contains("Rate limit exceeded for connection"));
+
+        // And against the credential's
+        let temp = TempDir::new().unwrap();
+        let users = UserStore::open(temp.path()).unwrap();
+        users.add_user("ana", Some("s3cret"), &[crate::auth::ADMIN_ROLE.to_string()]).unwrap();
+        let config = ServerConfig {
+            key_rate_limit: RateLimit { requests_per_sec: Some(3.0), bytes_per_sec: None },
+            ..Default::default()
+        };
+        let addr = serve(config, Some(users), &temp).await;
+        let client = Client::builder()
+            .address(&addr.to_string())
+            .password("ana", "s3cret")
+            .retry(RetryPolicy::none())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(pings(&client).send().await.unwrap().len(), 2);
+        let err = pings(&client).send().await.unwrap_err();
+        assert!(err.to_string().contains("Rate limit exceeded for credential"));
+    }
+
+    #[tokio::test]
+    async fn test_client_reconnects() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        // Accept one connection, answer its Hello and drop it, then serve normally
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        let client = tokio::join!(Client::connect(addr), async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let compressor = crate::distributed::Compressor::new();
+            read_frame(&mut stream, None).await.unwrap();
+            send_response(&mut stream, &Response::hello(PROTOCOL_VERSION), Some(&compressor), None)
+                .await
+                .unwrap();
+        }).0.unwrap();
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        client.status().await.unwrap();
+        assert!(client.insert_node("item", serde_json::json!({})).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shared_client() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::builder().address(&addr.to_string()).max_connections(2).build().await.unwrap();
+        let tasks: Vec<_> = (0..8)
+            .map(|n| {
+                let client = client.clone();
+                tokio::spawn(async move { client.insert_node("item", serde_json::json!({"n": n})).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(client.get_nodes_by_type("item", None).await.unwrap().len(), 8);
+        assert!((1..=2).contains(&client.idle_connections()));
+    }
+
+    #[tokio::test]
+    async fn test_client_batch() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        let batch = (0..100).fold(client.batch(), |batch, n| {
+            batch.insert_node("item", serde_json::json!({"n": n}))
+        });
+        assert_eq!(batch.len(), 100);
+
+        let responses = batch.delete_node("missing").send().await.unwrap();
+        assert_eq!(responses.len(), 101);
+        assert!(responses[..100].iter().all(|r| matches!(r, Response::Node(_))));
+        assert!(responses[100].is_error());
+        assert_eq!(client.get_nodes_by_type("item", None).await.unwrap().len(), 100);
+        assert!(client.batch().send().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let config = ServerConfig { read_timeout_secs: 1, ..Default::default() };
+        let server = Arc::new(Server::new(db, config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        // Announce a 10 byte request and never send it
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&10u32.to_le_bytes()).await.unwrap();
+
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await;
+        assert!(matches!(read, Ok(Ok(0)) | Ok(Err(_))), "server should drop the stalled connection");
+    }
+
+    #[tokio::test]
+    async fn test_session_admin() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let users = UserStore::open(temp.path()).unwrap();
+        users.add_user("root", Some("s3cret"), &[crate::auth::ADMIN_ROLE.to_string()]).unwrap();
+        users.add_user("ana", Some("s3cret"), &[crate::auth::READER_ROLE.to_string()]).unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()).with_user_store(users));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let admin = Client::builder().address(&addr.to_string()).password("root", "s3cret").build().await.unwrap();
+        let reader = Client::builder().address(&addr.to_string()).password("ana", "s3cret").build().await.unwrap();
+        assert!(reader.list_sessions().await.is_err());
+
+        let sessions = admin.list_sessions().await.unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.iter().all(|s| s.database == DEFAULT_DATABASE && s.operation.is_none()));
+        let ana = sessions.iter().find(|s| s.user.as_deref() == Some("ana")).unwrap();
+
+        // Nothing is running on the reader's connection
+        assert!(admin.kill_query(ana.id).await.is_err());
+        assert!(admin.kill_query(9999).await.is_err());
+        assert!(reader.kill_query(ana.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_version_negotiation() {
+        use crate::client::Client;
+        use crate::distributed::Compressor;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let config = ServerConfig { require_hello: true, ..Default::default() };
+        let server = Arc::new(Server::new(db, config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        assert_eq!(client.server_info().unwrap().protocol_version, PROTOCOL_VERSION);
+        client.ping().await.unwrap();
+
+        // An unversioned client gets an error it can decode
+        let compressor = Compressor::new();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let body = compressor.compress(&bincode::serialize(&Request::Ping).unwrap()).unwrap();
+        stream.write_all(&(body.len() as u32).to_le_bytes()).await.unwrap();
+        stream.write_all(&body).await.unwrap();
+
+        let body = read_frame(&mut stream, None).await.unwrap().unwrap();
+        let response: Response = bincode::deserialize(&compressor.decompress(&body).unwrap()).unwrap();
+        match response {
+            Response::Error { code, message } => {
+                assert_eq!(code, ErrorCode::InvalidRequest);
+                assert!(message.contains("version mismatch"));
+            }
+            _ => panic!("Expected error response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunked_responses() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let config = ServerConfig { chunk_size_bytes: 512, ..Default::default() };
+        let server = Arc::new(Server::new(db, config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        let mut batch = client.batch();
+        for i in 0..50 {
+            batch = batch.insert_node("item", serde_json::json!({ "n": i }));
+        }
+        batch.send().await.unwrap();
+
+        assert_eq!(client.get_nodes_by_type("item", None).await.unwrap().len(), 50);
+        let result = client.query("SELECT * FROM item", None).await.unwrap();
+        assert_eq!(result.rows.len(), 50);
+
+        // The connection is still in step after the chunked replies
+        client.ping().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_failover() {
+        use crate::client::Client;
+        use crate::distributed::{ReplicaConfig, ReplicaSet};
+
+        let temp = TempDir::new().unwrap();
+        let replica = |id: &str| {
+            Arc::new(ReplicaSet::new(ReplicaConfig { node_id: id.to_string(), ..Default::default() }))
+        };
+        let (a, b) = (replica("a"), replica("b"));
+
+        let listener_a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener_b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (addr_a, addr_b) = (listener_a.local_addr().unwrap(), listener_b.local_addr().unwrap());
+        for (name, replica, listener) in [("a", &a, listener_a), ("b", &b, listener_b)] {
+            let db = Database::create(temp.path().join(name), name).await.unwrap();
+            let cluster = ClusterView::new(Arc::clone(replica))
+                .with_address("a", addr_a.to_string())
+                .with_address("b", addr_b.to_string());
+            let server = Server::new(db, ServerConfig::default()).with_cluster(cluster);
+            tokio::spawn(async move { server.serve(listener).await });
+        }
+
+        // A leads; a client pointed at follower B is redirected on its first write
+        a.become_leader();
+        b.process_message(a.create_heartbeat());
+        let client = Client::builder()
+            .address(&addr_b.to_string())
+            .peer(addr_a.to_string())
+            .build()
+            .await
+            .unwrap();
+        client.ping().await.unwrap();
+        assert_eq!(client.addr(), addr_b);
+        client.insert_node("item", serde_json::json!({})).await.unwrap();
+        assert_eq!(client.addr(), addr_a);
+
+        // Without peers the follower's refusal is surfaced
+        let plain = Client::connect(addr_b).await.unwrap();
+        let err = plain.insert_node("item", serde_json::json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("Not the leader"));
+
+        // Followers serve reads unless the session asks for strong consistency
+        assert!(plain.get_nodes_by_type("item", None).await.is_ok());
+        plain.set("consistency", "strong").await.unwrap();
+        assert!(plain.get_nodes_by_type("item", None).await.is_err());
+
+        // A client whose server is down finds the leader through its peers
+        let gone = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let client = Client::builder()
+            .address(&gone.to_string())
+            .peer(addr_b.to_string())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.addr(), addr_a);
+        client.insert_node("item", serde_json::json!({})).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_settings() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::builder()
+            .address(&addr.to_string())
+            .set("timezone", "+01:00")
+            .build()
+            .await
+            .unwrap();
+        for i in 0..5 {
+            client.insert_node("item", serde_json::json!({ "n": i })).await.unwrap();
+        }
+
+        client.set("default_limit", "2").await.unwrap();
+        assert_eq!(client.get_nodes_by_type("item", None).await.unwrap().len(), 2);
+        assert_eq!(client.query("SELECT * FROM item", None).await.unwrap().rows.len(), 2);
+        assert_eq!(client.get_nodes_by_type("item", Some(4)).await.unwrap().len(), 4);
+
+        assert!(client.query("SELECT * FROM item", None).await.unwrap().stats.is_none());
+        client.set("timing", "on").await.unwrap();
+        let stats = client.query("SELECT * FROM item", None).await.unwrap().stats.unwrap();
+        assert_eq!(stats.rows_scanned, 5);
+        assert!(stats.stages.iter().any(|s| s.stage == "scan"));
+
+        let settings = client.show(None).await.unwrap();
+        assert!(settings.contains(&("timezone".to_string(), "+01:00".to_string())));
+        assert_eq!(client.show(Some("default_limit")).await.unwrap()[0].1, "2");
+        assert!(client.set("default_limit", "many").await.is_err());
+        assert!(client.show(Some("colour")).await.is_err());
+
+        // Connections opened later get the same settings
+        let busy: Vec<_> = (0..3).map(|_| client.get_nodes_by_type("item", None)).collect();
+        for nodes in futures::future::join_all(busy).await {
+            assert_eq!(nodes.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prepared_statements() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        let insert = client.prepare("INSERT INTO item (name, n) VALUES ($1, $2)").await.unwrap();
+        assert_eq!(insert.param_count(), 2);
+        for n in 0..4 {
+            let params = [serde_json::json!(format!("item{}", n)), serde_json::json!(n)];
+            insert.execute(&params, None).await.unwrap();
+        }
+        assert!(insert.execute(&[serde_json::json!("x")], None).await.is_err());
+
+        let select = client.prepare("SELECT * FROM item WHERE n >= ?").await.unwrap();
+        assert_eq!(select.execute(&[serde_json::json!(2)], None).await.unwrap().rows.len(), 2);
+        assert_eq!(select.execute(&[serde_json::json!(0)], Some(3)).await.unwrap().rows.len(), 3);
+
+        // Preparing the same SQL shares the statement
+        let handler = Arc::clone(server.tenants.default_tenant().handler());
+        let Response::Prepared { statement_id, .. } = handler
+            .handle(Request::Prepare { sql: select.sql().to_string() })
+            .await else {
+            panic!("Expected Prepared");
+        };
+
+        // A dropped statement is prepared again on next use
+        let response = handler.handle(Request::Deallocate { statement_id }).await;
+        assert!(matches!(response, Response::Ok));
+        assert_eq!(select.execute(&[serde_json::json!(3)], None).await.unwrap().rows.len(), 1);
+        select.close().await.unwrap();
+
+        let response = handler
+            .handle(Request::Execute { statement_id, params: Vec::new(), limit: None })
+            .await;
+        assert!(matches!(response, Response::Error { code: ErrorCode::StatementNotFound, .. }));
+        assert!(client.prepare("SELEC nonsense").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vector_and_rag_requests() {
+        use crate::client::Client;
+        use crate::rag::ContextOptions;
+        use crate::storage::DistanceMetric;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        let rust = client
+            .insert_with_embedding("chunk", serde_json::json!({"content": "Rust is fast"}), "embedding", vec![1.0, 0.0])
+            .await
+            .unwrap();
+        let tea = client
+            .insert_with_embedding("chunk", serde_json::json!({"content": "Tea is hot"}), "embedding", vec![0.0, 1.0])
+            .await
+            .unwrap();
+
+        let results = client
+            .similarity_search("chunk", &[0.9, 0.1], "embedding", 1, DistanceMetric::Cosine)
+            .await
+            .unwrap();
+        assert_eq!(results[0].node_id, rust.id);
+
+        let context = client
+            .retrieve_context(&[0.1, 0.9], "what is hot?", ContextOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(context.query, "what is hot?");
+        assert_eq!(context.chunks[0].content, "Tea is hot");
+
+        client.create_edge(&rust.id.to_string(), &tea.id.to_string(), "related", None).await.unwrap();
+        let traversal = client.traverse(&rust.id.to_string(), 1, Some(&["related"])).await.unwrap();
+        assert_eq!(traversal.nodes.len(), 2);
+        assert_eq!(traversal.edges.len(), 1);
+        assert!(client.traverse(&tea.id.to_string(), 1, Some(&["other"])).await.unwrap().edges.is_empty());
+        assert!(client.traverse("missing", 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_requests() {
+        use crate::client::Client;
+
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let server = Arc::new(Server::new(db, ServerConfig::default()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let serving = Arc::clone(&server);
+        tokio::spawn(async move { serving.serve(listener).await });
+
+        let client = Client::connect(addr).await.unwrap();
+        let schema = client.create_schema("user", "name:string:required,age:int").await.unwrap();
+        assert_eq!(schema.fields.len(), 2);
+        assert!(!schema.fields[0].nullable);
+
+        let schemas = client.list_schemas().await.unwrap();
+        assert_eq!(schemas.len(), 1);
+        assert_eq!(schemas[0].name, "user");
+        assert!(client.migrate().await.unwrap().is_empty());
+
+        client.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        assert!(client.drop_schema("user", false).await.is_err());
+        client.drop_schema("user", true).await.unwrap();
+        assert!(client.list_schemas().await.unwrap().is_empty());
+    }
 }
@@ -1,10 +1,13 @@
 //! Connection Pool
 //!
-//! Manages concurrent connections with semaphore-based limiting.
+//! Manages concurrent connections with semaphore-based limiting and hands
+//! out per-connection rate limiters.
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::Semaphore;
 
+use super::rate_limit::{RateLimit, RateLimiter};
+
 /// Connection pool for limiting concurrent connections
 pub struct ConnectionPool {
     /// Maximum connections allowed
@@ -13,6 +16,8 @@ pub struct ConnectionPool {
     semaphore: Semaphore,
     /// Current active connections
     active: AtomicUsize,
+    /// Rate limit applied to each connection
+    connection_limit: RateLimit,
 }
 
 impl ConnectionPool {
@@ -22,12 +27,28 @@ impl ConnectionPool {
             max_connections,
             semaphore: Semaphore::new(max_connections),
             active: AtomicUsize::new(0),
+            connection_limit: RateLimit::default(),
+        }
+    }
+
+    /// Create a pool whose connections are each rate limited
+    pub fn with_rate_limit(max_connections: usize, limit: RateLimit) -> Self {
+        Self {
+            connection_limit: limit,
+            ..Self::new(max_connections)
         }
     }
 
+    /// Rate limiter for a new connection (`None` when unlimited)
+    pub fn connection_limiter(&self) -> Option<RateLimiter> {
+        (!self.connection_limit.is_unlimited()).then(|| RateLimiter::new(self.connection_limit))
+    }
+
     /// Try to acquire a connection slot
     pub fn try_acquire(&self) -> bool {
-        if self.semaphore.try_acquire().is_ok() {
+        if let Ok(permit) = self.semaphore.try_acquire() {
+            // Held until `release` hands it back
+            permit.forget();
             self.active.fetch_add(1, Ordering::SeqCst);
             true
         } else {
@@ -94,4 +115,18 @@ mod tests {
         assert!(pool.try_acquire());
         assert_eq!(pool.active_count(), 2);
     }
+
+    #[test]
+    fn test_pool_connection_limiter() {
+        assert!(ConnectionPool::new(1).connection_limiter().is_none());
+
+        let limit = RateLimit { requests_per_sec: Some(1.0), bytes_per_sec: None };
+        let pool = ConnectionPool::with_rate_limit(1, limit);
+        let limiter = pool.connection_limiter().unwrap();
+        assert!(limiter.try_acquire(0));
+        assert!(!limiter.try_acquire(0));
+
+        // Each connection gets a fresh budget
+        assert!(pool.connection_limiter().unwrap().try_acquire(0));
+    }
 }
@@ -3,7 +3,24 @@
 //! Binary protocol using bincode for efficient serialization.
 
 use serde::{Serialize, Deserialize};
-use crate::storage::{Node, Edge, Value};
+use crate::auth::Credentials;
+use crate::query::{QueryOperation, QueryParser, QueryStats};
+use crate::rag::{ContextOptions, RetrievedContext};
+use crate::schema::{Migration, Schema};
+use super::session::SessionInfo;
+use crate::storage::{Node, Edge, Value, DistanceMetric, SimilarityResult, ChangeEvent};
+
+/// Wire protocol version spoken by this build
+///
+/// Bump when requests or responses change incompatibly. Clients that never
+/// send `Request::Hello` predate versioning and are served unversioned.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest protocol version this build can still speak
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// First protocol version that understands `Response::Chunk`
+pub(crate) const CHUNKED_PROTOCOL_VERSION: u32 = 2;
 
 /// Request types from client to server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +31,14 @@ pub enum Request {
     /// Disconnect from server
     Disconnect,
 
+    /// Authenticate the connection
+    ///
+    /// Required before any other request (except `Ping`) when the server
+    /// has users configured.
+    Authenticate {
+        credentials: Credentials,
+    },
+
     /// Insert a new node
     InsertNode {
         node_type: String,
@@ -73,6 +98,15 @@ pub enum Request {
         limit: Option<usize>,
     },
 
+    /// Vector similarity search over nodes of a type
+    SimilaritySearch {
+        node_type: String,
+        vector: Vec<f32>,
+        field: String,
+        k: usize,
+        metric: DistanceMetric,
+    },
+
     /// Graph traversal
     Traverse {
         start_id: String,
@@ -95,6 +129,138 @@ pub enum Request {
     RollbackTransaction {
         tx_id: u64,
     },
+
+    /// Subscribe to changes for a node type and/or predicate
+    ///
+    /// Only valid on streaming connections (TCP, WebSocket). Matching
+    /// changes are pushed as `Response::Change` frames.
+    Subscribe {
+        node_type: Option<String>,
+        predicate: Option<String>,
+    },
+
+    /// Cancel a subscription
+    Unsubscribe {
+        subscription_id: u64,
+    },
+
+    /// Switch the connection to another hosted database
+    ///
+    /// Part of the connection handshake; may be sent before `Authenticate`.
+    UseDatabase {
+        name: String,
+    },
+
+    /// Run several requests in one round trip
+    ///
+    /// Requests run in order and are answered by a `Response::Batch` with
+    /// one response per request. A failing request doesn't stop the rest.
+    /// Connection-level requests and nested batches are rejected.
+    Batch(Vec<Request>),
+
+    /// Run a request with a deadline
+    ///
+    /// If the request hasn't finished `timeout_ms` after the server reads
+    /// it, the server stops working on it and answers `ErrorCode::Timeout`.
+    WithDeadline {
+        timeout_ms: u64,
+        request: Box<Request>,
+    },
+
+    /// Negotiate the protocol version; sent first by versioned clients
+    ///
+    /// New variants must be appended to keep the encoding of existing ones
+    /// stable for older peers.
+    Hello {
+        protocol_version: u32,
+        format_version: u32,
+        client_version: String,
+    },
+
+    /// List connected clients and what they are running (admin only)
+    ListSessions,
+
+    /// Cancel the request a session is running (admin only)
+    KillQuery {
+        session_id: u64,
+    },
+
+    /// Ask which replica currently leads, so a client can find it after
+    /// a failover
+    WhoIsLeader,
+
+    /// Change a session setting for the rest of the connection
+    Set {
+        name: String,
+        value: String,
+    },
+
+    /// Read one session setting, or all of them
+    Show {
+        name: Option<String>,
+    },
+
+    /// Parse and plan SQL once for repeated execution
+    ///
+    /// The SQL may hold `$1`-style or `?` placeholders. Statements are
+    /// shared by every connection to the database, and preparing SQL that
+    /// is already prepared returns the existing statement.
+    Prepare {
+        sql: String,
+    },
+
+    /// Run a prepared statement with one value per placeholder
+    Execute {
+        statement_id: u64,
+        params: Vec<Value>,
+        limit: Option<usize>,
+    },
+
+    /// Drop a prepared statement
+    Deallocate {
+        statement_id: u64,
+    },
+
+    /// Insert a node with a vector embedding stored in `field`
+    InsertWithEmbedding {
+        node_type: String,
+        properties: Value,
+        field: String,
+        embedding: Vec<f32>,
+    },
+
+    /// Retrieve RAG context for an embedded query, within a token budget
+    ///
+    /// `query` is the text the vector was computed from; it is echoed back
+    /// in the context.
+    RetrieveContext {
+        vector: Vec<f32>,
+        query: String,
+        options: ContextOptions,
+    },
+
+    /// Create or replace a schema
+    ///
+    /// `fields` uses the CLI's syntax, e.g. `name:string:required,age:int`.
+    CreateSchema {
+        name: String,
+        fields: String,
+    },
+
+    /// List the schemas the caller may read
+    ListSchemas,
+
+    /// Drop a schema; without `force`, fails if nodes of the type exist
+    DropSchema {
+        name: String,
+        force: bool,
+    },
+
+    /// Run pending schema migrations (admin only)
+    Migrate,
+
+    /// Drop every cached query plan (admin only)
+    FlushPlanCache,
 }
 
 /// Response types from server to client
@@ -106,6 +272,11 @@ pub enum Response {
     /// Goodbye (connection closing)
     Goodbye,
 
+    /// Authentication succeeded
+    Authenticated {
+        username: String,
+    },
+
     /// Success with a single node
     Node(Node),
 
@@ -132,6 +303,9 @@ pub enum Response {
         execution_time_ms: u64,
     },
 
+    /// Similarity search results (best match first)
+    SimilarityResults(Vec<SimilarityResult>),
+
     /// Traversal results
     TraversalResult {
         nodes: Vec<Node>,
@@ -158,11 +332,84 @@ pub enum Response {
     /// Transaction rolled back
     TransactionRolledBack,
 
+    /// Database selected for the connection
+    DatabaseSelected {
+        name: String,
+    },
+
+    /// Subscription registered
+    Subscribed {
+        subscription_id: u64,
+    },
+
+    /// Pushed change notification (not a reply to any request)
+    Change {
+        subscription_id: u64,
+        event: Box<ChangeEvent>,
+    },
+
+    /// Connected sessions
+    Sessions(Vec<SessionInfo>),
+
+    /// Answer to `Request::Hello` with the version both sides will speak
+    Welcome {
+        protocol_version: u32,
+        format_version: u32,
+        server_version: String,
+    },
+
+    /// Responses to a `Request::Batch`, in request order
+    Batch(Vec<Response>),
+
+    /// Session settings and their values
+    Settings(Vec<(String, String)>),
+
+    /// Answer to `Request::WhoIsLeader`
+    ///
+    /// `leader` is the client address of the leading replica, if known.
+    /// A server that isn't replicated always reports itself as leader.
+    Leader {
+        leader: Option<String>,
+        is_leader: bool,
+    },
+
+    /// Created schema
+    Schema(Schema),
+
+    /// Schemas, for `Request::ListSchemas`
+    Schemas(Vec<Schema>),
+
+    /// Migrations applied by `Request::Migrate`
+    Migrations(Vec<Migration>),
+
+    /// Context retrieved for a `Request::RetrieveContext`
+    Context(RetrievedContext),
+
+    /// Statement prepared by `Request::Prepare`
+    Prepared {
+        statement_id: u64,
+        param_count: usize,
+    },
+
+    /// One frame of a large `Nodes` or `QueryResult` response, streamed
+    /// in parts; `more` is false on the last frame
+    Chunk {
+        response: Box<Response>,
+        more: bool,
+    },
+
     /// Error response
     Error {
         code: ErrorCode,
         message: String,
     },
+
+    /// Query result with how it was executed, for sessions with
+    /// `timing` on
+    Stats {
+        response: Box<Response>,
+        stats: QueryStats,
+    },
 }
 
 /// Error codes
@@ -188,6 +435,24 @@ pub enum ErrorCode {
     ServerOverloaded = 8,
     /// Internal error
     InternalError = 9,
+    /// Missing or invalid credentials
+    Unauthenticated = 10,
+    /// Requested database is not hosted by the server
+    DatabaseNotFound = 11,
+    /// Request or bandwidth rate limit exceeded
+    RateLimited = 12,
+    /// Request ran past its deadline
+    Timeout = 13,
+    /// Request was cancelled by an administrator
+    Cancelled = 14,
+    /// Client and server share no protocol version
+    VersionMismatch = 15,
+    /// Write sent to a replica that isn't the leader
+    NotLeader = 16,
+    /// Prepared statement is unknown, or was evicted; prepare it again
+    StatementNotFound = 17,
+    /// Query needed more memory than the database's limit allows
+    MemoryLimitExceeded = 18,
 }
 
 impl std::fmt::Display for ErrorCode {
@@ -203,10 +468,211 @@ impl std::fmt::Display for ErrorCode {
             ErrorCode::PermissionDenied => write!(f, "Permission denied"),
             ErrorCode::ServerOverloaded => write!(f, "Server overloaded"),
             ErrorCode::InternalError => write!(f, "Internal error"),
+            ErrorCode::Unauthenticated => write!(f, "Unauthenticated"),
+            ErrorCode::DatabaseNotFound => write!(f, "Database not found"),
+            ErrorCode::RateLimited => write!(f, "Rate limited"),
+            ErrorCode::Timeout => write!(f, "Timeout"),
+            ErrorCode::Cancelled => write!(f, "Cancelled"),
+            ErrorCode::VersionMismatch => write!(f, "Version mismatch"),
+            ErrorCode::NotLeader => write!(f, "Not leader"),
+            ErrorCode::StatementNotFound => write!(f, "Statement not found"),
+            ErrorCode::MemoryLimitExceeded => write!(f, "Memory limit exceeded"),
         }
     }
 }
 
+impl ErrorCode {
+    /// Whether the server rejected the request without running it, so
+    /// trying again later may succeed
+    pub fn is_transient(&self) -> bool {
+        matches!(self, ErrorCode::ServerOverloaded | ErrorCode::RateLimited)
+    }
+}
+
+impl Request {
+    /// Operation name used in logs and metrics
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Request::Ping => "ping",
+            Request::Disconnect => "disconnect",
+            Request::Authenticate { .. } => "authenticate",
+            Request::InsertNode { .. } => "insert_node",
+            Request::GetNode { .. } => "get_node",
+            Request::UpdateNode { .. } => "update_node",
+            Request::DeleteNode { .. } => "delete_node",
+            Request::GetNodesByType { .. } => "get_nodes_by_type",
+            Request::CreateEdge { .. } => "create_edge",
+            Request::GetEdgesFrom { .. } => "get_edges_from",
+            Request::GetEdgesTo { .. } => "get_edges_to",
+            Request::DeleteEdge { .. } => "delete_edge",
+            Request::Query { .. } => "query",
+            Request::SimilaritySearch { .. } => "similarity_search",
+            Request::Traverse { .. } => "traverse",
+            Request::Status => "status",
+            Request::BeginTransaction => "begin_transaction",
+            Request::CommitTransaction { .. } => "commit_transaction",
+            Request::RollbackTransaction { .. } => "rollback_transaction",
+            Request::Subscribe { .. } => "subscribe",
+            Request::Unsubscribe { .. } => "unsubscribe",
+            Request::UseDatabase { .. } => "use_database",
+            Request::Batch(_) => "batch",
+            Request::WithDeadline { request, .. } => request.operation(),
+            Request::Hello { .. } => "hello",
+            Request::ListSessions => "list_sessions",
+            Request::KillQuery { .. } => "kill_query",
+            Request::WhoIsLeader => "who_is_leader",
+            Request::Set { .. } => "set",
+            Request::Show { .. } => "show",
+            Request::Prepare { .. } => "prepare",
+            Request::Execute { .. } => "execute",
+            Request::Deallocate { .. } => "deallocate",
+            Request::InsertWithEmbedding { .. } => "insert_with_embedding",
+            Request::RetrieveContext { .. } => "retrieve_context",
+            Request::CreateSchema { .. } => "create_schema",
+            Request::ListSchemas => "list_schemas",
+            Request::DropSchema { .. } => "drop_schema",
+            Request::Migrate => "migrate",
+            Request::FlushPlanCache => "flush_plan_cache",
+        }
+    }
+
+    /// Whether repeating the request has the same effect as sending it once,
+    /// so it can be retried after a connection breaks mid-request
+    pub fn is_idempotent(&self) -> bool {
+        match self {
+            Request::Ping
+            | Request::Disconnect
+            | Request::Authenticate { .. }
+            | Request::UseDatabase { .. }
+            | Request::GetNode { .. }
+            | Request::UpdateNode { .. }
+            | Request::GetNodesByType { .. }
+            | Request::GetEdgesFrom { .. }
+            | Request::GetEdgesTo { .. }
+            | Request::SimilaritySearch { .. }
+            | Request::Traverse { .. }
+            | Request::Status
+            | Request::Unsubscribe { .. }
+            | Request::Hello { .. }
+            | Request::ListSessions
+            | Request::WhoIsLeader
+            | Request::Set { .. }
+            | Request::Show { .. }
+            | Request::Prepare { .. }
+            | Request::Deallocate { .. }
+            | Request::RetrieveContext { .. }
+            | Request::CreateSchema { .. }
+            | Request::ListSchemas
+            | Request::DropSchema { .. }
+            | Request::FlushPlanCache => true,
+            Request::Query { sql, .. } => QueryParser::new().parse(sql).is_ok_and(|query| {
+                matches!(
+                    query.operation,
+                    QueryOperation::Select | QueryOperation::Traverse | QueryOperation::VectorSearch
+                )
+            }),
+            Request::InsertNode { .. }
+            | Request::InsertWithEmbedding { .. }
+            | Request::DeleteNode { .. }
+            | Request::CreateEdge { .. }
+            | Request::DeleteEdge { .. }
+            | Request::BeginTransaction
+            | Request::CommitTransaction { .. }
+            | Request::RollbackTransaction { .. }
+            | Request::Subscribe { .. }
+            | Request::KillQuery { .. }
+            | Request::Execute { .. }
+            | Request::Migrate => false,
+            Request::Batch(requests) => requests.iter().all(Request::is_idempotent),
+            Request::WithDeadline { request, .. } => request.is_idempotent(),
+        }
+    }
+
+    /// Whether the request concerns the connection or server rather than
+    /// the database
+    ///
+    /// Streaming transports handle these outside the request handler, so
+    /// they can't be batched or given a deadline.
+    pub fn is_connection_state(&self) -> bool {
+        matches!(
+            self,
+            Request::Disconnect
+                | Request::Authenticate { .. }
+                | Request::UseDatabase { .. }
+                | Request::Subscribe { .. }
+                | Request::Unsubscribe { .. }
+                | Request::Hello { .. }
+                | Request::ListSessions
+                | Request::KillQuery { .. }
+                | Request::Set { .. }
+                | Request::Show { .. }
+        )
+    }
+
+    /// Whether the request may be part of a `Request::Batch`
+    ///
+    /// Connection-state requests must be sent on their own, and a deadline
+    /// applies to a whole batch rather than its parts.
+    pub fn is_batchable(&self) -> bool {
+        !self.is_connection_state()
+            && !matches!(self, Request::Batch(_) | Request::WithDeadline { .. })
+    }
+
+    /// Whether the request changes the database
+    ///
+    /// Only the leader of a replicated deployment accepts writes.
+    pub fn is_write(&self) -> bool {
+        match self {
+            Request::InsertNode { .. }
+            | Request::InsertWithEmbedding { .. }
+            | Request::CreateSchema { .. }
+            | Request::DropSchema { .. }
+            | Request::Migrate
+            | Request::UpdateNode { .. }
+            | Request::DeleteNode { .. }
+            | Request::CreateEdge { .. }
+            | Request::DeleteEdge { .. }
+            | Request::BeginTransaction
+            | Request::CommitTransaction { .. }
+            | Request::RollbackTransaction { .. } => true,
+            // Unparseable SQL fails the same way on any replica
+            Request::Query { sql, .. } => QueryParser::new().parse(sql).is_ok_and(|query| {
+                !matches!(
+                    query.operation,
+                    QueryOperation::Select | QueryOperation::Traverse | QueryOperation::VectorSearch
+                )
+            }),
+            Request::Batch(requests) => requests.iter().any(Request::is_write),
+            Request::WithDeadline { request, .. } => request.is_write(),
+            // The statement isn't known here; the handler checks the real one
+            Request::Execute { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// How many requests this counts as against rate limits: each part
+    /// of a batch is one
+    pub fn request_count(&self) -> u64 {
+        match self {
+            Request::Batch(requests) => requests.iter().map(Request::request_count).sum::<u64>().max(1),
+            Request::WithDeadline { request, .. } => request.request_count(),
+            _ => 1,
+        }
+    }
+
+    /// Whether the request may be sent before authenticating
+    pub fn is_public(&self) -> bool {
+        matches!(
+            self,
+            Request::Ping
+                | Request::Disconnect
+                | Request::WhoIsLeader
+                | Request::Hello { .. }
+                | Request::Authenticate { .. }
+        )
+    }
+}
+
 impl Response {
     /// Create an error response
     pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
@@ -216,10 +682,116 @@ impl Response {
         }
     }
 
+    /// Answer a `Request::Hello`, agreeing on the newest version both
+    /// sides speak
+    pub fn hello(client_version: u32) -> Self {
+        if client_version < MIN_PROTOCOL_VERSION {
+            return Response::error(
+                ErrorCode::VersionMismatch,
+                format!(
+                    "Protocol version mismatch: client speaks version {}, server needs {} to {}",
+                    client_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+                ),
+            );
+        }
+
+        Response::Welcome {
+            protocol_version: client_version.min(PROTOCOL_VERSION),
+            format_version: crate::FORMAT_VERSION,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
     /// Check if this is an error response
     pub fn is_error(&self) -> bool {
         matches!(self, Response::Error { .. })
     }
+
+    /// The response without its query statistics, and the statistics
+    pub fn split_stats(self) -> (Response, Option<QueryStats>) {
+        match self {
+            Response::Stats { response, stats } => (*response, Some(stats)),
+            response => (response, None),
+        }
+    }
+
+    /// Split a large `Nodes` or `QueryResult` into `Response::Chunk`
+    /// frames of roughly `max_bytes` each
+    ///
+    /// Other responses, and ones that already fit, come back whole.
+    pub fn into_chunks(self, max_bytes: usize) -> Vec<Response> {
+        // Statistics travel with the first part
+        if let Response::Stats { response, stats } = self {
+            let mut parts = response.into_chunks(max_bytes);
+            let first = match parts.first_mut() {
+                Some(Response::Chunk { response, .. }) => response.as_mut(),
+                Some(whole) => whole,
+                None => return parts,
+            };
+            let response = Box::new(std::mem::replace(first, Response::Ok));
+            *first = Response::Stats { response, stats };
+            return parts;
+        }
+
+        let size = bincode::serialized_size(&self).unwrap_or(0) as usize;
+        if max_bytes == 0 || size <= max_bytes {
+            return vec![self];
+        }
+        let parts = size.div_ceil(max_bytes);
+
+        let pieces: Vec<Response> = match self {
+            Response::Nodes(nodes) => {
+                let per_part = nodes.len().div_ceil(parts).max(1);
+                split(nodes, per_part).into_iter().map(Response::Nodes).collect()
+            }
+            Response::QueryResult { columns, rows, rows_affected, execution_time_ms } => {
+                let per_part = rows.len().div_ceil(parts).max(1);
+                split(rows, per_part)
+                    .into_iter()
+                    .map(|rows| Response::QueryResult {
+                        columns: columns.clone(),
+                        rows,
+                        rows_affected,
+                        execution_time_ms,
+                    })
+                    .collect()
+            }
+            response => return vec![response],
+        };
+        if pieces.len() < 2 {
+            return pieces;
+        }
+
+        let last = pieces.len() - 1;
+        pieces
+            .into_iter()
+            .enumerate()
+            .map(|(i, piece)| Response::Chunk { response: Box::new(piece), more: i < last })
+            .collect()
+    }
+
+    /// Append the rows of the next chunk of a streamed response
+    ///
+    /// Returns `false` if the chunk doesn't match the response's shape.
+    pub fn extend_from_chunk(&mut self, chunk: Response) -> bool {
+        match (self, chunk) {
+            (Response::Nodes(nodes), Response::Nodes(more)) => nodes.extend(more),
+            (Response::QueryResult { rows, .. }, Response::QueryResult { rows: more, .. }) => rows.extend(more),
+            (Response::Stats { response, .. }, chunk) => return response.extend_from_chunk(chunk),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Split items into consecutive runs of at most `per_part`
+fn split<T>(items: Vec<T>, per_part: usize) -> Vec<Vec<T>> {
+    let mut parts = Vec::with_capacity(items.len().div_ceil(per_part));
+    let mut items = items.into_iter().peekable();
+    while items.peek().is_some() {
+        parts.push(items.by_ref().take(per_part).collect());
+    }
+    parts
 }
 
 #[cfg(test)]
@@ -265,6 +837,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_change_serialization() {
+        use crate::storage::{ChangeKind, ChangeRecord, Timestamp};
+
+        let node = Node::new("user", Value::from_json(serde_json::json!({"name": "Alice"})).unwrap());
+        let response = Response::Change {
+            subscription_id: 7,
+            event: Box::new(ChangeEvent {
+                seq: 1,
+                kind: ChangeKind::Insert,
+                record: ChangeRecord::Node(node.clone()),
+                timestamp: Timestamp::now(),
+            }),
+        };
+
+        let bytes = bincode::serialize(&response).unwrap();
+        let deserialized: Response = bincode::deserialize(&bytes).unwrap();
+
+        match deserialized {
+            Response::Change { subscription_id, event } => {
+                assert_eq!(subscription_id, 7);
+                assert_eq!(event.record_id(), node.id.to_string());
+            }
+            _ => panic!("Wrong response type"),
+        }
+    }
+
+    #[test]
+    fn test_idempotent_requests() {
+        assert!(Request::Status.is_idempotent());
+        assert!(Request::Query { sql: "SELECT * FROM users".to_string(), limit: None }.is_idempotent());
+        assert!(!Request::Query { sql: "DELETE FROM users".to_string(), limit: None }.is_idempotent());
+        assert!(!Request::BeginTransaction.is_idempotent());
+        assert!(Request::Query { sql: "DELETE FROM users".to_string(), limit: None }.is_write());
+        assert!(!Request::Query { sql: "SELECT * FROM users".to_string(), limit: None }.is_write());
+        assert!(Request::Batch(vec![Request::Status, Request::DeleteNode { id: "x".to_string() }]).is_write());
+        assert!(ErrorCode::RateLimited.is_transient());
+        assert!(!ErrorCode::NodeNotFound.is_transient());
+    }
+
+    #[test]
+    fn test_hello() {
+        match Response::hello(PROTOCOL_VERSION + 1) {
+            Response::Welcome { protocol_version, format_version, .. } => {
+                assert_eq!(protocol_version, PROTOCOL_VERSION);
+                assert_eq!(format_version, crate::FORMAT_VERSION);
+            }
+            _ => panic!("Expected Welcome"),
+        }
+
+        let response = Response::hello(MIN_PROTOCOL_VERSION - 1);
+        assert!(matches!(response, Response::Error { code: ErrorCode::VersionMismatch, .. }));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let nodes: Vec<Node> = (0..100)
+            .map(|i| Node::new("item", Value::from_json(serde_json::json!({ "n": i })).unwrap()))
+            .collect();
+
+        let chunks = Response::Nodes(nodes.clone()).into_chunks(1024);
+        assert!(chunks.len() > 1);
+        assert!(matches!(chunks.last(), Some(Response::Chunk { more: false, .. })));
+
+        let mut merged: Option<Response> = None;
+        for chunk in chunks {
+            let Response::Chunk { response, .. } = chunk else { panic!("Expected chunk") };
+            match merged {
+                Some(ref mut whole) => assert!(whole.extend_from_chunk(*response)),
+                None => merged = Some(*response),
+            }
+        }
+        match merged {
+            Some(Response::Nodes(merged)) => {
+                let ids = |nodes: &[Node]| nodes.iter().map(|n| n.id.to_string()).collect::<Vec<_>>();
+                assert_eq!(ids(&merged), ids(&nodes));
+            }
+            _ => panic!("Expected nodes"),
+        }
+
+        // Small and unchunkable responses stay whole
+        assert!(matches!(Response::Nodes(nodes).into_chunks(0).as_slice(), [Response::Nodes(_)]));
+        assert!(matches!(Response::Ok.into_chunks(1).as_slice(), [Response::Ok]));
+    }
+
     #[test]
     fn test_error_response() {
         let response = Response::error(ErrorCode::NodeNotFound, "Node not found");
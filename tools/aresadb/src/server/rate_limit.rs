@@ -0,0 +1,211 @@
+//! Rate Limiting
+//!
+//! Token buckets bounding requests per second and bytes per second. Each
+//! TCP connection gets its own limiter from the connection pool; the
+//! request handler keeps one per credential (API key or user) so a client
+//! can't dodge its limit by opening more connections.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+
+/// Request and bandwidth limits (`None` means unlimited)
+///
+/// Buckets hold one second's worth of tokens, so short bursts up to the
+/// per-second rate are allowed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimit {
+    /// Maximum requests per second
+    pub requests_per_sec: Option<f64>,
+    /// Maximum request plus response bytes per second
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl RateLimit {
+    /// Whether no limit is configured
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_sec.is_none() && self.bytes_per_sec.is_none()
+    }
+}
+
+/// A token bucket refilled continuously at a fixed rate
+///
+/// Time comes from tokio's clock so tests can pause and advance it.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self { rate, tokens: rate, updated: Instant::now() }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+    }
+
+    /// Whether at least `amount` tokens are available (a bucket in debt
+    /// admits nothing)
+    fn has(&mut self, amount: f64) -> bool {
+        self.refill();
+        self.tokens >= amount.min(self.rate)
+    }
+
+    /// Take tokens, going into debt if needed
+    fn charge(&mut self, amount: f64) {
+        self.refill();
+        self.tokens -= amount;
+    }
+}
+
+/// Limiter for one connection or credential
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests: Option<Mutex<TokenBucket>>,
+    bytes: Option<Mutex<TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter with full buckets
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            requests: limit.requests_per_sec.map(|rate| Mutex::new(TokenBucket::new(rate))),
+            bytes: limit.bytes_per_sec.map(|rate| Mutex::new(TokenBucket::new(rate as f64))),
+        }
+    }
+
+    /// Admit a request of `bytes` bytes, charging for it if admitted
+    ///
+    /// A request larger than the whole byte budget is admitted once the
+    /// bucket is full and leaves it in debt.
+    pub fn try_acquire(&self, bytes: u64) -> bool {
+        self.try_acquire_many(1, bytes)
+    }
+
+    /// Admit `count` requests totalling `bytes` bytes, such as the parts of
+    /// a batch, charging for all of them if admitted
+    ///
+    /// As with bytes, more requests than a second's budget are admitted
+    /// once the bucket is full and leave it in debt.
+    pub fn try_acquire_many(&self, count: u64, bytes: u64) -> bool {
+        let mut requests = self.requests.as_ref().map(|b| b.lock());
+        let mut bandwidth = self.bytes.as_ref().map(|b| b.lock());
+
+        let admitted = requests.as_mut().is_none_or(|b| b.has(count as f64))
+            && bandwidth.as_mut().is_none_or(|b| b.has(bytes as f64));
+        if admitted {
+            if let Some(ref mut bucket) = requests {
+                bucket.charge(count as f64);
+            }
+            if let Some(ref mut bucket) = bandwidth {
+                bucket.charge(bytes as f64);
+            }
+        }
+        admitted
+    }
+
+    /// Charge bytes already sent (e.g. a response), possibly into debt
+    pub fn charge_bytes(&self, bytes: u64) {
+        if let Some(ref bucket) = self.bytes {
+            bucket.lock().charge(bytes as f64);
+        }
+    }
+}
+
+/// Limiters keyed by credential
+#[derive(Debug)]
+pub struct KeyedRateLimiter {
+    limit: RateLimit,
+    limiters: DashMap<String, RateLimiter>,
+}
+
+impl KeyedRateLimiter {
+    /// Create a keyed limiter applying the same limit to every key
+    pub fn new(limit: RateLimit) -> Self {
+        Self { limit, limiters: DashMap::new() }
+    }
+
+    /// Admit a request for a key
+    pub fn try_acquire(&self, key: &str, bytes: u64) -> bool {
+        self.try_acquire_many(key, 1, bytes)
+    }
+
+    /// Admit `count` requests for a key
+    pub fn try_acquire_many(&self, key: &str, count: u64, bytes: u64) -> bool {
+        if let Some(limiter) = self.limiters.get(key) {
+            return limiter.try_acquire_many(count, bytes);
+        }
+        self.limiters
+            .entry(key.to_string())
+            .or_insert_with(|| RateLimiter::new(self.limit))
+            .try_acquire_many(count, bytes)
+    }
+
+    /// Charge response bytes to a key
+    pub fn charge_bytes(&self, key: &str, bytes: u64) {
+        if let Some(limiter) = self.limiters.get(key) {
+            limiter.charge_bytes(bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_limit() {
+        let limiter = RateLimiter::new(RateLimit { requests_per_sec: Some(2.0), bytes_per_sec: None });
+
+        assert!(limiter.try_acquire(0));
+        assert!(limiter.try_acquire(0));
+        assert!(!limiter.try_acquire(0));
+
+        tokio::time::advance(std::time::Duration::from_millis(600)).await;
+        assert!(limiter.try_acquire(0));
+    }
+
+    #[test]
+    fn test_bandwidth_limit() {
+        let limiter = RateLimiter::new(RateLimit { requests_per_sec: None, bytes_per_sec: Some(1000) });
+
+        assert!(limiter.try_acquire(600));
+        assert!(!limiter.try_acquire(600));
+
+        // Responses can push the bucket into debt
+        let limiter = RateLimiter::new(RateLimit { requests_per_sec: None, bytes_per_sec: Some(1000) });
+        assert!(limiter.try_acquire(10));
+        limiter.charge_bytes(5000);
+        assert!(!limiter.try_acquire(1));
+    }
+
+    #[test]
+    fn test_acquire_many() {
+        let limiter = RateLimiter::new(RateLimit { requests_per_sec: Some(4.0), bytes_per_sec: None });
+
+        assert!(limiter.try_acquire_many(3, 0));
+        assert!(!limiter.try_acquire_many(2, 0));
+        assert!(limiter.try_acquire(0));
+        assert!(!limiter.try_acquire(0));
+
+        // More than a second's worth waits for a full bucket, then goes into debt
+        let limiter = RateLimiter::new(RateLimit { requests_per_sec: Some(4.0), bytes_per_sec: None });
+        assert!(limiter.try_acquire_many(10, 0));
+        assert!(!limiter.try_acquire(0));
+    }
+
+    #[test]
+    fn test_keyed_limits_are_independent() {
+        let limiter = KeyedRateLimiter::new(RateLimit { requests_per_sec: Some(1.0), bytes_per_sec: None });
+
+        assert!(limiter.try_acquire("key:a", 0));
+        assert!(!limiter.try_acquire("key:a", 0));
+        assert!(limiter.try_acquire("key:b", 0));
+    }
+}
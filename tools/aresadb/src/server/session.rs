@@ -0,0 +1,203 @@
+//! Client Sessions
+//!
+//! Every TCP connection is registered as a session so administrators can
+//! see who is connected and what they are running, and cancel a runaway
+//! request. Cancelling drops the in-flight request the same way a missed
+//! deadline does. Each write commits in one transaction, so a cancelled
+//! write is applied fully or not at all; the requests of a cancelled batch
+//! that finished before it stay applied.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Notify;
+
+use super::protocol::Request;
+use crate::storage::Timestamp;
+
+/// Snapshot of a session, as reported to administrators
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    /// Session ID, used to cancel its request
+    pub id: u64,
+    /// Client address, when known
+    pub client: Option<String>,
+    /// Database the session is using
+    pub database: String,
+    /// Authenticated user, if any
+    pub user: Option<String>,
+    /// When the client connected
+    pub connected_at: Timestamp,
+    /// Operation currently running, if any
+    pub operation: Option<String>,
+    /// SQL text of the running query, if it is one
+    pub query: Option<String>,
+    /// How long the current operation has been running
+    pub running_ms: Option<u64>,
+}
+
+/// The request a session is currently running
+struct Running {
+    operation: &'static str,
+    query: Option<String>,
+    started: Instant,
+    cancel: Arc<Notify>,
+}
+
+/// State of one connected client
+pub struct Session {
+    id: u64,
+    client: Option<SocketAddr>,
+    connected_at: Timestamp,
+    database: Mutex<String>,
+    user: Mutex<Option<String>>,
+    running: Mutex<Option<Running>>,
+}
+
+impl Session {
+    /// Session ID
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Record a database switch
+    pub fn set_database(&self, database: &str) {
+        *self.database.lock() = database.to_string();
+    }
+
+    /// Record the authenticated user
+    pub fn set_user(&self, user: Option<&str>) {
+        *self.user.lock() = user.map(str::to_string);
+    }
+
+    /// Mark a request as running, returning the signal that cancels it
+    pub fn begin(&self, request: &Request) -> Arc<Notify> {
+        let request = match request {
+            Request::WithDeadline { request, .. } => request,
+            request => request,
+        };
+        let query = match request {
+            Request::Query { sql, .. } => Some(sql.clone()),
+            _ => None,
+        };
+
+        let cancel = Arc::new(Notify::new());
+        *self.running.lock() = Some(Running {
+            operation: request.operation(),
+            query,
+            started: Instant::now(),
+            cancel: Arc::clone(&cancel),
+        });
+        cancel
+    }
+
+    /// Mark the running request as finished
+    pub fn finish(&self) {
+        *self.running.lock() = None;
+    }
+
+    /// Cancel the running request; `false` if nothing is running
+    pub fn cancel(&self) -> bool {
+        match *self.running.lock() {
+            Some(ref running) => {
+                // Stores a permit, so a request that hasn't started waiting
+                // yet still sees the cancellation
+                running.cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Snapshot for reporting
+    pub fn info(&self) -> SessionInfo {
+        let running = self.running.lock();
+        SessionInfo {
+            id: self.id,
+            client: self.client.map(|addr| addr.to_string()),
+            database: self.database.lock().clone(),
+            user: self.user.lock().clone(),
+            connected_at: self.connected_at,
+            operation: running.as_ref().map(|r| r.operation.to_string()),
+            query: running.as_ref().and_then(|r| r.query.clone()),
+            running_ms: running.as_ref().map(|r| r.started.elapsed().as_millis() as u64),
+        }
+    }
+}
+
+/// Sessions of every connected client
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: DashMap<u64, Arc<Session>>,
+    next_id: AtomicU64,
+}
+
+impl SessionRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new connection
+    pub fn open(&self, client: Option<SocketAddr>, database: &str) -> Arc<Session> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let session = Arc::new(Session {
+            id,
+            client,
+            connected_at: Timestamp::now(),
+            database: Mutex::new(database.to_string()),
+            user: Mutex::new(None),
+            running: Mutex::new(None),
+        });
+        self.sessions.insert(id, Arc::clone(&session));
+        session
+    }
+
+    /// Remove a closed connection
+    pub fn close(&self, id: u64) {
+        self.sessions.remove(&id);
+    }
+
+    /// Look up a session
+    pub fn get(&self, id: u64) -> Option<Arc<Session>> {
+        self.sessions.get(&id).map(|s| Arc::clone(&s))
+    }
+
+    /// Snapshots of all sessions, ordered by ID
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self.sessions.iter().map(|s| s.info()).collect();
+        sessions.sort_by_key(|s| s.id);
+        sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_lifecycle() {
+        let registry = SessionRegistry::new();
+        let session = registry.open(Some("127.0.0.1:5000".parse().unwrap()), "default");
+        session.set_user(Some("alice"));
+        assert!(!session.cancel());
+
+        let cancel = session.begin(&Request::Query { sql: "SELECT * FROM users".to_string(), limit: None });
+        let info = registry.list().remove(0);
+        assert_eq!(info.user.as_deref(), Some("alice"));
+        assert_eq!(info.operation.as_deref(), Some("query"));
+        assert_eq!(info.query.as_deref(), Some("SELECT * FROM users"));
+
+        assert!(registry.get(session.id()).unwrap().cancel());
+        cancel.notified().await;
+
+        session.finish();
+        assert!(registry.list()[0].operation.is_none());
+        registry.close(session.id());
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Session Settings
+//!
+//! Per-connection variables changed with `Request::Set` and read back with
+//! `Request::Show`. They last until the connection closes and are applied
+//! by the request handler to every request the connection sends.
+
+use anyhow::{Result, bail};
+use chrono::FixedOffset;
+
+use super::protocol::Request;
+
+/// Names of the settings, in the order `Request::Show` lists them
+pub const SETTING_NAMES: [&str; 5] = ["default_limit", "statement_timeout", "consistency", "timezone", "timing"];
+
+/// Which replica may answer reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Consistency {
+    /// Only the leader answers, so reads see every committed write
+    Strong,
+    /// Any replica answers from its own copy, which may lag the leader
+    #[default]
+    Eventual,
+}
+
+/// Settings of one connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSettings {
+    /// Row limit for queries and node listings that don't set one
+    pub default_limit: Option<usize>,
+    /// Deadline in milliseconds for requests that don't carry one
+    pub statement_timeout_ms: Option<u64>,
+    /// Which replica may answer reads
+    pub consistency: Consistency,
+    /// Zone clients should render timestamps in; they travel as UTC
+    /// milliseconds either way
+    pub timezone: FixedOffset,
+    /// Whether query results carry their execution statistics
+    pub timing: bool,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            default_limit: None,
+            statement_timeout_ms: None,
+            consistency: Consistency::default(),
+            timezone: FixedOffset::east_opt(0).expect("UTC is a valid offset"),
+            timing: false,
+        }
+    }
+}
+
+impl SessionSettings {
+    /// Change a setting; `DEFAULT` restores its default
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        let reset = value.eq_ignore_ascii_case("default");
+        let defaults = Self::default();
+
+        match name.to_ascii_lowercase().as_str() {
+            "default_limit" if reset => self.default_limit = defaults.default_limit,
+            "default_limit" => self.default_limit = parse_optional(name, value)?,
+            "statement_timeout" if reset => self.statement_timeout_ms = defaults.statement_timeout_ms,
+            "statement_timeout" => self.statement_timeout_ms = parse_optional(name, value)?,
+            "consistency" if reset => self.consistency = defaults.consistency,
+            "consistency" => {
+                self.consistency = match value.to_ascii_lowercase().as_str() {
+                    "strong" => Consistency::Strong,
+                    "eventual" => Consistency::Eventual,
+                    _ => bail!("Invalid consistency '{}': expected strong or eventual", value),
+                };
+            }
+            "timezone" if reset => self.timezone = defaults.timezone,
+            "timezone" if value.eq_ignore_ascii_case("utc") || value == "Z" => {
+                self.timezone = defaults.timezone;
+            }
+            "timezone" => {
+                self.timezone = value.parse().map_err(|_| {
+                    anyhow::anyhow!("Invalid timezone '{}': expected UTC or an offset like +02:00", value)
+                })?;
+            }
+            "timing" if reset => self.timing = defaults.timing,
+            "timing" => {
+                self.timing = match value.to_ascii_lowercase().as_str() {
+                    "on" | "true" => true,
+                    "off" | "false" => false,
+                    _ => bail!("Invalid timing '{}': expected on or off", value),
+                };
+            }
+            _ => bail!("Unknown setting: {}", name),
+        }
+        Ok(())
+    }
+
+    /// Current value of a setting
+    pub fn get(&self, name: &str) -> Result<String> {
+        let value = match name.to_ascii_lowercase().as_str() {
+            "default_limit" => self.default_limit.map_or("0".to_string(), |limit| limit.to_string()),
+            "statement_timeout" => self.statement_timeout_ms.map_or("0".to_string(), |ms| ms.to_string()),
+            "consistency" => match self.consistency {
+                Consistency::Strong => "strong".to_string(),
+                Consistency::Eventual => "eventual".to_string(),
+            },
+            "timezone" if self.timezone.local_minus_utc() == 0 => "UTC".to_string(),
+            "timezone" => self.timezone.to_string(),
+            "timing" => if self.timing { "on" } else { "off" }.to_string(),
+            _ => bail!("Unknown setting: {}", name),
+        };
+        Ok(value)
+    }
+
+    /// Every setting with its current value
+    pub fn show(&self) -> Vec<(String, String)> {
+        SETTING_NAMES
+            .iter()
+            .map(|name| (name.to_string(), self.get(name).expect("setting names are known")))
+            .collect()
+    }
+
+    /// Fill in the defaults a request leaves open
+    pub fn apply(&self, request: Request) -> Request {
+        match request {
+            Request::WithDeadline { timeout_ms, request } => Request::WithDeadline {
+                timeout_ms,
+                request: Box::new(self.apply_limit(*request)),
+            },
+            request => {
+                let request = self.apply_limit(request);
+                match self.statement_timeout_ms {
+                    Some(timeout_ms) if !request.is_connection_state() => Request::WithDeadline {
+                        timeout_ms,
+                        request: Box::new(request),
+                    },
+                    _ => request,
+                }
+            }
+        }
+    }
+
+    fn apply_limit(&self, request: Request) -> Request {
+        match request {
+            Request::Query { sql, limit: None } => Request::Query { sql, limit: self.default_limit },
+            Request::GetNodesByType { node_type, limit: None } => {
+                Request::GetNodesByType { node_type, limit: self.default_limit }
+            }
+            Request::Execute { statement_id, params, limit: None } => {
+                Request::Execute { statement_id, params, limit: self.default_limit }
+            }
+            Request::Batch(requests) => {
+                Request::Batch(requests.into_iter().map(|request| self.apply_limit(request)).collect())
+            }
+            request => request,
+        }
+    }
+}
+
+/// Parse a count where 0 means unset
+fn parse_optional<T: std::str::FromStr + Default + PartialEq>(name: &str, value: &str) -> Result<Option<T>> {
+    match value.parse::<T>() {
+        Ok(n) if n == T::default() => Ok(None),
+        Ok(n) => Ok(Some(n)),
+        Err(_) => bail!("Invalid {} '{}': expected a non-negative number", name, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let mut settings = SessionSettings::default();
+        settings.set("default_limit", "10").unwrap();
+        settings.set("STATEMENT_TIMEOUT", "500").unwrap();
+        settings.set("consistency", "Strong").unwrap();
+        settings.set("timezone", "+02:00").unwrap();
+        settings.set("timing", "ON").unwrap();
+
+        assert_eq!(settings.get("default_limit").unwrap(), "10");
+        assert_eq!(settings.get("timing").unwrap(), "on");
+        assert_eq!(settings.get("timezone").unwrap(), "+02:00");
+        assert_eq!(settings.consistency, Consistency::Strong);
+        assert_eq!(settings.show().len(), SETTING_NAMES.len());
+
+        assert!(settings.set("default_limit", "lots").is_err());
+        assert!(settings.set("timezone", "Mars/Olympus").is_err());
+        assert!(settings.set("colour", "blue").is_err());
+
+        settings.set("timezone", "DEFAULT").unwrap();
+        settings.set("default_limit", "0").unwrap();
+        assert_eq!(settings.get("timezone").unwrap(), "UTC");
+        assert_eq!(settings.default_limit, None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut settings = SessionSettings::default();
+        settings.set("default_limit", "5").unwrap();
+        settings.set("statement_timeout", "100").unwrap();
+
+        let request = settings.apply(Request::Query { sql: "SELECT * FROM users".to_string(), limit: None });
+        match request {
+            Request::WithDeadline { timeout_ms: 100, request } => {
+                assert!(matches!(*request, Request::Query { limit: Some(5), .. }));
+            }
+            _ => panic!("Expected a deadline"),
+        }
+
+        // Explicit limits and deadlines win
+        let request = settings.apply(Request::WithDeadline {
+            timeout_ms: 9,
+            request: Box::new(Request::GetNodesByType { node_type: "user".to_string(), limit: Some(2) }),
+        });
+        match request {
+            Request::WithDeadline { timeout_ms: 9, request } => {
+                assert!(matches!(*request, Request::GetNodesByType { limit: Some(2), .. }));
+            }
+            _ => panic!("Expected the original deadline"),
+        }
+    }
+}
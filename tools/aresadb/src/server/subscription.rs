@@ -0,0 +1,223 @@
+//! Change Subscriptions
+//!
+//! Per-connection filters over the database change feed. A subscription
+//! selects events by node (or edge) type and, optionally, a SQL-style
+//! predicate evaluated against the changed node.
+
+use anyhow::{Result, bail};
+use regex::Regex;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::query::{Condition, QueryParser};
+use crate::storage::{ChangeEvent, ChangeRecord};
+
+/// A single subscription filter
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    id: u64,
+    node_type: Option<String>,
+    conditions: Vec<Condition>,
+}
+
+impl Subscription {
+    /// Create a subscription, parsing the predicate as a `WHERE` clause
+    ///
+    /// Conjunctions (`AND`) of simple comparisons, `LIKE`, `IN` and
+    /// `IS [NOT] NULL` are supported.
+    pub fn new(id: u64, node_type: Option<String>, predicate: Option<&str>) -> Result<Self> {
+        let conditions = match predicate.map(str::trim).filter(|p| !p.is_empty()) {
+            Some(predicate) => {
+                let sql = format!("SELECT * FROM changes WHERE {}", predicate);
+                let conditions = QueryParser::new().parse(&sql)?.conditions;
+                if conditions.is_empty() {
+                    bail!("Unsupported subscription predicate: {}", predicate);
+                }
+                conditions
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Self { id, node_type, conditions })
+    }
+
+    /// Subscription ID
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Check whether an event should be delivered to this subscription
+    ///
+    /// Edge events only match subscriptions without a predicate.
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        if let Some(ref node_type) = self.node_type {
+            if event.type_name() != node_type {
+                return false;
+            }
+        }
+
+        match &event.record {
+            ChangeRecord::Node(node) => self.conditions.iter().all(|c| c.matches_node(node)),
+            ChangeRecord::Edge(_) => self.conditions.is_empty(),
+        }
+    }
+}
+
+/// Split a watch target into the node type and predicate to subscribe with
+///
+/// The target is a node type, `*` for everything, or a query of the form
+/// `SELECT ... FROM <type> [WHERE <predicate>]`; ordering and limits are
+/// ignored.
+pub fn parse_target(target: &str) -> Result<(Option<String>, Option<String>)> {
+    let target = target.trim();
+    if !target.to_lowercase().starts_with("select") {
+        let node_type = Some(target.to_string()).filter(|t| !t.is_empty() && t != "*");
+        return Ok((node_type, None));
+    }
+
+    let parsed = QueryParser::new().parse(target)?;
+    let predicate = Regex::new(r"(?is)\swhere\s+(.*?)(\s+order\s+by\s.*|\s+limit\s.*)?$")?
+        .captures(target)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().trim().to_string());
+    // Check the predicate here rather than on the server
+    Subscription::new(0, None, predicate.as_deref())?;
+    Ok((Some(parsed.target), predicate))
+}
+
+/// The set of subscriptions held by one connection
+///
+/// The underlying change feed receiver is only created once the first
+/// subscription is added, so idle connections never buffer events.
+#[derive(Default)]
+pub struct SubscriptionSet {
+    receiver: Option<broadcast::Receiver<ChangeEvent>>,
+    subscriptions: Vec<Subscription>,
+}
+
+impl SubscriptionSet {
+    /// Add a subscription, attaching to the feed if needed
+    pub fn add(
+        &mut self,
+        subscription: Subscription,
+        subscribe: impl FnOnce() -> Option<broadcast::Receiver<ChangeEvent>>,
+    ) {
+        if self.receiver.is_none() {
+            self.receiver = subscribe();
+        }
+        self.subscriptions.push(subscription);
+    }
+
+    /// Remove a subscription; returns false if it did not exist
+    pub fn remove(&mut self, id: u64) -> bool {
+        let before = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id() != id);
+        if self.subscriptions.is_empty() {
+            self.receiver = None;
+        }
+        self.subscriptions.len() != before
+    }
+
+    /// Whether there is anything to wait for
+    pub fn is_active(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Wait for the next event and return it with the matching subscription IDs
+    ///
+    /// Events matching no subscription are skipped. Returns `None` once the
+    /// feed has closed. Never resolves while the set is inactive.
+    pub async fn next(&mut self) -> Option<(Vec<u64>, ChangeEvent)> {
+        loop {
+            let Some(receiver) = self.receiver.as_mut() else {
+                return std::future::pending().await;
+            };
+
+            match receiver.recv().await {
+                Ok(event) => {
+                    let ids: Vec<u64> = self.subscriptions.iter()
+                        .filter(|s| s.matches(&event))
+                        .map(|s| s.id())
+                        .collect();
+                    if !ids.is_empty() {
+                        return Some((ids, event));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Subscriber lagged, {} change events dropped", skipped);
+                }
+                Err(RecvError::Closed) => {
+                    self.receiver = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ChangeFeed, ChangeKind, Node, Value};
+
+    fn user(age: i64) -> Node {
+        Node::new("user", Value::from_json(serde_json::json!({"age": age})).unwrap())
+    }
+
+    #[test]
+    fn test_subscription_matching() {
+        let feed = ChangeFeed::new();
+        let mut rx = feed.subscribe();
+
+        let sub = Subscription::new(1, Some("user".to_string()), Some("age >= 18")).unwrap();
+
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(user(30)));
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(user(10)));
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(Node::new("post", Value::Null)));
+
+        assert!(sub.matches(&rx.try_recv().unwrap()));
+        assert!(!sub.matches(&rx.try_recv().unwrap()));
+        assert!(!sub.matches(&rx.try_recv().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_predicate() {
+        assert!(Subscription::new(1, None, Some("age >>> 3")).is_err());
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("user").unwrap(), (Some("user".to_string()), None));
+        assert_eq!(parse_target("*").unwrap(), (None, None));
+        assert_eq!(
+            parse_target("select * from user where age >= 18 and name like 'A%' order by age limit 5").unwrap(),
+            (Some("user".to_string()), Some("age >= 18 and name like 'A%'".to_string()))
+        );
+        assert_eq!(parse_target("SELECT name FROM post").unwrap(), (Some("post".to_string()), None));
+        assert!(parse_target("SELECT * FROM user WHERE age >>> 3").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscription_set() {
+        let feed = ChangeFeed::new();
+        let mut set = SubscriptionSet::default();
+        assert!(!set.is_active());
+
+        set.add(Subscription::new(1, Some("user".to_string()), None).unwrap(), || Some(feed.subscribe()));
+        set.add(Subscription::new(2, None, Some("age < 18")).unwrap(), || Some(feed.subscribe()));
+        assert!(set.is_active());
+
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(Node::new("post", Value::Null)));
+        feed.publish(ChangeKind::Update, ChangeRecord::Node(user(12)));
+
+        let (ids, event) = set.next().await.unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(event.kind, ChangeKind::Update);
+
+        assert!(set.remove(1));
+        assert!(set.remove(2));
+        assert!(!set.remove(2));
+        assert!(!set.is_active());
+    }
+}
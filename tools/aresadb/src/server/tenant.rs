@@ -0,0 +1,294 @@
+//! Tenants
+//!
+//! A single server can host several named databases. Every connection
+//! starts on the default database and may switch with `Request::UseDatabase`
+//! once authenticated. HTTP and gRPC requests pick a database per request
+//! with the `x-aresadb-database` header. Each tenant has its own request
+//! handler and optional resource limits. A binary protocol connection holds
+//! a slot on its database while it uses it; an HTTP or gRPC request holds
+//! one for as long as it runs.
+
+use anyhow::{Result, bail};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::cluster::ClusterView;
+use super::handler::RequestHandler;
+use super::pool::ConnectionPool;
+use super::protocol::{ErrorCode, Response};
+use super::rate_limit::KeyedRateLimiter;
+use crate::auth::{Principal, UserStore};
+use crate::query::QueryLog;
+
+/// Name of the database a connection uses until it selects another
+pub const DEFAULT_DATABASE: &str = "default";
+
+/// HTTP header and gRPC metadata key selecting the database of a request
+pub const DATABASE_HEADER: &str = "x-aresadb-database";
+
+/// Per-tenant resource limits (`None` means unlimited)
+#[derive(Debug, Clone, Default)]
+pub struct TenantLimits {
+    /// Maximum concurrent connections using the database
+    pub max_connections: Option<usize>,
+    /// Maximum rows returned by a single query or node listing
+    pub max_result_rows: Option<usize>,
+}
+
+/// A hosted database
+pub struct Tenant {
+    name: String,
+    handler: Arc<RequestHandler>,
+    /// Connection slots; `None` when connections are unlimited
+    pool: Option<ConnectionPool>,
+    /// Connections currently using the database
+    active: AtomicUsize,
+}
+
+impl Tenant {
+    fn new(name: &str, handler: RequestHandler, limits: TenantLimits) -> Self {
+        let handler = handler.with_max_result_rows(limits.max_result_rows);
+        Self {
+            name: name.to_string(),
+            handler: Arc::new(handler),
+            pool: limits.max_connections.map(ConnectionPool::new),
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Database name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Request handler for this database
+    pub fn handler(&self) -> &Arc<RequestHandler> {
+        &self.handler
+    }
+
+    /// Take a connection slot for a client, released when the permit is
+    /// dropped, or the error to answer with at the limit
+    pub fn admit(self: &Arc<Self>) -> Result<TenantPermit, Response> {
+        if !self.try_acquire() {
+            return Err(Response::error(
+                ErrorCode::ServerOverloaded,
+                format!("Connection limit reached for database {}", self.name),
+            ));
+        }
+        Ok(TenantPermit { tenant: Arc::clone(self) })
+    }
+
+    /// Try to take a connection slot
+    pub fn try_acquire(&self) -> bool {
+        let acquired = self.pool.as_ref().is_none_or(|pool| pool.try_acquire());
+        if acquired {
+            self.active.fetch_add(1, Ordering::SeqCst);
+        }
+        acquired
+    }
+
+    /// Release a slot taken with `try_acquire`
+    pub fn release(&self) {
+        if let Some(ref pool) = self.pool {
+            pool.release();
+        }
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Connections currently using the database
+    pub fn connection_count(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+}
+
+/// A connection slot on a tenant, released on drop
+pub struct TenantPermit {
+    tenant: Arc<Tenant>,
+}
+
+impl TenantPermit {
+    /// The database the slot is on
+    pub fn tenant(&self) -> &Arc<Tenant> {
+        &self.tenant
+    }
+}
+
+impl Drop for TenantPermit {
+    fn drop(&mut self) {
+        self.tenant.release();
+    }
+}
+
+/// Databases hosted by a server
+pub struct TenantRegistry {
+    tenants: RwLock<BTreeMap<String, Arc<Tenant>>>,
+    /// Shared user store, applied to every tenant
+    users: RwLock<Option<Arc<UserStore>>>,
+    /// Shared query log, applied to every tenant
+    query_log: RwLock<Option<Arc<QueryLog>>>,
+    /// Shared per-credential rate limits, applied to every tenant
+    rate_limiter: RwLock<Option<Arc<KeyedRateLimiter>>>,
+    /// Shared replica leadership, applied to every tenant
+    cluster: RwLock<Option<Arc<ClusterView>>>,
+}
+
+impl TenantRegistry {
+    /// Create a registry with the default database
+    pub fn new(default: RequestHandler) -> Self {
+        let tenant = Tenant::new(DEFAULT_DATABASE, default, TenantLimits::default());
+
+        let mut tenants = BTreeMap::new();
+        tenants.insert(DEFAULT_DATABASE.to_string(), Arc::new(tenant));
+
+        Self {
+            tenants: RwLock::new(tenants),
+            users: RwLock::new(None),
+            query_log: RwLock::new(None),
+            rate_limiter: RwLock::new(None),
+            cluster: RwLock::new(None),
+        }
+    }
+
+    /// Host another database under a name
+    pub fn add(&self, name: &str, handler: RequestHandler, limits: TenantLimits) -> Result<()> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            bail!("Invalid database name: '{}'", name);
+        }
+
+        let mut tenants = self.tenants.write();
+        if tenants.contains_key(name) {
+            bail!("Database already hosted: {}", name);
+        }
+
+        handler.set_user_store(self.users.read().clone());
+        handler.set_query_log(self.query_log.read().clone(), name);
+        handler.set_rate_limiter(self.rate_limiter.read().clone());
+        handler.set_cluster(self.cluster.read().clone());
+        tenants.insert(name.to_string(), Arc::new(Tenant::new(name, handler, limits)));
+        Ok(())
+    }
+
+    /// Look up a database by name
+    pub fn get(&self, name: &str) -> Option<Arc<Tenant>> {
+        self.tenants.read().get(name).cloned()
+    }
+
+    /// The database named by a request header, or the default without one
+    pub fn resolve(&self, name: Option<&str>) -> Result<Arc<Tenant>, Response> {
+        let name = name.unwrap_or(DEFAULT_DATABASE);
+        self.get(name).ok_or_else(|| {
+            Response::error(ErrorCode::DatabaseNotFound, format!("Database not found: {}", name))
+        })
+    }
+
+    /// Admit an HTTP or gRPC request to the database it names
+    ///
+    /// The bearer key is checked and rate limited by that database's
+    /// handler, then the request takes a connection slot on it. Unknown
+    /// names are only reported once the key checks out, so unauthenticated
+    /// callers don't learn which databases exist.
+    pub fn admit_request(
+        &self,
+        database: Option<&str>,
+        authorization: Option<&str>,
+        bytes: u64,
+    ) -> Result<(Principal, TenantPermit), Response> {
+        let tenant = self.resolve(database);
+        let checker = match &tenant {
+            Ok(tenant) => Arc::clone(tenant),
+            Err(_) => self.default_tenant(),
+        };
+        let principal = checker.handler.authenticate_bearer(authorization)?;
+        checker.handler.check_rate_limit(&principal, 1, bytes)?;
+        Ok((principal, tenant?.admit()?))
+    }
+
+    /// The default database
+    pub fn default_tenant(&self) -> Arc<Tenant> {
+        self.get(DEFAULT_DATABASE).expect("default tenant is always registered")
+    }
+
+    /// Names of all hosted databases
+    pub fn names(&self) -> Vec<String> {
+        self.tenants.read().keys().cloned().collect()
+    }
+
+    /// Attach a user store to every current and future tenant
+    pub fn set_user_store(&self, users: Option<Arc<UserStore>>) {
+        for tenant in self.tenants.read().values() {
+            tenant.handler.set_user_store(users.clone());
+        }
+        *self.users.write() = users;
+    }
+
+    /// Attach a query log to every current and future tenant
+    pub fn set_query_log(&self, log: Option<Arc<QueryLog>>) {
+        for tenant in self.tenants.read().values() {
+            tenant.handler.set_query_log(log.clone(), &tenant.name);
+        }
+        *self.query_log.write() = log;
+    }
+
+    /// The shared query log, if one is attached
+    pub fn query_log(&self) -> Option<Arc<QueryLog>> {
+        self.query_log.read().clone()
+    }
+
+    /// Share per-credential rate limits across every current and future tenant
+    pub fn set_rate_limiter(&self, limiter: Option<Arc<KeyedRateLimiter>>) {
+        for tenant in self.tenants.read().values() {
+            tenant.handler.set_rate_limiter(limiter.clone());
+        }
+        *self.rate_limiter.write() = limiter;
+    }
+
+    /// Share replica leadership across every current and future tenant
+    pub fn set_cluster(&self, cluster: Option<Arc<ClusterView>>) {
+        for tenant in self.tenants.read().values() {
+            tenant.handler.set_cluster(cluster.clone());
+        }
+        *self.cluster.write() = cluster;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Database;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_registry() {
+        let temp = TempDir::new().unwrap();
+        let default = Database::create(temp.path().join("a"), "a").await.unwrap();
+        let other = Database::create(temp.path().join("b"), "b").await.unwrap();
+
+        let registry = TenantRegistry::new(RequestHandler::new(default));
+        let limits = TenantLimits { max_connections: Some(1), ..Default::default() };
+        registry.add("analytics", RequestHandler::new(other), limits).unwrap();
+        assert_eq!(registry.names(), vec!["analytics".to_string(), DEFAULT_DATABASE.to_string()]);
+
+        let tenant = registry.get("analytics").unwrap();
+        assert!(tenant.try_acquire());
+        assert!(!tenant.try_acquire());
+        tenant.release();
+        assert!(tenant.try_acquire());
+        assert_eq!(tenant.connection_count(), 1);
+
+        // The default tenant is unlimited
+        assert!(registry.default_tenant().try_acquire());
+        assert!(registry.default_tenant().try_acquire());
+        assert!(registry.get("missing").is_none());
+
+        // Permits hold a slot until dropped
+        tenant.release();
+        let permit = tenant.admit().unwrap();
+        assert!(tenant.admit().is_err());
+        drop(permit);
+        assert_eq!(tenant.connection_count(), 0);
+        assert!(registry.admit_request(Some("analytics"), None, 0).is_ok());
+        assert!(registry.admit_request(Some("missing"), None, 0).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+//! Snapshot backup and restore
+//!
+//! A backup is a directory holding a `manifest.json`, the database config
+//! and gzipped JSON Lines of nodes and edges, read in one transaction so
+//! they are consistent. The manifest records the size and SHA-256 of every
+//! file, so a backup can be verified before it is trusted.
+//!
+//! An incremental backup holds only the nodes updated and edges created
+//! since its base backup, plus the IDs of everything still alive so
+//! deletions can be replayed. Its base is named by directory and must sit
+//! next to it; restoring applies the chain from the full backup forward.
+
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::progress::{Progress, ProgressUnit};
+use super::{Database, Edge, EdgeId, LocalStorage, Node, NodeId, Timestamp};
+
+/// Version of the backup layout
+pub const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const CONFIG_FILE: &str = "config.toml";
+const NODES_FILE: &str = "nodes.jsonl.gz";
+const EDGES_FILE: &str = "edges.jsonl.gz";
+const LIVE_FILE: &str = "live.json.gz";
+
+/// Records per transaction on restore, and between progress reports
+const RESTORE_BATCH: usize = 1000;
+
+/// Whether a backup stands alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// Every node and edge
+    Full,
+    /// Changes since a base backup
+    Incremental,
+}
+
+/// A file in a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// File name within the backup directory
+    pub name: String,
+    /// Size in bytes
+    pub bytes: u64,
+    /// SHA-256 of the contents, as hex
+    pub sha256: String,
+    /// Records held, for node and edge files
+    #[serde(default)]
+    pub records: u64,
+}
+
+/// Description of a backup, stored as `manifest.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    /// Layout version
+    pub version: u32,
+    /// Full or incremental
+    pub kind: BackupKind,
+    /// When the snapshot was taken
+    pub created_at: Timestamp,
+    /// Directory name of the base backup, for incremental backups
+    pub base: Option<String>,
+    /// Name of the database backed up
+    pub database: String,
+    /// Nodes in the database at the time
+    pub node_count: u64,
+    /// Edges in the database at the time
+    pub edge_count: u64,
+    /// Files making up the backup
+    pub files: Vec<BackupFile>,
+}
+
+impl BackupManifest {
+    /// Read the manifest of a backup directory
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let path = dir.as_ref().join(MANIFEST_FILE);
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}. Is this an aresadb backup?", path.display()))?;
+        let manifest: Self = serde_json::from_str(&data)
+            .with_context(|| format!("Invalid backup manifest {}", path.display()))?;
+        anyhow::ensure!(
+            manifest.version <= BACKUP_VERSION,
+            "Backup {} has version {}; this aresadb reads up to {}",
+            dir.as_ref().display(), manifest.version, BACKUP_VERSION
+        );
+        Ok(manifest)
+    }
+
+    /// Name of the backup kind
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            BackupKind::Full => "full",
+            BackupKind::Incremental => "incremental",
+        }
+    }
+
+    fn file(&self, name: &str) -> Option<&BackupFile> {
+        self.files.iter().find(|f| f.name == name)
+    }
+}
+
+/// IDs alive when an incremental backup was taken
+#[derive(Default, Serialize, Deserialize)]
+struct LiveIds {
+    nodes: Vec<NodeId>,
+    edges: Vec<EdgeId>,
+}
+
+fn sha256_file(path: &Path) -> Result<(u64, String)> {
+    let mut file = File::open(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let bytes = std::io::copy(&mut file, &mut hasher)?;
+    let digest = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok((bytes, digest))
+}
+
+fn write_records<T: Serialize>(
+    dir: &Path,
+    name: &str,
+    records: &[T],
+    done: &mut u64,
+    total: u64,
+    on_progress: &mut impl FnMut(Progress),
+) -> Result<BackupFile> {
+    let path = dir.join(name);
+    let file = File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = GzEncoder::new(BufWriter::new(file), flate2::Compression::default());
+    for (i, record) in records.iter().enumerate() {
+        serde_json::to_writer(&mut out, record)?;
+        out.write_all(b"\n")?;
+        *done += 1;
+        if (i + 1) % RESTORE_BATCH == 0 {
+            on_progress(Progress::new("backup", ProgressUnit::Records, *done, Some(total)));
+        }
+    }
+    out.finish()?.flush()?;
+
+    let (bytes, sha256) = sha256_file(&path)?;
+    Ok(BackupFile { name: name.to_string(), bytes, sha256, records: records.len() as u64 })
+}
+
+fn read_records<T: serde::de::DeserializeOwned>(dir: &Path, name: &str) -> Result<Vec<T>> {
+    let path = dir.join(name);
+    let file = File::open(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut records = Vec::new();
+    for (n, line) in BufReader::new(GzDecoder::new(file)).lines().enumerate() {
+        let line = line.with_context(|| format!("{} is corrupt", path.display()))?;
+        records.push(serde_json::from_str(&line)
+            .with_context(|| format!("{} record {}", path.display(), n + 1))?);
+    }
+    Ok(records)
+}
+
+/// Back up a database into a new directory
+///
+/// With a `base` backup, only changes since it are written. The base must
+/// be a verified backup of the same database in the same parent directory
+/// as `dest`.
+pub async fn create_backup(
+    db: &Database,
+    dest: impl AsRef<Path>,
+    base: Option<&Path>,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<BackupManifest> {
+    let dest = dest.as_ref();
+    if dest.exists() && std::fs::read_dir(dest)?.next().is_some() {
+        anyhow::bail!("Backup directory {} is not empty", dest.display());
+    }
+
+    let base = match base {
+        Some(base) => {
+            let chain = verify_backup(base)?;
+            let manifest = chain.last().map(|(_, m)| m.clone()).context("Empty backup chain")?;
+            anyhow::ensure!(
+                manifest.database == db.name(),
+                "Base backup is of database {}, not {}", manifest.database, db.name()
+            );
+            let parent = |p: &Path| p.canonicalize().ok().and_then(|p| p.parent().map(Path::to_path_buf));
+            std::fs::create_dir_all(dest)?;
+            anyhow::ensure!(
+                parent(base).is_some() && parent(base) == parent(dest),
+                "Base backup {} must be in the same directory as {}", base.display(), dest.display()
+            );
+            let name = base.canonicalize()?.file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .context("Base backup has no directory name")?;
+            Some((name, manifest.created_at))
+        }
+        None => None,
+    };
+    std::fs::create_dir_all(dest)
+        .with_context(|| format!("Failed to create {}", dest.display()))?;
+
+    // Taken before the read, so a change racing it lands in the next backup too
+    let created_at = Timestamp::now();
+    let (nodes, edges) = db.local().snapshot().await?;
+    let (node_count, edge_count) = (nodes.len() as u64, edges.len() as u64);
+
+    let mut files = Vec::new();
+    std::fs::copy(db.path().join(".aresadb/config.toml"), dest.join(CONFIG_FILE))
+        .context("Failed to copy database config")?;
+    let (bytes, sha256) = sha256_file(&dest.join(CONFIG_FILE))?;
+    files.push(BackupFile { name: CONFIG_FILE.to_string(), bytes, sha256, records: 0 });
+
+    let (kind, changed_nodes, changed_edges) = match &base {
+        Some((_, since)) => {
+            let live = LiveIds {
+                nodes: nodes.iter().map(|n| n.id.clone()).collect(),
+                edges: edges.iter().map(|e| e.id.clone()).collect(),
+            };
+            let path = dest.join(LIVE_FILE);
+            let mut out = GzEncoder::new(BufWriter::new(File::create(&path)?), flate2::Compression::default());
+            serde_json::to_writer(&mut out, &live)?;
+            out.finish()?.flush()?;
+            let (bytes, sha256) = sha256_file(&path)?;
+            files.push(BackupFile { name: LIVE_FILE.to_string(), bytes, sha256, records: 0 });
+
+            let nodes: Vec<Node> = nodes.into_iter().filter(|n| n.updated_at >= *since).collect();
+            let edges: Vec<Edge> = edges.into_iter().filter(|e| e.created_at >= *since).collect();
+            (BackupKind::Incremental, nodes, edges)
+        }
+        None => (BackupKind::Full, nodes, edges),
+    };
+
+    let total = (changed_nodes.len() + changed_edges.len()) as u64;
+    let mut done = 0;
+    files.push(write_records(dest, NODES_FILE, &changed_nodes, &mut done, total, &mut on_progress)?);
+    files.push(write_records(dest, EDGES_FILE, &changed_edges, &mut done, total, &mut on_progress)?);
+    on_progress(Progress::new("backup", ProgressUnit::Records, done, Some(total)));
+
+    let manifest = BackupManifest {
+        version: BACKUP_VERSION,
+        kind,
+        created_at,
+        base: base.map(|(name, _)| name),
+        database: db.name(),
+        node_count,
+        edge_count,
+        files,
+    };
+    std::fs::write(dest.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+/// Check a backup and the chain of backups it builds on
+///
+/// Every file must match its recorded size and checksum, and every record
+/// must decode. Returns each backup directory with its manifest, from the
+/// full backup to `dir`.
+pub fn verify_backup(dir: impl AsRef<Path>) -> Result<Vec<(PathBuf, BackupManifest)>> {
+    let mut chain = Vec::new();
+    let mut next = Some(dir.as_ref().to_path_buf());
+    while let Some(dir) = next.take() {
+        anyhow::ensure!(
+            chain.iter().all(|(seen, _): &(PathBuf, _)| *seen != dir),
+            "Backup chain loops at {}", dir.display()
+        );
+        let manifest = BackupManifest::load(&dir)?;
+        for file in &manifest.files {
+            let (bytes, sha256) = sha256_file(&dir.join(&file.name))?;
+            anyhow::ensure!(
+                bytes == file.bytes && sha256 == file.sha256,
+                "{} in backup {} does not match its checksum", file.name, dir.display()
+            );
+        }
+        for (name, expected) in [NODES_FILE, EDGES_FILE].iter().map(|n| (*n, manifest.file(n))) {
+            let expected = expected.with_context(|| format!("Backup {} has no {}", dir.display(), name))?;
+            let records = if name == NODES_FILE {
+                read_records::<Node>(&dir, name)?.len()
+            } else {
+                read_records::<Edge>(&dir, name)?.len()
+            };
+            anyhow::ensure!(
+                records as u64 == expected.records,
+                "{} in backup {} holds {} records, expected {}", name, dir.display(), records, expected.records
+            );
+        }
+
+        match (manifest.kind, &manifest.base) {
+            (BackupKind::Full, _) => {}
+            (BackupKind::Incremental, Some(base)) => {
+                anyhow::ensure!(manifest.file(LIVE_FILE).is_some(), "Backup {} has no {}", dir.display(), LIVE_FILE);
+                let parent = dir.parent().unwrap_or(Path::new("."));
+                next = Some(parent.join(base));
+            }
+            (BackupKind::Incremental, None) => {
+                anyhow::bail!("Incremental backup {} names no base", dir.display())
+            }
+        }
+        chain.push((dir, manifest));
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+/// Restore a backup into a new database at `target`
+///
+/// The backup chain is verified first; the restored database is then
+/// checked against the node and edge counts the backup recorded.
+pub async fn restore_backup(
+    dir: impl AsRef<Path>,
+    target: impl AsRef<Path>,
+    mut on_progress: impl FnMut(Progress),
+) -> Result<Database> {
+    let target = target.as_ref();
+    anyhow::ensure!(
+        !target.join(".aresadb").exists(),
+        "A database already exists at {}", target.display()
+    );
+    let chain = verify_backup(dir)?;
+    let (last_dir, last) = chain.last().context("Empty backup chain")?;
+
+    // Replay the chain in memory, then write the final state once
+    let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+    let mut edges: HashMap<EdgeId, Edge> = HashMap::new();
+    for (dir, manifest) in &chain {
+        nodes.extend(read_records::<Node>(dir, NODES_FILE)?.into_iter().map(|n| (n.id.clone(), n)));
+        edges.extend(read_records::<Edge>(dir, EDGES_FILE)?.into_iter().map(|e| (e.id.clone(), e)));
+        if manifest.kind == BackupKind::Incremental {
+            let file = File::open(dir.join(LIVE_FILE))?;
+            let live: LiveIds = serde_json::from_reader(BufReader::new(GzDecoder::new(file)))
+                .with_context(|| format!("{} in backup {} is corrupt", LIVE_FILE, dir.display()))?;
+            let live_nodes: HashSet<NodeId> = live.nodes.into_iter().collect();
+            let live_edges: HashSet<EdgeId> = live.edges.into_iter().collect();
+            nodes.retain(|id, _| live_nodes.contains(id));
+            edges.retain(|id, _| live_edges.contains(id));
+        }
+    }
+
+    std::fs::create_dir_all(target.join(".aresadb"))
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+    std::fs::copy(last_dir.join(CONFIG_FILE), target.join(".aresadb/config.toml"))
+        .context("Failed to restore database config")?;
+
+    {
+        let local = LocalStorage::create(target).await?;
+        let total = (nodes.len() + edges.len()) as u64;
+        let mut done = 0;
+        let nodes: Vec<Node> = nodes.into_values().collect();
+        for batch in nodes.chunks(RESTORE_BATCH) {
+            let mut txn = local.begin_transaction()?;
+            for node in batch {
+                txn.insert_node(node.clone());
+            }
+            txn.commit()?;
+            done += batch.len() as u64;
+            on_progress(Progress::new("restore", ProgressUnit::Records, done, Some(total)));
+        }
+        let edges: Vec<Edge> = edges.into_values().collect();
+        for batch in edges.chunks(RESTORE_BATCH) {
+            let mut txn = local.begin_transaction()?;
+            for edge in batch {
+                txn.insert_edge(edge.clone());
+            }
+            txn.commit()?;
+            done += batch.len() as u64;
+            on_progress(Progress::new("restore", ProgressUnit::Records, done, Some(total)));
+        }
+        if total == 0 {
+            on_progress(Progress::new("restore", ProgressUnit::Records, 0, Some(0)));
+        }
+    }
+
+    let db = Database::open(target).await?;
+    let stats = db.local().stats().await?;
+    anyhow::ensure!(
+        stats.node_count == last.node_count && stats.edge_count == last.edge_count,
+        "Restored {} nodes and {} edges, but the backup recorded {} and {}",
+        stats.node_count, stats.edge_count, last.node_count, last.edge_count
+    );
+    Ok(db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress;
+
+    #[tokio::test]
+    async fn test_full_backup_restore() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path().join("db"), "test").await.unwrap();
+        let alice = db.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        let bob = db.insert_node("user", serde_json::json!({"name": "Bob"})).await.unwrap();
+        db.insert_with_embedding("chunk", serde_json::json!({"content": "hi"}), "embedding", vec![1.0, 0.0]).await.unwrap();
+        db.create_edge(&alice.id.to_string(), &bob.id.to_string(), "follows", None).await.unwrap();
+
+        let backup = temp.path().join("backups/full");
+        let (mut report, mut events) = progress::channel();
+        let manifest = create_backup(&db, &backup, None, &mut report).await.unwrap();
+        assert_eq!((manifest.kind, manifest.node_count, manifest.edge_count), (BackupKind::Full, 3, 1));
+        assert!(events.try_recv().unwrap().is_complete());
+        assert_eq!(verify_backup(&backup).unwrap().len(), 1);
+
+        let restored = restore_backup(&backup, temp.path().join("restored"), progress::ignore).await.unwrap();
+        assert_eq!(restored.name(), "test");
+        let node = restored.get_node(&alice.id.to_string()).await.unwrap().unwrap();
+        assert_eq!(node.properties.get("name").and_then(|v| v.as_str()), Some("Alice"));
+        let chunk = &restored.get_all_by_type("chunk", None).await.unwrap()[0];
+        assert!(chunk.properties.get("embedding").unwrap().as_vector().is_some());
+        assert!(restore_backup(&backup, temp.path().join("restored"), progress::ignore).await.is_err());
+
+        // A flipped byte fails verification
+        let nodes = backup.join(NODES_FILE);
+        let mut data = std::fs::read(&nodes).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        std::fs::write(&nodes, data).unwrap();
+        assert!(verify_backup(&backup).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_backup_restore() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path().join("db"), "test").await.unwrap();
+        let kept = db.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        let removed = db.insert_node("user", serde_json::json!({"name": "Bob"})).await.unwrap();
+        let full = temp.path().join("backups/full");
+        create_backup(&db, &full, None, progress::ignore).await.unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        db.update_node(&kept.id.to_string(), serde_json::json!({"name": "Alicia"})).await.unwrap();
+        db.delete_node(&removed.id.to_string()).await.unwrap();
+        db.insert_node("user", serde_json::json!({"name": "Carol"})).await.unwrap();
+
+        let incremental = temp.path().join("backups/incr");
+        let manifest = create_backup(&db, &incremental, Some(&full), progress::ignore).await.unwrap();
+        assert_eq!(manifest.kind, BackupKind::Incremental);
+        assert_eq!(manifest.base.as_deref(), Some("full"));
+        assert_eq!(manifest.file(NODES_FILE).unwrap().records, 2);
+        assert_eq!(verify_backup(&incremental).unwrap().len(), 2);
+
+        let restored = restore_backup(&incremental, temp.path().join("restored"), progress::ignore).await.unwrap();
+        let mut names: Vec<String> = restored.get_all_by_type("user", None).await.unwrap().iter()
+            .map(|n| n.properties.get("name").unwrap().as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["Alicia", "Carol"]);
+
+        // The chain needs its base, next to it
+        let elsewhere = temp.path().join("other");
+        assert!(create_backup(&db, &elsewhere, Some(&incremental), progress::ignore).await.is_err());
+        std::fs::remove_dir_all(&full).unwrap();
+        assert!(verify_backup(&incremental).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Group commit for independent writes
+//!
+//! Concurrent `insert_node`/`insert_edge` calls queue their writes here.
+//! The first writer to find the queue empty becomes the leader: it waits
+//! out the flush window, then commits everything queued by then in one
+//! redb transaction and hands each writer the outcome. Writers arriving
+//! while a commit holds the database lock queue up behind the next leader,
+//! so batches form under contention even with no window at all.
+
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+use super::node::{Edge, Node};
+
+/// A write waiting for the next group commit
+#[derive(Debug)]
+pub enum BatchedWrite {
+    Node(Node),
+    Edge(Edge),
+}
+
+/// A queued write and the channel its outcome goes back on
+type Pending = (BatchedWrite, oneshot::Sender<Result<(), String>>);
+
+/// Queue of writes waiting to be committed together
+pub struct WriteBatcher {
+    /// Writes queued since the last commit took the queue
+    pending: Mutex<Vec<Pending>>,
+    /// How long a leader waits for company before committing
+    window: Mutex<Duration>,
+    /// Group commits made
+    batches: AtomicU64,
+    /// Writes committed across all batches
+    writes: AtomicU64,
+}
+
+impl WriteBatcher {
+    /// Create a batcher that commits as soon as a leader gets the lock
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+            window: Mutex::new(Duration::ZERO),
+            batches: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+        }
+    }
+
+    /// Set how long a leader waits for more writes before committing
+    pub fn set_window(&self, window: Duration) {
+        *self.window.lock() = window;
+    }
+
+    /// Group commits made and writes committed by them
+    pub fn counts(&self) -> (u64, u64) {
+        (self.batches.load(Ordering::Relaxed), self.writes.load(Ordering::Relaxed))
+    }
+
+    /// Queue a write and wait until a group commit containing it finishes
+    ///
+    /// `commit` writes a batch in one transaction. It runs on the leader,
+    /// which takes the queue only once `lock` returns, so writes queued
+    /// while an earlier commit holds the database join this one.
+    pub async fn submit<L, G, C>(&self, write: BatchedWrite, lock: L, commit: C) -> Result<()>
+    where
+        L: Fn() -> G,
+        C: Fn(&G, &[BatchedWrite]) -> Result<()>,
+    {
+        let (done, outcome) = oneshot::channel();
+        let leader = {
+            let mut pending = self.pending.lock();
+            pending.push((write, done));
+            pending.len() == 1
+        };
+
+        if leader {
+            // Commits on drop, so followers are answered even if this
+            // future is cancelled while waiting out the window
+            let flush = Flush(|| self.flush(&lock, &commit));
+            let window = *self.window.lock();
+            if !window.is_zero() {
+                tokio::time::sleep(window).await;
+            }
+            drop(flush);
+        }
+
+        outcome
+            .await
+            .map_err(|_| anyhow!("Write batch was dropped before committing"))?
+            .map_err(|e| anyhow!(e))
+    }
+
+    fn flush<G>(&self, lock: impl Fn() -> G, commit: impl Fn(&G, &[BatchedWrite]) -> Result<()>) {
+        let guard = lock();
+        let batch = std::mem::take(&mut *self.pending.lock());
+        if batch.is_empty() {
+            return;
+        }
+        let (writes, waiters): (Vec<_>, Vec<_>) = batch.into_iter().unzip();
+        let result = commit(&guard, &writes).map_err(|e| format!("{:#}", e));
+        drop(guard);
+
+        if result.is_ok() {
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            self.writes.fetch_add(writes.len() as u64, Ordering::Relaxed);
+        }
+        for waiter in waiters {
+            let _ = waiter.send(result.clone());
+        }
+    }
+}
+
+impl Default for WriteBatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Leader's pending commit, run when dropped
+struct Flush<F: FnMut()>(F);
+
+impl<F: FnMut()> Drop for Flush<F> {
+    fn drop(&mut self) {
+        (self.0)();
+    }
+}
@@ -1,23 +1,43 @@
 //! Cloud bucket storage backend (S3/GCS)
 //!
 //! Provides remote storage capabilities with intelligent chunking and caching.
+//! With `ARESADB_BUCKET_KEY` set, everything written is encrypted first;
+//! see [`super::encryption`].
 
 use anyhow::{Result, Context, bail};
 use bytes::Bytes;
 use futures::StreamExt;
-use object_store::{ObjectStore, path::Path as ObjectPath};
-use object_store::aws::AmazonS3Builder;
+use object_store::{ObjectStore, PutMode, UpdateVersion, path::Path as ObjectPath};
+use object_store::aws::{AmazonS3Builder, S3ConditionalPut};
 use object_store::gcp::GoogleCloudStorageBuilder;
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::OnceCell;
 
-use super::{DatabaseConfig, SyncStats};
+use super::delta::{
+    load_base, plan_sync, preview_sync, save_base, segment_hash, segments_by_hash, ConflictStrategy, FileManifest, SyncManifest, MANIFEST_PATH,
+    PARTIAL_DIR, SEGMENT_BYTES, SEGMENT_DIR, STATE_PATH,
+};
+use super::encryption::{DataKey, MasterKey, WrappedKey, KEY_ENV, KEY_PATH};
+use super::{DatabaseConfig, SyncPreview, SyncStats};
+use crate::error::CodedError;
+use crate::progress::{self, Progress, ProgressUnit};
+
+/// The database config, relative to the database root
+const CONFIG_PATH: &str = ".aresadb/config.toml";
 
 /// Bucket storage backend for S3/GCS
 pub struct BucketStorage {
     store: Arc<dyn ObjectStore>,
     url: String,
     readonly: bool,
+    /// Master key from `ARESADB_BUCKET_KEY`
+    master: Option<MasterKey>,
+    /// Data key when the bucket is encrypted; loaded on connect, or
+    /// created by the first write to an empty bucket
+    cipher: OnceCell<DataKey>,
 }
 
 impl BucketStorage {
@@ -29,10 +49,13 @@ impl BucketStorage {
             let parts: Vec<&str> = path.splitn(2, '/').collect();
             let bucket = parts[0];
 
-            let s3 = AmazonS3Builder::from_env()
-                .with_bucket_name(bucket)
-                .build()
-                .context("Failed to build S3 client")?;
+            let mut s3 = AmazonS3Builder::from_env().with_bucket_name(bucket);
+            // Creating the data key needs If-None-Match, unless another
+            // conditional put, such as a DynamoDB lock, is configured
+            if std::env::var_os("AWS_CONDITIONAL_PUT").is_none() {
+                s3 = s3.with_conditional_put(S3ConditionalPut::ETagMatch);
+            }
+            let s3 = s3.build().context("Failed to build S3 client")?;
 
             Arc::new(s3)
         } else if url.starts_with("gs://") {
@@ -51,11 +74,106 @@ impl BucketStorage {
             bail!("Unsupported storage URL. Use s3://bucket/path or gs://bucket/path");
         };
 
-        Ok(Self {
+        let mut bucket = Self {
             store,
             url: url.to_string(),
             readonly: false,
-        })
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        bucket.unlock(MasterKey::from_env()?).await?;
+        Ok(bucket)
+    }
+
+    /// Load the bucket's data key with `master`
+    ///
+    /// Nothing is written here: a bucket nothing has been pushed to gets
+    /// its data key from its first write, see [`Self::writable`].
+    async fn unlock(&mut self, master: Option<MasterKey>) -> Result<()> {
+        match (self.load_key().await?, &master) {
+            (Some(wrapped), Some(master)) => self.cipher = OnceCell::from(DataKey::unwrap(&wrapped, master)?),
+            (Some(_), None) => bail!("Bucket is encrypted; set {} to its key", KEY_ENV),
+            (None, Some(_)) => {
+                if self.exists(CONFIG_PATH).await? || self.exists(MANIFEST_PATH).await? {
+                    bail!("Bucket holds unencrypted data and can't be encrypted in place; push to a new path instead");
+                }
+            }
+            (None, None) => {}
+        }
+        self.master = master;
+        Ok(())
+    }
+
+    /// The wrapped data key, if the bucket has one
+    async fn load_key(&self) -> Result<Option<WrappedKey>> {
+        match self.store.get(&self.object_path(KEY_PATH)).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data.bytes().await?)?)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Fail unless the bucket takes writes, first creating its data key if
+    /// it is to be encrypted and has none yet
+    ///
+    /// The key is written only if no key exists, so of two first pushes
+    /// racing, one creates it and the other adopts it.
+    async fn writable(&self) -> Result<()> {
+        if self.readonly {
+            bail!("Cannot write to readonly bucket");
+        }
+        let Some(master) = &self.master else {
+            return Ok(());
+        };
+        self.cipher
+            .get_or_try_init(|| async {
+                let key = DataKey::generate()?;
+                let wrapped = Bytes::from(serde_json::to_vec(&key.wrap(master)?)?);
+                match self.store.put_opts(&self.object_path(KEY_PATH), wrapped, PutMode::Create.into()).await {
+                    Ok(_) => Ok(key),
+                    Err(object_store::Error::AlreadyExists { .. }) => {
+                        let wrapped = self.load_key().await?.context("Bucket data key vanished while being created")?;
+                        DataKey::unwrap(&wrapped, master)
+                    }
+                    Err(object_store::Error::NotImplemented) => bail!(
+                        "Bucket does not support conditional writes, which creating its encryption key needs; \
+                         set AWS_CONDITIONAL_PUT"
+                    ),
+                    Err(e) => Err(e.into()),
+                }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Whether the bucket is encrypted, or will be once written to
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.initialized() || self.master.is_some()
+    }
+
+    async fn exists(&self, relative: &str) -> Result<bool> {
+        match self.store.head(&self.object_path(relative)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Encrypt an object's contents if the bucket is encrypted; `context`
+    /// names the object
+    fn seal(&self, data: Vec<u8>, context: &str) -> Result<Bytes> {
+        match self.cipher.get() {
+            Some(key) => Ok(Bytes::from(key.encrypt(&data, context)?)),
+            None => Ok(Bytes::from(data)),
+        }
+    }
+
+    /// Decrypt an object's contents if the bucket is encrypted
+    fn open(&self, data: Bytes, context: &str) -> Result<Bytes> {
+        match self.cipher.get() {
+            Some(key) => Ok(Bytes::from(key.decrypt(&data, context)?)),
+            None => Ok(data),
+        }
     }
 
     /// Set readonly mode
@@ -80,15 +198,8 @@ impl BucketStorage {
 
     /// Load database config from bucket
     pub async fn load_config(&self) -> Result<DatabaseConfig> {
-        let base = self.base_path();
-        let config_path = if base.is_empty() {
-            ObjectPath::from(".aresadb/config.toml")
-        } else {
-            ObjectPath::from(format!("{}/.aresadb/config.toml", base))
-        };
-
-        let data = self.store.get(&config_path).await?;
-        let bytes = data.bytes().await?;
+        let data = self.store.get(&self.object_path(CONFIG_PATH)).await?;
+        let bytes = self.open(data.bytes().await?, CONFIG_PATH)?;
         let config: DatabaseConfig = toml::from_str(std::str::from_utf8(&bytes)?)?;
 
         Ok(config)
@@ -96,192 +207,408 @@ impl BucketStorage {
 
     /// Save database config to bucket
     pub async fn save_config(&self, config: &DatabaseConfig) -> Result<()> {
-        if self.readonly {
-            bail!("Cannot write to readonly bucket");
-        }
+        self.writable().await?;
+
+        let config_str = toml::to_string_pretty(config)?;
+        self.store.put(&self.object_path(CONFIG_PATH), self.seal(config_str.into_bytes(), CONFIG_PATH)?).await?;
+
+        Ok(())
+    }
 
+    /// Path of an object relative to the database root
+    fn object_path(&self, relative: &str) -> ObjectPath {
         let base = self.base_path();
-        let config_path = if base.is_empty() {
-            ObjectPath::from(".aresadb/config.toml")
+        if base.is_empty() {
+            ObjectPath::from(relative.to_string())
         } else {
-            ObjectPath::from(format!("{}/.aresadb/config.toml", base))
-        };
+            ObjectPath::from(format!("{}/{}", base, relative))
+        }
+    }
 
-        let config_str = toml::to_string_pretty(config)?;
-        self.store.put(&config_path, Bytes::from(config_str)).await?;
+    /// Object name of a segment within the segment directory
+    fn segment_name(&self, hash: &str) -> String {
+        match self.cipher.get() {
+            Some(key) => key.segment_name(hash),
+            None => hash.to_string(),
+        }
+    }
 
-        Ok(())
+    fn segment_path(&self, hash: &str) -> ObjectPath {
+        self.object_path(&format!("{}/{}", SEGMENT_DIR, self.segment_name(hash)))
     }
 
-    /// Upload local database to bucket
-    pub async fn upload_from_local(&self, local_path: &Path) -> Result<()> {
-        if self.readonly {
-            bail!("Cannot write to readonly bucket");
+    /// Names of the segments in the bucket, including those an interrupted
+    /// push uploaded before it could record them in the manifest
+    async fn stored_segments(&self) -> Result<HashSet<String>> {
+        let prefix = self.object_path(SEGMENT_DIR);
+        let mut names = HashSet::new();
+        let mut listing = self.store.list(Some(&prefix));
+        while let Some(meta) = listing.next().await {
+            if let Some(name) = meta?.location.filename() {
+                names.insert(name.to_string());
+            }
         }
+        Ok(names)
+    }
 
-        let base = self.base_path();
+    /// Load the delta sync manifest; empty when nothing has been pushed
+    pub async fn load_manifest(&self) -> Result<SyncManifest> {
+        Ok(self.load_manifest_version().await?.0)
+    }
 
-        // Upload all files in .aresadb directory
-        let aresadb_dir = local_path.join(".aresadb");
-        for entry in walkdir::WalkDir::new(&aresadb_dir) {
-            let entry = entry?;
-            if entry.file_type().is_file() {
-                let relative = entry.path().strip_prefix(local_path)?;
-                let object_path = if base.is_empty() {
-                    ObjectPath::from(relative.to_string_lossy().to_string())
-                } else {
-                    ObjectPath::from(format!("{}/{}", base, relative.to_string_lossy()))
-                };
+    /// Load the manifest with the version `save_manifest` must replace;
+    /// no version when nothing has been pushed
+    async fn load_manifest_version(&self) -> Result<(SyncManifest, Option<UpdateVersion>)> {
+        match self.store.get(&self.object_path(MANIFEST_PATH)).await {
+            Ok(data) => {
+                let version = UpdateVersion { e_tag: data.meta.e_tag.clone(), version: data.meta.version.clone() };
+                let manifest = serde_json::from_slice(&self.open(data.bytes().await?, MANIFEST_PATH)?)?;
+                Ok((manifest, Some(version)))
+            }
+            Err(object_store::Error::NotFound { .. }) => Ok((SyncManifest::default(), None)),
+            Err(e) => Err(e.into()),
+        }
+    }
 
-                let data = tokio::fs::read(entry.path()).await?;
-                self.store.put(&object_path, Bytes::from(data)).await?;
+    /// Write the manifest, then delete segments only `previous` referred to
+    ///
+    /// The write only replaces the manifest `previous` was loaded as, at
+    /// `version`; if another client has written it since, nothing is
+    /// changed and the sync fails with a conflict.
+    async fn save_manifest(
+        &self,
+        manifest: &SyncManifest,
+        previous: &SyncManifest,
+        version: Option<UpdateVersion>,
+    ) -> Result<()> {
+        let data = self.seal(serde_json::to_vec(manifest)?, MANIFEST_PATH)?;
+        let mode = match version {
+            Some(version) => PutMode::Update(version),
+            None => PutMode::Create,
+        };
+        match self.store.put_opts(&self.object_path(MANIFEST_PATH), data, mode.into()).await {
+            Ok(_) => {}
+            Err(object_store::Error::Precondition { .. } | object_store::Error::AlreadyExists { .. }) => {
+                return Err(CodedError::conflict(format!(
+                    "{} was changed by another client during this sync; run it again",
+                    self.url
+                ))
+                .into());
             }
+            Err(e) => return Err(e.into()),
         }
 
+        let live = manifest.segment_hashes();
+        for hash in previous.segment_hashes().difference(&live) {
+            match self.store.delete(&self.segment_path(hash)).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
         Ok(())
     }
 
-    /// Download bucket contents to local path
-    pub async fn download_to_local(&self, local_path: &Path) -> Result<()> {
-        let base = self.base_path();
-        let prefix = if base.is_empty() {
-            None
-        } else {
-            Some(ObjectPath::from(base.clone()))
-        };
+    /// Fetch a segment by hash, failing if its content doesn't match
+    pub async fn get_segment(&self, hash: &str) -> Result<Bytes> {
+        let segment = self.store.get(&self.segment_path(hash)).await?.bytes().await?;
+        let segment = self.open(segment, hash)?;
+        if segment_hash(&segment) != hash {
+            bail!("Segment {} in {} is corrupt", hash, self.url);
+        }
+        Ok(segment)
+    }
 
-        // List all objects
-        let mut stream = self.store.list(prefix.as_ref());
+    /// Upload local database to bucket
+    pub async fn upload_from_local(&self, local_path: &Path) -> Result<()> {
+        self.upload_from_local_with_progress(local_path, progress::ignore).await
+    }
 
-        while let Some(result) = stream.next().await {
-            let meta = result?;
-            let object_path = meta.location;
-
-            // Calculate local path
-            let relative = if base.is_empty() {
-                object_path.to_string()
-            } else {
-                object_path.to_string().strip_prefix(&format!("{}/", base))
-                    .unwrap_or(&object_path.to_string())
-                    .to_string()
-            };
+    /// Upload local database to bucket, reporting bytes uploaded
+    ///
+    /// Segments the bucket already holds are not sent again, so a push
+    /// that was interrupted picks up after the last segment it uploaded.
+    pub async fn upload_from_local_with_progress<F>(&self, local_path: &Path, on_progress: F) -> Result<SyncStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        self.writable().await?;
+
+        let (previous, version) = self.load_manifest_version().await?;
+        let mut manifest = previous.clone();
+        let local = local_manifest(local_path)?;
+        let total = local.files.values().map(|file| file.size).sum();
+        let mut transfer = Transfer::new("push", total, on_progress);
+
+        let paths: Vec<String> = local.files.into_keys().collect();
+        self.upload_files(local_path, &paths, &mut manifest, &mut transfer).await?;
+        // Files deleted locally are dropped from the bucket too
+        manifest.files.retain(|path, _| paths.contains(path));
+        if manifest != previous {
+            self.save_manifest(&manifest, &previous, version).await?;
+        }
+        save_base(local_path, &self.url, &manifest)?;
 
-            let local_file = local_path.join(&relative);
+        Ok(transfer.stats)
+    }
 
-            // Create parent directories
+    /// Download bucket contents to local path
+    ///
+    /// Buckets pushed before the manifest existed hold each file whole
+    /// under its own path, and are downloaded that way.
+    pub async fn download_to_local(&self, local_path: &Path) -> Result<SyncStats> {
+        let manifest = self.load_manifest().await?;
+        if manifest.files.is_empty() {
+            return self.download_whole_files(local_path).await;
+        }
+        let total = manifest.files.values().map(|file| file.size).sum();
+        let mut transfer = Transfer::new("pull", total, progress::ignore);
+        let paths: Vec<String> = manifest.files.keys().cloned().collect();
+        self.download_files(local_path, &paths, &manifest, &mut transfer).await?;
+        Ok(transfer.stats)
+    }
+
+    /// Download the files of a bucket without a manifest, each stored
+    /// whole under its path relative to the database root
+    async fn download_whole_files(&self, local_path: &Path) -> Result<SyncStats> {
+        let base = self.base_path();
+        let mut files = Vec::new();
+        let mut listing = self.store.list(Some(&self.object_path(".aresadb")));
+        while let Some(meta) = listing.next().await {
+            let meta = meta?;
+            let location = meta.location.to_string();
+            let relative = match base.is_empty() {
+                true => location.as_str(),
+                false => location.strip_prefix(&format!("{}/", base)).unwrap_or(&location),
+            };
+            // Sync state, such as an encrypted bucket's key, is not a file
+            if relative.starts_with(".aresadb/sync/") {
+                continue;
+            }
+            files.push((relative.to_string(), meta.location, meta.size as u64));
+        }
+        if files.is_empty() {
+            bail!("No database has been pushed to {}", self.url);
+        }
+
+        let total = files.iter().map(|(_, _, size)| size).sum();
+        let mut transfer = Transfer::new("pull", total, progress::ignore);
+        for (relative, location, size) in files {
+            let data = self.store.get(&location).await?.bytes().await?;
+            let data = self.open(data, &relative)?;
+            let local_file = local_path.join(bucket_relative(&relative)?);
             if let Some(parent) = local_file.parent() {
                 tokio::fs::create_dir_all(parent).await?;
             }
-
-            // Download file
-            let data = self.store.get(&object_path).await?;
-            let bytes = data.bytes().await?;
-            tokio::fs::write(&local_file, bytes).await?;
+            tokio::fs::write(&local_file, &data).await?;
+            transfer.transferred(size);
+            transfer.stats.downloaded += 1;
         }
-
-        Ok(())
+        Ok(transfer.stats)
     }
 
     /// Bidirectional sync with local path
-    pub async fn sync_with_local(&self, local_path: &Path) -> Result<SyncStats> {
-        let mut stats = SyncStats::default();
-        let base = self.base_path();
-
-        // Get list of remote files with their modification times
-        let prefix = if base.is_empty() {
-            None
-        } else {
-            Some(ObjectPath::from(base.clone()))
-        };
-
-        let mut remote_files = std::collections::HashMap::new();
-        let mut stream = self.store.list(prefix.as_ref());
+    pub async fn sync_with_local(&self, local_path: &Path, strategy: ConflictStrategy) -> Result<SyncStats> {
+        self.sync_with_local_with_progress(local_path, strategy, progress::ignore).await
+    }
 
-        while let Some(result) = stream.next().await {
-            let meta = result?;
-            let path = meta.location.to_string();
-            let relative = if base.is_empty() {
-                path.clone()
-            } else {
-                path.strip_prefix(&format!("{}/", base))
-                    .unwrap_or(&path)
-                    .to_string()
-            };
-            remote_files.insert(relative, meta.last_modified);
+    /// Bidirectional sync with local path, reporting bytes transferred
+    ///
+    /// The database changed on one side since the last sync replaces the
+    /// other, files and all; one changed on both is settled by `strategy`.
+    /// Only the segments that differ from the other side are moved.
+    pub async fn sync_with_local_with_progress<F>(
+        &self,
+        local_path: &Path,
+        strategy: ConflictStrategy,
+        on_progress: F,
+    ) -> Result<SyncStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let (previous, version) = self.load_manifest_version().await?;
+        let mut manifest = previous.clone();
+        let local = local_manifest(local_path)?;
+        let base = load_base(local_path, &self.url)?;
+
+        let mut plan = plan_sync(&local, &manifest, base.as_ref(), strategy)?;
+        if self.readonly {
+            plan.uploads.clear();
+            plan.remote_deletes.clear();
+        }
+        if !plan.uploads.is_empty() || !plan.remote_deletes.is_empty() {
+            self.writable().await?;
         }
 
-        // Get list of local files
-        let aresadb_dir = local_path.join(".aresadb");
-        let mut local_files = std::collections::HashMap::new();
-
-        if aresadb_dir.exists() {
-            for entry in walkdir::WalkDir::new(&aresadb_dir) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    let relative = entry.path().strip_prefix(local_path)?;
-                    let modified = entry.metadata()?.modified()?;
-                    local_files.insert(relative.to_string_lossy().to_string(), modified);
-                }
+        let total = plan.uploads.iter().map(|path| local.files[path].size)
+            .chain(plan.downloads.iter().map(|path| manifest.files[path].size))
+            .sum();
+        let mut transfer = Transfer::new("sync", total, on_progress);
+
+        self.download_files(local_path, &plan.downloads, &manifest, &mut transfer).await?;
+        for path in &plan.local_deletes {
+            match tokio::fs::remove_file(local_path.join(bucket_relative(path)?)).await {
+                Ok(()) => transfer.stats.deleted += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
             }
         }
+        self.upload_files(local_path, &plan.uploads, &mut manifest, &mut transfer).await?;
+        for path in &plan.remote_deletes {
+            manifest.files.remove(path);
+            transfer.stats.deleted += 1;
+        }
+        if manifest != previous {
+            self.save_manifest(&manifest, &previous, version).await?;
+        }
+        save_base(local_path, &self.url, &manifest)?;
 
-        // Upload newer local files
-        if !self.readonly {
-            for (path, local_time) in &local_files {
-                let should_upload = if let Some(remote_time) = remote_files.get(path) {
-                    let local_datetime = chrono::DateTime::<chrono::Utc>::from(*local_time);
-                    local_datetime > *remote_time
-                } else {
-                    true
-                };
+        transfer.stats.conflicts = plan.conflicts;
+        Ok(transfer.stats)
+    }
 
-                if should_upload {
-                    let local_file = local_path.join(path);
-                    let data = tokio::fs::read(&local_file).await?;
+    /// What [`Self::sync_with_local`] would do, without changing either side
+    pub async fn preview_sync(&self, local_path: &Path, strategy: ConflictStrategy) -> Result<SyncPreview> {
+        let remote = self.load_manifest().await?;
+        let local = local_manifest(local_path)?;
+        let base = load_base(local_path, &self.url)?;
+        Ok(preview_sync(&local, &remote, base.as_ref(), strategy, self.readonly))
+    }
 
-                    let object_path = if base.is_empty() {
-                        ObjectPath::from(path.clone())
-                    } else {
-                        ObjectPath::from(format!("{}/{}", base, path))
-                    };
+    /// Upload the segments of local files that the bucket lacks, recording
+    /// the files in `manifest`
+    ///
+    /// Segments an interrupted push left in the bucket count as resumed.
+    async fn upload_files<F>(
+        &self,
+        local_path: &Path,
+        paths: &[String],
+        manifest: &mut SyncManifest,
+        transfer: &mut Transfer<F>,
+    ) -> Result<()>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let mut known = manifest.segment_hashes();
+        let mut stored = None;
+        for path in paths {
+            let local_file = local_path.join(path);
+            let data = tokio::fs::read(&local_file).await?;
+            let modified = tokio::fs::metadata(&local_file).await?.modified()?;
+            let file = FileManifest::new(&data, modified.into());
+
+            if manifest.files.get(path).is_some_and(|remote| remote.segments == file.segments) {
+                transfer.saved(file.size);
+                continue;
+            }
 
-                    self.store.put(&object_path, Bytes::from(data)).await?;
-                    stats.uploaded += 1;
+            // Only list the bucket once there is something to upload
+            let stored = match &mut stored {
+                Some(stored) => stored,
+                None => stored.insert(self.stored_segments().await?),
+            };
+            for (hash, segment) in file.segments.iter().zip(data.chunks(SEGMENT_BYTES)) {
+                if !known.insert(hash.clone()) {
+                    transfer.saved(segment.len() as u64);
+                } else if stored.contains(&self.segment_name(hash)) {
+                    transfer.resumed(segment.len() as u64);
+                } else {
+                    self.store.put(&self.segment_path(hash), self.seal(segment.to_vec(), hash)?).await?;
+                    transfer.transferred(segment.len() as u64);
                 }
             }
+
+            // The config is also kept whole, for connecting to the bucket
+            if path == CONFIG_PATH {
+                self.store.put(&self.object_path(CONFIG_PATH), self.seal(data, CONFIG_PATH)?).await?;
+            }
+            manifest.files.insert(path.clone(), file);
+            transfer.stats.uploaded += 1;
         }
+        Ok(())
+    }
 
-        // Download newer remote files
-        for (path, remote_time) in &remote_files {
-            let should_download = if let Some(local_time) = local_files.get(path) {
-                let local_datetime = chrono::DateTime::<chrono::Utc>::from(*local_time);
-                *remote_time > local_datetime
-            } else {
-                true
+    /// Rebuild files from the bucket's segments, reusing those the local
+    /// copy already has
+    ///
+    /// Each file is written segment by segment to a partial copy under
+    /// `.aresadb/sync/partial` and moved into place once complete. A
+    /// download that was interrupted keeps the segments its partial copy
+    /// already holds and fetches only the rest.
+    async fn download_files<F>(
+        &self,
+        local_path: &Path,
+        paths: &[String],
+        manifest: &SyncManifest,
+        transfer: &mut Transfer<F>,
+    ) -> Result<()>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        for path in paths {
+            let file = &manifest.files[path];
+            let relative = bucket_relative(path)?;
+            let local_file = local_path.join(relative);
+            let existing = match tokio::fs::read(&local_file).await {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e.into()),
             };
+            let have = segments_by_hash(&existing);
+            let unchanged = existing.len() as u64 == file.size
+                && existing.chunks(SEGMENT_BYTES).zip(&file.segments).all(|(chunk, hash)| have.get(hash) == Some(&chunk));
+            if unchanged {
+                transfer.saved(file.size);
+                continue;
+            }
 
-            if should_download {
-                let object_path = if base.is_empty() {
-                    ObjectPath::from(path.clone())
-                } else {
-                    ObjectPath::from(format!("{}/{}", base, path))
-                };
-
-                let local_file = local_path.join(path);
+            // Keep the leading segments of an interrupted download
+            let partial_file = local_path.join(PARTIAL_DIR).join(relative);
+            let mut partial = match tokio::fs::read(&partial_file).await {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => return Err(e.into()),
+            };
+            let resumed = partial.chunks(SEGMENT_BYTES).zip(&file.segments)
+                .take_while(|(segment, hash)| segment_hash(segment) == **hash)
+                .count();
+            partial.truncate((resumed * SEGMENT_BYTES).min(partial.len()));
+            transfer.resumed(partial.len() as u64);
 
-                // Create parent directories
-                if let Some(parent) = local_file.parent() {
-                    tokio::fs::create_dir_all(parent).await?;
+            if let Some(parent) = partial_file.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut out = tokio::fs::OpenOptions::new().create(true).append(true).open(&partial_file).await?;
+            out.set_len(partial.len() as u64).await?;
+            let mut written = partial.len() as u64;
+            for hash in &file.segments[resumed..] {
+                match have.get(hash) {
+                    Some(segment) => {
+                        out.write_all(segment).await?;
+                        written += segment.len() as u64;
+                        transfer.saved(segment.len() as u64);
+                    }
+                    None => {
+                        let segment = self.get_segment(hash).await?;
+                        out.write_all(&segment).await?;
+                        written += segment.len() as u64;
+                        transfer.transferred(segment.len() as u64);
+                    }
                 }
+            }
+            out.sync_all().await?;
+            drop(out);
+            if written != file.size {
+                bail!("{} from {} has {} bytes, expected {}", path, self.url, written, file.size);
+            }
 
-                let data = self.store.get(&object_path).await?;
-                let bytes = data.bytes().await?;
-                tokio::fs::write(&local_file, bytes).await?;
-                stats.downloaded += 1;
+            // Create parent directories
+            if let Some(parent) = local_file.parent() {
+                tokio::fs::create_dir_all(parent).await?;
             }
+            tokio::fs::rename(&partial_file, &local_file).await?;
+            transfer.stats.downloaded += 1;
         }
-
-        Ok(stats)
+        Ok(())
     }
 
     /// Get a single object from bucket
@@ -295,14 +622,12 @@ impl BucketStorage {
 
         let data = self.store.get(&object_path).await?;
         let bytes = data.bytes().await?;
-        Ok(bytes)
+        self.open(bytes, path)
     }
 
     /// Put a single object to bucket
     pub async fn put(&self, path: &str, data: Bytes) -> Result<()> {
-        if self.readonly {
-            bail!("Cannot write to readonly bucket");
-        }
+        self.writable().await?;
 
         let base = self.base_path();
         let object_path = if base.is_empty() {
@@ -311,7 +636,7 @@ impl BucketStorage {
             ObjectPath::from(format!("{}/{}", base, path))
         };
 
-        self.store.put(&object_path, data).await?;
+        self.store.put(&object_path, self.seal(data.to_vec(), path)?).await?;
         Ok(())
     }
 
@@ -349,3 +674,240 @@ impl BucketStorage {
     }
 }
 
+/// A path named by the bucket, checked to stay inside the database root
+///
+/// Manifest keys and object names come from whoever can write the bucket,
+/// so absolute paths and `..` are refused rather than joined.
+fn bucket_relative(path: &str) -> Result<&Path> {
+    let relative = Path::new(path);
+    let inside = relative.components().next().is_some()
+        && relative.components().all(|c| matches!(c, std::path::Component::Normal(_)));
+    if !inside {
+        bail!("Refusing path outside the database from the bucket: {}", path);
+    }
+    Ok(relative)
+}
+
+/// Describe the files under a database's `.aresadb` directory, by path
+/// relative to the database root
+///
+/// The local sync state is left out; it describes the other files.
+fn local_manifest(local_path: &Path) -> Result<SyncManifest> {
+    let aresadb_dir = local_path.join(".aresadb");
+    let sync_dir = local_path.join(STATE_PATH);
+    let sync_dir = sync_dir.parent().unwrap_or(aresadb_dir.as_path());
+    let mut manifest = SyncManifest::default();
+    if aresadb_dir.exists() {
+        for entry in walkdir::WalkDir::new(&aresadb_dir).into_iter().filter_entry(|e| e.path() != sync_dir) {
+            let entry = entry?;
+            if entry.file_type().is_file() {
+                let relative = entry.path().strip_prefix(local_path)?;
+                let modified = entry.metadata()?.modified()?;
+                let data = std::fs::read(entry.path())?;
+                manifest.files.insert(relative.to_string_lossy().to_string(), FileManifest::new(&data, modified.into()));
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+/// Progress and byte counts for a push, pull or sync
+///
+/// Bytes skipped because the other side already had them count towards
+/// progress the same as bytes sent.
+struct Transfer<F> {
+    operation: &'static str,
+    done: u64,
+    total: u64,
+    on_progress: F,
+    stats: SyncStats,
+}
+
+impl<F: FnMut(Progress)> Transfer<F> {
+    fn new(operation: &'static str, total: u64, mut on_progress: F) -> Self {
+        on_progress(Progress::new(operation, ProgressUnit::Bytes, 0, Some(total)));
+        Self { operation, done: 0, total, on_progress, stats: SyncStats::default() }
+    }
+
+    fn transferred(&mut self, bytes: u64) {
+        self.stats.bytes_transferred += bytes;
+        self.advance(bytes);
+    }
+
+    fn resumed(&mut self, bytes: u64) {
+        self.stats.bytes_resumed += bytes;
+        self.advance(bytes);
+    }
+
+    fn saved(&mut self, bytes: u64) {
+        self.stats.bytes_saved += bytes;
+        self.advance(bytes);
+    }
+
+    fn advance(&mut self, bytes: u64) {
+        self.done += bytes;
+        (self.on_progress)(Progress::new(self.operation, ProgressUnit::Bytes, self.done, Some(self.total)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_resume_interrupted_transfers() {
+        let bucket = BucketStorage {
+            store: Arc::new(InMemory::new()),
+            url: "s3://test/db".to_string(),
+            readonly: false,
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        let source = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..SEGMENT_BYTES * 3).map(|i| (i % 251) as u8).collect();
+        std::fs::create_dir_all(source.path().join(".aresadb")).unwrap();
+        std::fs::write(source.path().join(".aresadb/data.redb"), &data).unwrap();
+
+        // A push cut off before writing the manifest reuses its segments
+        bucket.upload_from_local(source.path()).await.unwrap();
+        bucket.store.delete(&bucket.object_path(MANIFEST_PATH)).await.unwrap();
+        let stats = bucket.upload_from_local(source.path()).await.unwrap();
+        assert_eq!((stats.bytes_transferred, stats.bytes_resumed), (0, data.len() as u64));
+
+        // A download cut off after one segment fetches only the other two,
+        // even once the first is gone from the bucket
+        let target = tempfile::tempdir().unwrap();
+        let partial = target.path().join(PARTIAL_DIR).join(".aresadb/data.redb");
+        std::fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        std::fs::write(&partial, &data[..SEGMENT_BYTES + 10]).unwrap();
+        bucket.store.delete(&bucket.segment_path(&segment_hash(&data[..SEGMENT_BYTES]))).await.unwrap();
+
+        let stats = bucket.download_to_local(target.path()).await.unwrap();
+        assert_eq!(stats.bytes_resumed, SEGMENT_BYTES as u64);
+        assert_eq!(std::fs::read(target.path().join(".aresadb/data.redb")).unwrap(), data);
+        assert!(!partial.exists());
+
+        // A tampered segment is refused before the file is replaced
+        let first = segment_hash(&data[..SEGMENT_BYTES]);
+        bucket.store.put(&bucket.segment_path(&first), Bytes::from(vec![0u8; SEGMENT_BYTES])).await.unwrap();
+        let fresh = tempfile::tempdir().unwrap();
+        let error = bucket.download_to_local(fresh.path()).await.unwrap_err();
+        assert!(error.to_string().contains("is corrupt"));
+        assert!(!fresh.path().join(".aresadb/data.redb").exists());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_push_conflicts() {
+        let store = Arc::new(InMemory::new());
+        let connect = || BucketStorage {
+            store: store.clone(),
+            url: "s3://test/db".to_string(),
+            readonly: false,
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        let (first, second) = (connect(), connect());
+        let local = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(local.path().join(".aresadb")).unwrap();
+        std::fs::write(local.path().join(".aresadb/data.redb"), b"one").unwrap();
+        first.upload_from_local(local.path()).await.unwrap();
+
+        // A push landing between another client's load and save wins; the
+        // stale save changes nothing
+        let (stale, version) = second.load_manifest_version().await.unwrap();
+        std::fs::write(local.path().join(".aresadb/data.redb"), b"two").unwrap();
+        first.upload_from_local(local.path()).await.unwrap();
+        let pushed = first.load_manifest().await.unwrap();
+
+        let error = second.save_manifest(&SyncManifest::default(), &stale, version).await.unwrap_err();
+        assert_eq!(crate::error::classify(&error), crate::error::ErrorCode::Conflict);
+        assert_eq!(second.load_manifest().await.unwrap(), pushed);
+        for hash in pushed.segment_hashes() {
+            assert!(second.exists(&format!("{}/{}", SEGMENT_DIR, hash)).await.unwrap());
+        }
+
+        // Two first pushes to an empty bucket can't both write the manifest
+        let error = second.save_manifest(&SyncManifest::default(), &SyncManifest::default(), None).await.unwrap_err();
+        assert_eq!(crate::error::classify(&error), crate::error::ErrorCode::Conflict);
+    }
+
+    #[tokio::test]
+    async fn test_key_created_on_first_write() {
+        use base64::Engine;
+
+        let store = Arc::new(InMemory::new());
+        let connect = |store: &Arc<InMemory>| BucketStorage {
+            store: store.clone(),
+            url: "s3://test/db".to_string(),
+            readonly: false,
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        let master = || MasterKey::from_base64(&base64::engine::general_purpose::STANDARD.encode([7u8; 32])).unwrap();
+        let (mut first, mut second) = (connect(&store), connect(&store));
+        first.unlock(Some(master())).await.unwrap();
+        second.unlock(Some(master())).await.unwrap();
+
+        // Connecting and previewing a sync leave the bucket empty
+        let local = tempfile::tempdir().unwrap();
+        first.preview_sync(local.path(), ConflictStrategy::default()).await.unwrap();
+        first.set_readonly(true);
+        assert!(first.writable().await.is_err());
+        assert!(!first.exists(KEY_PATH).await.unwrap());
+
+        // Of two pushes racing to an empty bucket, the second adopts the
+        // key the first created
+        first.set_readonly(false);
+        first.writable().await.unwrap();
+        second.writable().await.unwrap();
+        assert!(first.exists(KEY_PATH).await.unwrap());
+        assert_eq!(first.segment_name("abc"), second.segment_name("abc"));
+        assert_ne!(first.segment_name("abc"), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_pull_refuses_paths_outside_database() {
+        let bucket = BucketStorage {
+            store: Arc::new(InMemory::new()),
+            url: "s3://test/db".to_string(),
+            readonly: false,
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        let root = tempfile::tempdir().unwrap();
+        let target = root.path().join("db");
+        for path in ["../escaped", "/tmp/escaped", ".aresadb/../../escaped"] {
+            let mut manifest = SyncManifest::default();
+            manifest.files.insert(path.to_string(), FileManifest::new(b"data", chrono::Utc::now()));
+            bucket.save_manifest(&manifest, &SyncManifest::default(), bucket.load_manifest_version().await.unwrap().1).await.unwrap();
+
+            let error = bucket.download_to_local(&target).await.unwrap_err();
+            assert!(error.to_string().contains("Refusing path outside the database"), "{}", path);
+        }
+        assert!(!root.path().join("escaped").exists());
+        assert!(bucket_relative(".aresadb/data.redb").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pull_without_manifest() {
+        let bucket = BucketStorage {
+            store: Arc::new(InMemory::new()),
+            url: "s3://test/db".to_string(),
+            readonly: false,
+            master: None,
+            cipher: OnceCell::new(),
+        };
+        let target = tempfile::tempdir().unwrap();
+        let error = bucket.download_to_local(target.path()).await.unwrap_err();
+        assert!(error.to_string().contains("No database has been pushed"));
+
+        // Files pushed whole, as before segments, are pulled whole
+        bucket.store.put(&bucket.object_path(CONFIG_PATH), Bytes::from_static(b"name = 'old'")).await.unwrap();
+        bucket.store.put(&bucket.object_path(".aresadb/data.redb"), Bytes::from_static(b"data")).await.unwrap();
+        let stats = bucket.download_to_local(target.path()).await.unwrap();
+        assert_eq!((stats.downloaded, stats.bytes_transferred), (2, 16));
+        assert_eq!(std::fs::read(target.path().join(".aresadb/data.redb")).unwrap(), b"data");
+        assert_eq!(segment_hash(b"data").len(), 64);
+    }
+}
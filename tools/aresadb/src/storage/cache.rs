@@ -1,10 +1,18 @@
 //! Cache layer for bucket storage
 //!
-//! Provides LRU caching for remote data to reduce latency and bandwidth.
+//! Provides size-bounded caching for remote data to reduce latency and
+//! bandwidth, with a choice of eviction policy and node types that are
+//! never evicted.
 
 use anyhow::Result;
 use bytes::Bytes;
+use moka::notification::RemovalCause;
+use moka::policy::EvictionPolicy;
 use moka::sync::Cache;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -15,38 +23,116 @@ pub struct CacheEntry {
     pub size: usize,
 }
 
-/// LRU cache layer for remote storage
+/// How the cache picks entries to evict when it is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachePolicy {
+    /// Evict the least recently used entry
+    Lru,
+    /// Admit entries by estimated access frequency (TinyLFU) and evict by
+    /// recency among them, so a one-off scan cannot flush hot entries
+    #[default]
+    TinyLfu,
+}
+
+/// Cache settings, stored under `[cache]` in the database config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Maximum size in bytes, excluding pinned entries; unset uses the
+    /// backend's default
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Eviction policy
+    #[serde(default)]
+    pub policy: CachePolicy,
+    /// Node types whose entries are kept until removed, never evicted
+    #[serde(default)]
+    pub pinned_types: Vec<String>,
+    /// Seconds an entry is kept after it is cached, however often it is
+    /// read; unset keeps entries until they idle for an hour
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+    /// Node types read into the cache when a bucket database is connected
+    #[serde(default)]
+    pub warm_types: Vec<String>,
+}
+
+/// Size-bounded cache layer for remote storage
 pub struct CacheLayer {
     /// The cache store
     cache: Cache<String, Arc<CacheEntry>>,
+    /// Entries of pinned node types, outside the size bound
+    pinned: RwLock<HashMap<String, Arc<CacheEntry>>>,
+    /// Node types whose entries go to `pinned`
+    pinned_types: HashSet<String>,
+    /// Eviction policy in use
+    policy: CachePolicy,
     /// Maximum cache size in bytes
     max_size: u64,
-    /// Current cache size in bytes
-    current_size: std::sync::atomic::AtomicU64,
+    /// Lookups that found an entry
+    hits: AtomicU64,
+    /// Lookups that found nothing
+    misses: AtomicU64,
+    /// Entries dropped to stay within the size bound or after idling
+    evictions: Arc<AtomicU64>,
 }
 
 impl CacheLayer {
     /// Create a new cache layer with the given maximum size in bytes
     pub fn new(max_size_bytes: u64) -> Self {
-        let cache = Cache::builder()
-            .max_capacity(10000) // Max number of entries
+        Self::with_config(&CacheConfig::default(), max_size_bytes)
+    }
+
+    /// Create a cache layer from config, using `default_size` when the
+    /// config leaves the size unset
+    pub fn with_config(config: &CacheConfig, default_size: u64) -> Self {
+        let max_size = config.size_bytes.unwrap_or(default_size);
+        let evictions = Arc::new(AtomicU64::new(0));
+        let evicted = Arc::clone(&evictions);
+        let policy = match config.policy {
+            CachePolicy::Lru => EvictionPolicy::lru(),
+            CachePolicy::TinyLfu => EvictionPolicy::tiny_lfu(),
+        };
+
+        let mut builder = Cache::builder()
+            .max_capacity(max_size)
+            .eviction_policy(policy)
             .time_to_idle(Duration::from_secs(3600)) // 1 hour TTL
             .weigher(|_key: &String, value: &Arc<CacheEntry>| -> u32 {
                 // Weight by size (capped at u32::MAX)
                 value.size.min(u32::MAX as usize) as u32
             })
-            .build();
+            .eviction_listener(move |_key, _value, cause: RemovalCause| {
+                if cause.was_evicted() {
+                    evicted.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        if let Some(ttl) = config.ttl_secs {
+            builder = builder.time_to_live(Duration::from_secs(ttl));
+        }
+        let cache = builder.build();
 
         Self {
             cache,
-            max_size: max_size_bytes,
-            current_size: std::sync::atomic::AtomicU64::new(0),
+            pinned: RwLock::new(HashMap::new()),
+            pinned_types: config.pinned_types.iter().cloned().collect(),
+            policy: config.policy,
+            max_size,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions,
         }
     }
 
     /// Get an entry from cache
     pub fn get(&self, key: &str) -> Option<Bytes> {
-        self.cache.get(key).map(|entry| entry.data.clone())
+        let data = match self.pinned.read().get(key) {
+            Some(entry) => Some(entry.data.clone()),
+            None => self.cache.get(key).map(|entry| entry.data.clone()),
+        };
+        let counter = if data.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        data
     }
 
     /// Put an entry in cache
@@ -58,25 +144,34 @@ impl CacheLayer {
         });
 
         self.cache.insert(key.to_string(), entry);
-        self.current_size.fetch_add(size as u64, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Put an entry belonging to a node of `node_type`, pinning it if the
+    /// type is pinned
+    pub fn put_typed(&self, key: &str, node_type: &str, data: Bytes) {
+        if !self.pinned_types.contains(node_type) {
+            return self.put(key, data);
+        }
+        let size = data.len();
+        self.cache.invalidate(key);
+        self.pinned.write().insert(key.to_string(), Arc::new(CacheEntry { data, size }));
     }
 
     /// Remove an entry from cache
     pub fn remove(&self, key: &str) {
-        if let Some(entry) = self.cache.remove(key) {
-            self.current_size.fetch_sub(entry.size as u64, std::sync::atomic::Ordering::Relaxed);
-        }
+        self.pinned.write().remove(key);
+        self.cache.invalidate(key);
     }
 
-    /// Clear all entries from cache
+    /// Clear all entries from cache, pinned ones included
     pub fn clear(&self) {
+        self.pinned.write().clear();
         self.cache.invalidate_all();
-        self.current_size.store(0, std::sync::atomic::Ordering::Relaxed);
     }
 
-    /// Get current cache size in bytes
+    /// Get current cache size in bytes, pinned entries included
     pub fn size(&self) -> u64 {
-        self.current_size.load(std::sync::atomic::Ordering::Relaxed)
+        self.cache.weighted_size() + self.pinned_size()
     }
 
     /// Get maximum cache size in bytes
@@ -84,14 +179,23 @@ impl CacheLayer {
         self.max_size
     }
 
-    /// Get number of entries in cache
+    /// Get the eviction policy
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    /// Get number of entries in cache, pinned entries included
     pub fn entry_count(&self) -> u64 {
-        self.cache.entry_count()
+        self.cache.entry_count() + self.pinned.read().len() as u64
     }
 
     /// Check if key exists in cache
     pub fn contains(&self, key: &str) -> bool {
-        self.cache.contains_key(key)
+        self.pinned.read().contains_key(key) || self.cache.contains_key(key)
+    }
+
+    fn pinned_size(&self) -> u64 {
+        self.pinned.read().values().map(|entry| entry.size as u64).sum()
     }
 
     /// Get or fetch: returns cached value or fetches from provided async function
@@ -122,11 +226,55 @@ pub struct CacheStats {
     pub size_bytes: u64,
     pub max_size_bytes: u64,
     pub utilization_percent: f64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to stay within the size bound or after idling
+    pub evictions: u64,
+    /// Entries of pinned node types
+    pub pinned_entries: u64,
+    /// Eviction policy in use
+    pub policy: CachePolicy,
+}
+
+impl CacheStats {
+    /// Fraction of lookups served from cache (0 when there were none)
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+
+    /// One-line report of counts, size and policy
+    pub fn summary(&self) -> String {
+        let policy = match self.policy {
+            CachePolicy::Lru => "lru",
+            CachePolicy::TinyLfu => "tiny_lfu",
+        };
+        let mut summary = format!(
+            "{} hits, {} misses, {} evictions ({} of {}, {}",
+            self.hits,
+            self.misses,
+            self.evictions,
+            humansize::format_size(self.size_bytes, humansize::BINARY),
+            humansize::format_size(self.max_size_bytes, humansize::BINARY),
+            policy,
+        );
+        if self.pinned_entries > 0 {
+            summary.push_str(&format!(", {} pinned", self.pinned_entries));
+        }
+        summary.push(')');
+        summary
+    }
 }
 
 impl CacheLayer {
     /// Get cache statistics
     pub fn stats(&self) -> CacheStats {
+        // Apply pending evictions so sizes and counts are current
+        self.cache.run_pending_tasks();
         let size = self.size();
         let max_size = self.max_size();
 
@@ -135,6 +283,11 @@ impl CacheLayer {
             size_bytes: size,
             max_size_bytes: max_size,
             utilization_percent: (size as f64 / max_size as f64) * 100.0,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            pinned_entries: self.pinned.read().len() as u64,
+            policy: self.policy,
         }
     }
 }
@@ -197,7 +350,47 @@ mod tests {
             Ok(Bytes::from("should not see this"))
         }).await.unwrap();
         assert_eq!(&data[..], b"fetched");
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.hit_rate(), 0.5);
     }
-}
 
+    #[test]
+    fn test_eviction_counts() {
+        let config = CacheConfig { policy: CachePolicy::Lru, ..Default::default() };
+        let cache = CacheLayer::with_config(&config, 64);
+
+        for i in 0..8 {
+            cache.put(&format!("key{}", i), Bytes::from(vec![0u8; 16]));
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.policy, CachePolicy::Lru);
+        assert!(stats.size_bytes <= 64);
+        assert!(stats.evictions >= 4);
+    }
 
+    #[test]
+    fn test_pinned_types() {
+        let config = CacheConfig {
+            size_bytes: Some(64),
+            pinned_types: vec!["config".to_string()],
+            ..Default::default()
+        };
+        let cache = CacheLayer::with_config(&config, 1024);
+
+        cache.put_typed("settings", "config", Bytes::from(vec![1u8; 48]));
+        for i in 0..8 {
+            cache.put_typed(&format!("user{}", i), "user", Bytes::from(vec![0u8; 16]));
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.max_size_bytes, 64);
+        assert_eq!(stats.pinned_entries, 1);
+        assert_eq!(cache.get("settings").unwrap().len(), 48);
+
+        cache.remove("settings");
+        assert!(!cache.contains("settings"));
+    }
+}
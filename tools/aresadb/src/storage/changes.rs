@@ -0,0 +1,151 @@
+//! Change Data Capture
+//!
+//! In-process change feed for node and edge mutations. Every write that goes
+//! through `Database` is published to a broadcast channel so that servers can
+//! push live updates to subscribed clients.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+
+use super::node::{Edge, Node, Timestamp};
+
+/// Number of events buffered per subscriber before it starts lagging
+const CHANGE_FEED_CAPACITY: usize = 1024;
+
+/// Kind of mutation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Record was created
+    Insert,
+    /// Record properties were replaced
+    Update,
+    /// Record was removed
+    Delete,
+}
+
+/// The record affected by a change
+///
+/// Deletes carry the last known state of the record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeRecord {
+    /// A node change
+    Node(Node),
+    /// An edge change
+    Edge(Edge),
+}
+
+/// A single change published on the feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeEvent {
+    /// Monotonic sequence number within this process
+    pub seq: u64,
+    /// What happened to the record
+    pub kind: ChangeKind,
+    /// The record after the change (before it, for deletes)
+    pub record: ChangeRecord,
+    /// When the change was published
+    pub timestamp: Timestamp,
+}
+
+impl ChangeEvent {
+    /// Node type or edge type of the affected record
+    pub fn type_name(&self) -> &str {
+        match &self.record {
+            ChangeRecord::Node(node) => &node.node_type,
+            ChangeRecord::Edge(edge) => &edge.edge_type,
+        }
+    }
+
+    /// ID of the affected record
+    pub fn record_id(&self) -> String {
+        match &self.record {
+            ChangeRecord::Node(node) => node.id.to_string(),
+            ChangeRecord::Edge(edge) => edge.id.to_string(),
+        }
+    }
+}
+
+/// Broadcast channel of change events
+pub struct ChangeFeed {
+    sender: broadcast::Sender<ChangeEvent>,
+    seq: AtomicU64,
+}
+
+impl ChangeFeed {
+    /// Create an empty change feed
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANGE_FEED_CAPACITY);
+        Self {
+            sender,
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    /// Subscribe to all future changes
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Whether anyone is listening (lets writers skip extra lookups)
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publish a change; a no-op when there are no subscribers
+    pub fn publish(&self, kind: ChangeKind, record: ChangeRecord) {
+        if !self.has_subscribers() {
+            return;
+        }
+
+        let event = ChangeEvent {
+            seq: self.seq.fetch_add(1, Ordering::Relaxed) + 1,
+            kind,
+            record,
+            timestamp: Timestamp::now(),
+        };
+        // Receivers may drop between the check and the send; that's fine
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Value;
+
+    #[test]
+    fn test_publish_without_subscribers() {
+        let feed = ChangeFeed::new();
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(Node::new("user", Value::Null)));
+        assert!(!feed.has_subscribers());
+    }
+
+    #[tokio::test]
+    async fn test_publish_and_receive() {
+        let feed = ChangeFeed::new();
+        let mut rx = feed.subscribe();
+
+        let node = Node::new("user", Value::Null);
+        feed.publish(ChangeKind::Insert, ChangeRecord::Node(node.clone()));
+        feed.publish(ChangeKind::Delete, ChangeRecord::Node(node.clone()));
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.kind, ChangeKind::Insert);
+        assert_eq!(first.type_name(), "user");
+        assert_eq!(first.record_id(), node.id.to_string());
+
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second.seq, 2);
+        assert_eq!(second.kind, ChangeKind::Delete);
+    }
+}
@@ -0,0 +1,132 @@
+//! Node encoding for the nodes table
+//!
+//! Nodes are written as rkyv archives behind a short magic prefix, so scans
+//! can validate a row and read it in place instead of deserializing every
+//! property. Rows written before format version 2 are plain JSON; they are
+//! still decoded, and rewritten as archives the next time they are saved.
+
+use anyhow::{anyhow, Result};
+use rkyv::AlignedVec;
+
+use super::node::{ArchivedNode, Node};
+
+/// Prefix marking an archived row; JSON rows always start with `{`
+const MAGIC: &[u8; 8] = b"\0ARESNOD";
+
+/// Scratch space reserved for serializing a node
+const SCRATCH: usize = 1024;
+
+/// Encode a node for the nodes table
+pub fn encode_node(node: &Node) -> Result<Vec<u8>> {
+    let archive = rkyv::to_bytes::<_, SCRATCH>(node)
+        .map_err(|e| anyhow!("Failed to archive node {}: {}", node.id, e))?;
+    let mut bytes = Vec::with_capacity(MAGIC.len() + archive.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&archive);
+    Ok(bytes)
+}
+
+/// Decode a row of the nodes table, archived or legacy JSON
+pub fn decode_node(bytes: &[u8]) -> Result<Node> {
+    match bytes.strip_prefix(MAGIC.as_slice()) {
+        Some(_) => Ok(NodeReader::new().read(bytes)?.to_node()),
+        None => Ok(serde_json::from_slice(bytes)?),
+    }
+}
+
+/// Validated, allocation-free access to archived rows
+///
+/// redb makes no alignment promises for stored values, so each row is
+/// copied into a buffer owned by the reader before it is checked. The
+/// buffer is reused, which keeps a scan to one copy per row rather than a
+/// string and map allocation per property.
+pub struct NodeReader {
+    buffer: AlignedVec,
+}
+
+impl NodeReader {
+    /// Create a reader with an empty buffer
+    pub fn new() -> Self {
+        Self { buffer: AlignedVec::new() }
+    }
+
+    /// Validate a row and borrow it as an archived node
+    ///
+    /// Legacy JSON rows are archived into the buffer first, so callers see
+    /// the same view either way.
+    pub fn read(&mut self, bytes: &[u8]) -> Result<&ArchivedNode> {
+        match bytes.strip_prefix(MAGIC.as_slice()) {
+            Some(archive) => {
+                self.buffer.clear();
+                self.buffer.extend_from_slice(archive);
+            }
+            None => {
+                let node: Node = serde_json::from_slice(bytes)?;
+                self.buffer = rkyv::to_bytes::<_, SCRATCH>(&node)
+                    .map_err(|e| anyhow!("Failed to archive node {}: {}", node.id, e))?;
+            }
+        }
+        rkyv::check_archived_root::<Node>(&self.buffer)
+            .map_err(|e| anyhow!("Corrupt node archive: {}", e))
+    }
+}
+
+impl Default for NodeReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Value;
+
+    fn sample() -> Node {
+        Node::new("user", Value::from_json(serde_json::json!({
+            "name": "Alice",
+            "age": 30,
+            "tags": ["admin", "ops"],
+            "address": {"city": "Oslo"},
+            "scores": [1, 2, 3],
+        })).unwrap())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let node = sample();
+        let bytes = encode_node(&node).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+
+        let decoded = decode_node(&bytes).unwrap();
+        assert_eq!(decoded.id, node.id);
+        assert_eq!(decoded.properties, node.properties);
+        assert_eq!(decoded.created_at, node.created_at);
+
+        let mut reader = NodeReader::new();
+        let archived = reader.read(&bytes).unwrap();
+        assert_eq!(archived.node_id(), node.id);
+        assert_eq!(archived.node_type.as_str(), "user");
+        assert_eq!(archived.get("age").map(|v| v.to_value()), Some(Value::Int(30)));
+        assert!(archived.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_reads_legacy_json() {
+        let node = sample();
+        let json = serde_json::to_vec(&node).unwrap();
+
+        assert_eq!(decode_node(&json).unwrap().id, node.id);
+        let mut reader = NodeReader::new();
+        let archived = reader.read(&json).unwrap();
+        assert_eq!(archived.get("name").map(|v| v.to_value()), Some(Value::String("Alice".into())));
+    }
+
+    #[test]
+    fn test_rejects_corrupt_archive() {
+        let mut bytes = encode_node(&sample()).unwrap();
+        bytes.truncate(MAGIC.len() + 4);
+        assert!(decode_node(&bytes).is_err());
+        assert!(decode_node(b"{not json").is_err());
+    }
+}
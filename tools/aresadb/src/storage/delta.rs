@@ -0,0 +1,545 @@
+//! Delta sync layout
+//!
+//! Files pushed to a bucket are split into fixed-size segments stored
+//! under their content hash, and a manifest in the bucket lists each
+//! file's segments in order. A push or sync hashes the local files and
+//! moves only segments the other side doesn't already hold, so a small
+//! change to a large data file costs one segment rather than the file.
+//!
+//! After each sync the manifest is also saved locally, per bucket, as the
+//! base the next sync compares both sides against. The files of a database
+//! only make sense together, so a sync never mixes them: whichever side
+//! changed since the base replaces the other whole, and a database changed
+//! on both sides is a conflict, settled by a [`ConflictStrategy`].
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use super::{PendingFile, SyncPreview, SyncStats};
+
+/// Bytes per segment; the last segment of a file may be shorter
+pub const SEGMENT_BYTES: usize = 1 << 20;
+
+/// Manifest location, relative to the database root in the bucket
+pub const MANIFEST_PATH: &str = ".aresadb/sync/manifest.json";
+
+/// Directory holding segments, named by hash, relative to the database root
+pub const SEGMENT_DIR: &str = ".aresadb/sync/segments";
+
+/// Local directory of files partly downloaded, kept to resume from
+pub const PARTIAL_DIR: &str = ".aresadb/sync/partial";
+
+/// Local record of each bucket's manifest after the last sync with it
+pub const STATE_PATH: &str = ".aresadb/sync/state.json";
+
+/// Local record of the last push or sync to each destination
+pub const STATUS_PATH: &str = ".aresadb/sync/status.json";
+
+/// Every file the bucket holds and the segments it is made of
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncManifest {
+    /// Files by path relative to the database root
+    pub files: BTreeMap<String, FileManifest>,
+}
+
+impl SyncManifest {
+    /// Hashes of every segment the manifest refers to
+    pub fn segment_hashes(&self) -> HashSet<String> {
+        self.files.values().flat_map(|file| file.segments.iter().cloned()).collect()
+    }
+}
+
+/// One file's size, modification time and segment hashes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileManifest {
+    /// File length in bytes
+    pub size: u64,
+    /// When the file was last changed on the side that uploaded it
+    pub modified: DateTime<Utc>,
+    /// Segment hashes, in file order
+    pub segments: Vec<String>,
+}
+
+impl FileManifest {
+    /// Describe a file's contents
+    pub fn new(data: &[u8], modified: DateTime<Utc>) -> Self {
+        Self {
+            size: data.len() as u64,
+            modified,
+            segments: data.chunks(SEGMENT_BYTES).map(segment_hash).collect(),
+        }
+    }
+
+    /// Segment hashes with their lengths
+    fn segment_lens(&self) -> impl Iterator<Item = (&String, u64)> {
+        let size = self.size;
+        self.segments.iter().enumerate().map(move |(i, hash)| {
+            let start = (i * SEGMENT_BYTES) as u64;
+            (hash, (size - start).min(SEGMENT_BYTES as u64))
+        })
+    }
+}
+
+/// Hash naming a segment: SHA-256, since segments are addressed by
+/// content and two that collide would be taken for one another
+pub fn segment_hash(segment: &[u8]) -> String {
+    Sha256::digest(segment).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A file's segments by hash, so a download can reuse what is already local
+pub fn segments_by_hash(data: &[u8]) -> HashMap<String, &[u8]> {
+    data.chunks(SEGMENT_BYTES).map(|segment| (segment_hash(segment), segment)).collect()
+}
+
+/// How a sync settles a database changed both locally and in the bucket
+/// since the last sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Keep the local copy, replacing the bucket's
+    PreferLocal,
+    /// Keep the bucket's copy, replacing the local one
+    PreferRemote,
+    /// Keep whichever copy has the most recently modified file
+    #[serde(alias = "merge_by_timestamp")]
+    #[value(alias = "merge-by-timestamp")]
+    PreferNewest,
+    /// Change nothing and report the conflicting files
+    #[default]
+    Abort,
+}
+
+/// A file that differs between the two sides of a database changed on
+/// both since the last sync
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    /// Path relative to the database root
+    pub path: String,
+    /// Unset when the file exists only in the bucket
+    pub local_modified: Option<DateTime<Utc>>,
+    /// Unset when the file exists only locally
+    pub remote_modified: Option<DateTime<Utc>>,
+    /// Whether the local copy was kept; unset when the sync aborted
+    pub kept_local: Option<bool>,
+}
+
+/// Error returned when a sync aborts over conflicting changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflicts(pub Vec<SyncConflict>);
+
+impl std::fmt::Display for SyncConflicts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modified = |at: Option<DateTime<Utc>>| match at {
+            Some(at) => format!("modified {}", at.format("%Y-%m-%d %H:%M:%S")),
+            None => "missing".to_string(),
+        };
+        write!(
+            f,
+            "Sync aborted: the database changed both locally and in the bucket since the last sync; {} file(s) differ",
+            self.0.len()
+        )?;
+        for conflict in &self.0 {
+            write!(
+                f,
+                "\n  {} (local {}, remote {})",
+                conflict.path,
+                modified(conflict.local_modified),
+                modified(conflict.remote_modified)
+            )?;
+        }
+        write!(f, "\nChoose a conflict strategy to keep one side")
+    }
+}
+
+impl std::error::Error for SyncConflicts {}
+
+/// Which way each file goes in a sync
+///
+/// At most one direction is used: the side kept replaces the other whole.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncPlan {
+    pub uploads: Vec<String>,
+    pub downloads: Vec<String>,
+    /// Files the bucket holds but the kept local copy doesn't
+    pub remote_deletes: Vec<String>,
+    /// Local files the kept bucket copy doesn't have
+    pub local_deletes: Vec<String>,
+    /// Files that differ when both sides changed, and which side was kept
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Decide which way the database goes, comparing both sides with `base`
+///
+/// The files are taken as one unit, so a sync never leaves the data file
+/// from one side next to the config or WAL from the other. A database
+/// changed on one side only replaces the other. One changed on both, or
+/// differing with no base to compare against, is settled by `strategy`;
+/// under [`ConflictStrategy::Abort`] that fails the whole plan. An empty
+/// side, such as a bucket never pushed to, always takes the other.
+pub fn plan_sync(
+    local: &SyncManifest,
+    remote: &SyncManifest,
+    base: Option<&SyncManifest>,
+    strategy: ConflictStrategy,
+) -> Result<SyncPlan, SyncConflicts> {
+    let plan = plan_changes(local, remote, base, strategy);
+    if strategy == ConflictStrategy::Abort && !plan.conflicts.is_empty() {
+        return Err(SyncConflicts(plan.conflicts));
+    }
+    Ok(plan)
+}
+
+/// [`plan_sync`], listing conflicts in an otherwise empty plan rather
+/// than failing under [`ConflictStrategy::Abort`]
+fn plan_changes(
+    local: &SyncManifest,
+    remote: &SyncManifest,
+    base: Option<&SyncManifest>,
+    strategy: ConflictStrategy,
+) -> SyncPlan {
+    let mut plan = SyncPlan::default();
+    let differing: Vec<&String> = local.files.keys()
+        .chain(remote.files.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|path| !same_file(local.files.get(*path), remote.files.get(*path)))
+        .collect();
+    if differing.is_empty() {
+        return plan;
+    }
+
+    let local_changed = !local.files.is_empty() && base.is_none_or(|base| !same_contents(local, base));
+    let remote_changed = !remote.files.is_empty() && base.is_none_or(|base| !same_contents(remote, base));
+    let keep_local = match (local_changed, remote_changed) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => {
+            let keep_local = match strategy {
+                ConflictStrategy::PreferLocal => Some(true),
+                ConflictStrategy::PreferRemote => Some(false),
+                ConflictStrategy::PreferNewest => Some(last_modified(local) >= last_modified(remote)),
+                ConflictStrategy::Abort => None,
+            };
+            plan.conflicts = differing.iter()
+                .map(|path| SyncConflict {
+                    path: (*path).clone(),
+                    local_modified: local.files.get(*path).map(|file| file.modified),
+                    remote_modified: remote.files.get(*path).map(|file| file.modified),
+                    kept_local: keep_local,
+                })
+                .collect();
+            match keep_local {
+                Some(keep_local) => keep_local,
+                None => return plan,
+            }
+        }
+    };
+
+    for path in differing {
+        let (copy, delete) = match keep_local {
+            true => (&mut plan.uploads, &mut plan.remote_deletes),
+            false => (&mut plan.downloads, &mut plan.local_deletes),
+        };
+        let kept = if keep_local { local } else { remote };
+        match kept.files.contains_key(path) {
+            true => copy.push(path.clone()),
+            false => delete.push(path.clone()),
+        }
+    }
+    plan
+}
+
+fn same_file(a: Option<&FileManifest>, b: Option<&FileManifest>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.segments == b.segments,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Whether two manifests hold the same files with the same contents
+fn same_contents(a: &SyncManifest, b: &SyncManifest) -> bool {
+    a.files.len() == b.files.len()
+        && a.files.iter().all(|(path, file)| same_file(Some(file), b.files.get(path)))
+}
+
+/// When any file of a database was last changed
+fn last_modified(manifest: &SyncManifest) -> Option<DateTime<Utc>> {
+    manifest.files.values().map(|file| file.modified).max()
+}
+
+/// What a sync would move, and the segments it would leave unreferenced
+/// in the bucket, without touching either side
+///
+/// Under [`ConflictStrategy::Abort`], conflicts are listed rather than
+/// failing; the sync itself would then change nothing.
+pub fn preview_sync(
+    local: &SyncManifest,
+    remote: &SyncManifest,
+    base: Option<&SyncManifest>,
+    strategy: ConflictStrategy,
+    readonly: bool,
+) -> SyncPreview {
+    let mut plan = plan_changes(local, remote, base, strategy);
+    let mut preview = SyncPreview::default();
+    if strategy == ConflictStrategy::Abort && !plan.conflicts.is_empty() {
+        preview.conflicts = plan.conflicts;
+        return preview;
+    }
+    if readonly {
+        plan.uploads.clear();
+        plan.remote_deletes.clear();
+    }
+
+    for path in &plan.downloads {
+        let file = &remote.files[path];
+        let have: HashSet<&String> = local.files.get(path).map(|l| l.segments.iter().collect()).unwrap_or_default();
+        let bytes = file.segment_lens().filter(|(hash, _)| !have.contains(hash)).map(|(_, len)| len).sum();
+        preview.downloads.push(PendingFile { path: path.clone(), size: file.size, bytes });
+    }
+
+    let mut after = remote.clone();
+    for path in &plan.remote_deletes {
+        after.files.remove(path);
+    }
+    let mut known = remote.segment_hashes();
+    for path in &plan.uploads {
+        let file = &local.files[path];
+        let bytes = file.segment_lens().filter(|(hash, _)| known.insert((*hash).clone())).map(|(_, len)| len).sum();
+        preview.uploads.push(PendingFile { path: path.clone(), size: file.size, bytes });
+        after.files.insert(path.clone(), file.clone());
+    }
+
+    let live = after.segment_hashes();
+    let mut deleted = HashSet::new();
+    for (hash, len) in remote.files.values().flat_map(FileManifest::segment_lens) {
+        if !live.contains(hash) && deleted.insert(hash) {
+            preview.deleted_segments += 1;
+            preview.deleted_bytes += len;
+        }
+    }
+    preview.remote_deletes = plan.remote_deletes;
+    preview.local_deletes = plan.local_deletes;
+    preview.conflicts = plan.conflicts;
+    preview
+}
+
+/// The manifest recorded after the last sync with `url`, if any
+pub fn load_base(local_path: &Path, url: &str) -> Result<Option<SyncManifest>> {
+    let mut state = load_state(local_path)?;
+    Ok(state.remove(url))
+}
+
+/// Record the manifest a sync with `url` left both sides matching
+pub fn save_base(local_path: &Path, url: &str, manifest: &SyncManifest) -> Result<()> {
+    let mut state = load_state(local_path)?;
+    state.insert(url.to_string(), manifest.clone());
+    write_json(local_path, STATE_PATH, &state)
+}
+
+/// Outcome of the last push or sync to one destination
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DestinationStatus {
+    /// Bucket URL
+    pub url: String,
+    /// `push` or `sync`
+    pub operation: String,
+    /// When it finished
+    pub at: DateTime<Utc>,
+    /// Why it failed, if it did
+    pub error: Option<String>,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub bytes_transferred: u64,
+}
+
+impl DestinationStatus {
+    /// One-line report for status output
+    pub fn summary(&self) -> String {
+        let at = self.at.format("%Y-%m-%d %H:%M:%S");
+        match &self.error {
+            Some(error) => format!("{} failed at {}: {}", self.operation, at, error),
+            None => format!(
+                "{} ok at {}: {} uploaded, {} downloaded, {} transferred",
+                self.operation,
+                at,
+                self.uploaded,
+                self.downloaded,
+                humansize::format_size(self.bytes_transferred, humansize::BINARY)
+            ),
+        }
+    }
+}
+
+/// Record how a push or sync to `url` went
+pub fn record_status(local_path: &Path, url: &str, operation: &str, result: Result<&SyncStats, &anyhow::Error>) -> Result<()> {
+    let mut statuses = load_statuses(local_path)?;
+    let (stats, error) = match result {
+        Ok(stats) => (stats.clone(), None),
+        Err(e) => (SyncStats::default(), Some(format!("{:#}", e))),
+    };
+    statuses.retain(|status| status.url != url);
+    statuses.push(DestinationStatus {
+        url: url.to_string(),
+        operation: operation.to_string(),
+        at: Utc::now(),
+        error,
+        uploaded: stats.uploaded,
+        downloaded: stats.downloaded,
+        bytes_transferred: stats.bytes_transferred,
+    });
+    write_json(local_path, STATUS_PATH, &statuses)
+}
+
+/// The last recorded push or sync to each destination
+pub fn load_statuses(local_path: &Path) -> Result<Vec<DestinationStatus>> {
+    match std::fs::read(local_path.join(STATUS_PATH)) {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_json<T: Serialize>(local_path: &Path, relative: &str, value: &T) -> Result<()> {
+    let path = local_path.join(relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec(value)?)?;
+    Ok(())
+}
+
+fn load_state(local_path: &Path) -> Result<BTreeMap<String, SyncManifest>> {
+    match std::fs::read(local_path.join(STATE_PATH)) {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_segments() {
+        let mut data: Vec<u8> = (0..SEGMENT_BYTES * 3 + 10).map(|i| (i % 251) as u8).collect();
+        let before = FileManifest::new(&data, Utc::now());
+        assert_eq!(before.segments.len(), 4);
+
+        data[SEGMENT_BYTES + 1] ^= 0xff;
+        let after = FileManifest::new(&data, Utc::now());
+        let known = SyncManifest {
+            files: [("data".to_string(), before.clone())].into_iter().collect(),
+        }
+        .segment_hashes();
+
+        // Only the edited segment needs uploading
+        let new: Vec<usize> = (0..after.segments.len()).filter(|&i| !known.contains(&after.segments[i])).collect();
+        assert_eq!(new, vec![1]);
+
+        // And only it needs downloading to turn the new file back into the old
+        let local = segments_by_hash(&data);
+        let reused = before.segments.iter().filter(|hash| local.contains_key(*hash)).count();
+        assert_eq!(reused, 3);
+    }
+
+    #[test]
+    fn test_plan_sync_conflicts() {
+        let file = |bytes: &[u8], minute: u32| {
+            FileManifest::new(bytes, DateTime::parse_from_rfc3339(&format!("2024-01-01T00:{:02}:00Z", minute)).unwrap().into())
+        };
+        let manifest = |files: Vec<(&str, FileManifest)>| SyncManifest {
+            files: files.into_iter().map(|(path, file)| (path.to_string(), file)).collect(),
+        };
+
+        let base = manifest(vec![("a", file(b"a0", 0)), ("b", file(b"b0", 0)), ("c", file(b"c0", 0))]);
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        // Changed on one side only: that side replaces the other whole,
+        // dropping files it no longer has
+        let local = manifest(vec![("a", file(b"a1", 5)), ("b", file(b"b0", 0)), ("c", file(b"c0", 0)), ("d", file(b"d", 1))]);
+        let plan = plan_sync(&local, &base, Some(&base), ConflictStrategy::Abort).unwrap();
+        assert_eq!((plan.uploads, plan.downloads), (paths(&["a", "d"]), vec![]));
+        let remote = manifest(vec![("a", file(b"a0", 0)), ("b", file(b"b1", 4))]);
+        let plan = plan_sync(&base, &remote, Some(&base), ConflictStrategy::Abort).unwrap();
+        assert_eq!((plan.downloads, plan.local_deletes), (paths(&["b"]), paths(&["c"])));
+        assert!(plan.uploads.is_empty() && plan.conflicts.is_empty());
+
+        // Changed on both: never a mix of the two, even where the changes
+        // touch different files
+        let err = plan_sync(&local, &remote, Some(&base), ConflictStrategy::Abort).unwrap_err();
+        assert_eq!(err.0.iter().map(|c| c.path.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c", "d"]);
+        assert!(err.to_string().contains("\n  d (local modified 2024-01-01 00:01:00, remote missing)"));
+
+        let plan = plan_sync(&local, &remote, Some(&base), ConflictStrategy::PreferRemote).unwrap();
+        assert_eq!((plan.downloads, plan.local_deletes), (paths(&["a", "b"]), paths(&["c", "d"])));
+        assert!(plan.uploads.is_empty());
+        assert_eq!(plan.conflicts[0].kept_local, Some(false));
+
+        let plan = plan_sync(&local, &remote, Some(&base), ConflictStrategy::PreferLocal).unwrap();
+        assert_eq!((plan.uploads, plan.remote_deletes), (paths(&["a", "b", "c", "d"]), vec![]));
+        assert!(plan.downloads.is_empty());
+
+        // Newest goes by the latest change to any file: a at minute 5
+        let plan = plan_sync(&local, &remote, Some(&base), ConflictStrategy::PreferNewest).unwrap();
+        assert!(plan.downloads.is_empty() && plan.conflicts.iter().all(|c| c.kept_local == Some(true)));
+
+        // With no base, differing sides conflict, but an empty one doesn't
+        let plan = plan_sync(&local, &remote, None, ConflictStrategy::PreferLocal).unwrap();
+        assert_eq!(plan.conflicts.len(), 4);
+        let plan = plan_sync(&local, &SyncManifest::default(), None, ConflictStrategy::Abort).unwrap();
+        assert_eq!(plan.uploads.len(), 4);
+
+        // The old name of prefer-newest still parses
+        let strategy: ConflictStrategy = serde_json::from_str("\"merge_by_timestamp\"").unwrap();
+        assert_eq!(strategy, ConflictStrategy::PreferNewest);
+    }
+
+    #[test]
+    fn test_preview_sync() {
+        let old: Vec<u8> = (0..SEGMENT_BYTES + 100).map(|i| (i % 251) as u8).collect();
+        let mut new = old.clone();
+        new[SEGMENT_BYTES + 1] ^= 0xff;
+        let manifest = |files: Vec<(&str, &[u8])>| SyncManifest {
+            files: files.into_iter().map(|(path, data)| (path.to_string(), FileManifest::new(data, Utc::now()))).collect(),
+        };
+        let base = manifest(vec![("data", &old)]);
+        let local = manifest(vec![("data", &new), ("config", b"name")]);
+
+        let preview = preview_sync(&local, &base, Some(&base), ConflictStrategy::Abort, false);
+        assert!(preview.downloads.is_empty());
+        assert_eq!(preview.uploads.len(), 2);
+        // Only the changed tail segment of the data file goes up, and its
+        // old version is left unreferenced
+        assert_eq!(preview.uploads[1].path, "data");
+        assert_eq!(preview.uploads[1].bytes, 100);
+        assert_eq!((preview.deleted_segments, preview.deleted_bytes), (1, 100));
+        assert_eq!(preview.upload_bytes(), 104);
+
+        let preview = preview_sync(&local, &base, Some(&base), ConflictStrategy::Abort, true);
+        assert!(preview.uploads.is_empty() && preview.deleted_segments == 0);
+    }
+
+    #[test]
+    fn test_destination_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let stats = SyncStats { uploaded: 2, bytes_transferred: 2048, ..Default::default() };
+        record_status(dir.path(), "s3://primary/db", "push", Ok(&stats)).unwrap();
+        record_status(dir.path(), "gs://dr/db", "push", Err(&anyhow::anyhow!("access denied"))).unwrap();
+        record_status(dir.path(), "s3://primary/db", "sync", Ok(&SyncStats::default())).unwrap();
+
+        // One status per destination, the latest winning
+        let statuses = load_statuses(dir.path()).unwrap();
+        assert_eq!(statuses.len(), 2);
+        let dr = statuses.iter().find(|s| s.url == "gs://dr/db").unwrap();
+        assert_eq!(dr.error.as_deref(), Some("access denied"));
+        assert!(dr.summary().starts_with("push failed at"));
+        let primary = statuses.iter().find(|s| s.url == "s3://primary/db").unwrap();
+        assert_eq!((primary.operation.as_str(), primary.uploaded), ("sync", 0));
+    }
+}
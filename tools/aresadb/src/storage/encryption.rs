@@ -0,0 +1,187 @@
+//! Client-side encryption for bucket storage
+//!
+//! Envelope encryption: a random data key encrypts everything written to
+//! the bucket with AES-256-GCM, and the data key is itself stored in the
+//! bucket wrapped by a master key the user supplies through
+//! `ARESADB_BUCKET_KEY`. Without the master key, the bucket holds only
+//! ciphertext, and segment names are keyed hashes that reveal nothing
+//! about their contents.
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::to_hex;
+
+/// Environment variable holding the base64 master key
+pub const KEY_ENV: &str = "ARESADB_BUCKET_KEY";
+
+/// Wrapped data key location, relative to the database root in the bucket
+pub const KEY_PATH: &str = ".aresadb/sync/key.json";
+
+const KEY_LEN: usize = 32;
+
+/// The user's key, which wraps a bucket's data key
+pub struct MasterKey([u8; KEY_LEN]);
+
+impl MasterKey {
+    /// Parse a base64-encoded 32-byte key
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| anyhow!("{} is not valid base64: {}", KEY_ENV, e))?;
+        let key: [u8; KEY_LEN] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| anyhow!("{} must be {} bytes, not {}", KEY_ENV, KEY_LEN, bytes.len()))?;
+        Ok(Self(key))
+    }
+
+    /// The key from `ARESADB_BUCKET_KEY`, if set
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var(KEY_ENV) {
+            Ok(encoded) if !encoded.trim().is_empty() => Self::from_base64(&encoded).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Short fingerprint, recorded with a wrapped key to tell a wrong
+    /// master key from corrupt data
+    fn id(&self) -> String {
+        to_hex(&Sha256::digest(self.0)[..8])
+    }
+
+    fn cipher(&self) -> Result<LessSafeKey> {
+        aead_key(&self.0)
+    }
+}
+
+/// A data key as stored in the bucket, encrypted by a master key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrappedKey {
+    /// Fingerprint of the master key that wrapped it
+    pub master_key_id: String,
+    /// Base64 of the nonce followed by the encrypted key
+    pub wrapped: String,
+}
+
+/// The key a bucket's objects are encrypted with
+pub struct DataKey {
+    raw: [u8; KEY_LEN],
+    cipher: LessSafeKey,
+    names: hmac::Key,
+}
+
+impl DataKey {
+    /// A new random data key
+    pub fn generate() -> Result<Self> {
+        let mut raw = [0u8; KEY_LEN];
+        SystemRandom::new().fill(&mut raw).map_err(|_| anyhow!("Failed to generate a data key"))?;
+        Self::from_raw(raw)
+    }
+
+    fn from_raw(raw: [u8; KEY_LEN]) -> Result<Self> {
+        Ok(Self {
+            raw,
+            cipher: aead_key(&raw)?,
+            names: hmac::Key::new(hmac::HMAC_SHA256, &raw),
+        })
+    }
+
+    /// Encrypt the key for storage under `master`
+    pub fn wrap(&self, master: &MasterKey) -> Result<WrappedKey> {
+        let sealed = seal(&master.cipher()?, &self.raw, "data key")?;
+        Ok(WrappedKey {
+            master_key_id: master.id(),
+            wrapped: base64::engine::general_purpose::STANDARD.encode(sealed),
+        })
+    }
+
+    /// Recover a data key wrapped under `master`
+    pub fn unwrap(wrapped: &WrappedKey, master: &MasterKey) -> Result<Self> {
+        if wrapped.master_key_id != master.id() {
+            bail!("{} is not the key this bucket was encrypted with", KEY_ENV);
+        }
+        let sealed = base64::engine::general_purpose::STANDARD.decode(&wrapped.wrapped)?;
+        let raw: [u8; KEY_LEN] = open(&master.cipher()?, &sealed, "data key")?
+            .try_into()
+            .map_err(|_| anyhow!("Bucket data key is corrupt"))?;
+        Self::from_raw(raw)
+    }
+
+    /// Encrypt an object; `context` names it, so that one object's
+    /// ciphertext can't be passed off as another's
+    pub fn encrypt(&self, plaintext: &[u8], context: &str) -> Result<Vec<u8>> {
+        seal(&self.cipher, plaintext, context)
+    }
+
+    /// Decrypt an object written with the same `context`
+    pub fn decrypt(&self, data: &[u8], context: &str) -> Result<Vec<u8>> {
+        open(&self.cipher, data, context)
+    }
+
+    /// Object name for a segment, keyed so it reveals nothing about the
+    /// segment's contents
+    pub fn segment_name(&self, hash: &str) -> String {
+        to_hex(hmac::sign(&self.names, hash.as_bytes()).as_ref())
+    }
+}
+
+fn aead_key(raw: &[u8; KEY_LEN]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, raw).map_err(|_| anyhow!("Invalid encryption key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypt to the nonce followed by the ciphertext and tag
+fn seal(key: &LessSafeKey, plaintext: &[u8], context: &str) -> Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| anyhow!("Failed to generate a nonce"))?;
+    let mut sealed = plaintext.to_vec();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(context.as_bytes()), &mut sealed)
+        .map_err(|_| anyhow!("Failed to encrypt {}", context))?;
+
+    let mut data = Vec::with_capacity(NONCE_LEN + sealed.len());
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&sealed);
+    Ok(data)
+}
+
+fn open(key: &LessSafeKey, data: &[u8], context: &str) -> Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        bail!("Encrypted {} is truncated", context);
+    }
+    let (nonce, sealed) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Encrypted {} is truncated", context))?;
+    let mut buffer = sealed.to_vec();
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(context.as_bytes()), &mut buffer)
+        .map_err(|_| anyhow!("Failed to decrypt {}: wrong key or corrupt data", context))?;
+    Ok(plaintext.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_encryption() {
+        let master = MasterKey::from_base64(&base64::engine::general_purpose::STANDARD.encode([7u8; 32])).unwrap();
+        let key = DataKey::generate().unwrap();
+        let wrapped = key.wrap(&master).unwrap();
+
+        let sealed = key.encrypt(b"segment bytes", "abc").unwrap();
+        assert!(!sealed.windows(7).any(|w| w == b"segment"));
+
+        let unwrapped = DataKey::unwrap(&wrapped, &master).unwrap();
+        assert_eq!(unwrapped.decrypt(&sealed, "abc").unwrap(), b"segment bytes");
+        assert!(unwrapped.decrypt(&sealed, "abd").is_err());
+        assert_eq!(unwrapped.segment_name("abc"), key.segment_name("abc"));
+
+        let other = MasterKey::from_base64(&base64::engine::general_purpose::STANDARD.encode([8u8; 32])).unwrap();
+        assert!(DataKey::unwrap(&wrapped, &other).is_err());
+        assert!(MasterKey::from_base64("c2hvcnQ=").is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Property and full-text indexes
+//!
+//! Map the values (or the words) of one field of a node type to the nodes
+//! holding them, so equality lookups and keyword searches avoid scanning
+//! every node of the type. Like vector indexes, they are declared in the
+//! database config and built in memory from the stored nodes.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::progress::{Progress, ProgressUnit};
+use super::{Node, NodeId, Value};
+
+/// What a field index maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldIndexKind {
+    /// Whole values, for equality lookups
+    Property,
+    /// Lowercased words of string values, for keyword search
+    FullText,
+}
+
+impl FieldIndexKind {
+    /// Get the name of this kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Property => "property",
+            Self::FullText => "fulltext",
+        }
+    }
+}
+
+/// An index declared on one field of a node type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldIndexSpec {
+    /// Node type whose nodes are indexed
+    pub node_type: String,
+    /// Indexed field
+    pub field: String,
+    /// What the index maps
+    pub kind: FieldIndexKind,
+}
+
+/// Size of a built field index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldIndexStats {
+    /// Distinct values or words
+    pub keys: usize,
+    /// Nodes with at least one key
+    pub nodes: usize,
+    /// Approximate memory held by the index
+    pub size_bytes: usize,
+}
+
+/// Split text into lowercase words
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A built field index
+pub struct FieldIndex {
+    kind: FieldIndexKind,
+    field: String,
+    /// Nodes by key
+    entries: RwLock<BTreeMap<String, HashSet<NodeId>>>,
+    /// Keys by node, to unindex a node without its old value
+    keys: RwLock<HashMap<NodeId, Vec<String>>>,
+}
+
+impl FieldIndex {
+    /// Create an empty index on `field`
+    pub fn new(field: &str, kind: FieldIndexKind) -> Self {
+        Self {
+            kind,
+            field: field.to_string(),
+            entries: RwLock::new(BTreeMap::new()),
+            keys: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Build an index over nodes, reporting progress every thousand nodes
+    pub fn build<F>(field: &str, kind: FieldIndexKind, nodes: &[Node], mut on_progress: F) -> Self
+    where
+        F: FnMut(Progress),
+    {
+        let index = Self::new(field, kind);
+        let total = Some(nodes.len() as u64);
+        on_progress(Progress::new("index", ProgressUnit::Nodes, 0, total));
+        for (n, node) in nodes.iter().enumerate() {
+            index.insert(node);
+            if (n + 1) % 1000 == 0 {
+                on_progress(Progress::new("index", ProgressUnit::Nodes, n as u64 + 1, total));
+            }
+        }
+        on_progress(Progress::new("index", ProgressUnit::Nodes, nodes.len() as u64, total));
+        index
+    }
+
+    /// What the index maps
+    pub fn kind(&self) -> FieldIndexKind {
+        self.kind
+    }
+
+    /// Keys of a value: the value itself, or its words
+    fn keys_of(&self, value: &Value) -> Vec<String> {
+        match (self.kind, value) {
+            (_, Value::Null) => Vec::new(),
+            (FieldIndexKind::Property, value) => vec![Self::value_key(value)],
+            (FieldIndexKind::FullText, Value::String(text)) => {
+                let mut words = tokenize(text);
+                words.sort();
+                words.dedup();
+                words
+            }
+            (FieldIndexKind::FullText, Value::Array(items)) => {
+                let mut words: Vec<String> = items.iter().flat_map(|item| self.keys_of(item)).collect();
+                words.sort();
+                words.dedup();
+                words
+            }
+            (FieldIndexKind::FullText, _) => Vec::new(),
+        }
+    }
+
+    /// Key of a whole value; values that compare equal share a key
+    fn value_key(value: &Value) -> String {
+        serde_json::to_string(value).unwrap_or_default()
+    }
+
+    /// Index a node's current value, replacing any earlier one
+    pub fn insert(&self, node: &Node) {
+        self.remove(&node.id);
+        let keys = node.properties.get(&self.field).map(|v| self.keys_of(v)).unwrap_or_default();
+        if keys.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.write();
+        for key in &keys {
+            entries.entry(key.clone()).or_default().insert(node.id.clone());
+        }
+        self.keys.write().insert(node.id.clone(), keys);
+    }
+
+    /// Remove a node, returning whether it was indexed
+    pub fn remove(&self, id: &NodeId) -> bool {
+        let Some(keys) = self.keys.write().remove(id) else {
+            return false;
+        };
+        let mut entries = self.entries.write();
+        for key in keys {
+            if let Some(ids) = entries.get_mut(&key) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    entries.remove(&key);
+                }
+            }
+        }
+        true
+    }
+
+    /// Nodes whose value equals `value`
+    pub fn lookup(&self, value: &Value) -> Vec<NodeId> {
+        self.entries
+            .read()
+            .get(&Self::value_key(value))
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Nodes whose text contains every word of `query`
+    pub fn search(&self, query: &str) -> Vec<NodeId> {
+        let words = tokenize(query);
+        let entries = self.entries.read();
+        let mut matches: Option<HashSet<NodeId>> = None;
+        for word in &words {
+            let Some(ids) = entries.get(word) else {
+                return Vec::new();
+            };
+            matches = Some(match matches {
+                Some(found) => found.intersection(ids).cloned().collect(),
+                None => ids.clone(),
+            });
+        }
+        matches.map(|ids| ids.into_iter().collect()).unwrap_or_default()
+    }
+
+    /// Release capacity left behind by removed nodes and keys
+    pub fn compact(&self) {
+        let mut entries = self.entries.write();
+        for ids in entries.values_mut() {
+            ids.shrink_to_fit();
+        }
+        self.keys.write().shrink_to_fit();
+    }
+
+    /// Size of the index
+    pub fn stats(&self) -> FieldIndexStats {
+        let entries = self.entries.read();
+        let id_size = std::mem::size_of::<NodeId>();
+        let size_bytes = entries.iter().map(|(key, ids)| key.len() + ids.len() * id_size).sum::<usize>()
+            + self.keys.read().values().map(|keys| id_size + keys.iter().map(String::len).sum::<usize>()).sum::<usize>();
+        FieldIndexStats {
+            keys: entries.len(),
+            nodes: self.keys.read().len(),
+            size_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(field: &str, value: Value) -> Node {
+        Node::with_id(NodeId::new(), "doc", BTreeMap::from([(field.to_string(), value)]))
+    }
+
+    #[test]
+    fn test_property_index() {
+        let a = node("email", Value::String("a@example.com".into()));
+        let b = node("email", Value::String("b@example.com".into()));
+        let index = FieldIndex::build("email", FieldIndexKind::Property, &[a.clone(), b.clone()], |_| {});
+
+        assert_eq!(index.lookup(&Value::String("a@example.com".into())), vec![a.id.clone()]);
+        assert_eq!(index.stats().keys, 2);
+
+        let moved = Node::with_id(a.id.clone(), "doc", BTreeMap::from([("email".to_string(), Value::String("b@example.com".into()))]));
+        index.insert(&moved);
+        assert!(index.lookup(&Value::String("a@example.com".into())).is_empty());
+        assert_eq!(index.lookup(&Value::String("b@example.com".into())).len(), 2);
+
+        assert!(index.remove(&b.id));
+        assert_eq!(index.stats(), FieldIndexStats { keys: 1, nodes: 1, size_bytes: index.stats().size_bytes });
+    }
+
+    #[test]
+    fn test_fulltext_index() {
+        let a = node("body", Value::String("Graph databases, fast graphs".into()));
+        let b = node("body", Value::String("Fast vector search".into()));
+        let index = FieldIndex::build("body", FieldIndexKind::FullText, &[a.clone(), b.clone()], |_| {});
+
+        assert_eq!(index.search("GRAPH"), vec![a.id.clone()]);
+        assert_eq!(index.search("fast").len(), 2);
+        assert_eq!(index.search("fast search"), vec![b.id.clone()]);
+        assert!(index.search("fast missing").is_empty());
+    }
+}
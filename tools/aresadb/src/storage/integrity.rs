@@ -0,0 +1,378 @@
+//! Integrity checking
+//!
+//! Walks every table of a database and checks that records decode, edges
+//! point at nodes that exist, the type and adjacency indexes list exactly
+//! the records they should, and embeddings have the dimension fixed for
+//! their field. A write-ahead log can be checked alongside.
+//!
+//! With repair, undecodable records and dangling edges are removed, index
+//! entries are added or dropped to match the data, and a damaged WAL is
+//! cut back to its last intact entry. Wrong-sized vectors are only
+//! reported, since there is no right value to put back.
+
+use anyhow::{Context, Result};
+use redb::{MultimapTableDefinition, ReadTransaction, ReadableMultimapTable, ReadableTable};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::auth::to_hex;
+use crate::distributed::WriteAheadLog;
+use super::codec::decode_node;
+use super::local::{EDGES_TABLE, EDGE_FROM_INDEX, EDGE_TO_INDEX, EDGE_TYPE_INDEX, NODES_TABLE, NODE_TYPE_INDEX};
+use super::{Database, Edge, Node, Value};
+
+/// Kind of problem found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// A node or edge that does not decode
+    CorruptRecord,
+    /// An edge to or from a missing node
+    DanglingEdge,
+    /// An index missing a record, or listing one that isn't there
+    IndexMismatch,
+    /// An embedding of the wrong dimension
+    VectorDimension,
+    /// A write-ahead log that cannot be fully replayed
+    Wal,
+}
+
+/// A problem found by a check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    /// What kind of problem
+    pub kind: IssueKind,
+    /// Description naming the records involved
+    pub message: String,
+    /// Whether repair can fix it
+    pub repairable: bool,
+}
+
+/// Result of an integrity check
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Nodes checked
+    pub nodes: u64,
+    /// Edges checked
+    pub edges: u64,
+    /// Intact WAL entries, when a WAL was checked
+    pub wal_entries: Option<u64>,
+    /// Problems found
+    pub issues: Vec<IntegrityIssue>,
+    /// Whether repairable issues were fixed
+    pub repaired: bool,
+}
+
+impl IntegrityReport {
+    /// Whether nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn issue(&mut self, kind: IssueKind, repairable: bool, message: String) {
+        self.issues.push(IntegrityIssue { kind, message, repairable });
+    }
+}
+
+/// Index entries as (key, record ID) pairs; string keys as bytes
+type Entries = HashSet<(Vec<u8>, Vec<u8>)>;
+
+fn read_str_index(txn: &ReadTransaction, index: MultimapTableDefinition<&str, &[u8]>) -> Result<Entries> {
+    let mut entries = HashSet::new();
+    for result in txn.open_multimap_table(index)?.iter()? {
+        let (key, ids) = result?;
+        for id in ids {
+            entries.insert((key.value().as_bytes().to_vec(), id?.value().to_vec()));
+        }
+    }
+    Ok(entries)
+}
+
+fn read_bytes_index(txn: &ReadTransaction, index: MultimapTableDefinition<&[u8], &[u8]>) -> Result<Entries> {
+    let mut entries = HashSet::new();
+    for result in txn.open_multimap_table(index)?.iter()? {
+        let (key, ids) = result?;
+        for id in ids {
+            entries.insert((key.value().to_vec(), id?.value().to_vec()));
+        }
+    }
+    Ok(entries)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    match uuid::Uuid::from_slice(bytes) {
+        Ok(id) => id.to_string(),
+        Err(_) => to_hex(bytes),
+    }
+}
+
+/// An index's expected and actual entries
+struct IndexCheck {
+    name: &'static str,
+    string_keys: bool,
+    expected: Entries,
+    actual: Entries,
+}
+
+impl IndexCheck {
+    fn missing(&self) -> impl Iterator<Item = &(Vec<u8>, Vec<u8>)> {
+        self.expected.difference(&self.actual)
+    }
+
+    fn stale(&self) -> impl Iterator<Item = &(Vec<u8>, Vec<u8>)> {
+        self.actual.difference(&self.expected)
+    }
+
+    fn key(&self, key: &[u8]) -> String {
+        if self.string_keys { String::from_utf8_lossy(key).to_string() } else { hex(key) }
+    }
+}
+
+/// Check a database, and optionally a write-ahead log, for inconsistencies
+///
+/// With `repair`, fixes what can be fixed in one write transaction, so a
+/// failed repair leaves the database as it was.
+pub async fn check_integrity(db: &Database, wal: Option<&Path>, repair: bool) -> Result<IntegrityReport> {
+    let mut report = IntegrityReport::default();
+    let dimensions: HashMap<(String, String), usize> = db.config.read().vector_dimensions.iter()
+        .map(|d| ((d.node_type.clone(), d.field.clone()), d.dimension))
+        .collect();
+
+    let redb = db.local.db.read();
+    let read_txn = redb.begin_read()?;
+
+    // Nodes
+    let mut corrupt_nodes = Vec::new();
+    let mut node_ids = HashSet::new();
+    let mut node_type_index = HashSet::new();
+    for result in read_txn.open_table(NODES_TABLE)?.iter()? {
+        let (key, data) = result?;
+        let key = key.value().to_vec();
+        report.nodes += 1;
+        let node: Node = match decode_node(data.value()) {
+            Ok(node) => node,
+            Err(e) => {
+                report.issue(IssueKind::CorruptRecord, true, format!("Node {} does not decode: {}", hex(&key), e));
+                corrupt_nodes.push(key);
+                continue;
+            }
+        };
+        if node.id.uuid.as_slice() != key.as_slice() {
+            report.issue(IssueKind::CorruptRecord, true, format!("Node {} is stored under key {}", node.id, hex(&key)));
+            corrupt_nodes.push(key);
+            continue;
+        }
+        for (field, value) in &node.properties {
+            let (Value::Vector(vector), Some(&dimension)) =
+                (value, dimensions.get(&(node.node_type.clone(), field.clone())))
+            else {
+                continue;
+            };
+            if vector.len() != dimension {
+                report.issue(IssueKind::VectorDimension, false, format!(
+                    "Node {} has a {}-dimensional {}.{}, expected {}",
+                    node.id, vector.len(), node.node_type, field, dimension
+                ));
+            }
+        }
+        node_type_index.insert((node.node_type.as_bytes().to_vec(), key.clone()));
+        node_ids.insert(key);
+    }
+
+    // Edges
+    let mut corrupt_edges = Vec::new();
+    let mut dangling = Vec::new();
+    let (mut from_index, mut to_index, mut edge_type_index) = (HashSet::new(), HashSet::new(), HashSet::new());
+    for result in read_txn.open_table(EDGES_TABLE)?.iter()? {
+        let (key, data) = result?;
+        let key = key.value().to_vec();
+        report.edges += 1;
+        let edge: Edge = match serde_json::from_slice::<Edge>(data.value()) {
+            Ok(edge) if edge.id.uuid.as_slice() == key.as_slice() => edge,
+            Ok(edge) => {
+                report.issue(IssueKind::CorruptRecord, true, format!("Edge {} is stored under key {}", edge.id, hex(&key)));
+                corrupt_edges.push(key);
+                continue;
+            }
+            Err(e) => {
+                report.issue(IssueKind::CorruptRecord, true, format!("Edge {} does not decode: {}", hex(&key), e));
+                corrupt_edges.push(key);
+                continue;
+            }
+        };
+        let missing: Vec<String> = [&edge.from, &edge.to].iter()
+            .filter(|id| !node_ids.contains(id.uuid.as_slice()))
+            .map(|id| id.to_string())
+            .collect();
+        if !missing.is_empty() {
+            report.issue(IssueKind::DanglingEdge, true, format!(
+                "Edge {} ({}) points at missing node {}", edge.id, edge.edge_type, missing.join(", ")
+            ));
+            dangling.push(edge);
+            continue;
+        }
+        from_index.insert((edge.from.uuid.to_vec(), key.clone()));
+        to_index.insert((edge.to.uuid.to_vec(), key.clone()));
+        edge_type_index.insert((edge.edge_type.as_bytes().to_vec(), key));
+    }
+
+    // Indexes, against the records that will survive repair
+    let indexes = [
+        IndexCheck { name: "node type", string_keys: true, expected: node_type_index, actual: read_str_index(&read_txn, NODE_TYPE_INDEX)? },
+        IndexCheck { name: "edge from", string_keys: false, expected: from_index, actual: read_bytes_index(&read_txn, EDGE_FROM_INDEX)? },
+        IndexCheck { name: "edge to", string_keys: false, expected: to_index, actual: read_bytes_index(&read_txn, EDGE_TO_INDEX)? },
+        IndexCheck { name: "edge type", string_keys: true, expected: edge_type_index, actual: read_str_index(&read_txn, EDGE_TYPE_INDEX)? },
+    ];
+    for index in &indexes {
+        for (key, id) in index.missing() {
+            report.issue(IssueKind::IndexMismatch, true, format!(
+                "{} index is missing {} under {}", index.name, hex(id), index.key(key)
+            ));
+        }
+        for (key, id) in index.stale() {
+            // Entries of records removed for other reasons aren't news
+            if corrupt_nodes.contains(id) || corrupt_edges.contains(id) || dangling.iter().any(|e| e.id.uuid.as_slice() == id.as_slice()) {
+                continue;
+            }
+            report.issue(IssueKind::IndexMismatch, true, format!(
+                "{} index lists {} under {}, which isn't there", index.name, hex(id), index.key(key)
+            ));
+        }
+    }
+    drop(read_txn);
+
+    if repair && report.issues.iter().any(|i| i.repairable && i.kind != IssueKind::Wal) {
+        let write_txn = redb.begin_write()?;
+        {
+            let mut nodes = write_txn.open_table(NODES_TABLE)?;
+            for key in &corrupt_nodes {
+                nodes.remove(key.as_slice())?;
+            }
+            let mut edges = write_txn.open_table(EDGES_TABLE)?;
+            for key in &corrupt_edges {
+                edges.remove(key.as_slice())?;
+            }
+            for edge in &dangling {
+                edges.remove(edge.id.uuid.as_slice())?;
+            }
+
+            for index in &indexes {
+                if index.string_keys {
+                    let definition = if index.name == "node type" { NODE_TYPE_INDEX } else { EDGE_TYPE_INDEX };
+                    let mut table = write_txn.open_multimap_table(definition)?;
+                    for (key, id) in index.stale() {
+                        table.remove(String::from_utf8_lossy(key).as_ref(), id.as_slice())?;
+                    }
+                    for (key, id) in index.missing() {
+                        table.insert(String::from_utf8_lossy(key).as_ref(), id.as_slice())?;
+                    }
+                } else {
+                    let definition = if index.name == "edge from" { EDGE_FROM_INDEX } else { EDGE_TO_INDEX };
+                    let mut table = write_txn.open_multimap_table(definition)?;
+                    for (key, id) in index.stale() {
+                        table.remove(key.as_slice(), id.as_slice())?;
+                    }
+                    for (key, id) in index.missing() {
+                        table.insert(key.as_slice(), id.as_slice())?;
+                    }
+                }
+            }
+        }
+        write_txn.commit()?;
+        report.repaired = true;
+    }
+    drop(redb);
+
+    if let Some(path) = wal {
+        let scan = WriteAheadLog::scan(path)?;
+        report.wal_entries = Some(scan.entries);
+        let truncated = scan.valid_bytes < scan.total_bytes;
+        for problem in scan.problems {
+            report.issue(IssueKind::Wal, truncated, format!("{}: {}", path.display(), problem));
+        }
+        if repair && truncated {
+            let file = std::fs::OpenOptions::new().write(true).open(path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            file.set_len(scan.valid_bytes)?;
+            file.sync_all()?;
+            report.repaired = true;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distributed::WalEntryType;
+
+    #[tokio::test]
+    async fn test_check_and_repair() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let alice = db.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        let bob = db.insert_node("user", serde_json::json!({"name": "Bob"})).await.unwrap();
+        db.insert_with_embedding("chunk", serde_json::json!({"content": "hi"}), "embedding", vec![1.0, 0.0]).await.unwrap();
+        db.create_edge(&alice.id.to_string(), &bob.id.to_string(), "follows", None).await.unwrap();
+        let report = check_integrity(&db, None, false).await.unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!((report.nodes, report.edges), (3, 1));
+
+        // Break things behind the storage layer's back
+        {
+            let redb = db.local.db.read();
+            let txn = redb.begin_write().unwrap();
+            {
+                txn.open_table(NODES_TABLE).unwrap().remove(bob.id.uuid.as_slice()).unwrap();
+                txn.open_table(NODES_TABLE).unwrap().insert([7u8; 16].as_slice(), b"{not json".as_slice()).unwrap();
+                txn.open_multimap_table(NODE_TYPE_INDEX).unwrap().remove("user", alice.id.uuid.as_slice()).unwrap();
+            }
+            txn.commit().unwrap();
+        }
+        let chunk = db.get_all_by_type("chunk", None).await.unwrap().remove(0);
+        let wide = Value::Object([("embedding".to_string(), Value::Vector(vec![1.0, 0.0, 0.0]))].into());
+        db.local().update_node(&chunk.id, wide).await.unwrap();
+
+        let report = check_integrity(&db, None, false).await.unwrap();
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+        assert!(kinds.contains(&IssueKind::CorruptRecord));
+        assert!(kinds.contains(&IssueKind::DanglingEdge));
+        assert!(kinds.contains(&IssueKind::VectorDimension));
+        assert_eq!(kinds.iter().filter(|k| **k == IssueKind::IndexMismatch).count(), 2);
+
+        let report = check_integrity(&db, None, true).await.unwrap();
+        assert!(report.repaired);
+        let report = check_integrity(&db, None, false).await.unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::VectorDimension);
+        assert_eq!(db.get_all_by_type("user", None).await.unwrap().len(), 1);
+        assert!(db.local().get_edges_by_type("follows", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_wal() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path().join("db"), "test").await.unwrap();
+        let path = temp.path().join("db.wal");
+        {
+            let wal = WriteAheadLog::open(&path).unwrap();
+            wal.log_insert_node(&Node::new("user", Value::Null)).unwrap();
+            wal.append(WalEntryType::DeleteNode, vec![1, 2]).unwrap();
+        }
+        let report = check_integrity(&db, Some(&path), false).await.unwrap();
+        assert_eq!(report.wal_entries, Some(2));
+        assert_eq!(report.issues.len(), 1);
+        assert!(!report.issues[0].repairable);
+
+        // A torn write at the end is cut off by repair
+        let intact = std::fs::metadata(&path).unwrap().len();
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&[9, 0, 0, 0, 1]);
+        std::fs::write(&path, data).unwrap();
+        let report = check_integrity(&db, Some(&path), true).await.unwrap();
+        assert!(report.repaired && report.issues.iter().any(|i| i.repairable));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), intact);
+    }
+}
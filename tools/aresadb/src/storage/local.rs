@@ -2,23 +2,73 @@
 //!
 //! Provides ACID-compliant persistent storage with B+ tree indexes.
 
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use parking_lot::RwLock;
-use redb::{Database as RedbDatabase, TableDefinition, ReadableTable, ReadableMultimapTable, MultimapTableDefinition, ReadableTableMetadata};
+use rayon::prelude::*;
+use redb::{Database as RedbDatabase, ReadTransaction, WriteTransaction, TableDefinition, ReadableTable, ReadableMultimapTable, MultimapTableDefinition, ReadableTableMetadata};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use super::node::{Node, Edge, NodeId, EdgeId, Value, Timestamp};
+use super::batch::{BatchedWrite, WriteBatcher};
+use super::codec::{decode_node, encode_node, NodeReader};
+use super::node::{ArchivedNode, Node, Edge, NodeId, EdgeId, Value, Timestamp};
 
 // Table definitions for redb
-const NODES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("nodes");
-const EDGES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("edges");
-const NODE_TYPE_INDEX: MultimapTableDefinition<&str, &[u8]> = MultimapTableDefinition::new("node_type_index");
-const EDGE_FROM_INDEX: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("edge_from_index");
-const EDGE_TO_INDEX: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("edge_to_index");
-const EDGE_TYPE_INDEX: MultimapTableDefinition<&str, &[u8]> = MultimapTableDefinition::new("edge_type_index");
+pub(super) const NODES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("nodes");
+pub(super) const EDGES_TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("edges");
+pub(super) const NODE_TYPE_INDEX: MultimapTableDefinition<&str, &[u8]> = MultimapTableDefinition::new("node_type_index");
+pub(super) const EDGE_FROM_INDEX: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("edge_from_index");
+pub(super) const EDGE_TO_INDEX: MultimapTableDefinition<&[u8], &[u8]> = MultimapTableDefinition::new("edge_to_index");
+pub(super) const EDGE_TYPE_INDEX: MultimapTableDefinition<&str, &[u8]> = MultimapTableDefinition::new("edge_type_index");
 const METADATA_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("metadata");
 
+/// Visit the archived nodes of a type as seen by one read transaction
+pub(super) fn scan_archived_in<F>(read_txn: &ReadTransaction, node_type: &str, mut visit: F) -> Result<()>
+where
+    F: FnMut(&ArchivedNode) -> Result<bool>,
+{
+    let type_index = read_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+    let nodes_table = read_txn.open_table(NODES_TABLE)?;
+    let mut reader = NodeReader::new();
+
+    for result in type_index.get(node_type)? {
+        let id_bytes = result?.value().to_vec();
+        if let Some(data) = nodes_table.get(id_bytes.as_slice())? {
+            if !visit(reader.read(data.value())?)? {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Refuse a data file written by a newer format than this build reads
+///
+/// Archived rows are laid out for a specific format version, so reading a
+/// newer file could misinterpret them. Files without a recorded version
+/// (new or pre-versioning) are accepted.
+fn check_format_version(db: &RedbDatabase) -> Result<()> {
+    let read_txn = db.begin_read()?;
+    let meta_table = match read_txn.open_table(METADATA_TABLE) {
+        Ok(table) => table,
+        Err(redb::TableError::TableDoesNotExist(_)) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(bytes) = meta_table.get("version")? else {
+        return Ok(());
+    };
+
+    let version: u32 = serde_json::from_slice(bytes.value()).context("Invalid format version")?;
+    if version > crate::FORMAT_VERSION {
+        bail!(
+            "Database format version {} is newer than this build supports ({}); upgrade AresaDB to open it",
+            version, crate::FORMAT_VERSION,
+        );
+    }
+    Ok(())
+}
+
 /// Storage statistics
 #[derive(Debug, Clone, Default)]
 pub struct StorageStats {
@@ -26,6 +76,10 @@ pub struct StorageStats {
     pub edge_count: u64,
     pub schema_count: u64,
     pub size_bytes: u64,
+    /// Group commits made for inserts since the storage was opened
+    pub write_batches: u64,
+    /// Inserts committed by those group commits
+    pub batched_writes: u64,
 }
 
 /// Local storage backend using redb
@@ -33,7 +87,9 @@ pub struct LocalStorage {
     /// Path to the database directory
     path: PathBuf,
     /// redb database handle
-    db: Arc<RwLock<RedbDatabase>>,
+    pub(super) db: Arc<RwLock<RedbDatabase>>,
+    /// Inserts waiting to be committed together
+    batcher: WriteBatcher,
 }
 
 impl LocalStorage {
@@ -83,23 +139,71 @@ impl LocalStorage {
         Ok(Self {
             path,
             db: Arc::new(RwLock::new(db)),
+            batcher: WriteBatcher::new(),
         })
     }
 
     /// Open an existing local storage
     pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with(path, false).await
+    }
+
+    /// Open an existing local storage, serving page reads from a memory
+    /// mapping of the data file when `mmap` is set
+    ///
+    /// Memory-mapped reads are only available on Unix; elsewhere the flag
+    /// is ignored with a warning.
+    pub async fn open_with(path: impl AsRef<Path>, mmap: bool) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
         let db_path = path.join(".aresadb/data.redb");
 
-        let db = RedbDatabase::open(&db_path)
-            .context("Failed to open redb database")?;
+        let db = if mmap {
+            Self::open_mmap(&db_path)?
+        } else {
+            RedbDatabase::open(&db_path)
+                .context("Failed to open redb database")?
+        };
+        check_format_version(&db)?;
 
         Ok(Self {
             path,
             db: Arc::new(RwLock::new(db)),
+            batcher: WriteBatcher::new(),
+        })
+    }
+
+    /// Open storage whose data file is served by `backend`, keeping other
+    /// files under `path`
+    pub fn open_backend(path: impl AsRef<Path>, backend: impl redb::StorageBackend) -> Result<Self> {
+        let db = RedbDatabase::builder()
+            .create_with_backend(backend)
+            .context("Failed to open redb database")?;
+        check_format_version(&db)?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            db: Arc::new(RwLock::new(db)),
+            batcher: WriteBatcher::new(),
         })
     }
 
+    #[cfg(unix)]
+    fn open_mmap(db_path: &Path) -> Result<RedbDatabase> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(db_path)
+            .context("Failed to open redb database")?;
+        RedbDatabase::builder()
+            .create_with_backend(super::mmap::MmapBackend::new(file)?)
+            .context("Failed to open redb database")
+    }
+
+    #[cfg(not(unix))]
+    fn open_mmap(db_path: &Path) -> Result<RedbDatabase> {
+        tracing::warn!("Memory-mapped reads are not supported on this platform; using buffered reads");
+        RedbDatabase::open(db_path).context("Failed to open redb database")
+    }
+
     /// Get storage statistics
     pub async fn stats(&self) -> Result<StorageStats> {
         let db = self.db.read();
@@ -118,40 +222,40 @@ impl LocalStorage {
             .map(|m| m.len())
             .unwrap_or(0);
 
+        let (write_batches, batched_writes) = self.batcher.counts();
+
         Ok(StorageStats {
             node_count,
             edge_count,
             schema_count: 0, // Will be implemented with schema registry
             size_bytes,
+            write_batches,
+            batched_writes,
         })
     }
 
     // ========== Node Operations ==========
 
     /// Insert a new node
+    ///
+    /// Concurrent inserts share a transaction; see [`LocalStorage::set_flush_window`].
+    #[tracing::instrument(name = "storage.insert_node", level = "trace", skip_all, fields(node_type = %node.node_type))]
     pub async fn insert_node(&self, node: &Node) -> Result<()> {
-        let db = self.db.write();
-        let write_txn = db.begin_write()?;
-
-        {
-            // Serialize node
-            let node_bytes = serde_json::to_vec(node)?;
-            let id_bytes = node.id.uuid;
-
-            // Insert into nodes table
-            let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
-            nodes_table.insert(id_bytes.as_slice(), node_bytes.as_slice())?;
-
-            // Update type index
-            let mut type_index = write_txn.open_multimap_table(NODE_TYPE_INDEX)?;
-            type_index.insert(node.node_type.as_str(), id_bytes.as_slice())?;
-        }
+        self.batcher
+            .submit(BatchedWrite::Node(node.clone()), || self.db.write(), |db, writes| commit_writes(db, writes))
+            .await
+    }
 
-        write_txn.commit()?;
-        Ok(())
+    /// Set how long an insert waits for concurrent ones to commit with
+    ///
+    /// With no window, inserts only share a transaction when they queue
+    /// behind a commit already in progress.
+    pub fn set_flush_window(&self, window: std::time::Duration) {
+        self.batcher.set_window(window);
     }
 
     /// Get a node by ID
+    #[tracing::instrument(name = "storage.get_node", level = "trace", skip_all)]
     pub async fn get_node(&self, id: &NodeId) -> Result<Option<Node>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -159,7 +263,7 @@ impl LocalStorage {
         let nodes_table = read_txn.open_table(NODES_TABLE)?;
 
         if let Some(data) = nodes_table.get(id.uuid.as_slice())? {
-            let node: Node = serde_json::from_slice(data.value())?;
+            let node: Node = decode_node(data.value())?;
             Ok(Some(node))
         } else {
             Ok(None)
@@ -167,6 +271,7 @@ impl LocalStorage {
     }
 
     /// Update a node's properties
+    #[tracing::instrument(name = "storage.update_node", level = "trace", skip_all)]
     pub async fn update_node(&self, id: &NodeId, properties: Value) -> Result<Node> {
         let db = self.db.write();
         let write_txn = db.begin_write()?;
@@ -181,7 +286,7 @@ impl LocalStorage {
                 guard.value().to_vec()
             };
 
-            let mut node: Node = serde_json::from_slice(&node_data)?;
+            let mut node: Node = decode_node(&node_data)?;
 
             // Update properties
             if let Value::Object(new_props) = properties {
@@ -192,7 +297,7 @@ impl LocalStorage {
             node.updated_at = Timestamp::now();
 
             // Save updated node
-            let node_bytes = serde_json::to_vec(&node)?;
+            let node_bytes = encode_node(&node)?;
             nodes_table.insert(id.uuid.as_slice(), node_bytes.as_slice())?;
 
             node
@@ -203,69 +308,17 @@ impl LocalStorage {
     }
 
     /// Delete a node and its edges
+    #[tracing::instrument(name = "storage.delete_node", level = "trace", skip_all)]
     pub async fn delete_node(&self, id: &NodeId) -> Result<()> {
         let db = self.db.write();
         let write_txn = db.begin_write()?;
-
-        {
-            // Get node to find its type
-            let nodes_table = write_txn.open_table(NODES_TABLE)?;
-            if let Some(data) = nodes_table.get(id.uuid.as_slice())? {
-                let node: Node = serde_json::from_slice(data.value())?;
-
-                // Remove from type index
-                let mut type_index = write_txn.open_multimap_table(NODE_TYPE_INDEX)?;
-                type_index.remove(node.node_type.as_str(), id.uuid.as_slice())?;
-            }
-            drop(nodes_table);
-
-            // Remove node
-            let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
-            nodes_table.remove(id.uuid.as_slice())?;
-
-            // Remove edges from this node
-            let edge_from_index = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
-            let edge_ids: Vec<Vec<u8>> = edge_from_index
-                .get(id.uuid.as_slice())?
-                .map(|r| r.map(|v| v.value().to_vec()))
-                .collect::<std::result::Result<Vec<_>, _>>()?;
-            drop(edge_from_index);
-
-            let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
-            let mut edge_from = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
-            let mut edge_to = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
-
-            for edge_id in edge_ids {
-                edges_table.remove(edge_id.as_slice())?;
-                edge_from.remove(id.uuid.as_slice(), edge_id.as_slice())?;
-            }
-
-            // Remove edges to this node
-            drop(edges_table);
-            drop(edge_from);
-            drop(edge_to);
-
-            let edge_to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
-            let edge_ids: Vec<Vec<u8>> = edge_to_index
-                .get(id.uuid.as_slice())?
-                .map(|r| r.map(|v| v.value().to_vec()))
-                .collect::<std::result::Result<Vec<_>, _>>()?;
-            drop(edge_to_index);
-
-            let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
-            let mut edge_to = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
-
-            for edge_id in edge_ids {
-                edges_table.remove(edge_id.as_slice())?;
-                edge_to.remove(id.uuid.as_slice(), edge_id.as_slice())?;
-            }
-        }
-
+        delete_node_in(&write_txn, id)?;
         write_txn.commit()?;
         Ok(())
     }
 
     /// Get all nodes of a specific type
+    #[tracing::instrument(name = "storage.get_nodes_by_type", level = "debug", skip(self))]
     pub async fn get_nodes_by_type(&self, node_type: &str, limit: Option<usize>) -> Result<Vec<Node>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -283,7 +336,7 @@ impl LocalStorage {
 
             let id_bytes = result?.value().to_vec();
             if let Some(data) = nodes_table.get(id_bytes.as_slice())? {
-                let node: Node = serde_json::from_slice(data.value())?;
+                let node: Node = decode_node(data.value())?;
                 nodes.push(node);
             }
         }
@@ -291,7 +344,61 @@ impl LocalStorage {
         Ok(nodes)
     }
 
+    /// Visit the nodes of a type one at a time, without collecting them,
+    /// until `visit` returns false
+    #[tracing::instrument(name = "storage.scan_nodes_by_type", level = "debug", skip(self, visit))]
+    pub fn scan_nodes_by_type<F>(&self, node_type: &str, mut visit: F) -> Result<()>
+    where
+        F: FnMut(Node) -> Result<bool>,
+    {
+        self.scan_archived_nodes_by_type(node_type, |node| visit(node.to_node()))
+    }
+
+    /// Visit the nodes of a type as validated archives, without
+    /// deserializing them, until `visit` returns false
+    ///
+    /// A visitor that only looks at a few properties never builds a `Node`;
+    /// the archive it is given is only valid for the duration of the call.
+    #[tracing::instrument(name = "storage.scan_archived_nodes_by_type", level = "debug", skip(self, visit))]
+    pub fn scan_archived_nodes_by_type<F>(&self, node_type: &str, visit: F) -> Result<()>
+    where
+        F: FnMut(&ArchivedNode) -> Result<bool>,
+    {
+        let read_txn = self.db.read().begin_read()?;
+        scan_archived_in(&read_txn, node_type, visit)
+    }
+
+    /// Node types with at least one node
+    pub fn node_types(&self) -> Result<Vec<String>> {
+        let db = self.db.read();
+        let read_txn = db.begin_read()?;
+
+        let type_index = read_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+        let mut types = Vec::new();
+        for result in type_index.iter()? {
+            let (key, _) = result?;
+            types.push(key.value().to_string());
+        }
+        Ok(types)
+    }
+
+    /// IDs of the nodes of a type, read from the type index alone
+    pub fn node_ids_by_type(&self, node_type: &str) -> Result<Vec<NodeId>> {
+        let db = self.db.read();
+        let read_txn = db.begin_read()?;
+
+        let type_index = read_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+        let mut ids = Vec::new();
+        for result in type_index.get(node_type)? {
+            if let Ok(uuid) = <[u8; 16]>::try_from(result?.value()) {
+                ids.push(NodeId { uuid });
+            }
+        }
+        Ok(ids)
+    }
+
     /// Get all nodes (with optional limit)
+    #[tracing::instrument(name = "storage.get_all_nodes", level = "debug", skip(self))]
     pub async fn get_all_nodes(&self, limit: Option<usize>) -> Result<Vec<Node>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -307,47 +414,44 @@ impl LocalStorage {
             }
 
             let (_, data) = result?;
-            let node: Node = serde_json::from_slice(data.value())?;
+            let node: Node = decode_node(data.value())?;
             nodes.push(node);
         }
 
         Ok(nodes)
     }
 
+    /// Every node and edge, read in one transaction so they are consistent
+    #[tracing::instrument(name = "storage.snapshot", level = "debug", skip_all)]
+    pub async fn snapshot(&self) -> Result<(Vec<Node>, Vec<Edge>)> {
+        let db = self.db.read();
+        let read_txn = db.begin_read()?;
+
+        let mut nodes = Vec::new();
+        for result in read_txn.open_table(NODES_TABLE)?.iter()? {
+            let (_, data) = result?;
+            nodes.push(decode_node(data.value())?);
+        }
+        let mut edges = Vec::new();
+        for result in read_txn.open_table(EDGES_TABLE)?.iter()? {
+            let (_, data) = result?;
+            edges.push(serde_json::from_slice(data.value())?);
+        }
+        Ok((nodes, edges))
+    }
+
     // ========== Edge Operations ==========
 
     /// Insert a new edge
+    #[tracing::instrument(name = "storage.insert_edge", level = "trace", skip_all, fields(edge_type = %edge.edge_type))]
     pub async fn insert_edge(&self, edge: &Edge) -> Result<()> {
-        let db = self.db.write();
-        let write_txn = db.begin_write()?;
-
-        {
-            // Serialize edge
-            let edge_bytes = serde_json::to_vec(edge)?;
-            let id_bytes = edge.id.uuid;
-
-            // Insert into edges table
-            let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
-            edges_table.insert(id_bytes.as_slice(), edge_bytes.as_slice())?;
-
-            // Update from index
-            let mut from_index = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
-            from_index.insert(edge.from.uuid.as_slice(), id_bytes.as_slice())?;
-
-            // Update to index
-            let mut to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
-            to_index.insert(edge.to.uuid.as_slice(), id_bytes.as_slice())?;
-
-            // Update type index
-            let mut type_index = write_txn.open_multimap_table(EDGE_TYPE_INDEX)?;
-            type_index.insert(edge.edge_type.as_str(), id_bytes.as_slice())?;
-        }
-
-        write_txn.commit()?;
-        Ok(())
+        self.batcher
+            .submit(BatchedWrite::Edge(edge.clone()), || self.db.write(), |db, writes| commit_writes(db, writes))
+            .await
     }
 
     /// Get an edge by ID
+    #[tracing::instrument(name = "storage.get_edge", level = "trace", skip_all)]
     pub async fn get_edge(&self, id: &EdgeId) -> Result<Option<Edge>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -363,6 +467,7 @@ impl LocalStorage {
     }
 
     /// Get edges from a node
+    #[tracing::instrument(name = "storage.get_edges_from", level = "trace", skip(self))]
     pub async fn get_edges_from(&self, node_id: &NodeId, edge_type: Option<&str>) -> Result<Vec<Edge>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -392,6 +497,7 @@ impl LocalStorage {
     }
 
     /// Get edges to a node
+    #[tracing::instrument(name = "storage.get_edges_to", level = "trace", skip(self))]
     pub async fn get_edges_to(&self, node_id: &NodeId, edge_type: Option<&str>) -> Result<Vec<Edge>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -420,7 +526,79 @@ impl LocalStorage {
         Ok(edges)
     }
 
+    /// Get many nodes by ID, reading batches of them in parallel
+    ///
+    /// Each batch shares one read transaction. IDs with no node are
+    /// skipped; the rest come back in the order given.
+    #[tracing::instrument(name = "storage.get_nodes", level = "trace", skip(self, ids), fields(ids = ids.len()))]
+    pub async fn get_nodes(&self, ids: &[NodeId], batch_size: usize) -> Result<Vec<Node>> {
+        let guard = self.db.read();
+        let db: &RedbDatabase = &guard;
+        let batches: Vec<Result<Vec<Node>>> = ids.par_chunks(batch_size.max(1))
+            .map(|batch| {
+                let read_txn = db.begin_read()?;
+                let nodes_table = read_txn.open_table(NODES_TABLE)?;
+                let mut nodes = Vec::with_capacity(batch.len());
+                for id in batch {
+                    if let Some(data) = nodes_table.get(id.uuid.as_slice())? {
+                        nodes.push(decode_node(data.value())?);
+                    }
+                }
+                Ok(nodes)
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(ids.len());
+        for batch in batches {
+            nodes.extend(batch?);
+        }
+        Ok(nodes)
+    }
+
+    /// Get the edges from each of many nodes, reading batches in parallel
+    ///
+    /// The result has one list of edges per ID, in the order given.
+    #[tracing::instrument(name = "storage.get_edges_from_many", level = "trace", skip(self, ids), fields(ids = ids.len()))]
+    pub async fn get_edges_from_many(
+        &self,
+        ids: &[NodeId],
+        edge_type: Option<&str>,
+        batch_size: usize,
+    ) -> Result<Vec<Vec<Edge>>> {
+        let guard = self.db.read();
+        let db: &RedbDatabase = &guard;
+        let batches: Vec<Result<Vec<Vec<Edge>>>> = ids.par_chunks(batch_size.max(1))
+            .map(|batch| {
+                let read_txn = db.begin_read()?;
+                let from_index = read_txn.open_multimap_table(EDGE_FROM_INDEX)?;
+                let edges_table = read_txn.open_table(EDGES_TABLE)?;
+                let mut lists = Vec::with_capacity(batch.len());
+                for id in batch {
+                    let mut edges = Vec::new();
+                    for result in from_index.get(id.uuid.as_slice())? {
+                        let edge_id = result?.value().to_vec();
+                        if let Some(data) = edges_table.get(edge_id.as_slice())? {
+                            let edge: Edge = serde_json::from_slice(data.value())?;
+                            if edge_type.is_none_or(|et| edge.edge_type == et) {
+                                edges.push(edge);
+                            }
+                        }
+                    }
+                    lists.push(edges);
+                }
+                Ok(lists)
+            })
+            .collect();
+
+        let mut lists = Vec::with_capacity(ids.len());
+        for batch in batches {
+            lists.extend(batch?);
+        }
+        Ok(lists)
+    }
+
     /// Delete an edge
+    #[tracing::instrument(name = "storage.delete_edge", level = "trace", skip_all)]
     pub async fn delete_edge(&self, id: &EdgeId) -> Result<()> {
         let db = self.db.write();
         let write_txn = db.begin_write()?;
@@ -453,6 +631,7 @@ impl LocalStorage {
     }
 
     /// Get all edges of a specific type
+    #[tracing::instrument(name = "storage.get_edges_by_type", level = "debug", skip(self))]
     pub async fn get_edges_by_type(&self, edge_type: &str, limit: Option<usize>) -> Result<Vec<Edge>> {
         let db = self.db.read();
         let read_txn = db.begin_read()?;
@@ -491,6 +670,96 @@ impl LocalStorage {
     }
 }
 
+/// Delete a node and its edges within a write transaction
+fn delete_node_in(write_txn: &WriteTransaction, id: &NodeId) -> Result<()> {
+    // Get node to find its type
+    let nodes_table = write_txn.open_table(NODES_TABLE)?;
+    if let Some(data) = nodes_table.get(id.uuid.as_slice())? {
+        let node: Node = decode_node(data.value())?;
+
+        // Remove from type index
+        let mut type_index = write_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+        type_index.remove(node.node_type.as_str(), id.uuid.as_slice())?;
+    }
+    drop(nodes_table);
+
+    // Remove node
+    let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
+    nodes_table.remove(id.uuid.as_slice())?;
+
+    // Remove edges from this node
+    let edge_from_index = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
+    let edge_ids: Vec<Vec<u8>> = edge_from_index
+        .get(id.uuid.as_slice())?
+        .map(|r| r.map(|v| v.value().to_vec()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    drop(edge_from_index);
+
+    let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
+    let mut edge_from = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
+    let mut edge_to = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
+
+    for edge_id in edge_ids {
+        edges_table.remove(edge_id.as_slice())?;
+        edge_from.remove(id.uuid.as_slice(), edge_id.as_slice())?;
+    }
+
+    // Remove edges to this node
+    drop(edges_table);
+    drop(edge_from);
+    drop(edge_to);
+
+    let edge_to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
+    let edge_ids: Vec<Vec<u8>> = edge_to_index
+        .get(id.uuid.as_slice())?
+        .map(|r| r.map(|v| v.value().to_vec()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    drop(edge_to_index);
+
+    let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
+    let mut edge_to = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
+
+    for edge_id in edge_ids {
+        edges_table.remove(edge_id.as_slice())?;
+        edge_to.remove(id.uuid.as_slice(), edge_id.as_slice())?;
+    }
+    Ok(())
+}
+
+/// Write a group of inserts in one transaction
+fn commit_writes(db: &RedbDatabase, writes: &[BatchedWrite]) -> Result<()> {
+    let write_txn = db.begin_write()?;
+
+    {
+        let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
+        let mut node_type_index = write_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+        let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
+        let mut from_index = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
+        let mut to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
+        let mut edge_type_index = write_txn.open_multimap_table(EDGE_TYPE_INDEX)?;
+
+        for write in writes {
+            match write {
+                BatchedWrite::Node(node) => {
+                    let id_bytes = node.id.uuid;
+                    nodes_table.insert(id_bytes.as_slice(), encode_node(node)?.as_slice())?;
+                    node_type_index.insert(node.node_type.as_str(), id_bytes.as_slice())?;
+                }
+                BatchedWrite::Edge(edge) => {
+                    let id_bytes = edge.id.uuid;
+                    edges_table.insert(id_bytes.as_slice(), serde_json::to_vec(edge)?.as_slice())?;
+                    from_index.insert(edge.from.uuid.as_slice(), id_bytes.as_slice())?;
+                    to_index.insert(edge.to.uuid.as_slice(), id_bytes.as_slice())?;
+                    edge_type_index.insert(edge.edge_type.as_str(), id_bytes.as_slice())?;
+                }
+            }
+        }
+    }
+
+    write_txn.commit()?;
+    Ok(())
+}
+
 /// A database transaction for atomic operations
 pub struct Transaction {
     db: Arc<RwLock<RedbDatabase>>,
@@ -502,6 +771,7 @@ enum TransactionOp {
     InsertNode(Node),
     UpdateNode(NodeId, Value),
     DeleteNode(NodeId),
+    DeleteNodeAndEdges(NodeId),
     InsertEdge(Edge),
     DeleteEdge(EdgeId),
 }
@@ -529,6 +799,11 @@ impl Transaction {
         self.operations.push(TransactionOp::DeleteNode(id));
     }
 
+    /// Delete a node and every edge to or from it in this transaction
+    pub fn delete_node_and_edges(&mut self, id: NodeId) {
+        self.operations.push(TransactionOp::DeleteNodeAndEdges(id));
+    }
+
     /// Insert an edge in this transaction
     pub fn insert_edge(&mut self, edge: Edge) {
         self.operations.push(TransactionOp::InsertEdge(edge));
@@ -540,6 +815,7 @@ impl Transaction {
     }
 
     /// Commit the transaction
+    #[tracing::instrument(name = "storage.commit", level = "debug", skip_all, fields(operations = self.operations.len()))]
     pub fn commit(self) -> Result<()> {
         let db = self.db.write();
         let write_txn = db.begin_write()?;
@@ -547,7 +823,7 @@ impl Transaction {
         for op in self.operations {
             match op {
                 TransactionOp::InsertNode(node) => {
-                    let node_bytes = serde_json::to_vec(&node)?;
+                    let node_bytes = encode_node(&node)?;
                     let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
                     nodes_table.insert(node.id.uuid.as_slice(), node_bytes.as_slice())?;
 
@@ -561,20 +837,29 @@ impl Transaction {
                             .map(|d| d.value().to_vec())
                     };
                     if let Some(data) = node_data {
-                        let mut node: Node = serde_json::from_slice(&data)?;
+                        let mut node: Node = decode_node(&data)?;
                         if let Value::Object(new_props) = properties {
                             for (k, v) in new_props {
                                 node.properties.insert(k, v);
                             }
                         }
                         node.updated_at = Timestamp::now();
-                        let node_bytes = serde_json::to_vec(&node)?;
+                        let node_bytes = encode_node(&node)?;
                         nodes_table.insert(id.uuid.as_slice(), node_bytes.as_slice())?;
                     }
                 }
                 TransactionOp::DeleteNode(id) => {
                     let mut nodes_table = write_txn.open_table(NODES_TABLE)?;
-                    nodes_table.remove(id.uuid.as_slice())?;
+                    let removed = nodes_table.remove(id.uuid.as_slice())?
+                        .map(|data| data.value().to_vec());
+                    if let Some(data) = removed {
+                        let node: Node = decode_node(&data)?;
+                        let mut type_index = write_txn.open_multimap_table(NODE_TYPE_INDEX)?;
+                        type_index.remove(node.node_type.as_str(), id.uuid.as_slice())?;
+                    }
+                }
+                TransactionOp::DeleteNodeAndEdges(id) => {
+                    delete_node_in(&write_txn, &id)?;
                 }
                 TransactionOp::InsertEdge(edge) => {
                     let edge_bytes = serde_json::to_vec(&edge)?;
@@ -586,10 +871,23 @@ impl Transaction {
 
                     let mut to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
                     to_index.insert(edge.to.uuid.as_slice(), edge.id.uuid.as_slice())?;
+
+                    let mut type_index = write_txn.open_multimap_table(EDGE_TYPE_INDEX)?;
+                    type_index.insert(edge.edge_type.as_str(), edge.id.uuid.as_slice())?;
                 }
                 TransactionOp::DeleteEdge(id) => {
                     let mut edges_table = write_txn.open_table(EDGES_TABLE)?;
-                    edges_table.remove(id.uuid.as_slice())?;
+                    let removed = edges_table.remove(id.uuid.as_slice())?
+                        .map(|data| data.value().to_vec());
+                    if let Some(data) = removed {
+                        let edge: Edge = serde_json::from_slice(&data)?;
+                        let mut from_index = write_txn.open_multimap_table(EDGE_FROM_INDEX)?;
+                        from_index.remove(edge.from.uuid.as_slice(), id.uuid.as_slice())?;
+                        let mut to_index = write_txn.open_multimap_table(EDGE_TO_INDEX)?;
+                        to_index.remove(edge.to.uuid.as_slice(), id.uuid.as_slice())?;
+                        let mut type_index = write_txn.open_multimap_table(EDGE_TYPE_INDEX)?;
+                        type_index.remove(edge.edge_type.as_str(), id.uuid.as_slice())?;
+                    }
                 }
             }
         }
@@ -623,6 +921,26 @@ mod tests {
         assert_eq!(stats.node_count, 0);
     }
 
+    #[tokio::test]
+    async fn test_refuses_newer_format() {
+        let temp = TempDir::new().unwrap();
+        let storage = LocalStorage::create(temp.path()).await.unwrap();
+        {
+            let db = storage.db.read();
+            let write_txn = db.begin_write().unwrap();
+            {
+                let mut meta_table = write_txn.open_table(METADATA_TABLE).unwrap();
+                let version = serde_json::to_vec(&(crate::FORMAT_VERSION + 1)).unwrap();
+                meta_table.insert("version", version.as_slice()).unwrap();
+            }
+            write_txn.commit().unwrap();
+        }
+        drop(storage);
+
+        let err = LocalStorage::open(temp.path()).await.err().unwrap();
+        assert!(err.to_string().contains("newer than this build supports"));
+    }
+
     #[tokio::test]
     async fn test_node_crud() {
         let temp = TempDir::new().unwrap();
@@ -701,6 +1019,63 @@ mod tests {
         let nodes = storage.get_nodes_by_type("user", None).await.unwrap();
         assert_eq!(nodes.len(), 2);
     }
-}
 
+    #[tokio::test]
+    async fn test_scan_archived_reads_legacy_rows() {
+        let temp = TempDir::new().unwrap();
+        let storage = LocalStorage::create(temp.path()).await.unwrap();
+
+        let alice = Node::new("user", Value::from_json(serde_json::json!({"name": "Alice", "age": 30})).unwrap());
+        storage.insert_node(&alice).await.unwrap();
+
+        // A row written as JSON by format version 1
+        let bob = Node::new("user", Value::from_json(serde_json::json!({"name": "Bob", "age": 40})).unwrap());
+        {
+            let db = storage.db.write();
+            let write_txn = db.begin_write().unwrap();
+            write_txn.open_table(NODES_TABLE).unwrap()
+                .insert(bob.id.uuid.as_slice(), serde_json::to_vec(&bob).unwrap().as_slice()).unwrap();
+            write_txn.open_multimap_table(NODE_TYPE_INDEX).unwrap()
+                .insert("user", bob.id.uuid.as_slice()).unwrap();
+            write_txn.commit().unwrap();
+        }
 
+        let mut ages = Vec::new();
+        storage.scan_archived_nodes_by_type("user", |node| {
+            ages.push(node.get("age").unwrap().to_value().as_int().unwrap());
+            Ok(true)
+        }).unwrap();
+        ages.sort();
+        assert_eq!(ages, vec![30, 40]);
+
+        let retrieved = storage.get_node(&bob.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.get("name").unwrap().as_str(), Some("Bob"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_inserts_share_commits() {
+        let temp = TempDir::new().unwrap();
+        let storage = LocalStorage::create(temp.path()).await.unwrap();
+        storage.set_flush_window(std::time::Duration::from_millis(20));
+
+        let nodes: Vec<Node> = (0..20)
+            .map(|i| Node::new("user", Value::from_json(serde_json::json!({"n": i})).unwrap()))
+            .collect();
+        let edges: Vec<Edge> = nodes.windows(2)
+            .map(|pair| Edge::new(pair[0].id.clone(), pair[1].id.clone(), "next", Value::Null))
+            .collect();
+        let results = futures::future::join_all(
+            nodes.iter().map(|node| storage.insert_node(node))
+        ).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+        let results = futures::future::join_all(
+            edges.iter().map(|edge| storage.insert_edge(edge))
+        ).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        let stats = storage.stats().await.unwrap();
+        assert_eq!((stats.node_count, stats.edge_count), (20, 19));
+        assert_eq!((stats.write_batches, stats.batched_writes), (2, 39));
+        assert_eq!(storage.get_edges_from(&nodes[0].id, None).await.unwrap().len(), 1);
+    }
+}
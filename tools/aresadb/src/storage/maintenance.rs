@@ -0,0 +1,263 @@
+//! Background maintenance
+//!
+//! Upkeep a long-running process schedules on its databases: gathering
+//! per-type statistics, rebuilding the node ID bloom filter, compacting
+//! built indexes and sweeping nodes past their time to live. Tasks read
+//! nodes in small batches under an IO budget and yield between batches,
+//! so user requests aren't starved while one runs.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::Timestamp;
+
+/// Nodes a task reads between checks of the IO budget and pause flag
+pub(super) const BATCH_SIZE: usize = 256;
+
+/// A maintenance task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Count nodes and field usage per node type
+    Analyze,
+    /// Rebuild the bloom filter answering lookups of missing node IDs
+    BloomRebuild,
+    /// Release memory built indexes hold for removed entries
+    IndexCompaction,
+    /// Delete nodes older than their type's time to live
+    TtlSweep,
+}
+
+impl MaintenanceTask {
+    /// Every task, in the order a scheduled run performs them
+    pub const ALL: [MaintenanceTask; 4] = [
+        MaintenanceTask::Analyze,
+        MaintenanceTask::BloomRebuild,
+        MaintenanceTask::IndexCompaction,
+        MaintenanceTask::TtlSweep,
+    ];
+
+    /// Get the name of this task
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Analyze => "analyze",
+            Self::BloomRebuild => "bloom_rebuild",
+            Self::IndexCompaction => "index_compaction",
+            Self::TtlSweep => "ttl_sweep",
+        }
+    }
+}
+
+/// Maintenance settings, stored under `[maintenance]` in the database config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Seconds between scheduled runs of every task
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// Nodes a task may read per second; 0 leaves reads unthrottled
+    #[serde(default)]
+    pub io_limit: u64,
+    /// Node types whose nodes expire
+    #[serde(default)]
+    pub ttl: Vec<TtlRule>,
+}
+
+fn default_interval_secs() -> u64 {
+    600
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_interval_secs(),
+            io_limit: 0,
+            ttl: Vec::new(),
+        }
+    }
+}
+
+/// How long nodes of a type live after they are created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TtlRule {
+    /// Node type whose nodes expire
+    pub node_type: String,
+    /// Age in seconds past which a node is deleted
+    pub max_age_secs: u64,
+}
+
+/// Statistics gathered by `MaintenanceTask::Analyze` for one node type
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TypeStats {
+    /// Nodes of the type
+    pub nodes: u64,
+    /// Nodes with a non-null value, by field
+    pub fields: BTreeMap<String, u64>,
+    /// When the statistics were gathered
+    pub analyzed_at: Option<Timestamp>,
+}
+
+/// What a task did the last time it ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskStatus {
+    /// The task
+    pub task: MaintenanceTask,
+    /// Whether the task is running now
+    pub running: bool,
+    /// Completed runs, including failed ones
+    pub runs: u64,
+    /// When the last run started
+    pub last_started: Option<Timestamp>,
+    /// How long the last run took
+    pub last_duration_ms: u64,
+    /// Nodes or indexes the last run processed
+    pub last_items: u64,
+    /// Why the last run failed, if it did
+    pub last_error: Option<String>,
+}
+
+impl TaskStatus {
+    fn new(task: MaintenanceTask) -> Self {
+        Self {
+            task,
+            running: false,
+            runs: 0,
+            last_started: None,
+            last_duration_ms: 0,
+            last_items: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// State of a database's maintenance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    /// Whether scheduled runs are paused
+    pub paused: bool,
+    /// Every task, in run order
+    pub tasks: Vec<TaskStatus>,
+}
+
+/// Pause flag and per-task status of one database
+pub(super) struct Maintenance {
+    paused: AtomicBool,
+    tasks: Mutex<BTreeMap<MaintenanceTask, TaskStatus>>,
+}
+
+impl Maintenance {
+    pub(super) fn new() -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            tasks: Mutex::new(MaintenanceTask::ALL.iter().map(|&task| (task, TaskStatus::new(task))).collect()),
+        }
+    }
+
+    pub(super) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Mark a task running; `false` if it already is
+    pub(super) fn begin(&self, task: MaintenanceTask) -> bool {
+        let mut tasks = self.tasks.lock();
+        let status = tasks.entry(task).or_insert_with(|| TaskStatus::new(task));
+        if status.running {
+            return false;
+        }
+        status.running = true;
+        status.last_started = Some(Timestamp::now());
+        true
+    }
+
+    /// Record the outcome of a run started with `begin`
+    pub(super) fn finish(&self, task: MaintenanceTask, started: Instant, outcome: &anyhow::Result<u64>) -> TaskStatus {
+        let mut tasks = self.tasks.lock();
+        let status = tasks.entry(task).or_insert_with(|| TaskStatus::new(task));
+        status.running = false;
+        status.runs += 1;
+        status.last_duration_ms = started.elapsed().as_millis() as u64;
+        match outcome {
+            Ok(items) => {
+                status.last_items = *items;
+                status.last_error = None;
+            }
+            Err(e) => {
+                status.last_items = 0;
+                status.last_error = Some(e.to_string());
+            }
+        }
+        status.clone()
+    }
+
+    pub(super) fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            paused: self.is_paused(),
+            tasks: self.tasks.lock().values().cloned().collect(),
+        }
+    }
+}
+
+/// Spreads a task's reads out to stay under a nodes-per-second budget
+pub(super) struct Throttle {
+    limit: u64,
+    started: Instant,
+    used: u64,
+}
+
+impl Throttle {
+    /// Budget of `limit` nodes per second; 0 only yields between batches
+    pub(super) fn new(limit: u64) -> Self {
+        Self { limit, started: Instant::now(), used: 0 }
+    }
+
+    /// Account for `nodes` about to be read, sleeping while over budget
+    pub(super) async fn consume(&mut self, nodes: usize) {
+        self.used += nodes as u64;
+        if self.limit == 0 {
+            tokio::task::yield_now().await;
+            return;
+        }
+        let due = Duration::from_secs_f64(self.used as f64 / self.limit as f64);
+        match due.checked_sub(self.started.elapsed()) {
+            Some(wait) if !wait.is_zero() => tokio::time::sleep(wait).await,
+            _ => tokio::task::yield_now().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_status() {
+        let maintenance = Maintenance::new();
+        assert!(maintenance.begin(MaintenanceTask::Analyze));
+        assert!(!maintenance.begin(MaintenanceTask::Analyze));
+        assert!(maintenance.status().tasks[0].running);
+
+        let status = maintenance.finish(MaintenanceTask::Analyze, Instant::now(), &Ok(12));
+        assert_eq!((status.running, status.runs, status.last_items), (false, 1, 12));
+
+        maintenance.begin(MaintenanceTask::Analyze);
+        let status = maintenance.finish(MaintenanceTask::Analyze, Instant::now(), &Err(anyhow::anyhow!("disk full")));
+        assert_eq!(status.runs, 2);
+        assert_eq!(status.last_error.as_deref(), Some("disk full"));
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let mut throttle = Throttle::new(1000);
+        let start = Instant::now();
+        for _ in 0..4 {
+            throttle.consume(25).await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,210 @@
+//! Memory budget
+//!
+//! One limit covers a database's cache, the buffers queries sort in, and
+//! the nodes loaded to build a vector index. The cache takes its share
+//! when the database opens; queries and index builds reserve memory as
+//! they go and give it back when done. A sort that outgrows what is left
+//! spills to disk; anything else fails with [`MemoryLimitExceeded`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Error returned when a reservation would take a database past its
+/// memory limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryLimitExceeded {
+    /// What the memory was for
+    pub purpose: String,
+    /// Bytes the reservation needed, including what it already held
+    pub requested: u64,
+    /// Bytes free when it was refused
+    pub available: u64,
+    /// The database's limit
+    pub limit: u64,
+}
+
+impl std::fmt::Display for MemoryLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = |bytes: u64| humansize::format_size(bytes, humansize::BINARY);
+        write!(
+            f,
+            "Memory limit exceeded: {} needs {} but only {} of the {} limit is free",
+            self.purpose,
+            size(self.requested),
+            size(self.available),
+            size(self.limit)
+        )
+    }
+}
+
+impl std::error::Error for MemoryLimitExceeded {}
+
+/// Memory use against a database's limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The limit; `None` when memory is unbounded
+    pub limit: Option<u64>,
+    /// Bytes reserved now, including the cache's share
+    pub used: u64,
+    /// Most bytes reserved at once
+    pub peak: u64,
+}
+
+impl MemoryStats {
+    /// One-line summary for status output
+    pub fn summary(&self) -> String {
+        let size = |bytes: u64| humansize::format_size(bytes, humansize::BINARY);
+        match self.limit {
+            Some(limit) => format!("{} of {} used, {} peak", size(self.used), size(limit), size(self.peak)),
+            None => format!("{} used, {} peak (no limit)", size(self.used), size(self.peak)),
+        }
+    }
+}
+
+/// A database's memory limit and what is reserved against it
+#[derive(Debug, Default)]
+pub struct MemoryBudget {
+    limit: Option<u64>,
+    used: AtomicU64,
+    peak: AtomicU64,
+}
+
+impl MemoryBudget {
+    /// Create a budget; `None` accounts for memory without limiting it
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit,
+            used: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+        }
+    }
+
+    /// The limit, if any
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Bytes not yet reserved; `u64::MAX` when unbounded
+    pub fn available(&self) -> u64 {
+        match self.limit {
+            Some(limit) => limit.saturating_sub(self.used.load(Ordering::Relaxed)),
+            None => u64::MAX,
+        }
+    }
+
+    /// Current and peak use
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            limit: self.limit,
+            used: self.used.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Start an empty reservation, grown as its owner allocates
+    pub fn reservation(self: &Arc<Self>, purpose: impl Into<String>) -> MemoryReservation {
+        MemoryReservation {
+            budget: Arc::clone(self),
+            purpose: purpose.into(),
+            bytes: 0,
+        }
+    }
+
+    /// Reserve `bytes` at once
+    pub fn reserve(self: &Arc<Self>, purpose: impl Into<String>, bytes: u64) -> Result<MemoryReservation, MemoryLimitExceeded> {
+        let mut reservation = self.reservation(purpose);
+        reservation.grow(bytes)?;
+        Ok(reservation)
+    }
+
+    fn try_add(&self, bytes: u64) -> Result<(), u64> {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let next = used.saturating_add(bytes);
+            if self.limit.is_some_and(|limit| next > limit) {
+                return Err(used);
+            }
+            match self.used.compare_exchange_weak(used, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    self.peak.fetch_max(next, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(actual) => used = actual,
+            }
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        self.used.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Memory held against a budget, given back when dropped
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    purpose: String,
+    bytes: u64,
+}
+
+impl MemoryReservation {
+    /// Reserve `bytes` more, or fail leaving the reservation as it was
+    pub fn grow(&mut self, bytes: u64) -> Result<(), MemoryLimitExceeded> {
+        self.budget.try_add(bytes).map_err(|used| MemoryLimitExceeded {
+            purpose: self.purpose.clone(),
+            requested: self.bytes + bytes,
+            available: self.bytes + self.budget.limit.unwrap_or(u64::MAX).saturating_sub(used),
+            limit: self.budget.limit.unwrap_or(u64::MAX),
+        })?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    /// A new, empty reservation against the same budget
+    pub fn sibling(&self, purpose: impl Into<String>) -> MemoryReservation {
+        self.budget.reservation(purpose)
+    }
+
+    /// Give back everything held, keeping the reservation for reuse
+    pub fn clear(&mut self) {
+        self.budget.release(self.bytes);
+        self.bytes = 0;
+    }
+
+    /// Bytes held
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_budget() {
+        let budget = Arc::new(MemoryBudget::new(Some(1000)));
+        let cache = budget.reserve("cache", 400).unwrap();
+
+        let mut sort = budget.reservation("sort");
+        sort.grow(500).unwrap();
+        let err = sort.grow(200).unwrap_err();
+        assert_eq!((err.requested, err.available, err.limit), (700, 600, 1000));
+        assert_eq!(sort.bytes(), 500);
+        assert!(err.to_string().starts_with("Memory limit exceeded: sort needs 700 B"));
+
+        drop(sort);
+        assert_eq!(budget.stats(), MemoryStats { limit: Some(1000), used: 400, peak: 900 });
+        drop(cache);
+        assert_eq!(budget.available(), 1000);
+
+        let unbounded = Arc::new(MemoryBudget::new(None));
+        assert!(unbounded.reserve("sort", u64::MAX / 2).is_ok());
+    }
+}
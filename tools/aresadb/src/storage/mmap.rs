@@ -0,0 +1,226 @@
+//! Memory-mapped storage backend for redb
+//!
+//! Serves page reads from a shared mapping of the data file, so repeated
+//! scans copy straight out of the OS page cache instead of issuing a
+//! `pread` per page. Writes, resizes and syncs go through redb's own file
+//! backend, which also holds the exclusive lock on the file.
+//!
+//! A mapping must never outlive the bytes it covers: touching a page past
+//! the end of a truncated file raises SIGBUS. The mapping is dropped before
+//! the file is shrunk, re-checked against the file length whenever redb
+//! asks for it, and reads it does not cover fall back to `pread`, which
+//! reports a short file as an error instead. If the file can't be mapped
+//! at all (some filesystems don't support it), every read uses `pread`.
+
+use parking_lot::RwLock;
+use redb::backends::FileBackend;
+use redb::{DatabaseError, StorageBackend};
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+/// A read-only shared mapping of the first `len` bytes of a file
+#[derive(Debug)]
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only and only unmapped under the backend's write lock
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, len: usize) -> io::Result<Self> {
+        // SAFETY: a fresh read-only mapping of an open file; the kernel picks
+        // the address and the result is checked below
+        let ptr = unsafe {
+            libc::mmap(std::ptr::null_mut(), len, libc::PROT_READ, libc::MAP_SHARED, file.as_raw_fd(), 0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn slice(&self, offset: u64, len: usize) -> Option<&[u8]> {
+        let end = offset.checked_add(len as u64)?;
+        if end > self.len as u64 {
+            return None;
+        }
+        // SAFETY: the range lies inside the mapping, which stays valid until
+        // drop and covers bytes the file still holds
+        Some(unsafe { std::slice::from_raw_parts((self.ptr as *const u8).add(offset as usize), len) })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: ptr and len came from a successful mmap
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// redb backend reading through a memory mapping of the data file
+#[derive(Debug)]
+pub struct MmapBackend {
+    /// Handle the mapping is made from
+    file: File,
+    /// Backend for writes, resizes, syncs and locking
+    inner: FileBackend,
+    /// Current mapping, replaced when the file grows past it
+    mapping: RwLock<Option<Mapping>>,
+    /// Set once mapping the file fails; reads then always use `pread`
+    unmappable: AtomicBool,
+}
+
+impl MmapBackend {
+    /// Create a backend for an open data file, locking it like redb does
+    pub fn new(file: File) -> Result<Self, DatabaseError> {
+        let mapped = file.try_clone()?;
+        Ok(Self {
+            file: mapped,
+            inner: FileBackend::new(file)?,
+            mapping: RwLock::new(None),
+            unmappable: AtomicBool::new(false),
+        })
+    }
+
+    /// Map the whole file as it is now, if it has any bytes
+    fn remap(&self, mapping: &mut Option<Mapping>) -> io::Result<()> {
+        *mapping = None;
+        let len = self.file.metadata()?.len();
+        if len > 0 {
+            *mapping = Some(Mapping::new(&self.file, len as usize)?);
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for MmapBackend {
+    fn len(&self) -> Result<u64, io::Error> {
+        let len = self.inner.len()?;
+        // Another process may have shrunk the file under the mapping
+        let mut mapping = self.mapping.write();
+        if mapping.as_ref().is_some_and(|m| m.len as u64 > len) {
+            *mapping = None;
+        }
+        Ok(len)
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+        if self.unmappable.load(Ordering::Relaxed) {
+            return self.inner.read(offset, len);
+        }
+        if let Some(bytes) = self.mapping.read().as_ref().and_then(|m| m.slice(offset, len)) {
+            return Ok(bytes.to_vec());
+        }
+
+        let mut mapping = self.mapping.write();
+        if mapping.as_ref().and_then(|m| m.slice(offset, len)).is_none() {
+            if let Err(e) = self.remap(&mut mapping) {
+                warn!("Failed to memory-map the data file, using buffered reads: {}", e);
+                self.unmappable.store(true, Ordering::Relaxed);
+            }
+        }
+        match mapping.as_ref().and_then(|m| m.slice(offset, len)) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => self.inner.read(offset, len),
+        }
+    }
+
+    fn set_len(&self, len: u64) -> Result<(), io::Error> {
+        let mut mapping = self.mapping.write();
+        if mapping.as_ref().is_some_and(|m| m.len as u64 > len) {
+            *mapping = None;
+        }
+        self.inner.set_len(len)
+    }
+
+    fn sync_data(&self, eventual: bool) -> Result<(), io::Error> {
+        self.inner.sync_data(eventual)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        // The mapping is shared, so it sees the write through the page cache
+        self.inner.write(offset, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn backend(temp: &TempDir) -> MmapBackend {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(temp.path().join("data"))
+            .unwrap();
+        MmapBackend::new(file).unwrap()
+    }
+
+    #[test]
+    fn test_reads_follow_writes_and_growth() {
+        let temp = TempDir::new().unwrap();
+        let backend = backend(&temp);
+
+        backend.set_len(8).unwrap();
+        backend.write(0, b"abcdefgh").unwrap();
+        assert_eq!(backend.read(2, 3).unwrap(), b"cde");
+
+        backend.set_len(16).unwrap();
+        backend.write(8, b"ijklmnop").unwrap();
+        assert_eq!(backend.read(6, 4).unwrap(), b"ghij");
+        backend.write(0, b"A").unwrap();
+        assert_eq!(backend.read(0, 2).unwrap(), b"Ab");
+    }
+
+    #[test]
+    fn test_truncation_drops_mapping() {
+        let temp = TempDir::new().unwrap();
+        let backend = backend(&temp);
+
+        backend.set_len(4096).unwrap();
+        assert_eq!(backend.read(4000, 4).unwrap(), vec![0; 4]);
+
+        backend.set_len(16).unwrap();
+        assert!(backend.read(4000, 4).is_err());
+
+        // Shrunk behind the backend's back
+        backend.set_len(4096).unwrap();
+        backend.read(0, 4).unwrap();
+        std::fs::OpenOptions::new().write(true).open(temp.path().join("data")).unwrap().set_len(8).unwrap();
+        assert_eq!(backend.len().unwrap(), 8);
+        assert!(backend.read(4000, 4).is_err());
+    }
+
+    #[test]
+    fn test_falls_back_when_mapping_fails() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("data");
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).unwrap();
+
+        // A read-only mapping of a write-only handle is refused with EACCES
+        let backend = MmapBackend {
+            file: std::fs::OpenOptions::new().write(true).open(&path).unwrap(),
+            inner: FileBackend::new(file).unwrap(),
+            mapping: RwLock::new(None),
+            unmappable: AtomicBool::new(false),
+        };
+
+        backend.set_len(8).unwrap();
+        backend.write(0, b"abcdefgh").unwrap();
+        assert_eq!(backend.read(2, 3).unwrap(), b"cde");
+        assert!(backend.unmappable.load(Ordering::Relaxed));
+        assert!(backend.mapping.read().is_none());
+        assert_eq!(backend.read(6, 2).unwrap(), b"gh");
+    }
+}
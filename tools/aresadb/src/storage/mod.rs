@@ -3,26 +3,56 @@
 //! Unified storage layer supporting local filesystem and cloud bucket backends.
 
 mod node;
+mod codec;
+mod batch;
 mod local;
 mod bucket;
 mod cache;
 mod parallel;
+mod changes;
+mod transfer;
+mod backup;
+mod delta;
+mod encryption;
+mod integrity;
+mod field_index;
+mod maintenance;
+mod memory;
+#[cfg(unix)]
+mod mmap;
+mod remote;
 pub mod vector;
 pub mod vector_index;
 
-pub use node::{Node, Edge, NodeId, EdgeId, Value, Timestamp, DistanceMetric, SimilarityResult};
+pub use node::{Node, Edge, NodeId, EdgeId, Value, Timestamp, DistanceMetric, SimilarityResult, ArchivedNode, ArchivedValue};
+pub use codec::NodeReader;
 pub use local::LocalStorage;
 pub use bucket::BucketStorage;
-pub use cache::CacheLayer;
+pub use cache::{CacheConfig, CacheLayer, CachePolicy, CacheStats};
 pub use parallel::{ParallelExecutor, ParallelTraversalResult, SnapshotReader};
 pub use vector::{VectorSearch, VectorNodeBuilder};
-pub use vector_index::{VectorIndex, IndexStats};
+pub use vector_index::{VectorIndex, VectorIndexSpec, IndexStats};
+pub use field_index::{FieldIndex, FieldIndexKind, FieldIndexSpec, FieldIndexStats, tokenize};
+pub use changes::{ChangeFeed, ChangeEvent, ChangeKind, ChangeRecord};
+pub use transfer::{ImportOptions, TransferFormat, export_nodes, import_file, import_nodes};
+pub use integrity::{IntegrityIssue, IntegrityReport, IssueKind, check_integrity};
+pub use memory::{MemoryBudget, MemoryLimitExceeded, MemoryReservation, MemoryStats};
+pub use maintenance::{MaintenanceConfig, MaintenanceStatus, MaintenanceTask, TaskStatus, TtlRule, TypeStats};
+pub use delta::{ConflictStrategy, DestinationStatus, SyncConflict, SyncConflicts};
+pub use backup::{BackupFile, BackupKind, BackupManifest, create_backup, restore_backup, verify_backup};
 
 use anyhow::{Result, Context};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::OwnedMutexGuard;
 use serde::{Deserialize, Serialize};
+use crate::distributed::BloomFilter;
+use crate::error::{CodedError, ErrorCode};
+use crate::progress::{self, Progress};
+use crate::schema::{Schema, SchemaRelation};
+use maintenance::{Maintenance, Throttle};
 
 /// Database configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,8 +61,61 @@ pub struct DatabaseConfig {
     pub version: u32,
     pub created_at: Timestamp,
     pub bucket_url: Option<String>,
+    /// Further buckets that push and sync fan out to, e.g. a second cloud
+    /// for disaster recovery
+    #[serde(default)]
+    pub bucket_replicas: Vec<String>,
+    /// Vector indexes to maintain, built when first searched
+    #[serde(default)]
+    pub vector_indexes: Vec<VectorIndexSpec>,
+    /// Property and full-text indexes to maintain, built when first used
+    #[serde(default)]
+    pub field_indexes: Vec<FieldIndexSpec>,
+    /// Dimension of each embedding field, fixed by its first vector
+    #[serde(default)]
+    pub vector_dimensions: Vec<VectorDimension>,
+    /// Cache size, eviction policy and pinned node types
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// Serve page reads from a memory mapping of the data file (Unix only)
+    #[serde(default)]
+    pub mmap_reads: bool,
+    /// Milliseconds an insert waits for concurrent inserts to share its
+    /// commit; 0 only groups inserts queued behind a commit in progress
+    #[serde(default)]
+    pub write_batch_window_ms: u64,
+    /// Background task schedule, IO budget and node time to live
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// Bytes the cache, query sort buffers and vector index builds may
+    /// use together; unset leaves memory unbounded
+    #[serde(default)]
+    pub memory_limit_bytes: Option<u64>,
+    /// How a bucket sync settles a database changed on both sides
+    #[serde(default)]
+    pub sync_on_conflict: ConflictStrategy,
 }
 
+/// The dimension every vector in an embedding field must have
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorDimension {
+    /// Node type holding the field
+    pub node_type: String,
+    /// Field holding the embeddings
+    pub field: String,
+    /// Number of components
+    pub dimension: usize,
+}
+
+/// Cache size when the config leaves it unset, for local databases
+const LOCAL_CACHE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Cache size when the config leaves it unset, for bucket databases
+const REMOTE_CACHE_BYTES: u64 = 500 * 1024 * 1024;
+
+/// The data file, relative to the database root
+const DATA_FILE: &str = ".aresadb/data.redb";
+
 /// Database status information
 #[derive(Debug, Clone)]
 pub struct DatabaseStatus {
@@ -42,6 +125,10 @@ pub struct DatabaseStatus {
     pub edge_count: u64,
     pub schema_count: u64,
     pub size_bytes: u64,
+    /// Cache hit, miss and eviction counts
+    pub cache: CacheStats,
+    /// Memory reserved against the limit
+    pub memory: MemoryStats,
 }
 
 /// Sync statistics
@@ -49,6 +136,69 @@ pub struct DatabaseStatus {
 pub struct SyncStats {
     pub uploaded: u64,
     pub downloaded: u64,
+    /// Segment bytes sent or fetched
+    pub bytes_transferred: u64,
+    /// Bytes left alone because the other side already had them
+    pub bytes_saved: u64,
+    /// Bytes an interrupted push or pull had already moved
+    pub bytes_resumed: u64,
+    /// Files deleted from the side a sync replaced, as the kept side lacks them
+    pub deleted: u64,
+    /// Files that differed when the database changed on both sides since
+    /// the last sync, and which copy was kept
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// What a sync would do, worked out without changing either side
+#[derive(Debug, Clone, Default)]
+pub struct SyncPreview {
+    /// Files that would go to the bucket
+    pub uploads: Vec<PendingFile>,
+    /// Files that would come from the bucket
+    pub downloads: Vec<PendingFile>,
+    /// Files the bucket would drop, as the local copy lacks them
+    pub remote_deletes: Vec<String>,
+    /// Local files that would be deleted, as the bucket's copy lacks them
+    pub local_deletes: Vec<String>,
+    /// Segments the bucket would delete as no longer referenced
+    pub deleted_segments: u64,
+    pub deleted_bytes: u64,
+    /// Files that differ when the database changed on both sides; under
+    /// the abort strategy the sync would stop and change nothing
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl SyncPreview {
+    /// Segment bytes that would be sent
+    pub fn upload_bytes(&self) -> u64 {
+        self.uploads.iter().map(|file| file.bytes).sum()
+    }
+
+    /// Segment bytes that would be fetched
+    pub fn download_bytes(&self) -> u64 {
+        self.downloads.iter().map(|file| file.bytes).sum()
+    }
+
+    /// Whether the sync would change nothing
+    pub fn is_empty(&self) -> bool {
+        self.uploads.is_empty()
+            && self.downloads.is_empty()
+            && self.remote_deletes.is_empty()
+            && self.local_deletes.is_empty()
+            && self.deleted_segments == 0
+            && self.conflicts.is_empty()
+    }
+}
+
+/// A file a sync would move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingFile {
+    /// Path relative to the database root
+    pub path: String,
+    /// File length in bytes
+    pub size: u64,
+    /// Bytes of its segments the other side lacks
+    pub bytes: u64,
 }
 
 /// Graph representation for visualization
@@ -64,6 +214,22 @@ pub struct KvView {
     pub entries: Vec<(String, Value)>,
 }
 
+/// Cache layer and memory budget for a config
+///
+/// A memory limit caps the cache at half of it, and the cache's size is
+/// reserved up front so queries and index builds share what is left.
+fn cache_within_budget(config: &DatabaseConfig, default_size: u64) -> (Arc<CacheLayer>, Arc<MemoryBudget>, MemoryReservation) {
+    let memory = Arc::new(MemoryBudget::new(config.memory_limit_bytes));
+    let mut cache_config = config.cache.clone();
+    let mut size = cache_config.size_bytes.unwrap_or(default_size);
+    if let Some(limit) = config.memory_limit_bytes {
+        size = size.min(limit / 2);
+    }
+    cache_config.size_bytes = Some(size);
+    let reservation = memory.reserve("cache", size).unwrap_or_else(|_| memory.reservation("cache"));
+    (Arc::new(CacheLayer::with_config(&cache_config, size)), memory, reservation)
+}
+
 /// Main database handle
 pub struct Database {
     /// Path to the database
@@ -75,7 +241,65 @@ pub struct Database {
     /// Optional bucket storage backend
     bucket: Option<BucketStorage>,
     /// Cache layer for remote storage
-    cache: CacheLayer,
+    cache: Arc<CacheLayer>,
+    /// Change feed for subscribers
+    changes: ChangeFeed,
+    /// Built vector indexes, by node type and field
+    vector_indexes: RwLock<HashMap<(String, String), Arc<VectorIndex>>>,
+    /// Built property and full-text indexes, by node type and field
+    field_indexes: RwLock<HashMap<(String, String), Arc<FieldIndex>>>,
+    /// Registered schemas by node type, loaded when first written against
+    schemas: RwLock<Option<Arc<HashMap<String, Schema>>>>,
+    /// Strict relationships by edge type, loaded when first written against
+    relations: RwLock<Option<Arc<HashMap<String, SchemaRelation>>>>,
+    /// Node IDs that may exist, built by `MaintenanceTask::BloomRebuild`
+    node_filter: RwLock<NodeFilter>,
+    /// Statistics by node type, gathered by `MaintenanceTask::Analyze`
+    type_stats: RwLock<HashMap<String, TypeStats>>,
+    /// Held by writers of node types with unique fields from the check
+    /// until the write is committed and indexed
+    unique_locks: ConstraintLocks,
+    /// Held by writers of strict edge types from the cardinality check
+    /// until the edges are committed
+    relation_locks: ConstraintLocks,
+    /// Background task state
+    maintenance: Maintenance,
+    /// Memory limit shared by the cache, queries and index builds
+    memory: Arc<MemoryBudget>,
+    /// The cache's share of the memory limit, held while the database is open
+    _cache_memory: MemoryReservation,
+}
+
+/// Bloom filter over node IDs, letting lookups of missing IDs skip storage
+#[derive(Default)]
+struct NodeFilter {
+    /// The filter; `None` until first built
+    filter: Option<BloomFilter>,
+    /// IDs written while a replacement filter is being built
+    pending: Option<Vec<NodeId>>,
+}
+
+/// Locks held while a constraint is checked and the write it guards is
+/// committed, so two writers can't both pass the check
+#[derive(Default)]
+struct ConstraintLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+/// Guards taken by `ConstraintLocks::lock`, released on drop
+type ConstraintGuard = Vec<OwnedMutexGuard<()>>;
+
+impl ConstraintLocks {
+    /// Take the locks of several keys, in order so writers can't deadlock
+    async fn lock(&self, keys: impl IntoIterator<Item = String>) -> ConstraintGuard {
+        let keys: std::collections::BTreeSet<String> = keys.into_iter().collect();
+        let mut guards = Vec::with_capacity(keys.len());
+        for key in keys {
+            let lock = Arc::clone(self.locks.lock().entry(key).or_default());
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
 }
 
 impl Database {
@@ -94,6 +318,16 @@ impl Database {
             version: crate::FORMAT_VERSION,
             created_at: Timestamp::now(),
             bucket_url: None,
+            bucket_replicas: Vec::new(),
+            vector_indexes: Vec::new(),
+            field_indexes: Vec::new(),
+            vector_dimensions: Vec::new(),
+            cache: CacheConfig::default(),
+            mmap_reads: false,
+            write_batch_window_ms: 0,
+            maintenance: MaintenanceConfig::default(),
+            memory_limit_bytes: None,
+            sync_on_conflict: ConflictStrategy::default(),
         };
 
         // Write config file
@@ -103,7 +337,7 @@ impl Database {
 
         // Initialize local storage
         let local = LocalStorage::create(&path).await?;
-        let cache = CacheLayer::new(1024 * 1024 * 100); // 100MB cache
+        let (cache, memory, cache_memory) = cache_within_budget(&config, LOCAL_CACHE_BYTES);
 
         Ok(Self {
             path,
@@ -111,11 +345,28 @@ impl Database {
             local,
             bucket: None,
             cache,
+            changes: ChangeFeed::new(),
+            vector_indexes: RwLock::new(HashMap::new()),
+            field_indexes: RwLock::new(HashMap::new()),
+            schemas: RwLock::new(None),
+            relations: RwLock::new(None),
+            node_filter: RwLock::new(NodeFilter::default()),
+            type_stats: RwLock::new(HashMap::new()),
+            unique_locks: ConstraintLocks::default(),
+            relation_locks: ConstraintLocks::default(),
+            maintenance: Maintenance::new(),
+            memory,
+            _cache_memory: cache_memory,
         })
     }
 
     /// Open an existing database
+    ///
+    /// An `s3://` or `gs://` URL connects to the bucket read-only instead.
     pub async fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(url) = path.as_ref().to_str().filter(|p| p.starts_with("s3://") || p.starts_with("gs://")) {
+            return Self::connect_bucket(url, true).await;
+        }
         let path = path.as_ref().to_path_buf();
 
         // Load config
@@ -125,8 +376,9 @@ impl Database {
         let config: DatabaseConfig = toml::from_str(&config_str)?;
 
         // Open local storage
-        let local = LocalStorage::open(&path).await?;
-        let cache = CacheLayer::new(1024 * 1024 * 100);
+        let local = LocalStorage::open_with(&path, config.mmap_reads).await?;
+        local.set_flush_window(std::time::Duration::from_millis(config.write_batch_window_ms));
+        let (cache, memory, cache_memory) = cache_within_budget(&config, LOCAL_CACHE_BYTES);
 
         // Connect to bucket if configured
         let bucket = if let Some(ref url) = config.bucket_url {
@@ -141,42 +393,94 @@ impl Database {
             local,
             bucket,
             cache,
+            changes: ChangeFeed::new(),
+            vector_indexes: RwLock::new(HashMap::new()),
+            field_indexes: RwLock::new(HashMap::new()),
+            schemas: RwLock::new(None),
+            relations: RwLock::new(None),
+            node_filter: RwLock::new(NodeFilter::default()),
+            type_stats: RwLock::new(HashMap::new()),
+            unique_locks: ConstraintLocks::default(),
+            relation_locks: ConstraintLocks::default(),
+            maintenance: Maintenance::new(),
+            memory,
+            _cache_memory: cache_memory,
         })
     }
 
     /// Connect to a remote bucket database
+    ///
+    /// Pages of the data file are fetched from the bucket as they are read
+    /// and kept in the cache, sized, timed out and evicted as the bucket's
+    /// `[cache]` config says; its `warm_types` are read in before this
+    /// returns. Writes stay local to this handle.
     pub async fn connect_bucket(url: &str, readonly: bool) -> Result<Self> {
-        let bucket = BucketStorage::connect(url).await?;
+        let mut bucket = BucketStorage::connect(url).await?;
+        bucket.set_readonly(readonly);
         let config = bucket.load_config().await?;
+        let manifest = bucket.load_manifest().await?;
+        let data_file = manifest.files.get(DATA_FILE)
+            .with_context(|| format!("No database has been pushed to {}", url))?;
 
-        // Create temporary local cache
+        // Files other than the data file go in a temporary directory
         let temp_path = std::env::temp_dir().join(format!("aresadb-{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&temp_path)?;
+        std::fs::create_dir_all(temp_path.join(".aresadb"))?;
 
-        let local = LocalStorage::create(&temp_path).await?;
-        let cache = CacheLayer::new(1024 * 1024 * 500); // 500MB cache for remote
+        let (cache, memory, cache_memory) = cache_within_budget(&config, REMOTE_CACHE_BYTES);
+        let backend = remote::BucketBackend::new(data_file, Arc::clone(&cache), remote::BucketFetcher::start(url)?);
+        let local = LocalStorage::open_backend(&temp_path, backend)?;
+        let warm_types = config.cache.warm_types.clone();
 
-        Ok(Self {
+        let db = Self {
             path: temp_path,
             config: Arc::new(RwLock::new(config)),
             local,
             bucket: Some(bucket),
             cache,
-        })
+            changes: ChangeFeed::new(),
+            vector_indexes: RwLock::new(HashMap::new()),
+            field_indexes: RwLock::new(HashMap::new()),
+            schemas: RwLock::new(None),
+            relations: RwLock::new(None),
+            node_filter: RwLock::new(NodeFilter::default()),
+            type_stats: RwLock::new(HashMap::new()),
+            unique_locks: ConstraintLocks::default(),
+            relation_locks: ConstraintLocks::default(),
+            maintenance: Maintenance::new(),
+            memory,
+            _cache_memory: cache_memory,
+        };
+        for node_type in &warm_types {
+            db.warm_cache(node_type)?;
+        }
+        Ok(db)
+    }
+
+    /// Read every node of a type, so a bucket-connected database has its
+    /// pages cached; returns the number of nodes read
+    pub fn warm_cache(&self, node_type: &str) -> Result<u64> {
+        let mut nodes = 0;
+        self.local.scan_archived_nodes_by_type(node_type, |_| {
+            nodes += 1;
+            Ok(true)
+        })?;
+        Ok(nodes)
     }
 
     /// Get database status
     pub async fn status(&self) -> Result<DatabaseStatus> {
-        let config = self.config.read();
+        let name = self.config.read().name.clone();
         let stats = self.local.stats().await?;
 
         Ok(DatabaseStatus {
-            name: config.name.clone(),
+            name,
             path: self.path.display().to_string(),
             node_count: stats.node_count,
             edge_count: stats.edge_count,
             schema_count: stats.schema_count,
             size_bytes: stats.size_bytes,
+            cache: self.cache.stats(),
+            memory: self.memory.stats(),
         })
     }
 
@@ -186,13 +490,25 @@ impl Database {
     pub async fn insert_node(&self, node_type: &str, properties: serde_json::Value) -> Result<Node> {
         let props = Value::from_json(properties)?;
         let node = Node::new(node_type, props);
+        let dimensions = self.check_vectors(&[&node])?;
+        let _unique = self.lock_unique_types(&[&node]).await?;
+        self.check_schemas(&[&node], &[]).await?;
+        self.record_dimensions(dimensions)?;
         self.local.insert_node(&node).await?;
+        self.index_node(&node);
+        self.schema_written(&node.node_type);
+        self.changes.publish(ChangeKind::Insert, ChangeRecord::Node(node.clone()));
         Ok(node)
     }
 
     /// Get a node by ID
     pub async fn get_node(&self, id: &str) -> Result<Option<Node>> {
         let node_id = NodeId::parse(id)?;
+        if let Some(filter) = &self.node_filter.read().filter {
+            if !filter.may_contain(&node_id.uuid) {
+                return Ok(None);
+            }
+        }
         self.local.get_node(&node_id).await
     }
 
@@ -200,13 +516,143 @@ impl Database {
     pub async fn update_node(&self, id: &str, properties: serde_json::Value) -> Result<Node> {
         let node_id = NodeId::parse(id)?;
         let props = Value::from_json(properties)?;
-        self.local.update_node(&node_id, props).await
+
+        // Vectors are checked against the fields of the node's type, and the
+        // merged properties against its schema
+        let mut dimensions = Vec::new();
+        let mut unique = ConstraintGuard::new();
+        if let Value::Object(map) = &props {
+            let has_vectors = map.values().any(|v| matches!(v, Value::Vector(_)));
+            if has_vectors || !self.registered_schemas().await?.is_empty() {
+                if let Some(existing) = self.local.get_node(&node_id).await? {
+                    if has_vectors {
+                        let updated = Node::with_id(node_id.clone(), &existing.node_type, map.clone());
+                        dimensions = self.check_vectors(&[&updated])?;
+                    }
+                    let mut merged = existing;
+                    merged.properties.extend(map.clone());
+                    unique = self.lock_unique_types(&[&merged]).await?;
+                    self.check_schemas(&[&merged], &[]).await?;
+                }
+            }
+        }
+
+        self.record_dimensions(dimensions)?;
+        let node = self.local.update_node(&node_id, props).await?;
+        self.index_node(&node);
+        drop(unique);
+        self.schema_written(&node.node_type);
+        self.changes.publish(ChangeKind::Update, ChangeRecord::Node(node.clone()));
+        Ok(node)
     }
 
     /// Delete a node and its edges
     pub async fn delete_node(&self, id: &str) -> Result<()> {
         let node_id = NodeId::parse(id)?;
-        self.local.delete_node(&node_id).await
+
+        // Only pay for the extra read when someone is listening
+        let previous = if self.changes.has_subscribers() {
+            self.local.get_node(&node_id).await?
+        } else {
+            None
+        };
+
+        self.local.delete_node(&node_id).await?;
+        self.unindex_node(&node_id);
+        // The node's type is not known without a read; reloading is cheap
+        *self.schemas.write() = None;
+        *self.relations.write() = None;
+        if let Some(node) = previous {
+            self.changes.publish(ChangeKind::Delete, ChangeRecord::Node(node));
+        }
+        Ok(())
+    }
+
+    /// Merge the same properties into several nodes in one transaction, so
+    /// either every node is updated or none is
+    pub async fn update_nodes(&self, nodes: &[Node], properties: serde_json::Value) -> Result<Vec<Node>> {
+        let props = Value::from_json(properties)?;
+        let updated: Vec<Node> = nodes.iter()
+            .map(|node| {
+                let mut node = node.clone();
+                if let Value::Object(map) = &props {
+                    node.properties.extend(map.clone());
+                }
+                node.updated_at = Timestamp::now();
+                node
+            })
+            .collect();
+        let updated_refs: Vec<&Node> = updated.iter().collect();
+        let dimensions = self.check_vectors(&updated_refs)?;
+        let _unique = self.lock_unique_types(&updated_refs).await?;
+        self.check_schemas(&updated_refs, &[]).await?;
+        self.record_dimensions(dimensions)?;
+
+        let mut txn = self.local.begin_transaction()?;
+        for node in &updated {
+            txn.update_node(node.id.clone(), props.clone());
+        }
+        txn.commit()?;
+
+        for node in &updated {
+            self.index_node(node);
+            self.schema_written(&node.node_type);
+            self.changes.publish(ChangeKind::Update, ChangeRecord::Node(node.clone()));
+        }
+        Ok(updated)
+    }
+
+    /// Delete several nodes and their edges in one transaction, so either
+    /// every node is removed or none is
+    pub async fn delete_nodes(&self, nodes: &[Node]) -> Result<()> {
+        let mut txn = self.local.begin_transaction()?;
+        for node in nodes {
+            txn.delete_node_and_edges(node.id.clone());
+        }
+        txn.commit()?;
+
+        for node in nodes {
+            self.unindex_node(&node.id);
+            self.schema_written(&node.node_type);
+            self.changes.publish(ChangeKind::Delete, ChangeRecord::Node(node.clone()));
+        }
+        Ok(())
+    }
+
+    /// Delete some nodes and insert others in one transaction, so readers
+    /// see either all of the old nodes or all of the new ones
+    pub async fn replace_nodes(&self, old: Vec<Node>, new: Vec<Node>) -> Result<()> {
+        let new_refs: Vec<&Node> = new.iter().collect();
+        let dimensions = self.check_vectors(&new_refs)?;
+        let replaced: Vec<NodeId> = old.iter().map(|n| n.id.clone()).collect();
+        let _unique = self.lock_unique_types(&new_refs).await?;
+        self.check_schemas(&new_refs, &replaced).await?;
+        self.record_dimensions(dimensions)?;
+        let mut txn = self.local.begin_transaction()?;
+        for node in &old {
+            txn.delete_node(node.id.clone());
+        }
+        for node in &new {
+            txn.insert_node(node.clone());
+        }
+        txn.commit()?;
+        for node in old.iter().chain(&new) {
+            self.schema_written(&node.node_type);
+        }
+
+        for node in &old {
+            self.unindex_node(&node.id);
+        }
+        for node in &new {
+            self.index_node(node);
+        }
+        for node in old {
+            self.changes.publish(ChangeKind::Delete, ChangeRecord::Node(node));
+        }
+        for node in new {
+            self.changes.publish(ChangeKind::Insert, ChangeRecord::Node(node));
+        }
+        Ok(())
     }
 
     /// Get all nodes of a specific type
@@ -214,6 +660,22 @@ impl Database {
         self.local.get_nodes_by_type(node_type, limit).await
     }
 
+    /// Visit the nodes of a type one at a time until `visit` returns false
+    pub fn scan_by_type<F>(&self, node_type: &str, visit: F) -> Result<()>
+    where
+        F: FnMut(Node) -> Result<bool>,
+    {
+        self.local.scan_nodes_by_type(node_type, visit)
+    }
+
+    /// Visit the archived nodes of a type in place until `visit` returns false
+    pub fn scan_archived_by_type<F>(&self, node_type: &str, visit: F) -> Result<()>
+    where
+        F: FnMut(&ArchivedNode) -> Result<bool>,
+    {
+        self.local.scan_archived_nodes_by_type(node_type, visit)
+    }
+
     // ========== Edge Operations ==========
 
     /// Create an edge between two nodes
@@ -232,10 +694,36 @@ impl Database {
             .unwrap_or(Value::Object(Default::default()));
 
         let edge = Edge::new(from, to, edge_type, props);
+        let _relations = self.lock_strict_types(&[&edge]).await?;
+        self.check_relations(&[&edge]).await?;
         self.local.insert_edge(&edge).await?;
+        self.changes.publish(ChangeKind::Insert, ChangeRecord::Edge(edge.clone()));
         Ok(edge)
     }
 
+    /// Insert many edges in one transaction
+    pub async fn insert_edges(&self, edges: Vec<Edge>) -> Result<()> {
+        let edge_refs: Vec<&Edge> = edges.iter().collect();
+        let _relations = self.lock_strict_types(&edge_refs).await?;
+        self.check_relations(&edge_refs).await?;
+        let mut txn = self.local.begin_transaction()?;
+        for edge in &edges {
+            txn.insert_edge(edge.clone());
+        }
+        txn.commit()?;
+
+        for edge in edges {
+            self.changes.publish(ChangeKind::Insert, ChangeRecord::Edge(edge));
+        }
+        Ok(())
+    }
+
+    /// Get an edge by ID
+    pub async fn get_edge(&self, id: &str) -> Result<Option<Edge>> {
+        let edge_id = EdgeId::parse(id)?;
+        self.local.get_edge(&edge_id).await
+    }
+
     /// Get edges from a node
     pub async fn get_edges_from(&self, node_id: &str, edge_type: Option<&str>) -> Result<Vec<Edge>> {
         let id = NodeId::parse(node_id)?;
@@ -248,10 +736,40 @@ impl Database {
         self.local.get_edges_to(&id, edge_type).await
     }
 
+    /// Get many nodes by ID, reading them in parallel batches
+    ///
+    /// IDs with no node are skipped; the rest come back in the order given.
+    pub async fn get_nodes(&self, ids: &[NodeId], batch_size: usize) -> Result<Vec<Node>> {
+        let present: Option<Vec<NodeId>> = self.node_filter.read().filter.as_ref()
+            .map(|filter| ids.iter().filter(|id| filter.may_contain(&id.uuid)).cloned().collect());
+        self.local.get_nodes(present.as_deref().unwrap_or(ids), batch_size).await
+    }
+
+    /// Get the edges from each of many nodes, reading them in parallel batches
+    pub async fn get_edges_from_many(
+        &self,
+        ids: &[NodeId],
+        edge_type: Option<&str>,
+        batch_size: usize,
+    ) -> Result<Vec<Vec<Edge>>> {
+        self.local.get_edges_from_many(ids, edge_type, batch_size).await
+    }
+
     /// Delete an edge
     pub async fn delete_edge(&self, edge_id: &str) -> Result<()> {
         let id = EdgeId::parse(edge_id)?;
-        self.local.delete_edge(&id).await
+
+        let previous = if self.changes.has_subscribers() {
+            self.local.get_edge(&id).await?
+        } else {
+            None
+        };
+
+        self.local.delete_edge(&id).await?;
+        if let Some(edge) = previous {
+            self.changes.publish(ChangeKind::Delete, ChangeRecord::Edge(edge));
+        }
+        Ok(())
     }
 
     // ========== View Operations ==========
@@ -282,8 +800,83 @@ impl Database {
 
     // ========== Cloud Operations ==========
 
+    /// Buckets push and sync fan out to: the primary, then its replicas
+    pub fn bucket_destinations(&self) -> Vec<String> {
+        let config = self.config.read();
+        let mut destinations: Vec<String> = config.bucket_url.iter().cloned().collect();
+        for replica in &config.bucket_replicas {
+            if !destinations.contains(replica) {
+                destinations.push(replica.clone());
+            }
+        }
+        destinations
+    }
+
+    /// Add a bucket that push and sync also fan out to
+    ///
+    /// With no primary bucket yet, the first one added becomes it.
+    pub fn add_bucket_replica(&self, url: &str) -> Result<()> {
+        if !url.starts_with("s3://") && !url.starts_with("gs://") {
+            anyhow::bail!("Bucket URL must start with s3:// or gs://, got {}", url);
+        }
+        {
+            let mut config = self.config.write();
+            if config.bucket_url.is_none() {
+                config.bucket_url = Some(url.to_string());
+            } else if config.bucket_url.as_deref() != Some(url) && !config.bucket_replicas.iter().any(|r| r == url) {
+                config.bucket_replicas.push(url.to_string());
+            }
+        }
+        self.save_config()
+    }
+
+    /// Stop fanning out to a replica; returns whether it was configured
+    pub fn remove_bucket_replica(&self, url: &str) -> Result<bool> {
+        let removed = {
+            let mut config = self.config.write();
+            let before = config.bucket_replicas.len();
+            config.bucket_replicas.retain(|r| r != url);
+            config.bucket_replicas.len() != before
+        };
+        if removed {
+            self.save_config()?;
+        }
+        Ok(removed)
+    }
+
+    /// The last push or sync to each bucket, successful or not
+    pub fn destination_statuses(&self) -> Result<Vec<DestinationStatus>> {
+        delta::load_statuses(&self.path)
+    }
+
     /// Push database to a cloud bucket
-    pub async fn push_to_bucket(&self, url: &str) -> Result<()> {
+    pub async fn push_to_bucket(&self, url: &str) -> Result<SyncStats> {
+        self.push_to_bucket_with_progress(url, progress::ignore).await
+    }
+
+    /// Push database to a cloud bucket, reporting bytes uploaded
+    ///
+    /// The outcome is recorded in the destination's status. The first
+    /// bucket pushed to becomes the primary.
+    pub async fn push_to_bucket_with_progress<F>(&self, url: &str, on_progress: F) -> Result<SyncStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let result = self.push_to(url, on_progress).await;
+        delta::record_status(&self.path, url, "push", result.as_ref())?;
+        let stats = result?;
+
+        if self.config.read().bucket_url.is_none() {
+            self.config.write().bucket_url = Some(url.to_string());
+            self.save_config()?;
+        }
+        Ok(stats)
+    }
+
+    async fn push_to<F>(&self, url: &str, on_progress: F) -> Result<SyncStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
         let bucket = BucketStorage::connect(url).await?;
 
         // Save config
@@ -291,26 +884,59 @@ impl Database {
         bucket.save_config(&config).await?;
 
         // Upload data files
-        bucket.upload_from_local(&self.path).await?;
-
-        // Update local config with bucket URL
-        drop(config);
-        self.config.write().bucket_url = Some(url.to_string());
-        self.save_config()?;
-
-        Ok(())
+        bucket.upload_from_local_with_progress(&self.path, on_progress).await
     }
 
-    /// Sync local database with remote bucket
+    /// Sync local database with remote bucket, settling conflicts as the
+    /// config says
     pub async fn sync_with_bucket(&self, url: &str) -> Result<SyncStats> {
-        let bucket = BucketStorage::connect(url).await?;
+        self.sync_with_bucket_with_progress(url, None, progress::ignore).await
+    }
+
+    /// Sync local database with remote bucket, reporting bytes transferred
+    ///
+    /// `strategy` overrides the config's `sync_on_conflict`. The outcome
+    /// is recorded in the destination's status.
+    pub async fn sync_with_bucket_with_progress<F>(
+        &self,
+        url: &str,
+        strategy: Option<ConflictStrategy>,
+        on_progress: F,
+    ) -> Result<SyncStats>
+    where
+        F: FnMut(Progress) + Send,
+    {
+        let strategy = strategy.unwrap_or(self.config.read().sync_on_conflict);
 
         // Bidirectional sync
-        let stats = bucket.sync_with_local(&self.path).await?;
+        let result = match BucketStorage::connect(url).await {
+            Ok(bucket) => bucket.sync_with_local_with_progress(&self.path, strategy, on_progress).await,
+            Err(e) => Err(e),
+        };
+        delta::record_status(&self.path, url, "sync", result.as_ref())?;
+        let stats = result?;
+
+        // Pulled nodes are not in the built indexes; rebuild on next search
+        self.vector_indexes.write().clear();
+        self.field_indexes.write().clear();
 
         Ok(stats)
     }
 
+    /// What a sync with `url` would upload, download and delete, settling
+    /// conflicts as the config says
+    pub async fn sync_status(&self, url: &str) -> Result<SyncPreview> {
+        self.sync_status_with_strategy(url, None).await
+    }
+
+    /// What a sync with `url` would do; `strategy` overrides the config's
+    /// `sync_on_conflict`
+    pub async fn sync_status_with_strategy(&self, url: &str, strategy: Option<ConflictStrategy>) -> Result<SyncPreview> {
+        let bucket = BucketStorage::connect(url).await?;
+        let strategy = strategy.unwrap_or(self.config.read().sync_on_conflict);
+        bucket.preview_sync(&self.path, strategy).await
+    }
+
     /// Save config to disk
     fn save_config(&self) -> Result<()> {
         let config = self.config.read();
@@ -319,11 +945,29 @@ impl Database {
         Ok(())
     }
 
+    /// Subscribe to node and edge changes made through this handle
+    ///
+    /// Cascading edge removals from `delete_node` are not published
+    /// individually.
+    pub fn subscribe_changes(&self) -> tokio::sync::broadcast::Receiver<ChangeEvent> {
+        self.changes.subscribe()
+    }
+
     /// Get the local storage handle
     pub fn local(&self) -> &LocalStorage {
         &self.local
     }
 
+    /// Get cache usage statistics
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Memory limit shared by the cache, queries and index builds
+    pub fn memory(&self) -> &Arc<MemoryBudget> {
+        &self.memory
+    }
+
     /// Get database path
     pub fn path(&self) -> &Path {
         &self.path
@@ -352,11 +996,20 @@ impl Database {
         }
 
         let node = Node::new(node_type, props);
+        let dimensions = self.check_vectors(&[&node])?;
+        let _unique = self.lock_unique_types(&[&node]).await?;
+        self.check_schemas(&[&node], &[]).await?;
+        self.record_dimensions(dimensions)?;
         self.local.insert_node(&node).await?;
+        self.index_node(&node);
+        self.changes.publish(ChangeKind::Insert, ChangeRecord::Node(node.clone()));
         Ok(node)
     }
 
     /// Perform similarity search on vector embeddings
+    ///
+    /// Uses the field's vector index when one is declared with the same
+    /// metric (approximate), and compares against every node otherwise.
     pub async fn similarity_search(
         &self,
         query_vector: &[f32],
@@ -365,6 +1018,23 @@ impl Database {
         k: usize,
         metric: DistanceMetric,
     ) -> Result<Vec<SimilarityResult>> {
+        self.check_query(query_vector, node_type, embedding_field)?;
+        if let Some(index) = self.vector_index(node_type, embedding_field).await? {
+            if index.metric() == metric && index.dimension() == query_vector.len() {
+                let search = VectorSearch::new(metric);
+                let mut results = Vec::new();
+                for (id, _) in index.search(query_vector, k)? {
+                    let Some(node) = self.local.get_node(&id).await? else { continue };
+                    let vector = node.properties.get(embedding_field).and_then(|v| v.as_vector());
+                    if let Some((score, distance)) = vector.and_then(|v| search.compute_similarity(query_vector, v)) {
+                        results.push(SimilarityResult { node_id: id, score, distance });
+                    }
+                }
+                results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+                return Ok(results);
+            }
+        }
+
         // Get all nodes of the type
         let nodes = self.local.get_nodes_by_type(node_type, None).await?;
 
@@ -377,6 +1047,71 @@ impl Database {
         Ok(results)
     }
 
+    /// Perform similarity search over the nodes a predicate accepts
+    pub async fn similarity_search_where(
+        &self,
+        query_vector: &[f32],
+        node_type: &str,
+        embedding_field: &str,
+        k: usize,
+        metric: DistanceMetric,
+        predicate: impl Fn(&Node) -> bool,
+    ) -> Result<Vec<SimilarityResult>> {
+        self.check_query(query_vector, node_type, embedding_field)?;
+        let mut nodes = self.local.get_nodes_by_type(node_type, None).await?;
+        nodes.retain(|node| predicate(node));
+        let search = VectorSearch::new(metric);
+        Ok(search.search(query_vector, &nodes, embedding_field, k))
+    }
+
+    /// Perform similarity search over several embedding fields at once
+    ///
+    /// A node's score is the weighted mean of its per-field scores, a
+    /// field the node lacks counting as zero; its distance is the weighted
+    /// mean over the fields it has. Compares against every node.
+    pub async fn similarity_search_weighted(
+        &self,
+        query_vector: &[f32],
+        node_type: &str,
+        fields: &[(String, f64)],
+        k: usize,
+        metric: DistanceMetric,
+    ) -> Result<Vec<SimilarityResult>> {
+        let total_weight: f64 = fields.iter().map(|(_, weight)| weight).sum();
+        if fields.is_empty() || total_weight <= 0.0 {
+            anyhow::bail!("Weighted similarity search needs fields with positive total weight");
+        }
+        for (field, _) in fields {
+            self.check_query(query_vector, node_type, field)?;
+        }
+
+        let nodes = self.local.get_nodes_by_type(node_type, None).await?;
+        let search = VectorSearch::new(metric);
+        let mut results = Vec::new();
+        for node in nodes {
+            let (mut score, mut distance, mut matched_weight) = (0.0, 0.0, 0.0);
+            for (field, weight) in fields {
+                let vector = node.properties.get(field).and_then(|v| v.as_vector());
+                if let Some((s, d)) = vector.and_then(|v| search.compute_similarity(query_vector, v)) {
+                    score += weight * s;
+                    distance += weight * d;
+                    matched_weight += weight;
+                }
+            }
+            if matched_weight > 0.0 {
+                results.push(SimilarityResult {
+                    node_id: node.id,
+                    score: score / total_weight,
+                    distance: distance / matched_weight,
+                });
+            }
+        }
+
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(k);
+        Ok(results)
+    }
+
     /// Find similar nodes within a distance threshold
     pub async fn similarity_search_radius(
         &self,
@@ -386,12 +1121,738 @@ impl Database {
         max_distance: f64,
         metric: DistanceMetric,
     ) -> Result<Vec<SimilarityResult>> {
+        self.check_query(query_vector, node_type, embedding_field)?;
         let nodes = self.local.get_nodes_by_type(node_type, None).await?;
         let search = VectorSearch::new(metric);
         let results = search.search_radius(query_vector, &nodes, embedding_field, max_distance);
         Ok(results)
     }
 
+    /// Dimension recorded for an embedding field
+    pub fn vector_dimension(&self, node_type: &str, field: &str) -> Option<usize> {
+        self.config.read().vector_dimensions.iter()
+            .find(|d| d.node_type == node_type && d.field == field)
+            .map(|d| d.dimension)
+    }
+
+    /// Dimensions recorded for all embedding fields
+    pub fn vector_dimensions(&self) -> Vec<VectorDimension> {
+        self.config.read().vector_dimensions.clone()
+    }
+
+    /// Fix the dimension of an embedding field before any vector is
+    /// written to it; fails if vectors of another dimension are recorded
+    pub fn set_vector_dimension(&self, node_type: &str, field: &str, dimension: usize) -> Result<()> {
+        match self.vector_dimension(node_type, field) {
+            Some(existing) if existing != dimension => anyhow::bail!(
+                "Vector field '{}' of '{}' already has {} dimensions", field, node_type, existing
+            ),
+            Some(_) => Ok(()),
+            None => self.record_dimensions(vec![VectorDimension {
+                node_type: node_type.to_string(),
+                field: field.to_string(),
+                dimension,
+            }]),
+        }
+    }
+
+    /// Check the vectors of nodes about to be written against the recorded
+    /// dimensions, returning the fields they would record
+    fn check_vectors(&self, nodes: &[&Node]) -> Result<Vec<VectorDimension>> {
+        let mut new: Vec<VectorDimension> = Vec::new();
+        for node in nodes {
+            for (field, value) in &node.properties {
+                let Value::Vector(vector) = value else { continue };
+                let expected = self.vector_dimension(&node.node_type, field).or_else(|| {
+                    new.iter().find(|d| d.node_type == node.node_type && d.field == *field).map(|d| d.dimension)
+                });
+                match expected {
+                    Some(dimension) if dimension != vector.len() => anyhow::bail!(
+                        "Vector field '{}' of '{}' has {} dimensions, got a vector with {}",
+                        field, node.node_type, dimension, vector.len()
+                    ),
+                    Some(_) => {}
+                    None => new.push(VectorDimension {
+                        node_type: node.node_type.clone(),
+                        field: field.clone(),
+                        dimension: vector.len(),
+                    }),
+                }
+            }
+        }
+        Ok(new)
+    }
+
+    /// Schemas registered in the catalog, by node type
+    async fn registered_schemas(&self) -> Result<Arc<HashMap<String, Schema>>> {
+        if let Some(schemas) = self.schemas.read().clone() {
+            return Ok(schemas);
+        }
+        let nodes = self.local.get_nodes_by_type(Schema::NODE_TYPE, None).await?;
+        let schemas: Arc<HashMap<String, Schema>> =
+            Arc::new(nodes.iter().filter_map(Schema::from_node).map(|s| (s.name.clone(), s)).collect());
+        *self.schemas.write() = Some(Arc::clone(&schemas));
+        Ok(schemas)
+    }
+
+    /// Strict relationships registered in the catalog, by edge type
+    async fn strict_relations(&self) -> Result<Arc<HashMap<String, SchemaRelation>>> {
+        if let Some(relations) = self.relations.read().clone() {
+            return Ok(relations);
+        }
+        let nodes = self.local.get_nodes_by_type(SchemaRelation::NODE_TYPE, None).await?;
+        let relations: Arc<HashMap<String, SchemaRelation>> = Arc::new(
+            nodes
+                .iter()
+                .filter_map(SchemaRelation::from_node)
+                .filter(|r| r.strict)
+                .map(|r| (r.edge_type.clone(), r))
+                .collect(),
+        );
+        *self.relations.write() = Some(Arc::clone(&relations));
+        Ok(relations)
+    }
+
+    /// Lock the types of edges under strict relationships
+    ///
+    /// Held from `check_relations` until the edges are committed, so
+    /// concurrent writers can't both take a single-edge slot.
+    async fn lock_strict_types(&self, edges: &[&Edge]) -> Result<ConstraintGuard> {
+        let relations = self.strict_relations().await?;
+        let types = edges
+            .iter()
+            .filter(|edge| relations.contains_key(&edge.edge_type))
+            .map(|edge| edge.edge_type.clone());
+        Ok(self.relation_locks.lock(types).await)
+    }
+
+    /// Check edges about to be written against the cardinality of strict
+    /// relationships; edges of other types are not checked(&self, edges: &[&Edge]) -> Result<()> {
+        let relations = self.strict_relations().await?;
+        if relations.is_empty() {
+            return Ok(());
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for edge in edges {
+            let Some(relation) = relations.get(&edge.edge_type) else { continue };
+            let describe = |end: &str, id: &NodeId| {
+                format!(
+                    "{} node {} already has a {} edge ({} {} {})",
+                    end, id, edge.edge_type, relation.from_schema, relation.relation_type, relation.to_schema
+                )
+            };
+
+            if relation.single_source_edge() {
+                let taken = !seen.insert(("from", &edge.edge_type, edge.from.clone()))
+                    || !self.local.get_edges_from(&edge.from, Some(&edge.edge_type)).await?.is_empty();
+                if taken {
+                    return Err(CodedError::conflict(describe("Source", &edge.from)).into());
+                }
+            }
+            if relation.single_target_edge() {
+                let taken = !seen.insert(("to", &edge.edge_type, edge.to.clone()))
+                    || !self.local.get_edges_to(&edge.to, Some(&edge.edge_type)).await?.is_empty();
+                if taken {
+                    return Err(CodedError::conflict(describe("Target", &edge.to)).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fields of a type that no two nodes may share a value of
+    pub async fn unique_fields(&self, node_type: &str) -> Result<Vec<String>> {
+        let schemas = self.registered_schemas().await?;
+        Ok(schemas
+            .get(node_type)
+            .map(|schema| schema.unique_fields().into_iter().map(String::from).collect())
+            .unwrap_or_default())
+    }
+
+    /// Lock the types of nodes that have unique fields
+    ///
+    /// Held from `check_schemas` until the nodes are committed and indexed,
+    /// so concurrent writers of the same type can't both pass the check.
+    async fn lock_unique_types(&self, nodes: &[&Node]) -> Result<ConstraintGuard> {
+        let schemas = self.registered_schemas().await?;
+        let types = nodes
+            .iter()
+            .filter(|node| schemas.get(&node.node_type).is_some_and(|s| !s.unique_fields().is_empty()))
+            .map(|node| node.node_type.clone());
+        Ok(self.unique_locks.lock(types).await)
+    }
+
+    /// Check nodes against the schemas of their types; types without a
+    /// schema take any properties
+    ///
+    /// Unique fields are checked against stored nodes other than those in
+    /// `replacing`, and against each other. Callers hold
+    /// `lock_unique_types` across the check and the write.
+    async fn check_schemas(&self, nodes: &[&Node], replacing: &[NodeId]) -> Result<()> {
+        let schemas = self.registered_schemas().await?;
+        if schemas.is_empty() {
+            return Ok(());
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for node in nodes {
+            let Some(schema) = schemas.get(&node.node_type) else { continue };
+            if let Err(errors) = schema.validate(&node.properties) {
+                return Err(CodedError::new(
+                    ErrorCode::ConstraintViolation,
+                    format!("Node of type '{}' violates its schema: {}", node.node_type, errors.join("; ")),
+                )
+                .into());
+            }
+
+            for field in schema.unique_fields() {
+                let Some(value) = node.properties.get(field).filter(|v| !v.is_null()) else { continue };
+                let duplicate = !seen.insert((node.node_type.as_str(), field, value.to_json().to_string()))
+                    || self
+                        .find_by_property(&node.node_type, field, value)
+                        .await?
+                        .iter()
+                        .any(|other| other.id != node.id && !replacing.contains(&other.id));
+                if duplicate {
+                    return Err(CodedError::conflict(format!(
+                        "Duplicate value for unique field {}.{}: {}",
+                        node.node_type,
+                        field,
+                        value.to_json()
+                    ))
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the loaded schemas after the catalog changes
+    fn schema_written(&self, node_type: &str) {
+        if node_type == Schema::NODE_TYPE {
+            *self.schemas.write() = None;
+        }
+        if node_type == SchemaRelation::NODE_TYPE {
+            *self.relations.write() = None;
+        }
+    }
+
+    /// Record the dimensions of embedding fields about to be written
+    ///
+    /// Rechecked under the config lock, since another writer may have
+    /// recorded the same field since `check_vectors`; fails if it recorded
+    /// a different dimension.
+    fn record_dimensions(&self, dimensions: Vec<VectorDimension>) -> Result<()> {
+        if dimensions.is_empty() {
+            return Ok(());
+        }
+        {
+            let mut config = self.config.write();
+            let mut added = false;
+            for dimension in dimensions {
+                let recorded = config.vector_dimensions.iter()
+                    .find(|d| d.node_type == dimension.node_type && d.field == dimension.field);
+                match recorded {
+                    Some(d) if d.dimension != dimension.dimension => anyhow::bail!(
+                        "Vector field '{}' of '{}' has {} dimensions, got a vector with {}",
+                        dimension.field, dimension.node_type, d.dimension, dimension.dimension
+                    ),
+                    Some(_) => {}
+                    None => {
+                        config.vector_dimensions.push(dimension);
+                        added = true;
+                    }
+                }
+            }
+            if !added {
+                return Ok(());
+            }
+        }
+        self.save_config()
+    }
+
+    /// Reject a query vector that cannot match the field's vectors
+    fn check_query(&self, query_vector: &[f32], node_type: &str, field: &str) -> Result<()> {
+        match self.vector_dimension(node_type, field) {
+            Some(dimension) if dimension != query_vector.len() => anyhow::bail!(
+                "Vector field '{}' of '{}' has {} dimensions, got a query vector with {}",
+                field, node_type, dimension, query_vector.len()
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Declare a vector index on an embedding field and build it
+    ///
+    /// The declaration is saved with the database; a node type can have
+    /// an index per embedding field. Redeclaring a field replaces its index.
+    pub async fn create_vector_index(
+        &self,
+        node_type: &str,
+        field: &str,
+        metric: DistanceMetric,
+    ) -> Result<Option<IndexStats>> {
+        {
+            let mut config = self.config.write();
+            config.vector_indexes.retain(|spec| spec.node_type != node_type || spec.field != field);
+            config.vector_indexes.push(VectorIndexSpec {
+                node_type: node_type.to_string(),
+                field: field.to_string(),
+                metric,
+            });
+        }
+        self.save_config()?;
+        self.vector_indexes.write().remove(&(node_type.to_string(), field.to_string()));
+        Ok(self.vector_index(node_type, field).await?.map(|index| index.stats()))
+    }
+
+    /// Remove a vector index, returning whether one was declared
+    pub fn drop_vector_index(&self, node_type: &str, field: &str) -> Result<bool> {
+        let dropped = {
+            let mut config = self.config.write();
+            let before = config.vector_indexes.len();
+            config.vector_indexes.retain(|spec| spec.node_type != node_type || spec.field != field);
+            config.vector_indexes.len() != before
+        };
+        if dropped {
+            self.save_config()?;
+            self.vector_indexes.write().remove(&(node_type.to_string(), field.to_string()));
+        }
+        Ok(dropped)
+    }
+
+    /// Declared vector indexes
+    pub fn vector_indexes(&self) -> Vec<VectorIndexSpec> {
+        self.config.read().vector_indexes.clone()
+    }
+
+    /// The index of a field, built from the stored nodes on first use
+    ///
+    /// `None` when no index is declared on the field, or no node has a
+    /// vector in it yet to set the dimension.
+    async fn vector_index(&self, node_type: &str, field: &str) -> Result<Option<Arc<VectorIndex>>> {
+        let key = (node_type.to_string(), field.to_string());
+        if let Some(index) = self.vector_indexes.read().get(&key) {
+            return Ok(Some(index.clone()));
+        }
+        let Some(spec) = self.vector_indexes().into_iter().find(|spec| spec.node_type == node_type && spec.field == field) else {
+            return Ok(None);
+        };
+
+        // The vectors and their links are reserved as they are read, so a
+        // build too large for the memory limit fails before it is made
+        let mut memory = self.memory.reservation(format!("building the vector index on {}.{}", node_type, field));
+        let link_bytes = 16 * std::mem::size_of::<NodeId>() as u64;
+        let mut vectors: Vec<(NodeId, Vec<f32>)> = Vec::new();
+        self.local.scan_nodes_by_type(node_type, |mut node| {
+            if let Some(Value::Vector(vector)) = node.properties.remove(field) {
+                memory.grow(std::mem::size_of::<NodeId>() as u64 + (vector.len() * 4) as u64 + link_bytes)?;
+                vectors.push((node.id, vector));
+            }
+            Ok(true)
+        })?;
+        let Some(dimension) = vectors.first().map(|(_, v)| v.len()) else {
+            return Ok(None);
+        };
+
+        let index = VectorIndex::with_params(dimension, 16, 4, spec.metric);
+        for (id, vector) in vectors {
+            // Vectors of another dimension cannot be compared to queries
+            // the index serves, so they are left out
+            if vector.len() == dimension {
+                index.insert(id, vector)?;
+            }
+        }
+        let index = Arc::new(index);
+        self.vector_indexes.write().insert(key, index.clone());
+        Ok(Some(index))
+    }
+
+    /// Rebuild a vector index from the stored nodes
+    ///
+    /// `None` when no index is declared on the field, or no node has a
+    /// vector in it yet.
+    pub async fn rebuild_vector_index(&self, node_type: &str, field: &str) -> Result<Option<IndexStats>> {
+        self.vector_indexes.write().remove(&(node_type.to_string(), field.to_string()));
+        self.vector_index_stats(node_type, field).await
+    }
+
+    /// Statistics of a declared vector index, building it if needed
+    pub async fn vector_index_stats(&self, node_type: &str, field: &str) -> Result<Option<IndexStats>> {
+        Ok(self.vector_index(node_type, field).await?.map(|index| index.stats()))
+    }
+
+    // ========== Property and Full-Text Indexes ==========
+
+    /// Declare a property or full-text index on a field and build it
+    ///
+    /// The declaration is saved with the database. A field has at most one
+    /// such index; redeclaring it replaces the index.
+    pub async fn create_field_index<F>(
+        &self,
+        node_type: &str,
+        field: &str,
+        kind: FieldIndexKind,
+        on_progress: F,
+    ) -> Result<FieldIndexStats>
+    where
+        F: FnMut(Progress),
+    {
+        let spec = FieldIndexSpec { node_type: node_type.to_string(), field: field.to_string(), kind };
+        {
+            let mut config = self.config.write();
+            config.field_indexes.retain(|s| s.node_type != node_type || s.field != field);
+            config.field_indexes.push(spec.clone());
+        }
+        self.save_config()?;
+        Ok(self.build_field_index(&spec, on_progress).await?.stats())
+    }
+
+    /// Remove a property or full-text index, returning whether one was declared
+    pub fn drop_field_index(&self, node_type: &str, field: &str) -> Result<bool> {
+        let dropped = {
+            let mut config = self.config.write();
+            let before = config.field_indexes.len();
+            config.field_indexes.retain(|s| s.node_type != node_type || s.field != field);
+            config.field_indexes.len() != before
+        };
+        if dropped {
+            self.save_config()?;
+            self.field_indexes.write().remove(&(node_type.to_string(), field.to_string()));
+        }
+        Ok(dropped)
+    }
+
+    /// Declared property and full-text indexes
+    pub fn field_indexes(&self) -> Vec<FieldIndexSpec> {
+        self.config.read().field_indexes.clone()
+    }
+
+    /// Rebuild a property or full-text index from the stored nodes
+    ///
+    /// `None` when no index is declared on the field.
+    pub async fn rebuild_field_index<F>(&self, node_type: &str, field: &str, on_progress: F) -> Result<Option<FieldIndexStats>>
+    where
+        F: FnMut(Progress),
+    {
+        let Some(spec) = self.field_index_spec(node_type, field) else {
+            return Ok(None);
+        };
+        Ok(Some(self.build_field_index(&spec, on_progress).await?.stats()))
+    }
+
+    /// Statistics of a declared property or full-text index, building it if needed
+    pub async fn field_index_stats(&self, node_type: &str, field: &str) -> Result<Option<FieldIndexStats>> {
+        Ok(self.field_index(node_type, field).await?.map(|index| index.stats()))
+    }
+
+    /// Nodes of a type whose field equals `value`
+    ///
+    /// Uses the field's property index when one is declared, and scans the
+    /// type otherwise.
+    pub async fn find_by_property(&self, node_type: &str, field: &str, value: &Value) -> Result<Vec<Node>> {
+        if let Some(index) = self.field_index(node_type, field).await? {
+            if index.kind() == FieldIndexKind::Property && !value.is_null() {
+                return self.nodes_by_ids(index.lookup(value)).await;
+            }
+        }
+        let nodes = self.local.get_nodes_by_type(node_type, None).await?;
+        Ok(nodes.into_iter().filter(|node| node.properties.get(field).unwrap_or(&Value::Null) == value).collect())
+    }
+
+    /// Nodes of a type whose field contains every word of `query`
+    ///
+    /// Uses the field's full-text index when one is declared, and scans the
+    /// type otherwise.
+    pub async fn search_text(&self, node_type: &str, field: &str, query: &str) -> Result<Vec<Node>> {
+        if let Some(index) = self.field_index(node_type, field).await? {
+            if index.kind() == FieldIndexKind::FullText {
+                return self.nodes_by_ids(index.search(query)).await;
+            }
+        }
+        let words = tokenize(query);
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let nodes = self.local.get_nodes_by_type(node_type, None).await?;
+        Ok(nodes
+            .into_iter()
+            .filter(|node| match node.properties.get(field) {
+                Some(Value::String(text)) => {
+                    let text = tokenize(text);
+                    words.iter().all(|word| text.contains(word))
+                }
+                _ => false,
+            })
+            .collect())
+    }
+
+    /// The declaration of a field's property or full-text index
+    fn field_index_spec(&self, node_type: &str, field: &str) -> Option<FieldIndexSpec> {
+        self.config.read().field_indexes.iter().find(|s| s.node_type == node_type && s.field == field).cloned()
+    }
+
+    /// The index of a field, built from the stored nodes on first use
+    async fn field_index(&self, node_type: &str, field: &str) -> Result<Option<Arc<FieldIndex>>> {
+        if let Some(index) = self.field_indexes.read().get(&(node_type.to_string(), field.to_string())) {
+            return Ok(Some(index.clone()));
+        }
+        let Some(spec) = self.field_index_spec(node_type, field) else {
+            return Ok(None);
+        };
+        Ok(Some(self.build_field_index(&spec, progress::ignore).await?))
+    }
+
+    async fn build_field_index<F>(&self, spec: &FieldIndexSpec, on_progress: F) -> Result<Arc<FieldIndex>>
+    where
+        F: FnMut(Progress),
+    {
+        let nodes = self.local.get_nodes_by_type(&spec.node_type, None).await?;
+        let index = Arc::new(FieldIndex::build(&spec.field, spec.kind, &nodes, on_progress));
+        self.field_indexes.write().insert((spec.node_type.clone(), spec.field.clone()), index.clone());
+        Ok(index)
+    }
+
+    async fn nodes_by_ids(&self, ids: Vec<NodeId>) -> Result<Vec<Node>> {
+        let mut nodes = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(node) = self.local.get_node(&id).await? {
+                nodes.push(node);
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Bring a written node into the built indexes of its type
+    fn index_node(&self, node: &Node) {
+        {
+            let mut node_filter = self.node_filter.write();
+            if let Some(filter) = &mut node_filter.filter {
+                filter.insert(&node.id.uuid);
+            }
+            if let Some(pending) = &mut node_filter.pending {
+                pending.push(node.id.clone());
+            }
+        }
+
+        for ((node_type, _), index) in self.field_indexes.read().iter() {
+            if *node_type == node.node_type {
+                index.insert(node);
+            }
+        }
+
+        let indexes = self.vector_indexes.read();
+        for ((node_type, field), index) in indexes.iter() {
+            if *node_type != node.node_type {
+                continue;
+            }
+            index.remove(&node.id);
+            if let Some(vector) = node.properties.get(field).and_then(|v| v.as_vector()) {
+                if vector.len() == index.dimension() {
+                    let _ = index.insert(node.id.clone(), vector.to_vec());
+                }
+            }
+        }
+    }
+
+    /// Remove a deleted node from the built indexes
+    fn unindex_node(&self, id: &NodeId) {
+        for index in self.field_indexes.read().values() {
+            index.remove(id);
+        }
+        for index in self.vector_indexes.read().values() {
+            index.remove(id);
+        }
+    }
+
+    // ========== Maintenance ==========
+
+    /// Run every maintenance task each `interval_secs` until the database
+    /// is dropped
+    ///
+    /// Scheduled runs are skipped while maintenance is paused. The task
+    /// holds the database weakly, so it doesn't keep it open.
+    pub fn start_maintenance(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let db = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let Some(interval) = db.upgrade().map(|db| db.config.read().maintenance.interval_secs) else {
+                    return;
+                };
+                tokio::time::sleep(std::time::Duration::from_secs(interval.max(1))).await;
+                let Some(db) = db.upgrade() else {
+                    return;
+                };
+                for task in MaintenanceTask::ALL {
+                    if db.maintenance.is_paused() {
+                        break;
+                    }
+                    if let Err(e) = db.run_maintenance(task).await {
+                        tracing::warn!("Maintenance task {} failed: {}", task.name(), e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Run one maintenance task now
+    ///
+    /// Fails if the task is already running, or if maintenance is paused
+    /// before it completes; either way the outcome shows in
+    /// `maintenance_status`.
+    pub async fn run_maintenance(&self, task: MaintenanceTask) -> Result<TaskStatus> {
+        if !self.maintenance.begin(task) {
+            anyhow::bail!("Maintenance task {} is already running", task.name());
+        }
+        let started = std::time::Instant::now();
+        let mut throttle = Throttle::new(self.config.read().maintenance.io_limit);
+        let outcome = match task {
+            MaintenanceTask::Analyze => self.analyze(&mut throttle).await,
+            MaintenanceTask::BloomRebuild => self.rebuild_node_filter(&mut throttle).await,
+            MaintenanceTask::IndexCompaction => self.compact_indexes().await,
+            MaintenanceTask::TtlSweep => self.sweep_expired(&mut throttle).await,
+        };
+        let status = self.maintenance.finish(task, started, &outcome);
+        outcome.map(|_| status)
+    }
+
+    /// Stop scheduled maintenance, interrupting a running task at its next batch
+    pub fn pause_maintenance(&self) {
+        self.maintenance.set_paused(true);
+    }
+
+    /// Let scheduled maintenance run again
+    pub fn resume_maintenance(&self) {
+        self.maintenance.set_paused(false);
+    }
+
+    /// Whether maintenance is paused, and what each task last did
+    pub fn maintenance_status(&self) -> MaintenanceStatus {
+        self.maintenance.status()
+    }
+
+    /// Statistics of a node type from the last `MaintenanceTask::Analyze`
+    pub fn type_stats(&self, node_type: &str) -> Option<TypeStats> {
+        self.type_stats.read().get(node_type).cloned()
+    }
+
+    /// Wait for the IO budget before reading a batch of nodes
+    async fn maintenance_batch(&self, throttle: &mut Throttle, nodes: usize) -> Result<()> {
+        if self.maintenance.is_paused() {
+            anyhow::bail!("Maintenance paused");
+        }
+        throttle.consume(nodes).await;
+        Ok(())
+    }
+
+    /// Count nodes and non-null fields per type, returning nodes read
+    async fn analyze(&self, throttle: &mut Throttle) -> Result<u64> {
+        let mut stats = HashMap::new();
+        let mut read = 0;
+        for node_type in self.local.node_types()? {
+            let ids = self.local.node_ids_by_type(&node_type)?;
+            let mut type_stats = TypeStats {
+                nodes: ids.len() as u64,
+                analyzed_at: Some(Timestamp::now()),
+                ..Default::default()
+            };
+            for batch in ids.chunks(maintenance::BATCH_SIZE) {
+                self.maintenance_batch(throttle, batch.len()).await?;
+                for id in batch {
+                    let Some(node) = self.local.get_node(id).await? else {
+                        continue;
+                    };
+                    for (field, value) in &node.properties {
+                        if !matches!(value, Value::Null) {
+                            *type_stats.fields.entry(field.clone()).or_default() += 1;
+                        }
+                    }
+                }
+                read += batch.len() as u64;
+            }
+            stats.insert(node_type, type_stats);
+        }
+        *self.type_stats.write() = stats;
+        Ok(read)
+    }
+
+    /// Replace the node ID filter with one built from the stored IDs,
+    /// dropping deleted nodes and resizing for growth; returns IDs added
+    async fn rebuild_node_filter(&self, throttle: &mut Throttle) -> Result<u64> {
+        // Nodes written from here on are added to the new filter at the swap
+        self.node_filter.write().pending = Some(Vec::new());
+        let built = self.build_node_filter(throttle).await;
+
+        let mut node_filter = self.node_filter.write();
+        let pending = node_filter.pending.take().unwrap_or_default();
+        let (mut filter, count) = built?;
+        for id in &pending {
+            filter.insert(&id.uuid);
+        }
+        node_filter.filter = Some(filter);
+        Ok(count + pending.len() as u64)
+    }
+
+    async fn build_node_filter(&self, throttle: &mut Throttle) -> Result<(BloomFilter, u64)> {
+        let mut ids = Vec::new();
+        for node_type in self.local.node_types()? {
+            ids.extend(self.local.node_ids_by_type(&node_type)?);
+        }
+        // Room to double before the next rebuild
+        let mut filter = BloomFilter::new((ids.len() * 2).max(1024), 0.01);
+        for batch in ids.chunks(maintenance::BATCH_SIZE) {
+            self.maintenance_batch(throttle, batch.len()).await?;
+            for id in batch {
+                filter.insert(&id.uuid);
+            }
+        }
+        Ok((filter, ids.len() as u64))
+    }
+
+    /// Compact the built field and vector indexes, returning how many
+    async fn compact_indexes(&self) -> Result<u64> {
+        let field_indexes: Vec<Arc<FieldIndex>> = self.field_indexes.read().values().cloned().collect();
+        let vector_indexes: Vec<Arc<VectorIndex>> = self.vector_indexes.read().values().cloned().collect();
+        for index in &field_indexes {
+            index.compact();
+            tokio::task::yield_now().await;
+        }
+        for index in &vector_indexes {
+            index.compact();
+            tokio::task::yield_now().await;
+        }
+        Ok((field_indexes.len() + vector_indexes.len()) as u64)
+    }
+
+    /// Delete nodes older than their type's time to live, returning how many
+    async fn sweep_expired(&self, throttle: &mut Throttle) -> Result<u64> {
+        let rules = self.config.read().maintenance.ttl.clone();
+        let now = Timestamp::now().millis;
+        let mut deleted = 0;
+        for rule in rules {
+            let cutoff = now.saturating_sub((rule.max_age_secs as i64).saturating_mul(1000));
+            let ids = self.local.node_ids_by_type(&rule.node_type)?;
+            for batch in ids.chunks(maintenance::BATCH_SIZE) {
+                self.maintenance_batch(throttle, batch.len()).await?;
+                for id in batch {
+                    let expired = self.local.get_node(id).await?
+                        .is_some_and(|node| node.created_at.millis < cutoff);
+                    if !expired {
+                        continue;
+                    }
+                    match self.delete_node(&id.to_string()).await {
+                        Ok(()) => deleted += 1,
+                        // Deleted by someone else since it was read
+                        Err(e) if crate::error::classify(&e) == ErrorCode::NotFound => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
     /// Get a node and its embedding
     pub async fn get_node_with_embedding(
         &self,
@@ -438,6 +1899,290 @@ mod tests {
         let retrieved = retrieved.unwrap();
         assert_eq!(retrieved.node_type, "user");
     }
-}
 
+    #[tokio::test]
+    async fn test_named_embeddings() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "testdb").await.unwrap();
+
+        let mut ids = Vec::new();
+        for (name, title, body) in [
+            ("title match", [1.0, 0.0], [0.0, 1.0]),
+            ("body match", [0.0, 1.0], [1.0, 0.0]),
+            ("both", [0.8, 0.6], [0.8, 0.6]),
+        ] {
+            let mut props = Value::from_json(serde_json::json!({ "name": name })).unwrap();
+            if let Value::Object(ref mut map) = props {
+                map.insert("title_embedding".to_string(), Value::Vector(title.to_vec()));
+                map.insert("body_embedding".to_string(), Value::Vector(body.to_vec()));
+            }
+            let node = Node::new("doc", props);
+            db.replace_nodes(Vec::new(), vec![node.clone()]).await.unwrap();
+            ids.push(node.id);
+        }
+
+        let query = [1.0, 0.0];
+        let cosine = DistanceMetric::Cosine;
+        let top = |results: Vec<SimilarityResult>| results[0].node_id.clone();
+        assert_eq!(top(db.similarity_search(&query, "doc", "title_embedding", 3, cosine).await.unwrap()), ids[0]);
+        assert_eq!(top(db.similarity_search(&query, "doc", "body_embedding", 3, cosine).await.unwrap()), ids[1]);
+
+        let weights = |title: f64, body: f64| vec![("title_embedding".to_string(), title), ("body_embedding".to_string(), body)];
+        let even = db.similarity_search_weighted(&query, "doc", &weights(0.5, 0.5), 3, cosine).await.unwrap();
+        assert_eq!(top(even), ids[2]);
+        let body = db.similarity_search_weighted(&query, "doc", &weights(0.1, 0.9), 3, cosine).await.unwrap();
+        assert_eq!(top(body), ids[1]);
+
+        // Each field gets its own index, kept current by writes
+        db.create_vector_index("doc", "title_embedding", cosine).await.unwrap();
+        db.create_vector_index("doc", "body_embedding", cosine).await.unwrap();
+        assert_eq!(db.vector_indexes().len(), 2);
+        let indexed = db.similarity_search(&query, "doc", "body_embedding", 1, cosine).await.unwrap();
+        assert_eq!(top(indexed), ids[1]);
+        db.delete_node(&ids[1].to_string()).await.unwrap();
+        let indexed = db.similarity_search(&query, "doc", "body_embedding", 1, cosine).await.unwrap();
+        assert_eq!(top(indexed), ids[2]);
+
+        // Declarations persist with the database
+        drop(db);
+        let db = Database::open(temp.path()).await.unwrap();
+        assert_eq!(db.vector_indexes().len(), 2);
+        assert!(db.drop_vector_index("doc", "title_embedding").unwrap());
+        assert!(!db.drop_vector_index("doc", "title_embedding").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_vector_dimensions() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "testdb").await.unwrap();
+
+        // The first vector fixes the field's dimension
+        let node = db.insert_with_embedding("doc", serde_json::json!({}), "embedding", vec![1.0, 0.0]).await.unwrap();
+        assert_eq!(db.vector_dimension("doc", "embedding"), Some(2));
+        let err = db.insert_with_embedding("doc", serde_json::json!({}), "embedding", vec![1.0, 0.0, 0.0]).await.unwrap_err();
+        assert!(err.to_string().contains("has 2 dimensions"));
+        assert_eq!(db.get_all_by_type("doc", None).await.unwrap().len(), 1);
+
+        // Other fields and node types have their own
+        db.insert_with_embedding("doc", serde_json::json!({}), "title_embedding", vec![1.0; 3]).await.unwrap();
+        db.insert_with_embedding("note", serde_json::json!({}), "embedding", vec![1.0; 4]).await.unwrap();
+
+        let wrong = Node::with_id(NodeId::new(), "doc", [("embedding".to_string(), Value::Vector(vec![0.5]))].into());
+        assert!(db.replace_nodes(Vec::new(), vec![wrong]).await.is_err());
+        let err = db.similarity_search(&[1.0], "doc", "embedding", 5, DistanceMetric::Cosine).await.unwrap_err();
+        assert!(err.to_string().contains("query vector with 1"));
+        assert_eq!(db.similarity_search(&[1.0, 0.0], "doc", "embedding", 5, DistanceMetric::Cosine).await.unwrap().len(), 1);
+
+        // Updates are checked against the node's type; declared dimensions persist
+        let id = node.id.to_string();
+        assert!(db.update_node(&id, serde_json::json!({"embedding": {"$vector": [1.0]}})).await.is_err());
+        db.update_node(&id, serde_json::json!({"embedding": {"$vector": [0.0, 1.0]}})).await.unwrap();
+        assert!(db.set_vector_dimension("doc", "embedding", 5).is_err());
+        db.set_vector_dimension("doc", "summary_embedding", 8).unwrap();
+        drop(db);
+        let db = Database::open(temp.path()).await.unwrap();
+        assert_eq!(db.vector_dimension("doc", "summary_embedding"), Some(8));
+        assert_eq!(db.vector_dimensions().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_vector_dimension_race() {
+        let temp = TempDir::new().unwrap();
+        let db = Arc::new(Database::create(temp.path(), "testdb").await.unwrap());
+
+        // Racing first writes of different dimensions: one fixes the field
+        db.local().set_flush_window(std::time::Duration::from_millis(20));
+        let results = futures::future::join_all((1..=4).map(|n| {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move {
+                db.insert_with_embedding("doc", serde_json::json!({}), "embedding", vec![1.0; n]).await
+            })
+        }))
+        .await;
+
+        let inserted = results.into_iter().filter(|r| r.as_ref().unwrap().is_ok()).count();
+        assert_eq!(inserted, 1);
+        assert_eq!(db.vector_dimensions().len(), 1);
+        assert_eq!(db.get_all_by_type("doc", None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_schema_constraints() {
+        let dir = TempDir::new().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = crate::schema::SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_schema("users", "name:string:required, age:int:min=0:max=150").await.unwrap();
+
+        let ada = db.insert_node("users", serde_json::json!({"name": "Ada", "age": 36})).await.unwrap();
+        let err = db.insert_node("users", serde_json::json!({"age": 200})).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err), ErrorCode::ConstraintViolation);
+        assert!(err.to_string().contains("Missing required field: name"));
+        assert!(err.to_string().contains("must be at most 150"));
+
+        // Updates are checked with the properties they keep
+        let id = ada.id.to_string();
+        assert!(db.update_node(&id, serde_json::json!({"age": -1})).await.is_err());
+        assert!(db.update_node(&id, serde_json::json!({"name": null})).await.is_err());
+        db.update_node(&id, serde_json::json!({"age": 37})).await.unwrap();
+
+        // Unique fields reject a second node with the same value
+        manager.create_schema("accounts", "email:string unique").await.unwrap();
+        assert_eq!(db.field_indexes().len(), 1);
+        let a = db.insert_node("accounts", serde_json::json!({"email": "a@example.com"})).await.unwrap();
+        let err = db.insert_node("accounts", serde_json::json!({"email": "a@example.com"})).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err), ErrorCode::Conflict);
+        let b = db.insert_node("accounts", serde_json::json!({"email": "b@example.com"})).await.unwrap();
+        assert!(db.update_node(&b.id.to_string(), serde_json::json!({"email": "a@example.com"})).await.is_err());
+        db.update_node(&a.id.to_string(), serde_json::json!({"email": "a@example.com"})).await.unwrap();
+
+        // Check expressions are evaluated on every write
+        let checks = vec!["qty >= 0 AND qty < 100".to_string(), "sku LIKE 'SKU-%'".to_string()];
+        manager.create_schema_with_checks("items", "sku:string, qty:int", &checks).await.unwrap();
+        db.insert_node("items", serde_json::json!({"sku": "SKU-1", "qty": 5})).await.unwrap();
+        db.insert_node("items", serde_json::json!({"sku": "SKU-2"})).await.unwrap();
+        let err = db.insert_node("items", serde_json::json!({"sku": "X-3", "qty": 100})).await.unwrap_err();
+        assert_eq!(crate::error::classify(&err), ErrorCode::ConstraintViolation);
+        assert!(err.to_string().contains("Check failed: qty >= 0 AND qty < 100"));
+        assert!(err.to_string().contains("Check failed: sku LIKE 'SKU-%'"));
+        assert!(manager.create_schema_with_checks("bad", "qty:int", &["qty > 0 OR qty < -5".to_string()]).await.is_err());
+
+        // Other types are unconstrained, and dropping the schema lifts it
+        db.insert_node("notes", serde_json::json!({"age": -5})).await.unwrap();
+        manager.drop_schema("users", true).await.unwrap();
+        db.insert_node("users", serde_json::json!({"age": 200})).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unique_insert_race() {
+        let dir = TempDir::new().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let manager = crate::schema::SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_schema("accounts", "email:string unique").await.unwrap();
+
+        // A flush window puts the racing writers into the same group commit
+        db.local().set_flush_window(std::time::Duration::from_millis(20));
+        let results = futures::future::join_all((0..8).map(|_| {
+            let db = Arc::clone(&db);
+            tokio::spawn(async move {
+                db.insert_node("accounts", serde_json::json!({"email": "a@example.com"})).await
+            })
+        }))
+        .await;
+
+        let inserted = results.into_iter().filter(|r| r.as_ref().unwrap().is_ok()).count();
+        assert_eq!(inserted, 1);
+        assert_eq!(db.get_all_by_type("accounts", None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_multi_node_writes() {
+        let dir = TempDir::new().unwrap();
+        let db = Arc::new(Database::create(dir.path(), "test").await.unwrap());
+        let a = db.insert_node("items", serde_json::json!({"name": "a", "qty": 1})).await.unwrap();
+        let b = db.insert_node("items", serde_json::json!({"qty": 2})).await.unwrap();
+        db.create_edge(&a.id.to_string(), &b.id.to_string(), "next", None).await.unwrap();
+
+        // A node failing its schema leaves every node unchanged
+        let manager = crate::schema::SchemaManager::from_shared(Arc::clone(&db));
+        manager.create_schema("items", "name:string:required, qty:int").await.unwrap();
+        let nodes = vec![a.clone(), b.clone()];
+        assert!(db.update_nodes(&nodes, serde_json::json!({"qty": 5})).await.is_err());
+        let qty = |node: Option<Node>| node.unwrap().get("qty").cloned();
+        assert_eq!(qty(db.get_node(&a.id.to_string()).await.unwrap()), Some(Value::Int(1)));
+
+        let updated = db.update_nodes(&nodes, serde_json::json!({"name": "x", "qty": 5})).await.unwrap();
+        assert_eq!(updated.len(), 2);
+        assert_eq!(qty(db.get_node(&b.id.to_string()).await.unwrap()), Some(Value::Int(5)));
+
+        db.delete_nodes(&nodes).await.unwrap();
+        assert!(db.get_all_by_type("items", None).await.unwrap().is_empty());
+        assert!(db.get_edges_from(&a.id.to_string(), None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_field_indexes() {
+        let dir = TempDir::new().unwrap();
+        let db = Database::create(dir.path(), "test").await.unwrap();
+        let a = db.insert_node("users", serde_json::json!({"email": "a@example.com", "bio": "Rust graph nerd"})).await.unwrap();
+        db.insert_node("users", serde_json::json!({"email": "b@example.com", "bio": "Likes graphs and tea"})).await.unwrap();
+
+        let stats = db.create_field_index("users", "email", FieldIndexKind::Property, progress::ignore).await.unwrap();
+        assert_eq!(stats.keys, 2);
+        db.create_field_index("users", "bio", FieldIndexKind::FullText, progress::ignore).await.unwrap();
+
+        // Writes after the build keep the indexes current
+        let c = db.insert_node("users", serde_json::json!({"email": "c@example.com", "bio": "Graph theory"})).await.unwrap();
+        db.update_node(&a.id.to_string(), serde_json::json!({"email": "a2@example.com"})).await.unwrap();
+        let email = |e: &str| Value::String(e.to_string());
+        assert!(db.find_by_property("users", "email", &email("a@example.com")).await.unwrap().is_empty());
+        assert_eq!(db.find_by_property("users", "email", &email("a2@example.com")).await.unwrap()[0].id, a.id);
+        assert_eq!(db.search_text("users", "bio", "GRAPH").await.unwrap().len(), 2);
+        db.delete_node(&c.id.to_string()).await.unwrap();
+        assert_eq!(db.search_text("users", "bio", "graph").await.unwrap().len(), 1);
+
+        // Declarations survive reopening; the queries use the index
+        drop(db);
+        let db = Database::open(dir.path()).await.unwrap();
+        assert_eq!(db.field_indexes().len(), 2);
+        assert_eq!(db.field_index_stats("users", "email").await.unwrap().unwrap().keys, 2);
+        let engine = crate::query::QueryEngine::new(db);
+        let result = engine.execute_sql("SELECT * FROM users WHERE email = 'b@example.com'", None).await.unwrap();
+        assert_eq!(result.rows.len(), 1);
+    }
 
+    #[tokio::test]
+    async fn test_maintenance() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "testdb").await.unwrap();
+        let alice = db.insert_node("user", serde_json::json!({"name": "Alice", "age": 30})).await.unwrap();
+        db.insert_node("user", serde_json::json!({"name": "Bob"})).await.unwrap();
+        let event = db.insert_node("event", serde_json::json!({"kind": "login"})).await.unwrap();
+
+        db.run_maintenance(MaintenanceTask::Analyze).await.unwrap();
+        let stats = db.type_stats("user").unwrap();
+        assert_eq!(stats.nodes, 2);
+        assert_eq!(stats.fields.get("age"), Some(&1));
+
+        // The filter answers for missing IDs and keeps up with new nodes
+        let status = db.run_maintenance(MaintenanceTask::BloomRebuild).await.unwrap();
+        assert_eq!(status.last_items, 3);
+        assert!(db.get_node(&NodeId::new().to_string()).await.unwrap().is_none());
+        let carol = db.insert_node("user", serde_json::json!({"name": "Carol"})).await.unwrap();
+        for node in [&alice, &carol] {
+            assert!(db.get_node(&node.id.to_string()).await.unwrap().is_some());
+        }
+
+        db.config.write().maintenance.ttl.push(TtlRule { node_type: "event".to_string(), max_age_secs: 0 });
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let status = db.run_maintenance(MaintenanceTask::TtlSweep).await.unwrap();
+        assert_eq!(status.last_items, 1);
+        assert!(db.get_node(&event.id.to_string()).await.unwrap().is_none());
+        assert_eq!(db.get_all_by_type("user", None).await.unwrap().len(), 3);
+
+        db.pause_maintenance();
+        assert!(db.run_maintenance(MaintenanceTask::Analyze).await.is_err());
+        let status = db.maintenance_status();
+        assert!(status.paused);
+        assert_eq!(status.tasks[0].runs, 2);
+        assert_eq!(status.tasks[0].last_error.as_deref(), Some("Maintenance paused"));
+    }
+
+    #[tokio::test]
+    async fn test_mmap_reads() {
+        let temp = TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "testdb").await.unwrap();
+        let alice = db.insert_node("user", serde_json::json!({"name": "Alice"})).await.unwrap();
+        db.config.write().mmap_reads = true;
+        db.save_config().unwrap();
+        drop(db);
+
+        let db = Database::open(temp.path()).await.unwrap();
+        assert!(db.config.read().mmap_reads);
+        let bob = db.insert_node("user", serde_json::json!({"name": "Bob"})).await.unwrap();
+        for node in [&alice, &bob] {
+            let retrieved = db.get_node(&node.id.to_string()).await.unwrap().unwrap();
+            assert_eq!(retrieved.properties, node.properties);
+        }
+        assert_eq!(db.get_all_by_type("user", None).await.unwrap().len(), 2);
+    }
+}
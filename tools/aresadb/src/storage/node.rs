@@ -212,11 +212,21 @@ impl<'de> SerdeDeserialize<'de> for Timestamp {
 
 /// A flexible value type that can hold any data
 ///
-/// Note: We use serde for serialization instead of rkyv for the Value type
-/// because rkyv has issues with recursive types. The performance impact is
-/// minimal since we batch serialize nodes/edges anyway.
-#[derive(Debug, Clone, PartialEq, SerdeSerialize, SerdeDeserialize)]
-#[serde(untagged)]
+/// Values are archived with rkyv as part of a stored node. The recursive
+/// `Array` and `Object` variants omit the derived bounds, which would
+/// otherwise never resolve, and spell out what their containers need.
+///
+/// Human-readable formats (JSON) use an untagged representation so exports
+/// and the HTTP API stay plain JSON. Binary formats such as bincode cannot
+/// deserialize untagged enums, so they get an externally tagged encoding
+/// instead.
+#[derive(Debug, Clone, PartialEq, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive(bound(serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::Serializer"))]
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: rkyv::bytecheck::Error"
+))]
+#[archive_attr(derive(Debug))]
 pub enum Value {
     Null,
     Bool(bool),
@@ -226,12 +236,143 @@ pub enum Value {
     Bytes(Vec<u8>),
     /// Vector embedding for similarity search (RAG/ML)
     Vector(Vec<f32>),
+    Array(
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        Vec<Value>,
+    ),
+    Object(
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        BTreeMap<String, Value>,
+    ),
+}
+
+impl ArchivedValue {
+    /// Deserialize the archived value
+    pub fn to_value(&self) -> Value {
+        match Deserialize::<Value, _>::deserialize(self, &mut rkyv::Infallible) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+}
+
+/// Borrowed, externally tagged mirror of `Value` for binary formats
+#[derive(SerdeSerialize)]
+enum TaggedValueRef<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Vector(&'a [f32]),
+    Array(&'a [Value]),
+    Object(&'a BTreeMap<String, Value>),
+}
+
+/// Owned, externally tagged mirror of `Value` for binary formats
+#[derive(SerdeDeserialize)]
+enum TaggedValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Vector(Vec<f32>),
     Array(Vec<Value>),
     Object(BTreeMap<String, Value>),
 }
 
+/// Untagged mirror of `Value` for human-readable formats
+#[derive(SerdeDeserialize)]
+#[serde(untagged)]
+enum UntaggedValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Vector(Vec<f32>),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+impl SerdeSerialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            return match self {
+                Value::Null => serializer.serialize_unit(),
+                Value::Bool(b) => serializer.serialize_bool(*b),
+                Value::Int(i) => serializer.serialize_i64(*i),
+                Value::Float(f) => serializer.serialize_f64(*f),
+                Value::String(s) => serializer.serialize_str(s),
+                Value::Bytes(b) => SerdeSerialize::serialize(b, serializer),
+                Value::Vector(v) => SerdeSerialize::serialize(v, serializer),
+                Value::Array(a) => SerdeSerialize::serialize(a, serializer),
+                Value::Object(o) => SerdeSerialize::serialize(o, serializer),
+            };
+        }
+
+        let tagged = match self {
+            Value::Null => TaggedValueRef::Null,
+            Value::Bool(b) => TaggedValueRef::Bool(*b),
+            Value::Int(i) => TaggedValueRef::Int(*i),
+            Value::Float(f) => TaggedValueRef::Float(*f),
+            Value::String(s) => TaggedValueRef::String(s),
+            Value::Bytes(b) => TaggedValueRef::Bytes(b),
+            Value::Vector(v) => TaggedValueRef::Vector(v),
+            Value::Array(a) => TaggedValueRef::Array(a),
+            Value::Object(o) => TaggedValueRef::Object(o),
+        };
+        SerdeSerialize::serialize(&tagged, serializer)
+    }
+}
+
+impl<'de> SerdeDeserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            return Ok(match <UntaggedValue as SerdeDeserialize>::deserialize(deserializer)? {
+                UntaggedValue::Null => Value::Null,
+                UntaggedValue::Bool(b) => Value::Bool(b),
+                UntaggedValue::Int(i) => Value::Int(i),
+                UntaggedValue::Float(f) => Value::Float(f),
+                UntaggedValue::String(s) => Value::String(s),
+                // `[]` fits bytes first, but an empty list is what `from_json` reads
+                UntaggedValue::Bytes(b) if b.is_empty() => Value::Array(Vec::new()),
+                UntaggedValue::Bytes(b) => Value::Bytes(b),
+                UntaggedValue::Vector(v) => Value::Vector(v),
+                UntaggedValue::Array(a) => Value::Array(a),
+                UntaggedValue::Object(o) => Value::Object(o),
+            });
+        }
+
+        Ok(match <TaggedValue as SerdeDeserialize>::deserialize(deserializer)? {
+            TaggedValue::Null => Value::Null,
+            TaggedValue::Bool(b) => Value::Bool(b),
+            TaggedValue::Int(i) => Value::Int(i),
+            TaggedValue::Float(f) => Value::Float(f),
+            TaggedValue::String(s) => Value::String(s),
+            TaggedValue::Bytes(b) => Value::Bytes(b),
+            TaggedValue::Vector(v) => Value::Vector(v),
+            TaggedValue::Array(a) => Value::Array(a),
+            TaggedValue::Object(o) => Value::Object(o),
+        })
+    }
+}
+
 /// Distance metrics for vector similarity search
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerdeSerialize, SerdeDeserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum DistanceMetric {
     /// Cosine similarity (1 - cosine_distance)
     Cosine,
@@ -412,6 +553,18 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Approximate bytes the value occupies in memory
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Value>() + match self {
+            Value::String(s) => s.len(),
+            Value::Bytes(b) => b.len(),
+            Value::Vector(v) => v.len() * std::mem::size_of::<f32>(),
+            Value::Array(items) => items.iter().map(Value::memory_size).sum(),
+            Value::Object(map) => map.iter().map(|(k, v)| k.len() + v.memory_size()).sum(),
+            _ => 0,
+        }
+    }
 }
 
 impl Value {
@@ -495,6 +648,36 @@ impl Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::Float(f)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
 impl Default for Value {
     fn default() -> Self {
         Value::Null
@@ -562,7 +745,9 @@ mod base64 {
 }
 
 /// A node in the property graph
-#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
+#[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize, Archive, Serialize, Deserialize)]
+#[archive(check_bytes)]
+#[archive_attr(derive(Debug))]
 pub struct Node {
     /// Unique identifier
     pub id: NodeId,
@@ -610,6 +795,13 @@ impl Node {
         }
     }
 
+    /// Approximate bytes the node occupies in memory
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Node>()
+            + self.node_type.len()
+            + self.properties.iter().map(|(k, v)| k.len() + v.memory_size()).sum::<usize>()
+    }
+
     /// Get a property value
     pub fn get(&self, key: &str) -> Option<&Value> {
         self.properties.get(key)
@@ -638,6 +830,26 @@ impl Node {
     }
 }
 
+impl ArchivedNode {
+    /// The node's ID
+    pub fn node_id(&self) -> NodeId {
+        NodeId { uuid: self.id.uuid }
+    }
+
+    /// Get an archived property value
+    pub fn get(&self, key: &str) -> Option<&ArchivedValue> {
+        self.properties.get(key)
+    }
+
+    /// Deserialize the archived node
+    pub fn to_node(&self) -> Node {
+        match Deserialize::<Node, _>::deserialize(self, &mut rkyv::Infallible) {
+            Ok(node) => node,
+            Err(never) => match never {},
+        }
+    }
+}
+
 /// An edge connecting two nodes
 #[derive(Debug, Clone, SerdeSerialize, SerdeDeserialize)]
 pub struct Edge {
@@ -742,6 +954,32 @@ mod tests {
         assert_eq!(json, back);
     }
 
+    #[test]
+    fn test_value_bincode_roundtrip() {
+        let value = Value::from_json(serde_json::json!({
+            "name": "Alice",
+            "age": 30,
+            "score": 1.5,
+            "tags": ["a", "b"],
+            "embedding": {"$vector": [0.5, 0.25]},
+            "missing": null,
+        })).unwrap();
+
+        let bytes = bincode::serialize(&value).unwrap();
+        let decoded: Value = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, value);
+
+        // JSON stays untagged
+        let json = serde_json::to_string(&Value::Int(3)).unwrap();
+        assert_eq!(json, "3");
+
+        // An empty list comes back as one, not as empty bytes
+        let empty = Value::from_json(serde_json::json!({"checks": []})).unwrap();
+        let decoded: Value = serde_json::from_str(&serde_json::to_string(&empty).unwrap()).unwrap();
+        assert_eq!(decoded, empty);
+        assert_eq!(decoded.to_json(), serde_json::json!({"checks": []}));
+    }
+
     #[test]
     fn test_node_id_parsing() {
         let id = NodeId::new();
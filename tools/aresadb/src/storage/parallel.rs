@@ -8,11 +8,15 @@
 use anyhow::Result;
 use crossbeam::channel;
 use parking_lot::RwLock;
+use rayon::prelude::*;
+use redb::ReadTransaction;
 use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::thread;
 
-use super::{Node, Edge, NodeId, Value, LocalStorage};
+use super::codec::decode_node;
+use super::local::{scan_archived_in, NODES_TABLE};
+use super::{ArchivedNode, Node, Edge, NodeId, Value, LocalStorage};
 
 /// Parallel query executor for high-throughput operations
 pub struct ParallelExecutor {
@@ -256,50 +260,44 @@ mod num_cpus {
     }
 }
 
-/// Lock-free reader for MVCC-style snapshots
+/// Consistent reads from one point in time
 ///
-/// This provides consistent reads without blocking writers.
-/// The key insight: redb already provides MVCC via read transactions.
+/// Holds a redb read transaction, which is an MVCC snapshot: every read
+/// through the reader sees the storage as it was when the reader was
+/// created, and writers are never blocked by it.
 pub struct SnapshotReader {
-    // In practice, redb's read transactions are already MVCC snapshots
-    // This wrapper makes it explicit and adds parallel read capabilities
+    read_txn: ReadTransaction,
 }
 
 impl SnapshotReader {
-    /// Create a snapshot reader
-    pub fn new() -> Self {
-        Self {}
+    /// Take a snapshot of a storage's current state
+    pub fn new(storage: &LocalStorage) -> Result<Self> {
+        let read_txn = storage.db.read().begin_read()?;
+        Ok(Self { read_txn })
     }
 
-    /// Read multiple keys in parallel
-    pub async fn parallel_get(
-        &self,
-        storage: &LocalStorage,
-        ids: Vec<NodeId>,
-    ) -> Result<Vec<Option<Node>>> {
-        let results = Arc::new(RwLock::new(vec![None; ids.len()]));
-
-        let handles: Vec<_> = ids.into_iter().enumerate().map(|(idx, id)| {
-            let results = Arc::clone(&results);
-            let storage_path = storage.path().to_path_buf();
+    /// Read multiple nodes in parallel
+    pub fn parallel_get(&self, ids: &[NodeId]) -> Result<Vec<Option<Node>>> {
+        let nodes_table = self.read_txn.open_table(NODES_TABLE)?;
 
-            tokio::spawn(async move {
-                let storage = LocalStorage::open(&storage_path).await?;
-                let node = storage.get_node(&id).await?;
-                results.write()[idx] = node;
-                Ok::<_, anyhow::Error>(())
+        ids.par_iter()
+            .map(|id| {
+                nodes_table.get(id.uuid.as_slice())?
+                    .map(|data| decode_node(data.value()))
+                    .transpose()
             })
-        }).collect();
-
-        for handle in handles {
-            handle.await??;
-        }
-
-        let final_results = Arc::try_unwrap(results)
-            .map(|lock| lock.into_inner())
-            .unwrap_or_else(|arc| arc.read().clone());
+            .collect()
+    }
 
-        Ok(final_results)
+    /// Scan the nodes of a type as validated archives until `visit` returns false
+    ///
+    /// None is deserialized unless the visitor asks for it.
+    pub fn scan_archived(
+        &self,
+        node_type: &str,
+        visit: impl FnMut(&ArchivedNode) -> Result<bool>,
+    ) -> Result<()> {
+        scan_archived_in(&self.read_txn, node_type, visit)
     }
 }
 
@@ -344,8 +342,31 @@ mod tests {
         assert_eq!(result.nodes.len(), 3);
         assert_eq!(result.edges.len(), 2);
     }
-}
 
+    #[tokio::test]
+    async fn test_snapshot_reader() {
+        let temp = TempDir::new().unwrap();
+        let storage = LocalStorage::create(temp.path()).await.unwrap();
+
+        let alice = Node::new("user", Value::from_json(serde_json::json!({"name": "Alice"})).unwrap());
+        storage.insert_node(&alice).await.unwrap();
 
+        let snapshot = SnapshotReader::new(&storage).unwrap();
+        let bob = Node::new("user", Value::from_json(serde_json::json!({"name": "Bob"})).unwrap());
+        storage.insert_node(&bob).await.unwrap();
 
+        let mut names = Vec::new();
+        snapshot.scan_archived("user", |node| {
+            names.push(node.get("name").unwrap().to_value());
+            Ok(true)
+        }).unwrap();
+        assert_eq!(names, vec![Value::String("Alice".into())]);
 
+        let nodes = snapshot.parallel_get(&[alice.id.clone(), bob.id.clone()]).unwrap();
+        assert_eq!(nodes[0].as_ref().map(|n| &n.id), Some(&alice.id));
+        assert!(nodes[1].is_none());
+
+        let fresh = SnapshotReader::new(&storage).unwrap();
+        assert!(fresh.parallel_get(&[bob.id.clone()]).unwrap()[0].is_some());
+    }
+}
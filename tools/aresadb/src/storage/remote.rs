@@ -0,0 +1,269 @@
+//! Read-through storage backend for bucket-connected databases
+//!
+//! redb reads the data file's pages from its segments in the bucket. Each
+//! segment is fetched on first use and kept in the database's cache layer,
+//! so hot data is served locally within the cache's size, policy and TTL.
+//! Writes land in local copies of the segments they touch and never reach
+//! the bucket; push or sync a local database to change what it holds.
+
+use anyhow::Result;
+use bytes::Bytes;
+use crossbeam::channel;
+use futures::{StreamExt, TryStreamExt};
+use parking_lot::RwLock;
+use redb::StorageBackend;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use super::cache::CacheLayer;
+use super::delta::{FileManifest, SEGMENT_BYTES};
+use super::BucketStorage;
+
+/// Segments fetched from the bucket at once
+const FETCH_CONCURRENCY: usize = 8;
+
+/// Where segments missing from the cache come from
+pub trait SegmentSource: Send + Sync + 'static {
+    /// Fetch segments by hash, in the order given
+    fn fetch(&self, hashes: &[String]) -> io::Result<Vec<Bytes>>;
+}
+
+/// redb backend reading the data file from bucket segments
+pub struct BucketBackend {
+    /// Segment hashes of the data file as pushed
+    segments: Vec<String>,
+    /// Current file length, which local writes may grow
+    len: RwLock<u64>,
+    /// Segments changed locally, by index
+    written: RwLock<HashMap<usize, Vec<u8>>>,
+    cache: Arc<CacheLayer>,
+    source: Box<dyn SegmentSource>,
+}
+
+impl std::fmt::Debug for BucketBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BucketBackend")
+            .field("segments", &self.segments.len())
+            .field("len", &*self.len.read())
+            .field("written", &self.written.read().len())
+            .finish()
+    }
+}
+
+impl BucketBackend {
+    /// Serve `file` from `source`, caching segments in `cache`
+    pub fn new(file: &FileManifest, cache: Arc<CacheLayer>, source: impl SegmentSource) -> Self {
+        Self {
+            segments: file.segments.clone(),
+            len: RwLock::new(file.size),
+            written: RwLock::new(HashMap::new()),
+            cache,
+            source: Box::new(source),
+        }
+    }
+
+    fn cache_key(hash: &str) -> String {
+        format!("segment:{}", hash)
+    }
+
+    /// Pushed segments among `indexes`, from the cache or else fetched in
+    /// one batch; indexes past the pushed file are left out
+    fn load(&self, indexes: impl Iterator<Item = usize>) -> io::Result<HashMap<usize, Bytes>> {
+        let mut loaded = HashMap::new();
+        let mut missing = Vec::new();
+        for index in indexes {
+            let Some(hash) = self.segments.get(index) else {
+                continue;
+            };
+            match self.cache.get(&Self::cache_key(hash)) {
+                Some(segment) => {
+                    loaded.insert(index, segment);
+                }
+                None => missing.push(index),
+            }
+        }
+
+        if !missing.is_empty() {
+            let hashes: Vec<String> = missing.iter().map(|&i| self.segments[i].clone()).collect();
+            for ((index, hash), segment) in missing.into_iter().zip(&hashes).zip(self.source.fetch(&hashes)?) {
+                self.cache.put(&Self::cache_key(hash), segment.clone());
+                loaded.insert(index, segment);
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+/// Copy the part of segment `index` inside `offset..offset + out.len()`
+/// into `out`, leaving bytes the segment doesn't hold as zeros
+fn copy_segment(segment: &[u8], index: usize, offset: u64, out: &mut [u8]) {
+    let start = (index * SEGMENT_BYTES) as u64;
+    let from = offset.max(start);
+    let to = (offset + out.len() as u64).min(start + segment.len() as u64);
+    if from < to {
+        let src = &segment[(from - start) as usize..(to - start) as usize];
+        out[(from - offset) as usize..(to - offset) as usize].copy_from_slice(src);
+    }
+}
+
+impl StorageBackend for BucketBackend {
+    fn len(&self) -> Result<u64, io::Error> {
+        Ok(*self.len.read())
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+        let mut out = vec![0u8; len];
+        if len == 0 {
+            return Ok(out);
+        }
+        let first = (offset / SEGMENT_BYTES as u64) as usize;
+        let last = ((offset + len as u64 - 1) / SEGMENT_BYTES as u64) as usize;
+
+        let written = self.written.read();
+        let loaded = self.load((first..=last).filter(|i| !written.contains_key(i)))?;
+        for index in first..=last {
+            let segment = written.get(&index).map(Vec::as_slice).or_else(|| loaded.get(&index).map(|b| &b[..]));
+            if let Some(segment) = segment {
+                copy_segment(segment, index, offset, &mut out);
+            }
+        }
+        Ok(out)
+    }
+
+    fn set_len(&self, len: u64) -> Result<(), io::Error> {
+        *self.len.write() = len;
+        Ok(())
+    }
+
+    fn sync_data(&self, _eventual: bool) -> Result<(), io::Error> {
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let first = (offset / SEGMENT_BYTES as u64) as usize;
+        let last = ((offset + data.len() as u64 - 1) / SEGMENT_BYTES as u64) as usize;
+
+        let mut written = self.written.write();
+        let loaded = self.load((first..=last).filter(|i| !written.contains_key(i)))?;
+        for index in first..=last {
+            let segment = written.entry(index).or_insert_with(|| {
+                let mut segment = loaded.get(&index).map(|b| b.to_vec()).unwrap_or_default();
+                segment.resize(SEGMENT_BYTES, 0);
+                segment
+            });
+            let start = (index * SEGMENT_BYTES) as u64;
+            let from = offset.max(start);
+            let to = (offset + data.len() as u64).min(start + SEGMENT_BYTES as u64);
+            segment[(from - start) as usize..(to - start) as usize]
+                .copy_from_slice(&data[(from - offset) as usize..(to - offset) as usize]);
+        }
+        Ok(())
+    }
+}
+
+type FetchRequest = (Vec<String>, channel::Sender<io::Result<Vec<Bytes>>>);
+
+/// Fetches segments from a bucket on a thread of its own, since redb reads
+/// synchronously from inside the async runtime
+pub struct BucketFetcher {
+    requests: channel::Sender<FetchRequest>,
+}
+
+impl BucketFetcher {
+    /// Connect to the bucket at `url` on a new fetch thread
+    pub fn start(url: &str) -> Result<Self> {
+        let (requests, received) = channel::unbounded::<FetchRequest>();
+        let (ready, connected) = channel::bounded(1);
+        let url = url.to_string();
+
+        std::thread::Builder::new().name("aresadb-bucket-fetch".to_string()).spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    let _ = ready.send(Err(e.into()));
+                    return;
+                }
+            };
+            let bucket = match runtime.block_on(BucketStorage::connect(&url)) {
+                Ok(bucket) => bucket,
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+
+            for (hashes, reply) in received {
+                let fetched = runtime.block_on(
+                    futures::stream::iter(hashes.iter().map(|hash| bucket.get_segment(hash)))
+                        .buffered(FETCH_CONCURRENCY)
+                        .try_collect::<Vec<_>>(),
+                );
+                let _ = reply.send(fetched.map_err(io::Error::other));
+            }
+        })?;
+
+        connected.recv()??;
+        Ok(Self { requests })
+    }
+}
+
+impl SegmentSource for BucketFetcher {
+    fn fetch(&self, hashes: &[String]) -> io::Result<Vec<Bytes>> {
+        let (reply, response) = channel::bounded(1);
+        let stopped = || io::Error::other("Bucket fetch thread stopped");
+        self.requests.send((hashes.to_vec(), reply)).map_err(|_| stopped())?;
+        response.recv().map_err(|_| stopped())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct MapSource {
+        segments: HashMap<String, Bytes>,
+        fetches: Arc<AtomicUsize>,
+    }
+
+    impl SegmentSource for MapSource {
+        fn fetch(&self, hashes: &[String]) -> io::Result<Vec<Bytes>> {
+            self.fetches.fetch_add(hashes.len(), Ordering::Relaxed);
+            Ok(hashes.iter().map(|hash| self.segments[hash].clone()).collect())
+        }
+    }
+
+    #[test]
+    fn test_reads_through_cache() {
+        let data: Vec<u8> = (0..SEGMENT_BYTES * 2 + 100).map(|i| (i % 251) as u8).collect();
+        let file = FileManifest::new(&data, chrono::Utc::now());
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let source = MapSource {
+            segments: super::super::delta::segments_by_hash(&data)
+                .into_iter()
+                .map(|(hash, segment)| (hash, Bytes::copy_from_slice(segment)))
+                .collect(),
+            fetches: Arc::clone(&fetches),
+        };
+        let backend = BucketBackend::new(&file, Arc::new(CacheLayer::new(16 * 1024 * 1024)), source);
+
+        // A read spanning two segments fetches both, once
+        let offset = SEGMENT_BYTES as u64 - 10;
+        assert_eq!(backend.read(offset, 20).unwrap(), &data[SEGMENT_BYTES - 10..SEGMENT_BYTES + 10]);
+        assert_eq!(backend.read(offset, 20).unwrap(), &data[SEGMENT_BYTES - 10..SEGMENT_BYTES + 10]);
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+
+        // Writes stay local, past the end of the pushed file too
+        backend.write(5, b"hello").unwrap();
+        backend.set_len(data.len() as u64 + 10).unwrap();
+        backend.write(data.len() as u64, b"tail").unwrap();
+        assert_eq!(backend.read(3, 9).unwrap(), [&data[3..5], b"hello", &data[10..12]].concat());
+        assert_eq!(backend.read(data.len() as u64 - 1, 5).unwrap(), [&data[data.len() - 1..], b"tail"].concat());
+        assert_eq!(fetches.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,796 @@
+//! Bulk export and import
+//!
+//! Moves the nodes of a type to and from JSON Lines, CSV or Parquet files.
+//! Each record holds a node's properties plus its ID under `_id`, so an
+//! export imported into another database keeps the IDs edges refer to.
+//! Neither direction holds the whole type in memory: exports scan the type
+//! (CSV and Parquet twice, the first time to find the columns), and imports
+//! read a batch of records at a time and write it in one transaction.
+//!
+//! CSV cells are text. On export, nested values are written as JSON and
+//! strings that would read back as something else, such as `42`, `true` or
+//! the empty string, are quoted. On import, quoted cells and cells of
+//! fields the type's schema declares as text stay strings; otherwise
+//! numbers without leading zeros, booleans and JSON objects or arrays are
+//! recognised, and an empty cell is a missing property. Both directions use
+//! the run's CSV delimiter.
+//!
+//! Parquet columns take the type their values share: booleans, 64-bit
+//! integers, doubles, strings, binary, or lists of floats for vectors.
+//! Arrays, objects and columns mixing types are stored as JSON text and
+//! marked in the field metadata so they read back as values.
+
+use anyhow::{Context, Result};
+use arrow::array::{
+    Array, ArrayRef, AsArray, BinaryBuilder, BooleanBuilder, Float32Builder, Float64Builder, Int64Builder,
+    ListBuilder, StringBuilder,
+};
+use arrow::datatypes::{DataType, Field, Float32Type, Float64Type, Int64Type, Schema as ArrowSchema};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::output::{CsvOptions, CsvRenderer};
+use crate::progress::{Progress, ProgressUnit};
+use crate::schema::FieldType;
+use super::{Database, Node, NodeId, Value};
+
+/// Property holding the node ID in exported records
+pub const ID_FIELD: &str = "_id";
+
+/// Parquet field metadata marking a column of JSON text
+const JSON_METADATA: &str = "aresadb:json";
+
+/// Rows per Parquet record batch on export
+const PARQUET_BATCH: usize = 8192;
+
+/// A record's fields in file order
+type Record = Vec<(String, Value)>;
+
+/// File format for export and import
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Apache Parquet, one column per property
+    Parquet,
+}
+
+impl TransferFormat {
+    /// Parse a format name
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "jsonl" | "ndjson" | "json" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "parquet" | "pq" => Ok(Self::Parquet),
+            _ => anyhow::bail!("Unknown format: {}. Use jsonl, csv or parquet", name),
+        }
+    }
+
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::parse(path.extension()?.to_str()?).ok()
+    }
+
+    /// Get the name of this format
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+/// How records become nodes
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Type of the imported nodes
+    pub node_type: String,
+    /// File format
+    pub format: TransferFormat,
+    /// Fields renamed on import, from source name to property name
+    pub rename: Vec<(String, String)>,
+    /// Fields left out
+    pub skip: Vec<String>,
+    /// Keep IDs from `_id` fields; otherwise every record gets a new node
+    pub keep_ids: bool,
+    /// Records written per transaction
+    pub batch_size: usize,
+}
+
+impl ImportOptions {
+    /// Import `node_type` nodes from a format, keeping IDs
+    pub fn new(node_type: &str, format: TransferFormat) -> Self {
+        Self {
+            node_type: node_type.to_string(),
+            format,
+            rename: Vec::new(),
+            skip: Vec::new(),
+            keep_ids: true,
+            batch_size: 1000,
+        }
+    }
+
+    /// Property a source field is stored as
+    fn property<'a>(&'a self, field: &'a str) -> &'a str {
+        self.rename.iter()
+            .find(|(from, _)| from == field)
+            .map_or(field, |(_, to)| to.as_str())
+    }
+
+    /// Turn a record into a node
+    fn node(&self, record: Record) -> Result<Node> {
+        let mut id = None;
+        let mut properties = BTreeMap::new();
+        for (field, value) in record {
+            if field == ID_FIELD {
+                if let (true, Value::String(text)) = (self.keep_ids, &value) {
+                    id = Some(NodeId::parse(text)?);
+                }
+                continue;
+            }
+            if self.skip.contains(&field) {
+                continue;
+            }
+            properties.insert(self.property(&field).to_string(), value);
+        }
+        Ok(Node::with_id(id.unwrap_or_default(), &self.node_type, properties))
+    }
+}
+
+/// Write every node of a type, returning how many were written
+pub async fn export_nodes<W, F>(
+    db: &Database,
+    node_type: &str,
+    format: TransferFormat,
+    mut out: W,
+    mut on_progress: F,
+) -> Result<usize>
+where
+    W: Write + Send,
+    F: FnMut(Progress),
+{
+    let mut written = 0;
+    let mut progress = |written: usize, total: Option<u64>| {
+        on_progress(Progress::new("export", ProgressUnit::Nodes, written as u64, total));
+    };
+
+    match format {
+        TransferFormat::Jsonl => {
+            db.scan_by_type(node_type, |node| {
+                let mut record = serde_json::Map::new();
+                record.insert(ID_FIELD.to_string(), serde_json::json!(node.id.to_string()));
+                for (key, value) in &node.properties {
+                    record.insert(key.clone(), value.to_json());
+                }
+                writeln!(out, "{}", serde_json::Value::Object(record))?;
+                written += 1;
+                progress(written, None);
+                Ok(true)
+            })?;
+        }
+        TransferFormat::Csv => {
+            let (columns, total) = scan_columns(db, node_type)?;
+            let mut header = vec![ID_FIELD];
+            header.extend(columns.keys().map(|c| c.as_str()));
+            let mut csv = CsvRenderer::to_writer(&mut out, CsvOptions::defaults());
+            csv.write_header(&header)?;
+
+            db.scan_by_type(node_type, |node| {
+                let id = node.id.to_string();
+                let cells = header.iter().map(|column| match node.properties.get(*column) {
+                    _ if *column == ID_FIELD => (id.clone(), false),
+                    None | Some(Value::Null) => (String::new(), false),
+                    Some(Value::String(s)) => (s.clone(), !reads_back_as_text(s)),
+                    Some(value) => (value.to_json().to_string(), false),
+                });
+                csv.write_record_quoting(cells)?;
+                written += 1;
+                progress(written, Some(total));
+                Ok(true)
+            })?;
+        }
+        TransferFormat::Parquet => {
+            let (columns, total) = scan_columns(db, node_type)?;
+            let mut kinds = vec![(ID_FIELD.to_string(), ColumnKind::String)];
+            kinds.extend(columns.into_iter().map(|(name, kind)| (name, kind.unwrap_or(ColumnKind::String))));
+            let schema = Arc::new(ArrowSchema::new(
+                kinds.iter().map(|(name, kind)| kind.field(name)).collect::<Vec<_>>(),
+            ));
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            let mut writer = ArrowWriter::try_new(&mut out, schema.clone(), Some(properties))?;
+            let mut builders: Vec<ColumnBuilder> = kinds.iter().map(|(_, kind)| ColumnBuilder::new(*kind)).collect();
+            let mut pending = 0;
+
+            db.scan_by_type(node_type, |node| {
+                builders[0].append(Some(&Value::String(node.id.to_string())))?;
+                for ((name, _), builder) in kinds.iter().zip(builders.iter_mut()).skip(1) {
+                    builder
+                        .append(node.properties.get(name))
+                        .with_context(|| format!("{} {}.{}", node_type, node.id, name))?;
+                }
+                pending += 1;
+                if pending == PARQUET_BATCH {
+                    let columns = builders.iter_mut().map(ColumnBuilder::finish).collect();
+                    writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+                    pending = 0;
+                }
+                written += 1;
+                progress(written, Some(total));
+                Ok(true)
+            })?;
+            if pending > 0 {
+                let columns = builders.iter_mut().map(ColumnBuilder::finish).collect();
+                writer.write(&RecordBatch::try_new(schema, columns)?)?;
+            }
+            writer.close()?;
+        }
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+/// Properties of the nodes of a type, with the kind of value each holds
+/// (`None` when only nulls), and the number of nodes
+fn scan_columns(db: &Database, node_type: &str) -> Result<(BTreeMap<String, Option<ColumnKind>>, u64)> {
+    let mut columns: BTreeMap<String, Option<ColumnKind>> = BTreeMap::new();
+    let mut total = 0;
+    db.scan_by_type(node_type, |node| {
+        total += 1;
+        for (name, value) in node.properties {
+            let kind = columns.entry(name).or_default();
+            *kind = match (*kind, ColumnKind::of(&value)) {
+                (Some(kind), Some(other)) => Some(kind.merge(other)),
+                (kind, other) => kind.or(other),
+            };
+        }
+        Ok(true)
+    })?;
+    Ok((columns, total))
+}
+
+/// Read JSON Lines or CSV records into nodes, returning how many were
+/// imported; Parquet needs a file, see [`import_file`]
+///
+/// Records with an `_id` already in the database replace that node.
+pub async fn import_nodes<R, F>(db: &Database, input: R, options: &ImportOptions, on_progress: F) -> Result<usize>
+where
+    R: BufRead,
+    F: FnMut(Progress),
+{
+    match options.format {
+        TransferFormat::Jsonl => import_records(db, jsonl_records(input), options, on_progress).await,
+        TransferFormat::Csv => {
+            let records = CsvRecords::new(input, CsvOptions::defaults().delimiter)?;
+            let text_columns = match db.registered_schemas().await?.get(&options.node_type) {
+                Some(schema) => records
+                    .header
+                    .iter()
+                    .filter(|column| {
+                        matches!(
+                            schema.get_field(options.property(column)).map(|f| &f.field_type),
+                            Some(FieldType::String | FieldType::DateTime | FieldType::Uuid | FieldType::Enum(_))
+                        )
+                    })
+                    .cloned()
+                    .collect(),
+                None => HashSet::new(),
+            };
+            import_records(db, records.text_columns(text_columns), options, on_progress).await
+        }
+        TransferFormat::Parquet => anyhow::bail!("Parquet is imported from a file"),
+    }
+}
+
+/// Read the records of a file into nodes, returning how many were imported
+pub async fn import_file<F>(db: &Database, path: &Path, options: &ImportOptions, on_progress: F) -> Result<usize>
+where
+    F: FnMut(Progress),
+{
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    match options.format {
+        TransferFormat::Parquet => {
+            let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+                .with_context(|| format!("{} is not a Parquet file", path.display()))?
+                .with_batch_size(options.batch_size.max(1))
+                .build()?;
+            let records = reader.flat_map(|batch| {
+                match batch.map_err(anyhow::Error::from).and_then(|batch| batch_records(&batch)) {
+                    Ok(records) => records.into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(e) => vec![Err(e)],
+                }
+            });
+            import_records(db, records, options, on_progress).await
+        }
+        _ => import_nodes(db, std::io::BufReader::new(file), options, on_progress).await,
+    }
+}
+
+/// Write records in batches of `options.batch_size`, one transaction each
+async fn import_records<I, F>(db: &Database, mut records: I, options: &ImportOptions, mut on_progress: F) -> Result<usize>
+where
+    I: Iterator<Item = Result<Record>>,
+    F: FnMut(Progress),
+{
+    let mut imported = 0;
+    on_progress(Progress::new("import", ProgressUnit::Nodes, 0, None));
+    loop {
+        let mut old = Vec::new();
+        let mut new = Vec::new();
+        for record in records.by_ref().take(options.batch_size.max(1)) {
+            let node = record
+                .and_then(|record| options.node(record))
+                .with_context(|| format!("record {}", imported + new.len() + 1))?;
+            if let Some(existing) = db.get_node(&node.id.to_string()).await? {
+                old.push(existing);
+            }
+            new.push(node);
+        }
+        if new.is_empty() {
+            return Ok(imported);
+        }
+        imported += new.len();
+        db.replace_nodes(old, new).await?;
+        on_progress(Progress::new("import", ProgressUnit::Nodes, imported as u64, None));
+    }
+}
+
+/// Records of a JSON Lines stream, skipping blank lines
+fn jsonl_records<R: BufRead>(input: R) -> impl Iterator<Item = Result<Record>> {
+    input.lines().enumerate().filter_map(|(n, line)| {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => return None,
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let record: Result<Record> = match serde_json::from_str(&line).with_context(|| format!("Invalid JSON on line {}", n + 1)) {
+            Ok(serde_json::Value::Object(record)) => record
+                .into_iter()
+                .map(|(field, value)| Ok((field, Value::from_json(value)?)))
+                .collect(),
+            Ok(_) => Err(anyhow::anyhow!("line {}: expected a JSON object", n + 1)),
+            Err(e) => Err(e),
+        };
+        Some(record)
+    })
+}
+
+/// Rows of CSV text, read a row at a time; each cell comes with whether
+/// it was quoted
+struct CsvReader<R> {
+    input: R,
+    delimiter: char,
+    line: String,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    /// The next row, honouring quoted cells that span lines
+    fn next_row(&mut self) -> Result<Option<Vec<(String, bool)>>> {
+        let mut row = Vec::new();
+        let mut cell = String::new();
+        let (mut quoting, mut quoted) = (false, false);
+        loop {
+            self.line.clear();
+            if self.input.read_line(&mut self.line)? == 0 {
+                anyhow::ensure!(!quoting, "Unterminated quoted CSV field");
+                if row.is_empty() && cell.is_empty() && !quoted {
+                    return Ok(None);
+                }
+                row.push((cell, quoted));
+                return Ok(Some(row));
+            }
+
+            let mut chars = self.line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoting, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    }
+                    (true, '"') => quoting = false,
+                    (true, c) => cell.push(c),
+                    (false, '"') if cell.is_empty() && !quoted => (quoting, quoted) = (true, true),
+                    (false, c) if c == self.delimiter => {
+                        row.push((std::mem::take(&mut cell), std::mem::take(&mut quoted)));
+                    }
+                    (false, '\r') if chars.peek() == Some(&'\n') => {}
+                    (false, '\n') => {
+                        row.push((cell, quoted));
+                        return Ok(Some(row));
+                    }
+                    (false, c) => cell.push(c),
+                }
+            }
+        }
+    }
+}
+
+/// Records of a CSV stream with a header row
+struct CsvRecords<R> {
+    reader: CsvReader<R>,
+    header: Vec<String>,
+    /// Columns kept as text even when they look like numbers
+    text_columns: HashSet<String>,
+    row: usize,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    fn new(input: R, delimiter: char) -> Result<Self> {
+        let mut reader = CsvReader { input, delimiter, line: String::new() };
+        let header = reader.next_row()?.context("CSV file has no header row")?;
+        Ok(Self {
+            reader,
+            header: header.into_iter().map(|(column, _)| column).collect(),
+            text_columns: HashSet::new(),
+            row: 1,
+        })
+    }
+
+    fn text_columns(mut self, columns: HashSet<String>) -> Self {
+        self.text_columns = columns;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for CsvRecords<R> {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        loop {
+            let row = match self.reader.next_row() {
+                Ok(Some(row)) => row,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            self.row += 1;
+            if let [(cell, false)] = row.as_slice() {
+                if cell.is_empty() {
+                    continue;
+                }
+            }
+            if row.len() != self.header.len() {
+                return Some(Err(anyhow::anyhow!(
+                    "CSV row {} has {} fields, expected {}", self.row, row.len(), self.header.len()
+                )));
+            }
+            let record = self.header.iter()
+                .zip(row)
+                .filter_map(|(column, (cell, quoted))| {
+                    let value = if quoted || (self.text_columns.contains(column) && !cell.is_empty()) {
+                        Value::String(cell)
+                    } else {
+                        csv_value(cell)?
+                    };
+                    Some((column.clone(), value))
+                })
+                .collect();
+            return Some(Ok(record));
+        }
+    }
+}
+
+/// Typed value of an unquoted CSV cell, or `None` for an empty one
+fn csv_value(cell: String) -> Option<Value> {
+    if cell.is_empty() {
+        return None;
+    }
+    // Leading zeros mark codes, such as ZIP codes, rather than numbers
+    let digits = cell.strip_prefix('-').unwrap_or(&cell).as_bytes();
+    let padded = digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit();
+    if !padded {
+        if let Ok(i) = cell.parse::<i64>() {
+            return Some(Value::Int(i));
+        }
+        if let Ok(f) = cell.parse::<f64>() {
+            if f.is_finite() {
+                return Some(Value::Float(f));
+            }
+        }
+    }
+    match cell.as_str() {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if cell.starts_with('{') || cell.starts_with('[') {
+        if let Ok(value) = serde_json::from_str(&cell).map_err(anyhow::Error::from).and_then(Value::from_json) {
+            return Some(value);
+        }
+    }
+    Some(Value::String(cell))
+}
+
+/// Whether a string written as an unquoted cell imports as the same string
+fn reads_back_as_text(text: &str) -> bool {
+    matches!(csv_value(text.to_string()), Some(Value::String(s)) if s == text)
+}
+
+/// Type of an exported Parquet column, from the values it holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Bool,
+    Int,
+    Float,
+    String,
+    Bytes,
+    Vector,
+    Json,
+}
+
+impl ColumnKind {
+    fn of(value: &Value) -> Option<Self> {
+        Some(match value {
+            Value::Null => return None,
+            Value::Bool(_) => Self::Bool,
+            Value::Int(_) => Self::Int,
+            Value::Float(_) => Self::Float,
+            Value::String(_) => Self::String,
+            Value::Bytes(_) => Self::Bytes,
+            Value::Vector(_) => Self::Vector,
+            Value::Array(_) | Value::Object(_) => Self::Json,
+        })
+    }
+
+    /// Kind of a column holding values of both kinds
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Int, Self::Float) | (Self::Float, Self::Int) => Self::Float,
+            _ => Self::Json,
+        }
+    }
+
+    fn field(self, name: &str) -> Field {
+        let data_type = match self {
+            Self::Bool => DataType::Boolean,
+            Self::Int => DataType::Int64,
+            Self::Float => DataType::Float64,
+            Self::String | Self::Json => DataType::Utf8,
+            Self::Bytes => DataType::Binary,
+            Self::Vector => DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+        };
+        let field = Field::new(name, data_type, true);
+        match self {
+            Self::Json => field.with_metadata(HashMap::from([(JSON_METADATA.to_string(), "true".to_string())])),
+            _ => field,
+        }
+    }
+}
+
+/// Values of an exported Parquet column, one record batch at a time
+enum ColumnBuilder {
+    Bool(BooleanBuilder),
+    Int(Int64Builder),
+    Float(Float64Builder),
+    String(StringBuilder),
+    Bytes(BinaryBuilder),
+    Vector(ListBuilder<Float32Builder>),
+    Json(StringBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(kind: ColumnKind) -> Self {
+        match kind {
+            ColumnKind::Bool => Self::Bool(BooleanBuilder::new()),
+            ColumnKind::Int => Self::Int(Int64Builder::new()),
+            ColumnKind::Float => Self::Float(Float64Builder::new()),
+            ColumnKind::String => Self::String(StringBuilder::new()),
+            ColumnKind::Bytes => Self::Bytes(BinaryBuilder::new()),
+            ColumnKind::Vector => Self::Vector(ListBuilder::new(Float32Builder::new())),
+            ColumnKind::Json => Self::Json(StringBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, value: Option<&Value>) -> Result<()> {
+        match (self, value) {
+            (Self::Bool(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::Int(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::Float(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::String(b) | Self::Json(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::Bytes(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::Vector(b), None | Some(Value::Null)) => b.append_null(),
+            (Self::Bool(b), Some(Value::Bool(v))) => b.append_value(*v),
+            (Self::Int(b), Some(Value::Int(v))) => b.append_value(*v),
+            (Self::Float(b), Some(Value::Float(v))) => b.append_value(*v),
+            (Self::Float(b), Some(Value::Int(v))) => b.append_value(*v as f64),
+            (Self::String(b), Some(Value::String(v))) => b.append_value(v),
+            (Self::Bytes(b), Some(Value::Bytes(v))) => b.append_value(v),
+            (Self::Vector(b), Some(Value::Vector(v))) => {
+                b.values().append_slice(v);
+                b.append(true);
+            }
+            (Self::Json(b), Some(v)) => b.append_value(v.to_json().to_string()),
+            (_, Some(v)) => anyhow::bail!("value {} changed type during the export", v.to_json()),
+        }
+        Ok(())
+    }
+
+    /// The values appended since the last call
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            Self::Bool(b) => Arc::new(b.finish()),
+            Self::Int(b) => Arc::new(b.finish()),
+            Self::Float(b) => Arc::new(b.finish()),
+            Self::String(b) | Self::Json(b) => Arc::new(b.finish()),
+            Self::Bytes(b) => Arc::new(b.finish()),
+            Self::Vector(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// A Parquet column cast to the array type its values are read from
+struct ParquetColumn {
+    name: String,
+    kind: ColumnKind,
+    array: ArrayRef,
+}
+
+impl ParquetColumn {
+    /// Read booleans, integers, floats, binary and float lists as such,
+    /// JSON columns as values and anything else, such as timestamps or
+    /// decimals, as its text
+    fn new(field: &Field, array: &ArrayRef) -> Result<Self> {
+        let (kind, target) = match field.data_type() {
+            DataType::Boolean => (ColumnKind::Bool, DataType::Boolean),
+            t if t.is_integer() => (ColumnKind::Int, DataType::Int64),
+            t if t.is_floating() => (ColumnKind::Float, DataType::Float64),
+            DataType::Binary | DataType::LargeBinary | DataType::FixedSizeBinary(_) => {
+                (ColumnKind::Bytes, DataType::Binary)
+            }
+            DataType::List(item) | DataType::LargeList(item) | DataType::FixedSizeList(item, _)
+                if item.data_type().is_floating() =>
+            {
+                (ColumnKind::Vector, DataType::List(Arc::new(Field::new("item", DataType::Float32, true))))
+            }
+            _ if field.metadata().contains_key(JSON_METADATA) => (ColumnKind::Json, DataType::Utf8),
+            _ => (ColumnKind::String, DataType::Utf8),
+        };
+        let array = arrow::compute::cast(array, &target)
+            .with_context(|| format!("Cannot read Parquet column {} of type {}", field.name(), field.data_type()))?;
+        Ok(Self { name: field.name().clone(), kind, array })
+    }
+
+    /// The value in a row, `None` when null
+    fn value(&self, row: usize) -> Result<Option<Value>> {
+        if self.array.is_null(row) {
+            return Ok(None);
+        }
+        Ok(Some(match self.kind {
+            ColumnKind::Bool => Value::Bool(self.array.as_boolean().value(row)),
+            ColumnKind::Int => Value::Int(self.array.as_primitive::<Int64Type>().value(row)),
+            ColumnKind::Float => Value::Float(self.array.as_primitive::<Float64Type>().value(row)),
+            ColumnKind::String => Value::String(self.array.as_string::<i32>().value(row).to_string()),
+            ColumnKind::Json => {
+                let text = self.array.as_string::<i32>().value(row);
+                Value::from_json(serde_json::from_str(text).with_context(|| format!("Invalid JSON in {}", self.name))?)?
+            }
+            ColumnKind::Bytes => Value::Bytes(self.array.as_binary::<i32>().value(row).to_vec()),
+            ColumnKind::Vector => {
+                let list = self.array.as_list::<i32>().value(row);
+                Value::Vector(list.as_primitive::<Float32Type>().iter().map(Option::unwrap_or_default).collect())
+            }
+        }))
+    }
+}
+
+/// The rows of a Parquet record batch as records
+fn batch_records(batch: &RecordBatch) -> Result<Vec<Record>> {
+    let schema = batch.schema();
+    let columns = schema.fields().iter()
+        .zip(batch.columns())
+        .map(|(field, array)| ParquetColumn::new(field, array))
+        .collect::<Result<Vec<_>>>()?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let mut record = Vec::with_capacity(columns.len());
+            for column in &columns {
+                if let Some(value) = column.value(row)? {
+                    record.push((column.name.clone(), value));
+                }
+            }
+            Ok(record)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_export_import_roundtrip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let alice = db.insert_node("user", serde_json::json!({
+            "name": "Alice, \"Al\"", "age": 30, "tags": ["a", "b"], "embedding": {"$vector": [0.5, 1.0]}
+        })).await.unwrap();
+        // Strings that look like other types stay strings
+        let bob = db.insert_node("user", serde_json::json!({
+            "name": "Bob", "active": true, "zip": "00123", "code": "42", "flag": "true", "note": ""
+        })).await.unwrap();
+
+        for format in [TransferFormat::Jsonl, TransferFormat::Csv, TransferFormat::Parquet] {
+            let path = temp.path().join(format!("users.{}", format.name()));
+            let mut events = 0;
+            let file = std::fs::File::create(&path).unwrap();
+            let count = export_nodes(&db, "user", format, file, |_| events += 1).await.unwrap();
+            assert_eq!((count, events), (2, 2));
+            assert_eq!(TransferFormat::from_path(&path), Some(format));
+
+            let target = tempfile::TempDir::new().unwrap();
+            let copy = Database::create(target.path(), "copy").await.unwrap();
+            let options = ImportOptions::new("person", format);
+            assert_eq!(import_file(&copy, &path, &options, |_| {}).await.unwrap(), 2);
+
+            for node in [&alice, &bob] {
+                let imported = copy.get_node(&node.id.to_string()).await.unwrap().unwrap();
+                assert_eq!(imported.node_type, "person");
+                assert_eq!(imported.properties, node.properties, "{}", format.name());
+            }
+            assert_eq!(copy.get_all_by_type("person", None).await.unwrap().len(), 2);
+
+            // Importing again replaces rather than duplicates
+            import_file(&copy, &path, &options, |_| {}).await.unwrap();
+            assert_eq!(copy.get_all_by_type("person", None).await.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_mapping() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = Database::create(temp.path(), "test").await.unwrap();
+        let csv = "full_name,age,notes\nAda,36,\"line one\nline two\"\nGrace,,x\n";
+        let mut options = ImportOptions::new("user", TransferFormat::Csv);
+        options.rename = vec![("full_name".to_string(), "name".to_string())];
+        options.skip = vec!["notes".to_string()];
+        options.batch_size = 1;
+
+        let mut progress = Vec::new();
+        assert_eq!(import_nodes(&db, csv.as_bytes(), &options, |p| progress.push(p.done)).await.unwrap(), 2);
+        assert_eq!(progress, [0, 1, 2]);
+
+        let mut users = db.get_all_by_type("user", None).await.unwrap();
+        users.sort_by_key(|u| u.properties.get("name").and_then(|v| v.as_str()).map(String::from));
+        assert_eq!(users[0].properties.get("age"), Some(&Value::Int(36)));
+        assert!(!users[0].properties.contains_key("notes"));
+        assert!(!users[1].properties.contains_key("age"));
+
+        assert!(TransferFormat::parse("xlsx").is_err());
+        assert!(import_nodes(&db, "a,b\n1\n".as_bytes(), &options, |_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_import_csv_types() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let db = std::sync::Arc::new(Database::create(temp.path(), "test").await.unwrap());
+        let manager = crate::schema::SchemaManager::from_shared(db.clone());
+        manager.create_schema("account", "sku:string, qty:int").await.unwrap();
+
+        let csv = "sku,qty,zip,label\n42,7,00123,\"true\"\n";
+        let options = ImportOptions::new("account", TransferFormat::Csv);
+        import_nodes(&db, csv.as_bytes(), &options, |_| {}).await.unwrap();
+
+        let account = &db.get_all_by_type("account", None).await.unwrap()[0];
+        // Declared as a string by the schema
+        assert_eq!(account.properties.get("sku"), Some(&Value::String("42".to_string())));
+        assert_eq!(account.properties.get("qty"), Some(&Value::Int(7)));
+        // Leading zeros and quotes keep text as text
+        assert_eq!(account.properties.get("zip"), Some(&Value::String("00123".to_string())));
+        assert_eq!(account.properties.get("label"), Some(&Value::String("true".to_string())));
+
+        assert!(reads_back_as_text("Ada"));
+        assert!(!reads_back_as_text("-3.5"));
+        assert!(!reads_back_as_text("[1]"));
+        assert!(!reads_back_as_text(""));
+    }
+}
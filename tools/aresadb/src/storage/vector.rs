@@ -4,9 +4,14 @@
 //! for building RAG (Retrieval-Augmented Generation) systems.
 
 use crate::storage::{Node, NodeId, Value, DistanceMetric, SimilarityResult};
+use rayon::prelude::*;
 use std::collections::BinaryHeap;
 use std::cmp::Ordering;
 
+/// Fewest nodes worth splitting across threads; smaller searches run on
+/// the calling thread
+const PARALLEL_THRESHOLD: usize = 4096;
+
 /// A scored node for similarity ranking
 #[derive(Debug, Clone)]
 struct ScoredNode {
@@ -73,6 +78,9 @@ impl VectorSearch {
     }
 
     /// Find the k most similar nodes to a query vector
+    ///
+    /// Large node sets are split across the CPU cores, each part keeping
+    /// its own top k, and the parts merged.
     pub fn search(
         &self,
         query: &[f32],
@@ -80,24 +88,14 @@ impl VectorSearch {
         vector_field: &str,
         k: usize,
     ) -> Vec<SimilarityResult> {
-        let mut heap = BinaryHeap::with_capacity(k + 1);
-
-        for node in nodes {
-            if let Some(Value::Vector(vec)) = node.properties.get(vector_field) {
-                if let Some((score, distance)) = self.compute_similarity(query, vec) {
-                    heap.push(ScoredNode {
-                        node_id: node.id.clone(),
-                        score,
-                        distance,
-                    });
-
-                    // Keep only top k
-                    if heap.len() > k {
-                        heap.pop();
-                    }
-                }
-            }
-        }
+        let heap = if nodes.len() < PARALLEL_THRESHOLD {
+            self.top_k(query, nodes, vector_field, k)
+        } else {
+            nodes
+                .par_chunks(chunk_size(nodes.len()))
+                .map(|part| self.top_k(query, part, vector_field, k))
+                .reduce(BinaryHeap::new, |a, b| merge_top_k(a, b, k))
+        };
 
         // Convert to results (sorted by score descending)
         let mut results: Vec<_> = heap
@@ -121,26 +119,63 @@ impl VectorSearch {
         vector_field: &str,
         max_distance: f64,
     ) -> Vec<SimilarityResult> {
-        let mut results = Vec::new();
+        let within = |node: &Node| {
+            let Some(Value::Vector(vec)) = node.properties.get(vector_field) else { return None };
+            let (score, distance) = self.compute_similarity(query, vec)?;
+            (distance <= max_distance).then(|| SimilarityResult { node_id: node.id.clone(), score, distance })
+        };
+        let mut results: Vec<SimilarityResult> = if nodes.len() < PARALLEL_THRESHOLD {
+            nodes.iter().filter_map(within).collect()
+        } else {
+            nodes.par_iter().filter_map(within).collect()
+        };
+
+        // Sort by distance ascending
+        results.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
+        results
+    }
+
+    /// The k nodes most similar to the query, as a min-heap
+    fn top_k(&self, query: &[f32], nodes: &[Node], vector_field: &str, k: usize) -> BinaryHeap<ScoredNode> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
 
         for node in nodes {
             if let Some(Value::Vector(vec)) = node.properties.get(vector_field) {
                 if let Some((score, distance)) = self.compute_similarity(query, vec) {
-                    if distance <= max_distance {
-                        results.push(SimilarityResult {
-                            node_id: node.id.clone(),
-                            score,
-                            distance,
-                        });
+                    heap.push(ScoredNode {
+                        node_id: node.id.clone(),
+                        score,
+                        distance,
+                    });
+
+                    // Keep only top k
+                    if heap.len() > k {
+                        heap.pop();
                     }
                 }
             }
         }
+        heap
+    }
+}
 
-        // Sort by distance ascending
-        results.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
-        results
+/// Nodes per part of a parallel search: a few parts per thread so
+/// uneven parts balance out
+fn chunk_size(nodes: usize) -> usize {
+    let parts = rayon::current_num_threads() * 4;
+    nodes.div_ceil(parts).max(PARALLEL_THRESHOLD / 4)
+}
+
+/// Combine two top-k heaps into one
+fn merge_top_k(a: BinaryHeap<ScoredNode>, b: BinaryHeap<ScoredNode>, k: usize) -> BinaryHeap<ScoredNode> {
+    let (mut into, from) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    for scored in from {
+        into.push(scored);
+        if into.len() > k {
+            into.pop();
+        }
     }
+    into
 }
 
 /// Builder for creating vector-enabled nodes
@@ -276,6 +311,28 @@ pub mod utils {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parallel_search_matches_serial() {
+        let search = VectorSearch::new(DistanceMetric::Euclidean);
+        let nodes: Vec<Node> = (0..PARALLEL_THRESHOLD * 3)
+            .map(|_| VectorNodeBuilder::new("doc").embedding("embedding", utils::random_vector(8)).build())
+            .collect();
+        let query = utils::random_vector(8);
+
+        let results = search.search(&query, &nodes, "embedding", 10);
+        let mut serial: Vec<f64> = search.top_k(&query, &nodes, "embedding", 10).into_iter().map(|s| s.score).collect();
+        serial.sort_by(|a, b| b.partial_cmp(a).unwrap());
+        assert_eq!(results.iter().map(|r| r.score).collect::<Vec<_>>(), serial);
+
+        let radius = search.search_radius(&query, &nodes, "embedding", 0.8);
+        let within = nodes.iter().filter(|n| {
+            let vector = n.properties.get("embedding").and_then(|v| v.as_vector()).unwrap();
+            search.compute_similarity(&query, vector).unwrap().1 <= 0.8
+        });
+        assert_eq!(radius.len(), within.count());
+        assert!(radius.windows(2).all(|w| w[0].distance <= w[1].distance));
+    }
+
     #[test]
     fn test_vector_search_cosine() {
         let search = VectorSearch::new(DistanceMetric::Cosine);
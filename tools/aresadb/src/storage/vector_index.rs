@@ -6,11 +6,26 @@
 
 use anyhow::Result;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::collections::{BinaryHeap, HashMap};
 use std::cmp::Ordering;
 
 use super::{NodeId, Value, DistanceMetric};
 
+/// A vector index declared on one embedding field of a node type
+///
+/// A node may carry several embeddings (say `title_embedding` and
+/// `body_embedding`); each field is indexed on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorIndexSpec {
+    /// Node type whose nodes are indexed
+    pub node_type: String,
+    /// Field holding the embeddings
+    pub field: String,
+    /// Distance metric the index ranks by
+    pub metric: DistanceMetric,
+}
+
 /// A neighbor in the graph with distance
 #[derive(Clone)]
 struct Neighbor {
@@ -212,6 +227,27 @@ impl VectorIndex {
         }
     }
 
+    /// Release capacity left behind by removed vectors and links
+    pub fn compact(&self) {
+        let mut vectors = self.vectors.write();
+        for entry in vectors.values_mut() {
+            for layer_neighbors in &mut entry.neighbors {
+                layer_neighbors.shrink_to_fit();
+            }
+        }
+        vectors.shrink_to_fit();
+    }
+
+    /// Get the dimension of indexed vectors
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Get the distance metric
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
     /// Get number of vectors in index
     pub fn len(&self) -> usize {
         self.vectors.read().len()
@@ -412,6 +448,15 @@ pub struct IndexStats {
     pub max_layers: usize,
 }
 
+impl IndexStats {
+    /// Approximate memory held by the index's vectors and links
+    pub fn size_bytes(&self) -> usize {
+        let id_size = std::mem::size_of::<NodeId>();
+        self.num_vectors * (id_size + self.dimension * std::mem::size_of::<f32>())
+            + self.total_connections * id_size
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
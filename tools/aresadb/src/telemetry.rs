@@ -0,0 +1,335 @@
+//! Trace export over OTLP
+//!
+//! Queries, traversals, storage reads and writes, WAL appends and server
+//! requests open `tracing` spans for each stage of their work. [`OtlpLayer`]
+//! collects the finished spans and sends them in batches to an
+//! OpenTelemetry collector using OTLP/HTTP with JSON encoding, so the latency
+//! of a request can be broken down per stage in Jaeger, Tempo or any other
+//! OTLP backend.
+//!
+//! Export is off unless `OTEL_EXPORTER_OTLP_ENDPOINT` is set, e.g. to
+//! `http://localhost:4318`. `OTEL_SERVICE_NAME` overrides the service name,
+//! and `ARESADB_TRACE_FILTER` picks the exported spans with `RUST_LOG`
+//! syntax: the default `aresadb=debug` covers requests, query stages, bulk
+//! storage operations and WAL appends, while `aresadb=trace` adds every
+//! single-node read and write.
+
+use rand::Rng;
+use serde_json::{json, Value as JsonValue};
+use std::fmt;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::filter::{EnvFilter, Filtered};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::auth::to_hex;
+
+/// Spans sent per export request
+const BATCH_SIZE: usize = 512;
+
+/// Longest a finished span waits before it is exported
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Spans exported when `ARESADB_TRACE_FILTER` is not set
+const DEFAULT_FILTER: &str = "aresadb=debug";
+
+/// Build the OTLP layer if `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+///
+/// Keep the returned [`Telemetry`] until the process exits; dropping it
+/// exports the spans still buffered.
+#[allow(clippy::type_complexity)]
+pub fn otlp_layer<S>(service: &str) -> (Option<Filtered<OtlpLayer, EnvFilter, S>>, Telemetry)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let Some(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().filter(|e| !e.is_empty()) else {
+        return (None, Telemetry::disabled());
+    };
+    let service = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| service.to_string());
+    let filter = EnvFilter::new(std::env::var("ARESADB_TRACE_FILTER").unwrap_or_else(|_| DEFAULT_FILTER.into()));
+
+    let (sender, receiver) = mpsc::channel();
+    let exporter = Exporter::new(&endpoint, &service);
+    let worker = std::thread::Builder::new()
+        .name("otlp-exporter".to_string())
+        .spawn(move || exporter.run(receiver))
+        .ok();
+
+    let layer = OtlpLayer { sender: sender.clone() };
+    (Some(layer.with_filter(filter)), Telemetry { sender: Some(sender), worker })
+}
+
+/// Handle on the background exporter; flushes buffered spans when dropped
+pub struct Telemetry {
+    sender: Option<mpsc::Sender<SpanRecord>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Telemetry {
+    fn disabled() -> Self {
+        Self { sender: None, worker: None }
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        // The layer holds another sender, so ask the worker to stop rather
+        // than waiting for the channel to close
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(SpanRecord::shutdown());
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A `tracing` layer that records spans and hands them to the exporter
+pub struct OtlpLayer {
+    sender: mpsc::Sender<SpanRecord>,
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanRecord>().map(|p| (p.trace_id, p.span_id)));
+
+        let mut rng = rand::thread_rng();
+        let mut record = SpanRecord {
+            trace_id: parent.map(|(trace_id, _)| trace_id).unwrap_or_else(|| rng.gen()),
+            span_id: rng.gen(),
+            parent_span_id: parent.map(|(_, span_id)| span_id),
+            name: attrs.metadata().name().to_string(),
+            kind: 1,
+            start: SystemTime::now(),
+            end: None,
+            attributes: Vec::new(),
+            error: None,
+        };
+        attrs.record(&mut record);
+        span.extensions_mut().insert(record);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(record) = span.extensions_mut().get_mut::<SpanRecord>() {
+                values.record(record);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(mut record) = span.extensions_mut().remove::<SpanRecord>() else { return };
+        record.end = Some(SystemTime::now());
+        let _ = self.sender.send(record);
+    }
+}
+
+/// A span being recorded, then waiting for export
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    /// OTLP span kind: 1 internal, 2 server
+    kind: u8,
+    start: SystemTime,
+    /// `None` only for the shutdown marker
+    end: Option<SystemTime>,
+    attributes: Vec<(String, JsonValue)>,
+    error: Option<String>,
+}
+
+impl SpanRecord {
+    /// Marker asking the exporter to flush and stop
+    fn shutdown() -> Self {
+        Self {
+            trace_id: [0; 16],
+            span_id: [0; 8],
+            parent_span_id: None,
+            name: String::new(),
+            kind: 0,
+            start: UNIX_EPOCH,
+            end: None,
+            attributes: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn set(&mut self, field: &Field, value: JsonValue) {
+        match (field.name(), &value) {
+            ("otel.kind", JsonValue::String(kind)) if kind == "server" => self.kind = 2,
+            ("error", JsonValue::String(message)) => self.error = Some(message.clone()),
+            _ => {
+                self.attributes.retain(|(key, _)| key != field.name());
+                self.attributes.push((field.name().to_string(), value));
+            }
+        }
+    }
+
+    /// The span as an OTLP JSON span
+    fn to_json(&self) -> JsonValue {
+        let nanos = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+        let attributes: Vec<JsonValue> =
+            self.attributes.iter().map(|(key, value)| json!({ "key": key, "value": value })).collect();
+
+        let mut span = json!({
+            "traceId": to_hex(&self.trace_id),
+            "spanId": to_hex(&self.span_id),
+            "name": self.name,
+            "kind": self.kind,
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(self.end.unwrap_or(self.start)),
+            "attributes": attributes,
+        });
+        if let Some(parent) = self.parent_span_id {
+            span["parentSpanId"] = json!(to_hex(&parent));
+        }
+        if let Some(message) = &self.error {
+            span["status"] = json!({ "code": 2, "message": message });
+        }
+        span
+    }
+}
+
+impl Visit for SpanRecord {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, json!({ "boolValue": value }));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "otel.kind" || field.name() == "error" {
+            self.set(field, JsonValue::String(value.to_string()));
+        } else {
+            self.set(field, json!({ "stringValue": value }));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+/// Sends batches of finished spans to `<endpoint>/v1/traces`
+struct Exporter {
+    url: String,
+    service: String,
+}
+
+impl Exporter {
+    fn new(endpoint: &str, service: &str) -> Self {
+        let endpoint = endpoint.trim_end_matches('/');
+        let url = if endpoint.ends_with("/v1/traces") {
+            endpoint.to_string()
+        } else {
+            format!("{}/v1/traces", endpoint)
+        };
+        Self { url, service: service.to_string() }
+    }
+
+    /// Export spans as they arrive until asked to stop or every sender is
+    /// gone
+    fn run(self, receiver: mpsc::Receiver<SpanRecord>) {
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_all().build() else { return };
+        let client = reqwest::Client::new();
+        let mut batch = Vec::new();
+
+        loop {
+            let (stop, flush) = match receiver.recv_timeout(EXPORT_INTERVAL) {
+                Ok(record) if record.end.is_none() => (true, true),
+                Ok(record) => {
+                    batch.push(record);
+                    (false, batch.len() >= BATCH_SIZE)
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => (false, true),
+                Err(mpsc::RecvTimeoutError::Disconnected) => (true, true),
+            };
+            if flush && !batch.is_empty() {
+                let body = self.request_body(&batch);
+                batch.clear();
+                // A collector that is down must not hold up the database
+                let _ = runtime.block_on(client.post(&self.url).json(&body).timeout(Duration::from_secs(5)).send());
+            }
+            if stop {
+                return;
+            }
+        }
+    }
+
+    /// An OTLP `ExportTraceServiceRequest` in JSON
+    fn request_body(&self, spans: &[SpanRecord]) -> JsonValue {
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{ "key": "service.name", "value": { "stringValue": self.service } }],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "aresadb", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans.iter().map(SpanRecord::to_json).collect::<Vec<_>>(),
+                }],
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::field::Empty;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_spans_are_recorded_with_parents() {
+        let (sender, receiver) = mpsc::channel();
+        let subscriber = tracing_subscriber::registry().with(OtlpLayer { sender });
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", operation = "query", otel.kind = "server");
+            let _entered = request.enter();
+            let stage = tracing::debug_span!("query.parse", rows = Empty, error = Empty);
+            stage.record("rows", 3u64);
+            stage.record("error", "bad");
+        });
+
+        let spans: Vec<SpanRecord> = receiver.try_iter().collect();
+        assert_eq!(spans.len(), 2);
+        let (parse, request) = (&spans[0], &spans[1]);
+        assert_eq!(parse.name, "query.parse");
+        assert_eq!(parse.trace_id, request.trace_id);
+        assert_eq!(parse.parent_span_id, Some(request.span_id));
+        assert_eq!(request.kind, 2);
+
+        let json = parse.to_json();
+        assert_eq!(json["attributes"][0], json!({ "key": "rows", "value": { "intValue": "3" } }));
+        assert_eq!(json["status"]["code"], 2);
+        assert_eq!(json["traceId"].as_str().unwrap().len(), 32);
+
+        let exporter = Exporter::new("http://localhost:4318/", "aresadb");
+        assert_eq!(exporter.url, "http://localhost:4318/v1/traces");
+        let body = exporter.request_body(&spans);
+        assert_eq!(body["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap().len(), 2);
+    }
+}
@@ -108,6 +108,13 @@ mod database_tests {
 
         let incoming = db.get_edges_to(&bob.id.to_string(), None).await.unwrap();
         assert_eq!(incoming.len(), 1);
+
+        // Get and delete by ID
+        let fetched = db.get_edge(&wrote.id.to_string()).await.unwrap().unwrap();
+        assert_eq!(fetched.to, post.id);
+        db.delete_edge(&wrote.id.to_string()).await.unwrap();
+        assert!(db.get_edge(&wrote.id.to_string()).await.unwrap().is_none());
+        assert_eq!(db.get_edges_from(&alice.id.to_string(), None).await.unwrap().len(), 1);
     }
 
     #[tokio::test]